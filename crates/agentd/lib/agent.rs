@@ -11,21 +11,30 @@ use tokio::{
 
 use microsandbox_protocol::{
     codec::{MAX_FRAME_SIZE, encode_to_buf, try_decode_from_buf},
-    core::Ready,
+    compress::Compression,
+    core::{Capabilities, Ready},
     exec::{
-        ExecExited, ExecRequest, ExecResize, ExecSignal, ExecStarted, ExecStderr, ExecStdin,
-        ExecStdout,
+        ExecAttach, ExecExited, ExecRequest, ExecResize, ExecSessions, ExecSignal, ExecStarted,
+        ExecStderr, ExecStdin, ExecStdout,
     },
-    fs::{FsData, FsRequest},
-    message::{Message, MessageType},
+    flow::Credit,
+    fs::{FsData, FsOp, FsRequest},
+    message::{FLAG_TERMINAL, Message, MessageType},
+    process::ProcessRequest,
+    stats::{StatsRequest, StatsResponse},
+    tunnel::{TunnelBind, TunnelData, TunnelListen, TunnelOpen},
 };
 
 use crate::{
     error::{AgentdError, AgentdResult},
-    fs::FsWriteSession,
+    fs::FsStream,
     heartbeat::{heartbeat_dir_exists, write_heartbeat},
+    persistent::{Chunk, PersistentSessions},
+    process,
     serial::{AGENT_PORT_NAME, find_serial_port},
     session::{ExecSession, SessionOutput},
+    stats::StatsSampler,
+    tunnel::Tunnels,
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -44,6 +53,31 @@ const SERIAL_READ_BUF_SIZE: usize = 64 * 1024;
 /// Maximum allowed input buffer size (frame size limit + 4 bytes for length prefix).
 const MAX_INPUT_BUF_SIZE: usize = MAX_FRAME_SIZE as usize + 4;
 
+/// Message types accepted from the host, advertised in `core.ready`.
+///
+/// Must match the arms of [`handle_message`]: a host only sends types listed
+/// here, and anything else fails to decode on older agents.
+const ACCEPTED_MESSAGES: &[MessageType] = &[
+    MessageType::Shutdown,
+    MessageType::Credit,
+    MessageType::ExecRequest,
+    MessageType::ExecStdin,
+    MessageType::ExecResize,
+    MessageType::ExecSignal,
+    MessageType::ExecAttach,
+    MessageType::ExecDetach,
+    MessageType::ExecSessionsRequest,
+    MessageType::FsRequest,
+    MessageType::FsData,
+    MessageType::StatsRequest,
+    MessageType::ProcessRequest,
+    MessageType::TunnelOpen,
+    MessageType::TunnelListen,
+    MessageType::TunnelBind,
+    MessageType::TunnelData,
+    MessageType::TunnelClose,
+];
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
@@ -78,12 +112,26 @@ pub async fn run(boot_time_ns: u64, init_time_ns: u64) -> AgentdResult<()> {
     // Active exec sessions.
     let mut sessions: HashMap<u32, ExecSession> = HashMap::new();
 
-    // Active filesystem write sessions.
-    let mut write_sessions: HashMap<u32, FsWriteSession> = HashMap::new();
+    // Active streaming filesystem reads and writes.
+    let mut fs_streams: HashMap<u32, FsStream> = HashMap::new();
+
+    // Output codec requested by each exec client that asked for one.
+    let mut output_compression: HashMap<u32, Compression> = HashMap::new();
+
+    // Guest stats sampler (keeps per-process CPU time between requests).
+    let mut stats_sampler = StatsSampler::new();
 
     // Channel for session output events.
     let (session_tx, mut session_rx) = mpsc::unbounded_channel::<(u32, SessionOutput)>();
 
+    // Persistent exec sessions report output on their own channel, tagged
+    // by internal key rather than correlation ID.
+    let (persistent_tx, mut persistent_rx) = mpsc::unbounded_channel::<(u32, SessionOutput)>();
+    let mut persistent = PersistentSessions::new(persistent_tx);
+
+    // Active tunnels and tunnel listeners.
+    let mut tunnels = Tunnels::new(session_tx.clone());
+
     // Heartbeat state.
     let mut last_activity = Utc::now();
     let mut heartbeat_timer = interval(Duration::from_secs(HEARTBEAT_INTERVAL_SECS));
@@ -93,11 +141,7 @@ pub async fn run(boot_time_ns: u64, init_time_ns: u64) -> AgentdResult<()> {
     let ready_msg = Message::with_payload(
         MessageType::Ready,
         0,
-        &Ready {
-            boot_time_ns,
-            init_time_ns,
-            ready_time_ns,
-        },
+        &Ready::new(boot_time_ns, init_time_ns, ready_time_ns, capabilities()),
     )
     .map_err(|e| AgentdError::ExecSession(format!("encode ready: {e}")))?;
     encode_to_buf(&ready_msg, &mut serial_out_buf)
@@ -137,7 +181,11 @@ pub async fn run(boot_time_ns: u64, init_time_ns: u64) -> AgentdResult<()> {
                                 handle_message(
                                     msg,
                                     &mut sessions,
-                                    &mut write_sessions,
+                                    &mut persistent,
+                                    &mut fs_streams,
+                                    &mut output_compression,
+                                    &mut tunnels,
+                                    &mut stats_sampler,
                                     &session_tx,
                                     &mut serial_out_buf,
                                 ).await?;
@@ -157,42 +205,44 @@ pub async fn run(boot_time_ns: u64, init_time_ns: u64) -> AgentdResult<()> {
 
             // Receive output events from session reader tasks.
             Some((id, output)) = session_rx.recv() => {
-                match output {
-                    SessionOutput::Stdout(data) => {
-                        let msg = Message::with_payload(MessageType::ExecStdout, id, &ExecStdout { data })
-                            .map_err(|e| AgentdError::ExecSession(format!("encode stdout: {e}")))?;
-                        encode_to_buf(&msg, &mut serial_out_buf)
-                            .map_err(|e| AgentdError::ExecSession(format!("encode stdout frame: {e}")))?;
-                    }
-                    SessionOutput::Stderr(data) => {
-                        let msg = Message::with_payload(MessageType::ExecStderr, id, &ExecStderr { data })
-                            .map_err(|e| AgentdError::ExecSession(format!("encode stderr: {e}")))?;
-                        encode_to_buf(&msg, &mut serial_out_buf)
-                            .map_err(|e| AgentdError::ExecSession(format!("encode stderr frame: {e}")))?;
-                    }
-                    SessionOutput::Exited(code) => {
-                        let msg = Message::with_payload(MessageType::ExecExited, id, &ExecExited { code })
-                            .map_err(|e| AgentdError::ExecSession(format!("encode exited: {e}")))?;
-                        encode_to_buf(&msg, &mut serial_out_buf)
-                            .map_err(|e| AgentdError::ExecSession(format!("encode exited frame: {e}")))?;
+                let compression = output_compression.get(&id).copied();
+                match &output {
+                    SessionOutput::Exited(_) => {
                         sessions.remove(&id);
+                        output_compression.remove(&id);
                     }
-                    SessionOutput::Raw(frame_bytes) => {
-                        // Pre-encoded frame — write directly to output buffer.
-                        serial_out_buf.extend_from_slice(&frame_bytes);
+                    SessionOutput::Raw(frame) if is_terminal_frame(frame) => {
+                        fs_streams.remove(&id);
+                        tunnels.remove(id);
                     }
+                    _ => {}
                 }
+                encode_session_output(id, output, compression, &mut serial_out_buf)?;
 
                 if !serial_out_buf.is_empty() {
                     flush_write_buf(&async_port, &mut serial_out_buf).await?;
                 }
             }
 
+            // Receive output from persistent sessions, buffering it and
+            // forwarding it to the attached client, if any.
+            Some((key, output)) = persistent_rx.recv() => {
+                if let Some(id) = persistent.record(key, &output) {
+                    let compression = if matches!(output, SessionOutput::Exited(_)) {
+                        output_compression.remove(&id)
+                    } else {
+                        output_compression.get(&id).copied()
+                    };
+                    encode_session_output(id, output, compression, &mut serial_out_buf)?;
+                    flush_write_buf(&async_port, &mut serial_out_buf).await?;
+                }
+            }
+
             // Heartbeat tick.
             _ = heartbeat_timer.tick() => {
                 if heartbeat_dir_exists() {
                     let _ = write_heartbeat(
-                        sessions.len() as u32,
+                        (sessions.len() + persistent.running() + tunnels.len()) as u32,
                         last_activity,
                     ).await;
                 }
@@ -207,11 +257,68 @@ pub async fn run(boot_time_ns: u64, init_time_ns: u64) -> AgentdResult<()> {
 // Functions: Helpers
 //--------------------------------------------------------------------------------------------------
 
+/// Features advertised to the host in `core.ready`.
+fn capabilities() -> Capabilities {
+    Capabilities {
+        messages: ACCEPTED_MESSAGES
+            .iter()
+            .map(|t| t.as_str().to_string())
+            .collect(),
+        fs_ops: FsOp::NAMES.iter().map(|op| op.to_string()).collect(),
+        compression: Compression::ALL
+            .iter()
+            .map(|codec| codec.name().to_string())
+            .collect(),
+    }
+}
+
+/// Encodes a session output event as the matching protocol frame,
+/// compressing large stdout and stderr chunks with `compression`.
+fn encode_session_output(
+    id: u32,
+    output: SessionOutput,
+    compression: Option<Compression>,
+    out_buf: &mut Vec<u8>,
+) -> AgentdResult<()> {
+    match output {
+        SessionOutput::Stdout(data) => {
+            let msg = Message::with_payload(MessageType::ExecStdout, id, &ExecStdout { data })
+                .map_err(|e| AgentdError::ExecSession(format!("encode stdout: {e}")))?
+                .compress(compression);
+            encode_to_buf(&msg, out_buf)
+                .map_err(|e| AgentdError::ExecSession(format!("encode stdout frame: {e}")))?;
+        }
+        SessionOutput::Stderr(data) => {
+            let msg = Message::with_payload(MessageType::ExecStderr, id, &ExecStderr { data })
+                .map_err(|e| AgentdError::ExecSession(format!("encode stderr: {e}")))?
+                .compress(compression);
+            encode_to_buf(&msg, out_buf)
+                .map_err(|e| AgentdError::ExecSession(format!("encode stderr frame: {e}")))?;
+        }
+        SessionOutput::Exited(exited) => {
+            let msg = Message::with_payload(MessageType::ExecExited, id, &exited)
+                .map_err(|e| AgentdError::ExecSession(format!("encode exited: {e}")))?;
+            encode_to_buf(&msg, out_buf)
+                .map_err(|e| AgentdError::ExecSession(format!("encode exited frame: {e}")))?;
+        }
+        SessionOutput::Raw(frame_bytes) => {
+            // Pre-encoded frame — write directly to output buffer.
+            out_buf.extend_from_slice(&frame_bytes);
+        }
+    }
+    Ok(())
+}
+
 /// Handles a single incoming message from the host.
+#[allow(clippy::too_many_arguments)]
 async fn handle_message(
     msg: Message,
     sessions: &mut HashMap<u32, ExecSession>,
-    write_sessions: &mut HashMap<u32, FsWriteSession>,
+    persistent: &mut PersistentSessions,
+    fs_streams: &mut HashMap<u32, FsStream>,
+    output_compression: &mut HashMap<u32, Compression>,
+    tunnels: &mut Tunnels,
+    stats_sampler: &mut StatsSampler,
     session_tx: &mpsc::UnboundedSender<(u32, SessionOutput)>,
     out_buf: &mut Vec<u8>,
 ) -> AgentdResult<()> {
@@ -221,25 +328,38 @@ async fn handle_message(
                 .payload()
                 .map_err(|e| AgentdError::ExecSession(format!("decode exec request: {e}")))?;
             prepend_scripts_to_path(&mut req);
-            match ExecSession::spawn(msg.id, &req, session_tx.clone()) {
-                Ok(session) => {
+            let spawned = match req.persistent.clone() {
+                Some(name) => persistent.spawn(&name, msg.id, &req),
+                None => ExecSession::spawn(msg.id, &req, session_tx.clone()).map(|session| {
+                    let pid = session.pid();
+                    sessions.insert(msg.id, session);
+                    pid
+                }),
+            };
+            match spawned {
+                Ok(pid) => {
+                    if let Some(codec) = req.compression {
+                        output_compression.insert(msg.id, codec);
+                    }
                     let reply = Message::with_payload(
                         MessageType::ExecStarted,
                         msg.id,
-                        &ExecStarted { pid: session.pid() },
+                        &ExecStarted { pid },
                     )
                     .map_err(|e| AgentdError::ExecSession(format!("encode started: {e}")))?;
                     encode_to_buf(&reply, out_buf).map_err(|e| {
                         AgentdError::ExecSession(format!("encode started frame: {e}"))
                     })?;
-                    sessions.insert(msg.id, session);
                 }
                 Err(e) => {
                     // Send an immediate exit with code -1 on spawn failure.
                     let reply = Message::with_payload(
                         MessageType::ExecExited,
                         msg.id,
-                        &ExecExited { code: -1 },
+                        &ExecExited {
+                            code: -1,
+                            ..Default::default()
+                        },
                     )
                     .map_err(|e| AgentdError::ExecSession(format!("encode exited: {e}")))?;
                     encode_to_buf(&reply, out_buf).map_err(|e| {
@@ -254,7 +374,10 @@ async fn handle_message(
             let stdin: ExecStdin = msg
                 .payload()
                 .map_err(|e| AgentdError::ExecSession(format!("decode stdin: {e}")))?;
-            if let Some(session) = sessions.get_mut(&msg.id) {
+            if let Some(session) = sessions
+                .get_mut(&msg.id)
+                .or_else(|| persistent.attached_mut(msg.id))
+            {
                 if stdin.data.is_empty() {
                     // Empty data signals EOF — close stdin.
                     session.close_stdin();
@@ -268,7 +391,10 @@ async fn handle_message(
             let resize: ExecResize = msg
                 .payload()
                 .map_err(|e| AgentdError::ExecSession(format!("decode resize: {e}")))?;
-            if let Some(session) = sessions.get(&msg.id) {
+            if let Some(session) = sessions
+                .get_mut(&msg.id)
+                .or_else(|| persistent.attached_mut(msg.id))
+            {
                 let _ = session.resize(resize.rows, resize.cols);
             }
         }
@@ -277,18 +403,108 @@ async fn handle_message(
             let signal: ExecSignal = msg
                 .payload()
                 .map_err(|e| AgentdError::ExecSession(format!("decode signal: {e}")))?;
-            if let Some(session) = sessions.get(&msg.id) {
+            if let Some(session) = sessions
+                .get_mut(&msg.id)
+                .or_else(|| persistent.attached_mut(msg.id))
+            {
                 let _ = session.send_signal(signal.signal);
             }
         }
 
+        MessageType::ExecAttach => {
+            let attach: ExecAttach = msg
+                .payload()
+                .map_err(|e| AgentdError::ExecSession(format!("decode attach: {e}")))?;
+            let Some(attached) = persistent.attach(&attach.session, msg.id, attach.window) else {
+                let reply = Message::with_payload(
+                    MessageType::ExecExited,
+                    msg.id,
+                    &ExecExited {
+                        code: -1,
+                        ..Default::default()
+                    },
+                )
+                .map_err(|e| AgentdError::ExecSession(format!("encode exited: {e}")))?;
+                encode_to_buf(&reply, out_buf)
+                    .map_err(|e| AgentdError::ExecSession(format!("encode exited frame: {e}")))?;
+                return Ok(());
+            };
+
+            let reply = Message::with_payload(
+                MessageType::ExecStarted,
+                msg.id,
+                &ExecStarted { pid: attached.pid },
+            )
+            .map_err(|e| AgentdError::ExecSession(format!("encode started: {e}")))?;
+            encode_to_buf(&reply, out_buf)
+                .map_err(|e| AgentdError::ExecSession(format!("encode started frame: {e}")))?;
+
+            for chunk in attached.replay {
+                let output = match chunk {
+                    Chunk::Stdout(data) => SessionOutput::Stdout(data),
+                    Chunk::Stderr(data) => SessionOutput::Stderr(data),
+                };
+                encode_session_output(msg.id, output, attach.compression, out_buf)?;
+            }
+            match attached.exited {
+                Some(exited) => {
+                    encode_session_output(msg.id, SessionOutput::Exited(exited), None, out_buf)?;
+                }
+                None => {
+                    if let Some(codec) = attach.compression {
+                        output_compression.insert(msg.id, codec);
+                    }
+                }
+            }
+        }
+
+        MessageType::ExecDetach => {
+            // The client is gone: persistent sessions keep running detached,
+            // the rest stop waiting for credit so they can drain and exit.
+            persistent.detach(msg.id);
+            output_compression.remove(&msg.id);
+            if let Some(session) = sessions.get(&msg.id) {
+                session.window().release();
+            }
+            if let Some(FsStream::Read(window)) = fs_streams.remove(&msg.id) {
+                window.release();
+            }
+            tunnels.close(msg.id);
+        }
+
+        MessageType::Credit => {
+            let credit: Credit = msg
+                .payload()
+                .map_err(|e| AgentdError::ExecSession(format!("decode credit: {e}")))?;
+            if let Some(session) = sessions
+                .get_mut(&msg.id)
+                .or_else(|| persistent.attached_mut(msg.id))
+            {
+                session.window().grant(credit.bytes);
+            } else if let Some(FsStream::Read(window)) = fs_streams.get(&msg.id) {
+                window.grant(credit.bytes);
+            } else {
+                tunnels.grant(msg.id, credit.bytes);
+            }
+        }
+
+        MessageType::ExecSessionsRequest => {
+            let resp = ExecSessions {
+                sessions: persistent.list(),
+            };
+            let reply = Message::with_payload(MessageType::ExecSessionsResponse, msg.id, &resp)
+                .map_err(|e| AgentdError::ExecSession(format!("encode sessions: {e}")))?;
+            encode_to_buf(&reply, out_buf)
+                .map_err(|e| AgentdError::ExecSession(format!("encode sessions frame: {e}")))?;
+        }
+
         MessageType::FsRequest => {
             let req: FsRequest = msg
                 .payload()
                 .map_err(|e| AgentdError::ExecSession(format!("decode fs request: {e}")))?;
             match crate::fs::handle_fs_request(msg.id, req, out_buf, session_tx).await {
-                Ok(Some(ws)) => {
-                    write_sessions.insert(msg.id, ws);
+                Ok(Some(stream)) => {
+                    fs_streams.insert(msg.id, stream);
                 }
                 Ok(None) => {}
                 Err(e) => {
@@ -301,16 +517,16 @@ async fn handle_message(
             let data: FsData = msg
                 .payload()
                 .map_err(|e| AgentdError::ExecSession(format!("decode fs data: {e}")))?;
-            if let Some(session) = write_sessions.get_mut(&msg.id) {
+            if let Some(FsStream::Write(session)) = fs_streams.get_mut(&msg.id) {
                 match crate::fs::handle_fs_data(msg.id, data, session, out_buf).await {
                     Ok(true) => {
                         // Session complete — remove it.
-                        write_sessions.remove(&msg.id);
+                        fs_streams.remove(&msg.id);
                     }
                     Ok(false) => {}
                     Err(e) => {
                         eprintln!("fs data error for {}: {e}", msg.id);
-                        write_sessions.remove(&msg.id);
+                        fs_streams.remove(&msg.id);
                     }
                 }
             } else {
@@ -327,6 +543,72 @@ async fn handle_message(
             }
         }
 
+        MessageType::StatsRequest => {
+            let req: StatsRequest = msg
+                .payload()
+                .map_err(|e| AgentdError::ExecSession(format!("decode stats request: {e}")))?;
+            let resp = match stats_sampler.sample(req.processes) {
+                Ok(stats) => StatsResponse {
+                    ok: true,
+                    error: None,
+                    stats: Some(stats),
+                },
+                Err(e) => StatsResponse {
+                    ok: false,
+                    error: Some(e.to_string()),
+                    stats: None,
+                },
+            };
+            let reply = Message::with_payload(MessageType::StatsResponse, msg.id, &resp)
+                .map_err(|e| AgentdError::ExecSession(format!("encode stats: {e}")))?;
+            encode_to_buf(&reply, out_buf)
+                .map_err(|e| AgentdError::ExecSession(format!("encode stats frame: {e}")))?;
+        }
+
+        MessageType::ProcessRequest => {
+            let req: ProcessRequest = msg
+                .payload()
+                .map_err(|e| AgentdError::ExecSession(format!("decode process request: {e}")))?;
+            let resp = process::handle(req, stats_sampler);
+            let reply = Message::with_payload(MessageType::ProcessResponse, msg.id, &resp)
+                .map_err(|e| AgentdError::ExecSession(format!("encode process response: {e}")))?;
+            encode_to_buf(&reply, out_buf).map_err(|e| {
+                AgentdError::ExecSession(format!("encode process response frame: {e}"))
+            })?;
+        }
+
+        MessageType::TunnelOpen => {
+            let req: TunnelOpen = msg
+                .payload()
+                .map_err(|e| AgentdError::ExecSession(format!("decode tunnel open: {e}")))?;
+            tunnels.open(msg.id, req);
+        }
+
+        MessageType::TunnelListen => {
+            let req: TunnelListen = msg
+                .payload()
+                .map_err(|e| AgentdError::ExecSession(format!("decode tunnel listen: {e}")))?;
+            tunnels.listen(msg.id, req).await;
+        }
+
+        MessageType::TunnelBind => {
+            let req: TunnelBind = msg
+                .payload()
+                .map_err(|e| AgentdError::ExecSession(format!("decode tunnel bind: {e}")))?;
+            tunnels.bind(msg.id, req);
+        }
+
+        MessageType::TunnelData => {
+            let data: TunnelData = msg
+                .payload()
+                .map_err(|e| AgentdError::ExecSession(format!("decode tunnel data: {e}")))?;
+            tunnels.write(msg.id, data.data);
+        }
+
+        MessageType::TunnelClose => {
+            tunnels.close(msg.id);
+        }
+
         MessageType::Shutdown => {
             // Graceful shutdown — signal all sessions, then ask the guest
             // kernel to power off so block-root filesystems can shut down
@@ -334,7 +616,9 @@ async fn handle_message(
             for (_, session) in sessions.drain() {
                 let _ = session.send_signal(15); // SIGTERM
             }
-            write_sessions.clear();
+            persistent.signal_all(15);
+            fs_streams.clear();
+            tunnels.close_all();
 
             request_guest_poweroff()?;
             return Err(AgentdError::Shutdown);
@@ -348,6 +632,12 @@ async fn handle_message(
     Ok(())
 }
 
+/// Whether a pre-encoded frame ends its correlation ID's stream.
+fn is_terminal_frame(frame: &[u8]) -> bool {
+    // [len: u32][id: u32][flags: u8]...
+    frame.get(8).is_some_and(|flags| flags & FLAG_TERMINAL != 0)
+}
+
 /// Prepends `/.msb/scripts` to PATH in the exec request's environment.
 ///
 /// If the request already has a PATH entry, prepends to it. Otherwise
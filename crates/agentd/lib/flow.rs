@@ -0,0 +1,185 @@
+//! Send-side flow control for data streamed to the host.
+//!
+//! Each flow-controlled stream (exec output, file reads) has a [`FlowWindow`]
+//! shared between the agent loop, which adds the credit the host returns, and
+//! the task producing the data, which waits for credit before sending more.
+//! While a producer waits it stops reading from the process's pipe or PTY, so
+//! a slow host reader pauses the process instead of growing buffers.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Credit available to a stream's producer.
+///
+/// Cloning shares the window.
+#[derive(Debug, Clone)]
+pub struct FlowWindow {
+    inner: Arc<WindowInner>,
+}
+
+#[derive(Debug)]
+struct WindowInner {
+    state: Mutex<WindowState>,
+    notify: Notify,
+}
+
+#[derive(Debug)]
+struct WindowState {
+    /// Bytes the producer may still send. Goes negative when a chunk larger
+    /// than the remaining credit is sent.
+    credit: i64,
+
+    /// Whether credit is ignored (no flow control, or no consumer left).
+    unlimited: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl FlowWindow {
+    /// Create a window with `window` bytes of initial credit, or an
+    /// unlimited one if `window` is `None`.
+    pub fn new(window: Option<u32>) -> Self {
+        Self {
+            inner: Arc::new(WindowInner {
+                state: Mutex::new(WindowState::new(window)),
+                notify: Notify::new(),
+            }),
+        }
+    }
+
+    /// Wait until there is credit, then take `bytes` of it.
+    ///
+    /// A single chunk may overdraw the window; the producer then waits until
+    /// the host has returned enough credit to cover it.
+    pub async fn acquire(&self, bytes: usize) {
+        loop {
+            {
+                let mut state = self.lock();
+                if state.unlimited {
+                    return;
+                }
+                if state.credit > 0 {
+                    state.credit -= bytes as i64;
+                    return;
+                }
+            }
+            self.inner.notify.notified().await;
+        }
+    }
+
+    /// Take `bytes` of credit without waiting, for data sent outside the
+    /// producer (such as replayed scrollback).
+    pub fn charge(&self, bytes: usize) {
+        let mut state = self.lock();
+        if !state.unlimited {
+            state.credit -= bytes as i64;
+        }
+    }
+
+    /// Return `bytes` of credit from the host.
+    pub fn grant(&self, bytes: u32) {
+        self.lock().credit += i64::from(bytes);
+        self.inner.notify.notify_one();
+    }
+
+    /// Stop enforcing the window. Used when the consumer goes away so the
+    /// producer can drain its process and report the exit.
+    pub fn release(&self) {
+        self.lock().unlimited = true;
+        self.inner.notify.notify_one();
+    }
+
+    /// Start over with a new consumer's window.
+    pub fn reset(&self, window: Option<u32>) {
+        *self.lock() = WindowState::new(window);
+        self.inner.notify.notify_one();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, WindowState> {
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl WindowState {
+    fn new(window: Option<u32>) -> Self {
+        Self {
+            credit: window.map(i64::from).unwrap_or(0),
+            unlimited: window.is_none(),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_credit() {
+        let window = FlowWindow::new(Some(8));
+        window.acquire(6).await;
+        window.acquire(6).await; // Overdraws to -4.
+
+        let waiter = window.clone();
+        let blocked = tokio::spawn(async move { waiter.acquire(1).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+
+        // Covering the overdraft is not enough; credit must be positive.
+        window.grant(4);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+
+        window.grant(1);
+        timeout(Duration::from_secs(1), blocked)
+            .await
+            .expect("acquire should resume after credit")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_release_and_unlimited_never_wait() {
+        let unlimited = FlowWindow::new(None);
+        unlimited.acquire(1 << 30).await;
+        unlimited.acquire(1 << 30).await;
+
+        let window = FlowWindow::new(Some(1));
+        window.acquire(1).await;
+        let waiter = window.clone();
+        let blocked = tokio::spawn(async move { waiter.acquire(1).await });
+        window.release();
+        timeout(Duration::from_secs(1), blocked)
+            .await
+            .expect("release should unblock the producer")
+            .unwrap();
+
+        window.reset(Some(4));
+        window.charge(4);
+        let waiter = window.clone();
+        let blocked = tokio::spawn(async move { waiter.acquire(1).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+        window.grant(4);
+        timeout(Duration::from_secs(1), blocked)
+            .await
+            .expect("acquire should resume after credit")
+            .unwrap();
+    }
+}
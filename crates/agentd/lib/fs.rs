@@ -7,6 +7,8 @@ use std::os::unix::fs::{MetadataExt, PermissionsExt};
 
 use microsandbox_protocol::{
     codec::encode_to_buf,
+    compress::Compression,
+    flow::Credit,
     fs::{FS_CHUNK_SIZE, FsData, FsEntryInfo, FsOp, FsRequest, FsResponse, FsResponseData},
     message::{Message, MessageType},
 };
@@ -15,15 +17,27 @@ use tokio::{
     sync::mpsc,
 };
 
-use crate::session::SessionOutput;
+use crate::{flow::FlowWindow, session::SessionOutput};
 
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
 
+/// A streaming operation that continues after its `FsRequest`.
+pub enum FsStream {
+    /// A file read sending `FsData` chunks, paced by this window.
+    Read(FlowWindow),
+
+    /// A file write receiving `FsData` chunks.
+    Write(FsWriteSession),
+}
+
 /// Tracks an in-progress streaming write operation.
 pub struct FsWriteSession {
     file: tokio::fs::File,
+
+    /// Whether the host is flow-controlled and waits for credit.
+    credit: bool,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -36,7 +50,8 @@ pub struct FsWriteSession {
 /// the response is encoded directly into `out_buf`.
 ///
 /// For streaming read, a background task is spawned that sends `FsData` chunks
-/// via `session_tx`, followed by a terminal `FsResponse`.
+/// via `session_tx`, followed by a terminal `FsResponse`. Its flow-control
+/// window is returned so the caller can route credit to it.
 ///
 /// For streaming write, a `FsWriteSession` is created and returned for the
 /// caller to insert into the write sessions map.
@@ -45,7 +60,7 @@ pub async fn handle_fs_request(
     req: FsRequest,
     out_buf: &mut Vec<u8>,
     session_tx: &mpsc::UnboundedSender<(u32, SessionOutput)>,
-) -> Result<Option<FsWriteSession>, String> {
+) -> Result<Option<FsStream>, String> {
     match req.op {
         FsOp::Stat { path } => {
             let resp = handle_stat(&path).await;
@@ -57,15 +72,24 @@ pub async fn handle_fs_request(
             encode_response(id, resp, out_buf)?;
             Ok(None)
         }
-        FsOp::Read { path } => {
+        FsOp::Read {
+            path,
+            window,
+            compression,
+        } => {
             let tx = session_tx.clone();
+            let window = FlowWindow::new(window);
+            let reader_window = window.clone();
             tokio::spawn(async move {
-                handle_read_stream(id, &path, &tx).await;
+                handle_read_stream(id, &path, &reader_window, compression, &tx).await;
             });
-            Ok(None)
+            Ok(Some(FsStream::Read(window)))
         }
-        FsOp::Write { path, mode } => match handle_write_open(&path, mode).await {
-            Ok(session) => Ok(Some(session)),
+        FsOp::Write { path, mode, window } => match handle_write_open(&path, mode).await {
+            Ok(mut session) => {
+                session.credit = window.is_some();
+                Ok(Some(FsStream::Write(session)))
+            }
             Err(e) => {
                 let resp = FsResponse {
                     ok: false,
@@ -107,7 +131,9 @@ pub async fn handle_fs_request(
 /// Handles an incoming `FsData` message for a streaming write session.
 ///
 /// If `data` is empty, the file is closed and a terminal `FsResponse` is sent.
-/// Returns `true` if the session should be removed (EOF received).
+/// Otherwise the chunk is written and, for flow-controlled writes, its size
+/// is returned to the host as credit. Returns `true` if the session should
+/// be removed (EOF received).
 pub async fn handle_fs_data(
     id: u32,
     data: FsData,
@@ -144,6 +170,14 @@ pub async fn handle_fs_data(
             encode_response(id, resp, out_buf)?;
             return Ok(true);
         }
+        if session.credit {
+            let credit = Credit {
+                bytes: data.data.len() as u32,
+            };
+            let msg = Message::with_payload(MessageType::Credit, id, &credit)
+                .map_err(|e| format!("encode fs credit: {e}"))?;
+            encode_to_buf(&msg, out_buf).map_err(|e| format!("encode fs credit frame: {e}"))?;
+        }
         Ok(false)
     }
 }
@@ -227,7 +261,16 @@ async fn handle_list(path: &str) -> FsResponse {
 }
 
 /// Stream file contents as `FsData` chunks, then send terminal `FsResponse`.
-async fn handle_read_stream(id: u32, path: &str, tx: &mpsc::UnboundedSender<(u32, SessionOutput)>) {
+///
+/// Waits for credit in `window` before sending each chunk, and compresses
+/// chunks with `compression` where that makes them smaller.
+async fn handle_read_stream(
+    id: u32,
+    path: &str,
+    window: &FlowWindow,
+    compression: Option<Compression>,
+    tx: &mpsc::UnboundedSender<(u32, SessionOutput)>,
+) {
     let file = match tokio::fs::File::open(path).await {
         Ok(f) => f,
         Err(e) => {
@@ -244,11 +287,12 @@ async fn handle_read_stream(id: u32, path: &str, tx: &mpsc::UnboundedSender<(u32
         match reader.read(&mut chunk).await {
             Ok(0) => break,
             Ok(n) => {
+                window.acquire(n).await;
                 let data = FsData {
                     data: chunk[..n].to_vec(),
                 };
                 let msg = match Message::with_payload(MessageType::FsData, id, &data) {
-                    Ok(msg) => msg,
+                    Ok(msg) => msg.compress(compression),
                     Err(e) => {
                         send_raw_response(id, false, Some(format!("encode chunk: {e}")), None, tx);
                         return;
@@ -334,7 +378,10 @@ async fn handle_write_open(path: &str, mode: Option<u32>) -> Result<FsWriteSessi
             .map_err(|e| format!("set permissions: {e}"))?;
     }
 
-    Ok(FsWriteSession { file })
+    Ok(FsWriteSession {
+        file,
+        credit: false,
+    })
 }
 
 /// Create a directory (and parents).
@@ -14,12 +14,17 @@ mod error;
 
 pub mod agent;
 pub mod clock;
+pub mod flow;
 pub mod fs;
 pub mod heartbeat;
 pub mod init;
 pub mod network;
+pub mod persistent;
+pub mod process;
 pub mod serial;
 pub mod session;
+pub mod stats;
 pub mod tls;
+pub mod tunnel;
 
 pub use error::*;
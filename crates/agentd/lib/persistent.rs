@@ -0,0 +1,420 @@
+//! Persistent exec sessions: processes that outlive the host client that
+//! started them.
+//!
+//! A persistent session is spawned like any other exec session, but its
+//! output goes through a [`PersistentSessions`] table instead of straight to
+//! the host. The table keeps a bounded scrollback of recent output and the
+//! correlation ID of the client currently attached, if any. When the client
+//! goes away the session keeps running and buffering; a later `ExecAttach`
+//! binds it to the new client's correlation ID and replays the scrollback.
+//!
+//! Only an attached client applies flow control. While detached, the
+//! session's window is released and output goes to the bounded scrollback.
+//!
+//! Sessions are keyed internally by an agentd-assigned key rather than by
+//! correlation ID, because correlation IDs are reused once the relay hands
+//! the client's ID range to someone else.
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::Utc;
+use microsandbox_protocol::exec::{ExecExited, ExecRequest, ExecSessionInfo};
+use tokio::sync::mpsc;
+
+use crate::{
+    error::{AgentdError, AgentdResult},
+    session::{ExecSession, SessionOutput},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Maximum bytes of output kept per session for replay on reattach.
+pub const SCROLLBACK_LIMIT: usize = 1024 * 1024;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// All persistent sessions known to the agent.
+pub struct PersistentSessions {
+    /// Sessions keyed by their internal key.
+    sessions: HashMap<u32, PersistentSession>,
+
+    /// Next internal key to assign.
+    next_key: u32,
+
+    /// Channel the sessions' reader tasks send output on, tagged by key.
+    tx: mpsc::UnboundedSender<(u32, SessionOutput)>,
+}
+
+/// A single persistent session.
+struct PersistentSession {
+    name: String,
+    cmd: String,
+    tty: bool,
+    pid: u32,
+    started_at: i64,
+    session: ExecSession,
+
+    /// Correlation ID of the attached client, if any.
+    attached: Option<u32>,
+
+    scrollback: Scrollback,
+
+    /// How the process exited, once it has.
+    exited: Option<ExecExited>,
+}
+
+/// Bounded buffer of a session's most recent output.
+#[derive(Default)]
+pub struct Scrollback {
+    chunks: VecDeque<Chunk>,
+    len: usize,
+}
+
+/// A buffered piece of output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chunk {
+    /// Data written to stdout (or the PTY).
+    Stdout(Vec<u8>),
+
+    /// Data written to stderr.
+    Stderr(Vec<u8>),
+}
+
+/// State handed back when a client attaches to a session.
+pub struct Attached {
+    /// PID of the session's process.
+    pub pid: u32,
+
+    /// Buffered output to replay, oldest first.
+    pub replay: Vec<Chunk>,
+
+    /// How the process exited, if it already has. The session is forgotten
+    /// once this has been delivered.
+    pub exited: Option<ExecExited>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl PersistentSessions {
+    /// Create an empty table whose sessions report output on `tx`.
+    pub fn new(tx: mpsc::UnboundedSender<(u32, SessionOutput)>) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            next_key: 1,
+            tx,
+        }
+    }
+
+    /// Spawn a persistent session named `name`, attached to correlation ID `id`.
+    ///
+    /// Fails if a session with the same name is still running. An exited
+    /// session with the same name is replaced.
+    pub fn spawn(&mut self, name: &str, id: u32, req: &ExecRequest) -> AgentdResult<u32> {
+        if let Some(key) = self.key_by_name(name) {
+            if self.sessions[&key].exited.is_none() {
+                return Err(AgentdError::ExecSession(format!(
+                    "persistent session already running: {name}"
+                )));
+            }
+            self.sessions.remove(&key);
+        }
+
+        let key = self.next_key;
+        self.next_key = self.next_key.wrapping_add(1).max(1);
+
+        let session = ExecSession::spawn(key, req, self.tx.clone())?;
+        let pid = session.pid();
+        let cmd = std::iter::once(req.cmd.as_str())
+            .chain(req.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.sessions.insert(
+            key,
+            PersistentSession {
+                name: name.to_string(),
+                cmd,
+                tty: req.tty,
+                pid,
+                started_at: Utc::now().timestamp(),
+                session,
+                attached: Some(id),
+                scrollback: Scrollback::default(),
+                exited: None,
+            },
+        );
+        Ok(pid)
+    }
+
+    /// Attach the session named `name` to correlation ID `id`, with the
+    /// client's flow-control window.
+    ///
+    /// Returns `None` if there is no such session or another client is
+    /// attached to it.
+    pub fn attach(&mut self, name: &str, id: u32, window: Option<u32>) -> Option<Attached> {
+        let key = self.key_by_name(name)?;
+        let entry = self.sessions.get_mut(&key)?;
+        if entry.attached.is_some() {
+            return None;
+        }
+
+        let attached = Attached {
+            pid: entry.pid,
+            replay: entry.scrollback.chunks.iter().cloned().collect(),
+            exited: entry.exited.clone(),
+        };
+
+        if attached.exited.is_some() {
+            self.sessions.remove(&key);
+        } else {
+            entry.attached = Some(id);
+            entry.session.window().reset(window);
+            entry.session.window().charge(entry.scrollback.len);
+        }
+        Some(attached)
+    }
+
+    /// Mark the session attached to correlation ID `id` as detached.
+    pub fn detach(&mut self, id: u32) {
+        if let Some(entry) = self
+            .sessions
+            .values_mut()
+            .find(|entry| entry.attached == Some(id))
+        {
+            entry.attached = None;
+            entry.session.window().release();
+        }
+    }
+
+    /// The session attached to correlation ID `id`, for stdin, resize and
+    /// signals.
+    pub fn attached_mut(&mut self, id: u32) -> Option<&mut ExecSession> {
+        self.sessions
+            .values_mut()
+            .find(|entry| entry.attached == Some(id) && entry.exited.is_none())
+            .map(|entry| &mut entry.session)
+    }
+
+    /// Record output from the session with internal key `key`.
+    ///
+    /// Returns the correlation ID to forward the output to, if a client is
+    /// attached. A session that exits while attached is forgotten once its
+    /// exit has been forwarded.
+    pub fn record(&mut self, key: u32, output: &SessionOutput) -> Option<u32> {
+        let entry = self.sessions.get_mut(&key)?;
+        match output {
+            SessionOutput::Stdout(data) => entry.scrollback.push(Chunk::Stdout(data.clone())),
+            SessionOutput::Stderr(data) => entry.scrollback.push(Chunk::Stderr(data.clone())),
+            SessionOutput::Exited(exited) => {
+                entry.exited = Some(exited.clone());
+                if entry.attached.is_some() {
+                    return self.sessions.remove(&key).and_then(|entry| entry.attached);
+                }
+            }
+            SessionOutput::Raw(_) => {}
+        }
+        entry.attached
+    }
+
+    /// Describe every known session.
+    pub fn list(&self) -> Vec<ExecSessionInfo> {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .values()
+            .map(|entry| ExecSessionInfo {
+                name: entry.name.clone(),
+                pid: entry.pid,
+                cmd: entry.cmd.clone(),
+                tty: entry.tty,
+                attached: entry.attached.is_some(),
+                exit_code: entry.exited.as_ref().map(|exited| exited.code),
+                buffered: entry.scrollback.len as u64,
+                started_at: entry.started_at,
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.name.cmp(&b.name));
+        sessions
+    }
+
+    /// Number of sessions whose process is still running.
+    pub fn running(&self) -> usize {
+        self.sessions
+            .values()
+            .filter(|entry| entry.exited.is_none())
+            .count()
+    }
+
+    /// Send a signal to every running session.
+    pub fn signal_all(&self, signal: i32) {
+        for entry in self.sessions.values() {
+            if entry.exited.is_none() {
+                let _ = entry.session.send_signal(signal);
+            }
+        }
+    }
+
+    fn key_by_name(&self, name: &str) -> Option<u32> {
+        self.sessions
+            .iter()
+            .find(|(_, entry)| entry.name == name)
+            .map(|(key, _)| *key)
+    }
+}
+
+impl Scrollback {
+    /// Append a chunk, dropping the oldest output beyond [`SCROLLBACK_LIMIT`].
+    pub fn push(&mut self, chunk: Chunk) {
+        self.len += chunk.data().len();
+        self.chunks.push_back(chunk);
+
+        while self.len > SCROLLBACK_LIMIT {
+            let Some(front) = self.chunks.front_mut() else {
+                break;
+            };
+            let excess = self.len - SCROLLBACK_LIMIT;
+            let data = front.data_mut();
+            if data.len() <= excess {
+                self.len -= data.len();
+                self.chunks.pop_front();
+            } else {
+                data.drain(..excess);
+                self.len -= excess;
+            }
+        }
+    }
+
+    /// Total bytes buffered.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether nothing is buffered.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Chunk {
+    fn data(&self) -> &[u8] {
+        match self {
+            Self::Stdout(data) | Self::Stderr(data) => data,
+        }
+    }
+
+    fn data_mut(&mut self) -> &mut Vec<u8> {
+        match self {
+            Self::Stdout(data) | Self::Stderr(data) => data,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn request(script: &str) -> ExecRequest {
+        ExecRequest {
+            cmd: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env: vec!["PATH=/usr/local/bin:/usr/bin:/bin".to_string()],
+            clear_env: false,
+            cwd: None,
+            user: None,
+            tty: false,
+            rows: 24,
+            cols: 80,
+            rlimits: Vec::new(),
+            traceparent: None,
+            persistent: Some("build".to_string()),
+            window: None,
+            compression: None,
+        }
+    }
+
+    #[test]
+    fn test_scrollback_keeps_most_recent_bytes() {
+        let mut scrollback = Scrollback::default();
+        scrollback.push(Chunk::Stdout(vec![b'a'; SCROLLBACK_LIMIT - 2]));
+        scrollback.push(Chunk::Stderr(b"bcde".to_vec()));
+
+        assert_eq!(scrollback.len(), SCROLLBACK_LIMIT);
+        let first = scrollback.chunks.front().unwrap().data();
+        assert_eq!(first.len(), SCROLLBACK_LIMIT - 4);
+
+        scrollback.push(Chunk::Stdout(vec![b'z'; SCROLLBACK_LIMIT]));
+        assert_eq!(scrollback.len(), SCROLLBACK_LIMIT);
+        assert_eq!(scrollback.chunks.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_buffers_while_detached_and_replays_on_attach() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut sessions = PersistentSessions::new(tx);
+
+        sessions
+            .spawn("build", 10, &request("read line; echo got $line"))
+            .expect("spawn persistent session");
+        assert!(
+            sessions.spawn("build", 11, &request("true")).is_err(),
+            "duplicate running name must be rejected"
+        );
+
+        // The client goes away; input and output now wait for a reattach.
+        sessions.detach(10);
+        assert!(sessions.attached_mut(10).is_none());
+        assert!(sessions.attach("missing", 20, None).is_none());
+
+        let attached = sessions.attach("build", 20, None).expect("reattach");
+        assert!(attached.replay.is_empty());
+        assert!(
+            sessions.attach("build", 21, None).is_none(),
+            "already attached"
+        );
+        sessions
+            .attached_mut(20)
+            .expect("attached session")
+            .write_stdin(b"hello\n")
+            .await
+            .unwrap();
+
+        // Detach again before the output arrives so it is only buffered.
+        sessions.detach(20);
+        let mut exited = false;
+        while !exited {
+            let (key, output) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("session output")
+                .unwrap();
+            exited = matches!(output, SessionOutput::Exited(_));
+            assert_eq!(sessions.record(key, &output), None);
+        }
+
+        let info = sessions.list();
+        assert_eq!(info.len(), 1);
+        assert_eq!(info[0].exit_code, Some(0));
+        assert!(!info[0].attached);
+        assert_eq!(sessions.running(), 0);
+
+        let attached = sessions
+            .attach("build", 30, None)
+            .expect("attach to exited");
+        assert_eq!(attached.exited.map(|exited| exited.code), Some(0));
+        assert_eq!(attached.replay, [Chunk::Stdout(b"got hello\n".to_vec())]);
+        assert!(
+            sessions.list().is_empty(),
+            "exit delivered, session forgotten"
+        );
+    }
+}
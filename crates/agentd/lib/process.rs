@@ -0,0 +1,90 @@
+//! Guest process operations for `core.process.request`.
+
+use microsandbox_protocol::process::{ProcessOp, ProcessRequest, ProcessResponse};
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+
+use crate::stats::StatsSampler;
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Performs a process operation and builds its response.
+pub fn handle(req: ProcessRequest, sampler: &mut StatsSampler) -> ProcessResponse {
+    let result = match req.op {
+        ProcessOp::List => sampler.processes().map_err(|e| e.to_string()),
+        ProcessOp::Signal { pid, signal } => send_signal(pid, signal).map(|()| Vec::new()),
+    };
+
+    match result {
+        Ok(processes) => ProcessResponse {
+            ok: true,
+            error: None,
+            processes,
+        },
+        Err(error) => ProcessResponse {
+            ok: false,
+            error: Some(error),
+            processes: Vec::new(),
+        },
+    }
+}
+
+/// Sends `signal` to a single process. Signal 0 only checks that the
+/// process exists.
+fn send_signal(pid: i32, signal: i32) -> Result<(), String> {
+    // kill(2) treats 0 and negative PIDs as process groups or "every
+    // process"; only single processes are addressable here.
+    if pid <= 0 {
+        return Err(format!("invalid pid {pid}"));
+    }
+    if pid == std::process::id() as i32 {
+        return Err(format!("pid {pid} is agentd and cannot be signalled"));
+    }
+
+    let sig = match signal {
+        0 => None,
+        n => Some(Signal::try_from(n).map_err(|e| format!("invalid signal {n}: {e}"))?),
+    };
+    kill(Pid::from_raw(pid), sig).map_err(|e| format!("signal pid {pid}: {e}"))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_rejects_group_and_agentd_targets() {
+        assert!(send_signal(0, 15).unwrap_err().contains("invalid pid"));
+        assert!(send_signal(-1, 9).unwrap_err().contains("invalid pid"));
+
+        let own = std::process::id() as i32;
+        assert!(send_signal(own, 0).unwrap_err().contains("agentd"));
+    }
+
+    #[test]
+    fn test_signal_zero_probes_existing_process() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        let pid = child.id() as i32;
+
+        assert!(send_signal(pid, 0).is_ok());
+        assert!(
+            send_signal(pid, 999)
+                .unwrap_err()
+                .contains("invalid signal")
+        );
+        assert!(send_signal(pid, libc::SIGKILL).is_ok());
+        child.wait().unwrap();
+        assert!(send_signal(pid, 0).is_err());
+    }
+}
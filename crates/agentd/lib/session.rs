@@ -5,6 +5,7 @@ use std::{
     mem::MaybeUninit,
     os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
     process::Stdio,
+    time::Instant,
 };
 
 use nix::{
@@ -18,9 +19,19 @@ use tokio::{
     sync::mpsc,
 };
 
-use microsandbox_protocol::exec::ExecRequest;
+use microsandbox_protocol::exec::{ExecExited, ExecRequest, ExecUsage};
 
-use crate::error::{AgentdError, AgentdResult};
+use crate::{
+    error::{AgentdError, AgentdResult},
+    flow::FlowWindow,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Environment variable carrying the W3C trace context into exec'd processes.
+const TRACEPARENT_ENV: &str = "TRACEPARENT";
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -40,6 +51,9 @@ pub struct ExecSession {
 
     /// The child's stdin (only for pipe mode).
     stdin: Option<tokio::process::ChildStdin>,
+
+    /// Flow-control window for the process's output.
+    window: FlowWindow,
 }
 
 /// Output from a session that the agent loop should forward to the host.
@@ -50,8 +64,8 @@ pub enum SessionOutput {
     /// Data from stderr (pipe mode only).
     Stderr(Vec<u8>),
 
-    /// The process has exited with the given code.
-    Exited(i32),
+    /// The process has exited.
+    Exited(ExecExited),
 
     /// Pre-encoded frame bytes to write directly to the serial output buffer.
     ///
@@ -109,6 +123,11 @@ impl ExecSession {
         self.pid as u32
     }
 
+    /// Returns the flow-control window the output reader waits on.
+    pub fn window(&self) -> &FlowWindow {
+        &self.window
+    }
+
     /// Writes data to the process's stdin (or PTY master).
     pub async fn write_stdin(&self, data: &[u8]) -> AgentdResult<()> {
         if let Some(ref master) = self.pty_master {
@@ -197,11 +216,8 @@ impl ExecSession {
             .collect();
 
         // Pre-parse environment variables into CStrings.
-        let c_env: Vec<(CString, CString)> = req
-            .env
-            .iter()
-            .filter_map(|var| {
-                let (key, val) = var.split_once('=')?;
+        let c_env: Vec<(CString, CString)> = exec_env(req)
+            .filter_map(|(key, val)| {
                 let k = CString::new(key).ok()?;
                 let v = CString::new(val).ok()?;
                 Some((k, v))
@@ -230,6 +246,7 @@ impl ExecSession {
         let parsed_rlimits = parse_rlimits(req);
 
         // Fork.
+        let started = Instant::now();
         let pid = unsafe { libc::fork() };
         if pid < 0 {
             return Err(std::io::Error::last_os_error().into());
@@ -268,6 +285,11 @@ impl ExecSession {
             }
 
             // Set environment variables using pre-built CStrings.
+            if req.clear_env {
+                unsafe {
+                    libc::clearenv();
+                }
+            }
             for (key, val) in &c_env {
                 unsafe {
                     libc::setenv(key.as_ptr(), val.as_ptr(), 1);
@@ -332,12 +354,21 @@ impl ExecSession {
         let reader_fd = unsafe { OwnedFd::from_raw_fd(reader_fd) };
 
         // Spawn background reader task.
-        tokio::spawn(pty_reader_task(id, pid, reader_fd, tx));
+        let window = FlowWindow::new(req.window);
+        tokio::spawn(pty_reader_task(
+            id,
+            pid,
+            started,
+            reader_fd,
+            window.clone(),
+            tx,
+        ));
 
         Ok(Self {
             pid,
             pty_master: Some(pty.master),
             stdin: None,
+            window,
         })
     }
 
@@ -353,11 +384,10 @@ impl ExecSession {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        for var in &req.env {
-            if let Some((key, val)) = var.split_once('=') {
-                cmd.env(key, val);
-            }
+        if req.clear_env {
+            cmd.env_clear();
         }
+        cmd.envs(exec_env(req));
 
         if let Some(ref dir) = req.cwd {
             cmd.current_dir(dir);
@@ -386,6 +416,7 @@ impl ExecSession {
             }
         }
 
+        let started = Instant::now();
         let mut child = cmd.spawn().map_err(|err| {
             AgentdError::ExecSession(format!(
                 "spawn pipe cmd={} args={:?} cwd={:?}: {}",
@@ -398,12 +429,22 @@ impl ExecSession {
         let stderr = child.stderr.take();
 
         // Spawn background reader task.
-        tokio::spawn(pipe_reader_task(id, child, stdout, stderr, tx));
+        let window = FlowWindow::new(req.window);
+        tokio::spawn(pipe_reader_task(
+            id,
+            child,
+            started,
+            stdout,
+            stderr,
+            window.clone(),
+            tx,
+        ));
 
         Ok(Self {
             pid,
             pty_master: None,
             stdin,
+            window,
         })
     }
 }
@@ -743,6 +784,21 @@ fn default_home_dir<'a>(req: &ExecRequest, user: Option<&'a ResolvedUser>) -> Op
     user.and_then(|user| user.home_dir.as_deref())
 }
 
+/// Environment variables for the spawned process: the request's own, plus
+/// `TRACEPARENT` when the host propagated a trace context.
+fn exec_env(req: &ExecRequest) -> impl Iterator<Item = (&str, &str)> {
+    let traceparent = req
+        .traceparent
+        .as_deref()
+        .filter(|_| !env_contains_key(&req.env, TRACEPARENT_ENV))
+        .map(|traceparent| (TRACEPARENT_ENV, traceparent));
+
+    req.env
+        .iter()
+        .filter_map(|var| var.split_once('='))
+        .chain(traceparent)
+}
+
 fn env_contains_key(env: &[String], key: &str) -> bool {
     env.iter().any(|entry| {
         entry
@@ -776,13 +832,19 @@ async fn blocking_write_fd(fd: RawFd, data: &[u8]) -> AgentdResult<()> {
 }
 
 /// Background task that reads from a PTY master fd and sends output events.
+///
+/// Stops reading while `window` has no credit, which blocks the process once
+/// the PTY buffer fills.
 async fn pty_reader_task(
     id: u32,
     pid: i32,
+    started: Instant,
     master_fd: OwnedFd,
+    window: FlowWindow,
     tx: mpsc::UnboundedSender<(u32, SessionOutput)>,
 ) {
     let tx_output = tx.clone();
+    let runtime = tokio::runtime::Handle::current();
     let read_result = tokio::task::spawn_blocking(move || {
         // PTY masters are safer with a dedicated blocking read loop than with
         // edge-driven readiness. Fast writers followed by process exit can
@@ -798,6 +860,7 @@ async fn pty_reader_task(
             let n = unsafe { libc::read(raw, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
 
             if n > 0 {
+                runtime.block_on(window.acquire(n as usize));
                 if tx_output
                     .send((id, SessionOutput::Stdout(buf[..n as usize].to_vec())))
                     .is_err()
@@ -823,16 +886,21 @@ async fn pty_reader_task(
 
     let _ = read_result;
 
-    let code = wait_for_pid(pid).await;
-    let _ = tx.send((id, SessionOutput::Exited(code)));
+    let exited = wait_for_pid(pid, started).await;
+    let _ = tx.send((id, SessionOutput::Exited(exited)));
 }
 
 /// Background task that reads from piped stdout/stderr and sends output events.
+///
+/// Stops reading while `window` has no credit, which blocks the process once
+/// the pipe buffers fill.
 async fn pipe_reader_task(
     id: u32,
-    mut child: Child,
+    child: Child,
+    started: Instant,
     stdout: Option<tokio::process::ChildStdout>,
     stderr: Option<tokio::process::ChildStderr>,
+    window: FlowWindow,
     tx: mpsc::UnboundedSender<(u32, SessionOutput)>,
 ) {
     let mut stdout = stdout;
@@ -857,6 +925,7 @@ async fn pipe_reader_task(
                         stdout_eof = true;
                     }
                     Ok(n) => {
+                        window.acquire(n).await;
                         let _ = tx.send((id, SessionOutput::Stdout(stdout_buf[..n].to_vec())));
                     }
                 }
@@ -873,6 +942,7 @@ async fn pipe_reader_task(
                         stderr_eof = true;
                     }
                     Ok(n) => {
+                        window.acquire(n).await;
                         let _ = tx.send((id, SessionOutput::Stderr(stderr_buf[..n].to_vec())));
                     }
                 }
@@ -880,30 +950,75 @@ async fn pipe_reader_task(
         }
     }
 
-    // Both streams are done — wait for process exit.
-    let code = match child.wait().await {
-        Ok(status) => status.code().unwrap_or(-1),
-        Err(_) => -1,
+    // Both streams are done — wait for process exit. Reap by PID to get the
+    // rusage; `child` stays alive until then so tokio does not reap it first.
+    let exited = match child.id() {
+        Some(pid) => wait_for_pid(pid as i32, started).await,
+        None => ExecExited {
+            code: -1,
+            ..Default::default()
+        },
     };
+    drop(child);
 
-    let _ = tx.send((id, SessionOutput::Exited(code)));
+    let _ = tx.send((id, SessionOutput::Exited(exited)));
 }
 
-/// Waits for a process to exit by PID and returns the exit code.
-async fn wait_for_pid(pid: i32) -> i32 {
+/// Waits for a process to exit by PID and reports how it exited and the
+/// resources it used.
+async fn wait_for_pid(pid: i32, started: Instant) -> ExecExited {
     tokio::task::spawn_blocking(move || {
         let mut status: i32 = 0;
-        unsafe {
-            libc::waitpid(pid, &mut status, 0);
+        let mut rusage = MaybeUninit::<libc::rusage>::zeroed();
+        let reaped = loop {
+            let ret = unsafe { libc::wait4(pid, &mut status, 0, rusage.as_mut_ptr()) };
+            if ret == -1
+                && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+            {
+                continue;
+            }
+            break ret == pid;
+        };
+        if !reaped {
+            return ExecExited {
+                code: -1,
+                ..Default::default()
+            };
         }
-        if libc::WIFEXITED(status) {
+
+        let rusage = unsafe { rusage.assume_init() };
+        exec_exited(status, &rusage, started)
+    })
+    .await
+    .unwrap_or_else(|_| ExecExited {
+        code: -1,
+        ..Default::default()
+    })
+}
+
+/// Build the exit report from a `wait4` status and rusage.
+fn exec_exited(status: i32, rusage: &libc::rusage, started: Instant) -> ExecExited {
+    let signaled = libc::WIFSIGNALED(status);
+    ExecExited {
+        code: if libc::WIFEXITED(status) {
             libc::WEXITSTATUS(status)
         } else {
             -1
-        }
-    })
-    .await
-    .unwrap_or(-1)
+        },
+        signal: signaled.then(|| libc::WTERMSIG(status)),
+        core_dumped: signaled && libc::WCOREDUMP(status),
+        usage: Some(ExecUsage {
+            wall_time_us: started.elapsed().as_micros() as u64,
+            user_time_us: timeval_us(&rusage.ru_utime),
+            sys_time_us: timeval_us(&rusage.ru_stime),
+            // Linux reports ru_maxrss in KiB.
+            max_rss_kib: rusage.ru_maxrss.max(0) as u64,
+        }),
+    }
+}
+
+fn timeval_us(tv: &libc::timeval) -> u64 {
+    (tv.tv_sec.max(0) as u64) * 1_000_000 + tv.tv_usec.max(0) as u64
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -931,12 +1046,17 @@ mod tests {
                     .to_string(),
             ],
             env: vec!["PATH=/usr/local/bin:/usr/bin:/bin".to_string()],
+            clear_env: false,
             cwd: None,
             user: None,
             tty: true,
             rows: 24,
             cols: 80,
             rlimits: Vec::new(),
+            traceparent: None,
+            persistent: None,
+            window: None,
+            compression: None,
         };
 
         let session = ExecSession::spawn(7, &req, tx).expect("spawn pty session");
@@ -948,8 +1068,8 @@ mod tests {
                 assert_eq!(id, 7);
                 match output {
                     SessionOutput::Stdout(data) => stdout.extend_from_slice(&data),
-                    SessionOutput::Exited(code) => {
-                        exit = Some(code);
+                    SessionOutput::Exited(exited) => {
+                        exit = Some(exited.code);
                         break;
                     }
                     SessionOutput::Stderr(_) | SessionOutput::Raw(_) => {}
@@ -979,6 +1099,143 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_pipe_output_waits_for_credit() {
+        const WINDOW: u32 = 8 * 1024;
+        const TOTAL: usize = 256 * 1024;
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let req = ExecRequest {
+            cmd: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), format!("head -c {TOTAL} /dev/zero")],
+            env: vec!["PATH=/usr/local/bin:/usr/bin:/bin".to_string()],
+            clear_env: false,
+            cwd: None,
+            user: None,
+            tty: false,
+            rows: 24,
+            cols: 80,
+            rlimits: Vec::new(),
+            traceparent: None,
+            persistent: None,
+            window: Some(WINDOW),
+            compression: None,
+        };
+
+        let session = ExecSession::spawn(5, &req, tx).expect("spawn pipe session");
+
+        // Without credit, output stops after the window plus one read.
+        let mut received = 0;
+        while let Ok(Some((_, output))) = timeout(Duration::from_millis(200), rx.recv()).await {
+            match output {
+                SessionOutput::Stdout(data) => received += data.len(),
+                _ => panic!("session should be paused, not exited"),
+            }
+        }
+        assert!(received >= WINDOW as usize);
+        assert!(received < WINDOW as usize + 8192, "received {received}");
+
+        // Returning credit as output is consumed lets the rest through.
+        let exited = timeout(Duration::from_secs(15), async {
+            session.window().grant(received as u32);
+            loop {
+                match rx.recv().await {
+                    Some((_, SessionOutput::Stdout(data))) => {
+                        received += data.len();
+                        session.window().grant(data.len() as u32);
+                    }
+                    Some((_, SessionOutput::Exited(exited))) => break exited,
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for exit");
+        assert_eq!(exited.code, 0);
+        assert_eq!(received, TOTAL);
+    }
+
+    #[tokio::test]
+    async fn test_exit_reports_signal_and_usage() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let req = ExecRequest {
+            cmd: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), "kill -KILL $$".to_string()],
+            env: vec!["PATH=/usr/local/bin:/usr/bin:/bin".to_string()],
+            clear_env: false,
+            cwd: None,
+            user: None,
+            tty: false,
+            rows: 24,
+            cols: 80,
+            rlimits: Vec::new(),
+            traceparent: None,
+            persistent: None,
+            window: None,
+            compression: None,
+        };
+
+        let _session = ExecSession::spawn(3, &req, tx).expect("spawn pipe session");
+        let exited = timeout(Duration::from_secs(15), async {
+            loop {
+                if let Some((_, SessionOutput::Exited(exited))) = rx.recv().await {
+                    break exited;
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for exit");
+
+        assert_eq!(exited.code, -1);
+        assert_eq!(exited.signal, Some(libc::SIGKILL));
+        assert!(!exited.core_dumped);
+        let usage = exited.usage.expect("rusage from wait4");
+        assert!(usage.max_rss_kib > 0);
+        assert!(usage.wall_time_us > 0);
+    }
+
+    #[tokio::test]
+    async fn test_clear_env_drops_inherited_environment() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let req = ExecRequest {
+            cmd: "/usr/bin/env".to_string(),
+            args: Vec::new(),
+            env: vec!["ONLY_VAR=1".to_string()],
+            clear_env: true,
+            cwd: None,
+            user: None,
+            tty: false,
+            rows: 24,
+            cols: 80,
+            rlimits: Vec::new(),
+            traceparent: None,
+            persistent: None,
+            window: None,
+            compression: None,
+        };
+
+        let _session = ExecSession::spawn(4, &req, tx).expect("spawn pipe session");
+        let mut stdout = Vec::new();
+        timeout(Duration::from_secs(15), async {
+            loop {
+                match rx.recv().await {
+                    Some((_, SessionOutput::Stdout(data))) => stdout.extend_from_slice(&data),
+                    Some((_, SessionOutput::Exited(_))) | None => break,
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for exit");
+
+        let stdout = String::from_utf8_lossy(&stdout);
+        assert!(stdout.lines().any(|line| line == "ONLY_VAR=1"));
+        assert!(
+            !stdout.lines().any(|line| line.starts_with("PATH=")),
+            "inherited PATH leaked into a cleared environment: {stdout:?}"
+        );
+    }
+
     #[test]
     fn test_resolve_user_spec_for_current_uid_gid() {
         let uid = unsafe { libc::getuid() };
@@ -998,12 +1255,17 @@ mod tests {
             cmd: "/bin/true".to_string(),
             args: Vec::new(),
             env: Vec::new(),
+            clear_env: false,
             cwd: None,
             user: Some("1:1".to_string()),
             tty: false,
             rows: 24,
             cols: 80,
             rlimits: Vec::new(),
+            traceparent: None,
+            persistent: None,
+            window: None,
+            compression: None,
         };
 
         let resolved = resolve_requested_user(&req).expect("resolve requested user");
@@ -1020,12 +1282,17 @@ mod tests {
             cmd: "/bin/true".to_string(),
             args: Vec::new(),
             env: Vec::new(),
+            clear_env: false,
             cwd: None,
             user: None,
             tty: false,
             rows: 24,
             cols: 80,
             rlimits: Vec::new(),
+            traceparent: None,
+            persistent: None,
+            window: None,
+            compression: None,
         };
         let user = ResolvedUser {
             uid: 1000,
@@ -1040,18 +1307,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_exec_env_exports_traceparent_unless_set() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let mut req = ExecRequest {
+            cmd: "/bin/true".to_string(),
+            args: Vec::new(),
+            env: vec!["PATH=/bin".to_string()],
+            clear_env: false,
+            cwd: None,
+            user: None,
+            tty: false,
+            rows: 24,
+            cols: 80,
+            rlimits: Vec::new(),
+            traceparent: Some(traceparent.to_string()),
+            persistent: None,
+            window: None,
+            compression: None,
+        };
+        assert_eq!(
+            exec_env(&req).collect::<Vec<_>>(),
+            [("PATH", "/bin"), ("TRACEPARENT", traceparent)]
+        );
+
+        req.env.push("TRACEPARENT=00-custom".to_string());
+        assert_eq!(
+            exec_env(&req).collect::<Vec<_>>(),
+            [("PATH", "/bin"), ("TRACEPARENT", "00-custom")]
+        );
+    }
+
     #[test]
     fn test_default_home_dir_respects_explicit_home_env() {
         let req = ExecRequest {
             cmd: "/bin/true".to_string(),
             args: Vec::new(),
             env: vec!["HOME=/tmp/custom".to_string()],
+            clear_env: false,
             cwd: None,
             user: None,
             tty: false,
             rows: 24,
             cols: 80,
             rlimits: Vec::new(),
+            traceparent: None,
+            persistent: None,
+            window: None,
+            compression: None,
         };
         let user = ResolvedUser {
             uid: 1000,
@@ -1070,12 +1373,17 @@ mod tests {
             cmd: "/definitely/not/a/real/binary".to_string(),
             args: Vec::new(),
             env: Vec::new(),
+            clear_env: false,
             cwd: None,
             user: None,
             tty: false,
             rows: 24,
             cols: 80,
             rlimits: Vec::new(),
+            traceparent: None,
+            persistent: None,
+            window: None,
+            compression: None,
         };
 
         let err = ExecSession::spawn(9, &req, tx).expect_err("spawn should fail");
@@ -0,0 +1,445 @@
+//! Guest resource statistics collected from `/proc` for `core.stats.request`.
+
+use std::{collections::HashMap, fs};
+
+use microsandbox_protocol::stats::{
+    GuestFilesystemStats, GuestMemoryStats, GuestProcessStats, GuestStats,
+};
+use nix::sys::statvfs::statvfs;
+
+use crate::error::{AgentdError, AgentdResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Filesystem types that are not backed by storage and are omitted from the report.
+const PSEUDO_FS_TYPES: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "proc",
+    "pstore",
+    "securityfs",
+    "sysfs",
+    "tracefs",
+];
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Collects guest statistics, remembering per-process CPU time between calls
+/// so CPU usage reflects the interval since the previous request.
+#[derive(Debug)]
+pub struct StatsSampler {
+    clock_ticks: f64,
+    page_size: u64,
+    prev_uptime: f64,
+    prev_ticks: HashMap<i32, (u64, u64)>,
+}
+
+/// Fields parsed from `/proc/[pid]/stat`.
+#[derive(Debug, PartialEq)]
+struct ProcStat {
+    name: String,
+    state: String,
+    ppid: i32,
+    cpu_ticks: u64,
+    threads: u32,
+    start_ticks: u64,
+    vsize: u64,
+    rss_pages: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl StatsSampler {
+    /// Creates a sampler with no previous CPU snapshot.
+    pub fn new() -> Self {
+        Self {
+            clock_ticks: sysconf(libc::_SC_CLK_TCK).unwrap_or(100) as f64,
+            page_size: sysconf(libc::_SC_PAGESIZE).unwrap_or(4096),
+            prev_uptime: 0.0,
+            prev_ticks: HashMap::new(),
+        }
+    }
+
+    /// Takes a snapshot of guest resource usage.
+    pub fn sample(&mut self, processes: bool) -> AgentdResult<GuestStats> {
+        let uptime_secs = parse_uptime(&fs::read_to_string("/proc/uptime")?)?;
+        let load_average = parse_loadavg(&fs::read_to_string("/proc/loadavg")?)?;
+        let memory = parse_meminfo(&fs::read_to_string("/proc/meminfo")?);
+        let filesystems = collect_filesystems(&fs::read_to_string("/proc/mounts")?);
+        let processes = if processes {
+            self.collect_processes(uptime_secs)
+        } else {
+            Vec::new()
+        };
+
+        Ok(GuestStats {
+            uptime_secs,
+            cpu_count: sysconf(libc::_SC_NPROCESSORS_ONLN).unwrap_or(1) as u32,
+            load_average,
+            memory,
+            filesystems,
+            processes,
+        })
+    }
+
+    /// Lists guest processes, without the rest of the snapshot.
+    ///
+    /// Shares the CPU baseline with [`sample`](Self::sample).
+    pub fn processes(&mut self) -> AgentdResult<Vec<GuestProcessStats>> {
+        let uptime_secs = parse_uptime(&fs::read_to_string("/proc/uptime")?)?;
+        Ok(self.collect_processes(uptime_secs))
+    }
+
+    fn collect_processes(&mut self, uptime_secs: f64) -> Vec<GuestProcessStats> {
+        let Ok(entries) = fs::read_dir("/proc") else {
+            return Vec::new();
+        };
+        let users = fs::read_to_string("/etc/passwd")
+            .map(|s| parse_passwd(&s))
+            .unwrap_or_default();
+
+        let elapsed = uptime_secs - self.prev_uptime;
+        let mut ticks = HashMap::new();
+        let mut processes = Vec::new();
+
+        for entry in entries.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|s| s.parse::<i32>().ok())
+            else {
+                continue;
+            };
+
+            // Processes can exit between listing and reading; skip them.
+            let Some(stat) = fs::read_to_string(format!("/proc/{pid}/stat"))
+                .ok()
+                .and_then(|s| parse_proc_stat(&s))
+            else {
+                continue;
+            };
+
+            let cmdline = fs::read(format!("/proc/{pid}/cmdline"))
+                .map(|raw| parse_cmdline(&raw))
+                .unwrap_or_default();
+            let uid = fs::read_to_string(format!("/proc/{pid}/status"))
+                .ok()
+                .and_then(|s| parse_status_uid(&s))
+                .unwrap_or(0);
+
+            let cpu_secs = stat.cpu_ticks as f64 / self.clock_ticks;
+            let cpu_percent = match self.prev_ticks.get(&pid) {
+                Some(&(prev, start)) if start == stat.start_ticks && elapsed > 0.0 => {
+                    stat.cpu_ticks.saturating_sub(prev) as f64 / self.clock_ticks / elapsed
+                }
+                _ => {
+                    let lifetime = uptime_secs - stat.start_ticks as f64 / self.clock_ticks;
+                    if lifetime > 0.0 {
+                        cpu_secs / lifetime
+                    } else {
+                        0.0
+                    }
+                }
+            } * 100.0;
+
+            ticks.insert(pid, (stat.cpu_ticks, stat.start_ticks));
+            processes.push(GuestProcessStats {
+                pid,
+                ppid: stat.ppid,
+                uid,
+                user: users.get(&uid).cloned().unwrap_or_default(),
+                name: stat.name,
+                cmdline,
+                state: stat.state,
+                threads: stat.threads,
+                rss_bytes: stat.rss_pages * self.page_size,
+                vsize_bytes: stat.vsize,
+                cpu_time_ms: (cpu_secs * 1000.0) as u64,
+                cpu_percent: cpu_percent as f32,
+                start_time_secs: stat.start_ticks as f64 / self.clock_ticks,
+            });
+        }
+
+        self.prev_uptime = uptime_secs;
+        self.prev_ticks = ticks;
+        processes.sort_by_key(|p| p.pid);
+        processes
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for StatsSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+fn sysconf(name: libc::c_int) -> Option<u64> {
+    // SAFETY: sysconf has no memory-safety preconditions.
+    let value = unsafe { libc::sysconf(name) };
+    (value > 0).then_some(value as u64)
+}
+
+fn parse_uptime(contents: &str) -> AgentdResult<f64> {
+    contents
+        .split_whitespace()
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| AgentdError::ExecSession(format!("invalid /proc/uptime: {contents:?}")))
+}
+
+fn parse_loadavg(contents: &str) -> AgentdResult<[f64; 3]> {
+    let mut fields = contents.split_whitespace().map(|s| s.parse::<f64>().ok());
+    match (fields.next(), fields.next(), fields.next()) {
+        (Some(Some(one)), Some(Some(five)), Some(Some(fifteen))) => Ok([one, five, fifteen]),
+        _ => Err(AgentdError::ExecSession(format!(
+            "invalid /proc/loadavg: {contents:?}"
+        ))),
+    }
+}
+
+fn parse_meminfo(contents: &str) -> GuestMemoryStats {
+    let mut memory = GuestMemoryStats::default();
+    for line in contents.lines() {
+        let Some((key, rest)) = line.split_once(':') else {
+            continue;
+        };
+        let Some(kib) = rest
+            .split_whitespace()
+            .next()
+            .and_then(|s| s.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let bytes = kib * 1024;
+        match key {
+            "MemTotal" => memory.total_bytes = bytes,
+            "MemFree" => memory.free_bytes = bytes,
+            "MemAvailable" => memory.available_bytes = bytes,
+            "Cached" => memory.cached_bytes = bytes,
+            "Buffers" => memory.buffers_bytes = bytes,
+            "SwapTotal" => memory.swap_total_bytes = bytes,
+            "SwapFree" => memory.swap_free_bytes = bytes,
+            _ => {}
+        }
+    }
+    memory
+}
+
+fn collect_filesystems(mounts: &str) -> Vec<GuestFilesystemStats> {
+    let mut filesystems = Vec::new();
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(source), Some(mount_point), Some(fs_type)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if PSEUDO_FS_TYPES.contains(&fs_type) {
+            continue;
+        }
+
+        let mount_point = unescape_mount_field(mount_point);
+        let Ok(vfs) = statvfs(mount_point.as_str()) else {
+            continue;
+        };
+        let fragment = vfs.fragment_size();
+        let total_bytes = vfs.blocks() * fragment;
+        if total_bytes == 0 {
+            continue;
+        }
+
+        filesystems.push(GuestFilesystemStats {
+            mount_point,
+            source: unescape_mount_field(source),
+            fs_type: fs_type.to_string(),
+            total_bytes,
+            used_bytes: total_bytes.saturating_sub(vfs.blocks_free() * fragment),
+            available_bytes: vfs.blocks_available() * fragment,
+            total_inodes: vfs.files(),
+            free_inodes: vfs.files_free(),
+        });
+    }
+    filesystems
+}
+
+/// Decodes the octal escapes (`\040` for space, etc.) used in `/proc/mounts`.
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && let Some(c) = bytes
+                .get(i + 1..i + 4)
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok())
+        {
+            out.push(c);
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parses `/proc/[pid]/stat`. The command name is enclosed in parentheses and
+/// may itself contain spaces or parentheses, so fields are split after the
+/// last `)`.
+fn parse_proc_stat(contents: &str) -> Option<ProcStat> {
+    let open = contents.find('(')?;
+    let close = contents.rfind(')')?;
+    let name = contents.get(open + 1..close)?.to_string();
+    let fields: Vec<&str> = contents.get(close + 2..)?.split_whitespace().collect();
+    let field = |i: usize| fields.get(i).and_then(|s| s.parse::<u64>().ok());
+
+    Some(ProcStat {
+        name,
+        state: fields.first()?.to_string(),
+        ppid: fields.get(1)?.parse().ok()?,
+        cpu_ticks: field(11)? + field(12)?,
+        threads: field(17)? as u32,
+        start_ticks: field(19)?,
+        vsize: field(20)?,
+        rss_pages: field(21)?,
+    })
+}
+
+fn parse_cmdline(raw: &[u8]) -> String {
+    raw.split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_status_uid(contents: &str) -> Option<u32> {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok())
+}
+
+/// Map user IDs to names from `/etc/passwd` contents. The first entry for a
+/// UID wins, as with `getpwuid`.
+fn parse_passwd(contents: &str) -> HashMap<u32, String> {
+    let mut users = HashMap::new();
+    for line in contents.lines() {
+        let mut fields = line.split(':');
+        let (Some(name), Some(_), Some(uid)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        if let Ok(uid) = uid.parse() {
+            users.entry(uid).or_insert_with(|| name.to_string());
+        }
+    }
+    users
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_stat_with_parens_in_name() {
+        let stat = "42 (my (weird) proc) S 1 42 42 0 -1 4194560 100 0 0 0 250 50 0 0 20 0 3 0 1200 10485760 512 18446744073709551615 0 0 0 0 0 0 0 0 0 0 0 0 17 0 0 0 0 0 0";
+        let parsed = parse_proc_stat(stat).unwrap();
+        assert_eq!(
+            parsed,
+            ProcStat {
+                name: "my (weird) proc".into(),
+                state: "S".into(),
+                ppid: 1,
+                cpu_ticks: 300,
+                threads: 3,
+                start_ticks: 1200,
+                vsize: 10_485_760,
+                rss_pages: 512,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let meminfo = "MemTotal:        2035084 kB\nMemFree:          1500000 kB\nMemAvailable:     1800000 kB\nBuffers:            1024 kB\nCached:           200000 kB\nSwapCached:            0 kB\nSwapTotal:             0 kB\nSwapFree:              0 kB\n";
+        let memory = parse_meminfo(meminfo);
+        assert_eq!(memory.total_bytes, 2_035_084 * 1024);
+        assert_eq!(memory.available_bytes, 1_800_000 * 1024);
+        assert_eq!(memory.cached_bytes, 200_000 * 1024);
+        assert_eq!(memory.buffers_bytes, 1024 * 1024);
+        assert_eq!(memory.swap_total_bytes, 0);
+    }
+
+    #[test]
+    fn test_parse_loadavg_and_uptime() {
+        assert_eq!(
+            parse_loadavg("0.52 0.31 0.12 2/87 1234\n").unwrap(),
+            [0.52, 0.31, 0.12]
+        );
+        assert!(parse_loadavg("garbage").is_err());
+        assert_eq!(parse_uptime("123.45 456.78\n").unwrap(), 123.45);
+    }
+
+    #[test]
+    fn test_parse_cmdline_and_uid() {
+        assert_eq!(
+            parse_cmdline(b"python3\0-m\0http.server\0"),
+            "python3 -m http.server"
+        );
+        assert_eq!(parse_cmdline(b""), "");
+        assert_eq!(
+            parse_status_uid("Name:\tsh\nUid:\t1000\t1000\t1000\t1000\n"),
+            Some(1000)
+        );
+    }
+
+    #[test]
+    fn test_parse_passwd() {
+        let users = parse_passwd(
+            "root:x:0:0:root:/root:/bin/sh\n\
+             # comment\n\
+             nobody:x:65534:65534:nobody:/:/sbin/nologin\n\
+             toor:x:0:0::/root:/bin/sh\n",
+        );
+        assert_eq!(users.get(&0).map(String::as_str), Some("root"));
+        assert_eq!(users.get(&65534).map(String::as_str), Some("nobody"));
+        assert_eq!(users.len(), 2);
+    }
+
+    #[test]
+    fn test_unescape_mount_field() {
+        assert_eq!(unescape_mount_field("/mnt/my\\040data"), "/mnt/my data");
+        assert_eq!(unescape_mount_field("/plain"), "/plain");
+        assert_eq!(unescape_mount_field("/trailing\\04"), "/trailing\\04");
+    }
+}
@@ -0,0 +1,584 @@
+//! Guest-side tunnel handlers.
+//!
+//! Handles `core.tunnel.*` messages by connecting to or listening on guest
+//! sockets and pumping bytes between them and the agent channel. Each
+//! connection runs in its own task, which sends its frames through the
+//! session channel like exec and file-read streams do.
+
+use std::{
+    collections::HashMap,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use microsandbox_protocol::{
+    codec::encode_to_buf,
+    flow::Credit,
+    message::{Message, MessageType},
+    tunnel::{
+        TUNNEL_CHUNK_SIZE, TunnelAccept, TunnelBind, TunnelClose, TunnelData, TunnelEndpoint,
+        TunnelListen, TunnelOpen,
+    },
+};
+use serde::Serialize;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UnixListener, UnixStream},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+use crate::{flow::FlowWindow, session::SessionOutput};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A connected guest socket.
+trait Socket: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
+
+/// Accepted connections waiting for the host to bind them.
+type Pending = Arc<Mutex<HashMap<u32, Box<dyn Socket>>>>;
+
+/// Open tunnels and listeners, keyed by correlation ID.
+pub struct Tunnels {
+    conns: HashMap<u32, TunnelConn>,
+    listeners: HashMap<u32, TunnelListener>,
+    tx: mpsc::UnboundedSender<(u32, SessionOutput)>,
+}
+
+/// A tunneled connection.
+struct TunnelConn {
+    /// Credit for data sent to the host.
+    window: FlowWindow,
+
+    /// Data from the host, written to the socket. Empty data shuts down the
+    /// socket's write half.
+    to_socket: mpsc::UnboundedSender<Vec<u8>>,
+
+    task: JoinHandle<()>,
+}
+
+/// A listening guest socket.
+enum Listener {
+    Tcp(TcpListener),
+    Unix {
+        listener: UnixListener,
+
+        /// Held so the socket file goes away with the listener.
+        _file: SocketFile,
+    },
+}
+
+/// Removes a Unix socket file when dropped.
+struct SocketFile(PathBuf);
+
+/// A guest listener reporting connections to the host.
+struct TunnelListener {
+    pending: Pending,
+    task: JoinHandle<()>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Tunnels {
+    /// Create an empty set whose tasks send frames through `tx`.
+    pub fn new(tx: mpsc::UnboundedSender<(u32, SessionOutput)>) -> Self {
+        Self {
+            conns: HashMap::new(),
+            listeners: HashMap::new(),
+            tx,
+        }
+    }
+
+    /// Number of open connections.
+    pub fn len(&self) -> usize {
+        self.conns.len()
+    }
+
+    /// Whether no connections are open.
+    pub fn is_empty(&self) -> bool {
+        self.conns.is_empty()
+    }
+
+    /// Connect to a guest endpoint. The connection task replies
+    /// `core.tunnel.opened` once connected, or closes the tunnel.
+    pub fn open(&mut self, id: u32, req: TunnelOpen) {
+        let tx = self.tx.clone();
+        self.start(id, req.window, move |window, from_host| async move {
+            let stream: Box<dyn Socket> = match connect(&req.endpoint).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    send_close(id, Some(e), &tx);
+                    return;
+                }
+            };
+            send_frame(id, MessageType::TunnelOpened, &(), &tx);
+            pump(id, stream, window, req.window.is_some(), from_host, &tx).await;
+        });
+    }
+
+    /// Listen on a guest endpoint and report accepted connections to the
+    /// host with `core.tunnel.accept`.
+    pub async fn listen(&mut self, id: u32, req: TunnelListen) {
+        let listener = match bind(&req.endpoint).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                send_close(id, Some(e), &self.tx);
+                return;
+            }
+        };
+        send_frame(id, MessageType::TunnelOpened, &(), &self.tx);
+
+        let pending = Pending::default();
+        let task = tokio::spawn(accept_loop(id, listener, pending.clone(), self.tx.clone()));
+        self.listeners.insert(id, TunnelListener { pending, task });
+    }
+
+    /// Start tunneling a connection accepted by one of our listeners.
+    pub fn bind(&mut self, id: u32, req: TunnelBind) {
+        let stream = self.listeners.get(&req.listener).and_then(|listener| {
+            listener
+                .pending
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .remove(&req.conn)
+        });
+        let Some(stream) = stream else {
+            send_close(
+                id,
+                Some(format!(
+                    "no pending connection {} on listener {}",
+                    req.conn, req.listener
+                )),
+                &self.tx,
+            );
+            return;
+        };
+
+        let tx = self.tx.clone();
+        self.start(id, req.window, move |window, from_host| async move {
+            send_frame(id, MessageType::TunnelOpened, &(), &tx);
+            pump(id, stream, window, req.window.is_some(), from_host, &tx).await;
+        });
+    }
+
+    /// Queue data from the host for the tunnel's socket.
+    pub fn write(&mut self, id: u32, data: Vec<u8>) {
+        if let Some(conn) = self.conns.get(&id) {
+            let _ = conn.to_socket.send(data);
+        }
+    }
+
+    /// Return credit from the host to the tunnel's reader.
+    pub fn grant(&self, id: u32, bytes: u32) {
+        if let Some(conn) = self.conns.get(&id) {
+            conn.window.grant(bytes);
+        }
+    }
+
+    /// Forget a tunnel whose task has finished.
+    pub fn remove(&mut self, id: u32) {
+        self.conns.remove(&id);
+        self.listeners.remove(&id);
+    }
+
+    /// Abort a tunnel or listener without reporting back to the host.
+    pub fn close(&mut self, id: u32) {
+        if let Some(conn) = self.conns.remove(&id) {
+            conn.task.abort();
+        }
+        if let Some(listener) = self.listeners.remove(&id) {
+            listener.task.abort();
+        }
+    }
+
+    /// Abort all tunnels and listeners.
+    pub fn close_all(&mut self) {
+        for (_, conn) in self.conns.drain() {
+            conn.task.abort();
+        }
+        for (_, listener) in self.listeners.drain() {
+            listener.task.abort();
+        }
+    }
+
+    fn start<F, Fut>(&mut self, id: u32, window: Option<u32>, run: F)
+    where
+        F: FnOnce(FlowWindow, mpsc::UnboundedReceiver<Vec<u8>>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let window_handle = FlowWindow::new(window);
+        let (to_socket, from_host) = mpsc::unbounded_channel();
+        let task = tokio::spawn(run(window_handle.clone(), from_host));
+        self.close(id);
+        self.conns.insert(
+            id,
+            TunnelConn {
+                window: window_handle,
+                to_socket,
+                task,
+            },
+        );
+    }
+}
+
+impl Listener {
+    async fn accept(&self) -> std::io::Result<Box<dyn Socket>> {
+        match self {
+            Self::Tcp(listener) => Ok(Box::new(listener.accept().await?.0)),
+            Self::Unix { listener, .. } => Ok(Box::new(listener.accept().await?.0)),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static> Socket for T {}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Connect to a guest endpoint.
+async fn connect(endpoint: &TunnelEndpoint) -> Result<Box<dyn Socket>, String> {
+    match endpoint {
+        TunnelEndpoint::Tcp { port } => TcpStream::connect(("127.0.0.1", *port))
+            .await
+            .map(|stream| Box::new(stream) as Box<dyn Socket>)
+            .map_err(|e| format!("connect to port {port}: {e}")),
+        TunnelEndpoint::Unix { path } => UnixStream::connect(path)
+            .await
+            .map(|stream| Box::new(stream) as Box<dyn Socket>)
+            .map_err(|e| format!("connect to {path}: {e}")),
+    }
+}
+
+/// Listen on a guest endpoint.
+///
+/// A Unix socket is created (replacing a stale socket left at the path) so
+/// that any guest user can connect, and is removed when the listener closes.
+async fn bind(endpoint: &TunnelEndpoint) -> Result<Listener, String> {
+    match endpoint {
+        TunnelEndpoint::Tcp { port } => TcpListener::bind(("127.0.0.1", *port))
+            .await
+            .map(Listener::Tcp)
+            .map_err(|e| format!("listen on port {port}: {e}")),
+        TunnelEndpoint::Unix { path } => {
+            let err = |e: std::io::Error| format!("listen on {path}: {e}");
+            if let Some(parent) = Path::new(path).parent() {
+                tokio::fs::create_dir_all(parent).await.map_err(err)?;
+            }
+            if let Ok(meta) = tokio::fs::symlink_metadata(path).await
+                && meta.file_type().is_socket()
+            {
+                tokio::fs::remove_file(path).await.map_err(err)?;
+            }
+            let listener = UnixListener::bind(path).map_err(err)?;
+            let file = SocketFile(path.into());
+            tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o666))
+                .await
+                .map_err(err)?;
+            Ok(Listener::Unix {
+                listener,
+                _file: file,
+            })
+        }
+    }
+}
+
+/// Accept connections, park them until the host binds them, and tell the
+/// host about each one.
+async fn accept_loop(
+    id: u32,
+    listener: Listener,
+    pending: Pending,
+    tx: mpsc::UnboundedSender<(u32, SessionOutput)>,
+) {
+    let mut next_conn = 0u32;
+    loop {
+        match listener.accept().await {
+            Ok(stream) => {
+                let conn = next_conn;
+                next_conn = next_conn.wrapping_add(1);
+                pending
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .insert(conn, stream);
+                if !send_frame(id, MessageType::TunnelAccept, &TunnelAccept { conn }, &tx) {
+                    return;
+                }
+            }
+            Err(e) => {
+                send_close(id, Some(format!("accept: {e}")), &tx);
+                return;
+            }
+        }
+    }
+}
+
+/// Copy bytes both ways between the socket and the host until both
+/// directions have ended or either fails, then close the tunnel.
+///
+/// Reads wait for credit in `window`. When `credit` is set, each chunk
+/// written to the socket is returned to the host as credit.
+async fn pump(
+    id: u32,
+    stream: Box<dyn Socket>,
+    window: FlowWindow,
+    credit: bool,
+    mut from_host: mpsc::UnboundedReceiver<Vec<u8>>,
+    tx: &mpsc::UnboundedSender<(u32, SessionOutput)>,
+) {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    let to_host = async {
+        let mut chunk = vec![0u8; TUNNEL_CHUNK_SIZE];
+        loop {
+            let n = reader
+                .read(&mut chunk)
+                .await
+                .map_err(|e| format!("read: {e}"))?;
+            if n > 0 {
+                window.acquire(n).await;
+            }
+            let data = TunnelData {
+                data: chunk[..n].to_vec(),
+            };
+            if !send_frame(id, MessageType::TunnelData, &data, tx) {
+                return Err("agent channel closed".to_string());
+            }
+            if n == 0 {
+                return Ok(());
+            }
+        }
+    };
+
+    let to_socket = async {
+        while let Some(data) = from_host.recv().await {
+            if data.is_empty() {
+                break;
+            }
+            writer
+                .write_all(&data)
+                .await
+                .map_err(|e| format!("write: {e}"))?;
+            if credit {
+                let credit = Credit {
+                    bytes: data.len() as u32,
+                };
+                send_frame(id, MessageType::Credit, &credit, tx);
+            }
+        }
+        writer
+            .shutdown()
+            .await
+            .map_err(|e| format!("shutdown: {e}"))
+    };
+
+    let error = tokio::try_join!(to_host, to_socket).err();
+    send_close(id, error, tx);
+}
+
+/// Send the terminal `core.tunnel.close`.
+fn send_close(id: u32, error: Option<String>, tx: &mpsc::UnboundedSender<(u32, SessionOutput)>) {
+    send_frame(id, MessageType::TunnelClose, &TunnelClose { error }, tx);
+}
+
+/// Encode a message and send it as a raw frame. Returns `false` once the
+/// agent loop is gone.
+fn send_frame<T: Serialize>(
+    id: u32,
+    t: MessageType,
+    payload: &T,
+    tx: &mpsc::UnboundedSender<(u32, SessionOutput)>,
+) -> bool {
+    let msg = match Message::with_payload(t, id, payload) {
+        Ok(msg) => msg,
+        Err(e) => {
+            eprintln!("failed to encode tunnel message for {id}: {e}");
+            return true;
+        }
+    };
+    let mut buf = Vec::new();
+    if let Err(e) = encode_to_buf(&msg, &mut buf) {
+        eprintln!("failed to encode tunnel frame for {id}: {e}");
+        return true;
+    }
+    tx.send((id, SessionOutput::Raw(buf))).is_ok()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use microsandbox_protocol::codec::try_decode_from_buf;
+    use tokio::time::timeout;
+
+    use super::*;
+
+    async fn next_message(rx: &mut mpsc::UnboundedReceiver<(u32, SessionOutput)>) -> Message {
+        let (_, output) = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timed out waiting for a tunnel frame")
+            .expect("tunnel channel closed");
+        let SessionOutput::Raw(mut frame) = output else {
+            panic!("expected a raw frame");
+        };
+        try_decode_from_buf(&mut frame).unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_open_tunnels_to_guest_port() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = server.accept().await.unwrap();
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await.unwrap();
+            buf.make_ascii_uppercase();
+            stream.write_all(&buf).await.unwrap();
+        });
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut tunnels = Tunnels::new(tx);
+        tunnels.open(
+            1,
+            TunnelOpen {
+                endpoint: TunnelEndpoint::Tcp { port },
+                window: Some(1024),
+            },
+        );
+        assert_eq!(next_message(&mut rx).await.t, MessageType::TunnelOpened);
+
+        tunnels.write(1, b"hello".to_vec());
+        let credit = next_message(&mut rx).await;
+        assert_eq!(credit.t, MessageType::Credit);
+        assert_eq!(credit.payload::<Credit>().unwrap().bytes, 5);
+
+        tunnels.write(1, Vec::new());
+        let mut received = Vec::new();
+        loop {
+            let msg = next_message(&mut rx).await;
+            match msg.t {
+                MessageType::TunnelData => {
+                    received.extend(msg.payload::<TunnelData>().unwrap().data);
+                }
+                MessageType::TunnelClose => {
+                    assert_eq!(msg.payload::<TunnelClose>().unwrap().error, None);
+                    break;
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert_eq!(received, b"HELLO");
+    }
+
+    #[tokio::test]
+    async fn test_listen_reports_and_binds_connections() {
+        let port = {
+            let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            probe.local_addr().unwrap().port()
+        };
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut tunnels = Tunnels::new(tx);
+        tunnels
+            .listen(
+                1,
+                TunnelListen {
+                    endpoint: TunnelEndpoint::Tcp { port },
+                },
+            )
+            .await;
+        assert_eq!(next_message(&mut rx).await.t, MessageType::TunnelOpened);
+
+        let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let accept = next_message(&mut rx).await;
+        assert_eq!(accept.t, MessageType::TunnelAccept);
+        assert_eq!(accept.id, 1);
+        let conn = accept.payload::<TunnelAccept>().unwrap().conn;
+
+        tunnels.bind(
+            2,
+            TunnelBind {
+                listener: 1,
+                conn,
+                window: None,
+            },
+        );
+        assert_eq!(next_message(&mut rx).await.t, MessageType::TunnelOpened);
+
+        client.write_all(b"ping").await.unwrap();
+        let data = next_message(&mut rx).await;
+        assert_eq!(data.id, 2);
+        assert_eq!(data.payload::<TunnelData>().unwrap().data, b"ping");
+
+        tunnels.write(2, b"pong".to_vec());
+        let mut buf = [0u8; 4];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+
+        // The same connection cannot be bound twice.
+        tunnels.bind(
+            3,
+            TunnelBind {
+                listener: 1,
+                conn,
+                window: None,
+            },
+        );
+        let close = next_message(&mut rx).await;
+        assert_eq!(close.t, MessageType::TunnelClose);
+        assert_eq!(close.id, 3);
+        assert!(close.payload::<TunnelClose>().unwrap().error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_unix_listener_replaces_stale_socket_and_cleans_up() {
+        let dir = std::env::temp_dir().join(format!("agentd-tunnel-{}", std::process::id()));
+        let path = dir.join("nested/agent.sock");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists(), "stale socket file should be left behind");
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut tunnels = Tunnels::new(tx);
+        tunnels
+            .listen(
+                1,
+                TunnelListen {
+                    endpoint: TunnelEndpoint::Unix {
+                        path: path.to_string_lossy().into_owned(),
+                    },
+                },
+            )
+            .await;
+        assert_eq!(next_message(&mut rx).await.t, MessageType::TunnelOpened);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o666);
+
+        let _client = UnixStream::connect(&path).await.unwrap();
+        assert_eq!(next_message(&mut rx).await.t, MessageType::TunnelAccept);
+
+        tunnels.close(1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!path.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
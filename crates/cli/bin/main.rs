@@ -1,10 +1,13 @@
 //! Entry point for the `msb` CLI binary.
 
 use clap::{CommandFactory, Parser, Subcommand};
+#[cfg(feature = "net")]
+use microsandbox_cli::commands::network;
 use microsandbox_cli::{
     commands::{
-        create, exec, image, inspect, install, list, metrics, ps, pull, registry, remove, run,
-        self_cmd, start, stop, uninstall, volume,
+        create, down, events, exec, export, image, import, inspect, install, list, metrics,
+        port_forward, ps, ps_guest, pull, registry, remove, run, self_cmd, serve, start, stop,
+        supervise, top, uninstall, up, volume,
     },
     log_args::{self, LogArgs},
     sandbox_cmd::{self, SandboxArgs},
@@ -41,6 +44,10 @@ enum Commands {
     #[command(hide = true)]
     Sandbox(Box<SandboxArgs>),
 
+    /// Restart a sandbox according to its restart policy (internal).
+    #[command(hide = true)]
+    Supervise(supervise::SuperviseArgs),
+
     /// Create a sandbox from an image and run a command in it.
     Run(run::RunArgs),
 
@@ -53,6 +60,12 @@ enum Commands {
     /// Stop a running sandbox.
     Stop(stop::StopArgs),
 
+    /// Create the sandboxes described by a compose file.
+    Up(up::UpArgs),
+
+    /// Stop and remove the sandboxes of a compose project.
+    Down(down::DownArgs),
+
     /// List all sandboxes.
     #[command(visible_alias = "ls")]
     List(list::ListArgs),
@@ -64,6 +77,15 @@ enum Commands {
     /// Show live metrics for a running sandbox.
     Metrics(metrics::MetricsArgs),
 
+    /// Show processes and resource usage inside a running sandbox.
+    Top(top::TopArgs),
+
+    /// List or signal processes inside a running sandbox.
+    PsGuest(ps_guest::PsGuestArgs),
+
+    /// Show lifecycle events for all sandboxes.
+    Events(events::EventsArgs),
+
     /// Remove one or more sandboxes.
     #[command(visible_alias = "rm")]
     Remove(remove::RemoveArgs),
@@ -71,6 +93,9 @@ enum Commands {
     /// Run a command in a running sandbox.
     Exec(exec::ExecArgs),
 
+    /// Forward TCP ports between the host and a running sandbox.
+    PortForward(port_forward::PortForwardArgs),
+
     /// Manage OCI images.
     Image(image::ImageArgs),
 
@@ -91,10 +116,24 @@ enum Commands {
     /// Show detailed sandbox configuration and status.
     Inspect(inspect::InspectArgs),
 
+    /// Export a stopped sandbox to a portable archive.
+    Export(export::ExportArgs),
+
+    /// Import a sandbox from an exported archive.
+    Import(import::ImportArgs),
+
     /// Manage named volumes.
     #[command(visible_alias = "vol")]
     Volume(volume::VolumeArgs),
 
+    /// Manage private networks shared between sandboxes.
+    #[cfg(feature = "net")]
+    #[command(visible_alias = "net")]
+    Network(network::NetworkArgs),
+
+    /// Serve this host's sandboxes to remote clients.
+    Serve(serve::ServeArgs),
+
     /// Install a sandbox as a system command.
     Install(install::InstallArgs),
 
@@ -141,12 +180,22 @@ fn main() {
         // are captured in host.log for post-mortem debugging.
         Commands::Sandbox(args) => {
             let sandbox_level = log_level.or(Some(microsandbox_runtime::logging::LogLevel::Info));
-            log_args::init_tracing(sandbox_level);
+            log_args::init_tracing(sandbox_level, &args.telemetry());
             sandbox_cmd::run(*args, log_level)
         }
         command => {
-            log_args::init_tracing(log_level);
-            run_async_command(command, log_level)
+            // Supervisors run unattended; keep their restart decisions in
+            // supervisor.log by default.
+            let log_level = match &command {
+                Commands::Supervise(_) => {
+                    log_level.or(Some(microsandbox_runtime::logging::LogLevel::Info))
+                }
+                _ => log_level,
+            };
+            log_args::init_tracing(log_level, &microsandbox::config::config().telemetry);
+            let result = run_async_command(command, log_level);
+            microsandbox::telemetry::shutdown();
+            result
         }
     };
 
@@ -179,18 +228,30 @@ fn run_async_command(
             Commands::Create(args) => create::run(args).await.map_err(Into::into),
             Commands::Start(args) => start::run(args).await.map_err(Into::into),
             Commands::Stop(args) => stop::run(args).await.map_err(Into::into),
+            Commands::Up(args) => up::run(args).await.map_err(Into::into),
+            Commands::Down(args) => down::run(args).await.map_err(Into::into),
+            Commands::Supervise(args) => supervise::run(args).await.map_err(Into::into),
             Commands::List(args) => list::run(args).await.map_err(Into::into),
             Commands::Status(args) => ps::run(args).await.map_err(Into::into),
             Commands::Metrics(args) => metrics::run(args).await.map_err(Into::into),
+            Commands::Top(args) => top::run(args).await.map_err(Into::into),
+            Commands::PsGuest(args) => ps_guest::run(args).await.map_err(Into::into),
+            Commands::Events(args) => events::run(args).await.map_err(Into::into),
             Commands::Remove(args) => remove::run(args).await.map_err(Into::into),
             Commands::Exec(args) => exec::run(args).await.map_err(Into::into),
+            Commands::PortForward(args) => port_forward::run(args).await.map_err(Into::into),
             Commands::Image(args) => image::run(args).await.map_err(Into::into),
             Commands::Pull(args) => image::run_pull(args).await.map_err(Into::into),
             Commands::Registry(args) => registry::run(args).await.map_err(Into::into),
             Commands::Images(args) => image::run_list(args).await.map_err(Into::into),
             Commands::Rmi(args) => image::run_remove(args).await.map_err(Into::into),
             Commands::Inspect(args) => inspect::run(args).await.map_err(Into::into),
+            Commands::Export(args) => export::run(args).await.map_err(Into::into),
+            Commands::Import(args) => import::run(args).await.map_err(Into::into),
             Commands::Volume(args) => volume::run(args).await.map_err(Into::into),
+            #[cfg(feature = "net")]
+            Commands::Network(args) => network::run(args).await.map_err(Into::into),
+            Commands::Serve(args) => serve::run(args).await.map_err(Into::into),
             Commands::Install(args) => install::run(args).await.map_err(Into::into),
             Commands::Uninstall(args) => uninstall::run(args).await.map_err(Into::into),
             Commands::Self_(args) => self_cmd::run(args).await.map_err(Into::into),
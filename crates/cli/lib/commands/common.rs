@@ -3,7 +3,7 @@
 use std::path::PathBuf;
 
 use clap::Args;
-use microsandbox::sandbox::SandboxBuilder;
+use microsandbox::sandbox::{HealthCheck, HealthProbe, SandboxBuilder};
 
 use crate::ui;
 
@@ -42,6 +42,16 @@ pub struct SandboxOpts {
     #[arg(short, long)]
     pub env: Vec<String>,
 
+    /// Set an environment variable whose value is kept out of the sandbox
+    /// database and `msb inspect` (KEY=value, or KEY to read it from the
+    /// host environment).
+    #[arg(long, value_name = "KEY[=VALUE]")]
+    pub sensitive_env: Vec<String>,
+
+    /// Set a label on the sandbox (KEY=value).
+    #[arg(long)]
+    pub label: Vec<String>,
+
     /// Replace an existing sandbox with the same name.
     #[arg(long)]
     pub replace: bool,
@@ -59,6 +69,19 @@ pub struct SandboxOpts {
     #[arg(long)]
     pub script: Vec<String>,
 
+    /// Make a host Unix socket available inside the sandbox (HOST_PATH:GUEST_PATH).
+    #[arg(long, value_name = "HOST_PATH:GUEST_PATH")]
+    pub socket: Vec<String>,
+
+    /// Make a Unix socket served inside the sandbox available on the host
+    /// (GUEST_PATH:HOST_PATH).
+    #[arg(long, value_name = "GUEST_PATH:HOST_PATH")]
+    pub expose_socket: Vec<String>,
+
+    /// Forward the host's ssh-agent (SSH_AUTH_SOCK) into the sandbox.
+    #[arg(long)]
+    pub ssh_agent: bool,
+
     // --- Image/Runtime overrides ---
     /// Override the image's default entrypoint command.
     #[arg(long)]
@@ -76,6 +99,14 @@ pub struct SandboxOpts {
     #[arg(long)]
     pub pull: Option<String>,
 
+    /// Image platform to select from a multi-platform image (e.g. linux/amd64).
+    #[arg(long, value_name = "OS/ARCH")]
+    pub platform: Option<String>,
+
+    /// Materialize layers on first access; estargz layers boot before their download finishes.
+    #[arg(long)]
+    pub lazy: bool,
+
     /// Log verbosity for the sandbox runtime (error, warn, info, debug, trace).
     #[arg(long)]
     pub log_level: Option<String>,
@@ -89,6 +120,49 @@ pub struct SandboxOpts {
     #[arg(long)]
     pub idle_timeout: Option<String>,
 
+    /// Act when a resource crosses a threshold: METRIC=THRESHOLD[/WINDOW][:ACTION]
+    /// (e.g. cpu=90/30s:drain, memory=2G:kill, net-egress=1G:signal=15).
+    #[arg(long = "limit", value_name = "SPEC")]
+    pub limits: Vec<String>,
+
+    /// Start the sandbox again after it stops: no, on-failure[:MAX], always
+    /// or unless-stopped. Requires a detached sandbox.
+    #[arg(long, value_name = "POLICY")]
+    pub restart: Option<String>,
+
+    // --- Health check ---
+    /// Health check command, run with /bin/sh -c in the guest; exit code 0 is healthy.
+    #[arg(long, value_name = "CMD", conflicts_with_all = ["health_tcp", "health_http"])]
+    pub health_cmd: Option<String>,
+
+    /// Health check that connects to a TCP port in the guest.
+    #[arg(long, value_name = "PORT", conflicts_with = "health_http")]
+    pub health_tcp: Option<u16>,
+
+    /// Health check that sends an HTTP GET to a guest port; 2xx/3xx is healthy.
+    #[arg(long, value_name = "PORT[/PATH]")]
+    pub health_http: Option<String>,
+
+    /// Time between health checks (e.g. 10s, 1m). Default: 10s.
+    #[arg(long)]
+    pub health_interval: Option<String>,
+
+    /// Time a single health check may take (e.g. 5s). Default: 5s.
+    #[arg(long)]
+    pub health_timeout: Option<String>,
+
+    /// Consecutive failures before the sandbox is unhealthy. Default: 3.
+    #[arg(long)]
+    pub health_retries: Option<u32>,
+
+    /// Time after boot during which failed health checks don't count (e.g. 30s).
+    #[arg(long)]
+    pub health_start_period: Option<String>,
+
+    /// Action when the sandbox becomes unhealthy: log, signal=N, drain or kill.
+    #[arg(long, value_name = "ACTION")]
+    pub health_on_unhealthy: Option<String>,
+
     // --- Networking (requires "net" feature) ---
     /// Forward a host port to the sandbox (HOST:GUEST or HOST:GUEST/udp).
     #[cfg(feature = "net")]
@@ -100,6 +174,11 @@ pub struct SandboxOpts {
     #[arg(long)]
     pub no_network: bool,
 
+    /// Join a private network so other members can reach the sandbox by name.
+    #[cfg(feature = "net")]
+    #[arg(long, value_name = "NAME")]
+    pub network: Option<String>,
+
     /// Block DNS lookups for a domain (returns NXDOMAIN).
     #[cfg(feature = "net")]
     #[arg(long)]
@@ -193,19 +272,37 @@ impl SandboxOpts {
             || self.workdir.is_some()
             || self.shell.is_some()
             || !self.env.is_empty()
+            || !self.sensitive_env.is_empty()
+            || !self.label.is_empty()
             || !self.tmpfs.is_empty()
             || !self.script.is_empty()
+            || !self.socket.is_empty()
+            || !self.expose_socket.is_empty()
+            || self.ssh_agent
             || self.entrypoint.is_some()
             || self.hostname.is_some()
             || self.user.is_some()
             || self.pull.is_some()
+            || self.platform.is_some()
+            || self.lazy
             || self.log_level.is_some()
             || self.max_duration.is_some()
-            || self.idle_timeout.is_some();
+            || self.idle_timeout.is_some()
+            || !self.limits.is_empty()
+            || self.restart.is_some()
+            || self.health_cmd.is_some()
+            || self.health_tcp.is_some()
+            || self.health_http.is_some()
+            || self.health_interval.is_some()
+            || self.health_timeout.is_some()
+            || self.health_retries.is_some()
+            || self.health_start_period.is_some()
+            || self.health_on_unhealthy.is_some();
 
         #[cfg(feature = "net")]
         let net = !self.port.is_empty()
             || self.no_network
+            || self.network.is_some()
             || !self.dns_block_domain.is_empty()
             || !self.dns_block_suffix.is_empty()
             || self.no_dns_rebind_protection
@@ -259,6 +356,16 @@ pub fn apply_sandbox_opts(
         let (k, v) = ui::parse_env(env_str).map_err(anyhow::Error::msg)?;
         builder = builder.env(k, v);
     }
+    for env_str in &opts.sensitive_env {
+        let (k, v) = ui::parse_env(env_str).map_err(anyhow::Error::msg)?;
+        builder = builder.sensitive_env(k, v);
+    }
+
+    // --- Labels ---
+    for label_str in &opts.label {
+        let (k, v) = ui::parse_label(label_str).map_err(anyhow::Error::msg)?;
+        builder = builder.label(k, v);
+    }
 
     // --- Volumes ---
     for vol_str in &opts.volume {
@@ -281,6 +388,19 @@ pub fn apply_sandbox_opts(
         builder = builder.script(name, content);
     }
 
+    // --- Sockets ---
+    for spec in &opts.socket {
+        let (host, guest) = parse_socket(spec, "HOST_PATH:GUEST_PATH")?;
+        builder = builder.socket_mount(std::path::absolute(host)?, guest);
+    }
+    for spec in &opts.expose_socket {
+        let (guest, host) = parse_socket(spec, "GUEST_PATH:HOST_PATH")?;
+        builder = builder.expose_socket(guest, std::path::absolute(host)?);
+    }
+    if opts.ssh_agent {
+        builder = builder.forward_ssh_agent();
+    }
+
     // --- Image/Runtime overrides ---
     if let Some(ref ep) = opts.entrypoint {
         builder = builder.entrypoint(vec![ep.clone()]);
@@ -294,6 +414,12 @@ pub fn apply_sandbox_opts(
     if let Some(ref pull) = opts.pull {
         builder = builder.pull_policy(parse_pull_policy(pull)?);
     }
+    if let Some(ref platform) = opts.platform {
+        builder = builder.platform(platform);
+    }
+    if opts.lazy {
+        builder = builder.lazy_layers(true);
+    }
 
     // --- Log level ---
     if let Some(ref level) = opts.log_level {
@@ -307,6 +433,15 @@ pub fn apply_sandbox_opts(
     if let Some(ref dur) = opts.idle_timeout {
         builder = builder.idle_timeout(parse_duration_secs(dur)?);
     }
+    for spec in &opts.limits {
+        builder = builder.resource_limit(spec.parse()?);
+    }
+    if let Some(ref policy) = opts.restart {
+        builder = builder.restart_policy(policy.parse()?);
+    }
+    if let Some(check) = parse_health_check(opts)? {
+        builder = builder.health_check(check);
+    }
 
     // --- Networking ---
     #[cfg(feature = "net")]
@@ -348,9 +483,18 @@ fn apply_network_opts(
 
     // Disable networking.
     if opts.no_network {
+        if opts.network.is_some() {
+            anyhow::bail!("--network cannot be combined with --no-network");
+        }
         builder = builder.disable_network();
     }
 
+    // Private network.
+    if let Some(ref network) = opts.network {
+        let network = network.clone();
+        builder = builder.network(move |n| n.attach(network));
+    }
+
     // Secrets.
     for secret_str in &opts.secret {
         let (env_var, value, host) = parse_secret(secret_str)?;
@@ -454,10 +598,12 @@ fn apply_network_opts(
 
 // --- Parsing helpers ---
 
-/// Parse a duration string (e.g., "30s", "5m", "1h") into seconds.
+/// Parse a duration string (e.g., "30s", "5m", "1h", "7d") into seconds.
 pub fn parse_duration_secs(s: &str) -> anyhow::Result<u64> {
     let s = s.trim();
-    if let Some(n) = s.strip_suffix('s') {
+    if let Some(n) = s.strip_suffix('d') {
+        Ok(n.trim().parse::<u64>()? * 86_400)
+    } else if let Some(n) = s.strip_suffix('s') {
         Ok(n.trim().parse::<u64>()?)
     } else if let Some(n) = s.strip_suffix('m') {
         Ok(n.trim().parse::<u64>()? * 60)
@@ -468,9 +614,59 @@ pub fn parse_duration_secs(s: &str) -> anyhow::Result<u64> {
     }
 }
 
+/// Build a health check from the `--health-*` flags, or `None` if no probe is set.
+fn parse_health_check(opts: &SandboxOpts) -> anyhow::Result<Option<HealthCheck>> {
+    let probe = if let Some(ref cmd) = opts.health_cmd {
+        HealthProbe::Exec {
+            command: vec!["/bin/sh".into(), "-c".into(), cmd.clone()],
+        }
+    } else if let Some(port) = opts.health_tcp {
+        HealthProbe::Tcp { port }
+    } else if let Some(ref spec) = opts.health_http {
+        let (port, path) = match spec.split_once('/') {
+            Some((port, path)) => (port, format!("/{path}")),
+            None => (spec.as_str(), "/".to_string()),
+        };
+        let port = port
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid --health-http port: {spec}"))?;
+        HealthProbe::Http { port, path }
+    } else {
+        let tuning = opts.health_interval.is_some()
+            || opts.health_timeout.is_some()
+            || opts.health_retries.is_some()
+            || opts.health_start_period.is_some()
+            || opts.health_on_unhealthy.is_some();
+        if tuning {
+            anyhow::bail!(
+                "health check options require --health-cmd, --health-tcp or --health-http"
+            );
+        }
+        return Ok(None);
+    };
+
+    let mut check = HealthCheck::new(probe);
+    if let Some(ref interval) = opts.health_interval {
+        check.interval_secs = parse_duration_secs(interval)?;
+    }
+    if let Some(ref timeout) = opts.health_timeout {
+        check.timeout_secs = parse_duration_secs(timeout)?;
+    }
+    if let Some(retries) = opts.health_retries {
+        check.retries = retries;
+    }
+    if let Some(ref period) = opts.health_start_period {
+        check.start_period_secs = parse_duration_secs(period)?;
+    }
+    if let Some(ref action) = opts.health_on_unhealthy {
+        check.on_unhealthy = action.parse()?;
+    }
+    Ok(Some(check))
+}
+
 /// Parse a `--network-policy` value into a [`NetworkPolicy`], or `None` to leave the default.
 #[cfg(feature = "net")]
-fn parse_network_policy(
+pub fn parse_network_policy(
     s: Option<&str>,
 ) -> anyhow::Result<Option<microsandbox_network::policy::NetworkPolicy>> {
     use microsandbox_network::policy::NetworkPolicy;
@@ -489,7 +685,7 @@ fn parse_network_policy(
 
 /// Parse a port spec: `HOST:GUEST` or `HOST:GUEST/udp` or `HOST:GUEST/tcp`.
 #[cfg(feature = "net")]
-fn parse_port(spec: &str) -> anyhow::Result<(u16, u16, bool)> {
+pub fn parse_port(spec: &str) -> anyhow::Result<(u16, u16, bool)> {
     let (port_part, udp) = if let Some(p) = spec.strip_suffix("/udp") {
         (p, true)
     } else if let Some(p) = spec.strip_suffix("/tcp") {
@@ -554,7 +750,7 @@ fn parse_violation_action(
 }
 
 /// Parse a tmpfs spec: `PATH` or `PATH:SIZE`.
-fn parse_tmpfs(spec: &str) -> anyhow::Result<(String, Option<u32>)> {
+pub fn parse_tmpfs(spec: &str) -> anyhow::Result<(String, Option<u32>)> {
     if let Some((path, size_str)) = spec.split_once(':') {
         let size_mib = ui::parse_size_mib(size_str).map_err(anyhow::Error::msg)?;
         Ok((path.to_string(), Some(size_mib)))
@@ -563,6 +759,13 @@ fn parse_tmpfs(spec: &str) -> anyhow::Result<(String, Option<u32>)> {
     }
 }
 
+/// Split a socket spec into its two paths.
+fn parse_socket<'a>(spec: &'a str, format: &str) -> anyhow::Result<(&'a str, &'a str)> {
+    spec.split_once(':')
+        .filter(|(a, b)| !a.is_empty() && !b.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("socket must be in format {format}"))
+}
+
 /// Parse a script spec: `NAME:PATH` and read file content.
 fn parse_script(spec: &str) -> anyhow::Result<(String, String)> {
     let (name, path) = spec
@@ -0,0 +1,172 @@
+//! `msb down` command — stop and remove the sandboxes of a compose project.
+
+use std::{path::PathBuf, time::Duration};
+
+use clap::Args;
+use microsandbox::{
+    LabelSelector, Volume,
+    sandbox::{Sandbox, SandboxHandle, SandboxStatus},
+};
+
+use crate::{
+    compose::{self, ComposeFile, PROJECT_LABEL, SERVICE_LABEL},
+    ui,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Default seconds to wait for graceful shutdown before force-killing.
+const DEFAULT_STOP_TIMEOUT_SECS: u64 = 10;
+
+/// Interval between checks while waiting for a sandbox to stop.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Stop and remove the sandboxes of a compose project.
+#[derive(Debug, Args)]
+pub struct DownArgs {
+    /// Compose file (default: sandbox.yaml, sandbox.yml or sandbox.toml).
+    #[arg(short, long)]
+    pub file: Option<PathBuf>,
+
+    /// Project name. When set, no compose file is needed.
+    #[arg(short, long)]
+    pub project: Option<String>,
+
+    /// Also remove named volumes created for the project.
+    #[arg(long)]
+    pub volumes: bool,
+
+    /// Seconds to wait for graceful shutdown before force-killing.
+    #[arg(short = 't', long)]
+    pub timeout: Option<u64>,
+
+    /// Suppress progress output.
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb down` command.
+pub async fn run(args: DownArgs) -> anyhow::Result<()> {
+    // The file is only needed to name the project and order the shutdown.
+    let compose = match (&args.project, args.file.as_deref()) {
+        (Some(_), None) => None,
+        (project, file) => Some(ComposeFile::load(
+            &compose::resolve_file(file)?,
+            project.clone(),
+        )?),
+    };
+    let project = match (&args.project, &compose) {
+        (Some(project), _) => project.clone(),
+        (None, Some(compose)) => compose.project().to_string(),
+        (None, None) => unreachable!("compose file is loaded when no project is given"),
+    };
+
+    let selector = LabelSelector::parse(&format!("{PROJECT_LABEL}={project}"))?;
+    let mut handles = Sandbox::list_matching(&selector).await?;
+    if handles.is_empty() && !args.quiet {
+        eprintln!("No sandboxes in project '{project}'.");
+    }
+
+    // Stop dependents before their dependencies.
+    if let Some(ref compose) = compose {
+        let order = compose.startup_order()?;
+        handles.sort_by_key(|handle| {
+            let service = handle.labels().remove(SERVICE_LABEL);
+            std::cmp::Reverse(service.and_then(|service| order.iter().position(|s| *s == service)))
+        });
+    }
+
+    let timeout = Duration::from_secs(args.timeout.unwrap_or(DEFAULT_STOP_TIMEOUT_SECS));
+    let mut failed = false;
+    for handle in handles {
+        let spinner = if args.quiet {
+            ui::Spinner::quiet()
+        } else {
+            ui::Spinner::start("Removing", handle.name())
+        };
+        match stop_and_remove(handle, timeout).await {
+            Ok(()) => spinner.finish_success("Removed"),
+            Err(e) => {
+                spinner.finish_error();
+                if !args.quiet {
+                    ui::error(&format!("{e}"));
+                }
+                failed = true;
+            }
+        }
+    }
+
+    // Networks created by `msb up` go with the project's sandboxes.
+    #[cfg(feature = "net")]
+    for network in microsandbox::Network::list_matching(&selector).await? {
+        match network.remove().await {
+            Ok(()) if !args.quiet => ui::success("Removed", network.name()),
+            Ok(()) => {}
+            Err(e) => {
+                if !args.quiet {
+                    ui::error(&format!("network '{}': {e}", network.name()));
+                }
+                failed = true;
+            }
+        }
+    }
+
+    if args.volumes {
+        for volume in Volume::list_matching(&selector).await? {
+            match volume.remove().await {
+                Ok(()) if !args.quiet => ui::success("Removed", volume.name()),
+                Ok(()) => {}
+                Err(e) => {
+                    if !args.quiet {
+                        ui::error(&format!("volume '{}': {e}", volume.name()));
+                    }
+                    failed = true;
+                }
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Stop a sandbox gracefully, killing it after `timeout`, then remove it.
+async fn stop_and_remove(mut handle: SandboxHandle, timeout: Duration) -> anyhow::Result<()> {
+    if matches!(
+        handle.status(),
+        SandboxStatus::Running | SandboxStatus::Draining
+    ) {
+        handle.stop().await?;
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            handle = Sandbox::get(handle.name()).await?;
+            if !matches!(
+                handle.status(),
+                SandboxStatus::Running | SandboxStatus::Draining
+            ) {
+                break;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                handle.kill().await?;
+                break;
+            }
+            tokio::time::sleep(STOP_POLL_INTERVAL).await;
+        }
+    }
+
+    handle.remove().await?;
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! `msb events` command — show sandbox lifecycle events.
+
+use std::time::Duration;
+
+use clap::Args;
+use futures::StreamExt;
+use microsandbox::events::{self, EventKind, SandboxEvent};
+
+use crate::ui;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Show lifecycle events for all sandboxes.
+#[derive(Debug, Args)]
+pub struct EventsArgs {
+    /// Only show events for these sandboxes.
+    pub names: Vec<String>,
+
+    /// Keep running and print new events as they happen.
+    #[arg(short, long)]
+    pub follow: bool,
+
+    /// Show events from this long ago (e.g. 10m, 1h, 7d).
+    #[arg(long, value_name = "DURATION")]
+    pub since: Option<String>,
+
+    /// Output format (json). Prints one JSON object per line.
+    #[arg(long, value_name = "FORMAT", value_parser = ["json"])]
+    pub format: Option<String>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb events` command.
+pub async fn run(args: EventsArgs) -> anyhow::Result<()> {
+    let since = match args.since.as_deref() {
+        Some(since) => {
+            let since = Duration::from_secs(super::common::parse_duration_secs(since)?);
+            Some(chrono::Utc::now() - chrono::Duration::from_std(since)?)
+        }
+        None => None,
+    };
+
+    if !args.follow {
+        for event in events::history(since).await? {
+            print_event(&args, &event)?;
+        }
+        return Ok(());
+    }
+
+    let mut stream = match since {
+        Some(since) => events::subscribe_since(since).boxed(),
+        None => events::subscribe().boxed(),
+    };
+    while let Some(event) = stream.next().await {
+        print_event(&args, &event?)?;
+    }
+
+    Ok(())
+}
+
+fn print_event(args: &EventsArgs, event: &SandboxEvent) -> anyhow::Result<()> {
+    if !args.names.is_empty() && !args.names.contains(&event.sandbox) {
+        return Ok(());
+    }
+
+    if args.format.as_deref() == Some("json") {
+        println!("{}", serde_json::to_string(event)?);
+        return Ok(());
+    }
+
+    let line = format!(
+        "{}  {:<20}  {:<16}  {}",
+        ui::format_datetime(&event.timestamp),
+        event.sandbox,
+        event.kind.name(),
+        describe(&event.kind)
+    );
+    println!("{}", line.trim_end());
+    Ok(())
+}
+
+/// One-line summary of an event's details.
+fn describe(kind: &EventKind) -> String {
+    match kind {
+        EventKind::Created | EventKind::Started | EventKind::Crashed | EventKind::Removed => {
+            String::new()
+        }
+        EventKind::Draining { reason } => reason.clone(),
+        EventKind::Stopped {
+            reason,
+            exit_code,
+            detail,
+        } => {
+            let mut out = format!("{reason:?}");
+            if let Some(code) = exit_code {
+                out.push_str(&format!(" (exit {code})"));
+            }
+            if let Some(detail) = detail {
+                out.push_str(&format!(": {detail}"));
+            }
+            out
+        }
+        EventKind::ExecStarted {
+            session_id,
+            cmd,
+            pid,
+        } => format!("#{session_id} pid {pid}: {cmd}"),
+        EventKind::ExecExited {
+            session_id,
+            exit_code,
+        } => format!("#{session_id} exit {exit_code}"),
+        EventKind::ResourceLimit { limit, detail } => format!("{limit}: {detail}"),
+        EventKind::HealthChanged { status, detail } => match detail {
+            Some(detail) => format!("{status}: {detail}"),
+            None => status.to_string(),
+        },
+        EventKind::SecretViolation { host } => format!("secret sent to {host}"),
+        EventKind::PolicyDenial {
+            destination,
+            protocol,
+        } => format!("{protocol} {destination}"),
+    }
+}
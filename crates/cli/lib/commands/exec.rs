@@ -1,10 +1,11 @@
 //! `msb exec` command — execute a command in a sandbox.
 
 use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
 use std::time::Duration;
 
 use clap::Args;
-use microsandbox::sandbox::ExecOutput;
+use microsandbox::sandbox::{ExecOutput, Sandbox, exec::ExecEvent};
 use tokio::io::AsyncReadExt;
 
 use crate::ui;
@@ -23,6 +24,15 @@ pub struct ExecArgs {
     #[arg(short, long)]
     pub env: Vec<String>,
 
+    /// Read environment variables from a file of KEY=value lines (repeatable).
+    #[arg(long, value_name = "PATH")]
+    pub env_file: Vec<PathBuf>,
+
+    /// Don't inherit the sandbox's environment; pass only --env and
+    /// --env-file variables.
+    #[arg(long)]
+    pub clear_env: bool,
+
     /// Set the working directory for the command.
     #[arg(short, long)]
     pub workdir: Option<String>,
@@ -43,6 +53,23 @@ pub struct ExecArgs {
     #[arg(long)]
     pub rlimit: Vec<String>,
 
+    /// Keep the command running under this session name if the client
+    /// disconnects. Reattach later with --attach.
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["attach", "list_sessions"])]
+    pub persistent: Option<String>,
+
+    /// Reattach to a persistent session instead of starting a command.
+    #[arg(long, value_name = "SESSION", conflicts_with = "list_sessions")]
+    pub attach: Option<String>,
+
+    /// List the sandbox's persistent sessions.
+    #[arg(long)]
+    pub list_sessions: bool,
+
+    /// Output format for --list-sessions (json).
+    #[arg(long, value_name = "FORMAT", value_parser = ["json"], requires = "list_sessions")]
+    pub format: Option<String>,
+
     /// Suppress progress output.
     #[arg(short, long)]
     pub quiet: bool,
@@ -61,6 +88,16 @@ pub struct ExecArgs {
 pub async fn run(args: ExecArgs) -> anyhow::Result<()> {
     let sandbox = super::resolve_and_start(&args.name, args.quiet).await?;
 
+    if args.list_sessions {
+        let result = list_sessions(&sandbox, args.format.as_deref()).await;
+        super::maybe_stop(&sandbox).await;
+        return result;
+    }
+
+    if let Some(ref session) = args.attach {
+        return reattach(sandbox, session).await;
+    }
+
     // Build exec options.
     let env_pairs: Vec<(String, String)> = args
         .env
@@ -108,7 +145,10 @@ pub async fn run(args: ExecArgs) -> anyhow::Result<()> {
         // Interactive mode with TTY — use attach.
         let exit_code = sandbox
             .attach_with(cmd, |a| {
-                let mut a = a.args(cmd_args);
+                let mut a = a.args(cmd_args).inherit_env(!args.clear_env);
+                for path in &args.env_file {
+                    a = a.env_file(path);
+                }
                 for (k, v) in &env_pairs {
                     a = a.env(k, v);
                 }
@@ -121,11 +161,14 @@ pub async fn run(args: ExecArgs) -> anyhow::Result<()> {
                 for &(resource, soft, hard) in &rlimits {
                     a = a.rlimit_range(resource, soft, hard);
                 }
+                if let Some(ref name) = args.persistent {
+                    a = a.persistent(name);
+                }
                 a
             })
             .await?;
 
-        super::maybe_stop(&sandbox).await;
+        finish(sandbox, args.persistent.is_some()).await;
 
         if exit_code != 0 {
             std::process::exit(exit_code);
@@ -136,8 +179,12 @@ pub async fn run(args: ExecArgs) -> anyhow::Result<()> {
             .exec_with(cmd, |e| {
                 let mut e = e
                     .args(cmd_args)
-                    .stdin_bytes(piped_stdin.unwrap_or_default());
+                    .stdin_bytes(piped_stdin.unwrap_or_default())
+                    .inherit_env(!args.clear_env);
 
+                for path in &args.env_file {
+                    e = e.env_file(path);
+                }
                 for (k, v) in &env_pairs {
                     e = e.env(k, v);
                 }
@@ -156,6 +203,9 @@ pub async fn run(args: ExecArgs) -> anyhow::Result<()> {
                 for &(resource, soft, hard) in &rlimits {
                     e = e.rlimit_range(resource, soft, hard);
                 }
+                if let Some(ref name) = args.persistent {
+                    e = e.persistent(name);
+                }
                 e
             })
             .await?;
@@ -163,12 +213,94 @@ pub async fn run(args: ExecArgs) -> anyhow::Result<()> {
         std::io::stdout().write_all(output.stdout_bytes())?;
         std::io::stderr().write_all(output.stderr_bytes())?;
 
-        super::maybe_stop(&sandbox).await;
+        finish(sandbox, args.persistent.is_some()).await;
 
         if !output.status().success {
-            std::process::exit(output.status().code);
+            std::process::exit(output.status().shell_code());
         }
     }
 
     Ok(())
 }
+
+/// Reattach to a persistent session, through the terminal when interactive.
+async fn reattach(sandbox: Sandbox, session: &str) -> anyhow::Result<()> {
+    let exit_code = if std::io::stdin().is_terminal() {
+        sandbox.attach_session(session).await?
+    } else {
+        let mut handle = sandbox.reattach(session).await?;
+
+        // Forward piped input without closing the session's stdin.
+        let mut input = Vec::new();
+        tokio::io::stdin().read_to_end(&mut input).await.ok();
+        if !input.is_empty()
+            && let Some(sink) = handle.take_stdin()
+        {
+            sink.write(&input).await?;
+        }
+
+        let mut code = -1;
+        while let Some(event) = handle.recv().await {
+            match event {
+                ExecEvent::Stdout(data) => std::io::stdout().write_all(&data)?,
+                ExecEvent::Stderr(data) => std::io::stderr().write_all(&data)?,
+                ExecEvent::Exited { status, .. } => code = status.shell_code(),
+                _ => {}
+            }
+        }
+        code
+    };
+
+    finish(sandbox, true).await;
+
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+    Ok(())
+}
+
+/// Print the sandbox's persistent sessions.
+async fn list_sessions(sandbox: &Sandbox, format: Option<&str>) -> anyhow::Result<()> {
+    let sessions = sandbox.list_sessions().await?;
+
+    if format == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+        return Ok(());
+    }
+
+    if sessions.is_empty() {
+        eprintln!("No persistent sessions.");
+        return Ok(());
+    }
+
+    let mut table = ui::Table::new(&["NAME", "PID", "STATUS", "STARTED", "COMMAND"]);
+    for s in &sessions {
+        let status = match s.exit_code {
+            Some(code) => format!("exited ({code})"),
+            None if s.attached => "attached".to_string(),
+            None => "detached".to_string(),
+        };
+        let started = chrono::DateTime::from_timestamp(s.started_at, 0)
+            .map(|dt| ui::format_datetime(&dt))
+            .unwrap_or_else(|| "-".to_string());
+        table.add_row(vec![
+            s.name.clone(),
+            s.pid.to_string(),
+            status,
+            started,
+            s.cmd.clone(),
+        ]);
+    }
+    table.print();
+    Ok(())
+}
+
+/// Release the sandbox after a command. Sandboxes with persistent sessions
+/// are left running so the sessions can be reattached.
+async fn finish(sandbox: Sandbox, persistent: bool) {
+    if persistent {
+        sandbox.detach().await;
+    } else {
+        super::maybe_stop(&sandbox).await;
+    }
+}
@@ -0,0 +1,73 @@
+//! `msb export` command — write a stopped sandbox to a portable archive.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use microsandbox::sandbox::{ExportOptions, Sandbox};
+
+use crate::ui;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Export a stopped sandbox to a portable archive.
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Sandbox to export.
+    pub name: String,
+
+    /// Output path (defaults to `<name>.tar.zst`).
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Bundle the contents of named volumes mounted by the sandbox.
+    #[arg(long)]
+    pub with_volumes: bool,
+
+    /// Bundle image layers so the archive imports without registry access.
+    #[arg(long)]
+    pub with_layers: bool,
+
+    /// Suppress progress output.
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb export` command.
+pub async fn run(args: ExportArgs) -> anyhow::Result<()> {
+    let output = args
+        .output
+        .unwrap_or_else(|| PathBuf::from(format!("{}.tar.zst", args.name)));
+
+    let spinner = if args.quiet {
+        ui::Spinner::quiet()
+    } else {
+        ui::Spinner::start("Exporting", &args.name)
+    };
+
+    let options = ExportOptions {
+        include_volumes: args.with_volumes,
+        include_layers: args.with_layers,
+    };
+
+    match Sandbox::export(&args.name, &output, options).await {
+        Ok(()) => {
+            spinner.finish_success("Exported");
+        }
+        Err(e) => {
+            spinner.finish_error();
+            return Err(e.into());
+        }
+    }
+
+    if !args.quiet {
+        println!("{}", output.display());
+    }
+
+    Ok(())
+}
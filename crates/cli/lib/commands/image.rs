@@ -43,6 +43,10 @@ pub enum ImageCommands {
 /// Arguments for `msb image list`.
 #[derive(Debug, Args)]
 pub struct ImageListArgs {
+    /// Filter by label selector (e.g. label=session=abc, label='tier in (web,api)').
+    #[arg(long, value_name = "FILTER")]
+    pub filter: Vec<String>,
+
     /// Output format (json).
     #[arg(long, value_name = "FORMAT", value_parser = ["json"])]
     pub format: Option<String>,
@@ -89,6 +93,7 @@ pub async fn run(args: ImageArgs) -> anyhow::Result<()> {
         ImageCommands::Pull(args) => {
             run_pull_inner(
                 args.reference,
+                args.platform,
                 args.force,
                 args.quiet,
                 microsandbox_image::PullPolicy::Always,
@@ -105,6 +110,7 @@ pub async fn run(args: ImageArgs) -> anyhow::Result<()> {
 pub async fn run_pull(args: pull::PullArgs) -> anyhow::Result<()> {
     run_pull_inner(
         args.reference,
+        args.platform,
         args.force,
         args.quiet,
         microsandbox_image::PullPolicy::Always,
@@ -115,6 +121,7 @@ pub async fn run_pull(args: pull::PullArgs) -> anyhow::Result<()> {
 /// Shared pull logic with DB persistence.
 async fn run_pull_inner(
     reference: String,
+    platform: Option<String>,
     force: bool,
     quiet: bool,
     pull_policy: microsandbox_image::PullPolicy,
@@ -123,13 +130,16 @@ async fn run_pull_inner(
 
     let global = microsandbox::config::config();
     let cache = microsandbox_image::GlobalCache::new(&global.cache_dir())?;
-    let platform = microsandbox_image::Platform::host_linux();
+    let platform: microsandbox_image::Platform = match platform {
+        Some(platform) => platform.parse()?,
+        None => microsandbox_image::Platform::host_linux(),
+    };
     let image_ref: microsandbox_image::Reference = reference
         .parse()
         .map_err(|e| anyhow::anyhow!("invalid image reference: {e}"))?;
 
     let auth = global.resolve_registry_auth(image_ref.registry())?;
-    let registry = microsandbox_image::Registry::with_auth(platform, cache, auth)?;
+    let registry = microsandbox_image::Registry::with_auth(platform.clone(), cache, auth)?;
 
     let options = microsandbox_image::PullOptions {
         pull_policy,
@@ -167,7 +177,7 @@ async fn run_pull_inner(
 
     // Persist to database.
     let cache = microsandbox_image::GlobalCache::new(&global.cache_dir())?;
-    match cache.read_image_metadata(&image_ref) {
+    match cache.read_image_metadata(&image_ref, &platform) {
         Ok(Some(metadata)) => {
             if let Err(e) = Image::persist(&reference, metadata).await {
                 tracing::warn!(error = %e, "failed to persist image metadata to database");
@@ -180,7 +190,7 @@ async fn run_pull_inner(
     }
 
     if !quiet {
-        let suffix = if result.cached {
+        let mut suffix = if result.cached {
             " (already cached)".to_string()
         } else {
             let elapsed = start.elapsed();
@@ -190,6 +200,9 @@ async fn run_pull_inner(
                 String::new()
             }
         };
+        if !platform.is_host_native() {
+            suffix.push_str(&format!(" [{platform}, not runnable on this host]"));
+        }
 
         eprintln!(
             "   {} {:<12} {}{}",
@@ -216,6 +229,7 @@ pub(crate) async fn pull_if_missing(reference: &str, quiet: bool) -> anyhow::Res
 
     run_pull_inner(
         reference.to_string(),
+        None,
         false,
         quiet,
         microsandbox_image::PullPolicy::IfMissing,
@@ -225,7 +239,8 @@ pub(crate) async fn pull_if_missing(reference: &str, quiet: bool) -> anyhow::Res
 
 /// Execute `msb image list` / `msb images`.
 pub async fn run_list(args: ImageListArgs) -> anyhow::Result<()> {
-    let images = Image::list().await?;
+    let selector = ui::parse_label_filters(&args.filter).map_err(anyhow::Error::msg)?;
+    let images = Image::list_matching(&selector).await?;
 
     if args.format.as_deref() == Some("json") {
         let entries: Vec<serde_json::Value> = images
@@ -238,6 +253,7 @@ pub async fn run_list(args: ImageListArgs) -> anyhow::Result<()> {
                     "architecture": img.architecture(),
                     "os": img.os(),
                     "layer_count": img.layer_count(),
+                    "labels": img.labels(),
                     "created_at": img.created_at().map(|dt| ui::format_datetime(&dt)),
                 })
             })
@@ -0,0 +1,66 @@
+//! `msb import` command — recreate a sandbox from an exported archive.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use microsandbox::sandbox::{ImportOptions, Sandbox};
+
+use crate::ui;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Import a sandbox from an archive created by `msb export`.
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// Archive to import.
+    pub archive: PathBuf,
+
+    /// Import under a different sandbox name.
+    #[arg(short, long)]
+    pub name: Option<String>,
+
+    /// Replace an existing sandbox with the same name.
+    #[arg(long)]
+    pub replace: bool,
+
+    /// Suppress progress output.
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb import` command.
+pub async fn run(args: ImportArgs) -> anyhow::Result<()> {
+    let spinner = if args.quiet {
+        ui::Spinner::quiet()
+    } else {
+        ui::Spinner::start("Importing", &args.archive.display().to_string())
+    };
+
+    let options = ImportOptions {
+        name: args.name,
+        replace: args.replace,
+    };
+
+    let handle = match Sandbox::import(&args.archive, options).await {
+        Ok(handle) => {
+            spinner.finish_success("Imported");
+            handle
+        }
+        Err(e) => {
+            spinner.finish_error();
+            return Err(e.into());
+        }
+    };
+
+    if !args.quiet {
+        println!("{}", handle.name());
+    }
+
+    Ok(())
+}
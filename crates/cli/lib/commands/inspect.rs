@@ -1,7 +1,7 @@
 //! `msb inspect` command — show detailed sandbox information.
 
 use clap::Args;
-use microsandbox::sandbox::{Sandbox, SandboxConfig, VolumeMount};
+use microsandbox::sandbox::{Sandbox, SandboxConfig, SocketDirection, VolumeMount};
 
 use crate::ui;
 
@@ -27,6 +27,7 @@ pub struct InspectArgs {
 /// Execute the `msb inspect` command.
 pub async fn run(args: InspectArgs) -> anyhow::Result<()> {
     let handle = Sandbox::get(&args.name).await?;
+    let restart_count = handle.restart_count().await?;
 
     if args.format.as_deref() == Some("json") {
         let config: serde_json::Value =
@@ -34,7 +35,9 @@ pub async fn run(args: InspectArgs) -> anyhow::Result<()> {
         let json = serde_json::json!({
             "name": handle.name(),
             "status": format!("{:?}", handle.status()),
+            "health": handle.health(),
             "config": config,
+            "restart_count": restart_count,
             "created_at": handle.created_at().map(|dt| ui::format_datetime(&dt)),
             "updated_at": handle.updated_at().map(|dt| ui::format_datetime(&dt)),
         });
@@ -46,6 +49,9 @@ pub async fn run(args: InspectArgs) -> anyhow::Result<()> {
 
     ui::detail_kv("Name", handle.name());
     ui::detail_kv("Status", &ui::format_status(&status));
+    if let Some(health) = handle.health() {
+        ui::detail_kv("Health", &ui::format_health(&health.to_string()));
+    }
 
     if let Some(dt) = handle.created_at() {
         ui::detail_kv("Created", &ui::format_datetime(&dt));
@@ -65,6 +71,23 @@ pub async fn run(args: InspectArgs) -> anyhow::Result<()> {
         };
         ui::detail_kv("Image", &image);
 
+        if config.restart_policy.is_enabled() {
+            ui::detail_kv(
+                "Restart",
+                &format!("{} ({restart_count} restarts)", config.restart_policy),
+            );
+        }
+
+        if let Some(ref check) = config.policy.health_check {
+            ui::detail_kv(
+                "Health check",
+                &format!(
+                    "{} (every {}s, {} retries, on unhealthy: {})",
+                    check.probe, check.interval_secs, check.retries, check.on_unhealthy
+                ),
+            );
+        }
+
         ui::detail_header("Resources");
         ui::detail_kv_indent("CPUs", &config.cpus.to_string());
         ui::detail_kv_indent("Memory", &format!("{} MiB", config.memory_mib));
@@ -76,11 +99,18 @@ pub async fn run(args: InspectArgs) -> anyhow::Result<()> {
             ui::detail_kv("Shell", shell);
         }
 
-        if !config.env.is_empty() {
+        if !config.env.is_empty() || !config.sensitive_env.is_empty() {
             ui::detail_header("Environment");
-            for (k, v) in &config.env {
+            for (k, v) in config
+                .env
+                .iter()
+                .filter(|(k, _)| !config.is_sensitive_env(k))
+            {
                 println!("  {k}={v}");
             }
+            for k in &config.sensitive_env {
+                println!("  {k}=<redacted>");
+            }
         }
 
         if !config.mounts.is_empty() {
@@ -110,6 +140,18 @@ pub async fn run(args: InspectArgs) -> anyhow::Result<()> {
                 }
             }
         }
+
+        if !config.socket_mounts.is_empty() {
+            ui::detail_header("Sockets");
+            for mount in &config.socket_mounts {
+                let guest = &mount.guest_path;
+                let host = mount.host_path.display();
+                match mount.direction {
+                    SocketDirection::HostToGuest => println!("  {guest:<16}\u{2192} {host}"),
+                    SocketDirection::GuestToHost => println!("  {guest:<16}\u{2190} {host}"),
+                }
+            }
+        }
     }
 
     Ok(())
@@ -20,6 +20,10 @@ pub struct ListArgs {
     #[arg(long)]
     pub stopped: bool,
 
+    /// Filter by label selector (e.g. label=session=abc, label='tier in (web,api)').
+    #[arg(long, value_name = "FILTER")]
+    pub filter: Vec<String>,
+
     /// Output format (json).
     #[arg(long, value_name = "FORMAT", value_parser = ["json"])]
     pub format: Option<String>,
@@ -35,7 +39,8 @@ pub struct ListArgs {
 
 /// Execute the `msb list` command.
 pub async fn run(args: ListArgs) -> anyhow::Result<()> {
-    let sandboxes = Sandbox::list().await?;
+    let selector = ui::parse_label_filters(&args.filter).map_err(anyhow::Error::msg)?;
+    let sandboxes = Sandbox::list_matching(&selector).await?;
 
     let filtered: Vec<_> = sandboxes
         .into_iter()
@@ -113,6 +118,7 @@ fn print_json(sandboxes: &[SandboxHandle]) -> anyhow::Result<()> {
                 "status": format!("{:?}", s.status()),
                 "created_at": s.created_at().map(|dt| ui::format_datetime(&dt)),
                 "image": extract_image(s.config_json()),
+                "labels": s.labels(),
             })
         })
         .collect();
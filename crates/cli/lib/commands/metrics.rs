@@ -1,7 +1,11 @@
 //! `msb metrics` command — show live sandbox metrics.
 
-use clap::Args;
-use microsandbox::sandbox::{Sandbox, SandboxMetrics, all_sandbox_metrics};
+use std::time::Duration;
+
+use clap::{Args, Subcommand};
+use microsandbox::sandbox::{
+    METRICS_PATH, MetricsHistoryPoint, MetricsServer, Sandbox, SandboxMetrics, all_sandbox_metrics,
+};
 
 use crate::ui;
 
@@ -11,23 +15,81 @@ use crate::ui;
 
 /// Show live metrics for a running sandbox.
 #[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct MetricsArgs {
+    /// Metrics subcommand.
+    #[command(subcommand)]
+    pub command: Option<MetricsCommands>,
+
     /// Sandbox to inspect. Omit to show all running sandboxes.
     pub name: Option<String>,
 
+    /// Show history from this long ago instead of the latest sample (e.g. 1h, 7d).
+    #[arg(long, value_name = "DURATION", requires = "name")]
+    pub since: Option<String>,
+
+    /// Width of each history point (e.g. 10s, 1m, 1h). Defaults to 1m.
+    #[arg(long, value_name = "DURATION", requires = "since")]
+    pub step: Option<String>,
+
     /// Output format (json).
     #[arg(long, value_name = "FORMAT", value_parser = ["json"])]
     pub format: Option<String>,
 }
 
+/// Metrics subcommands.
+#[derive(Debug, Subcommand)]
+pub enum MetricsCommands {
+    /// Serve metrics for all sandboxes in OpenMetrics format for Prometheus scraping.
+    Serve(MetricsServeArgs),
+}
+
+/// Arguments for `msb metrics serve`.
+#[derive(Debug, Args)]
+pub struct MetricsServeArgs {
+    /// Address to listen on.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:9464")]
+    pub listen: String,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
 
 /// Execute the `msb metrics` command.
 pub async fn run(args: MetricsArgs) -> anyhow::Result<()> {
+    if let Some(MetricsCommands::Serve(serve_args)) = args.command {
+        return run_serve(serve_args).await;
+    }
+
     if let Some(name) = args.name.as_deref() {
         let handle = Sandbox::get(name).await?;
+
+        if let Some(since) = args.since.as_deref() {
+            let since = Duration::from_secs(super::common::parse_duration_secs(since)?);
+            let step = match args.step.as_deref() {
+                Some(step) => Duration::from_secs(super::common::parse_duration_secs(step)?),
+                None => Duration::from_secs(60),
+            };
+            let end = chrono::Utc::now();
+            let start = end - chrono::Duration::from_std(since)?;
+            let history = handle.metrics_history(start..end, step).await?;
+
+            if args.format.as_deref() == Some("json") {
+                let json = serde_json::Value::Array(history.iter().map(history_json).collect());
+                println!("{}", serde_json::to_string_pretty(&json)?);
+                return Ok(());
+            }
+
+            if history.is_empty() {
+                eprintln!("No metrics recorded for '{name}' in that range.");
+                return Ok(());
+            }
+
+            print_history_table(&history);
+            return Ok(());
+        }
+
         let metrics = handle.metrics().await?;
 
         if args.format.as_deref() == Some("json") {
@@ -69,6 +131,22 @@ pub async fn run(args: MetricsArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn run_serve(args: MetricsServeArgs) -> anyhow::Result<()> {
+    let server = MetricsServer::bind(args.listen.as_str()).await?;
+    eprintln!(
+        "Serving metrics on http://{}{METRICS_PATH} (Ctrl-C to stop)",
+        server.local_addr()?
+    );
+
+    server
+        .serve_with_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
+    Ok(())
+}
+
 fn print_table(metrics: &[(String, SandboxMetrics)]) {
     let mut table = ui::Table::new(&[
         "NAME",
@@ -103,6 +181,63 @@ fn print_table(metrics: &[(String, SandboxMetrics)]) {
     table.print();
 }
 
+fn print_history_table(history: &[MetricsHistoryPoint]) {
+    let mut table = ui::Table::new(&[
+        "TIME",
+        "SAMPLES",
+        "CPU AVG",
+        "CPU MAX",
+        "MEM AVG",
+        "MEM MAX",
+        "DISK READ",
+        "DISK WRITE",
+        "NET RX",
+        "NET TX",
+    ]);
+
+    for point in history {
+        table.add_row(vec![
+            point
+                .timestamp
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            point.samples.to_string(),
+            format!("{:.1}%", point.cpu_percent.avg),
+            format!("{:.1}%", point.cpu_percent.max),
+            ui::format_bytes(point.memory_bytes.avg),
+            ui::format_bytes(point.memory_bytes.max),
+            ui::format_bytes(point.disk_read_bytes),
+            ui::format_bytes(point.disk_write_bytes),
+            ui::format_bytes(point.net_rx_bytes),
+            ui::format_bytes(point.net_tx_bytes),
+        ]);
+    }
+
+    table.print();
+}
+
+fn history_json(point: &MetricsHistoryPoint) -> serde_json::Value {
+    serde_json::json!({
+        "timestamp": point.timestamp.to_rfc3339(),
+        "samples": point.samples,
+        "cpu_percent": {
+            "min": point.cpu_percent.min,
+            "avg": point.cpu_percent.avg,
+            "max": point.cpu_percent.max,
+        },
+        "memory_bytes": {
+            "min": point.memory_bytes.min,
+            "avg": point.memory_bytes.avg,
+            "max": point.memory_bytes.max,
+        },
+        "disk_read_bytes": point.disk_read_bytes,
+        "disk_write_bytes": point.disk_write_bytes,
+        "net_rx_bytes": point.net_rx_bytes,
+        "net_tx_bytes": point.net_tx_bytes,
+    })
+}
+
 fn metrics_json(name: &str, metrics: &SandboxMetrics) -> serde_json::Value {
     serde_json::json!({
         "name": name,
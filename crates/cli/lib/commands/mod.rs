@@ -10,21 +10,33 @@ use crate::ui;
 
 pub mod common;
 pub mod create;
+pub mod down;
+pub mod events;
 pub mod exec;
+pub mod export;
 pub mod image;
+pub mod import;
 pub mod inspect;
 pub mod install;
 pub mod list;
 pub mod metrics;
+#[cfg(feature = "net")]
+pub mod network;
+pub mod port_forward;
 pub mod ps;
+pub mod ps_guest;
 pub mod pull;
 pub mod registry;
 pub mod remove;
 pub mod run;
 pub mod self_cmd;
+pub mod serve;
 pub mod start;
 pub mod stop;
+pub mod supervise;
+pub mod top;
 pub mod uninstall;
+pub mod up;
 pub mod volume;
 
 //--------------------------------------------------------------------------------------------------
@@ -60,7 +72,16 @@ pub async fn resolve_and_start(name: &str, quiet: bool) -> anyhow::Result<Sandbo
             } else {
                 ui::Spinner::start("Starting", name)
             };
-            match handle.start().await {
+            // Restart policies only apply to detached sandboxes.
+            let detached = handle
+                .config()
+                .is_ok_and(|config| config.restart_policy.is_enabled());
+            let result = if detached {
+                handle.start_detached().await
+            } else {
+                handle.start().await
+            };
+            match result {
                 Ok(s) => {
                     spinner.finish_clear();
                     Ok(s)
@@ -0,0 +1,243 @@
+//! `msb network` command — manage private networks shared between sandboxes.
+
+use clap::{Args, Subcommand};
+use microsandbox::network::Network;
+
+use crate::ui;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Manage private networks.
+#[derive(Debug, Args)]
+pub struct NetworkArgs {
+    /// Network subcommand.
+    #[command(subcommand)]
+    pub command: NetworkCommands,
+}
+
+/// Network subcommands.
+#[derive(Debug, Subcommand)]
+pub enum NetworkCommands {
+    /// Create a new private network.
+    Create(NetworkCreateArgs),
+
+    /// List all networks.
+    #[command(visible_alias = "ls")]
+    List(NetworkListArgs),
+
+    /// Show detailed network information.
+    Inspect(NetworkInspectArgs),
+
+    /// Delete one or more networks.
+    #[command(visible_alias = "rm")]
+    Remove(NetworkRemoveArgs),
+}
+
+/// Arguments for `msb network create`.
+#[derive(Debug, Args)]
+pub struct NetworkCreateArgs {
+    /// Name for the new network.
+    pub name: String,
+
+    /// IPv4 subnet for the network (default: next free /24 in 198.18.0.0/15).
+    #[arg(long)]
+    pub subnet: Option<String>,
+
+    /// Set a label on the network (KEY=value).
+    #[arg(long)]
+    pub label: Vec<String>,
+
+    /// Suppress output.
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+/// Arguments for `msb network list`.
+#[derive(Debug, Args)]
+pub struct NetworkListArgs {
+    /// Filter by label selector (e.g. label=project=shop).
+    #[arg(long, value_name = "FILTER")]
+    pub filter: Vec<String>,
+
+    /// Output format (json).
+    #[arg(long, value_name = "FORMAT", value_parser = ["json"])]
+    pub format: Option<String>,
+
+    /// Show only network names.
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+/// Arguments for `msb network inspect`.
+#[derive(Debug, Args)]
+pub struct NetworkInspectArgs {
+    /// Network to inspect.
+    pub name: String,
+}
+
+/// Arguments for `msb network remove`.
+#[derive(Debug, Args)]
+pub struct NetworkRemoveArgs {
+    /// Network(s) to remove.
+    #[arg(required = true)]
+    pub names: Vec<String>,
+
+    /// Suppress output.
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb network` command.
+pub async fn run(args: NetworkArgs) -> anyhow::Result<()> {
+    match args.command {
+        NetworkCommands::Create(args) => create(args).await,
+        NetworkCommands::List(args) => list(args).await,
+        NetworkCommands::Inspect(args) => inspect(args).await,
+        NetworkCommands::Remove(args) => remove(args).await,
+    }
+}
+
+async fn create(args: NetworkCreateArgs) -> anyhow::Result<()> {
+    let mut builder = Network::builder(&args.name);
+
+    if let Some(ref subnet) = args.subnet {
+        let subnet = subnet
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid subnet '{subnet}': {e}"))?;
+        builder = builder.subnet(subnet);
+    }
+
+    for label_str in &args.label {
+        let (k, v) = ui::parse_label(label_str).map_err(anyhow::Error::msg)?;
+        builder = builder.label(k, v);
+    }
+
+    let network = builder.create().await?;
+
+    if !args.quiet {
+        println!("{} {}", network.name(), network.subnet());
+    }
+
+    Ok(())
+}
+
+async fn list(args: NetworkListArgs) -> anyhow::Result<()> {
+    let selector = ui::parse_label_filters(&args.filter).map_err(anyhow::Error::msg)?;
+    let networks = Network::list_matching(&selector).await?;
+
+    if args.format.as_deref() == Some("json") {
+        let entries: Vec<serde_json::Value> = networks
+            .iter()
+            .map(|n| {
+                serde_json::json!({
+                    "name": n.name(),
+                    "subnet": n.subnet().to_string(),
+                    "labels": n.labels().iter().cloned().collect::<std::collections::HashMap<_, _>>(),
+                    "created_at": n.created_at().map(|dt| ui::format_datetime(&dt)),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if args.quiet {
+        for n in &networks {
+            println!("{}", n.name());
+        }
+        return Ok(());
+    }
+
+    if networks.is_empty() {
+        eprintln!("No networks found.");
+        return Ok(());
+    }
+
+    let mut table = ui::Table::new(&["NAME", "SUBNET", "CREATED"]);
+
+    for n in &networks {
+        let created = n
+            .created_at()
+            .as_ref()
+            .map(ui::format_datetime)
+            .unwrap_or_else(|| "-".to_string());
+
+        table.add_row(vec![n.name().to_string(), n.subnet().to_string(), created]);
+    }
+
+    table.print();
+    Ok(())
+}
+
+async fn inspect(args: NetworkInspectArgs) -> anyhow::Result<()> {
+    let handle = Network::get(&args.name).await?;
+
+    let created = handle
+        .created_at()
+        .as_ref()
+        .map(ui::format_datetime)
+        .unwrap_or_else(|| "-".to_string());
+
+    let labels = handle.labels();
+    let labels_str = if labels.is_empty() {
+        "-".to_string()
+    } else {
+        labels
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let members = handle.members().await?;
+    let members_str = if members.is_empty() {
+        "-".to_string()
+    } else {
+        members.join(", ")
+    };
+
+    ui::detail_kv("Name", handle.name());
+    ui::detail_kv("Subnet", &handle.subnet().to_string());
+    ui::detail_kv("Created", &created);
+    ui::detail_kv("Members", &members_str);
+    ui::detail_kv("Labels", &labels_str);
+
+    Ok(())
+}
+
+async fn remove(args: NetworkRemoveArgs) -> anyhow::Result<()> {
+    let mut failed = false;
+
+    for name in &args.names {
+        let spinner = if args.quiet {
+            ui::Spinner::quiet()
+        } else {
+            ui::Spinner::start("Removing", name)
+        };
+
+        match Network::remove(name).await {
+            Ok(()) => {
+                spinner.finish_success("Removed");
+            }
+            Err(e) => {
+                spinner.finish_error();
+                if !args.quiet {
+                    ui::error(&format!("{e}"));
+                }
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! `msb port-forward` command — forward TCP ports to or from a sandbox.
+
+use clap::Args;
+use futures::future::select_all;
+use microsandbox::sandbox::{Sandbox, SandboxStatus};
+
+use crate::ui;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Forward TCP ports between the host and a running sandbox.
+#[derive(Debug, Args)]
+pub struct PortForwardArgs {
+    /// Sandbox to forward to.
+    pub name: String,
+
+    /// Ports to forward, as HOST_PORT:GUEST_PORT, or PORT for the same port on both sides.
+    #[arg(required = true, value_name = "[HOST_PORT:]GUEST_PORT", value_parser = parse_port_pair)]
+    pub ports: Vec<(u16, u16)>,
+
+    /// Host address to listen on, or to connect to with --reverse.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1")]
+    pub address: String,
+
+    /// Expose host ports inside the sandbox instead: listen on the guest port
+    /// and connect to the host port.
+    #[arg(short = 'R', long)]
+    pub reverse: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb port-forward` command.
+pub async fn run(args: PortForwardArgs) -> anyhow::Result<()> {
+    let handle = Sandbox::get(&args.name).await?;
+    if !matches!(
+        handle.status(),
+        SandboxStatus::Running | SandboxStatus::Draining
+    ) {
+        anyhow::bail!("sandbox '{}' is not running", args.name);
+    }
+    let sandbox = handle.connect().await?;
+
+    let mut forwards = Vec::with_capacity(args.ports.len());
+    for &(host_port, guest_port) in &args.ports {
+        let host_addr = (args.address.as_str(), host_port);
+        let forward = if args.reverse {
+            let forward = sandbox.reverse_forward_port(guest_port, host_addr).await?;
+            ui::success(
+                "Forwarding",
+                &format!("guest :{guest_port} -> {}", forward.host_addr()),
+            );
+            forward
+        } else {
+            let forward = sandbox.forward_port(host_addr, guest_port).await?;
+            ui::success(
+                "Forwarding",
+                &format!("{} -> guest :{guest_port}", forward.host_addr()),
+            );
+            forward
+        };
+        forwards.push(forward);
+    }
+    eprintln!("Press Ctrl-C to stop");
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => Ok(()),
+        (result, _, _) = select_all(forwards.iter_mut().map(|f| Box::pin(f.wait()))) => {
+            result?;
+            Ok(())
+        }
+    }
+}
+
+/// Parse `HOST_PORT:GUEST_PORT` or a single `PORT`.
+fn parse_port_pair(s: &str) -> Result<(u16, u16), String> {
+    let parse = |p: &str| {
+        p.parse::<u16>()
+            .map_err(|_| format!("invalid port '{p}' in '{s}'"))
+    };
+    match s.split_once(':') {
+        Some((host, guest)) => Ok((parse(host)?, parse(guest)?)),
+        None => {
+            let port = parse(s)?;
+            Ok((port, port))
+        }
+    }
+}
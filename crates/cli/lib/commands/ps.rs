@@ -121,13 +121,16 @@ fn status_row(handle: &SandboxHandle) -> StatusRow {
         .as_ref()
         .map(format_ports)
         .unwrap_or_else(|| "-".to_string());
-    let status = format!("{:?}", handle.status());
+    let mut status = ui::format_status(&format!("{:?}", handle.status()));
+    if let Some(health) = handle.health() {
+        status = format!("{status} ({})", ui::format_health(&health.to_string()));
+    }
 
     StatusRow {
         name: handle.name().to_string(),
         image,
         command,
-        status: ui::format_status(&status),
+        status,
         ports,
     }
 }
@@ -139,6 +142,7 @@ fn status_json(handle: &SandboxHandle) -> serde_json::Value {
     serde_json::json!({
         "name": handle.name(),
         "status": status,
+        "health": handle.health(),
         "image": config.as_ref().map(extract_image_raw).unwrap_or_else(|| "-".to_string()),
         "command": config.as_ref().map(format_command_raw).unwrap_or_else(|| "-".to_string()),
         "ports": config.as_ref().map(format_ports_raw).unwrap_or_default(),
@@ -0,0 +1,138 @@
+//! `msb ps-guest` command — list or signal processes inside a sandbox.
+
+use std::time::Duration;
+
+use clap::Args;
+use microsandbox::sandbox::{GuestProcessStats, Sandbox, SandboxStatus};
+
+use crate::ui;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Signal names accepted by `--signal`, without the `SIG` prefix.
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("TERM", libc::SIGTERM),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+];
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// List processes inside a running sandbox, or signal them by PID.
+#[derive(Debug, Args)]
+pub struct PsGuestArgs {
+    /// Sandbox to inspect.
+    pub name: String,
+
+    /// Only show processes owned by this user name or UID.
+    #[arg(short, long)]
+    pub user: Option<String>,
+
+    /// Send a signal to these PIDs instead of listing processes.
+    #[arg(long, value_name = "PID", value_delimiter = ',')]
+    pub kill: Vec<i32>,
+
+    /// Signal to send with --kill, by name (TERM, SIGKILL) or number.
+    #[arg(short, long, value_name = "SIGNAL", default_value = "TERM", value_parser = parse_signal, requires = "kill")]
+    pub signal: i32,
+
+    /// Output format (json).
+    #[arg(long, value_name = "FORMAT", value_parser = ["json"])]
+    pub format: Option<String>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb ps-guest` command.
+pub async fn run(args: PsGuestArgs) -> anyhow::Result<()> {
+    let handle = Sandbox::get(&args.name).await?;
+    if !matches!(
+        handle.status(),
+        SandboxStatus::Running | SandboxStatus::Draining
+    ) {
+        anyhow::bail!("sandbox '{}' is not running", args.name);
+    }
+    let sandbox = handle.connect().await?;
+
+    if !args.kill.is_empty() {
+        let mut failed = false;
+        for &pid in &args.kill {
+            match sandbox.signal_pid(pid, args.signal).await {
+                Ok(()) => ui::success("Signalled", &pid.to_string()),
+                Err(e) => {
+                    ui::error(&format!("{pid}: {e}"));
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            anyhow::bail!("failed to signal some processes");
+        }
+        return Ok(());
+    }
+
+    let mut processes = sandbox.processes().await?;
+    if let Some(user) = &args.user {
+        processes.retain(|p| p.user == *user || p.uid.to_string() == *user);
+    }
+
+    if args.format.as_deref() == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&processes)?);
+        return Ok(());
+    }
+
+    print_processes(&processes);
+    Ok(())
+}
+
+fn print_processes(processes: &[GuestProcessStats]) {
+    let mut table = ui::Table::new(&["PID", "PPID", "USER", "CPU", "RSS", "TIME", "COMMAND"]);
+    for process in processes {
+        let user = if process.user.is_empty() {
+            process.uid.to_string()
+        } else {
+            process.user.clone()
+        };
+        let command = if process.cmdline.is_empty() {
+            format!("[{}]", process.name)
+        } else {
+            process.cmdline.clone()
+        };
+        table.add_row(vec![
+            process.pid.to_string(),
+            process.ppid.to_string(),
+            user,
+            format!("{:.1}%", process.cpu_percent),
+            ui::format_bytes(process.rss_bytes),
+            ui::format_duration(Duration::from_millis(process.cpu_time_ms)),
+            command,
+        ]);
+    }
+    table.print();
+}
+
+/// Parse a signal name (`TERM`, `SIGTERM`, case-insensitive) or number.
+fn parse_signal(s: &str) -> Result<i32, String> {
+    if let Ok(n) = s.parse::<i32>() {
+        return Ok(n);
+    }
+    let upper = s.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|&(_, n)| n)
+        .ok_or_else(|| format!("unknown signal '{s}'"))
+}
@@ -19,6 +19,10 @@ pub struct PullArgs {
     #[arg(short, long)]
     pub force: bool,
 
+    /// Image platform to pull (e.g. linux/amd64, linux/arm64). Defaults to the host.
+    #[arg(long, value_name = "OS/ARCH")]
+    pub platform: Option<String>,
+
     /// Suppress progress output.
     #[arg(short, long)]
     pub quiet: bool,
@@ -13,9 +13,13 @@ use crate::ui;
 #[derive(Debug, Args)]
 pub struct RemoveArgs {
     /// Sandbox(es) to remove.
-    #[arg(required = true)]
+    #[arg(required_unless_present = "filter", conflicts_with = "filter")]
     pub names: Vec<String>,
 
+    /// Remove every sandbox matching a label selector (e.g. label=session=abc).
+    #[arg(long, value_name = "FILTER")]
+    pub filter: Vec<String>,
+
     /// Stop the sandbox if running, then remove it.
     #[arg(short, long)]
     pub force: bool,
@@ -31,9 +35,24 @@ pub struct RemoveArgs {
 
 /// Execute the `msb remove` command.
 pub async fn run(args: RemoveArgs) -> anyhow::Result<()> {
+    let names = if args.filter.is_empty() {
+        args.names.clone()
+    } else {
+        let selector = ui::parse_label_filters(&args.filter).map_err(anyhow::Error::msg)?;
+        let names: Vec<_> = Sandbox::list_matching(&selector)
+            .await?
+            .iter()
+            .map(|h| h.name().to_string())
+            .collect();
+        if names.is_empty() && !args.quiet {
+            eprintln!("No sandboxes match the filter.");
+        }
+        names
+    };
+
     let mut failed = false;
 
-    for name in &args.names {
+    for name in &names {
         if args.force {
             // Kill the sandbox first if it's running.
             if let Ok(mut handle) = Sandbox::get(name).await {
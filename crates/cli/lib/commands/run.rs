@@ -271,7 +271,7 @@ async fn exec_in_sandbox(
         Ok(if output.status().success {
             0
         } else {
-            output.status().code
+            output.status().shell_code()
         })
     }
 }
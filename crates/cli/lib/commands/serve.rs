@@ -0,0 +1,94 @@
+//! `msb serve` command — expose sandboxes to remote clients.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use microsandbox::gateway::{DEFAULT_GATEWAY_ADDR, GatewayServer};
+
+use crate::ui;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Environment variable holding the gateway token.
+const TOKEN_ENV: &str = "MSB_GATEWAY_TOKEN";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Serve this host's sandboxes to remote clients over TCP.
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Address to listen on.
+    #[arg(long, value_name = "ADDR", default_value = DEFAULT_GATEWAY_ADDR)]
+    pub listen: String,
+
+    /// Read the bearer token clients must present from this file.
+    /// Defaults to the MSB_GATEWAY_TOKEN environment variable.
+    #[arg(long, value_name = "PATH")]
+    pub token_file: Option<PathBuf>,
+
+    /// Serve TLS with this PEM certificate chain.
+    #[arg(long, value_name = "PATH", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// Private key for --tls-cert.
+    #[arg(long, value_name = "PATH", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Require client certificates signed by a CA in this PEM file (mutual TLS).
+    #[arg(long, value_name = "PATH", requires = "tls_cert")]
+    pub tls_client_ca: Option<PathBuf>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb serve` command.
+pub async fn run(args: ServeArgs) -> anyhow::Result<()> {
+    let token = match &args.token_file {
+        Some(path) => Some(
+            std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("read {}: {e}", path.display()))?
+                .trim()
+                .to_string(),
+        ),
+        None => std::env::var(TOKEN_ENV).ok(),
+    }
+    .filter(|t| !t.is_empty());
+
+    if token.is_none() && args.tls_client_ca.is_none() {
+        anyhow::bail!(
+            "refusing to serve without authentication: set {TOKEN_ENV}, pass --token-file, or pass --tls-client-ca"
+        );
+    }
+
+    let mut server = GatewayServer::bind(args.listen.as_str()).await?;
+    if let Some(token) = token {
+        server = server.token(token);
+    }
+    let scheme = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => {
+            server = server.tls(cert, key, args.tls_client_ca.as_deref())?;
+            "tls"
+        }
+        _ => "tcp",
+    };
+
+    let addr = server.local_addr()?;
+    if scheme == "tcp" && !addr.ip().is_loopback() {
+        ui::warn("serving without TLS on a non-loopback address; the token is sent in clear text");
+    }
+    eprintln!("Serving sandboxes on {scheme}://{addr} (Ctrl-C to stop)");
+
+    server
+        .serve_with_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 //! `msb stop` command — stop a running sandbox.
 
 use clap::Args;
-use microsandbox::sandbox::Sandbox;
+use microsandbox::sandbox::{Sandbox, SandboxHandle, SandboxStatus};
 
 use crate::ui;
 
@@ -13,7 +13,12 @@ use crate::ui;
 #[derive(Debug, Args)]
 pub struct StopArgs {
     /// Sandbox to stop.
-    pub name: String,
+    #[arg(required_unless_present = "filter", conflicts_with = "filter")]
+    pub name: Option<String>,
+
+    /// Stop every running sandbox matching a label selector (e.g. label=session=abc).
+    #[arg(long, value_name = "FILTER")]
+    pub filter: Vec<String>,
 
     /// Immediately kill the sandbox without graceful shutdown.
     #[arg(short, long)]
@@ -34,13 +39,55 @@ pub struct StopArgs {
 
 /// Execute the `msb stop` command.
 pub async fn run(args: StopArgs) -> anyhow::Result<()> {
+    let Some(name) = args.name.as_deref() else {
+        return run_filtered(&args).await;
+    };
+
+    let mut handle = Sandbox::get(name).await?;
+    stop_one(&mut handle, &args).await
+}
+
+/// Stop every running sandbox that matches `--filter`.
+async fn run_filtered(args: &StopArgs) -> anyhow::Result<()> {
+    let selector = ui::parse_label_filters(&args.filter).map_err(anyhow::Error::msg)?;
+    let mut handles: Vec<_> = Sandbox::list_matching(&selector)
+        .await?
+        .into_iter()
+        .filter(|h| matches!(h.status(), SandboxStatus::Running | SandboxStatus::Draining))
+        .collect();
+
+    if handles.is_empty() {
+        if !args.quiet {
+            eprintln!("No running sandboxes match the filter.");
+        }
+        return Ok(());
+    }
+
+    let mut failed = false;
+    for handle in &mut handles {
+        if let Err(e) = stop_one(handle, args).await {
+            if !args.quiet {
+                ui::error(&format!("{e}"));
+            }
+            failed = true;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Stop a single sandbox, honoring `--force` and `--timeout`.
+async fn stop_one(handle: &mut SandboxHandle, args: &StopArgs) -> anyhow::Result<()> {
     let spinner = if args.quiet {
         ui::Spinner::quiet()
     } else {
-        ui::Spinner::start("Stopping", &args.name)
+        ui::Spinner::start("Stopping", handle.name())
     };
 
-    let mut handle = Sandbox::get(&args.name).await?;
     let result = if args.force {
         handle.kill().await
     } else if let Some(timeout_secs) = args.timeout {
@@ -0,0 +1,24 @@
+//! `msb supervise` command — restart a detached sandbox per its restart policy.
+
+use clap::Args;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Restart a sandbox according to its restart policy.
+#[derive(Debug, Args)]
+pub struct SuperviseArgs {
+    /// Sandbox to supervise.
+    pub name: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb supervise` command.
+pub async fn run(args: SuperviseArgs) -> anyhow::Result<()> {
+    microsandbox::sandbox::supervise(&args.name).await?;
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! `msb top` command — live view of processes and resources inside a sandbox.
+
+use std::{io::IsTerminal, time::Duration};
+
+use clap::Args;
+use console::Term;
+use microsandbox::sandbox::{GuestProcessStats, GuestStats, Sandbox, SandboxStatus};
+
+use crate::ui;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Show processes and resource usage inside a running sandbox.
+#[derive(Debug, Args)]
+pub struct TopArgs {
+    /// Sandbox to inspect.
+    pub name: String,
+
+    /// Refresh interval (e.g. 2s, 1m).
+    #[arg(short, long, value_name = "DURATION", default_value = "2s")]
+    pub interval: String,
+
+    /// Sort processes by this column.
+    #[arg(long, value_name = "COLUMN", default_value = "cpu", value_parser = ["cpu", "mem", "pid"])]
+    pub sort: String,
+
+    /// Maximum number of processes to show.
+    #[arg(short = 'n', long, value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// Print a single snapshot and exit.
+    #[arg(long)]
+    pub once: bool,
+
+    /// Output format (json). Implies --once.
+    #[arg(long, value_name = "FORMAT", value_parser = ["json"])]
+    pub format: Option<String>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb top` command.
+pub async fn run(args: TopArgs) -> anyhow::Result<()> {
+    let handle = Sandbox::get(&args.name).await?;
+    if !matches!(
+        handle.status(),
+        SandboxStatus::Running | SandboxStatus::Draining
+    ) {
+        anyhow::bail!("sandbox '{}' is not running", args.name);
+    }
+    let sandbox = handle.connect().await?;
+
+    if args.format.as_deref() == Some("json") {
+        let stats = sandbox.guest_stats().await?;
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    let interval = Duration::from_secs(super::common::parse_duration_secs(&args.interval)?.max(1));
+    let term = Term::stdout();
+    let live = !args.once && std::io::stdout().is_terminal();
+
+    // The first snapshot reports lifetime CPU averages; when refreshing, take
+    // a priming sample so the first screen already shows recent usage.
+    if live {
+        sandbox.guest_stats().await?;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    loop {
+        let stats = sandbox.guest_stats().await?;
+
+        if live {
+            term.clear_screen()?;
+        }
+        print_stats(&args, &stats);
+
+        if args.once {
+            return Ok(());
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+fn print_stats(args: &TopArgs, stats: &GuestStats) {
+    let [one, five, fifteen] = stats.load_average;
+    println!(
+        "{}  up {}  cpus {}  load {one:.2} {five:.2} {fifteen:.2}",
+        args.name,
+        ui::format_duration(Duration::from_secs_f64(stats.uptime_secs)),
+        stats.cpu_count,
+    );
+
+    let memory = &stats.memory;
+    println!(
+        "mem  {} used / {} total  ({} available, {} cached)",
+        ui::format_bytes(memory.total_bytes.saturating_sub(memory.available_bytes)),
+        ui::format_bytes(memory.total_bytes),
+        ui::format_bytes(memory.available_bytes),
+        ui::format_bytes(memory.cached_bytes + memory.buffers_bytes),
+    );
+    if memory.swap_total_bytes > 0 {
+        println!(
+            "swap {} used / {} total",
+            ui::format_bytes(memory.swap_total_bytes - memory.swap_free_bytes),
+            ui::format_bytes(memory.swap_total_bytes),
+        );
+    }
+
+    for fs in &stats.filesystems {
+        println!(
+            "disk {}  {} used / {} total  ({})",
+            fs.mount_point,
+            ui::format_bytes(fs.used_bytes),
+            ui::format_bytes(fs.total_bytes),
+            fs.fs_type,
+        );
+    }
+    println!();
+
+    let mut processes: Vec<&GuestProcessStats> = stats.processes.iter().collect();
+    match args.sort.as_str() {
+        "mem" => processes.sort_by_key(|p| std::cmp::Reverse(p.rss_bytes)),
+        "pid" => processes.sort_by_key(|p| p.pid),
+        _ => processes.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent)),
+    }
+    if let Some(limit) = args.limit {
+        processes.truncate(limit);
+    }
+
+    let mut table = ui::Table::new(&[
+        "PID", "PPID", "UID", "STATE", "CPU", "RSS", "THREADS", "TIME", "COMMAND",
+    ]);
+    for process in processes {
+        let command = if process.cmdline.is_empty() {
+            format!("[{}]", process.name)
+        } else {
+            process.cmdline.clone()
+        };
+        table.add_row(vec![
+            process.pid.to_string(),
+            process.ppid.to_string(),
+            process.uid.to_string(),
+            process.state.clone(),
+            format!("{:.1}%", process.cpu_percent),
+            ui::format_bytes(process.rss_bytes),
+            process.threads.to_string(),
+            ui::format_duration(Duration::from_millis(process.cpu_time_ms)),
+            command,
+        ]);
+    }
+    table.print();
+}
@@ -0,0 +1,219 @@
+//! `msb up` command — create the sandboxes described by a compose file.
+
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use clap::Args;
+use microsandbox::{
+    LabelSelector, MicrosandboxError, Volume,
+    sandbox::{Sandbox, SandboxStatus},
+};
+
+use super::common::parse_duration_secs;
+use crate::{
+    compose::{self, ComposeFile, Condition, PROJECT_LABEL},
+    ui,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Create the sandboxes in a compose file, replacing existing ones.
+#[derive(Debug, Args)]
+pub struct UpArgs {
+    /// Compose file (default: sandbox.yaml, sandbox.yml or sandbox.toml).
+    #[arg(short, long)]
+    pub file: Option<PathBuf>,
+
+    /// Project name (default: the file's `name`, or its directory name).
+    #[arg(short, long)]
+    pub project: Option<String>,
+
+    /// How long to wait for a dependency to become healthy (e.g. 30s, 2m).
+    #[arg(long, default_value = "2m")]
+    pub wait_timeout: String,
+
+    /// Remove sandboxes in the project that are no longer in the file.
+    #[arg(long)]
+    pub remove_orphans: bool,
+
+    /// Suppress progress output.
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the `msb up` command.
+pub async fn run(args: UpArgs) -> anyhow::Result<()> {
+    let path = compose::resolve_file(args.file.as_deref())?;
+    let compose = ComposeFile::load(&path, args.project.clone())?;
+    let wait_timeout = Duration::from_secs(parse_duration_secs(&args.wait_timeout)?);
+
+    handle_orphans(&compose, &args).await?;
+    create_volumes(&compose).await?;
+    #[cfg(feature = "net")]
+    create_networks(&compose).await?;
+
+    // Keep every sandbox we start attached until the whole group is up so
+    // dependents can wait on their health, then detach them all — including
+    // on failure, so the sandboxes that did start can be inspected.
+    let mut started = HashMap::new();
+    let result = start_all(&compose, wait_timeout, args.quiet, &mut started).await;
+    for (_, sandbox) in started {
+        sandbox.detach().await;
+    }
+    result
+}
+
+/// Start every service in dependency order.
+async fn start_all<'a>(
+    compose: &'a ComposeFile,
+    wait_timeout: Duration,
+    quiet: bool,
+    started: &mut HashMap<&'a str, Sandbox>,
+) -> anyhow::Result<()> {
+    for service in compose.startup_order()? {
+        for (dep, condition) in compose.sandboxes[service].dependencies() {
+            if condition == Condition::Healthy {
+                wait_healthy(compose, &started[dep], wait_timeout, quiet).await?;
+            }
+        }
+
+        let sandbox = create(compose, service, quiet).await?;
+        started.insert(service, sandbox);
+    }
+
+    Ok(())
+}
+
+/// Create (or replace) the sandbox for one service.
+async fn create(compose: &ComposeFile, service: &str, quiet: bool) -> anyhow::Result<Sandbox> {
+    let image = &compose.sandboxes[service].image;
+    let builder = compose.builder(service)?.replace();
+
+    let (mut progress, task) = builder.create_detached_with_pull_progress()?;
+    let mut display = if quiet {
+        ui::PullProgressDisplay::quiet(image)
+    } else {
+        ui::PullProgressDisplay::new(image)
+    };
+    while let Some(event) = progress.recv().await {
+        display.handle_event(event);
+    }
+    display.finish();
+
+    let sandbox = task
+        .await
+        .map_err(|e| anyhow::anyhow!("create task panicked: {e}"))??;
+    if !quiet {
+        ui::success("Started", sandbox.name());
+    }
+    Ok(sandbox)
+}
+
+async fn wait_healthy(
+    compose: &ComposeFile,
+    sandbox: &Sandbox,
+    timeout: Duration,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let spinner = if quiet {
+        ui::Spinner::quiet()
+    } else {
+        ui::Spinner::start("Waiting for", sandbox.name())
+    };
+
+    match sandbox.wait_healthy(timeout).await {
+        Ok(()) => {
+            spinner.finish_success("Healthy");
+            Ok(())
+        }
+        Err(e) => {
+            spinner.finish_error();
+            Err(anyhow::anyhow!(
+                "project '{}' dependency not ready: {e}",
+                compose.project()
+            ))
+        }
+    }
+}
+
+/// Create named volumes that don't exist yet, labelled with the project so
+/// `msb down --volumes` can remove them.
+async fn create_volumes(compose: &ComposeFile) -> anyhow::Result<()> {
+    for name in compose.named_volumes() {
+        match Volume::get(name).await {
+            Ok(_) => {}
+            Err(MicrosandboxError::VolumeNotFound(_)) => {
+                Volume::builder(name)
+                    .label(PROJECT_LABEL, compose.project())
+                    .create()
+                    .await?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Create private networks that don't exist yet, labelled with the project
+/// so `msb down` removes them.
+#[cfg(feature = "net")]
+async fn create_networks(compose: &ComposeFile) -> anyhow::Result<()> {
+    use microsandbox::Network;
+
+    for name in compose.networks() {
+        match Network::get(name).await {
+            Ok(_) => {}
+            Err(MicrosandboxError::NetworkNotFound(_)) => {
+                Network::builder(name)
+                    .label(PROJECT_LABEL, compose.project())
+                    .create()
+                    .await?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Warn about, or remove, project sandboxes no longer in the file.
+async fn handle_orphans(compose: &ComposeFile, args: &UpArgs) -> anyhow::Result<()> {
+    let selector = LabelSelector::parse(&format!("{PROJECT_LABEL}={}", compose.project()))?;
+    let expected: Vec<_> = compose
+        .sandboxes
+        .keys()
+        .map(|service| compose.sandbox_name(service))
+        .collect();
+
+    for mut handle in Sandbox::list_matching(&selector).await? {
+        if expected.iter().any(|name| name == handle.name()) {
+            continue;
+        }
+        if !args.remove_orphans {
+            if !args.quiet {
+                ui::warn(&format!(
+                    "sandbox '{}' is not in the compose file (use --remove-orphans to remove it)",
+                    handle.name()
+                ));
+            }
+            continue;
+        }
+
+        if matches!(
+            handle.status(),
+            SandboxStatus::Running | SandboxStatus::Draining
+        ) {
+            handle.kill().await?;
+        }
+        handle.remove().await?;
+        if !args.quiet {
+            ui::success("Removed", handle.name());
+        }
+    }
+
+    Ok(())
+}
@@ -45,6 +45,10 @@ pub struct VolumeCreateArgs {
     #[arg(long)]
     pub size: Option<String>,
 
+    /// Set a label on the volume (KEY=value).
+    #[arg(long)]
+    pub label: Vec<String>,
+
     /// Suppress output.
     #[arg(short, long)]
     pub quiet: bool,
@@ -53,6 +57,10 @@ pub struct VolumeCreateArgs {
 /// Arguments for `msb volume list`.
 #[derive(Debug, Args)]
 pub struct VolumeListArgs {
+    /// Filter by label selector (e.g. label=session=abc, label='tier in (web,api)').
+    #[arg(long, value_name = "FILTER")]
+    pub filter: Vec<String>,
+
     /// Output format (json).
     #[arg(long, value_name = "FORMAT", value_parser = ["json"])]
     pub format: Option<String>,
@@ -103,6 +111,11 @@ async fn create(args: VolumeCreateArgs) -> anyhow::Result<()> {
         builder = builder.quota(mib);
     }
 
+    for label_str in &args.label {
+        let (k, v) = ui::parse_label(label_str).map_err(anyhow::Error::msg)?;
+        builder = builder.label(k, v);
+    }
+
     builder.create().await?;
 
     if !args.quiet {
@@ -113,7 +126,8 @@ async fn create(args: VolumeCreateArgs) -> anyhow::Result<()> {
 }
 
 async fn list(args: VolumeListArgs) -> anyhow::Result<()> {
-    let volumes = Volume::list().await?;
+    let selector = ui::parse_label_filters(&args.filter).map_err(anyhow::Error::msg)?;
+    let volumes = Volume::list_matching(&selector).await?;
 
     if args.format.as_deref() == Some("json") {
         let entries: Vec<serde_json::Value> = volumes
@@ -123,6 +137,7 @@ async fn list(args: VolumeListArgs) -> anyhow::Result<()> {
                     "name": v.name(),
                     "quota_mib": v.quota_mib(),
                     "used_bytes": v.used_bytes(),
+                    "labels": v.labels().iter().cloned().collect::<std::collections::HashMap<_, _>>(),
                     "created_at": v.created_at().map(|dt| ui::format_datetime(&dt)),
                 })
             })
@@ -0,0 +1,763 @@
+//! Compose files: declarative groups of sandboxes managed by `msb up` and
+//! `msb down`.
+//!
+//! A compose file names a project and the sandboxes that belong to it:
+//!
+//! ```yaml
+//! name: shop
+//! sandboxes:
+//!   db:
+//!     image: postgres:16
+//!     env: { POSTGRES_PASSWORD: dev }
+//!     volumes: ["pgdata:/var/lib/postgresql/data"]
+//!     health_check:
+//!       probe: { type: tcp, port: 5432 }
+//!   app:
+//!     image: ./app-rootfs
+//!     ports: ["8080:80"]
+//!     depends_on:
+//!       db: { condition: healthy }
+//! ```
+//!
+//! The same structure can be written as TOML. Each sandbox is named
+//! `<project>-<service>` and labelled with [`PROJECT_LABEL`] and
+//! [`SERVICE_LABEL`] so the group can be found again without the file.
+//! Services that set the same `network` share a private network and reach
+//! each other by service name.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use microsandbox::sandbox::{HealthCheck, RestartPolicy, Sandbox, SandboxBuilder};
+use serde::Deserialize;
+
+use crate::{commands::common, ui};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Label recording the compose project a sandbox or volume belongs to.
+pub const PROJECT_LABEL: &str = "microsandbox.compose.project";
+
+/// Label recording the service a sandbox was created for.
+pub const SERVICE_LABEL: &str = "microsandbox.compose.service";
+
+/// Files looked up in the current directory when no file is given.
+pub const DEFAULT_FILES: &[&str] = &["sandbox.yaml", "sandbox.yml", "sandbox.toml"];
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A parsed compose file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComposeFile {
+    /// Project name. Defaults to the name of the directory holding the file.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Sandboxes in the project, keyed by service name.
+    pub sandboxes: BTreeMap<String, ServiceSpec>,
+
+    /// Resolved project name.
+    #[serde(skip)]
+    project: String,
+
+    /// Directory relative paths in the file are resolved against.
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+/// Configuration for one sandbox in a compose file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceSpec {
+    /// Image to boot (e.g. `python:3.12`, `./rootfs`).
+    pub image: String,
+
+    /// Sandbox name. Defaults to `<project>-<service>`.
+    pub name: Option<String>,
+
+    /// Number of virtual CPUs.
+    pub cpus: Option<u8>,
+
+    /// Guest memory, in MiB or as a size string (e.g. `512M`, `1G`).
+    pub memory: Option<Scalar>,
+
+    /// Working directory for commands.
+    pub workdir: Option<String>,
+
+    /// Shell for scripts and interactive sessions.
+    pub shell: Option<String>,
+
+    /// User identity inside the sandbox.
+    pub user: Option<String>,
+
+    /// Guest hostname.
+    pub hostname: Option<String>,
+
+    /// Image entrypoint override.
+    pub entrypoint: Option<Vec<String>>,
+
+    /// Environment variables, as a map or a list of `KEY=value` entries.
+    pub env: Env,
+
+    /// Extra labels.
+    pub labels: BTreeMap<String, String>,
+
+    /// Volume mounts (`SOURCE:DEST`). Relative host paths are resolved
+    /// against the compose file's directory; named volumes are created on
+    /// demand.
+    pub volumes: Vec<String>,
+
+    /// Tmpfs mounts (`PATH` or `PATH:SIZE`).
+    pub tmpfs: Vec<String>,
+
+    /// Published ports (`HOST:GUEST[/udp]`).
+    pub ports: Vec<String>,
+
+    /// Network policy: `none`, `public-only`, `nonlocal` or `allow-all`.
+    pub network_policy: Option<String>,
+
+    /// Disable networking entirely.
+    pub no_network: bool,
+
+    /// Private network to join. Other services on the same network reach
+    /// this one by its service name. Created on demand.
+    pub network: Option<String>,
+
+    /// Secrets whose values are read from the host environment.
+    pub secrets: Vec<SecretRef>,
+
+    /// Scripts available at `/.msb/scripts/<name>`, keyed by name.
+    pub scripts: BTreeMap<String, String>,
+
+    /// Restart policy (e.g. `on-failure:5`).
+    pub restart: Option<String>,
+
+    /// Health check, in the same form as [`HealthCheck`].
+    pub health_check: Option<HealthCheck>,
+
+    /// Services that must be started before this one.
+    pub depends_on: DependsOn,
+}
+
+/// A secret injected by placeholder, whose value comes from the host.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecretRef {
+    /// Environment variable holding the placeholder inside the sandbox.
+    pub env: String,
+
+    /// Host environment variable holding the real value. Defaults to `env`.
+    #[serde(default)]
+    pub from_env: Option<String>,
+
+    /// Hosts the real value may be sent to.
+    pub hosts: Vec<String>,
+}
+
+/// Environment variables for a service.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Env {
+    /// `KEY: value` pairs.
+    Map(BTreeMap<String, Scalar>),
+
+    /// `KEY=value` entries, or bare `KEY` to copy the host's value.
+    List(Vec<String>),
+}
+
+/// Dependencies of a service.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum DependsOn {
+    /// Service names, each waited on until started.
+    List(Vec<String>),
+
+    /// Service names with the condition to wait for.
+    Map(BTreeMap<String, Dependency>),
+}
+
+/// How a service depends on another.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Dependency {
+    /// What to wait for before starting the dependent service.
+    pub condition: Condition,
+}
+
+/// Readiness a dependency must reach before its dependents start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// The dependency has booted.
+    #[default]
+    Started,
+
+    /// The dependency's health check has passed.
+    Healthy,
+}
+
+/// A scalar value written without quotes, such as `PORT: 5432`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Scalar {
+    /// A boolean.
+    Bool(bool),
+
+    /// An integer.
+    Integer(i64),
+
+    /// A floating-point number.
+    Float(f64),
+
+    /// A string.
+    String(String),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ComposeFile {
+    /// Load and validate a compose file. Files ending in `.toml` are parsed
+    /// as TOML, everything else as YAML.
+    pub fn load(path: &Path, project: Option<String>) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read '{}': {e}", path.display()))?;
+        let base_dir = std::path::absolute(path)?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+        Self::parse(&text, is_toml, base_dir, project)
+            .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))
+    }
+
+    /// Parse and validate a compose file's contents.
+    pub fn parse(
+        text: &str,
+        is_toml: bool,
+        base_dir: PathBuf,
+        project: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let mut compose: Self = if is_toml {
+            toml::from_str(text)?
+        } else {
+            serde_yaml::from_str(text)?
+        };
+
+        compose.project = match project.or_else(|| compose.name.clone()) {
+            Some(project) => project,
+            None => base_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .ok_or_else(|| anyhow::anyhow!("cannot infer a project name; set `name`"))?,
+        };
+        compose.base_dir = base_dir;
+        compose.validate()?;
+        Ok(compose)
+    }
+
+    /// The project name.
+    pub fn project(&self) -> &str {
+        &self.project
+    }
+
+    /// The sandbox name for a service.
+    pub fn sandbox_name(&self, service: &str) -> String {
+        match self
+            .sandboxes
+            .get(service)
+            .and_then(|spec| spec.name.clone())
+        {
+            Some(name) => name,
+            None => format!("{}-{service}", self.project),
+        }
+    }
+
+    /// Service names ordered so that every service comes after its
+    /// dependencies. Ties are broken alphabetically.
+    pub fn startup_order(&self) -> anyhow::Result<Vec<&str>> {
+        let mut order = Vec::with_capacity(self.sandboxes.len());
+        let mut pending: BTreeSet<&str> = self.sandboxes.keys().map(String::as_str).collect();
+
+        while !pending.is_empty() {
+            let ready = pending.iter().copied().find(|service| {
+                self.sandboxes[*service]
+                    .dependencies()
+                    .iter()
+                    .all(|(dep, _)| !pending.contains(dep))
+            });
+            let Some(ready) = ready else {
+                let cycle: Vec<_> = pending.into_iter().collect();
+                anyhow::bail!("dependency cycle between: {}", cycle.join(", "));
+            };
+            pending.remove(ready);
+            order.push(ready);
+        }
+
+        Ok(order)
+    }
+
+    /// Names of the named volumes mounted by any service.
+    pub fn named_volumes(&self) -> BTreeSet<&str> {
+        self.sandboxes
+            .values()
+            .flat_map(|spec| &spec.volumes)
+            .filter_map(|spec| spec.split_once(':').map(|(source, _)| source))
+            .filter(|source| !is_host_path(source))
+            .collect()
+    }
+
+    /// Names of the private networks joined by any service.
+    pub fn networks(&self) -> BTreeSet<&str> {
+        self.sandboxes
+            .values()
+            .filter_map(|spec| spec.network.as_deref())
+            .collect()
+    }
+
+    /// Build the sandbox for a service, labelled as part of the project.
+    pub fn builder(&self, service: &str) -> anyhow::Result<SandboxBuilder> {
+        let spec = self
+            .sandboxes
+            .get(service)
+            .ok_or_else(|| anyhow::anyhow!("unknown service '{service}'"))?;
+
+        let mut builder = Sandbox::builder(self.sandbox_name(service))
+            .image(self.resolve_image(&spec.image))
+            .labels(&spec.labels)
+            .label(PROJECT_LABEL, &self.project)
+            .label(SERVICE_LABEL, service);
+
+        if let Some(cpus) = spec.cpus {
+            builder = builder.cpus(cpus);
+        }
+        if let Some(ref memory) = spec.memory {
+            builder = builder.memory(
+                ui::parse_size_mib(&memory.to_string())
+                    .map_err(|e| anyhow::anyhow!("service '{service}' memory: {e}"))?,
+            );
+        }
+        if let Some(ref workdir) = spec.workdir {
+            builder = builder.workdir(workdir);
+        }
+        if let Some(ref shell) = spec.shell {
+            builder = builder.shell(shell);
+        }
+        if let Some(ref user) = spec.user {
+            builder = builder.user(user);
+        }
+        if let Some(ref hostname) = spec.hostname {
+            builder = builder.hostname(hostname);
+        }
+        if let Some(ref entrypoint) = spec.entrypoint {
+            builder = builder.entrypoint(entrypoint);
+        }
+
+        match &spec.env {
+            Env::Map(vars) => {
+                builder = builder.envs(vars.iter().map(|(k, v)| (k, v.to_string())));
+            }
+            Env::List(vars) => {
+                for var in vars {
+                    let (k, v) = ui::parse_env(var).map_err(anyhow::Error::msg)?;
+                    builder = builder.env(k, v);
+                }
+            }
+        }
+
+        for volume in &spec.volumes {
+            builder = common::apply_volume(builder, &self.resolve_volume(volume))?;
+        }
+        for tmpfs in &spec.tmpfs {
+            let (path, size) = common::parse_tmpfs(tmpfs)?;
+            builder = builder.volume(&path, |m| match size {
+                Some(size_mib) => m.tmpfs().size(size_mib),
+                None => m.tmpfs(),
+            });
+        }
+        builder = builder.scripts(&spec.scripts);
+
+        if let Some(ref restart) = spec.restart {
+            builder = builder.restart_policy(restart.parse::<RestartPolicy>()?);
+        }
+        if let Some(ref check) = spec.health_check {
+            builder = builder.health_check(check.clone());
+        }
+
+        #[cfg(feature = "net")]
+        {
+            builder = apply_network(builder, service, spec)?;
+        }
+
+        Ok(builder)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        validate_name("project", &self.project)?;
+        if self.sandboxes.is_empty() {
+            anyhow::bail!("no sandboxes defined");
+        }
+
+        for (service, spec) in &self.sandboxes {
+            validate_name("service", service)?;
+            if spec.image.is_empty() {
+                anyhow::bail!("service '{service}' has no image");
+            }
+            for (dep, condition) in spec.dependencies() {
+                let Some(target) = self.sandboxes.get(dep) else {
+                    anyhow::bail!("service '{service}' depends on unknown service '{dep}'");
+                };
+                if dep == service {
+                    anyhow::bail!("service '{service}' depends on itself");
+                }
+                if condition == Condition::Healthy && target.health_check.is_none() {
+                    anyhow::bail!(
+                        "service '{service}' waits for '{dep}' to be healthy, but '{dep}' has no health check"
+                    );
+                }
+            }
+            if let Some(ref network) = spec.network {
+                validate_name("network", network)?;
+                if spec.no_network {
+                    anyhow::bail!("service '{service}' sets both 'network' and 'no_network'");
+                }
+            }
+            for secret in &spec.secrets {
+                if secret.hosts.is_empty() {
+                    anyhow::bail!(
+                        "service '{service}' secret '{}' has no allowed hosts",
+                        secret.env
+                    );
+                }
+            }
+        }
+
+        self.startup_order().map(drop)
+    }
+
+    /// Resolve a relative local image path against the file's directory.
+    fn resolve_image(&self, image: &str) -> String {
+        if image.starts_with("./") || image.starts_with("../") {
+            self.base_dir.join(image).display().to_string()
+        } else {
+            image.to_string()
+        }
+    }
+
+    /// Resolve a relative bind mount source against the file's directory.
+    fn resolve_volume(&self, spec: &str) -> String {
+        match spec.split_once(':') {
+            Some((source, guest)) if source.starts_with("./") || source.starts_with("../") => {
+                format!("{}:{guest}", self.base_dir.join(source).display())
+            }
+            _ => spec.to_string(),
+        }
+    }
+}
+
+impl ServiceSpec {
+    /// The services this one depends on, with the condition to wait for.
+    pub fn dependencies(&self) -> Vec<(&str, Condition)> {
+        match &self.depends_on {
+            DependsOn::List(names) => names
+                .iter()
+                .map(|name| (name.as_str(), Condition::Started))
+                .collect(),
+            DependsOn::Map(deps) => deps
+                .iter()
+                .map(|(name, dep)| (name.as_str(), dep.condition))
+                .collect(),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::List(Vec::new())
+    }
+}
+
+impl Default for DependsOn {
+    fn default() -> Self {
+        Self::List(Vec::new())
+    }
+}
+
+impl fmt::Display for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::Float(value) => write!(f, "{value}"),
+            Self::String(value) => f.write_str(value),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Find the compose file to use: `file` if given, otherwise the first of
+/// [`DEFAULT_FILES`] in the current directory.
+pub fn resolve_file(file: Option<&Path>) -> anyhow::Result<PathBuf> {
+    if let Some(file) = file {
+        return Ok(file.to_path_buf());
+    }
+
+    DEFAULT_FILES
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "no compose file found (looked for {}); pass --file",
+                DEFAULT_FILES.join(", ")
+            )
+        })
+}
+
+/// Apply ports, network policy, private network and secrets to the builder.
+#[cfg(feature = "net")]
+fn apply_network(
+    mut builder: SandboxBuilder,
+    service: &str,
+    spec: &ServiceSpec,
+) -> anyhow::Result<SandboxBuilder> {
+    for port in &spec.ports {
+        let (host, guest, udp) = common::parse_port(port)?;
+        builder = if udp {
+            builder.port_udp(host, guest)
+        } else {
+            builder.port(host, guest)
+        };
+    }
+
+    if spec.no_network {
+        builder = builder.disable_network();
+    }
+    if let Some(policy) = common::parse_network_policy(spec.network_policy.as_deref())? {
+        builder = builder.network(|n| n.policy(policy));
+    }
+    if let Some(ref network) = spec.network {
+        builder = builder.network(|n| n.attach(network).alias(service));
+    }
+
+    for secret in &spec.secrets {
+        let from_env = secret.from_env.as_deref().unwrap_or(&secret.env);
+        let value = std::env::var(from_env).map_err(|_| {
+            anyhow::anyhow!(
+                "secret '{}' reads environment variable '{from_env}', which is not set",
+                secret.env
+            )
+        })?;
+        builder = builder.secret(|mut s| {
+            s = s.env(&secret.env).value(value);
+            for host in &secret.hosts {
+                s = s.allow_host(host);
+            }
+            s
+        });
+    }
+
+    Ok(builder)
+}
+
+/// Whether a volume source is a host path rather than a named volume.
+fn is_host_path(source: &str) -> bool {
+    source.starts_with('/') || source.starts_with("./") || source.starts_with("../")
+}
+
+/// Project and service names become part of sandbox names and label
+/// selectors, so keep them to a conservative character set.
+fn validate_name(kind: &str, name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        anyhow::bail!("invalid {kind} name '{name}' (use letters, digits, '-', '_' or '.')");
+    }
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_yaml(text: &str) -> anyhow::Result<ComposeFile> {
+        ComposeFile::parse(text, false, PathBuf::from("/work/shop"), None)
+    }
+
+    #[test]
+    fn test_yaml_and_toml_parse_to_the_same_spec() {
+        let yaml = parse_yaml(
+            r#"
+sandboxes:
+  db:
+    image: postgres:16
+    memory: 1G
+    env: { POSTGRES_PORT: 5432 }
+"#,
+        )
+        .unwrap();
+        let toml = ComposeFile::parse(
+            r#"
+[sandboxes.db]
+image = "postgres:16"
+memory = "1G"
+env = { POSTGRES_PORT = 5432 }
+"#,
+            true,
+            PathBuf::from("/work/shop"),
+            None,
+        )
+        .unwrap();
+
+        for compose in [yaml, toml] {
+            assert_eq!(compose.project(), "shop");
+            assert_eq!(compose.sandbox_name("db"), "shop-db");
+            let db = &compose.sandboxes["db"];
+            assert_eq!(db.memory.as_ref().unwrap().to_string(), "1G");
+            let Env::Map(ref env) = db.env else {
+                panic!("expected env map");
+            };
+            assert_eq!(env["POSTGRES_PORT"].to_string(), "5432");
+        }
+    }
+
+    #[test]
+    fn test_startup_order_follows_dependencies() {
+        let compose = parse_yaml(
+            r#"
+name: shop
+sandboxes:
+  app:
+    image: app
+    depends_on: [db, mock]
+  db:
+    image: postgres
+    depends_on:
+      mock: { condition: started }
+  mock:
+    image: wiremock
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(compose.startup_order().unwrap(), ["mock", "db", "app"]);
+    }
+
+    #[test]
+    fn test_rejects_cycles_and_unknown_dependencies() {
+        let cycle = parse_yaml(
+            r#"
+sandboxes:
+  a: { image: x, depends_on: [b] }
+  b: { image: x, depends_on: [a] }
+"#,
+        );
+        assert!(cycle.unwrap_err().to_string().contains("cycle"));
+
+        let unknown = parse_yaml("sandboxes:\n  a: { image: x, depends_on: [b] }\n");
+        assert!(unknown.unwrap_err().to_string().contains("unknown service"));
+    }
+
+    #[test]
+    fn test_healthy_condition_requires_health_check() {
+        let missing = parse_yaml(
+            r#"
+sandboxes:
+  db: { image: postgres }
+  app:
+    image: app
+    depends_on: { db: { condition: healthy } }
+"#,
+        );
+        assert!(missing.unwrap_err().to_string().contains("no health check"));
+
+        let ok = parse_yaml(
+            r#"
+sandboxes:
+  db:
+    image: postgres
+    health_check: { probe: { type: tcp, port: 5432 } }
+  app:
+    image: app
+    depends_on: { db: { condition: healthy } }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ok.sandboxes["app"].dependencies(),
+            [("db", Condition::Healthy)]
+        );
+    }
+
+    #[test]
+    fn test_relative_paths_resolve_against_file_directory() {
+        let compose = parse_yaml(
+            r#"
+sandboxes:
+  app:
+    image: ./rootfs
+    volumes: ["./data:/data", "cache:/cache", "/abs:/abs"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(compose.resolve_image("./rootfs"), "/work/shop/./rootfs");
+        assert_eq!(
+            compose.resolve_volume("./data:/data"),
+            "/work/shop/./data:/data"
+        );
+        assert_eq!(compose.resolve_volume("/abs:/abs"), "/abs:/abs");
+        assert_eq!(
+            compose.named_volumes().into_iter().collect::<Vec<_>>(),
+            ["cache"]
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_fields() {
+        let err = parse_yaml("sandboxes:\n  a: { image: x, imagee: y }\n").unwrap_err();
+        assert!(err.to_string().contains("imagee"));
+    }
+
+    #[test]
+    fn test_networks_are_collected_and_validated() {
+        let compose = parse_yaml(
+            r#"
+sandboxes:
+  db: { image: postgres, network: backend }
+  app: { image: app, network: backend }
+  batch: { image: batch }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            compose.networks().into_iter().collect::<Vec<_>>(),
+            ["backend"]
+        );
+
+        let conflict = parse_yaml("sandboxes:\n  a: { image: x, network: n, no_network: true }\n");
+        assert!(conflict.unwrap_err().to_string().contains("no_network"));
+    }
+}
@@ -8,6 +8,7 @@
 //--------------------------------------------------------------------------------------------------
 
 pub mod commands;
+pub mod compose;
 pub mod log_args;
 pub mod sandbox_cmd;
 pub mod styles;
@@ -1,9 +1,11 @@
 //! Shared CLI verbosity flags for `msb` and its hidden runtime subcommands.
 
 use clap::Args;
+use microsandbox::config::TelemetryConfig;
 use microsandbox_runtime::logging::LogLevel;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::filter::Directive;
+use tracing_subscriber::prelude::*;
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -37,23 +39,49 @@ pub struct LogArgs {
 // Functions
 //--------------------------------------------------------------------------------------------------
 
-/// Install a tracing subscriber for the selected level.
+/// Install a tracing subscriber for the selected level, exporting spans to
+/// the configured OTLP endpoint in `otel` builds.
 ///
-/// If no level is selected, logging stays disabled.
-pub fn init_tracing(log_level: Option<LogLevel>) {
-    if let Some(level) = log_level {
+/// If no level is selected and nothing is exported, tracing stays disabled.
+pub fn init_tracing(log_level: Option<LogLevel>, telemetry: &TelemetryConfig) {
+    let fmt = log_level.map(|level| {
         // Silence oci_client logs — the crate logs the auth token in debug mode
         // See: https://github.com/oras-project/rust-oci-client/issues/254
         let filter = EnvFilter::new(level.as_tracing_level().to_string())
             .add_directive("oci_client=info".parse::<Directive>().unwrap());
 
-        tracing_subscriber::fmt()
+        tracing_subscriber::fmt::layer()
             .with_writer(std::io::stderr)
-            .with_env_filter(filter)
-            .init();
+            .with_filter(filter)
+    });
+    let otel = otel_layer(telemetry);
+
+    if fmt.is_some() || otel.is_some() {
+        tracing_subscriber::registry().with(fmt).with(otel).init();
     }
 }
 
+#[cfg(feature = "otel")]
+fn otel_layer<S>(telemetry: &TelemetryConfig) -> Option<impl tracing_subscriber::Layer<S>>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    match microsandbox::telemetry::layer(telemetry) {
+        Ok(layer) => {
+            layer.map(|layer| layer.with_filter(tracing_subscriber::filter::LevelFilter::INFO))
+        }
+        Err(e) => {
+            eprintln!("warning: trace export disabled: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+fn otel_layer(_telemetry: &TelemetryConfig) -> Option<tracing_subscriber::layer::Identity> {
+    None
+}
+
 //--------------------------------------------------------------------------------------------------
 // Methods
 //--------------------------------------------------------------------------------------------------
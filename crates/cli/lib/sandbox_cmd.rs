@@ -4,11 +4,16 @@
 //! [`microsandbox_runtime::vm::enter()`]. This command **never returns**
 //! — the VMM calls `_exit()` on guest shutdown.
 
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use clap::Args;
+use microsandbox::config::TelemetryConfig;
 use microsandbox_runtime::{
     logging::LogLevel,
+    metrics::MetricsRetention,
+    policy::{HealthCheck, ResourceLimit},
+    sockets::SocketMount,
+    telemetry::DEFAULT_SERVICE_NAME,
     vm::{Config, VmConfig},
 };
 
@@ -55,6 +60,42 @@ pub struct SandboxArgs {
     #[arg(long)]
     pub idle_timeout: Option<u64>,
 
+    /// Resource limit spec (`METRIC=THRESHOLD[/WINDOW][:ACTION]`). Repeatable.
+    #[arg(long = "resource-limit")]
+    pub resource_limits: Vec<ResourceLimit>,
+
+    /// Health check as JSON.
+    #[arg(long, value_parser = parse_health_check)]
+    pub health_check: Option<HealthCheck>,
+
+    /// Unix socket mount as JSON. Repeatable.
+    #[arg(long = "socket-mount", value_parser = parse_socket_mount)]
+    pub socket_mounts: Vec<SocketMount>,
+
+    /// Automatic restarts preceding this run.
+    #[arg(long, default_value_t = 0)]
+    pub restart_count: u32,
+
+    /// OTLP/HTTP collector to export trace spans to.
+    #[arg(long)]
+    pub otel_endpoint: Option<String>,
+
+    /// Service name attached to exported spans.
+    #[arg(long, default_value = DEFAULT_SERVICE_NAME)]
+    pub otel_service_name: String,
+
+    /// Seconds to keep raw per-second metrics samples.
+    #[arg(long)]
+    pub metrics_raw_retention: Option<u64>,
+
+    /// Seconds to keep 1-minute metrics rollups.
+    #[arg(long)]
+    pub metrics_minute_retention: Option<u64>,
+
+    /// Seconds to keep 1-hour metrics rollups.
+    #[arg(long)]
+    pub metrics_hour_retention: Option<u64>,
+
     // ── VM configuration ─────────────────────────────────────────────────
     /// Path to the libkrunfw shared library.
     #[arg(long)]
@@ -131,6 +172,20 @@ pub struct SandboxArgs {
     pub exec_args: Vec<String>,
 }
 
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl SandboxArgs {
+    /// Trace export settings passed down by the spawning process.
+    pub fn telemetry(&self) -> TelemetryConfig {
+        TelemetryConfig {
+            otlp_endpoint: self.otel_endpoint.clone(),
+            service_name: self.otel_service_name.clone(),
+        }
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
@@ -168,6 +223,19 @@ pub fn run(args: SandboxArgs, log_level: Option<LogLevel>) -> ! {
         sandbox_slot: args.sandbox_slot,
     };
 
+    let defaults = MetricsRetention::default();
+    let metrics_retention = MetricsRetention {
+        raw: args
+            .metrics_raw_retention
+            .map_or(defaults.raw, Duration::from_secs),
+        minute: args
+            .metrics_minute_retention
+            .map_or(defaults.minute, Duration::from_secs),
+        hour: args
+            .metrics_hour_retention
+            .map_or(defaults.hour, Duration::from_secs),
+    };
+
     let config = Config {
         sandbox_name: args.sandbox_name,
         sandbox_id: args.sandbox_id,
@@ -179,8 +247,21 @@ pub fn run(args: SandboxArgs, log_level: Option<LogLevel>) -> ! {
         forward_output: args.forward_output,
         idle_timeout_secs: args.idle_timeout,
         max_duration_secs: args.max_duration,
+        metrics_retention,
+        resource_limits: args.resource_limits,
+        health_check: args.health_check,
+        socket_mounts: args.socket_mounts,
+        restart_count: args.restart_count,
         vm: vm_config,
     };
 
     microsandbox_runtime::vm::enter(config)
 }
+
+fn parse_health_check(s: &str) -> Result<HealthCheck, String> {
+    serde_json::from_str(s).map_err(|e| format!("invalid health check: {e}"))
+}
+
+fn parse_socket_mount(s: &str) -> Result<SocketMount, String> {
+    serde_json::from_str(s).map_err(|e| format!("invalid socket mount: {e}"))
+}
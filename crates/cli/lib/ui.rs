@@ -307,6 +307,16 @@ pub fn format_status(status: &str) -> String {
     }
 }
 
+/// Format a health check status with color.
+pub fn format_health(health: &str) -> String {
+    match health {
+        "healthy" => format!("{}", style("healthy").green().bold()),
+        "starting" => format!("{}", style("starting").yellow()),
+        "unhealthy" => format!("{}", style("unhealthy").red().bold()),
+        other => other.to_string(),
+    }
+}
+
 /// Print a section header in detail views.
 pub fn detail_header(title: &str) {
     println!();
@@ -360,6 +370,34 @@ pub fn parse_env(s: &str) -> Result<(String, String), String> {
     }
 }
 
+/// Parse a label specification (KEY=value).
+pub fn parse_label(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("invalid label '{s}' (expected KEY=value)")),
+    }
+}
+
+/// Parse `--filter` values into a single label selector.
+///
+/// Each filter has the form `label=<selector>`; all filters must match.
+/// An empty selector is rejected rather than matching everything, so that
+/// e.g. `--filter label=$UNSET` cannot widen a bulk stop or remove.
+pub fn parse_label_filters(filters: &[String]) -> Result<microsandbox::LabelSelector, String> {
+    let mut selector = microsandbox::LabelSelector::default();
+    for filter in filters {
+        let expr = filter
+            .strip_prefix("label=")
+            .ok_or_else(|| format!("unsupported filter '{filter}' (expected label=<selector>)"))?;
+        let parsed = microsandbox::LabelSelector::parse(expr).map_err(|e| e.to_string())?;
+        if parsed.is_empty() {
+            return Err(format!("empty label selector in filter '{filter}'"));
+        }
+        selector = selector.and(parsed);
+    }
+    Ok(selector)
+}
+
 /// Generate a random sandbox name.
 pub fn generate_name() -> String {
     use rand::RngExt;
@@ -576,3 +614,28 @@ impl PullProgressDisplay {
         let _ = self.mp.clear();
     }
 }
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_label_filters_combines_selectors() {
+        let selector =
+            parse_label_filters(&["label=app=web".into(), "label=env!=prod".into()]).unwrap();
+        assert_eq!(selector.requirements().len(), 2);
+        assert!(parse_label_filters(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_label_filters_rejects_empty_selector() {
+        for filter in ["label=", "label= ", "label=  "] {
+            let err = parse_label_filters(&[filter.into()]).unwrap_err();
+            assert!(err.contains("empty label selector"), "{filter}: {err}");
+        }
+    }
+}
@@ -28,6 +28,7 @@ pub struct Model {
     pub rootfs_type: Option<String>,
     pub rootfs_diff_ids: Option<String>,
     pub history: Option<String>,
+    pub labels: Option<String>,
     pub created_at: Option<DateTime>,
 }
 
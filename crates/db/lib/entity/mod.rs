@@ -6,9 +6,12 @@ pub mod index;
 pub mod layer;
 pub mod manifest;
 pub mod manifest_layer;
+pub mod network;
 pub mod run;
 pub mod sandbox;
+pub mod sandbox_event;
 pub mod sandbox_image;
 pub mod sandbox_metric;
+pub mod sandbox_metric_rollup;
 pub mod snapshot;
 pub mod volume;
@@ -1,6 +1,7 @@
 //! Entity definition for the `run` table.
 
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -20,7 +21,7 @@ pub enum RunStatus {
 }
 
 /// The reason a sandbox run terminated.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
 #[sea_orm(rs_type = "String", db_type = "Text")]
 pub enum TerminationReason {
     /// Sandbox exited cleanly (exit code 0).
@@ -39,6 +40,15 @@ pub enum TerminationReason {
     #[sea_orm(string_value = "IdleTimeout")]
     IdleTimeout,
 
+    /// A policy resource limit with a drain or kill action was hit.
+    /// `termination_detail` records which limit.
+    #[sea_orm(string_value = "ResourceLimitExceeded")]
+    ResourceLimitExceeded,
+
+    /// The health check failed and its unhealthy action stopped the sandbox.
+    #[sea_orm(string_value = "Unhealthy")]
+    Unhealthy,
+
     /// SIGUSR1 received (explicit drain request).
     #[sea_orm(string_value = "DrainRequested")]
     DrainRequested,
@@ -68,6 +78,8 @@ pub struct Model {
     pub signals_sent: Option<String>,
     pub started_at: Option<DateTime>,
     pub terminated_at: Option<DateTime>,
+    pub restart_count: i32,
+    pub stop_requested: bool,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -104,6 +116,8 @@ impl std::fmt::Display for TerminationReason {
             Self::Failed => f.write_str("Failed"),
             Self::MaxDurationExceeded => f.write_str("MaxDurationExceeded"),
             Self::IdleTimeout => f.write_str("IdleTimeout"),
+            Self::ResourceLimitExceeded => f.write_str("ResourceLimitExceeded"),
+            Self::Unhealthy => f.write_str("Unhealthy"),
             Self::DrainRequested => f.write_str("DrainRequested"),
             Self::Signal => f.write_str("Signal"),
             Self::InternalError => f.write_str("InternalError"),
@@ -1,6 +1,7 @@
 //! Entity definition for the `sandboxes` table.
 
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -31,6 +32,24 @@ pub enum SandboxStatus {
     Crashed,
 }
 
+/// The result of a sandbox's health check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Text")]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// The check has not passed yet.
+    #[sea_orm(string_value = "Starting")]
+    Starting,
+
+    /// The last check passed.
+    #[sea_orm(string_value = "Healthy")]
+    Healthy,
+
+    /// The check failed more times in a row than its retries allow.
+    #[sea_orm(string_value = "Unhealthy")]
+    Unhealthy,
+}
+
 /// The sandbox entity model.
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "sandbox")]
@@ -43,6 +62,7 @@ pub struct Model {
     pub status: SandboxStatus,
     pub created_at: Option<DateTime>,
     pub updated_at: Option<DateTime>,
+    pub health: Option<HealthStatus>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -60,6 +80,10 @@ pub enum Relation {
     #[sea_orm(has_many = "super::sandbox_metric::Entity")]
     SandboxMetric,
 
+    /// A sandbox has many downsampled metric rollups.
+    #[sea_orm(has_many = "super::sandbox_metric_rollup::Entity")]
+    SandboxMetricRollup,
+
     /// A sandbox has many snapshots.
     #[sea_orm(has_many = "super::snapshot::Entity")]
     Snapshot,
@@ -77,6 +101,12 @@ impl Related<super::sandbox_metric::Entity> for Entity {
     }
 }
 
+impl Related<super::sandbox_metric_rollup::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SandboxMetricRollup.def()
+    }
+}
+
 impl Related<super::snapshot::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Snapshot.def()
@@ -87,4 +117,14 @@ impl Related<super::snapshot::Entity> for Entity {
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
 
+impl std::fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Starting => f.write_str("starting"),
+            Self::Healthy => f.write_str("healthy"),
+            Self::Unhealthy => f.write_str("unhealthy"),
+        }
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,37 @@
+//! Entity definition for the `sandbox_event` table.
+
+use sea_orm::entity::prelude::*;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A lifecycle event recorded for a sandbox.
+///
+/// Events are not tied to the sandbox row by a foreign key so that history
+/// survives the sandbox's removal. `payload` holds the JSON-encoded event.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "sandbox_event")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub sandbox_id: Option<i32>,
+    pub sandbox_name: String,
+    pub event_type: String,
+    pub payload: String,
+    pub created_at: DateTime,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Types: Relations
+//--------------------------------------------------------------------------------------------------
+
+/// Relations for the sandbox_event entity.
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl ActiveModelBehavior for ActiveModel {}
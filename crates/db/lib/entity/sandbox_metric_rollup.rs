@@ -0,0 +1,61 @@
+//! Entity definition for the `sandbox_metric_rollup` table.
+
+use sea_orm::entity::prelude::*;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A downsampled bucket of sandbox metrics.
+///
+/// Gauges keep min/avg/max over the bucket; cumulative counters keep the last
+/// value observed in the bucket.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "sandbox_metric_rollup")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub sandbox_id: i32,
+    pub resolution_secs: i32,
+    pub bucket_start: DateTime,
+    pub sample_count: i64,
+    pub cpu_percent_min: Option<f32>,
+    pub cpu_percent_avg: Option<f32>,
+    pub cpu_percent_max: Option<f32>,
+    pub memory_bytes_min: Option<i64>,
+    pub memory_bytes_avg: Option<i64>,
+    pub memory_bytes_max: Option<i64>,
+    pub disk_read_bytes: Option<i64>,
+    pub disk_write_bytes: Option<i64>,
+    pub net_rx_bytes: Option<i64>,
+    pub net_tx_bytes: Option<i64>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Types: Relations
+//--------------------------------------------------------------------------------------------------
+
+/// Relations for the sandbox_metric_rollup entity.
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    /// A rollup belongs to a sandbox.
+    #[sea_orm(
+        belongs_to = "super::sandbox::Entity",
+        from = "Column::SandboxId",
+        to = "super::sandbox::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Sandbox,
+}
+
+impl Related<super::sandbox::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sandbox.def()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl ActiveModelBehavior for ActiveModel {}
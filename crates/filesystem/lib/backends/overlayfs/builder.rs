@@ -32,6 +32,7 @@ use std::{
 
 use super::{
     OverlayFs,
+    lazy::LazyLayer,
     types::{CachePolicy, Layer, NameTable, OverlayConfig},
 };
 use crate::backends::shared::{init_binary, platform, stat_override};
@@ -45,6 +46,7 @@ use microsandbox_utils::index::MmapIndex;
 pub struct OverlayFsBuilder {
     lowers: Vec<PathBuf>,
     lower_indexes: Vec<Option<PathBuf>>,
+    lower_lazy: Vec<Option<(PathBuf, PathBuf)>>,
     upper_dir: Option<PathBuf>,
     staging_dir: Option<PathBuf>,
     read_only: bool,
@@ -65,6 +67,7 @@ impl OverlayFsBuilder {
         Self {
             lowers: Vec::new(),
             lower_indexes: Vec::new(),
+            lower_lazy: Vec::new(),
             upper_dir: None,
             staging_dir: None,
             read_only: false,
@@ -80,6 +83,7 @@ impl OverlayFsBuilder {
     pub fn layer(mut self, path: impl Into<PathBuf>) -> Self {
         self.lowers.push(path.into());
         self.lower_indexes.push(None);
+        self.lower_lazy.push(None);
         self
     }
 
@@ -94,6 +98,29 @@ impl OverlayFsBuilder {
     ) -> Self {
         self.lowers.push(path.into());
         self.lower_indexes.push(Some(index_path.into()));
+        self.lower_lazy.push(None);
+        self
+    }
+
+    /// Add a lazily materialized lower layer.
+    ///
+    /// `path` starts out empty; entries are materialized from `data_path` on
+    /// first lookup, using the offsets recorded in `toc_path`. `data_path` is
+    /// the uncompressed tarball or, for estargz layers, the compressed blob,
+    /// which may still be downloading. Unlike
+    /// [`layer_with_index`](Self::layer_with_index), the index is required:
+    /// `build()` fails if it is missing or corrupt.
+    pub fn layer_lazy(
+        mut self,
+        path: impl Into<PathBuf>,
+        index_path: impl Into<PathBuf>,
+        toc_path: impl Into<PathBuf>,
+        data_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.lowers.push(path.into());
+        self.lower_indexes.push(Some(index_path.into()));
+        self.lower_lazy
+            .push(Some((toc_path.into(), data_path.into())));
         self
     }
 
@@ -103,6 +130,7 @@ impl OverlayFsBuilder {
         for path in iter {
             self.lowers.push(path);
             self.lower_indexes.push(None);
+            self.lower_lazy.push(None);
         }
         self
     }
@@ -193,6 +221,23 @@ impl OverlayFsBuilder {
                 .and_then(|opt| opt.as_ref())
                 .and_then(|p| MmapIndex::open(p));
 
+            // Lazy layers have no on-disk tree to fall back to.
+            let lazy = match self.lower_lazy.get(index).and_then(|opt| opt.as_ref()) {
+                Some((toc_path, data_path)) => {
+                    if lower_index.is_none() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "lazy layer index missing or invalid: {}",
+                                lower_path.display()
+                            ),
+                        ));
+                    }
+                    Some(LazyLayer::open(&root_fd, toc_path, data_path)?)
+                }
+                None => None,
+            };
+
             #[cfg(target_os = "linux")]
             let layer_proc_fd = dup_fd(&proc_self_fd_main)?;
 
@@ -200,6 +245,7 @@ impl OverlayFsBuilder {
                 root_fd,
                 index,
                 lower_index,
+                lazy,
                 #[cfg(target_os = "linux")]
                 proc_self_fd: layer_proc_fd,
                 #[cfg(target_os = "linux")]
@@ -320,6 +366,7 @@ impl OverlayFsBuilder {
             root_fd: upper_root_fd,
             index: upper_index,
             lower_index: None,
+            lazy: None,
             #[cfg(target_os = "linux")]
             proc_self_fd: upper_proc_fd,
             #[cfg(target_os = "linux")]
@@ -647,6 +647,14 @@ pub(crate) fn do_lookup(fs: &OverlayFs, parent: u64, name: &CStr) -> io::Result<
                 let name_bytes_lazy = parent_name_bytes.get_or_insert_with(|| {
                     get_parent_lower_path(fs, &parent_node).unwrap_or_default()
                 });
+                if let Some(ref lazy) = lower.lazy {
+                    let mut path = name_bytes_lazy.join(&b"/"[..]);
+                    if !path.is_empty() {
+                        path.push(b'/');
+                    }
+                    path.extend_from_slice(name.to_bytes());
+                    lazy.materialize(&path)?;
+                }
                 let lower_parent_fd = match open_lower_parent(lower, &parent_node, name_bytes_lazy)
                 {
                     Some(fd) => fd,
@@ -0,0 +1,598 @@
+//! On-demand materialization for lazily pulled lower layers.
+//!
+//! A lazy layer starts as an empty directory with a sidecar index, a raw tarball,
+//! and a [`LayerToc`] describing where each entry's data lives. Lookup consults
+//! the index as usual; before touching the host filesystem it asks the
+//! [`LazyLayer`] to materialize the entry (and its ancestors). Only the lookup
+//! that needs an entry blocks on it — concurrent lookups of other paths proceed.
+//!
+//! Entries are built under a temporary name and renamed into place without
+//! replacing, so an entry that exists on disk is always complete. This also
+//! makes it safe for several sandboxes to share one lazy layer directory.
+//!
+//! For estargz layers the data file is the compressed blob, which may still be
+//! downloading when the sandbox boots. Materializing a file waits until the
+//! blob has grown past the file's chunks, for as long as the downloading
+//! process holds the blob lock, and then checks the file's digest.
+
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    fs::File,
+    io::{self, Read},
+    os::{
+        fd::{AsRawFd, FromRawFd, RawFd},
+        unix::fs::FileExt,
+    },
+    path::{Path, PathBuf},
+    sync::{
+        Condvar, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use flate2::read::GzDecoder;
+use microsandbox_utils::index::{
+    LayerToc, TocChunk, TocEntry, TocEntryKind, TocSource, blob_lock_path,
+};
+use sha2::{Digest, Sha256};
+
+use crate::backends::shared::{platform, stat_override};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Opaque directory marker written into materialized opaque directories.
+const OPAQUE_MARKER: &[u8] = b".wh..wh..opq";
+
+/// Maximum hardlink indirections followed when resolving a link target.
+const MAX_LINK_DEPTH: usize = 16;
+
+/// Copy buffer size for file materialization.
+const COPY_BUF_SIZE: usize = 256 * 1024;
+
+/// How often a lookup re-checks a blob that is still downloading.
+const BLOB_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Materialization state for one lazy lower layer.
+pub(crate) struct LazyLayer {
+    /// Layer root directory (O_RDONLY | O_DIRECTORY).
+    root_fd: File,
+
+    /// File holding entry data.
+    data: DataSource,
+
+    /// Table of contents keyed by layer-relative path.
+    entries: HashMap<Vec<u8>, TocEntry>,
+
+    /// Paths that are materialized or being materialized.
+    state: Mutex<HashMap<Vec<u8>, EntryState>>,
+
+    /// Signalled whenever an in-flight entry finishes.
+    done: Condvar,
+
+    /// Counter for unique temporary names.
+    next_tmp: AtomicU64,
+}
+
+/// Backing file for entry data, matching the table of contents' [`TocSource`].
+enum DataSource {
+    /// Uncompressed layer tarball.
+    Tar(File),
+    /// Compressed estargz blob, possibly still being downloaded.
+    Estargz {
+        blob: File,
+        blob_size: u64,
+        lock_path: PathBuf,
+    },
+}
+
+/// Reads a byte range of the blob without moving a shared file offset.
+struct BlobRange<'a> {
+    blob: &'a File,
+    pos: u64,
+    end: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EntryState {
+    InFlight,
+    Ready,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl LazyLayer {
+    /// Open a lazy layer from its directory, table of contents, and data file.
+    ///
+    /// `data_path` is the raw tarball or the estargz blob, depending on the
+    /// source recorded in the table of contents.
+    pub(crate) fn open(root_fd: &File, toc_path: &Path, data_path: &Path) -> io::Result<Self> {
+        let toc = LayerToc::read(toc_path)?;
+        let data = match toc.source {
+            TocSource::Tar => DataSource::Tar(File::open(data_path)?),
+            TocSource::Estargz { blob_size } => DataSource::Estargz {
+                blob: File::open(data_path)?,
+                blob_size,
+                lock_path: blob_lock_path(data_path),
+            },
+        };
+        let root_fd = dup_cloexec(root_fd.as_raw_fd())?;
+
+        let entries = toc
+            .entries
+            .into_iter()
+            .map(|entry| (entry.path.as_bytes().to_vec(), entry))
+            .collect();
+
+        let layer = Self {
+            root_fd,
+            data,
+            entries,
+            state: Mutex::new(HashMap::new()),
+            done: Condvar::new(),
+            next_tmp: AtomicU64::new(0),
+        };
+
+        // The root's metadata is needed as soon as the layer is mounted.
+        layer.materialize(b"")?;
+        Ok(layer)
+    }
+
+    /// Ensure `path` (relative to the layer root) and its ancestors exist on disk.
+    ///
+    /// Paths that are not in the table of contents are ignored; the caller's
+    /// subsequent host lookup reports them as missing.
+    pub(crate) fn materialize(&self, path: &[u8]) -> io::Result<()> {
+        self.materialize_depth(path, 0).map_err(|e| {
+            tracing::warn!(
+                path = %String::from_utf8_lossy(path),
+                error = %e,
+                "failed to materialize lazy layer entry"
+            );
+            platform::linux_error(e)
+        })
+    }
+
+    fn materialize_depth(&self, path: &[u8], depth: usize) -> io::Result<()> {
+        if depth > MAX_LINK_DEPTH {
+            return Err(io::Error::from_raw_os_error(libc::ELOOP));
+        }
+
+        let mut end = 0;
+        loop {
+            let next = path[end..]
+                .iter()
+                .position(|b| *b == b'/')
+                .map(|pos| end + pos);
+            let prefix = &path[..next.unwrap_or(path.len())];
+            if let Some(entry) = self.entries.get(prefix) {
+                self.materialize_one(prefix, entry, depth)?;
+            }
+            match next {
+                Some(pos) => end = pos + 1,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Materialize a single entry, waiting if another thread is already on it.
+    fn materialize_one(&self, path: &[u8], entry: &TocEntry, depth: usize) -> io::Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            loop {
+                match state.get(path) {
+                    Some(EntryState::Ready) => return Ok(()),
+                    Some(EntryState::InFlight) => state = self.done.wait(state).unwrap(),
+                    None => {
+                        state.insert(path.to_vec(), EntryState::InFlight);
+                        break;
+                    }
+                }
+            }
+        }
+
+        let result = self.write_entry(path, entry, depth);
+
+        let mut state = self.state.lock().unwrap();
+        match result {
+            Ok(()) => {
+                state.insert(path.to_vec(), EntryState::Ready);
+            }
+            Err(_) => {
+                state.remove(path);
+            }
+        }
+        self.done.notify_all();
+        result
+    }
+
+    /// Create one entry on disk under a temporary name and move it into place.
+    fn write_entry(&self, path: &[u8], entry: &TocEntry, depth: usize) -> io::Result<()> {
+        if path.is_empty() {
+            return stat_override::set_override(
+                self.root_fd.as_raw_fd(),
+                entry.uid,
+                entry.gid,
+                entry.mode,
+                0,
+            );
+        }
+
+        let (parent, name) = match path.iter().rposition(|b| *b == b'/') {
+            Some(pos) => (&path[..pos], &path[pos + 1..]),
+            None => (&path[..0], path),
+        };
+        let parent_dir = self.open_dir(parent)?;
+        let parent_fd = parent_dir.as_raw_fd();
+        let name = CString::new(name).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+
+        // Materialized by an earlier run or another sandbox sharing the layer.
+        if platform::fstatat_nofollow(parent_fd, &name).is_ok() {
+            return Ok(());
+        }
+
+        if entry.kind == TocEntryKind::Hardlink {
+            return self.write_hardlink(parent_fd, &name, entry, depth);
+        }
+
+        let tmp = self.tmp_name();
+        let _cleanup = scopeguard::guard((), |_| unsafe {
+            if libc::unlinkat(parent_fd, tmp.as_ptr(), 0) < 0 {
+                libc::unlinkat(parent_fd, tmp.as_ptr(), libc::AT_REMOVEDIR);
+            }
+        });
+
+        match entry.kind {
+            TocEntryKind::Dir => {
+                if unsafe { libc::mkdirat(parent_fd, tmp.as_ptr(), 0o700) } < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let dir = open_at(
+                    parent_fd,
+                    &tmp,
+                    libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW,
+                    0,
+                )?;
+                stat_override::set_override(dir.as_raw_fd(), entry.uid, entry.gid, entry.mode, 0)?;
+                if entry.opaque {
+                    let marker = CString::new(OPAQUE_MARKER).unwrap();
+                    open_at(
+                        dir.as_raw_fd(),
+                        &marker,
+                        libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL,
+                        0o600,
+                    )?;
+                }
+            }
+            #[cfg(target_os = "macos")]
+            TocEntryKind::Symlink => {
+                let target = CString::new(entry.link.clone().unwrap_or_default())
+                    .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+                if unsafe { libc::symlinkat(target.as_ptr(), parent_fd, tmp.as_ptr()) } < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                set_symlink_override(parent_fd, &tmp, entry)?;
+            }
+            _ => {
+                let file = open_at(
+                    parent_fd,
+                    &tmp,
+                    libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL,
+                    0o600,
+                )?;
+                match entry.kind {
+                    TocEntryKind::File => self.copy_data(&file, entry)?,
+                    TocEntryKind::Symlink => {
+                        // Linux: symlinks are regular files holding the target,
+                        // typed via the override xattr (matches extraction).
+                        let target = entry.link.as_deref().unwrap_or_default();
+                        file.write_all_at(target.as_bytes(), 0)?;
+                    }
+                    _ => {}
+                }
+                stat_override::set_override(
+                    file.as_raw_fd(),
+                    entry.uid,
+                    entry.gid,
+                    entry.mode,
+                    entry.rdev,
+                )?;
+            }
+        }
+
+        match rename_noreplace(parent_fd, &tmp, &name) {
+            Ok(()) => Ok(()),
+            // Lost a race with another sandbox; its copy is equivalent.
+            Err(e) if exists_error(&e) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Link a hardlink entry to its (materialized) target.
+    fn write_hardlink(
+        &self,
+        parent_fd: RawFd,
+        name: &CString,
+        entry: &TocEntry,
+        depth: usize,
+    ) -> io::Result<()> {
+        let target = entry
+            .link
+            .as_deref()
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::EINVAL))?;
+        self.materialize_depth(target.as_bytes(), depth + 1)?;
+
+        let (target_parent, target_name) = match target.rfind('/') {
+            Some(pos) => (&target[..pos], &target[pos + 1..]),
+            None => ("", target),
+        };
+        let target_parent_fd = self.open_dir(target_parent.as_bytes())?;
+        let target_name =
+            CString::new(target_name).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+
+        let ret = unsafe {
+            libc::linkat(
+                target_parent_fd.as_raw_fd(),
+                target_name.as_ptr(),
+                parent_fd,
+                name.as_ptr(),
+                0,
+            )
+        };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if !exists_error(&err) {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy a regular file's data out of the tarball or estargz blob.
+    fn copy_data(&self, file: &File, entry: &TocEntry) -> io::Result<()> {
+        match &self.data {
+            DataSource::Tar(tar) => copy_from_tar(tar, file, entry),
+            DataSource::Estargz {
+                blob,
+                blob_size,
+                lock_path,
+            } => {
+                let mut hasher = Sha256::new();
+                let mut chunks: Vec<&TocChunk> = entry.chunks.iter().collect();
+                chunks.sort_by_key(|chunk| chunk.chunk_offset);
+                for chunk in chunks {
+                    let end = (chunk.offset + chunk.compressed_size).min(*blob_size);
+                    wait_for_blob(blob, end, lock_path)?;
+                    copy_chunk(blob, end, chunk, file, &mut hasher)?;
+                }
+                verify_digest(entry, hasher)
+            }
+        }
+    }
+    /// Open a directory in the layer by walking components without following symlinks.
+    fn open_dir(&self, path: &[u8]) -> io::Result<File> {
+        let mut dir = dup_cloexec(self.root_fd.as_raw_fd())?;
+        for component in path.split(|b| *b == b'/').filter(|c| !c.is_empty()) {
+            let name =
+                CString::new(component).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+            dir = open_at(
+                dir.as_raw_fd(),
+                &name,
+                libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW,
+                0,
+            )?;
+        }
+        Ok(dir)
+    }
+
+    fn tmp_name(&self) -> CString {
+        let n = self.next_tmp.fetch_add(1, Ordering::Relaxed);
+        CString::new(format!(".tmp.lazy.{}.{n}", std::process::id())).unwrap()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Read for BlobRange<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let want = (self.end.saturating_sub(self.pos) as usize).min(buf.len());
+        if want == 0 {
+            return Ok(0);
+        }
+        let n = self.blob.read_at(&mut buf[..want], self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Copy a regular file's data out of an uncompressed tarball.
+fn copy_from_tar(tar: &File, file: &File, entry: &TocEntry) -> io::Result<()> {
+    let mut buf = vec![0u8; COPY_BUF_SIZE.min(entry.size as usize).max(1)];
+    let mut copied = 0u64;
+    while copied < entry.size {
+        let want = ((entry.size - copied) as usize).min(buf.len());
+        let n = tar.read_at(&mut buf[..want], entry.offset + copied)?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "lazy layer tarball is truncated",
+            ));
+        }
+        file.write_all_at(&buf[..n], copied)?;
+        copied += n as u64;
+    }
+    Ok(())
+}
+
+/// Decompress one estargz chunk into `file`, feeding its bytes to `hasher`.
+fn copy_chunk(
+    blob: &File,
+    end: u64,
+    chunk: &TocChunk,
+    file: &File,
+    hasher: &mut Sha256,
+) -> io::Result<()> {
+    let range = BlobRange {
+        blob,
+        pos: chunk.offset,
+        end,
+    };
+    let mut decoder = GzDecoder::new(range).take(chunk.chunk_size);
+    let mut buf = vec![0u8; COPY_BUF_SIZE.min(chunk.chunk_size as usize).max(1)];
+    let mut copied = 0u64;
+    loop {
+        let n = decoder.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        file.write_all_at(&buf[..n], chunk.chunk_offset + copied)?;
+        hasher.update(&buf[..n]);
+        copied += n as u64;
+    }
+    if copied != chunk.chunk_size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "estargz chunk is truncated",
+        ));
+    }
+    Ok(())
+}
+
+/// Check a materialized file against the digest recorded in the table of contents.
+fn verify_digest(entry: &TocEntry, hasher: Sha256) -> io::Result<()> {
+    let Some(expected) = entry.digest.as_deref() else {
+        return Ok(());
+    };
+    let actual = format!("sha256:{}", hex::encode(hasher.finalize()));
+    if actual != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "digest mismatch for {}: expected {expected}, got {actual}",
+                entry.path
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Block until `blob` is at least `end` bytes long.
+///
+/// Gives up once no process holds the blob lock, since the blob can then no
+/// longer grow.
+fn wait_for_blob(blob: &File, end: u64, lock_path: &Path) -> io::Result<()> {
+    loop {
+        if blob.metadata()?.len() >= end {
+            return Ok(());
+        }
+        if !blob_download_active(lock_path)? {
+            // The download may have finished between the two checks.
+            if blob.metadata()?.len() >= end {
+                return Ok(());
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "estargz blob is incomplete and no download is in progress",
+            ));
+        }
+        std::thread::sleep(BLOB_POLL_INTERVAL);
+    }
+}
+
+/// Check whether some process holds the exclusive blob download lock.
+fn blob_download_active(lock_path: &Path) -> io::Result<bool> {
+    let file = match File::open(lock_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) } == 0 {
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) };
+        return Ok(false);
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(true)
+    } else {
+        Err(err)
+    }
+}
+
+fn open_at(dirfd: RawFd, name: &CString, flags: i32, mode: libc::c_uint) -> io::Result<File> {
+    let fd = unsafe { libc::openat(dirfd, name.as_ptr(), flags | libc::O_CLOEXEC, mode) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+fn dup_cloexec(fd: RawFd) -> io::Result<File> {
+    let new_fd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if new_fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(new_fd) })
+}
+
+fn exists_error(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EEXIST) | Some(libc::ENOTEMPTY)
+    )
+}
+
+/// Rename without replacing an existing destination.
+fn rename_noreplace(dirfd: RawFd, from: &CString, to: &CString) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            dirfd,
+            from.as_ptr(),
+            dirfd,
+            to.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+
+    #[cfg(target_os = "macos")]
+    let ret =
+        unsafe { libc::renameatx_np(dirfd, from.as_ptr(), dirfd, to.as_ptr(), libc::RENAME_EXCL) };
+
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Set the override xattr on a real symlink (macOS, `XATTR_NOFOLLOW`).
+#[cfg(target_os = "macos")]
+fn set_symlink_override(dirfd: RawFd, name: &CString, entry: &TocEntry) -> io::Result<()> {
+    let fd = unsafe {
+        libc::openat(
+            dirfd,
+            name.as_ptr(),
+            libc::O_RDONLY | libc::O_SYMLINK | libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let file = unsafe { File::from_raw_fd(fd) };
+    stat_override::set_override(file.as_raw_fd(), entry.uid, entry.gid, entry.mode, 0)
+}
@@ -14,6 +14,7 @@ mod dir_ops;
 mod file_ops;
 pub(crate) mod inode;
 pub(crate) mod layer;
+mod lazy;
 mod metadata;
 mod origin;
 mod remove_ops;
@@ -0,0 +1,460 @@
+//! Tests for lazily materialized lower layers.
+
+use std::{
+    io::Write,
+    os::fd::AsRawFd,
+    time::{Duration, Instant},
+};
+
+use flate2::{Compression, write::GzEncoder};
+use microsandbox_utils::index::{
+    IndexBuilder, LayerToc, TocChunk, TocEntry, TocEntryKind, TocSource, blob_lock_path,
+};
+use sha2::{Digest, Sha256};
+
+use super::*;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A mounted overlay over a single lazy lower layer.
+struct LazyFixture {
+    fs: OverlayFs,
+    lower: PathBuf,
+    _tmp: TempDir,
+}
+
+/// A mounted overlay over an estargz layer whose blob is only partly present.
+struct EstargzFixture {
+    fs: OverlayFs,
+    blob_path: PathBuf,
+    /// Blob bytes not yet written to `blob_path`.
+    missing: Vec<u8>,
+    /// Held like the downloading process holds the blob lock.
+    lock: Option<std::fs::File>,
+    _tmp: TempDir,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Test Helpers
+//--------------------------------------------------------------------------------------------------
+
+fn toc_entry(path: &str, kind: TocEntryKind, mode: u32) -> TocEntry {
+    TocEntry {
+        path: path.to_string(),
+        kind,
+        mode,
+        uid: 1000,
+        gid: 1000,
+        rdev: 0,
+        size: 0,
+        offset: 0,
+        link: None,
+        opaque: false,
+        implicit: false,
+        chunks: Vec::new(),
+        digest: None,
+    }
+}
+
+fn gzip_member(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn sha256_digest(data: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(data)))
+}
+
+/// Build an estargz layer with `/etc/hosts` in the first gzip member and
+/// `/etc/motd` split across two more. Only the first member is on disk when
+/// the overlay is mounted; `motd_digest` overrides the recorded digest.
+fn mount_estargz_partial(lock_held: bool, motd_digest: Option<&str>) -> EstargzFixture {
+    let tmp = tempfile::tempdir().unwrap();
+    let lower = tmp.path().join("lower.extracted");
+    std::fs::create_dir(&lower).unwrap();
+
+    let hosts = b"127.0.0.1 localhost\n";
+    let motd = b"welcome to the lazily fetched layer\n";
+    let (motd_head, motd_tail) = motd.split_at(10);
+
+    let mut blob = gzip_member(hosts);
+    let hosts_end = blob.len() as u64;
+    let motd_head_offset = blob.len() as u64;
+    blob.extend(gzip_member(motd_head));
+    let motd_tail_offset = blob.len() as u64;
+    blob.extend(gzip_member(motd_tail));
+    let blob_size = blob.len() as u64;
+
+    let mut toc = LayerToc::new();
+    toc.source = TocSource::Estargz { blob_size };
+    toc.entries = vec![
+        toc_entry("", TocEntryKind::Dir, libc::S_IFDIR as u32 | 0o755),
+        toc_entry("etc", TocEntryKind::Dir, libc::S_IFDIR as u32 | 0o755),
+        TocEntry {
+            size: hosts.len() as u64,
+            chunks: vec![TocChunk {
+                offset: 0,
+                compressed_size: hosts_end,
+                chunk_offset: 0,
+                chunk_size: hosts.len() as u64,
+            }],
+            digest: Some(sha256_digest(hosts)),
+            ..toc_entry(
+                "etc/hosts",
+                TocEntryKind::File,
+                libc::S_IFREG as u32 | 0o644,
+            )
+        },
+        TocEntry {
+            size: motd.len() as u64,
+            chunks: vec![
+                TocChunk {
+                    offset: motd_head_offset,
+                    compressed_size: motd_tail_offset - motd_head_offset,
+                    chunk_offset: 0,
+                    chunk_size: motd_head.len() as u64,
+                },
+                TocChunk {
+                    offset: motd_tail_offset,
+                    compressed_size: blob_size - motd_tail_offset,
+                    chunk_offset: motd_head.len() as u64,
+                    chunk_size: motd_tail.len() as u64,
+                },
+            ],
+            digest: Some(motd_digest.map_or_else(|| sha256_digest(motd), str::to_string)),
+            ..toc_entry("etc/motd", TocEntryKind::File, libc::S_IFREG as u32 | 0o644)
+        },
+    ];
+    toc.write(&lower.with_extension("toc")).unwrap();
+
+    IndexBuilder::new()
+        .dir("")
+        .dir("etc")
+        .subdir("", "etc", 0o755)
+        .file("etc", "hosts", 0o644)
+        .file("etc", "motd", 0o644)
+        .build_to_file(&lower.with_extension("index"))
+        .unwrap();
+
+    let blob_path = lower.with_extension("blob");
+    std::fs::write(&blob_path, &blob[..hosts_end as usize]).unwrap();
+    let lock = lock_held.then(|| {
+        let lock = std::fs::File::create(blob_lock_path(&blob_path)).unwrap();
+        assert_eq!(
+            unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) },
+            0
+        );
+        lock
+    });
+
+    let fs = OverlayFs::builder()
+        .layer_lazy(
+            &lower,
+            lower.with_extension("index"),
+            lower.with_extension("toc"),
+            &blob_path,
+        )
+        .read_only()
+        .build()
+        .unwrap();
+    fs.init(FsOptions::empty()).unwrap();
+
+    EstargzFixture {
+        fs,
+        blob_path,
+        missing: blob[hosts_end as usize..].to_vec(),
+        lock,
+        _tmp: tmp,
+    }
+}
+
+fn read_all(fs: &OverlayFs, entry: &Entry) -> Vec<u8> {
+    let handle = fs_fuse_open(fs, entry.inode, libc::O_RDONLY as u32).unwrap();
+    fs_fuse_read(fs, entry.inode, handle, 256, 0).unwrap()
+}
+
+/// Build a lazy layer with `/etc/hosts`, `/etc/motd`, a `/etc/hosts.bak`
+/// hardlink, a `/bin/sh` symlink, and a whiteout for `/etc/passwd`.
+fn mount_lazy() -> LazyFixture {
+    let tmp = tempfile::tempdir().unwrap();
+    let lower = tmp.path().join("lower.extracted");
+    let upper = tmp.path().join("upper");
+    let staging = tmp.path().join("staging");
+    for dir in [&lower, &upper, &staging] {
+        std::fs::create_dir(dir).unwrap();
+    }
+
+    // The materializer only reads data at recorded offsets, so a flat blob
+    // stands in for the raw tarball.
+    let hosts = b"127.0.0.1 localhost\n";
+    let motd = b"welcome\n";
+    let mut blob = vec![0u8; 512];
+    let hosts_offset = blob.len() as u64;
+    blob.extend_from_slice(hosts);
+    blob.resize(1024, 0);
+    let motd_offset = blob.len() as u64;
+    blob.extend_from_slice(motd);
+    std::fs::write(lower.with_extension("tar"), &blob).unwrap();
+
+    let mut toc = LayerToc::new();
+    toc.entries = vec![
+        toc_entry("", TocEntryKind::Dir, libc::S_IFDIR as u32 | 0o755),
+        toc_entry("bin", TocEntryKind::Dir, libc::S_IFDIR as u32 | 0o755),
+        TocEntry {
+            link: Some("/usr/bin/busybox".to_string()),
+            ..toc_entry(
+                "bin/sh",
+                TocEntryKind::Symlink,
+                libc::S_IFLNK as u32 | 0o777,
+            )
+        },
+        toc_entry("etc", TocEntryKind::Dir, libc::S_IFDIR as u32 | 0o750),
+        TocEntry {
+            size: hosts.len() as u64,
+            offset: hosts_offset,
+            ..toc_entry(
+                "etc/hosts",
+                TocEntryKind::File,
+                libc::S_IFREG as u32 | 0o644,
+            )
+        },
+        TocEntry {
+            link: Some("etc/hosts".to_string()),
+            ..toc_entry(
+                "etc/hosts.bak",
+                TocEntryKind::Hardlink,
+                libc::S_IFREG as u32 | 0o644,
+            )
+        },
+        TocEntry {
+            size: motd.len() as u64,
+            offset: motd_offset,
+            ..toc_entry("etc/motd", TocEntryKind::File, libc::S_IFREG as u32 | 0o600)
+        },
+    ];
+    toc.write(&lower.with_extension("toc")).unwrap();
+
+    IndexBuilder::new()
+        .dir("")
+        .dir("bin")
+        .dir("etc")
+        .subdir("", "bin", 0o755)
+        .subdir("", "etc", 0o750)
+        .symlink("bin", "sh")
+        .file("etc", "hosts", 0o644)
+        .file("etc", "hosts.bak", 0o644)
+        .file("etc", "motd", 0o600)
+        .whiteout("etc", "passwd")
+        .build_to_file(&lower.with_extension("index"))
+        .unwrap();
+
+    let fs = OverlayFs::builder()
+        .layer_lazy(
+            &lower,
+            lower.with_extension("index"),
+            lower.with_extension("toc"),
+            lower.with_extension("tar"),
+        )
+        .writable(&upper)
+        .staging(&staging)
+        .build()
+        .unwrap();
+    fs.init(FsOptions::empty()).unwrap();
+
+    LazyFixture {
+        fs,
+        lower,
+        _tmp: tmp,
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[test]
+fn test_lazy_layer_starts_empty() {
+    let fixture = mount_lazy();
+    assert_eq!(std::fs::read_dir(&fixture.lower).unwrap().count(), 0);
+}
+
+#[test]
+fn test_lazy_lookup_materializes_only_requested_entry() {
+    let fixture = mount_lazy();
+    let fs = &fixture.fs;
+
+    let etc = fs_lookup_root(fs, "etc").unwrap();
+    assert_eq!(etc.attr.st_mode as u32 & 0o7777, 0o750);
+    let hosts = fs_lookup(fs, etc.inode, "hosts").unwrap();
+    assert_eq!(hosts.attr.st_uid, 1000);
+    assert_eq!(hosts.attr.st_size, 20);
+
+    let handle = fs_fuse_open(fs, hosts.inode, libc::O_RDONLY as u32).unwrap();
+    let data = fs_fuse_read(fs, hosts.inode, handle, 64, 0).unwrap();
+    assert_eq!(data, b"127.0.0.1 localhost\n");
+
+    assert!(fixture.lower.join("etc/hosts").exists());
+    assert!(!fixture.lower.join("etc/motd").exists());
+    assert!(!fixture.lower.join("bin").exists());
+}
+
+#[test]
+fn test_lazy_readdir_lists_unmaterialized_entries() {
+    let fixture = mount_lazy();
+    let fs = &fixture.fs;
+
+    let etc = fs_lookup_root(fs, "etc").unwrap();
+    let mut names = fs_readdir_names(fs, etc.inode).unwrap();
+    names.retain(|name| name != b"." && name != b"..");
+    names.sort();
+    assert_eq!(
+        names,
+        vec![b"hosts".to_vec(), b"hosts.bak".to_vec(), b"motd".to_vec()]
+    );
+}
+
+#[test]
+fn test_lazy_lookup_respects_index_whiteouts() {
+    let fixture = mount_lazy();
+    let fs = &fixture.fs;
+
+    let etc = fs_lookup_root(fs, "etc").unwrap();
+    assert_errno(fs_lookup(fs, etc.inode, "passwd"), LINUX_ENOENT);
+    assert_errno(fs_lookup(fs, etc.inode, "missing"), LINUX_ENOENT);
+}
+
+#[test]
+fn test_lazy_hardlink_materializes_target() {
+    let fixture = mount_lazy();
+    let fs = &fixture.fs;
+
+    let etc = fs_lookup_root(fs, "etc").unwrap();
+    let bak = fs_lookup(fs, etc.inode, "hosts.bak").unwrap();
+    let handle = fs_fuse_open(fs, bak.inode, libc::O_RDONLY as u32).unwrap();
+    let data = fs_fuse_read(fs, bak.inode, handle, 64, 0).unwrap();
+    assert_eq!(data, b"127.0.0.1 localhost\n");
+
+    use std::os::unix::fs::MetadataExt;
+    let target = std::fs::metadata(fixture.lower.join("etc/hosts")).unwrap();
+    let link = std::fs::metadata(fixture.lower.join("etc/hosts.bak")).unwrap();
+    assert_eq!(target.ino(), link.ino());
+}
+
+#[test]
+fn test_lazy_symlink_materializes_as_symlink() {
+    let fixture = mount_lazy();
+    let fs = &fixture.fs;
+
+    let bin = fs_lookup_root(fs, "bin").unwrap();
+    let sh = fs_lookup(fs, bin.inode, "sh").unwrap();
+    assert_eq!(
+        sh.attr.st_mode as u32 & libc::S_IFMT as u32,
+        libc::S_IFLNK as u32
+    );
+    assert_eq!(
+        fs.readlink(root_ctx(), sh.inode).unwrap(),
+        b"/usr/bin/busybox"
+    );
+}
+
+#[test]
+fn test_lazy_concurrent_lookups_share_materialization() {
+    let fixture = mount_lazy();
+    let fs = &fixture.fs;
+    let etc = fs_lookup_root(fs, "etc").unwrap();
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| scope.spawn(|| fs_lookup(fs, etc.inode, "motd").unwrap().inode))
+            .collect();
+        let inodes: Vec<u64> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert!(inodes.iter().all(|ino| *ino == inodes[0]));
+    });
+
+    let leftovers = std::fs::read_dir(fixture.lower.join("etc"))
+        .unwrap()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(".tmp."))
+        .count();
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn test_lazy_layer_requires_index() {
+    let fixture = mount_lazy();
+    std::fs::remove_file(fixture.lower.with_extension("index")).unwrap();
+
+    let result = OverlayFs::builder()
+        .layer_lazy(
+            &fixture.lower,
+            fixture.lower.with_extension("index"),
+            fixture.lower.with_extension("toc"),
+            fixture.lower.with_extension("tar"),
+        )
+        .read_only()
+        .build();
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_estargz_boots_before_blob_is_downloaded() {
+    let fixture = mount_estargz_partial(true, None);
+    let fs = &fixture.fs;
+
+    // Entries whose chunks have arrived are served right away.
+    let etc = fs_lookup_root(fs, "etc").unwrap();
+    let hosts = fs_lookup(fs, etc.inode, "hosts").unwrap();
+    assert_eq!(read_all(fs, &hosts), b"127.0.0.1 localhost\n");
+
+    // A lookup that needs missing bytes blocks until the download delivers them.
+    std::thread::scope(|scope| {
+        let lookup = scope.spawn(|| fs_lookup(fs, etc.inode, "motd"));
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!lookup.is_finished());
+
+        let mut blob = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&fixture.blob_path)
+            .unwrap();
+        blob.write_all(&fixture.missing).unwrap();
+
+        let started = Instant::now();
+        let motd = lookup.join().unwrap().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            read_all(fs, &motd),
+            b"welcome to the lazily fetched layer\n"
+        );
+    });
+    drop(fixture.lock);
+}
+
+#[test]
+fn test_estargz_lookup_fails_when_download_stopped() {
+    let fixture = mount_estargz_partial(false, None);
+    let fs = &fixture.fs;
+
+    let etc = fs_lookup_root(fs, "etc").unwrap();
+    assert!(fs_lookup(fs, etc.inode, "hosts").is_ok());
+    assert!(fs_lookup(fs, etc.inode, "motd").is_err());
+}
+
+#[test]
+fn test_estargz_rejects_chunk_digest_mismatch() {
+    let bad = sha256_digest(b"something else");
+    let fixture = mount_estargz_partial(false, Some(&bad));
+    let fs = &fixture.fs;
+
+    let mut blob = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&fixture.blob_path)
+        .unwrap();
+    blob.write_all(&fixture.missing).unwrap();
+
+    let etc = fs_lookup_root(fs, "etc").unwrap();
+    assert!(fs_lookup(fs, etc.inode, "motd").is_err());
+    assert!(fs_lookup(fs, etc.inode, "hosts").is_ok());
+}
@@ -168,6 +168,10 @@ pub(crate) struct Layer {
     /// `None` if no index was provided or the index failed validation.
     pub lower_index: Option<microsandbox_utils::index::MmapIndex>,
 
+    /// On-demand materializer for lazily pulled lower layers.
+    /// `None` for fully extracted layers and the upper layer.
+    pub lazy: Option<super::lazy::LazyLayer>,
+
     /// Linux: /proc/self/fd handle for secure inode reopening.
     #[cfg(target_os = "linux")]
     #[allow(dead_code)]
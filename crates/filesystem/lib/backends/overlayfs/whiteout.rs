@@ -129,6 +129,27 @@ pub(crate) fn has_lower_entry(fs: &OverlayFs, parent_inode: u64, name: &[u8]) ->
 
     // Check each lower layer (top-down).
     for lower in fs.lowers.iter().rev() {
+        // Lazy layers may not have materialized the entry yet; ask the index.
+        if lower.lazy.is_some()
+            && let Some(ref idx) = lower.lower_index
+        {
+            let Some(dir_rec) =
+                inode::find_dir_record_for_parent(fs, idx, lower.index, &parent_node)
+            else {
+                continue;
+            };
+            if idx.has_whiteout(dir_rec, name) {
+                return Ok(false);
+            }
+            if idx.find_entry(dir_rec, name).is_some() {
+                return Ok(true);
+            }
+            if idx.is_opaque(dir_rec) {
+                return Ok(false);
+            }
+            continue;
+        }
+
         let lower_parent_fd = match inode::open_lower_parent(lower, &parent_node, &path_components)
         {
             Some(fd) => fd,
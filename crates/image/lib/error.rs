@@ -26,6 +26,23 @@ pub enum ImageError {
         arch: Arch,
     },
 
+    /// A platform string could not be parsed (expected `os/arch[/variant]`).
+    #[error("invalid platform '{0}': expected os/arch[/variant], e.g. linux/amd64")]
+    InvalidPlatform(String),
+
+    /// The image platform cannot run on this host without emulation.
+    #[error(
+        "image platform {platform} cannot run natively on this {host} host; \
+         microsandbox does not emulate foreign architectures. Use a {host} \
+         variant of the image (e.g. --platform {host}) or run it on a {platform} machine"
+    )]
+    UnsupportedPlatform {
+        /// The requested image platform.
+        platform: String,
+        /// The host platform.
+        host: String,
+    },
+
     /// OCI manifest or index parsing failed.
     #[error("manifest parse error: {0}")]
     ManifestParse(String),
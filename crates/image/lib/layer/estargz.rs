@@ -0,0 +1,459 @@
+//! Seekable estargz layers.
+//!
+//! An estargz blob is a gzip stream made of independently decompressible
+//! members: each regular file's data (or each chunk of a large file) starts
+//! a new member, and the blob ends with a gzip-wrapped tar holding a JSON
+//! table of contents followed by a fixed-size footer pointing at it.
+//!
+//! Lazy pulls fetch only the footer and table of contents with range
+//! requests, so the layer is ready before its data is downloaded. The table
+//! of contents is converted into the same sidecar index and [`LayerToc`]
+//! used for tar-backed lazy layers, with file data described as compressed
+//! chunks of the blob.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use async_compression::tokio::bufread::GzipDecoder;
+use microsandbox_utils::index::{TocChunk, TocEntry, TocEntryKind};
+use serde::Deserialize;
+use sha2::{Digest as Sha2Digest, Sha256};
+use tokio::io::AsyncReadExt;
+use tokio_tar as tar;
+
+use super::{
+    S_IFLNK,
+    extraction::{
+        MAX_ENTRY_COUNT, MAX_FILE_SIZE, MAX_TOTAL_SIZE, S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO,
+        S_IFREG, makedev, validate_entry_path,
+    },
+    lazy::{TocScan, normalize_rel},
+};
+use crate::{
+    digest::Digest,
+    error::{ImageError, ImageResult},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Layer descriptor annotation carrying the digest of the uncompressed TOC JSON.
+pub(crate) const TOC_DIGEST_ANNOTATION: &str = "containerd.io/snapshot/stargz/toc.digest";
+
+/// Size of the estargz footer at the end of the blob.
+pub(crate) const FOOTER_SIZE: u64 = 51;
+
+/// Name of the TOC entry inside the final tar.
+const TOC_ENTRY_NAME: &str = "stargz.index.json";
+
+/// Landmark files estargz writers add to the layer root; not part of the image.
+const LANDMARKS: [&str; 2] = [".prefetch.landmark", ".no.prefetch.landmark"];
+
+/// Maximum accepted size of the uncompressed TOC JSON (256 MiB).
+const MAX_TOC_SIZE: u64 = 256 * 1024 * 1024;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The `stargz.index.json` table of contents.
+#[derive(Debug, Deserialize)]
+pub(crate) struct EstargzToc {
+    entries: Vec<EstargzEntry>,
+}
+
+/// One entry of the estargz table of contents.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EstargzEntry {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    link_name: String,
+    #[serde(default)]
+    mode: u32,
+    #[serde(default)]
+    uid: u32,
+    #[serde(default)]
+    gid: u32,
+    #[serde(default)]
+    dev_major: u32,
+    #[serde(default)]
+    dev_minor: u32,
+    #[serde(default)]
+    offset: u64,
+    #[serde(default)]
+    chunk_offset: u64,
+    #[serde(default)]
+    chunk_size: u64,
+    #[serde(default)]
+    digest: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Parse the blob footer and return the offset of the TOC's gzip member.
+pub(crate) fn parse_footer(footer: &[u8], digest: &Digest) -> ImageResult<u64> {
+    // gzip header (10) + XLEN (2) + subfield "SG" + length (4) + "%016xSTARGZ"
+    // (22), followed by an empty deflate block and the gzip trailer.
+    let invalid = || estargz_err(digest, "blob does not end with an estargz footer");
+    if footer.len() != FOOTER_SIZE as usize
+        || footer[..3] != [0x1f, 0x8b, 0x08]
+        || footer[3] & 0x04 == 0
+        || u16::from_le_bytes([footer[10], footer[11]]) != 26
+        || &footer[12..14] != b"SG"
+        || u16::from_le_bytes([footer[14], footer[15]]) != 22
+        || &footer[32..38] != b"STARGZ"
+    {
+        return Err(invalid());
+    }
+
+    std::str::from_utf8(&footer[16..32])
+        .ok()
+        .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+        .ok_or_else(invalid)
+}
+
+/// Decompress the TOC member and verify it against the descriptor annotation.
+pub(crate) async fn decode_toc(
+    toc_member: &[u8],
+    expected_digest: &str,
+    digest: &Digest,
+) -> ImageResult<EstargzToc> {
+    use futures::StreamExt;
+
+    let mut archive = tar::Archive::new(GzipDecoder::new(toc_member));
+    let mut entries = archive
+        .entries()
+        .map_err(|e| estargz_err(digest, format!("failed to read TOC tar: {e}")))?;
+
+    while let Some(entry) = entries.next().await {
+        let mut entry =
+            entry.map_err(|e| estargz_err(digest, format!("failed to read TOC tar: {e}")))?;
+        let is_toc = entry
+            .path()
+            .map(|path| path.as_ref() == Path::new(TOC_ENTRY_NAME))
+            .unwrap_or(false);
+        if !is_toc {
+            continue;
+        }
+
+        let size = entry.header().size().unwrap_or(0);
+        if size > MAX_TOC_SIZE {
+            return Err(estargz_err(
+                digest,
+                format!("TOC too large: {size} bytes (max {MAX_TOC_SIZE})"),
+            ));
+        }
+        let mut json = Vec::with_capacity(size as usize);
+        entry
+            .read_to_end(&mut json)
+            .await
+            .map_err(|e| estargz_err(digest, format!("failed to read TOC: {e}")))?;
+
+        let actual = format!("sha256:{}", hex::encode(Sha256::digest(&json)));
+        if actual != expected_digest {
+            return Err(ImageError::DigestMismatch {
+                digest: format!("{digest} (estargz TOC)"),
+                expected: expected_digest.to_string(),
+                actual,
+            });
+        }
+
+        return serde_json::from_slice(&json)
+            .map_err(|e| estargz_err(digest, format!("invalid TOC JSON: {e}")));
+    }
+
+    Err(estargz_err(digest, format!("{TOC_ENTRY_NAME} not found")))
+}
+
+/// Convert an estargz TOC into lazy layer entries.
+///
+/// `toc_offset` bounds the compressed size of the last data member. `dest` is
+/// the layer directory, used to reject entries that escape it.
+pub(super) fn scan_toc(
+    toc: &EstargzToc,
+    toc_offset: u64,
+    dest: &Path,
+    digest: &Digest,
+) -> ImageResult<TocScan> {
+    let blob_id = PathBuf::from(digest.to_string());
+
+    // A chunk's member ends at most where the next data member (or the TOC)
+    // starts, so that is how much of the blob a reader has to wait for.
+    let mut member_offsets: BTreeSet<u64> = toc
+        .entries
+        .iter()
+        .filter(|entry| has_data(entry))
+        .map(|entry| entry.offset)
+        .collect();
+    member_offsets.insert(toc_offset);
+    let chunk_for = |entry: &EstargzEntry, file_size: u64| -> ImageResult<TocChunk> {
+        let end = member_offsets
+            .range(entry.offset + 1..)
+            .next()
+            .copied()
+            .filter(|end| *end <= toc_offset && entry.offset < toc_offset)
+            .ok_or_else(|| estargz_err(digest, format!("bad data offset for {}", entry.name)))?;
+        let chunk_size = match entry.chunk_size {
+            0 => file_size.saturating_sub(entry.chunk_offset),
+            size => size,
+        };
+        Ok(TocChunk {
+            offset: entry.offset,
+            compressed_size: end - entry.offset,
+            chunk_offset: entry.chunk_offset,
+            chunk_size,
+        })
+    };
+
+    let mut scan = TocScan::new();
+    let mut total_size: u64 = 0;
+    let mut last_file: Option<(String, u64)> = None;
+
+    if toc.entries.len() as u64 > MAX_ENTRY_COUNT {
+        return Err(estargz_err(
+            digest,
+            format!("exceeded max entry count ({MAX_ENTRY_COUNT})"),
+        ));
+    }
+
+    for entry in &toc.entries {
+        validate_entry_path(dest, Path::new(&entry.name), &blob_id)?;
+        let rel = normalize_rel(Path::new(&entry.name));
+
+        if entry.kind == "chunk" {
+            let Some((path, size)) = last_file.as_ref().filter(|(path, _)| *path == rel) else {
+                return Err(estargz_err(
+                    digest,
+                    format!("chunk for {} does not follow its file", entry.name),
+                ));
+            };
+            let chunk = chunk_for(entry, *size)?;
+            if let Some(file) = scan.entries.get_mut(path) {
+                file.chunks.push(chunk);
+            }
+            continue;
+        }
+        last_file = None;
+
+        if LANDMARKS.contains(&rel.as_str()) || scan.record_whiteout(&rel) {
+            continue;
+        }
+
+        let perm = entry.mode & 0o7777;
+        let mut toc_entry = TocEntry {
+            path: rel,
+            kind: TocEntryKind::File,
+            mode: 0,
+            uid: entry.uid,
+            gid: entry.gid,
+            rdev: 0,
+            size: 0,
+            offset: 0,
+            link: None,
+            opaque: false,
+            implicit: false,
+            chunks: Vec::new(),
+            digest: None,
+        };
+
+        match entry.kind.as_str() {
+            "dir" => {
+                toc_entry.kind = TocEntryKind::Dir;
+                toc_entry.mode = S_IFDIR | perm;
+            }
+            "reg" => {
+                if entry.size > MAX_FILE_SIZE {
+                    return Err(estargz_err(
+                        digest,
+                        format!("file too large: {} bytes (max {MAX_FILE_SIZE})", entry.size),
+                    ));
+                }
+                total_size += entry.size;
+                if total_size > MAX_TOTAL_SIZE {
+                    return Err(estargz_err(
+                        digest,
+                        format!("total extraction size exceeded {MAX_TOTAL_SIZE} bytes"),
+                    ));
+                }
+                toc_entry.mode = S_IFREG | perm;
+                toc_entry.size = entry.size;
+                if entry.size > 0 {
+                    toc_entry.chunks.push(chunk_for(entry, entry.size)?);
+                }
+                toc_entry.digest = Some(entry.digest.clone()).filter(|d| !d.is_empty());
+                last_file = Some((toc_entry.path.clone(), entry.size));
+            }
+            "symlink" => {
+                toc_entry.kind = TocEntryKind::Symlink;
+                toc_entry.mode = S_IFLNK | 0o777;
+                toc_entry.link = Some(entry.link_name.clone());
+            }
+            "hardlink" => {
+                validate_entry_path(dest, Path::new(&entry.link_name), &blob_id)?;
+                let target = normalize_rel(Path::new(&entry.link_name));
+                let Some(target_entry) = scan.entries.get(&target) else {
+                    tracing::warn!(target = %target, link = %toc_entry.path, "hardlink target not found, skipping");
+                    continue;
+                };
+                toc_entry.kind = TocEntryKind::Hardlink;
+                toc_entry.mode = target_entry.mode;
+                toc_entry.uid = target_entry.uid;
+                toc_entry.gid = target_entry.gid;
+                toc_entry.rdev = target_entry.rdev;
+                toc_entry.link = Some(target);
+            }
+            "char" | "block" => {
+                let type_bits = if entry.kind == "block" {
+                    S_IFBLK
+                } else {
+                    S_IFCHR
+                };
+                toc_entry.kind = TocEntryKind::Special;
+                toc_entry.mode = type_bits | perm;
+                toc_entry.rdev = makedev(entry.dev_major, entry.dev_minor);
+            }
+            "fifo" => {
+                toc_entry.kind = TocEntryKind::Special;
+                toc_entry.mode = S_IFIFO | perm;
+            }
+            _ => continue,
+        }
+
+        scan.insert(toc_entry);
+    }
+
+    Ok(scan)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Whether a TOC entry starts a data member in the blob.
+fn has_data(entry: &EstargzEntry) -> bool {
+    entry.kind == "chunk" || (entry.kind == "reg" && entry.size > 0)
+}
+
+fn estargz_err(digest: &Digest, message: impl Into<String>) -> ImageError {
+    ImageError::Extraction {
+        digest: digest.to_string(),
+        message: format!("estargz: {}", message.into()),
+        source: None,
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::GzEncoder};
+    use microsandbox_utils::index::TocEntryKind;
+    use sha2::{Digest as Sha2Digest, Sha256};
+
+    use super::{FOOTER_SIZE, decode_toc, parse_footer, scan_toc};
+    use crate::digest::Digest;
+
+    fn digest() -> Digest {
+        format!("sha256:{}", "a".repeat(64)).parse().unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Build the footer the estargz writer emits for `toc_offset`.
+    fn footer(toc_offset: u64) -> Vec<u8> {
+        let mut extra = b"SG".to_vec();
+        extra.extend_from_slice(&22u16.to_le_bytes());
+        extra.extend_from_slice(format!("{toc_offset:016x}STARGZ").as_bytes());
+
+        let mut out = vec![0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff];
+        out.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        out.extend_from_slice(&extra);
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+        out.extend_from_slice(&[0; 8]);
+        out
+    }
+
+    #[test]
+    fn test_parse_footer_reads_toc_offset() {
+        let footer = footer(0x1234);
+        assert_eq!(footer.len() as u64, FOOTER_SIZE);
+        assert_eq!(parse_footer(&footer, &digest()).unwrap(), 0x1234);
+
+        let mut plain = footer.clone();
+        plain[32..38].copy_from_slice(b"NOTGZ!");
+        assert!(parse_footer(&plain, &digest()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decode_toc_verifies_digest_and_builds_chunks() {
+        let json = serde_json::json!({
+            "version": 1,
+            "entries": [
+                {"name": "etc/", "type": "dir", "mode": 0o750, "uid": 1000, "gid": 1000},
+                {"name": "etc/hosts", "type": "reg", "size": 9, "mode": 0o644,
+                 "offset": 100, "digest": "sha256:abc"},
+                {"name": "etc/.wh.passwd", "type": "reg", "size": 0},
+                {"name": "model.bin", "type": "reg", "size": 20, "mode": 0o600,
+                 "offset": 200, "chunkSize": 12},
+                {"name": "model.bin", "type": "chunk", "offset": 300, "chunkOffset": 12},
+                {"name": ".prefetch.landmark", "type": "reg", "size": 1, "offset": 400},
+                {"name": "bin/sh", "type": "symlink", "linkName": "busybox"},
+            ]
+        })
+        .to_string()
+        .into_bytes();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_ustar();
+        header.set_size(json.len() as u64);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "stargz.index.json", json.as_slice())
+            .unwrap();
+        let member = gzip(&builder.into_inner().unwrap());
+
+        let expected = format!("sha256:{}", hex::encode(Sha256::digest(&json)));
+        assert!(decode_toc(&member, "sha256:0000", &digest()).await.is_err());
+        let toc = decode_toc(&member, &expected, &digest()).await.unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        let scan = scan_toc(&toc, 500, dest.path(), &digest()).unwrap();
+
+        let hosts = &scan.entries["etc/hosts"];
+        assert_eq!(hosts.kind, TocEntryKind::File);
+        assert_eq!(hosts.digest.as_deref(), Some("sha256:abc"));
+        assert_eq!(hosts.chunks.len(), 1);
+        assert_eq!(hosts.chunks[0].offset, 100);
+        assert_eq!(hosts.chunks[0].compressed_size, 100);
+
+        let model = &scan.entries["model.bin"];
+        assert_eq!(model.chunks.len(), 2);
+        assert_eq!(model.chunks[0].chunk_size, 12);
+        assert_eq!(model.chunks[1].chunk_offset, 12);
+        assert_eq!(model.chunks[1].chunk_size, 8);
+        assert_eq!(model.chunks[1].compressed_size, 100);
+
+        assert!(!scan.entries.contains_key(".prefetch.landmark"));
+        assert!(!scan.entries.contains_key("etc/.wh.passwd"));
+        assert!(scan.entries["bin"].implicit);
+        assert_eq!(scan.entries["bin/sh"].link.as_deref(), Some("busybox"));
+    }
+}
@@ -30,13 +30,13 @@ use crate::{
 const OVERRIDE_STAT_VERSION: u8 = 1;
 
 /// Maximum total extracted size (10 GiB).
-const MAX_TOTAL_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+pub(super) const MAX_TOTAL_SIZE: u64 = 10 * 1024 * 1024 * 1024;
 
 /// Maximum single file size (5 GiB).
-const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+pub(super) const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024 * 1024;
 
 /// Maximum number of tar entries.
-const MAX_ENTRY_COUNT: u64 = 1_000_000;
+pub(super) const MAX_ENTRY_COUNT: u64 = 1_000_000;
 
 /// Maximum path depth.
 const MAX_PATH_DEPTH: usize = 128;
@@ -46,29 +46,29 @@ const EXTRACT_PROGRESS_INTERVAL: u64 = 64 * 1024;
 
 /// File type bits (from libc).
 #[cfg(target_os = "linux")]
-const S_IFREG: u32 = libc::S_IFREG;
+pub(super) const S_IFREG: u32 = libc::S_IFREG;
 #[cfg(target_os = "macos")]
-const S_IFREG: u32 = libc::S_IFREG as u32;
+pub(super) const S_IFREG: u32 = libc::S_IFREG as u32;
 #[cfg(target_os = "linux")]
-const S_IFDIR: u32 = libc::S_IFDIR;
+pub(super) const S_IFDIR: u32 = libc::S_IFDIR;
 #[cfg(target_os = "macos")]
-const S_IFDIR: u32 = libc::S_IFDIR as u32;
+pub(super) const S_IFDIR: u32 = libc::S_IFDIR as u32;
 #[cfg(target_os = "linux")]
 const S_IFLNK: u32 = libc::S_IFLNK;
 #[cfg(target_os = "macos")]
 const S_IFLNK: u32 = libc::S_IFLNK as u32;
 #[cfg(target_os = "linux")]
-const S_IFBLK: u32 = libc::S_IFBLK;
+pub(super) const S_IFBLK: u32 = libc::S_IFBLK;
 #[cfg(target_os = "macos")]
-const S_IFBLK: u32 = libc::S_IFBLK as u32;
+pub(super) const S_IFBLK: u32 = libc::S_IFBLK as u32;
 #[cfg(target_os = "linux")]
-const S_IFCHR: u32 = libc::S_IFCHR;
+pub(super) const S_IFCHR: u32 = libc::S_IFCHR;
 #[cfg(target_os = "macos")]
-const S_IFCHR: u32 = libc::S_IFCHR as u32;
+pub(super) const S_IFCHR: u32 = libc::S_IFCHR as u32;
 #[cfg(target_os = "linux")]
-const S_IFIFO: u32 = libc::S_IFIFO;
+pub(super) const S_IFIFO: u32 = libc::S_IFIFO;
 #[cfg(target_os = "macos")]
-const S_IFIFO: u32 = libc::S_IFIFO as u32;
+pub(super) const S_IFIFO: u32 = libc::S_IFIFO as u32;
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -93,7 +93,7 @@ struct DeferredHardlink {
 
 /// Compression format for a layer blob.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum LayerCompression {
+pub(super) enum LayerCompression {
     Plain,
     Gzip,
     Zstd,
@@ -394,7 +394,11 @@ pub(crate) async fn extract_layer(
 //--------------------------------------------------------------------------------------------------
 
 /// Validate a tar entry path to prevent path traversal.
-fn validate_entry_path(dest: &Path, entry_path: &Path, tar_path: &Path) -> ImageResult<PathBuf> {
+pub(super) fn validate_entry_path(
+    dest: &Path,
+    entry_path: &Path,
+    tar_path: &Path,
+) -> ImageResult<PathBuf> {
     // Reject absolute paths.
     if entry_path.is_absolute() {
         return Err(ImageError::Extraction {
@@ -585,11 +589,11 @@ fn set_override_stat_symlink(
 }
 
 /// Construct a device number from major and minor (glibc-compatible encoding).
-fn makedev(major: u32, minor: u32) -> u32 {
+pub(super) fn makedev(major: u32, minor: u32) -> u32 {
     ((major & 0xFFF) << 8) | (minor & 0xFF) | ((minor & 0xFFFFF00) << 12)
 }
 
-fn extraction_err(
+pub(super) fn extraction_err(
     tar_path: &Path,
     e: impl Into<Box<dyn std::error::Error + Send + Sync>>,
 ) -> ImageError {
@@ -624,6 +628,18 @@ pub(crate) fn fixup_implicit_dirs(
         // Search lower layers top-to-bottom (most recent first).
         for lower in lower_layers.iter().rev() {
             let source = lower.join(rel_dir);
+            if !source.exists()
+                && let Some(data) = super::lazy::lazy_lower_override_stat(lower, rel_dir)
+            {
+                if let Err(e) = xattr::set(&target, OVERRIDE_XATTR_KEY, &data) {
+                    tracing::warn!(
+                        target = %target.display(),
+                        error = %e,
+                        "failed to copy override_stat from lazy layer during fixup"
+                    );
+                }
+                break;
+            }
             if source.exists() {
                 if let Ok(Some(data)) = xattr::get(&source, OVERRIDE_XATTR_KEY)
                     && let Err(e) = xattr::set(&target, OVERRIDE_XATTR_KEY, &data)
@@ -684,7 +700,7 @@ fn ensure_host_path_contained(dest: &Path, path: &Path, tar_path: &Path) -> Imag
 }
 
 /// Detect the compression format for a layer blob.
-fn detect_layer_compression(
+pub(super) fn detect_layer_compression(
     tar_path: &Path,
     media_type: Option<&str>,
 ) -> ImageResult<LayerCompression> {
@@ -0,0 +1,667 @@
+//! Lazy layer preparation.
+//!
+//! Instead of extracting a layer, the blob is decompressed once into a raw
+//! tarball and its headers are scanned to build the sidecar index and a
+//! [`LayerToc`]. The layer directory starts out empty; OverlayFs materializes
+//! individual entries from the tarball the first time they are looked up.
+//!
+//! This path needs the whole blob before the layer is ready. Seekable estargz
+//! layers skip it; see [`estargz`](super::estargz).
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Component, Path, PathBuf},
+};
+
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use microsandbox_utils::index::{IndexBuilder, LayerToc, TocEntry, TocEntryKind, TocSource};
+use tokio::io::{AsyncRead, BufReader};
+use tokio_tar as tar;
+
+use super::{
+    OVERRIDE_XATTR_KEY, S_IFLNK,
+    extraction::{
+        LayerCompression, MAX_ENTRY_COUNT, MAX_FILE_SIZE, MAX_TOTAL_SIZE, S_IFBLK, S_IFCHR,
+        S_IFDIR, S_IFIFO, S_IFREG, detect_layer_compression, extraction_err, makedev,
+        validate_entry_path,
+    },
+};
+use crate::error::{ImageError, ImageResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Whiteout file prefix.
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// Opaque whiteout marker.
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Paths produced by lazy preparation.
+pub(crate) struct LazyLayerPaths<'a> {
+    /// Empty layer directory that entries are materialized into.
+    pub dir: &'a Path,
+    /// Sidecar index output.
+    pub index: &'a Path,
+    /// Uncompressed tarball output.
+    pub tar: &'a Path,
+    /// Table of contents output, written last.
+    pub toc: &'a Path,
+}
+
+/// Per-directory state collected while scanning tar headers.
+#[derive(Default)]
+struct DirState {
+    opaque: bool,
+    whiteouts: BTreeSet<String>,
+}
+
+/// Accumulates entries from tar headers in tar order (later entries win).
+#[derive(Default)]
+pub(super) struct TocScan {
+    pub(super) entries: BTreeMap<String, TocEntry>,
+    dirs: BTreeMap<String, DirState>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl TocScan {
+    pub(super) fn new() -> Self {
+        let mut scan = Self::default();
+        scan.dirs.insert(String::new(), DirState::default());
+        scan
+    }
+
+    /// Record implicit parent directories for `rel`.
+    fn ensure_parents(&mut self, rel: &str) {
+        let (parent, _) = split_parent(rel);
+        self.ensure_dir(parent);
+    }
+
+    /// Record `dir` and its ancestors as implicit directories if not yet seen.
+    fn ensure_dir(&mut self, dir: &str) {
+        if self.dirs.contains_key(dir) {
+            return;
+        }
+        self.ensure_parents(dir);
+        self.dirs.insert(dir.to_string(), DirState::default());
+        self.entries.insert(
+            dir.to_string(),
+            TocEntry {
+                path: dir.to_string(),
+                kind: TocEntryKind::Dir,
+                mode: S_IFDIR | 0o755,
+                uid: 0,
+                gid: 0,
+                rdev: 0,
+                size: 0,
+                offset: 0,
+                link: None,
+                opaque: false,
+                implicit: true,
+                chunks: Vec::new(),
+                digest: None,
+            },
+        );
+    }
+
+    pub(super) fn insert(&mut self, entry: TocEntry) {
+        self.ensure_parents(&entry.path);
+        if entry.kind == TocEntryKind::Dir {
+            self.dirs.entry(entry.path.clone()).or_default();
+        } else if self.dirs.remove(&entry.path).is_some() {
+            // A non-directory replaces a directory from earlier in the tar.
+            let prefix = format!("{}/", entry.path);
+            self.dirs.retain(|path, _| !path.starts_with(&prefix));
+            self.entries.retain(|path, _| !path.starts_with(&prefix));
+        }
+        let (parent, name) = split_parent(&entry.path);
+        if let Some(dir) = self.dirs.get_mut(parent) {
+            dir.whiteouts.remove(name);
+        }
+        self.entries.insert(entry.path.clone(), entry);
+    }
+
+    /// Record `rel` as a whiteout or opaque marker if its name is one.
+    ///
+    /// Returns `false` for ordinary entries.
+    pub(super) fn record_whiteout(&mut self, rel: &str) -> bool {
+        let (parent, name) = split_parent(rel);
+        if name == OPAQUE_WHITEOUT {
+            self.opaque(parent);
+            return true;
+        }
+        if let Some(target) = name.strip_prefix(WHITEOUT_PREFIX) {
+            if !target.is_empty() {
+                self.whiteout(parent, target);
+            }
+            return true;
+        }
+        false
+    }
+
+    fn whiteout(&mut self, dir: &str, name: &str) {
+        self.ensure_dir(dir);
+        if let Some(state) = self.dirs.get_mut(dir) {
+            state.whiteouts.insert(name.to_string());
+        }
+    }
+
+    fn opaque(&mut self, dir: &str) {
+        self.ensure_dir(dir);
+        if let Some(state) = self.dirs.get_mut(dir) {
+            state.opaque = true;
+        }
+    }
+
+    /// Build the sidecar index bytes from the scanned entries.
+    fn build_index(&self) -> Vec<u8> {
+        let mut builder = IndexBuilder::new();
+        for (path, state) in &self.dirs {
+            builder = if state.opaque {
+                builder.opaque_dir(path)
+            } else {
+                builder.dir(path)
+            };
+        }
+
+        for entry in self.entries.values() {
+            if entry.path.is_empty() {
+                continue;
+            }
+            let (parent, name) = split_parent(&entry.path);
+            builder = match entry.kind {
+                TocEntryKind::Dir => builder.subdir(parent, name, entry.mode & 0o7777),
+                TocEntryKind::Symlink => builder.symlink(parent, name),
+                _ => builder.file(parent, name, entry.mode & 0o7777),
+            };
+        }
+
+        for (path, state) in &self.dirs {
+            for name in &state.whiteouts {
+                if !self.entries.contains_key(&join_rel(path, name)) {
+                    builder = builder.whiteout(path, name);
+                }
+            }
+        }
+
+        builder.build()
+    }
+
+    fn into_toc(mut self, source: TocSource) -> LayerToc {
+        for (path, state) in &self.dirs {
+            if state.opaque
+                && let Some(entry) = self.entries.get_mut(path)
+            {
+                entry.opaque = true;
+            }
+        }
+        let mut toc = LayerToc::new();
+        toc.source = source;
+        toc.entries = self.entries.into_values().collect();
+        toc
+    }
+
+    /// Write the sidecar index and then the table of contents, which marks
+    /// the layer as ready.
+    pub(super) fn write(
+        self,
+        dir: &Path,
+        index_path: &Path,
+        toc_path: &Path,
+        source: TocSource,
+    ) -> ImageResult<()> {
+        let index = self.build_index();
+        std::fs::write(index_path, &index)
+            .map_err(|e| ImageError::IndexBuild(dir.display().to_string(), e))?;
+
+        self.into_toc(source)
+            .write(toc_path)
+            .map_err(|e| ImageError::Cache {
+                path: toc_path.to_path_buf(),
+                source: e,
+            })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Prepare a layer blob for lazy materialization.
+///
+/// Decompresses `blob_path` into `paths.tar`, scans the tar headers, and
+/// writes the sidecar index and table of contents. The table of contents is
+/// written last and marks the layer as ready.
+pub(crate) async fn prepare_lazy_layer(
+    blob_path: &Path,
+    paths: LazyLayerPaths<'_>,
+    media_type: Option<&str>,
+) -> ImageResult<()> {
+    decompress_to_tar(blob_path, paths.tar, media_type).await?;
+
+    std::fs::create_dir_all(paths.dir).map_err(|e| ImageError::Cache {
+        path: paths.dir.to_path_buf(),
+        source: e,
+    })?;
+
+    let scan = scan_tar(paths.tar, paths.dir, blob_path).await?;
+    scan.write(paths.dir, paths.index, paths.toc, TocSource::Tar)
+}
+
+/// Fix metadata of implicit directories in a lazy layer from lower layers.
+///
+/// Mirrors [`fixup_implicit_dirs`](super::extraction::fixup_implicit_dirs) for
+/// layers whose entries are described by a table of contents. Lower layers may
+/// be either extracted or lazy.
+pub(crate) fn fixup_implicit_dirs(toc_path: &Path, lower_layers: &[PathBuf]) -> ImageResult<()> {
+    let mut toc = LayerToc::read(toc_path).map_err(|e| ImageError::Cache {
+        path: toc_path.to_path_buf(),
+        source: e,
+    })?;
+    if !toc.entries.iter().any(|entry| entry.implicit) {
+        return Ok(());
+    }
+
+    let mut lower_tocs: HashMap<usize, Option<LayerToc>> = HashMap::new();
+    for entry in toc.entries.iter_mut().filter(|entry| entry.implicit) {
+        for (i, lower) in lower_layers.iter().enumerate().rev() {
+            let lower_toc = lower_tocs
+                .entry(i)
+                .or_insert_with(|| LayerToc::read(&lower.with_extension("toc")).ok());
+            if let Some(stat) = lower_dir_stat(lower, lower_toc.as_ref(), &entry.path) {
+                (entry.uid, entry.gid, entry.mode) = stat;
+                break;
+            }
+        }
+        entry.implicit = false;
+    }
+
+    toc.write(toc_path).map_err(|e| ImageError::Cache {
+        path: toc_path.to_path_buf(),
+        source: e,
+    })
+}
+
+/// Look up the override stat bytes for `rel_dir` in a lazy lower layer.
+///
+/// Returns `None` if `lower` is not lazy or does not define the directory.
+pub(crate) fn lazy_lower_override_stat(lower: &Path, rel_dir: &Path) -> Option<[u8; 20]> {
+    let toc = LayerToc::read(&lower.with_extension("toc")).ok()?;
+    let rel = rel_dir.to_string_lossy();
+    let entry = toc
+        .entries
+        .iter()
+        .find(|entry| entry.path == rel && entry.kind == TocEntryKind::Dir)?;
+    Some(override_stat_bytes(entry.uid, entry.gid, entry.mode))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Decompress a layer blob into an uncompressed tarball.
+async fn decompress_to_tar(
+    blob_path: &Path,
+    tar_path: &Path,
+    media_type: Option<&str>,
+) -> ImageResult<()> {
+    let compression = detect_layer_compression(blob_path, media_type)?;
+    let file = tokio::fs::File::open(blob_path)
+        .await
+        .map_err(|e| ImageError::Extraction {
+            digest: blob_path.display().to_string(),
+            message: format!("failed to open tarball: {e}"),
+            source: Some(Box::new(e)),
+        })?;
+
+    let mut reader: Box<dyn AsyncRead + Unpin + Send> = match compression {
+        LayerCompression::Plain => Box::new(file),
+        LayerCompression::Gzip => Box::new(GzipDecoder::new(BufReader::new(file))),
+        LayerCompression::Zstd => Box::new(ZstdDecoder::new(BufReader::new(file))),
+    };
+
+    let part_path = tar_path.with_extension("tar.part");
+    let mut out = tokio::fs::File::create(&part_path)
+        .await
+        .map_err(|e| ImageError::Cache {
+            path: part_path.clone(),
+            source: e,
+        })?;
+    if let Err(e) = tokio::io::copy(&mut reader, &mut out).await {
+        let _ = std::fs::remove_file(&part_path);
+        return Err(extraction_err(blob_path, e));
+    }
+    out.sync_all().await.map_err(|e| ImageError::Cache {
+        path: part_path.clone(),
+        source: e,
+    })?;
+    drop(out);
+
+    std::fs::rename(&part_path, tar_path).map_err(|e| ImageError::Cache {
+        path: tar_path.to_path_buf(),
+        source: e,
+    })
+}
+
+/// Scan an uncompressed tarball and collect its entries.
+async fn scan_tar(tar_path: &Path, dest: &Path, blob_path: &Path) -> ImageResult<TocScan> {
+    use futures::StreamExt;
+
+    let file = tokio::fs::File::open(tar_path)
+        .await
+        .map_err(|e| extraction_err(blob_path, e))?;
+    let mut archive = tar::Archive::new(BufReader::new(file));
+    let mut entries = archive
+        .entries()
+        .map_err(|e| extraction_err(blob_path, e))?;
+
+    let mut scan = TocScan::new();
+    let mut total_size: u64 = 0;
+    let mut entry_count: u64 = 0;
+
+    while let Some(entry_result) = entries.next().await {
+        let entry = entry_result.map_err(|e| extraction_err(blob_path, e))?;
+
+        entry_count += 1;
+        if entry_count > MAX_ENTRY_COUNT {
+            return Err(ImageError::Extraction {
+                digest: blob_path.display().to_string(),
+                message: format!("exceeded max entry count ({MAX_ENTRY_COUNT})"),
+                source: None,
+            });
+        }
+
+        let header = entry.header();
+        let entry_path = entry
+            .path()
+            .map_err(|e| extraction_err(blob_path, e))?
+            .into_owned();
+        validate_entry_path(dest, &entry_path, blob_path)?;
+        let rel = normalize_rel(&entry_path);
+
+        let uid = header.uid().unwrap_or(0) as u32;
+        let gid = header.gid().unwrap_or(0) as u32;
+        let tar_mode = header.mode().unwrap_or(0o644) & 0o7777;
+        let size = header.size().unwrap_or(0);
+        let entry_type = header.entry_type();
+
+        if scan.record_whiteout(&rel) {
+            continue;
+        }
+
+        let mut toc_entry = TocEntry {
+            path: rel,
+            kind: TocEntryKind::File,
+            mode: 0,
+            uid,
+            gid,
+            rdev: 0,
+            size: 0,
+            offset: 0,
+            link: None,
+            opaque: false,
+            implicit: false,
+            chunks: Vec::new(),
+            digest: None,
+        };
+
+        if entry_type == tar::EntryType::Directory {
+            toc_entry.kind = TocEntryKind::Dir;
+            toc_entry.mode = S_IFDIR | tar_mode;
+        } else if entry_type == tar::EntryType::Symlink {
+            let target = entry
+                .link_name()
+                .map_err(|e| extraction_err(blob_path, e))?
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            toc_entry.kind = TocEntryKind::Symlink;
+            toc_entry.mode = S_IFLNK | 0o777;
+            toc_entry.link = Some(target);
+        } else if entry_type == tar::EntryType::Link {
+            let Some(target) = entry
+                .link_name()
+                .map_err(|e| extraction_err(blob_path, e))?
+                .map(|p| p.into_owned())
+            else {
+                continue;
+            };
+            validate_entry_path(dest, &target, blob_path)?;
+            let target = normalize_rel(&target);
+            let Some(target_entry) = scan.entries.get(&target) else {
+                tracing::warn!(target = %target, link = %toc_entry.path, "hardlink target not found, skipping");
+                continue;
+            };
+            toc_entry.kind = TocEntryKind::Hardlink;
+            toc_entry.mode = target_entry.mode;
+            toc_entry.uid = target_entry.uid;
+            toc_entry.gid = target_entry.gid;
+            toc_entry.rdev = target_entry.rdev;
+            toc_entry.link = Some(target);
+        } else if entry_type == tar::EntryType::Regular || entry_type == tar::EntryType::Continuous
+        {
+            if size > MAX_FILE_SIZE {
+                return Err(ImageError::Extraction {
+                    digest: blob_path.display().to_string(),
+                    message: format!("file too large: {size} bytes (max {MAX_FILE_SIZE})"),
+                    source: None,
+                });
+            }
+            total_size += size;
+            if total_size > MAX_TOTAL_SIZE {
+                return Err(ImageError::Extraction {
+                    digest: blob_path.display().to_string(),
+                    message: format!("total extraction size exceeded {MAX_TOTAL_SIZE} bytes"),
+                    source: None,
+                });
+            }
+            toc_entry.mode = S_IFREG | tar_mode;
+            toc_entry.size = size;
+            toc_entry.offset = entry.raw_file_position();
+        } else if entry_type == tar::EntryType::Block || entry_type == tar::EntryType::Char {
+            let major = header.device_major().unwrap_or(None).unwrap_or(0);
+            let minor = header.device_minor().unwrap_or(None).unwrap_or(0);
+            let type_bits = if entry_type == tar::EntryType::Block {
+                S_IFBLK
+            } else {
+                S_IFCHR
+            };
+            toc_entry.kind = TocEntryKind::Special;
+            toc_entry.mode = type_bits | tar_mode;
+            toc_entry.rdev = makedev(major, minor);
+        } else if entry_type == tar::EntryType::Fifo {
+            toc_entry.kind = TocEntryKind::Special;
+            toc_entry.mode = S_IFIFO | tar_mode;
+        } else {
+            // Skip other types (GNUSparse, XHeader, etc.)
+            continue;
+        }
+
+        scan.insert(toc_entry);
+    }
+
+    Ok(scan)
+}
+
+/// Normalize a tar entry path into a `/`-separated layer-relative string.
+pub(super) fn normalize_rel(path: &Path) -> String {
+    let parts: Vec<_> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect();
+    parts.join("/")
+}
+
+/// Split a layer-relative path into its parent directory and final name.
+fn split_parent(rel: &str) -> (&str, &str) {
+    match rel.rfind('/') {
+        Some(pos) => (&rel[..pos], &rel[pos + 1..]),
+        None => ("", rel),
+    }
+}
+
+fn join_rel(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+/// Look up a directory's `(uid, gid, mode)` in a lower layer.
+fn lower_dir_stat(
+    lower: &Path,
+    lower_toc: Option<&LayerToc>,
+    rel: &str,
+) -> Option<(u32, u32, u32)> {
+    if let Some(toc) = lower_toc {
+        return toc
+            .entries
+            .iter()
+            .find(|entry| entry.path == rel && entry.kind == TocEntryKind::Dir)
+            .map(|entry| (entry.uid, entry.gid, entry.mode));
+    }
+
+    let data = xattr::get(lower.join(rel), OVERRIDE_XATTR_KEY).ok()??;
+    if data.len() < 20 {
+        return None;
+    }
+    let uid = u32::from_le_bytes(data[4..8].try_into().ok()?);
+    let gid = u32::from_le_bytes(data[8..12].try_into().ok()?);
+    let mode = u32::from_le_bytes(data[12..16].try_into().ok()?);
+    Some((uid, gid, mode))
+}
+
+fn override_stat_bytes(uid: u32, gid: u32, mode: u32) -> [u8; 20] {
+    let mut buf = [0u8; 20];
+    buf[0] = 1;
+    buf[4..8].copy_from_slice(&uid.to_le_bytes());
+    buf[8..12].copy_from_slice(&gid.to_le_bytes());
+    buf[12..16].copy_from_slice(&mode.to_le_bytes());
+    buf
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use microsandbox_utils::index::{LayerToc, MmapIndex, TocEntryKind};
+    use tempfile::tempdir;
+
+    use super::{LazyLayerPaths, fixup_implicit_dirs, prepare_lazy_layer};
+
+    fn append(builder: &mut tar::Builder<Vec<u8>>, path: &str, kind: tar::EntryType, data: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(data.len() as u64);
+        header.set_mode(if kind == tar::EntryType::Directory {
+            0o750
+        } else {
+            0o640
+        });
+        header.set_uid(1000);
+        header.set_gid(1000);
+        header.set_path(path).unwrap();
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_prepare_lazy_layer_builds_toc_and_index_without_extracting() {
+        let temp = tempdir().unwrap();
+        let blob = temp.path().join("layer.tar");
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, "etc/", tar::EntryType::Directory, b"");
+        append(
+            &mut builder,
+            "etc/hosts",
+            tar::EntryType::Regular,
+            b"127.0.0.1",
+        );
+        append(
+            &mut builder,
+            "usr/lib/model.bin",
+            tar::EntryType::Regular,
+            b"weights",
+        );
+        append(&mut builder, "etc/.wh.passwd", tar::EntryType::Regular, b"");
+        std::fs::write(&blob, builder.into_inner().unwrap()).unwrap();
+
+        let dir = temp.path().join("lazy.extracted");
+        let paths = LazyLayerPaths {
+            dir: &dir,
+            index: &dir.with_extension("index"),
+            tar: &dir.with_extension("tar"),
+            toc: &dir.with_extension("toc"),
+        };
+        prepare_lazy_layer(&blob, paths, Some("application/vnd.oci.image.layer.v1.tar"))
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let toc = LayerToc::read(&dir.with_extension("toc")).unwrap();
+        let hosts = toc.entries.iter().find(|e| e.path == "etc/hosts").unwrap();
+        assert_eq!(hosts.kind, TocEntryKind::File);
+        assert_eq!(hosts.uid, 1000);
+        let raw = std::fs::read(dir.with_extension("tar")).unwrap();
+        let offset = hosts.offset as usize;
+        assert_eq!(&raw[offset..offset + hosts.size as usize], b"127.0.0.1");
+
+        let usr = toc.entries.iter().find(|e| e.path == "usr").unwrap();
+        assert!(usr.implicit);
+
+        let index = MmapIndex::open(&dir.with_extension("index")).unwrap();
+        let (_, etc) = index.find_dir(b"etc").unwrap();
+        assert!(index.find_entry(etc, b"hosts").is_some());
+        assert!(index.has_whiteout(etc, b"passwd"));
+    }
+
+    #[tokio::test]
+    async fn test_fixup_implicit_dirs_copies_stat_from_lower_toc() {
+        let temp = tempdir().unwrap();
+
+        let lower_blob = temp.path().join("lower.tar");
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, "usr/", tar::EntryType::Directory, b"");
+        std::fs::write(&lower_blob, builder.into_inner().unwrap()).unwrap();
+
+        let upper_blob = temp.path().join("upper.tar");
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, "usr/bin", tar::EntryType::Regular, b"");
+        std::fs::write(&upper_blob, builder.into_inner().unwrap()).unwrap();
+
+        let mut dirs = Vec::new();
+        for (name, blob) in [("lower", &lower_blob), ("upper", &upper_blob)] {
+            let dir = temp.path().join(format!("{name}.extracted"));
+            let paths = LazyLayerPaths {
+                dir: &dir,
+                index: &dir.with_extension("index"),
+                tar: &dir.with_extension("tar"),
+                toc: &dir.with_extension("toc"),
+            };
+            prepare_lazy_layer(blob, paths, None).await.unwrap();
+            dirs.push(dir);
+        }
+
+        fixup_implicit_dirs(&dirs[1].with_extension("toc"), &dirs[..1]).unwrap();
+
+        let toc = LayerToc::read(&dirs[1].with_extension("toc")).unwrap();
+        let usr = toc.entries.iter().find(|e| e.path == "usr").unwrap();
+        assert!(!usr.implicit);
+        assert_eq!(usr.uid, 1000);
+        assert_eq!(usr.mode & 0o7777, 0o750);
+    }
+}
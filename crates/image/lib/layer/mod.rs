@@ -1,7 +1,9 @@
 //! Layer download, extraction, and management.
 
+pub(crate) mod estargz;
 pub(crate) mod extraction;
 pub(crate) mod index;
+pub(crate) mod lazy;
 
 use std::{
     fs::{File, OpenOptions},
@@ -10,6 +12,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use microsandbox_utils::index::{TocSource, blob_lock_path};
 use oci_client::client::{BlobResponse, SizedStream};
 use sha2::{Digest as Sha2Digest, Sha256};
 
@@ -26,6 +29,13 @@ use crate::{
 /// Xattr key for stat virtualization.
 pub(crate) const OVERRIDE_XATTR_KEY: &str = "user.containers.override_stat";
 
+/// Attempts to take the estargz blob lock before assuming another process
+/// is downloading the blob. Readers hold a shared lock only briefly.
+const BLOB_LOCK_ATTEMPTS: u32 = 5;
+
+/// Delay between attempts to take the estargz blob lock.
+const BLOB_LOCK_RETRY: std::time::Duration = std::time::Duration::from_millis(10);
+
 /// File type mask.
 #[cfg(target_os = "linux")]
 pub(crate) const S_IFMT: u32 = libc::S_IFMT;
@@ -55,6 +65,11 @@ pub(crate) struct Layer {
     lock_path: PathBuf,
     download_lock_path: PathBuf,
     part_path: PathBuf,
+    lazy_dir: PathBuf,
+    lazy_index_path: PathBuf,
+    lazy_tar_path: PathBuf,
+    lazy_blob_path: PathBuf,
+    lazy_toc_path: PathBuf,
 }
 
 enum DownloadStart {
@@ -79,6 +94,11 @@ impl Layer {
             lock_path: cache.lock_path(&digest),
             download_lock_path: cache.download_lock_path(&digest),
             part_path: cache.part_path(&digest),
+            lazy_dir: cache.lazy_dir(&digest),
+            lazy_index_path: cache.lazy_index_path(&digest),
+            lazy_tar_path: cache.lazy_tar_path(&digest),
+            lazy_blob_path: cache.lazy_blob_path(&digest),
+            lazy_toc_path: cache.lazy_toc_path(&digest),
             digest,
         }
     }
@@ -88,6 +108,21 @@ impl Layer {
         self.extracted_dir.clone()
     }
 
+    /// Path to the lazily materialized layer directory.
+    pub fn lazy_dir(&self) -> PathBuf {
+        self.lazy_dir.clone()
+    }
+
+    /// Path to the table of contents for the lazy form of this layer.
+    pub fn lazy_toc_path(&self) -> &Path {
+        &self.lazy_toc_path
+    }
+
+    /// Check if the lazy form of this layer is ready.
+    pub fn is_lazy_ready(&self) -> bool {
+        self.lazy_toc_path.exists()
+    }
+
     /// Check if this layer is already fully extracted.
     pub fn is_extracted(&self) -> bool {
         self.extracted_dir.join(store::COMPLETE_MARKER).exists()
@@ -370,6 +405,245 @@ impl Layer {
         Ok(result)
     }
 
+    /// Prepare this layer for lazy materialization instead of extracting it.
+    ///
+    /// Decompresses the blob into a raw tarball and builds the sidecar index
+    /// and table of contents from its headers. Shares the extraction `flock()`
+    /// so a concurrent extraction and preparation of the same layer serialize.
+    pub async fn prepare_lazy(
+        &self,
+        progress: Option<&crate::progress::PullProgressSender>,
+        layer_index: usize,
+        media_type: Option<&str>,
+        diff_id: &str,
+        force: bool,
+    ) -> ImageResult<()> {
+        let lock_file = open_lock_file(&self.lock_path)?;
+        flock_exclusive(&lock_file)?;
+        let lock_path = self.lock_path.clone();
+        let _flock_guard = scopeguard::guard(lock_file, |f| {
+            let _ = flock_unlock(&f);
+            drop(f);
+            let _ = std::fs::remove_file(&lock_path);
+        });
+
+        if self.is_lazy_ready() && !force {
+            return Ok(());
+        }
+
+        let diff_id_arc: std::sync::Arc<str> = diff_id.into();
+        if let Some(p) = progress {
+            p.send(crate::progress::PullProgress::LayerExtractStarted {
+                layer_index,
+                diff_id: diff_id_arc.clone(),
+            });
+        }
+
+        // Clean up any previous incomplete preparation.
+        remove_file_if_exists(&self.lazy_toc_path)?;
+        remove_file_if_exists(&self.lazy_index_path)?;
+        remove_file_if_exists(&self.lazy_tar_path)?;
+        remove_file_if_exists(&self.lazy_blob_path)?;
+        let _ = std::fs::remove_dir_all(&self.lazy_dir);
+
+        let paths = lazy::LazyLayerPaths {
+            dir: &self.lazy_dir,
+            index: &self.lazy_index_path,
+            tar: &self.lazy_tar_path,
+            toc: &self.lazy_toc_path,
+        };
+        if let Err(e) = lazy::prepare_lazy_layer(&self.tar_path, paths, media_type).await {
+            let _ = std::fs::remove_file(&self.lazy_tar_path);
+            let _ = std::fs::remove_file(&self.lazy_index_path);
+            let _ = std::fs::remove_dir_all(&self.lazy_dir);
+            return Err(e);
+        }
+
+        if let Some(p) = progress {
+            p.send(crate::progress::PullProgress::LayerExtractComplete {
+                layer_index,
+                diff_id: diff_id_arc,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Prepare this layer for lazy materialization from a seekable estargz blob.
+    ///
+    /// Fetches only the footer and table of contents with range requests and
+    /// builds the sidecar index and [`LayerToc`](microsandbox_utils::index::LayerToc)
+    /// from them, so the layer is ready before its data is downloaded. If the
+    /// blob is incomplete and no other process is downloading it, returns a
+    /// task that streams the rest of it in the background.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn prepare_estargz(
+        &self,
+        client: &oci_client::Client,
+        image_ref: &oci_client::Reference,
+        blob_size: u64,
+        toc_digest: &str,
+        progress: Option<&crate::progress::PullProgressSender>,
+        layer_index: usize,
+        force: bool,
+    ) -> ImageResult<Option<tokio::task::JoinHandle<ImageResult<()>>>> {
+        let lock_file = open_lock_file(&self.lock_path)?;
+        flock_exclusive(&lock_file)?;
+        let lock_path = self.lock_path.clone();
+        let _flock_guard = scopeguard::guard(lock_file, |f| {
+            let _ = flock_unlock(&f);
+            drop(f);
+            let _ = std::fs::remove_file(&lock_path);
+        });
+
+        if !self.is_lazy_ready() || force {
+            // Clean up any previous incomplete preparation.
+            remove_file_if_exists(&self.lazy_toc_path)?;
+            remove_file_if_exists(&self.lazy_index_path)?;
+            remove_file_if_exists(&self.lazy_tar_path)?;
+            remove_file_if_exists(&self.lazy_blob_path)?;
+            let _ = std::fs::remove_dir_all(&self.lazy_dir);
+
+            if let Err(e) = self
+                .write_estargz_toc(client, image_ref, blob_size, toc_digest)
+                .await
+            {
+                let _ = std::fs::remove_file(&self.lazy_index_path);
+                let _ = std::fs::remove_file(&self.lazy_blob_path);
+                let _ = std::fs::remove_dir_all(&self.lazy_dir);
+                return Err(e);
+            }
+        } else if !self.lazy_blob_path.exists() {
+            // Prepared earlier from a fully downloaded tarball.
+            return Ok(None);
+        }
+
+        self.start_blob_fetch(client, image_ref, blob_size, progress, layer_index)
+    }
+
+    /// Fetch the estargz footer and TOC and write the index and table of contents.
+    async fn write_estargz_toc(
+        &self,
+        client: &oci_client::Client,
+        image_ref: &oci_client::Reference,
+        blob_size: u64,
+        toc_digest: &str,
+    ) -> ImageResult<()> {
+        if blob_size <= estargz::FOOTER_SIZE {
+            return Err(ImageError::Extraction {
+                digest: self.digest.to_string(),
+                message: "estargz: blob is smaller than its footer".into(),
+                source: None,
+            });
+        }
+        let footer_offset = blob_size - estargz::FOOTER_SIZE;
+        let footer = fetch_blob_range(
+            client,
+            image_ref,
+            &self.digest,
+            footer_offset,
+            estargz::FOOTER_SIZE,
+        )
+        .await?;
+        let toc_offset = estargz::parse_footer(&footer, &self.digest)?;
+        if toc_offset >= footer_offset {
+            return Err(ImageError::Extraction {
+                digest: self.digest.to_string(),
+                message: format!("estargz: TOC offset {toc_offset} is past the footer"),
+                source: None,
+            });
+        }
+
+        let member = fetch_blob_range(
+            client,
+            image_ref,
+            &self.digest,
+            toc_offset,
+            footer_offset - toc_offset,
+        )
+        .await?;
+        let toc = estargz::decode_toc(&member, toc_digest, &self.digest).await?;
+
+        std::fs::create_dir_all(&self.lazy_dir).map_err(|e| ImageError::Cache {
+            path: self.lazy_dir.clone(),
+            source: e,
+        })?;
+        let scan = estargz::scan_toc(&toc, toc_offset, &self.lazy_dir, &self.digest)?;
+
+        // The blob must exist before the table of contents marks the layer
+        // ready, since the runtime opens it as soon as it sees the TOC.
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.lazy_blob_path)
+            .map_err(|e| ImageError::Cache {
+                path: self.lazy_blob_path.clone(),
+                source: e,
+            })?;
+
+        scan.write(
+            &self.lazy_dir,
+            &self.lazy_index_path,
+            &self.lazy_toc_path,
+            TocSource::Estargz { blob_size },
+        )
+    }
+
+    /// Start streaming the rest of the estargz blob unless it is complete or
+    /// another process is already downloading it.
+    fn start_blob_fetch(
+        &self,
+        client: &oci_client::Client,
+        image_ref: &oci_client::Reference,
+        blob_size: u64,
+        progress: Option<&crate::progress::PullProgressSender>,
+        layer_index: usize,
+    ) -> ImageResult<Option<tokio::task::JoinHandle<ImageResult<()>>>> {
+        let have = std::fs::metadata(&self.lazy_blob_path)
+            .map(|meta| meta.len())
+            .unwrap_or(0);
+        if have == blob_size {
+            return Ok(None);
+        }
+
+        // Take the lock before returning so the runtime never sees a partial
+        // blob without a download in progress.
+        let lock_path = blob_lock_path(&self.lazy_blob_path);
+        let lock_file = open_lock_file(&lock_path)?;
+        if !flock_exclusive_retry(&lock_file)? {
+            return Ok(None);
+        }
+        let guard = scopeguard::guard(lock_file, move |f| {
+            let _ = flock_unlock(&f);
+            drop(f);
+            let _ = std::fs::remove_file(&lock_path);
+        });
+
+        let client = client.clone();
+        let image_ref = image_ref.clone();
+        let digest = self.digest.clone();
+        let blob_path = self.lazy_blob_path.clone();
+        let progress = progress.cloned();
+        Ok(Some(tokio::spawn(async move {
+            let _guard = guard;
+            let result = download_blob_tail(
+                &client,
+                &image_ref,
+                &digest,
+                &blob_path,
+                blob_size,
+                progress.as_ref(),
+                layer_index,
+            )
+            .await;
+            if let Err(ref e) = result {
+                tracing::warn!(digest = %digest, error = %e, "estargz blob download failed");
+            }
+            result
+        })))
+    }
+
     /// Generate the binary sidecar index for this layer's extracted tree.
     pub async fn build_index(&self) -> ImageResult<()> {
         index::build_sidecar_index(&self.extracted_dir, &self.index_path).await
@@ -422,6 +696,150 @@ fn flock_unlock(file: &File) -> ImageResult<()> {
     Ok(())
 }
 
+/// Try to take an exclusive `flock()`, retrying briefly while readers hold
+/// shared locks. Returns `false` if another process keeps holding it.
+fn flock_exclusive_retry(file: &File) -> ImageResult<bool> {
+    for attempt in 0..BLOB_LOCK_ATTEMPTS {
+        let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if ret == 0 {
+            return Ok(true);
+        }
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EWOULDBLOCK) {
+            return Err(ImageError::Io(err));
+        }
+        if attempt + 1 < BLOB_LOCK_ATTEMPTS {
+            std::thread::sleep(BLOB_LOCK_RETRY);
+        }
+    }
+    Ok(false)
+}
+
+/// Fetch `len` bytes of a blob starting at `offset`.
+async fn fetch_blob_range(
+    client: &oci_client::Client,
+    image_ref: &oci_client::Reference,
+    digest: &Digest,
+    offset: u64,
+    len: u64,
+) -> ImageResult<Vec<u8>> {
+    use futures::StreamExt;
+
+    let digest_str = digest.to_string();
+    let response = client
+        .pull_blob_stream_partial(image_ref, digest_str.as_str(), offset, Some(len))
+        .await?;
+    // Registries that ignore the range send the whole blob.
+    let (mut stream, mut skip) = match response {
+        BlobResponse::Partial(stream) => (stream, 0),
+        BlobResponse::Full(stream) => (stream, offset),
+    };
+
+    let mut out = Vec::with_capacity(len as usize);
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        let chunk = skip_prefix(&chunk, &mut skip);
+        let want = (len as usize - out.len()).min(chunk.len());
+        out.extend_from_slice(&chunk[..want]);
+        if out.len() as u64 == len {
+            break;
+        }
+    }
+    if (out.len() as u64) < len {
+        return Err(ImageError::Extraction {
+            digest: digest_str,
+            message: format!(
+                "short range read at offset {offset}: {} of {len} bytes",
+                out.len()
+            ),
+            source: None,
+        });
+    }
+    Ok(out)
+}
+
+/// Append the rest of an estargz blob to `blob_path` and verify it.
+///
+/// Writes go straight to the file so readers waiting on a range see them as
+/// they arrive. A blob that fails verification is truncated so nothing keeps
+/// reading from it.
+async fn download_blob_tail(
+    client: &oci_client::Client,
+    image_ref: &oci_client::Reference,
+    digest: &Digest,
+    blob_path: &Path,
+    blob_size: u64,
+    progress: Option<&crate::progress::PullProgressSender>,
+    layer_index: usize,
+) -> ImageResult<()> {
+    use futures::StreamExt;
+
+    let cache_err = |e| ImageError::Cache {
+        path: blob_path.to_path_buf(),
+        source: e,
+    };
+    let digest_str: std::sync::Arc<str> = digest.to_string().into();
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(blob_path)
+        .map_err(cache_err)?;
+    let mut downloaded = file.metadata().map_err(cache_err)?.len();
+
+    if downloaded < blob_size {
+        let response = client
+            .pull_blob_stream_partial(image_ref, &*digest_str, downloaded, None)
+            .await?;
+        let (mut stream, mut skip) = match response {
+            BlobResponse::Partial(stream) => (stream, 0),
+            BlobResponse::Full(stream) => (stream, downloaded),
+        };
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            let chunk = skip_prefix(&chunk, &mut skip);
+            let want = (blob_size - downloaded).min(chunk.len() as u64) as usize;
+            file.write_all(&chunk[..want]).map_err(cache_err)?;
+            downloaded += want as u64;
+
+            if let Some(p) = progress {
+                p.send(crate::progress::PullProgress::LayerDownloadProgress {
+                    layer_index,
+                    digest: digest_str.clone(),
+                    downloaded_bytes: downloaded,
+                    total_bytes: Some(blob_size),
+                });
+            }
+        }
+    }
+
+    let actual_hash = compute_sha256_file(blob_path)?;
+    if downloaded != blob_size || actual_hash != digest.hex() {
+        let _ = file.set_len(0);
+        return Err(ImageError::DigestMismatch {
+            digest: digest.to_string(),
+            expected: digest.hex().to_string(),
+            actual: actual_hash,
+        });
+    }
+
+    if let Some(p) = progress {
+        p.send(crate::progress::PullProgress::LayerDownloadComplete {
+            layer_index,
+            digest: digest_str,
+            downloaded_bytes: downloaded,
+        });
+    }
+
+    Ok(())
+}
+
+/// Drop up to `skip` leading bytes of `chunk`, decrementing `skip`.
+fn skip_prefix<'a>(chunk: &'a [u8], skip: &mut u64) -> &'a [u8] {
+    let n = (*skip).min(chunk.len() as u64) as usize;
+    *skip -= n as u64;
+    &chunk[n..]
+}
+
 /// Compute the SHA-256 hex digest of a file.
 fn compute_sha256_file(path: &Path) -> ImageResult<String> {
     let mut file = File::open(path).map_err(|e| ImageError::Cache {
@@ -29,6 +29,6 @@ pub use error::{ImageError, ImageResult};
 pub use oci_client::Reference;
 pub use platform::{Arch, Os, Platform};
 pub use progress::{PullProgress, PullProgressHandle, PullProgressSender, progress_channel};
-pub use pull::{PullOptions, PullPolicy, PullResult};
+pub use pull::{LayerFetches, PullOptions, PullPolicy, PullResult};
 pub use registry::Registry;
 pub use store::{CachedImageMetadata, CachedLayerMetadata, GlobalCache};
@@ -1,5 +1,11 @@
 //! Target platform for OCI image resolution.
 
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::ImageError;
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -62,6 +68,15 @@ impl Platform {
         };
         Self::new("linux", arch)
     }
+
+    /// Whether images for this platform can run natively on the current host.
+    ///
+    /// Microsandbox runs guests under hardware virtualization only, so the
+    /// architecture must match the host. Variants are not compared.
+    pub fn is_host_native(&self) -> bool {
+        let host = Self::host_linux();
+        self.os == host.os && self.arch == host.arch
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -74,6 +89,59 @@ impl Default for Platform {
     }
 }
 
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.os, self.arch)?;
+        if let Some(variant) = &self.variant {
+            write!(f, "/{variant}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Platform {
+    type Err = ImageError;
+
+    /// Parse `os/arch[/variant]` (e.g. `linux/amd64`, `linux/arm/v7`).
+    ///
+    /// The common `x86_64` and `aarch64` spellings are normalized to their
+    /// OCI names.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('/');
+        let (Some(os), Some(arch)) = (parts.next(), parts.next()) else {
+            return Err(ImageError::InvalidPlatform(s.to_string()));
+        };
+        let variant = parts.next();
+        if os.is_empty() || arch.is_empty() || variant == Some("") || parts.next().is_some() {
+            return Err(ImageError::InvalidPlatform(s.to_string()));
+        }
+
+        let arch = match arch {
+            "x86_64" => "amd64",
+            "aarch64" => "arm64",
+            other => other,
+        };
+
+        Ok(match variant {
+            Some(variant) => Self::with_variant(os, arch, variant),
+            None => Self::new(os, arch),
+        })
+    }
+}
+
+impl Serialize for Platform {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Platform {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Tests
 //--------------------------------------------------------------------------------------------------
@@ -94,4 +162,46 @@ mod tests {
         let p = Platform::default();
         assert_eq!(p.os, Os::Linux);
     }
+
+    #[test]
+    fn test_parse_and_display_roundtrip() {
+        let p: Platform = "linux/amd64".parse().unwrap();
+        assert_eq!(p, Platform::new("linux", "amd64"));
+        assert_eq!(p.to_string(), "linux/amd64");
+
+        let p: Platform = "linux/arm/v7".parse().unwrap();
+        assert_eq!(p, Platform::with_variant("linux", "arm", "v7"));
+        assert_eq!(p.to_string(), "linux/arm/v7");
+
+        let p: Platform = "linux/aarch64".parse().unwrap();
+        assert_eq!(p.arch, Arch::ARM64);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        for input in [
+            "",
+            "linux",
+            "linux/",
+            "/amd64",
+            "linux/arm/",
+            "linux/arm/v7/x",
+        ] {
+            assert!(
+                input.parse::<Platform>().is_err(),
+                "{input:?} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn test_host_is_native() {
+        assert!(Platform::host_linux().is_host_native());
+        let foreign = if Platform::host_linux().arch == Arch::Amd64 {
+            Platform::new("linux", "arm64")
+        } else {
+            Platform::new("linux", "amd64")
+        };
+        assert!(!foreign.is_host_native());
+    }
 }
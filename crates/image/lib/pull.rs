@@ -3,8 +3,13 @@
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
 
-use crate::{config::ImageConfig, digest::Digest};
+use crate::{
+    config::ImageConfig,
+    digest::Digest,
+    error::{ImageError, ImageResult},
+};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -36,6 +41,15 @@ pub struct PullOptions {
 
     /// Generate binary sidecar indexes after extraction.
     pub build_index: bool,
+
+    /// Skip extraction for layers that are not already extracted. Files are
+    /// materialized on first access by the overlay backend.
+    ///
+    /// Seekable estargz layers are ready as soon as their table of contents
+    /// has been fetched; the rest of the blob is streamed in the background
+    /// (see [`PullResult::fetches`]). Other layers are downloaded in full and
+    /// indexed from their tar headers, skipping only the extraction.
+    pub lazy: bool,
 }
 
 /// Result of a successful image pull.
@@ -51,6 +65,50 @@ pub struct PullResult {
 
     /// True if all layers were already cached and no downloads occurred.
     pub cached: bool,
+
+    /// Estargz blobs still being downloaded for lazy layers.
+    pub fetches: LayerFetches,
+}
+
+/// Background downloads of estargz blobs backing lazy layers.
+///
+/// The downloads run on the Tokio runtime that performed the pull and keep
+/// going if this value is dropped, for as long as that runtime is alive.
+/// Lookups of entries whose data has not arrived yet block until it does.
+#[derive(Default)]
+pub struct LayerFetches {
+    pub(crate) tasks: Vec<(Digest, JoinHandle<ImageResult<()>>)>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl LayerFetches {
+    /// True if no blob downloads are pending.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Wait for every pending blob download, returning the first failure.
+    pub async fn wait(self) -> ImageResult<()> {
+        let mut first_error = None;
+        for (digest, task) in self.tasks {
+            let result = task.await.unwrap_or_else(|e| {
+                Err(ImageError::Extraction {
+                    digest: digest.to_string(),
+                    message: format!("blob download task failed: {e}"),
+                    source: None,
+                })
+            });
+            if let Err(e) = result
+                && first_error.is_none()
+            {
+                first_error = Some(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -63,6 +121,7 @@ impl Default for PullOptions {
             pull_policy: PullPolicy::default(),
             force: false,
             build_index: true,
+            lazy: false,
         }
     }
 }
@@ -22,7 +22,7 @@ use crate::{
     manifest::OciManifest,
     platform::Platform,
     progress::{self, PullProgress, PullProgressHandle, PullProgressSender},
-    pull::{PullOptions, PullPolicy, PullResult},
+    pull::{LayerFetches, PullOptions, PullPolicy, PullResult},
     store::{CachedImageMetadata, CachedLayerMetadata, GlobalCache},
 };
 
@@ -44,6 +44,8 @@ struct LayerDescriptor {
     digest: Digest,
     media_type: Option<String>,
     size: Option<u64>,
+    /// Digest of the estargz table of contents, if the layer is seekable.
+    toc_digest: Option<String>,
 }
 
 struct CachedPullInfo {
@@ -56,6 +58,8 @@ struct LayerPipelineSuccess {
     layer: Layer,
     extracted_dir: PathBuf,
     implicit_dirs: Vec<PathBuf>,
+    lazy: bool,
+    fetch: Option<JoinHandle<ImageResult<()>>>,
 }
 
 struct LayerPipelineFailure {
@@ -99,10 +103,13 @@ impl Registry {
     pub fn pull_cached(
         cache: &GlobalCache,
         reference: &oci_client::Reference,
+        platform: &Platform,
         options: &PullOptions,
     ) -> ImageResult<Option<(PullResult, CachedImageMetadata)>> {
-        Ok(resolve_cached_pull_result(cache, reference, options)?
-            .map(|cached| (cached.result, cached.metadata)))
+        Ok(
+            resolve_cached_pull_result(cache, reference, platform, options)?
+                .map(|cached| (cached.result, cached.metadata)),
+        )
     }
 
     /// Pull an image. Downloads, extracts, and indexes layers concurrently.
@@ -190,7 +197,7 @@ impl Registry {
     ) -> ImageResult<PullResult> {
         let ref_str: Arc<str> = reference.to_string().into();
         let oci_ref = reference;
-        let image_lock_path = self.cache.image_lock_path(reference);
+        let image_lock_path = self.cache.image_lock_path(reference, &self.platform);
         let image_lock_file = open_lock_file(&image_lock_path)?;
         flock_exclusive(&image_lock_file)?;
         let _image_lock_guard = scopeguard::guard(image_lock_file, |file| {
@@ -200,7 +207,9 @@ impl Registry {
         });
 
         // Step 1: Early cache check using persisted image metadata.
-        if let Some(cached) = resolve_cached_pull_result(&self.cache, reference, options)? {
+        if let Some(cached) =
+            resolve_cached_pull_result(&self.cache, reference, &self.platform, options)?
+        {
             if let Some(ref p) = progress {
                 p.send(PullProgress::Resolving {
                     reference: ref_str.clone(),
@@ -289,11 +298,45 @@ impl Registry {
                 let size = layer_desc.size;
                 let force = options.force;
                 let build_index = options.build_index;
+                let lazy = options.lazy && (force || !layer.is_extracted());
                 let progress = progress.clone();
                 let media_type = layer_desc.media_type.clone();
+                let toc_digest = layer_desc.toc_digest.clone();
                 let diff_id = diff_ids.get(i).cloned().unwrap_or_default();
 
                 async move {
+                    // Lazy estargz: fetch only the table of contents and let
+                    // the rest of the blob stream in behind the sandbox.
+                    if let (true, Some(toc_digest), Some(size)) = (lazy, &toc_digest, size) {
+                        let fetch = match layer
+                            .prepare_estargz(
+                                &client,
+                                &oci_ref,
+                                size,
+                                toc_digest,
+                                progress.as_ref(),
+                                i,
+                                force,
+                            )
+                            .await
+                        {
+                            Ok(fetch) => fetch,
+                            Err(error) => return Err(LayerPipelineFailure { error }),
+                        };
+                        if let Some(ref p) = progress {
+                            p.send(PullProgress::LayerIndexComplete { layer_index: i });
+                        }
+
+                        return Ok(LayerPipelineSuccess {
+                            layer_index: i,
+                            extracted_dir: layer.lazy_dir(),
+                            implicit_dirs: Vec::new(),
+                            layer,
+                            lazy: true,
+                            fetch,
+                        });
+                    }
+
                     // Download.
                     if let Err(error) = layer
                         .download(&client, &oci_ref, size, force, progress.as_ref(), i)
@@ -302,6 +345,35 @@ impl Registry {
                         return Err(LayerPipelineFailure { error });
                     }
 
+                    // Lazy: index the tar headers and defer extraction to
+                    // first access by the overlay backend.
+                    if lazy {
+                        if let Err(error) = layer
+                            .prepare_lazy(
+                                progress.as_ref(),
+                                i,
+                                media_type.as_deref(),
+                                &diff_id,
+                                force,
+                            )
+                            .await
+                        {
+                            return Err(LayerPipelineFailure { error });
+                        }
+                        if let Some(ref p) = progress {
+                            p.send(PullProgress::LayerIndexComplete { layer_index: i });
+                        }
+
+                        return Ok(LayerPipelineSuccess {
+                            layer_index: i,
+                            extracted_dir: layer.lazy_dir(),
+                            implicit_dirs: Vec::new(),
+                            layer,
+                            lazy: true,
+                            fetch: None,
+                        });
+                    }
+
                     // Extract (no parent layer dependency — parallel safe).
                     let result = if !layer.is_extracted() || force {
                         let result = match layer
@@ -346,6 +418,8 @@ impl Registry {
                         extracted_dir: layer.extracted_dir(),
                         implicit_dirs: result.implicit_dirs,
                         layer,
+                        lazy: false,
+                        fetch: None,
                     })
                 }
             })
@@ -355,9 +429,16 @@ impl Registry {
         let mut results: Vec<LayerPipelineSuccess> = Vec::with_capacity(layer_count);
         let mut first_error: Option<ImageError> = None;
 
+        let mut fetches = LayerFetches::default();
+
         for outcome in outcomes {
             match outcome {
-                Ok(result) => results.push(result),
+                Ok(mut result) => {
+                    if let Some(fetch) = result.fetch.take() {
+                        fetches.tasks.push((result.layer.digest.clone(), fetch));
+                    }
+                    results.push(result);
+                }
                 Err(failure) => {
                     if first_error.is_none() {
                         first_error = Some(failure.error);
@@ -384,6 +465,13 @@ impl Registry {
             .map(|result| result.extracted_dir.clone())
             .collect();
         for result in &results {
+            if result.lazy {
+                crate::layer::lazy::fixup_implicit_dirs(
+                    result.layer.lazy_toc_path(),
+                    &extracted_dirs[..result.layer_index],
+                )?;
+                continue;
+            }
             if !result.implicit_dirs.is_empty() && result.layer_index > 0 {
                 crate::layer::extraction::fixup_implicit_dirs(
                     &extracted_dirs[result.layer_index],
@@ -410,6 +498,7 @@ impl Registry {
                     diff_id: diff_ids.get(i).cloned().unwrap_or_default(),
                 })
                 .collect(),
+            platform: self.platform.clone(),
         };
         self.cache.write_image_metadata(reference, &cached_image)?;
 
@@ -425,6 +514,7 @@ impl Registry {
             config: image_config,
             manifest_digest,
             cached: false,
+            fetches,
         })
     }
 
@@ -570,10 +660,16 @@ impl Registry {
                         } else {
                             None
                         };
+                        let toc_digest = desc
+                            .annotations()
+                            .as_ref()
+                            .and_then(|a| a.get(crate::layer::estargz::TOC_DIGEST_ANNOTATION))
+                            .cloned();
                         Ok(LayerDescriptor {
                             digest,
                             media_type: Some(desc.media_type().to_string()),
                             size,
+                            toc_digest,
                         })
                     })
                     .collect::<ImageResult<Vec<_>>>()?;
@@ -658,9 +754,12 @@ fn resolve_platform_digest(manifests: &[ImageIndexEntry], target: &Platform) ->
 }
 
 /// Build a pull result from cached image metadata.
+///
+/// Extracted layers are preferred; lazy layers are used only when `lazy` is set.
 fn cached_pull_result(
     cache: &GlobalCache,
     metadata: &CachedImageMetadata,
+    lazy: bool,
 ) -> ImageResult<PullResult> {
     let manifest_digest: Digest = metadata.manifest_digest.parse()?;
     let layer_digests = metadata
@@ -672,24 +771,32 @@ fn cached_pull_result(
     Ok(PullResult {
         layers: layer_digests
             .iter()
-            .map(|digest| cache.extracted_dir(digest))
+            .map(|digest| {
+                if lazy && !cache.is_extracted(digest) {
+                    cache.lazy_dir(digest)
+                } else {
+                    cache.extracted_dir(digest)
+                }
+            })
             .collect(),
         config: metadata.config.clone(),
         manifest_digest,
         cached: true,
+        fetches: LayerFetches::default(),
     })
 }
 
 fn resolve_cached_pull_result(
     cache: &GlobalCache,
     reference: &oci_client::Reference,
+    platform: &Platform,
     options: &PullOptions,
 ) -> ImageResult<Option<CachedPullInfo>> {
     if options.force || options.pull_policy == PullPolicy::Always {
         return Ok(None);
     }
 
-    let Some(metadata) = cache.read_image_metadata(reference)? else {
+    let Some(metadata) = cache.read_image_metadata(reference, platform)? else {
         return Ok(None);
     };
 
@@ -703,11 +810,23 @@ fn resolve_cached_pull_result(
         Err(_) => return Ok(None),
     };
 
-    if !cache.all_layers_extracted(&cached_digests) {
+    // A lazy estargz layer whose blob is still incomplete goes through the
+    // full pull so its background download is resumed.
+    let layers_ready = if options.lazy {
+        cached_digests
+            .iter()
+            .zip(&metadata.layers)
+            .all(|(digest, layer)| {
+                cache.is_extracted(digest) || cache.is_lazy_ready(digest, layer.size_bytes)
+            })
+    } else {
+        cache.all_layers_extracted(&cached_digests)
+    };
+    if !layers_ready {
         return Ok(None);
     }
 
-    let result = match cached_pull_result(cache, &metadata) {
+    let result = match cached_pull_result(cache, &metadata, options.lazy) {
         Ok(result) => result,
         Err(_) => return Ok(None),
     };
@@ -809,10 +928,12 @@ mod tests {
         let cached = resolve_cached_pull_result(
             &cache,
             &reference,
+            &Platform::default(),
             &PullOptions {
                 pull_policy: PullPolicy::IfMissing,
                 force: false,
                 build_index: true,
+                lazy: false,
             },
         )
         .unwrap()
@@ -845,10 +966,12 @@ mod tests {
         let cached = resolve_cached_pull_result(
             &cache,
             &reference,
+            &Platform::default(),
             &PullOptions {
                 pull_policy: PullPolicy::Never,
                 force: false,
                 build_index: true,
+                lazy: false,
             },
         )
         .unwrap();
@@ -867,10 +990,12 @@ mod tests {
         let cached = super::Registry::pull_cached(
             &cache,
             &reference,
+            &Platform::default(),
             &PullOptions {
                 pull_policy: PullPolicy::IfMissing,
                 force: false,
                 build_index: true,
+                lazy: false,
             },
         )
         .unwrap()
@@ -884,6 +1009,44 @@ mod tests {
         assert_eq!(cached.1.manifest_digest, metadata.manifest_digest);
     }
 
+    #[test]
+    fn test_resolve_cached_pull_result_is_keyed_by_platform() {
+        let temp = tempdir().unwrap();
+        let cache = GlobalCache::new(temp.path()).unwrap();
+        let reference: oci_client::Reference = "docker.io/library/alpine".parse().unwrap();
+        let host_metadata = write_cached_image_fixture(&cache, &reference, &[true]);
+
+        let foreign = if Platform::host_linux().arch == crate::platform::Arch::Amd64 {
+            Platform::new("linux", "arm64")
+        } else {
+            Platform::new("linux", "amd64")
+        };
+        let options = PullOptions::default();
+
+        let missing = resolve_cached_pull_result(&cache, &reference, &foreign, &options).unwrap();
+        assert!(missing.is_none());
+
+        let mut foreign_metadata = host_metadata.clone();
+        foreign_metadata.manifest_digest =
+            "sha256:cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc".into();
+        foreign_metadata.platform = foreign.clone();
+        cache
+            .write_image_metadata(&reference, &foreign_metadata)
+            .unwrap();
+
+        let host = resolve_cached_pull_result(&cache, &reference, &Platform::default(), &options)
+            .unwrap()
+            .unwrap();
+        let foreign = resolve_cached_pull_result(&cache, &reference, &foreign, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(host.metadata.manifest_digest, host_metadata.manifest_digest);
+        assert_eq!(
+            foreign.metadata.manifest_digest,
+            foreign_metadata.manifest_digest
+        );
+    }
+
     #[tokio::test]
     async fn test_pull_never_returns_not_cached_when_any_layer_is_missing() {
         let temp = tempdir().unwrap();
@@ -894,10 +1057,12 @@ mod tests {
         let cached = resolve_cached_pull_result(
             &cache,
             &reference,
+            &Platform::default(),
             &PullOptions {
                 pull_policy: PullPolicy::Never,
                 force: false,
                 build_index: true,
+                lazy: false,
             },
         )
         .unwrap();
@@ -911,6 +1076,7 @@ mod tests {
                     pull_policy: PullPolicy::Never,
                     force: false,
                     build_index: true,
+                    lazy: false,
                 },
             )
             .await;
@@ -929,10 +1095,12 @@ mod tests {
         let cached = resolve_cached_pull_result(
             &cache,
             &reference,
+            &Platform::default(),
             &PullOptions {
                 pull_policy: PullPolicy::IfMissing,
                 force: false,
                 build_index: true,
+                lazy: false,
             },
         )
         .unwrap();
@@ -950,10 +1118,12 @@ mod tests {
         let forced = resolve_cached_pull_result(
             &cache,
             &reference,
+            &Platform::default(),
             &PullOptions {
                 pull_policy: PullPolicy::IfMissing,
                 force: true,
                 build_index: true,
+                lazy: false,
             },
         )
         .unwrap();
@@ -962,10 +1132,12 @@ mod tests {
         let always = resolve_cached_pull_result(
             &cache,
             &reference,
+            &Platform::default(),
             &PullOptions {
                 pull_policy: PullPolicy::Always,
                 force: false,
                 build_index: true,
+                lazy: false,
             },
         )
         .unwrap();
@@ -984,10 +1156,12 @@ mod tests {
         let cached = resolve_cached_pull_result(
             &cache,
             &reference,
+            &Platform::default(),
             &PullOptions {
                 pull_policy: PullPolicy::IfMissing,
                 force: false,
                 build_index: true,
+                lazy: false,
             },
         )
         .unwrap();
@@ -1012,6 +1186,7 @@ mod tests {
                     pull_policy: PullPolicy::Never,
                     force: false,
                     build_index: true,
+                    lazy: false,
                 },
             )
             .await;
@@ -1033,6 +1208,7 @@ mod tests {
                 pull_policy: PullPolicy::IfMissing,
                 force: false,
                 build_index: true,
+                lazy: false,
             },
         );
 
@@ -1092,6 +1268,7 @@ mod tests {
                     diff_id: format!("sha256:{:064x}", index as u64 + 1000),
                 })
                 .collect(),
+            platform: Platform::default(),
         };
 
         cache.write_image_metadata(reference, &metadata).unwrap();
@@ -2,6 +2,7 @@
 
 use std::path::{Path, PathBuf};
 
+use microsandbox_utils::index::{TOC_BLOB_EXTENSION, TOC_EXTENSION, TOC_TAR_EXTENSION};
 use oci_client::Reference;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest as Sha2Digest, Sha256};
@@ -10,6 +11,7 @@ use crate::{
     config::ImageConfig,
     digest::Digest,
     error::{ImageError, ImageResult},
+    platform::Platform,
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -19,6 +21,9 @@ use crate::{
 /// Subdirectory under the cache root for layer storage.
 const LAYERS_DIR: &str = "layers";
 
+/// Subdirectory under the layer cache for lazily materialized layers.
+const LAZY_DIR: &str = "lazy";
+
 /// Subdirectory under the cache root for image metadata.
 const IMAGES_DIR: &str = "images";
 
@@ -39,7 +44,15 @@ pub(crate) const COMPLETE_MARKER: &str = ".complete";
 /// ~/.microsandbox/cache/layers/<digest_safe>.implicit_dirs     # pending implicit-dir fixups
 /// ~/.microsandbox/cache/layers/<digest_safe>.lock              # extraction flock files
 /// ~/.microsandbox/cache/layers/<digest_safe>.download.lock     # download flock files
+/// ~/.microsandbox/cache/layers/lazy/<digest_safe>.extracted/   # lazily materialized entries
+/// ~/.microsandbox/cache/layers/lazy/<digest_safe>.index        # index built from tar headers
+/// ~/.microsandbox/cache/layers/lazy/<digest_safe>.tar          # uncompressed layer tarball
+/// ~/.microsandbox/cache/layers/lazy/<digest_safe>.blob         # estargz blob (streamed in background)
+/// ~/.microsandbox/cache/layers/lazy/<digest_safe>.toc          # table of contents (ready marker)
 /// ```
+///
+/// Lazy layer files share a stem so the runtime can locate the index,
+/// tarball, and table of contents next to the layer directory.
 pub struct GlobalCache {
     /// Root of the layer cache directory (`~/.microsandbox/cache/layers/`).
     layers_dir: PathBuf,
@@ -59,6 +72,10 @@ pub struct CachedImageMetadata {
     pub config: ImageConfig,
     /// Layer metadata in bottom-to-top order.
     pub layers: Vec<CachedLayerMetadata>,
+    /// Platform the manifest was resolved for. Older cache entries predate
+    /// this field and were always resolved for the host.
+    #[serde(default)]
+    pub platform: Platform,
 }
 
 /// Cached metadata for a single layer descriptor.
@@ -152,10 +169,53 @@ impl GlobalCache {
             .join(format!("{}.download.lock", digest.to_path_safe()))
     }
 
-    /// Path to the pull lock file for an image reference.
-    pub fn image_lock_path(&self, reference: &Reference) -> PathBuf {
+    /// Directory holding lazily materialized layers.
+    pub fn lazy_layers_dir(&self) -> PathBuf {
+        self.layers_dir.join(LAZY_DIR)
+    }
+
+    /// Path to the lazily materialized layer directory.
+    pub fn lazy_dir(&self, digest: &Digest) -> PathBuf {
+        self.lazy_layers_dir()
+            .join(format!("{}.extracted", digest.to_path_safe()))
+    }
+
+    /// Path to the sidecar index for a lazy layer.
+    pub fn lazy_index_path(&self, digest: &Digest) -> PathBuf {
+        self.lazy_dir(digest).with_extension("index")
+    }
+
+    /// Path to the uncompressed tarball backing a lazy layer.
+    pub fn lazy_tar_path(&self, digest: &Digest) -> PathBuf {
+        self.lazy_dir(digest).with_extension(TOC_TAR_EXTENSION)
+    }
+
+    /// Path to the estargz blob backing a lazy layer.
+    pub fn lazy_blob_path(&self, digest: &Digest) -> PathBuf {
+        self.lazy_dir(digest).with_extension(TOC_BLOB_EXTENSION)
+    }
+
+    /// Path to the table of contents for a lazy layer.
+    pub fn lazy_toc_path(&self, digest: &Digest) -> PathBuf {
+        self.lazy_dir(digest).with_extension(TOC_EXTENSION)
+    }
+
+    /// Check if a lazy layer is ready: its table of contents is present and,
+    /// for estargz layers, the blob has been downloaded in full.
+    pub fn is_lazy_ready(&self, digest: &Digest, size: Option<u64>) -> bool {
+        if !self.lazy_toc_path(digest).exists() {
+            return false;
+        }
+        match std::fs::metadata(self.lazy_blob_path(digest)) {
+            Ok(meta) => Some(meta.len()) == size,
+            Err(_) => true,
+        }
+    }
+
+    /// Path to the pull lock file for an image reference and platform.
+    pub fn image_lock_path(&self, reference: &Reference, platform: &Platform) -> PathBuf {
         self.images_dir
-            .join(format!("{}.lock", image_cache_key(reference)))
+            .join(format!("{}.lock", image_cache_key(reference, platform)))
     }
 
     /// Check if a layer is fully extracted (`.complete` marker present).
@@ -168,12 +228,13 @@ impl GlobalCache {
         digests.iter().all(|d| self.is_extracted(d))
     }
 
-    /// Read cached metadata for an image reference.
+    /// Read cached metadata for an image reference resolved for `platform`.
     pub fn read_image_metadata(
         &self,
         reference: &Reference,
+        platform: &Platform,
     ) -> ImageResult<Option<CachedImageMetadata>> {
-        let path = self.image_metadata_path(reference);
+        let path = self.image_metadata_path(reference, platform);
 
         let data = match std::fs::read_to_string(&path) {
             Ok(data) => data,
@@ -191,12 +252,14 @@ impl GlobalCache {
     }
 
     /// Write cached metadata for an image reference.
+    ///
+    /// The entry is keyed by the platform recorded in `metadata`.
     pub(crate) fn write_image_metadata(
         &self,
         reference: &Reference,
         metadata: &CachedImageMetadata,
     ) -> ImageResult<()> {
-        let path = self.image_metadata_path(reference);
+        let path = self.image_metadata_path(reference, &metadata.platform);
         let temp_path = path.with_extension("json.part");
         let payload = serde_json::to_vec(metadata).map_err(|e| {
             ImageError::ConfigParse(format!("failed to serialize cached image metadata: {e}"))
@@ -211,9 +274,13 @@ impl GlobalCache {
         Ok(())
     }
 
-    /// Delete cached metadata for an image reference.
-    pub fn delete_image_metadata(&self, reference: &Reference) -> ImageResult<()> {
-        let path = self.image_metadata_path(reference);
+    /// Delete cached metadata for an image reference resolved for `platform`.
+    pub fn delete_image_metadata(
+        &self,
+        reference: &Reference,
+        platform: &Platform,
+    ) -> ImageResult<()> {
+        let path = self.image_metadata_path(reference, platform);
         match std::fs::remove_file(&path) {
             Ok(()) => Ok(()),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
@@ -221,10 +288,10 @@ impl GlobalCache {
         }
     }
 
-    /// Path to the cached metadata file for an image reference.
-    fn image_metadata_path(&self, reference: &Reference) -> PathBuf {
+    /// Path to the cached metadata file for an image reference and platform.
+    fn image_metadata_path(&self, reference: &Reference, platform: &Platform) -> PathBuf {
         self.images_dir
-            .join(format!("{}.json", image_cache_key(reference)))
+            .join(format!("{}.json", image_cache_key(reference, platform)))
     }
 }
 
@@ -232,8 +299,16 @@ impl GlobalCache {
 // Functions
 //--------------------------------------------------------------------------------------------------
 
-fn image_cache_key(reference: &Reference) -> String {
+/// Cache key for a reference/platform pair.
+///
+/// Host-platform entries keep the reference-only key so caches written before
+/// platform selection existed stay valid.
+fn image_cache_key(reference: &Reference, platform: &Platform) -> String {
     let mut hasher = Sha256::new();
     hasher.update(reference.to_string().as_bytes());
+    if *platform != Platform::host_linux() {
+        hasher.update(b"#");
+        hasher.update(platform.to_string().as_bytes());
+    }
     hex::encode(hasher.finalize())
 }
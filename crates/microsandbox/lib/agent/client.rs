@@ -1,9 +1,10 @@
 //! SDK-side client for connecting to the sandbox agent relay.
 //!
 //! [`AgentClient`] communicates over a Unix domain socket to the sandbox's
-//! relay. During connection, the relay assigns a non-overlapping correlation ID
-//! range and sends the cached `core.ready` payload so the client can begin
-//! issuing commands immediately.
+//! relay, or over a [remote gateway](crate::gateway) that forwards the same
+//! byte stream. During connection, the relay assigns a non-overlapping
+//! correlation ID range and sends the cached `core.ready` payload so the client
+//! can begin issuing commands immediately.
 
 use std::collections::HashMap;
 use std::path::Path;
@@ -14,15 +15,17 @@ use std::sync::{
 
 use microsandbox_protocol::{
     codec,
+    compress::Compression,
     core::Ready,
-    message::{FLAG_TERMINAL, Message},
+    fs::FsOp,
+    message::{FLAG_TERMINAL, MIN_PROTOCOL_VERSION, Message, MessageType, PROTOCOL_VERSION},
 };
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 use tokio::net::UnixStream;
 use tokio::sync::{Mutex, mpsc};
 use tokio::task::JoinHandle;
 
-use crate::MicrosandboxResult;
+use crate::{MicrosandboxResult, gateway::RemoteGateway};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -30,12 +33,12 @@ use crate::MicrosandboxResult;
 
 /// Client for communicating with agentd through the agent relay.
 ///
-/// Connects over a Unix domain socket to the sandbox process's agent relay.
-/// Correlation IDs are allocated from the range assigned during the relay
-/// handshake.
+/// Connects over a Unix domain socket to the sandbox process's agent relay,
+/// or through a remote gateway. Correlation IDs are allocated from the range
+/// assigned during the relay handshake.
 pub struct AgentClient {
-    /// Writer half of the Unix socket connection.
-    writer: Arc<Mutex<tokio::net::unix::OwnedWriteHalf>>,
+    /// Writer half of the relay connection.
+    writer: Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>,
     /// Next correlation ID to allocate (starts at `id_offset + 1`).
     next_id: AtomicU32,
     /// Upper bound (exclusive) of the assigned ID range.
@@ -65,7 +68,27 @@ impl AgentClient {
             ))
         })?;
 
-        let (mut reader, writer) = stream.into_split();
+        Self::handshake(stream).await
+    }
+
+    /// Connect to a sandbox's agent relay through a remote gateway.
+    ///
+    /// The gateway forwards the connection to the sandbox's relay socket on
+    /// its host, so the handshake and framing are the same as for
+    /// [`connect`](Self::connect).
+    pub async fn connect_remote(
+        gateway: &RemoteGateway,
+        sandbox: &str,
+    ) -> MicrosandboxResult<Self> {
+        let (stream, _) = gateway.open_relay(sandbox).await?;
+        Self::handshake(stream).await
+    }
+
+    /// Perform the relay handshake over an established connection.
+    pub(crate) async fn handshake(
+        stream: impl AsyncRead + AsyncWrite + Send + 'static,
+    ) -> MicrosandboxResult<Self> {
+        let (mut reader, writer) = tokio::io::split(stream);
 
         // Read the handshake: [id_offset: u32 BE][ready_frame_bytes...]
         let mut offset_buf = [0u8; 4];
@@ -82,6 +105,12 @@ impl AgentClient {
         let ready: Ready = ready_msg
             .payload()
             .map_err(|e| crate::MicrosandboxError::Runtime(format!("decode ready payload: {e}")))?;
+        if ready.negotiate().is_none() {
+            return Err(crate::MicrosandboxError::IncompatibleAgent(format!(
+                "agent speaks protocol versions {}-{}, this build speaks {MIN_PROTOCOL_VERSION}-{PROTOCOL_VERSION}",
+                ready.min_version, ready.max_version
+            )));
+        }
 
         tracing::info!(
             "agent client: connected to relay, id_offset={id_offset}, boot_time={}ns",
@@ -93,7 +122,8 @@ impl AgentClient {
 
         let reader_handle = tokio::spawn(reader_loop(reader, Arc::clone(&pending)));
 
-        let writer = Arc::new(Mutex::new(writer));
+        let writer: Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>> =
+            Arc::new(Mutex::new(Box::new(writer)));
 
         // Compute the upper bound of the assigned ID range.
         // ID_RANGE_STEP = u32::MAX / 16 ≈ 268M IDs per client.
@@ -126,7 +156,13 @@ impl AgentClient {
     }
 
     /// Send a message to agentd through the relay without waiting for a response.
+    ///
+    /// Fails with [`Unsupported`](crate::MicrosandboxError::Unsupported)
+    /// without sending if the agent does not accept the message type, since
+    /// an agent that doesn't know a type drops the connection.
     pub async fn send(&self, msg: &Message) -> MicrosandboxResult<()> {
+        self.require(&msg.t)?;
+
         let mut buf = Vec::new();
         codec::encode_to_buf(msg, &mut buf)
             .map_err(|e| crate::MicrosandboxError::Runtime(format!("encode message: {e}")))?;
@@ -173,9 +209,55 @@ impl AgentClient {
     }
 
     /// Return the cached `core.ready` payload from the relay handshake.
+    ///
+    /// Besides boot timing, it carries the protocol versions and
+    /// capabilities the guest agent advertised.
     pub fn ready(&self) -> &Ready {
         &self.ready
     }
+
+    /// Whether the guest agent accepts this message type.
+    pub fn supports(&self, t: &MessageType) -> bool {
+        self.ready.supports(t)
+    }
+
+    /// Fail if the guest agent does not accept this message type.
+    pub fn require(&self, t: &MessageType) -> MicrosandboxResult<()> {
+        if self.supports(t) {
+            Ok(())
+        } else {
+            Err(crate::MicrosandboxError::Unsupported(format!(
+                "{} (update the agent in the guest image)",
+                t.as_str()
+            )))
+        }
+    }
+
+    /// Fail if the guest agent does not implement this filesystem operation.
+    pub fn require_fs_op(&self, op: &FsOp) -> MicrosandboxResult<()> {
+        if self.ready.supports_fs_op(op.name()) {
+            Ok(())
+        } else {
+            Err(crate::MicrosandboxError::Unsupported(format!(
+                "filesystem operation '{}'",
+                op.name()
+            )))
+        }
+    }
+
+    /// The flow-control window to request for a stream: `window` if the
+    /// agent supports flow control, otherwise `None` so older agents stream
+    /// without waiting for credit.
+    pub fn flow_window(&self, window: u32) -> Option<u32> {
+        self.supports(&MessageType::Credit).then_some(window)
+    }
+
+    /// The codec to compress bulk data with: the preferred one the agent
+    /// supports, or `None` for older agents, which only read uncompressed
+    /// frames.
+    pub fn compression(&self) -> Option<Compression> {
+        self.ready.compression()
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -185,7 +267,7 @@ impl AgentClient {
 /// Background task that reads messages from the relay and dispatches them
 /// to pending channels by correlation ID.
 async fn reader_loop(
-    mut reader: tokio::net::unix::OwnedReadHalf,
+    mut reader: impl AsyncRead + Unpin,
     pending: Arc<Mutex<HashMap<u32, mpsc::UnboundedSender<Message>>>>,
 ) {
     loop {
@@ -228,3 +310,105 @@ impl Drop for AgentClient {
         self.reader_handle.abort();
     }
 }
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use microsandbox_protocol::{core::Capabilities, fs::FsData};
+    use tokio::io::{AsyncWriteExt, DuplexStream};
+
+    use super::*;
+    use crate::MicrosandboxError;
+
+    /// Perform the relay side of the handshake with the given ready payload.
+    async fn connect_with(ready: &Ready) -> (MicrosandboxResult<AgentClient>, DuplexStream) {
+        let (client_side, mut relay_side) = tokio::io::duplex(4096);
+        let mut handshake = 0u32.to_be_bytes().to_vec();
+        codec::encode_to_buf(
+            &Message::with_payload(MessageType::Ready, 0, ready).unwrap(),
+            &mut handshake,
+        )
+        .unwrap();
+        relay_side.write_all(&handshake).await.unwrap();
+        (AgentClient::handshake(client_side).await, relay_side)
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_agent_without_common_version() {
+        let mut ready = Ready::new(0, 0, 0, Capabilities::default());
+        ready.min_version = PROTOCOL_VERSION + 1;
+        ready.max_version = PROTOCOL_VERSION + 1;
+
+        let (result, _relay) = connect_with(&ready).await;
+        assert!(matches!(
+            result,
+            Err(MicrosandboxError::IncompatibleAgent(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_rejects_messages_the_agent_does_not_accept() {
+        let ready = Ready::new(
+            0,
+            0,
+            0,
+            Capabilities {
+                messages: vec![MessageType::ExecRequest.as_str().into()],
+                fs_ops: vec!["stat".into()],
+                ..Default::default()
+            },
+        );
+        let (client, _relay) = connect_with(&ready).await;
+        let client = client.unwrap();
+
+        let msg = Message::new(MessageType::TunnelOpen, 1, Vec::new());
+        assert!(matches!(
+            client.send(&msg).await,
+            Err(MicrosandboxError::Unsupported(_))
+        ));
+        assert_eq!(client.flow_window(1024), None);
+        assert!(
+            client
+                .require_fs_op(&FsOp::Stat { path: "/".into() })
+                .is_ok()
+        );
+        assert!(
+            client
+                .require_fs_op(&FsOp::Mkdir { path: "/".into() })
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compression_follows_agent_codecs() {
+        let (client, _relay) = connect_with(&Ready::new(0, 0, 0, Capabilities::default())).await;
+        assert_eq!(client.unwrap().compression(), None);
+
+        let ready = Ready::new(
+            0,
+            0,
+            0,
+            Capabilities {
+                messages: vec![MessageType::FsData.as_str().into()],
+                compression: vec!["lz4".into(), "brotli".into()],
+                ..Default::default()
+            },
+        );
+        let (client, mut relay) = connect_with(&ready).await;
+        let client = client.unwrap();
+        assert_eq!(client.compression(), Some(Compression::Lz4));
+
+        let data = vec![b'a'; 16 * 1024];
+        let msg = Message::with_payload(MessageType::FsData, 7, &FsData { data: data.clone() })
+            .unwrap()
+            .compress(client.compression());
+        client.send(&msg).await.unwrap();
+
+        let received = codec::read_message(&mut relay).await.unwrap();
+        assert_eq!(received.id, 7);
+        assert_eq!(received.payload::<FsData>().unwrap().data, data);
+    }
+}
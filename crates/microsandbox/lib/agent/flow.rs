@@ -0,0 +1,125 @@
+//! Receive-side flow control for streams from the guest.
+//!
+//! Streams opened with a `window` pause in the guest once that many bytes
+//! are unacknowledged. [`CreditReturn`] acknowledges data as the consumer
+//! takes it, so a slow consumer pauses the guest-side producer instead of
+//! buffering on the host.
+
+use std::sync::Arc;
+
+use microsandbox_protocol::{
+    flow::Credit,
+    message::{Message, MessageType},
+};
+
+use crate::MicrosandboxResult;
+
+use super::AgentClient;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Returns window credit to the guest for one flow-controlled stream.
+pub(crate) struct CreditReturn {
+    id: u32,
+    client: Arc<AgentClient>,
+
+    /// Consumed bytes at which credit is returned.
+    threshold: u32,
+
+    /// Consumed bytes not yet returned.
+    pending: u32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl CreditReturn {
+    /// Track consumption for the stream `id`, opened with `window` bytes.
+    pub fn new(client: Arc<AgentClient>, id: u32, window: u32) -> Self {
+        Self {
+            id,
+            client,
+            threshold: (window / 2).max(1),
+            pending: 0,
+        }
+    }
+
+    /// Record that `bytes` of the stream's payload were consumed.
+    ///
+    /// Credit is batched and sent once half the window has been consumed, so
+    /// the guest never stalls on a full window while the host keeps up.
+    /// Nothing is sent to agents without flow control.
+    pub async fn consumed(&mut self, bytes: usize) -> MicrosandboxResult<()> {
+        if !self.client.supports(&MessageType::Credit) {
+            return Ok(());
+        }
+        self.pending = self
+            .pending
+            .saturating_add(u32::try_from(bytes).unwrap_or(u32::MAX));
+        if self.pending < self.threshold {
+            return Ok(());
+        }
+
+        let credit = Credit {
+            bytes: std::mem::take(&mut self.pending),
+        };
+        let msg = Message::with_payload(MessageType::Credit, self.id, &credit)?;
+        self.client.send(&msg).await
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use microsandbox_protocol::{
+        codec,
+        core::{Capabilities, Ready},
+    };
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_credit_is_batched_at_half_window() {
+        let (client_side, mut relay_side) = tokio::io::duplex(4096);
+
+        let ready = Message::with_payload(
+            MessageType::Ready,
+            0,
+            &Ready::new(
+                0,
+                0,
+                0,
+                Capabilities {
+                    messages: vec![MessageType::Credit.as_str().into()],
+                    ..Default::default()
+                },
+            ),
+        )
+        .unwrap();
+        let mut handshake = 0u32.to_be_bytes().to_vec();
+        codec::encode_to_buf(&ready, &mut handshake).unwrap();
+        relay_side.write_all(&handshake).await.unwrap();
+
+        let client = Arc::new(AgentClient::handshake(client_side).await.unwrap());
+        let mut credit = CreditReturn::new(client, 7, 100);
+
+        credit.consumed(30).await.unwrap();
+        credit.consumed(30).await.unwrap();
+        let msg = codec::read_message(&mut relay_side).await.unwrap();
+        assert_eq!(msg.t, MessageType::Credit);
+        assert_eq!(msg.id, 7);
+        assert_eq!(msg.payload::<Credit>().unwrap(), Credit { bytes: 60 });
+
+        credit.consumed(49).await.unwrap();
+        credit.consumed(1).await.unwrap();
+        let msg = codec::read_message(&mut relay_side).await.unwrap();
+        assert_eq!(msg.payload::<Credit>().unwrap(), Credit { bytes: 50 });
+    }
+}
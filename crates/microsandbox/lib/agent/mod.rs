@@ -4,9 +4,11 @@
 //! through the sandbox process's agent relay socket.
 
 mod client;
+mod flow;
 
 //--------------------------------------------------------------------------------------------------
 // Re-Exports
 //--------------------------------------------------------------------------------------------------
 
 pub use client::AgentClient;
+pub(crate) use flow::CreditReturn;
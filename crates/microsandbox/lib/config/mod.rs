@@ -7,11 +7,14 @@ use std::{
     collections::HashMap,
     path::{Path, PathBuf},
     sync::OnceLock,
+    time::Duration,
 };
 
 use docker_credential::{CredentialRetrievalError, DockerCredential};
 use microsandbox_image::RegistryAuth;
-use microsandbox_runtime::logging::LogLevel;
+use microsandbox_runtime::{
+    logging::LogLevel, metrics::MetricsRetention, telemetry::DEFAULT_SERVICE_NAME,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::MicrosandboxResult;
@@ -29,6 +32,15 @@ const DEFAULT_MEMORY_MIB: u32 = 512;
 /// Default database max connections.
 pub(crate) const DEFAULT_MAX_CONNECTIONS: u32 = 5;
 
+/// Default retention for raw per-second metrics samples, in hours.
+const DEFAULT_METRICS_RAW_RETENTION_HOURS: u64 = 6;
+
+/// Default retention for 1-minute metrics rollups, in days.
+const DEFAULT_METRICS_MINUTE_RETENTION_DAYS: u64 = 7;
+
+/// Default retention for 1-hour metrics rollups, in days.
+const DEFAULT_METRICS_HOUR_RETENTION_DAYS: u64 = 90;
+
 /// Service name for microsandbox-managed registry credentials in the OS keyring.
 const REGISTRY_KEYRING_SERVICE: &str = "dev.microsandbox.registry";
 
@@ -61,6 +73,12 @@ pub struct GlobalConfig {
 
     /// Registry authentication configuration.
     pub registries: RegistriesConfig,
+
+    /// Metrics retention configuration.
+    pub metrics: MetricsConfig,
+
+    /// OpenTelemetry trace export configuration.
+    pub telemetry: TelemetryConfig,
 }
 
 /// Database configuration.
@@ -96,6 +114,9 @@ pub struct PathsConfig {
     /// Named volumes directory.
     pub volumes: Option<PathBuf>,
 
+    /// Private networks directory.
+    pub networks: Option<PathBuf>,
+
     /// Logs directory.
     pub logs: Option<PathBuf>,
 
@@ -120,6 +141,38 @@ pub struct SandboxDefaults {
     pub workdir: Option<String>,
 }
 
+/// Retention for persisted sandbox metrics.
+///
+/// Raw per-second samples are kept for `raw_retention_hours`, then survive as
+/// 1-minute min/avg/max rollups for `minute_retention_days` and as 1-hour
+/// rollups for `hour_retention_days`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Hours to keep raw per-second samples.
+    pub raw_retention_hours: u64,
+
+    /// Days to keep 1-minute rollups.
+    pub minute_retention_days: u64,
+
+    /// Days to keep 1-hour rollups.
+    pub hour_retention_days: u64,
+}
+
+/// OpenTelemetry trace export.
+///
+/// Only takes effect in builds with the `otel` feature. When `otlp_endpoint`
+/// is set, `msb` and the sandbox processes it spawns export their spans there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// Base URL of an OTLP/HTTP collector (e.g. `http://localhost:4318`).
+    pub otlp_endpoint: Option<String>,
+
+    /// Service name attached to exported spans.
+    pub service_name: String,
+}
+
 /// Registry authentication configuration.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
@@ -199,6 +252,14 @@ impl GlobalConfig {
             .unwrap_or_else(|| self.home().join(microsandbox_utils::VOLUMES_SUBDIR))
     }
 
+    /// Resolve the `networks` directory.
+    pub fn networks_dir(&self) -> PathBuf {
+        self.paths
+            .networks
+            .clone()
+            .unwrap_or_else(|| self.home().join(microsandbox_utils::NETWORKS_SUBDIR))
+    }
+
     /// Resolve the `logs` directory.
     pub fn logs_dir(&self) -> PathBuf {
         self.paths
@@ -319,6 +380,17 @@ impl GlobalConfig {
     }
 }
 
+impl MetricsConfig {
+    /// The retention the sandbox runtime applies when compacting metrics.
+    pub fn retention(&self) -> MetricsRetention {
+        MetricsRetention {
+            raw: Duration::from_secs(self.raw_retention_hours.saturating_mul(3600)),
+            minute: Duration::from_secs(self.minute_retention_days.saturating_mul(86_400)),
+            hour: Duration::from_secs(self.hour_retention_days.saturating_mul(86_400)),
+        }
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
@@ -332,6 +404,25 @@ impl Default for DatabaseConfig {
     }
 }
 
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            raw_retention_hours: DEFAULT_METRICS_RAW_RETENTION_HOURS,
+            minute_retention_days: DEFAULT_METRICS_MINUTE_RETENTION_DAYS,
+            hour_retention_days: DEFAULT_METRICS_HOUR_RETENTION_DAYS,
+        }
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: DEFAULT_SERVICE_NAME.into(),
+        }
+    }
+}
+
 impl Default for SandboxDefaults {
     fn default() -> Self {
         Self {
@@ -805,6 +896,17 @@ mod tests {
         assert_eq!(cfg.log_level, Some(LogLevel::Debug));
     }
 
+    #[test]
+    fn test_deserialize_telemetry() {
+        let json = r#"{"telemetry":{"otlp_endpoint":"http://localhost:4318"}}"#;
+        let cfg: GlobalConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            cfg.telemetry.otlp_endpoint.as_deref(),
+            Some("http://localhost:4318")
+        );
+        assert_eq!(cfg.telemetry.service_name, "microsandbox");
+    }
+
     #[test]
     fn test_home_resolution() {
         let cfg = GlobalConfig {
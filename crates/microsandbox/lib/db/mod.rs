@@ -107,7 +107,7 @@ pub fn project() -> Option<&'static DatabaseConnection> {
 // Functions: Helpers
 //--------------------------------------------------------------------------------------------------
 
-async fn connect_and_migrate(
+pub(crate) async fn connect_and_migrate(
     db_dir: &Path,
     max_connections: u32,
 ) -> MicrosandboxResult<DatabaseConnection> {
@@ -151,7 +151,7 @@ mod tests {
         // DB file should exist on disk.
         assert!(db_dir.join(microsandbox_utils::DB_FILENAME).exists());
 
-        // All 13 tables should be present.
+        // All 14 tables should be present.
         let rows = conn
             .query_all(Statement::from_string(
                 sea_orm::DatabaseBackend::Sqlite,
@@ -172,10 +172,13 @@ mod tests {
             "layer",
             "manifest",
             "manifest_layer",
+            "network",
             "run",
             "sandbox",
+            "sandbox_event",
             "sandbox_image",
             "sandbox_metric",
+            "sandbox_metric_rollup",
             "snapshot",
             "volume",
         ];
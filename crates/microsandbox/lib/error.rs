@@ -58,6 +58,14 @@ pub enum MicrosandboxError {
     #[error("exec timed out after {0:?}")]
     ExecTimeout(std::time::Duration),
 
+    /// The requested persistent exec session was not found.
+    #[error("exec session not found: {0}")]
+    ExecSessionNotFound(String),
+
+    /// A persistent exec session with the same name is still running.
+    #[error("exec session already exists: {0}")]
+    ExecSessionAlreadyExists(String),
+
     /// A terminal operation failed.
     #[error("terminal error: {0}")]
     Terminal(String),
@@ -82,6 +90,18 @@ pub enum MicrosandboxError {
     #[error("volume already exists: {0}")]
     VolumeAlreadyExists(String),
 
+    /// The requested network was not found.
+    #[error("network not found: {0}")]
+    NetworkNotFound(String),
+
+    /// The network already exists.
+    #[error("network already exists: {0}")]
+    NetworkAlreadyExists(String),
+
+    /// The network is in use by one or more sandboxes.
+    #[error("network in use by sandbox(es): {0}")]
+    NetworkInUse(String),
+
     /// An OCI image operation failed.
     #[error("image error: {0}")]
     Image(#[from] microsandbox_image::ImageError),
@@ -90,6 +110,30 @@ pub enum MicrosandboxError {
     #[error("patch failed: {0}")]
     PatchFailed(String),
 
+    /// A sandbox health check did not pass.
+    #[error("health check: {0}")]
+    HealthCheck(String),
+
+    /// A label selector expression could not be parsed.
+    #[error("invalid label selector: {0}")]
+    InvalidLabelSelector(String),
+
+    /// A remote gateway request failed or was rejected.
+    #[error("gateway error: {0}")]
+    Gateway(String),
+
+    /// A port forward could not be set up or one of its connections failed.
+    #[error("port forward error: {0}")]
+    PortForward(String),
+
+    /// The guest agent speaks no protocol version this build supports.
+    #[error("incompatible guest agent: {0}")]
+    IncompatibleAgent(String),
+
+    /// The guest agent is too old for the requested feature.
+    #[error("not supported by the guest agent: {0}")]
+    Unsupported(String),
+
     /// A custom error message.
     #[error("{0}")]
     Custom(String),
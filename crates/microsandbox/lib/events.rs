@@ -0,0 +1,336 @@
+//! Lifecycle events for all sandboxes.
+//!
+//! Sandbox state changes, exec activity and network policy violations are
+//! appended to a durable event log. [`subscribe`] follows the log as it
+//! grows, so an orchestrator can react to crashes or policy denials without
+//! polling [`Sandbox::list`](crate::Sandbox::list):
+//!
+//! ```ignore
+//! use futures::StreamExt;
+//! use microsandbox::events::{self, EventKind};
+//!
+//! let mut events = std::pin::pin!(events::subscribe());
+//! while let Some(event) = events.next().await {
+//!     let event = event?;
+//!     if let EventKind::Crashed = event.kind {
+//!         println!("{} crashed", event.sandbox);
+//!     }
+//! }
+//! ```
+
+use std::{collections::VecDeque, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use microsandbox_db::entity::sandbox_event as event_entity;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::Serialize;
+
+use crate::MicrosandboxResult;
+
+//--------------------------------------------------------------------------------------------------
+// Exports
+//--------------------------------------------------------------------------------------------------
+
+pub use microsandbox_db::entity::run::TerminationReason;
+pub use microsandbox_runtime::events::EventKind;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// How often [`subscribe`] checks the event log for new entries.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Maximum number of events fetched per poll.
+const POLL_BATCH: u64 = 256;
+
+/// Events older than this are pruned by the background reaper.
+const RETENTION: chrono::TimeDelta = chrono::TimeDelta::days(7);
+
+/// Most events the background reaper keeps, however recent. Bounds the log
+/// when sandboxes churn or a policy denies traffic in a tight loop.
+const MAX_EVENTS: u64 = 100_000;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A recorded sandbox lifecycle event.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SandboxEvent {
+    /// Position in the event log. Strictly increasing.
+    pub id: i32,
+
+    /// Name of the sandbox the event belongs to.
+    pub sandbox: String,
+
+    /// When the event was recorded.
+    pub timestamp: DateTime<Utc>,
+
+    /// What happened.
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Stream events recorded from now on, across all sandboxes.
+pub fn subscribe() -> impl Stream<Item = MicrosandboxResult<SandboxEvent>> + Send + 'static {
+    follow(None)
+}
+
+/// Stream events recorded at or after `since`, then keep following the log.
+pub fn subscribe_since(
+    since: DateTime<Utc>,
+) -> impl Stream<Item = MicrosandboxResult<SandboxEvent>> + Send + 'static {
+    follow(Some(since))
+}
+
+/// Events recorded at or after `since` (or all retained events), oldest first.
+pub async fn history(since: Option<DateTime<Utc>>) -> MicrosandboxResult<Vec<SandboxEvent>> {
+    let db = crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+    let mut query = event_entity::Entity::find().order_by_asc(event_entity::Column::Id);
+    if let Some(since) = since {
+        query = query.filter(event_entity::Column::CreatedAt.gte(since.naive_utc()));
+    }
+
+    query.all(db).await?.into_iter().map(decode_event).collect()
+}
+
+/// Record an event for a sandbox. Failures are logged, never returned:
+/// event delivery must not fail the operation that produced it.
+pub(crate) async fn emit(
+    db: &DatabaseConnection,
+    sandbox_id: Option<i32>,
+    sandbox_name: &str,
+    kind: EventKind,
+) {
+    if let Err(e) =
+        microsandbox_runtime::events::record_event(db, sandbox_id, sandbox_name, &kind).await
+    {
+        tracing::warn!(sandbox = sandbox_name, event = kind.name(), error = %e, "failed to record sandbox event");
+    }
+}
+
+/// Delete events older than the retention window, then the oldest events
+/// beyond the row cap.
+pub(crate) async fn prune(db: &DatabaseConnection) -> MicrosandboxResult<()> {
+    prune_with(db, Utc::now() - RETENTION, MAX_EVENTS).await
+}
+
+async fn prune_with(
+    db: &DatabaseConnection,
+    cutoff: DateTime<Utc>,
+    max_events: u64,
+) -> MicrosandboxResult<()> {
+    event_entity::Entity::delete_many()
+        .filter(event_entity::Column::CreatedAt.lt(cutoff.naive_utc()))
+        .exec(db)
+        .await?;
+
+    // IDs only grow, so everything older than the `max_events`-th newest
+    // event is beyond the cap.
+    let oldest_kept: Option<i32> = event_entity::Entity::find()
+        .select_only()
+        .column(event_entity::Column::Id)
+        .order_by_desc(event_entity::Column::Id)
+        .offset(max_events.saturating_sub(1))
+        .limit(1)
+        .into_tuple()
+        .one(db)
+        .await?;
+    if let Some(oldest_kept) = oldest_kept {
+        event_entity::Entity::delete_many()
+            .filter(event_entity::Column::Id.lt(oldest_kept))
+            .exec(db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn follow(
+    since: Option<DateTime<Utc>>,
+) -> impl Stream<Item = MicrosandboxResult<SandboxEvent>> + Send + 'static {
+    // State: (cursor, buffered events, ticker). The cursor is resolved on the
+    // first poll so that a database failure surfaces as a stream error.
+    let state = (
+        None::<i32>,
+        VecDeque::<SandboxEvent>::new(),
+        tokio::time::interval(POLL_INTERVAL),
+    );
+
+    stream::unfold(
+        state,
+        move |(mut cursor, mut pending, mut ticker)| async move {
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    return Some((Ok(event), (cursor, pending, ticker)));
+                }
+
+                ticker.tick().await;
+                let db = match crate::db::init_global(Some(
+                    crate::config::config().database.max_connections,
+                ))
+                .await
+                {
+                    Ok(db) => db,
+                    Err(e) => return Some((Err(e), (cursor, pending, ticker))),
+                };
+
+                let after = match cursor {
+                    Some(after) => after,
+                    None => match start_cursor(db, since).await {
+                        Ok(after) => *cursor.insert(after),
+                        Err(e) => return Some((Err(e), (cursor, pending, ticker))),
+                    },
+                };
+
+                match fetch_after(db, after).await {
+                    Ok(events) => {
+                        if let Some(last) = events.last() {
+                            cursor = Some(last.id);
+                        }
+                        pending.extend(events);
+                    }
+                    Err(e) => return Some((Err(e), (cursor, pending, ticker))),
+                }
+            }
+        },
+    )
+}
+
+/// The ID after which a subscription starts: just before the first event at
+/// or after `since`, or the current end of the log.
+async fn start_cursor(
+    db: &DatabaseConnection,
+    since: Option<DateTime<Utc>>,
+) -> MicrosandboxResult<i32> {
+    if let Some(since) = since
+        && let Some(first) = event_entity::Entity::find()
+            .filter(event_entity::Column::CreatedAt.gte(since.naive_utc()))
+            .order_by_asc(event_entity::Column::Id)
+            .one(db)
+            .await?
+    {
+        return Ok(first.id - 1);
+    }
+
+    Ok(event_entity::Entity::find()
+        .order_by_desc(event_entity::Column::Id)
+        .one(db)
+        .await?
+        .map_or(0, |last| last.id))
+}
+
+async fn fetch_after(db: &DatabaseConnection, after: i32) -> MicrosandboxResult<Vec<SandboxEvent>> {
+    event_entity::Entity::find()
+        .filter(event_entity::Column::Id.gt(after))
+        .order_by_asc(event_entity::Column::Id)
+        .limit(POLL_BATCH)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(decode_event)
+        .collect()
+}
+
+fn decode_event(model: event_entity::Model) -> MicrosandboxResult<SandboxEvent> {
+    Ok(SandboxEvent {
+        id: model.id,
+        sandbox: model.sandbox_name,
+        timestamp: model.created_at.and_utc(),
+        kind: serde_json::from_str(&model.payload)?,
+    })
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_event_json_flattens_kind() {
+        let event = SandboxEvent {
+            id: 7,
+            sandbox: "worker".into(),
+            timestamp: DateTime::from_timestamp(1_760_000_000, 0).unwrap(),
+            kind: EventKind::Stopped {
+                reason: TerminationReason::IdleTimeout,
+                exit_code: Some(0),
+                detail: None,
+            },
+        };
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "stopped");
+        assert_eq!(json["sandbox"], "worker");
+        assert_eq!(json["reason"], "IdleTimeout");
+        assert_eq!(json["timestamp"], "2025-10-09T08:53:20Z");
+    }
+
+    #[test]
+    fn test_decode_event_reads_payload() {
+        let model = event_entity::Model {
+            id: 3,
+            sandbox_id: Some(1),
+            sandbox_name: "worker".into(),
+            event_type: "exec_exited".into(),
+            payload: r#"{"type":"exec_exited","session_id":2,"exit_code":1}"#.into(),
+            created_at: DateTime::from_timestamp(0, 0).unwrap().naive_utc(),
+        };
+
+        let event = decode_event(model).unwrap();
+        assert_eq!(
+            event.kind,
+            EventKind::ExecExited {
+                session_id: 2,
+                exit_code: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prune_drops_expired_events_and_caps_the_log() {
+        use sea_orm::{ActiveModelTrait, PaginatorTrait, Set};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let db = crate::db::connect_and_migrate(&tmp.path().join("db"), 1)
+            .await
+            .unwrap();
+        let now = Utc::now();
+        for age_days in [10, 8, 3, 2, 1, 0] {
+            event_entity::ActiveModel {
+                sandbox_id: Set(None),
+                sandbox_name: Set(format!("worker-{age_days}")),
+                event_type: Set("created".into()),
+                payload: Set(r#"{"type":"created"}"#.into()),
+                created_at: Set((now - chrono::TimeDelta::days(age_days)).naive_utc()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        prune_with(&db, now - RETENTION, 10).await.unwrap();
+        assert_eq!(event_entity::Entity::find().count(&db).await.unwrap(), 4);
+
+        prune_with(&db, now - RETENTION, 2).await.unwrap();
+        let kept: Vec<_> = event_entity::Entity::find()
+            .order_by_asc(event_entity::Column::Id)
+            .all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| event.sandbox_name)
+            .collect();
+        assert_eq!(kept, ["worker-1", "worker-0"]);
+    }
+}
@@ -0,0 +1,260 @@
+//! Client for a remote gateway.
+
+use std::{path::PathBuf, sync::Arc};
+
+use rustls::pki_types::ServerName;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    sync::OnceCell,
+};
+use tokio_rustls::TlsConnector;
+
+use crate::{
+    MicrosandboxError, MicrosandboxResult,
+    sandbox::{Sandbox, SandboxConfig},
+};
+
+use super::{
+    GatewayOp, GatewayReply, GatewayRequest, GatewayUrl, RemoteSandbox, read_frame, write_frame,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A connection target for a remote gateway (`msb serve`).
+///
+/// Every call opens a fresh TCP connection, so a `RemoteGateway` is cheap to
+/// clone and share.
+///
+/// ```rust,no_run
+/// # async fn example() -> microsandbox::MicrosandboxResult<()> {
+/// use microsandbox::gateway::RemoteGateway;
+///
+/// let gateway = RemoteGateway::new("tls://kvm-01.internal:7443")?
+///     .token(std::env::var("MSB_GATEWAY_TOKEN").unwrap())
+///     .ca_cert("/etc/msb/gateway-ca.pem");
+///
+/// for sandbox in gateway.list().await? {
+///     println!("{} {}", sandbox.name, sandbox.status);
+/// }
+/// let sb = gateway.connect("worker").await?;
+/// let output = sb.shell("uname -a").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RemoteGateway {
+    url: GatewayUrl,
+    token: Option<String>,
+    ca_certs: Vec<PathBuf>,
+    client_cert: Option<(PathBuf, PathBuf)>,
+    connector: Arc<OnceCell<TlsConnector>>,
+}
+
+/// A connection to the gateway.
+trait GatewayStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RemoteGateway {
+    /// Target the gateway at a `tcp://host:port` or `tls://host:port` URL.
+    pub fn new(url: &str) -> MicrosandboxResult<Self> {
+        Ok(Self {
+            url: GatewayUrl::parse(url)?,
+            token: None,
+            ca_certs: Vec::new(),
+            client_cert: None,
+            connector: Arc::new(OnceCell::new()),
+        })
+    }
+
+    /// Authenticate with a bearer token.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Trust the CA certificates in this PEM file for the gateway's server
+    /// certificate, in addition to the system roots.
+    pub fn ca_cert(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_certs.push(path.into());
+        self
+    }
+
+    /// Present a client certificate (mutual TLS).
+    pub fn client_cert(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.client_cert = Some((cert.into(), key.into()));
+        self
+    }
+
+    /// The gateway's `microsandbox` version. Useful as a connectivity and
+    /// credentials check.
+    pub async fn version(&self) -> MicrosandboxResult<String> {
+        match self.call(GatewayOp::Version).await? {
+            GatewayReply::Version { version } => Ok(version),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    /// List every sandbox on the gateway's host.
+    pub async fn list(&self) -> MicrosandboxResult<Vec<RemoteSandbox>> {
+        match self.call(GatewayOp::List).await? {
+            GatewayReply::Sandboxes { sandboxes } => Ok(sandboxes),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    /// Create and boot a sandbox on the gateway's host, then connect to it.
+    ///
+    /// Host paths in the config (bind mounts, volumes) refer to the
+    /// gateway's host.
+    pub async fn create(&self, config: SandboxConfig) -> MicrosandboxResult<Sandbox> {
+        let name = config.name.clone();
+        self.lifecycle(GatewayOp::Create {
+            config: Box::new(config),
+        })
+        .await?;
+        self.connect(&name).await
+    }
+
+    /// Start a stopped sandbox on the gateway's host.
+    pub async fn start(&self, name: &str) -> MicrosandboxResult<()> {
+        self.lifecycle(GatewayOp::Start { name: name.into() }).await
+    }
+
+    /// Stop a sandbox on the gateway's host.
+    pub async fn stop(&self, name: &str) -> MicrosandboxResult<()> {
+        self.lifecycle(GatewayOp::Stop { name: name.into() }).await
+    }
+
+    /// Remove a stopped sandbox from the gateway's host.
+    pub async fn remove(&self, name: &str) -> MicrosandboxResult<()> {
+        self.lifecycle(GatewayOp::Remove { name: name.into() })
+            .await
+    }
+
+    /// Connect to a running sandbox. See [`Sandbox::connect_remote`].
+    pub async fn connect(&self, name: &str) -> MicrosandboxResult<Sandbox> {
+        Sandbox::connect_remote(self, name).await
+    }
+
+    /// Open a connection spliced onto a sandbox's relay socket.
+    pub(crate) async fn open_relay(
+        &self,
+        name: &str,
+    ) -> MicrosandboxResult<(impl AsyncRead + AsyncWrite + Send + 'static, SandboxConfig)> {
+        let mut stream = self
+            .request(GatewayOp::Connect { name: name.into() })
+            .await?;
+        match read_frame(&mut stream).await? {
+            GatewayReply::Connected { config } => Ok((stream, *config)),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn lifecycle(&self, op: GatewayOp) -> MicrosandboxResult<()> {
+        match self.call(op).await? {
+            GatewayReply::Done => Ok(()),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn call(&self, op: GatewayOp) -> MicrosandboxResult<GatewayReply> {
+        let mut stream = self.request(op).await?;
+        read_frame(&mut stream).await
+    }
+
+    /// Connect and send a request.
+    async fn request(&self, op: GatewayOp) -> MicrosandboxResult<Box<dyn GatewayStream>> {
+        let mut stream = self.dial().await?;
+        let request = GatewayRequest {
+            token: self.token.clone(),
+            op,
+        };
+        write_frame(&mut stream, &request).await?;
+        Ok(stream)
+    }
+
+    async fn dial(&self) -> MicrosandboxResult<Box<dyn GatewayStream>> {
+        let addr = (self.url.host.as_str(), self.url.port);
+        let tcp = TcpStream::connect(addr).await.map_err(|e| {
+            MicrosandboxError::Gateway(format!(
+                "connect to {}:{}: {e}",
+                self.url.host, self.url.port
+            ))
+        })?;
+        tcp.set_nodelay(true)?;
+
+        if !self.url.tls {
+            return Ok(Box::new(tcp));
+        }
+
+        let connector = self
+            .connector
+            .get_or_try_init(|| async { self.tls_connector() })
+            .await?;
+        let server_name = ServerName::try_from(self.url.host.clone()).map_err(super::tls_error)?;
+        let tls = connector
+            .connect(server_name, tcp)
+            .await
+            .map_err(super::tls_error)?;
+        Ok(Box::new(tls))
+    }
+
+    fn tls_connector(&self) -> MicrosandboxResult<TlsConnector> {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in rustls_native_certs::load_native_certs().certs {
+            let _ = roots.add(cert);
+        }
+        for path in &self.ca_certs {
+            for cert in super::load_certs(path)? {
+                roots.add(cert).map_err(super::tls_error)?;
+            }
+        }
+
+        let builder = rustls::ClientConfig::builder_with_provider(super::crypto_provider())
+            .with_safe_default_protocol_versions()
+            .map_err(super::tls_error)?
+            .with_root_certificates(roots);
+        let config = match &self.client_cert {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(super::load_certs(cert)?, super::load_private_key(key)?)
+                .map_err(super::tls_error)?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(TlsConnector::from(Arc::new(config)))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> GatewayStream for T {}
+
+impl std::fmt::Debug for RemoteGateway {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteGateway")
+            .field("url", &self.url)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("ca_certs", &self.ca_certs)
+            .field("client_cert", &self.client_cert)
+            .finish()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Turn an error reply, or a reply of the wrong kind, into an error.
+fn unexpected(reply: GatewayReply) -> MicrosandboxError {
+    match reply {
+        GatewayReply::Error { message } => MicrosandboxError::Gateway(message),
+        other => MicrosandboxError::Gateway(format!("unexpected reply: {other:?}")),
+    }
+}
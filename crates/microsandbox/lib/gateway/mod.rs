@@ -0,0 +1,339 @@
+//! Remote access to sandboxes over TCP.
+//!
+//! A sandbox's agent relay is a Unix socket on the host that runs it, so only
+//! local processes can reach it. [`GatewayServer`] (`msb serve`) exposes that
+//! host's sandboxes over TCP, optionally with TLS, and [`RemoteGateway`] is
+//! the matching client.
+//!
+//! Each connection starts with one length-prefixed JSON request carrying the
+//! bearer token and an operation. Lifecycle operations (list, create, start,
+//! stop, remove) get one reply and the connection closes. A `connect`
+//! operation is answered with the sandbox's config, after which the gateway
+//! splices the connection onto the sandbox's relay socket and the client
+//! speaks the ordinary agent protocol over it.
+//!
+//! Every request must be authenticated, by bearer token, by a client
+//! certificate signed by the gateway's client CA (mutual TLS), or both.
+
+mod client;
+mod server;
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use rustls::{
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{MicrosandboxError, MicrosandboxResult, sandbox::SandboxConfig};
+
+//--------------------------------------------------------------------------------------------------
+// Re-Exports
+//--------------------------------------------------------------------------------------------------
+
+pub use client::RemoteGateway;
+pub use server::GatewayServer;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Default address `msb serve` listens on.
+pub const DEFAULT_GATEWAY_ADDR: &str = "127.0.0.1:7443";
+
+/// Upper bound on the size of a gateway request or reply.
+const MAX_FRAME_SIZE: usize = 4 * 1024 * 1024;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A sandbox as reported by a remote gateway.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteSandbox {
+    /// Sandbox name.
+    pub name: String,
+
+    /// Lifecycle status (e.g. `Running`, `Stopped`).
+    pub status: String,
+
+    /// Health status, if the sandbox has a health check.
+    #[serde(default)]
+    pub health: Option<String>,
+
+    /// User labels.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+
+    /// When the sandbox was created.
+    #[serde(default)]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Transport and address of a gateway, parsed from a `tcp://` or `tls://` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GatewayUrl {
+    /// Whether the connection is wrapped in TLS.
+    pub tls: bool,
+
+    /// Host name or IP address, without brackets.
+    pub host: String,
+
+    /// TCP port.
+    pub port: u16,
+}
+
+/// The request that opens every gateway connection.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct GatewayRequest {
+    /// Bearer token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+
+    /// What to do.
+    pub op: GatewayOp,
+}
+
+/// Operations a gateway client can request.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum GatewayOp {
+    /// Report the gateway's version.
+    Version,
+
+    /// List all sandboxes.
+    List,
+
+    /// Create and boot a sandbox.
+    Create {
+        /// Sandbox configuration. Paths refer to the gateway's host.
+        config: Box<SandboxConfig>,
+    },
+
+    /// Start a stopped sandbox.
+    Start {
+        /// Sandbox name.
+        name: String,
+    },
+
+    /// Stop a running sandbox.
+    Stop {
+        /// Sandbox name.
+        name: String,
+    },
+
+    /// Remove a stopped sandbox.
+    Remove {
+        /// Sandbox name.
+        name: String,
+    },
+
+    /// Splice the connection onto a running sandbox's agent relay.
+    Connect {
+        /// Sandbox name.
+        name: String,
+    },
+}
+
+/// The gateway's reply to a [`GatewayRequest`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum GatewayReply {
+    /// Reply to [`GatewayOp::Version`].
+    Version {
+        /// The gateway's `microsandbox` version.
+        version: String,
+    },
+
+    /// Reply to [`GatewayOp::List`].
+    Sandboxes {
+        /// Every sandbox on the gateway's host.
+        sandboxes: Vec<RemoteSandbox>,
+    },
+
+    /// A lifecycle operation succeeded.
+    Done,
+
+    /// Reply to [`GatewayOp::Connect`]; the agent protocol follows.
+    Connected {
+        /// The sandbox's configuration.
+        config: Box<SandboxConfig>,
+    },
+
+    /// The request failed or was not authorized.
+    Error {
+        /// Human-readable reason.
+        message: String,
+    },
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl GatewayUrl {
+    /// Parse a `tcp://host:port` or `tls://host:port` URL.
+    pub fn parse(url: &str) -> MicrosandboxResult<Self> {
+        let invalid = |reason: &str| {
+            MicrosandboxError::InvalidConfig(format!("invalid gateway url '{url}': {reason}"))
+        };
+
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or_else(|| invalid("expected tcp:// or tls://"))?;
+        let tls = match scheme {
+            "tcp" => false,
+            "tls" => true,
+            _ => return Err(invalid("expected tcp:// or tls://")),
+        };
+
+        let authority = rest.strip_suffix('/').unwrap_or(rest);
+        let (host, port) = authority
+            .rsplit_once(':')
+            .ok_or_else(|| invalid("missing port"))?;
+        let host = host
+            .strip_prefix('[')
+            .and_then(|h| h.strip_suffix(']'))
+            .unwrap_or(host);
+        if host.is_empty() || host.contains(['/', '@']) {
+            return Err(invalid("bad host"));
+        }
+        let port = port.parse().map_err(|_| invalid("bad port"))?;
+
+        Ok(Self {
+            tls,
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Read one length-prefixed JSON frame.
+pub(crate) async fn read_frame<T: DeserializeOwned>(
+    reader: &mut (impl AsyncRead + Unpin),
+) -> MicrosandboxResult<T> {
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(MicrosandboxError::Gateway(format!(
+            "frame too large: {len} bytes"
+        )));
+    }
+
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).await?;
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+/// Write one length-prefixed JSON frame.
+pub(crate) async fn write_frame<T: Serialize>(
+    writer: &mut (impl AsyncWrite + Unpin),
+    value: &T,
+) -> MicrosandboxResult<()> {
+    let body = serde_json::to_vec(value)?;
+    let mut buf = Vec::with_capacity(4 + body.len());
+    buf.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    buf.extend_from_slice(&body);
+    writer.write_all(&buf).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// The crypto provider for gateway TLS, independent of any process default.
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// Load every certificate from a PEM file.
+fn load_certs(path: &Path) -> MicrosandboxResult<Vec<CertificateDer<'static>>> {
+    let pem = std::fs::read(path)?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice()).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(MicrosandboxError::InvalidConfig(format!(
+            "no certificates in {}",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+/// Load the first private key from a PEM file.
+fn load_private_key(path: &Path) -> MicrosandboxResult<PrivateKeyDer<'static>> {
+    let pem = std::fs::read(path)?;
+    rustls_pemfile::private_key(&mut pem.as_slice())?.ok_or_else(|| {
+        MicrosandboxError::InvalidConfig(format!("no private key in {}", path.display()))
+    })
+}
+
+fn tls_error(e: impl std::fmt::Display) -> MicrosandboxError {
+    MicrosandboxError::Gateway(format!("tls: {e}"))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gateway_url() {
+        let url = GatewayUrl::parse("tls://kvm-01.internal:7443").unwrap();
+        assert_eq!(
+            url,
+            GatewayUrl {
+                tls: true,
+                host: "kvm-01.internal".into(),
+                port: 7443,
+            }
+        );
+
+        let url = GatewayUrl::parse("tcp://[::1]:7443/").unwrap();
+        assert!(!url.tls);
+        assert_eq!(url.host, "::1");
+
+        for bad in [
+            "http://host:1",
+            "tcp://host",
+            "tcp://:7443",
+            "tcp://host:port",
+            "tcp://user@host:1",
+        ] {
+            assert!(GatewayUrl::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        let req = GatewayRequest {
+            token: Some("secret".into()),
+            op: GatewayOp::Stop {
+                name: "worker".into(),
+            },
+        };
+        write_frame(&mut a, &req).await.unwrap();
+
+        let decoded: GatewayRequest = read_frame(&mut b).await.unwrap();
+        assert_eq!(decoded.token.as_deref(), Some("secret"));
+        assert!(matches!(decoded.op, GatewayOp::Stop { name } if name == "worker"));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_rejected() {
+        let (mut a, mut b) = tokio::io::duplex(64);
+        a.write_all(&(MAX_FRAME_SIZE as u32 + 1).to_be_bytes())
+            .await
+            .unwrap();
+        let result: MicrosandboxResult<GatewayReply> = read_frame(&mut b).await;
+        assert!(matches!(result, Err(MicrosandboxError::Gateway(_))));
+    }
+}
@@ -0,0 +1,416 @@
+//! The gateway server behind `msb serve`.
+
+use std::{future::Future, net::SocketAddr, path::Path, sync::Arc, time::Duration};
+
+use rustls::server::WebPkiClientVerifier;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream, ToSocketAddrs, UnixStream},
+};
+use tokio_rustls::TlsAcceptor;
+
+use crate::{MicrosandboxError, MicrosandboxResult, sandbox::Sandbox};
+
+use super::{GatewayOp, GatewayReply, GatewayRequest, RemoteSandbox, read_frame, write_frame};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// How long a client has to complete TLS and send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A TCP server exposing this host's sandboxes to [`RemoteGateway`] clients.
+///
+/// ```rust,no_run
+/// # async fn example() -> microsandbox::MicrosandboxResult<()> {
+/// use microsandbox::gateway::GatewayServer;
+///
+/// let server = GatewayServer::bind("0.0.0.0:7443")
+///     .await?
+///     .token("s3cret")
+///     .tls("server.pem".as_ref(), "server.key".as_ref(), None)?;
+/// server.serve().await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`RemoteGateway`]: super::RemoteGateway
+pub struct GatewayServer {
+    listener: TcpListener,
+    token: Option<String>,
+    tls: Option<TlsAcceptor>,
+    client_auth: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl GatewayServer {
+    /// Bind the gateway to a socket address.
+    pub async fn bind(addr: impl ToSocketAddrs) -> MicrosandboxResult<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self {
+            listener,
+            token: None,
+            tls: None,
+            client_auth: false,
+        })
+    }
+
+    /// Require clients to present this bearer token.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Serve over TLS with a PEM certificate chain and private key.
+    ///
+    /// With `client_ca`, clients must also present a certificate signed by
+    /// one of the CA certificates in that PEM file, which authenticates them
+    /// without a token.
+    pub fn tls(
+        mut self,
+        cert: &Path,
+        key: &Path,
+        client_ca: Option<&Path>,
+    ) -> MicrosandboxResult<Self> {
+        let provider = super::crypto_provider();
+        let builder = rustls::ServerConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .map_err(super::tls_error)?;
+
+        let builder = match client_ca {
+            Some(path) => {
+                let mut roots = rustls::RootCertStore::empty();
+                for cert in super::load_certs(path)? {
+                    roots.add(cert).map_err(super::tls_error)?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                        .build()
+                        .map_err(super::tls_error)?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let config = builder
+            .with_single_cert(super::load_certs(cert)?, super::load_private_key(key)?)
+            .map_err(super::tls_error)?;
+
+        self.tls = Some(TlsAcceptor::from(Arc::new(config)));
+        self.client_auth = client_ca.is_some();
+        Ok(self)
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> MicrosandboxResult<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve clients until the process exits.
+    pub async fn serve(self) -> MicrosandboxResult<()> {
+        self.serve_with_shutdown(std::future::pending()).await
+    }
+
+    /// Serve clients until `shutdown` resolves.
+    ///
+    /// Fails immediately if neither a token nor client certificates are
+    /// configured: the gateway never serves unauthenticated clients.
+    pub async fn serve_with_shutdown(
+        self,
+        shutdown: impl Future<Output = ()>,
+    ) -> MicrosandboxResult<()> {
+        if self.token.is_none() && !self.client_auth {
+            return Err(MicrosandboxError::InvalidConfig(
+                "gateway requires a token or client certificate authentication".into(),
+            ));
+        }
+
+        let Self {
+            listener,
+            token,
+            tls,
+            ..
+        } = self;
+        let token: Arc<Option<String>> = Arc::new(token);
+
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => return Ok(()),
+                accepted = listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!(error = %e, "gateway: accept failed");
+                            continue;
+                        }
+                    };
+                    let token = Arc::clone(&token);
+                    let tls = tls.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, tls, &token).await {
+                            tracing::debug!(%peer, error = %e, "gateway: connection failed");
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+async fn handle_connection(
+    stream: TcpStream,
+    tls: Option<TlsAcceptor>,
+    token: &Option<String>,
+) -> MicrosandboxResult<()> {
+    match tls {
+        Some(acceptor) => {
+            let stream = tokio::time::timeout(REQUEST_TIMEOUT, acceptor.accept(stream))
+                .await
+                .map_err(|_| MicrosandboxError::Gateway("TLS handshake timed out".into()))??;
+            serve_request(stream, token).await
+        }
+        None => serve_request(stream, token).await,
+    }
+}
+
+/// Read, authorize and answer the connection's request.
+async fn serve_request(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    token: &Option<String>,
+) -> MicrosandboxResult<()> {
+    let request: GatewayRequest = tokio::time::timeout(REQUEST_TIMEOUT, read_frame(&mut stream))
+        .await
+        .map_err(|_| MicrosandboxError::Gateway("request timed out".into()))??;
+
+    if let Some(expected) = token
+        && !request
+            .token
+            .as_deref()
+            .is_some_and(|given| constant_time_eq(given.as_bytes(), expected.as_bytes()))
+    {
+        let reply = GatewayReply::Error {
+            message: "unauthorized".into(),
+        };
+        return write_frame(&mut stream, &reply).await;
+    }
+
+    if let GatewayOp::Connect { name } = request.op {
+        return splice_relay(stream, &name).await;
+    }
+
+    let reply = match execute(request.op).await {
+        Ok(reply) => reply,
+        Err(e) => GatewayReply::Error {
+            message: e.to_string(),
+        },
+    };
+    write_frame(&mut stream, &reply).await
+}
+
+/// Run a lifecycle operation.
+async fn execute(op: GatewayOp) -> MicrosandboxResult<GatewayReply> {
+    match op {
+        GatewayOp::Version => Ok(GatewayReply::Version {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }),
+        GatewayOp::List => {
+            let sandboxes = Sandbox::list()
+                .await?
+                .into_iter()
+                .map(|handle| RemoteSandbox {
+                    name: handle.name().to_string(),
+                    status: format!("{:?}", handle.status()),
+                    health: handle.health().map(|h| format!("{h:?}")),
+                    labels: handle.labels(),
+                    created_at: handle.created_at(),
+                })
+                .collect();
+            Ok(GatewayReply::Sandboxes { sandboxes })
+        }
+        GatewayOp::Create { config } => {
+            tracing::info!(sandbox = %config.name, "gateway: create");
+            Sandbox::create_detached(*config).await?.detach().await;
+            Ok(GatewayReply::Done)
+        }
+        GatewayOp::Start { name } => {
+            tracing::info!(sandbox = %name, "gateway: start");
+            Sandbox::start_detached(&name).await?.detach().await;
+            Ok(GatewayReply::Done)
+        }
+        GatewayOp::Stop { name } => {
+            tracing::info!(sandbox = %name, "gateway: stop");
+            Sandbox::get(&name).await?.stop().await?;
+            Ok(GatewayReply::Done)
+        }
+        GatewayOp::Remove { name } => {
+            tracing::info!(sandbox = %name, "gateway: remove");
+            Sandbox::remove(&name).await?;
+            Ok(GatewayReply::Done)
+        }
+        GatewayOp::Connect { .. } => unreachable!("connect is handled by splice_relay"),
+    }
+}
+
+/// Answer a connect request and forward bytes between the client and the
+/// sandbox's relay socket until either side closes.
+async fn splice_relay(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    name: &str,
+) -> MicrosandboxResult<()> {
+    let opened = async {
+        let handle = Sandbox::get(name).await?;
+        let relay = UnixStream::connect(handle.relay_socket_path()?).await?;
+        MicrosandboxResult::Ok((relay, handle.config()?))
+    }
+    .await;
+
+    let (mut relay, config) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            let reply = GatewayReply::Error {
+                message: e.to_string(),
+            };
+            return write_frame(&mut stream, &reply).await;
+        }
+    };
+
+    let reply = GatewayReply::Connected {
+        config: Box::new(config),
+    };
+    write_frame(&mut stream, &reply).await?;
+
+    tracing::info!(sandbox = %name, "gateway: relay connected");
+    let _ = tokio::io::copy_bidirectional(&mut stream, &mut relay).await;
+    tracing::info!(sandbox = %name, "gateway: relay closed");
+    Ok(())
+}
+
+/// Compare two byte strings in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::RemoteGateway;
+
+    async fn spawn_server(token: &str) -> SocketAddr {
+        let server = GatewayServer::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .token(token);
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.serve());
+        addr
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+    }
+
+    #[tokio::test]
+    async fn test_server_requires_authentication() {
+        let server = GatewayServer::bind("127.0.0.1:0").await.unwrap();
+        assert!(matches!(
+            server.serve().await,
+            Err(MicrosandboxError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_token_checked_over_loopback() {
+        let addr = spawn_server("s3cret").await;
+        let url = format!("tcp://{addr}");
+
+        let gateway = RemoteGateway::new(&url).unwrap().token("s3cret");
+        assert_eq!(gateway.version().await.unwrap(), env!("CARGO_PKG_VERSION"));
+
+        for gateway in [
+            RemoteGateway::new(&url).unwrap(),
+            RemoteGateway::new(&url).unwrap().token("wrong"),
+        ] {
+            match gateway.version().await {
+                Err(MicrosandboxError::Gateway(msg)) => assert_eq!(msg, "unauthorized"),
+                other => panic!("expected unauthorized, got {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_authenticates_over_tls() {
+        use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, pem: String| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, pem).unwrap();
+            path
+        };
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = KeyPair::generate().unwrap();
+        let server_cert = CertificateParams::new(vec!["localhost".into()])
+            .unwrap()
+            .signed_by(&server_key, &ca, &ca_key)
+            .unwrap();
+
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = CertificateParams::new(vec!["orchestrator".into()]).unwrap();
+        client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let client_cert = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+        let ca_pem = write("ca.pem", ca.pem());
+        let server = GatewayServer::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .tls(
+                &write("server.pem", server_cert.pem()),
+                &write("server.key", server_key.serialize_pem()),
+                Some(&ca_pem),
+            )
+            .unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(server.serve());
+
+        let url = format!("tls://localhost:{port}");
+        let gateway = RemoteGateway::new(&url)
+            .unwrap()
+            .ca_cert(&ca_pem)
+            .client_cert(
+                write("client.pem", client_cert.pem()),
+                write("client.key", client_key.serialize_pem()),
+            );
+        assert_eq!(gateway.version().await.unwrap(), env!("CARGO_PKG_VERSION"));
+
+        let anonymous = RemoteGateway::new(&url).unwrap().ca_cert(&ca_pem);
+        assert!(anonymous.version().await.is_err());
+    }
+}
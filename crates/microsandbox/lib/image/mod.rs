@@ -4,13 +4,16 @@
 //! OCI image metadata in the database. The on-disk layer cache is managed
 //! by [`microsandbox_image::GlobalCache`]; this module owns the DB lifecycle.
 
+use std::collections::HashMap;
+
+use microsandbox_utils::index::blob_lock_path;
 use sea_orm::{
     ColumnTrait, ConnectionTrait, EntityTrait, JoinType, PaginatorTrait, QueryFilter, QueryOrder,
     QuerySelect, RelationTrait, Set, TransactionTrait, sea_query::OnConflict,
 };
 
 use crate::{
-    MicrosandboxError, MicrosandboxResult,
+    LabelSelector, MicrosandboxError, MicrosandboxResult,
     db::entity::{
         config as config_entity, image as image_entity, layer as layer_entity,
         manifest as manifest_entity, manifest_layer as manifest_layer_entity,
@@ -39,6 +42,7 @@ pub struct ImageHandle {
     architecture: Option<String>,
     os: Option<String>,
     layer_count: usize,
+    labels: HashMap<String, String>,
     last_used_at: Option<chrono::DateTime<chrono::Utc>>,
     created_at: Option<chrono::DateTime<chrono::Utc>>,
 }
@@ -77,6 +81,8 @@ pub struct ImageConfigDetail {
     pub exposed_ports: Vec<String>,
     /// Declared volume mount points.
     pub volumes: Vec<String>,
+    /// Image labels.
+    pub labels: HashMap<String, String>,
 }
 
 /// Metadata for a single layer.
@@ -129,6 +135,11 @@ impl ImageHandle {
         self.layer_count
     }
 
+    /// Labels from the image config.
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.labels
+    }
+
     /// When this image was last used by a sandbox or pull.
     pub fn last_used_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
         self.last_used_at
@@ -175,13 +186,12 @@ impl Image {
                     upsert_manifest_record(txn, image_id, &metadata.manifest_digest).await?;
 
                 // 3. Upsert config record.
-                let platform = microsandbox_image::Platform::host_linux();
                 upsert_config_record(
                     txn,
                     manifest_id,
                     &metadata.config_digest,
                     &metadata.config,
-                    &platform,
+                    &metadata.platform,
                 )
                 .await?;
 
@@ -245,6 +255,13 @@ impl Image {
         Ok(handles)
     }
 
+    /// List cached images whose config labels match a selector.
+    pub async fn list_matching(selector: &LabelSelector) -> MicrosandboxResult<Vec<ImageHandle>> {
+        let mut handles = Self::list().await?;
+        handles.retain(|handle| selector.matches(&handle.labels));
+        Ok(handles)
+    }
+
     /// Get full detail for an image (config + layers).
     pub async fn inspect(reference: &str) -> MicrosandboxResult<ImageDetail> {
         let db =
@@ -301,6 +318,7 @@ impl Image {
                     user: c.user,
                     exposed_ports: parse_vec("exposed_ports", c.exposed_ports),
                     volumes: parse_vec("volumes", c.volumes),
+                    labels: parse_labels(c.labels.as_deref()),
                 }
             });
 
@@ -339,6 +357,10 @@ impl Image {
                 .and_then(|c| c.architecture.as_deref()),
             config_detail.as_ref().and_then(|c| c.os.as_deref()),
             layers.len(),
+            config_detail
+                .as_ref()
+                .map(|c| c.labels.clone())
+                .unwrap_or_default(),
         );
 
         Ok(ImageDetail {
@@ -362,6 +384,10 @@ impl Image {
             .await?
             .ok_or_else(|| MicrosandboxError::ImageNotFound(reference.into()))?;
 
+        // Every platform variant pulled under this reference has its own
+        // cache metadata entry; collect them before the cascade delete.
+        let platforms = image_platforms(db, image_model.id).await?;
+
         // Run all DB mutations in a transaction to avoid orphaned state.
         let image_id = image_model.id;
         let layer_digests = db
@@ -454,11 +480,21 @@ impl Image {
                     let _ = tokio::fs::remove_file(cache.part_path(&digest)).await;
                     let _ = tokio::fs::remove_file(cache.implicit_dirs_path(&digest)).await;
                     let _ = tokio::fs::remove_dir_all(cache.extracting_dir(&digest)).await;
+                    let _ = tokio::fs::remove_dir_all(cache.lazy_dir(&digest)).await;
+                    let _ = tokio::fs::remove_file(cache.lazy_index_path(&digest)).await;
+                    let _ = tokio::fs::remove_file(cache.lazy_tar_path(&digest)).await;
+                    let blob_path = cache.lazy_blob_path(&digest);
+                    let _ = tokio::fs::remove_file(blob_lock_path(&blob_path)).await;
+                    let _ = tokio::fs::remove_file(&blob_path).await;
+                    let _ = tokio::fs::remove_file(cache.lazy_toc_path(&digest)).await;
                 }
             }
 
             if let Ok(image_ref) = reference.parse::<microsandbox_image::Reference>() {
-                let _ = cache.delete_image_metadata(&image_ref);
+                let _ = cache.delete_image_metadata(&image_ref, &Default::default());
+                for platform in &platforms {
+                    let _ = cache.delete_image_metadata(&image_ref, platform);
+                }
             }
         }
 
@@ -480,7 +516,7 @@ async fn build_handle<C: ConnectionTrait>(
         .one(db)
         .await?;
 
-    let (digest, arch, os, layer_count) = if let Some(ref manifest) = manifest {
+    let (digest, arch, os, layer_count, labels) = if let Some(ref manifest) = manifest {
         let config = config_entity::Entity::find()
             .filter(config_entity::Column::ManifestId.eq(manifest.id))
             .one(db)
@@ -496,9 +532,10 @@ async fn build_handle<C: ConnectionTrait>(
             config.as_ref().and_then(|c| c.architecture.clone()),
             config.as_ref().and_then(|c| c.os.clone()),
             count,
+            parse_labels(config.as_ref().and_then(|c| c.labels.as_deref())),
         )
     } else {
-        (None, None, None, 0)
+        (None, None, None, 0, HashMap::new())
     };
 
     Ok(build_handle_from_parts(
@@ -507,9 +544,35 @@ async fn build_handle<C: ConnectionTrait>(
         arch.as_deref(),
         os.as_deref(),
         layer_count,
+        labels,
     ))
 }
 
+/// Platforms recorded in the config table for an image's manifests.
+async fn image_platforms<C: ConnectionTrait>(
+    db: &C,
+    image_id: i32,
+) -> MicrosandboxResult<Vec<microsandbox_image::Platform>> {
+    let configs = config_entity::Entity::find()
+        .join(JoinType::InnerJoin, config_entity::Relation::Manifest.def())
+        .filter(manifest_entity::Column::ImageId.eq(image_id))
+        .all(db)
+        .await?;
+
+    Ok(configs
+        .into_iter()
+        .filter_map(|config| {
+            let (os, arch) = (config.os?, config.architecture?);
+            Some(match config.os_variant {
+                Some(variant) => {
+                    microsandbox_image::Platform::with_variant(os.as_str(), arch.as_str(), variant)
+                }
+                None => microsandbox_image::Platform::new(os.as_str(), arch.as_str()),
+            })
+        })
+        .collect())
+}
+
 /// Build an [`ImageHandle`] from pre-fetched parts.
 fn build_handle_from_parts(
     model: &image_entity::Model,
@@ -517,6 +580,7 @@ fn build_handle_from_parts(
     architecture: Option<&str>,
     os: Option<&str>,
     layer_count: usize,
+    labels: HashMap<String, String>,
 ) -> ImageHandle {
     ImageHandle {
         db_id: model.id,
@@ -526,11 +590,22 @@ fn build_handle_from_parts(
         architecture: architecture.map(|s| s.to_string()),
         os: os.map(|s| s.to_string()),
         layer_count,
+        labels,
         last_used_at: model.last_used_at.map(|dt| dt.and_utc()),
         created_at: model.created_at.map(|dt| dt.and_utc()),
     }
 }
 
+/// Parse the JSON-encoded labels column of a config record.
+fn parse_labels(raw: Option<&str>) -> HashMap<String, String> {
+    raw.and_then(|s| {
+        serde_json::from_str(s)
+            .map_err(|e| tracing::warn!("failed to parse config labels: {e}"))
+            .ok()
+    })
+    .unwrap_or_default()
+}
+
 /// Upsert an image record by reference. Returns the image ID.
 pub(crate) async fn upsert_image_record<C: ConnectionTrait>(
     db: &C,
@@ -634,6 +709,11 @@ async fn upsert_config_record<C: ConnectionTrait>(
     } else {
         Some(serde_json::to_string(&config.exposed_ports)?)
     };
+    let labels_json = if config.labels.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&config.labels)?)
+    };
 
     let now = chrono::Utc::now().naive_utc();
 
@@ -648,7 +728,7 @@ async fn upsert_config_record<C: ConnectionTrait>(
         digest: Set(digest.to_string()),
         architecture: Set(Some(platform.arch.to_string())),
         os: Set(Some(platform.os.to_string())),
-        os_variant: Set(None),
+        os_variant: Set(platform.variant.clone()),
         env: Set(env_json),
         cmd: Set(cmd_json),
         entrypoint: Set(entrypoint_json),
@@ -659,6 +739,7 @@ async fn upsert_config_record<C: ConnectionTrait>(
         rootfs_type: Set(Some("layers".to_string())),
         rootfs_diff_ids: Set(None),
         history: Set(None),
+        labels: Set(labels_json),
         created_at: Set(Some(now)),
         ..Default::default()
     })
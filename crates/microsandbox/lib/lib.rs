@@ -13,10 +13,16 @@ pub mod agent;
 pub mod config;
 #[allow(dead_code)]
 pub(crate) mod db;
+pub mod events;
+pub mod gateway;
 pub mod image;
+#[cfg(feature = "net")]
+pub mod network;
 pub mod runtime;
 pub mod sandbox;
+pub mod selector;
 pub mod setup;
+pub mod telemetry;
 pub mod volume;
 
 pub use error::*;
@@ -24,7 +30,10 @@ pub use microsandbox_image::RegistryAuth;
 pub use microsandbox_runtime::logging::LogLevel;
 pub use microsandbox_utils::size;
 #[cfg(feature = "net")]
+pub use network::Network;
+#[cfg(feature = "net")]
 pub use sandbox::NetworkPolicy;
 pub use sandbox::exec::{ExecEvent, ExecHandle};
-pub use sandbox::{ExecOutput, Sandbox, SandboxConfig};
+pub use sandbox::{ExecOutput, Sandbox, SandboxConfig, SandboxPool};
+pub use selector::LabelSelector;
 pub use volume::Volume;
@@ -0,0 +1,483 @@
+//! Private network management.
+//!
+//! A private network lets sandboxes reach each other by name without
+//! publishing ports on the host. Each network owns an IPv4 subnet (by default
+//! a `/24` from `198.18.0.0/15`) and a rendezvous directory under
+//! `~/.microsandbox/networks/<name>/` where the sandbox processes of its
+//! members find each other. Traffic between members is switched host-side
+//! and checked against each sandbox's network policy.
+
+use std::{borrow::Cow, net::Ipv4Addr};
+
+use ipnetwork::Ipv4Network;
+use microsandbox_network::config::NetworkAttachment;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+
+use crate::{
+    LabelSelector, MicrosandboxError, MicrosandboxResult,
+    db::entity::{network as network_entity, sandbox as sandbox_entity},
+    sandbox::SandboxConfig,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Base of the pool default subnets are carved from: `198.18.0.0/15`, the
+/// RFC 2544 benchmarking range, which no policy destination group covers.
+const SUBNET_POOL_BASE: Ipv4Addr = Ipv4Addr::new(198, 18, 0, 0);
+
+/// Prefix length of the default subnet pool.
+const SUBNET_POOL_PREFIX: u8 = 15;
+
+/// Prefix length of default subnets: 64 members each.
+const DEFAULT_PREFIX: u8 = 24;
+
+/// Smallest allowed subnet: room for two members.
+const MAX_PREFIX: u8 = 29;
+
+/// Largest allowed subnet.
+const MIN_PREFIX: u8 = 16;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A private network shared between sandboxes.
+pub struct Network {
+    name: String,
+    subnet: Ipv4Network,
+}
+
+/// Options for creating a network.
+#[derive(Debug, Clone)]
+pub struct NetworkSpec {
+    /// Network name.
+    pub name: String,
+
+    /// Subnet (None = next free `/24` from `198.18.0.0/15`).
+    pub subnet: Option<Ipv4Network>,
+
+    /// Labels for organization (JSON-serialized in DB).
+    pub labels: Vec<(String, String)>,
+}
+
+/// A lightweight handle to a network from the database.
+///
+/// Obtained via [`Network::get`] or [`Network::list`].
+#[derive(Debug)]
+pub struct NetworkHandle {
+    db_id: i32,
+    name: String,
+    subnet: Ipv4Network,
+    labels: Vec<(String, String)>,
+    created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Builder for creating a network.
+pub struct NetworkBuilder {
+    spec: NetworkSpec,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods: NetworkHandle
+//--------------------------------------------------------------------------------------------------
+
+impl NetworkHandle {
+    /// Create a handle from a database entity model.
+    pub(crate) fn from_model(model: network_entity::Model) -> MicrosandboxResult<Self> {
+        let labels = model
+            .labels
+            .as_deref()
+            .map(|s| {
+                serde_json::from_str::<Vec<(String, String)>>(s).unwrap_or_else(|e| {
+                    tracing::warn!(network = %model.name, error = %e, "failed to parse network labels JSON");
+                    Vec::new()
+                })
+            })
+            .unwrap_or_default();
+        let subnet = model.subnet.parse().map_err(|e| {
+            MicrosandboxError::Custom(format!("network {}: invalid subnet: {e}", model.name))
+        })?;
+
+        Ok(Self {
+            db_id: model.id,
+            name: model.name,
+            subnet,
+            labels,
+            created_at: model.created_at.map(|dt| dt.and_utc()),
+        })
+    }
+
+    /// Unique name identifying this network. Sandboxes join it via
+    /// `.network(|n| n.attach(handle.name()))`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The subnet members get their addresses from.
+    pub fn subnet(&self) -> Ipv4Network {
+        self.subnet
+    }
+
+    /// Key-value labels for organizing and filtering networks.
+    pub fn labels(&self) -> &[(String, String)] {
+        &self.labels
+    }
+
+    /// When this network was created, if recorded.
+    pub fn created_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.created_at
+    }
+
+    /// Names of the sandboxes configured to join this network, running or
+    /// not.
+    pub async fn members(&self) -> MicrosandboxResult<Vec<String>> {
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+
+        Ok(sandbox_entity::Entity::find()
+            .order_by_asc(sandbox_entity::Column::Name)
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|sandbox| {
+                serde_json::from_str::<SandboxConfig>(&sandbox.config).is_ok_and(|config| {
+                    config
+                        .network
+                        .attachment
+                        .is_some_and(|attachment| attachment.name == self.name)
+                })
+            })
+            .map(|sandbox| sandbox.name)
+            .collect())
+    }
+
+    /// Remove this network from the database and filesystem.
+    ///
+    /// Fails with [`MicrosandboxError::NetworkInUse`] while any sandbox,
+    /// running or not, is configured to join it.
+    pub async fn remove(&self) -> MicrosandboxResult<()> {
+        let members = self.members().await?;
+        if !members.is_empty() {
+            return Err(MicrosandboxError::NetworkInUse(members.join(", ")));
+        }
+
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+        network_entity::Entity::delete_by_id(self.db_id)
+            .exec(db)
+            .await?;
+
+        let path = crate::config::config().networks_dir().join(&self.name);
+        if path.exists() {
+            tokio::fs::remove_dir_all(&path).await?;
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods: Static
+//--------------------------------------------------------------------------------------------------
+
+impl Network {
+    /// Start building a new network. Call `.create()` on the returned
+    /// builder to persist it.
+    pub fn builder(name: impl Into<String>) -> NetworkBuilder {
+        NetworkBuilder::new(name)
+    }
+
+    /// Create a network: allocates its subnet and records it in the
+    /// database. Fails with [`MicrosandboxError::NetworkAlreadyExists`] if a
+    /// network with the same name already exists.
+    pub async fn create(spec: NetworkSpec) -> MicrosandboxResult<Self> {
+        tracing::debug!(name = %spec.name, subnet = ?spec.subnet, "Network::create");
+        validate_network_name(&spec.name)?;
+
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+
+        let existing = network_entity::Entity::find().all(db).await?;
+        if existing.iter().any(|model| model.name == spec.name) {
+            return Err(MicrosandboxError::NetworkAlreadyExists(spec.name));
+        }
+        let taken: Vec<Ipv4Network> = existing
+            .iter()
+            .filter_map(|model| model.subnet.parse().ok())
+            .collect();
+
+        let subnet = match spec.subnet {
+            Some(subnet) => {
+                validate_subnet(subnet, &taken)?;
+                subnet
+            }
+            None => allocate_subnet(&taken).ok_or_else(|| {
+                MicrosandboxError::InvalidConfig(format!(
+                    "no free /{DEFAULT_PREFIX} subnet left in {SUBNET_POOL_BASE}/{SUBNET_POOL_PREFIX}"
+                ))
+            })?,
+        };
+
+        let labels_json = if spec.labels.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&spec.labels)?)
+        };
+
+        let now = chrono::Utc::now().naive_utc();
+        let model = network_entity::ActiveModel {
+            name: Set(spec.name.clone()),
+            subnet: Set(subnet.to_string()),
+            labels: Set(labels_json),
+            created_at: Set(Some(now)),
+            updated_at: Set(Some(now)),
+            ..Default::default()
+        };
+        network_entity::Entity::insert(model).exec(db).await?;
+
+        Ok(Self {
+            name: spec.name,
+            subnet,
+        })
+    }
+
+    /// Get a network handle by name from the database.
+    pub async fn get(name: &str) -> MicrosandboxResult<NetworkHandle> {
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+
+        let model = network_entity::Entity::find()
+            .filter(network_entity::Column::Name.eq(name))
+            .one(db)
+            .await?
+            .ok_or_else(|| MicrosandboxError::NetworkNotFound(name.into()))?;
+
+        NetworkHandle::from_model(model)
+    }
+
+    /// List all networks, ordered by creation time (newest first).
+    pub async fn list() -> MicrosandboxResult<Vec<NetworkHandle>> {
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+
+        network_entity::Entity::find()
+            .order_by_desc(network_entity::Column::CreatedAt)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(NetworkHandle::from_model)
+            .collect()
+    }
+
+    /// List networks whose labels match a selector.
+    pub async fn list_matching(selector: &LabelSelector) -> MicrosandboxResult<Vec<NetworkHandle>> {
+        let mut handles = Self::list().await?;
+        handles.retain(|handle| selector.matches(handle.labels().iter().map(|(k, v)| (k, v))));
+        Ok(handles)
+    }
+
+    /// Delete a network. Fails with [`MicrosandboxError::NetworkNotFound`]
+    /// if no such network exists.
+    pub async fn remove(name: &str) -> MicrosandboxResult<()> {
+        Self::get(name).await?.remove().await
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods: Instance
+//--------------------------------------------------------------------------------------------------
+
+impl Network {
+    /// Unique name identifying this network.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The subnet members get their addresses from.
+    pub fn subnet(&self) -> Ipv4Network {
+        self.subnet
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods: NetworkBuilder
+//--------------------------------------------------------------------------------------------------
+
+impl NetworkBuilder {
+    /// Start building a network with the given name. Names must contain only
+    /// alphanumeric characters, dots, hyphens, and underscores.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            spec: NetworkSpec {
+                name: name.into(),
+                subnet: None,
+                labels: Vec::new(),
+            },
+        }
+    }
+
+    /// Use a specific subnet instead of the next free one. Prefixes from
+    /// `/16` to `/29` are accepted; each member takes a `/30` block.
+    ///
+    /// Subnets inside a policy destination group (e.g. `10.0.0.0/8` is
+    /// `private`) are denied by the default `public-only` policy.
+    pub fn subnet(mut self, subnet: Ipv4Network) -> Self {
+        self.spec.subnet = Some(subnet);
+        self
+    }
+
+    /// Attach a key-value label for organizing and filtering networks.
+    /// Can be called multiple times.
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.spec.labels.push((key.into(), value.into()));
+        self
+    }
+
+    /// Build the network spec without creating it.
+    pub fn build(self) -> NetworkSpec {
+        self.spec
+    }
+
+    /// Create the network.
+    pub async fn create(self) -> MicrosandboxResult<Network> {
+        Network::create(self.spec).await
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl From<NetworkSpec> for NetworkBuilder {
+    fn from(spec: NetworkSpec) -> Self {
+        Self { spec }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Fill in the subnet, rendezvous directory and member name of the network
+/// a sandbox joins, for the sandbox process to bind its switch port.
+///
+/// Returns the config unchanged when the sandbox joins no network.
+pub(crate) async fn resolve_attachment(
+    config: &SandboxConfig,
+) -> MicrosandboxResult<Cow<'_, SandboxConfig>> {
+    let Some(ref attachment) = config.network.attachment else {
+        return Ok(Cow::Borrowed(config));
+    };
+
+    let network = Network::get(&attachment.name).await?;
+    let dir = crate::config::config().networks_dir().join(network.name());
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let mut config = config.clone();
+    config.network.attachment = Some(NetworkAttachment {
+        name: network.name().to_string(),
+        aliases: attachment.aliases.clone(),
+        subnet: Some(network.subnet()),
+        dir: Some(dir),
+        member_name: Some(config.name.clone()),
+    });
+    Ok(Cow::Owned(config))
+}
+
+/// Pick the first default-sized subnet in the pool that overlaps no
+/// existing network.
+fn allocate_subnet(taken: &[Ipv4Network]) -> Option<Ipv4Network> {
+    let base = u32::from(SUBNET_POOL_BASE);
+    let count = 1u32 << (DEFAULT_PREFIX - SUBNET_POOL_PREFIX);
+    (0..count)
+        .map(|i| {
+            let network = Ipv4Addr::from(base + (i << (32 - DEFAULT_PREFIX)));
+            Ipv4Network::new(network, DEFAULT_PREFIX).expect("valid prefix")
+        })
+        .find(|candidate| !taken.iter().any(|t| t.overlaps(*candidate)))
+}
+
+/// Check an explicitly requested subnet.
+fn validate_subnet(subnet: Ipv4Network, taken: &[Ipv4Network]) -> MicrosandboxResult<()> {
+    if !(MIN_PREFIX..=MAX_PREFIX).contains(&subnet.prefix()) {
+        return Err(MicrosandboxError::InvalidConfig(format!(
+            "network subnet prefix must be between /{MIN_PREFIX} and /{MAX_PREFIX}: {subnet}"
+        )));
+    }
+    if subnet.network() != subnet.ip() {
+        return Err(MicrosandboxError::InvalidConfig(format!(
+            "network subnet has host bits set: {subnet}"
+        )));
+    }
+    if let Some(other) = taken.iter().find(|t| t.overlaps(subnet)) {
+        return Err(MicrosandboxError::InvalidConfig(format!(
+            "network subnet {subnet} overlaps existing network subnet {other}"
+        )));
+    }
+    Ok(())
+}
+
+/// Validate that a network name is safe for use as a directory name.
+///
+/// Names must start with an alphanumeric character and contain only
+/// alphanumeric characters, dots, hyphens, and underscores.
+fn validate_network_name(name: &str) -> MicrosandboxResult<()> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+
+    if !valid {
+        return Err(MicrosandboxError::InvalidConfig(format!(
+            "network name must start with an alphanumeric character and contain only \
+             alphanumeric characters, dots, hyphens, and underscores: {name:?}"
+        )));
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_subnet_skips_taken_ranges() {
+        assert_eq!(allocate_subnet(&[]), Some("198.18.0.0/24".parse().unwrap()));
+        assert_eq!(
+            allocate_subnet(&[
+                "198.18.0.0/24".parse().unwrap(),
+                "198.18.1.128/25".parse().unwrap(),
+            ]),
+            Some("198.18.2.0/24".parse().unwrap())
+        );
+        assert_eq!(allocate_subnet(&["198.18.0.0/15".parse().unwrap()]), None);
+    }
+
+    #[test]
+    fn test_validate_subnet_checks_prefix_and_overlap() {
+        let taken = ["198.18.0.0/24".parse().unwrap()];
+        assert!(validate_subnet("10.1.0.0/24".parse().unwrap(), &taken).is_ok());
+        assert!(validate_subnet("10.1.0.0/30".parse().unwrap(), &taken).is_err());
+        assert!(validate_subnet("10.0.0.0/8".parse().unwrap(), &taken).is_err());
+        assert!(validate_subnet("10.1.0.1/24".parse().unwrap(), &taken).is_err());
+        assert!(validate_subnet("198.18.0.0/23".parse().unwrap(), &taken).is_err());
+    }
+
+    #[test]
+    fn test_validate_network_name_rejects_paths() {
+        assert!(validate_network_name("backend_1.internal").is_ok());
+        assert!(validate_network_name("").is_err());
+        assert!(validate_network_name("../etc").is_err());
+        assert!(validate_network_name("a/b").is_err());
+    }
+}
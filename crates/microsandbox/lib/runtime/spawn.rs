@@ -112,6 +112,12 @@ pub async fn spawn_sandbox(
     // Compute the agent relay socket path.
     let agent_sock_path = runtime_dir.join("agent.sock");
 
+    // Resolve the private network (if any) so the sandbox process can join it.
+    #[cfg(feature = "net")]
+    let config = crate::network::resolve_attachment(config).await?;
+    #[cfg(feature = "net")]
+    let config = &*config;
+
     // Stage file bind mounts: each file gets its own isolated directory so
     // that virtio-fs (which requires directories) can share it without
     // exposing adjacent files on the host.
@@ -138,6 +144,24 @@ pub async fn spawn_sandbox(
     // mode, which corrupts the parent's terminal output (\n without \r).
     cmd.stdin(Stdio::null());
 
+    // Keep sensitive env values off the sandbox process's command line.
+    let sensitive_env: Vec<String> = config
+        .sensitive_env_values()
+        .into_iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect();
+    if !sensitive_env.is_empty() {
+        cmd.env(
+            microsandbox_runtime::vm::SENSITIVE_ENV_VAR,
+            serde_json::to_string(&sensitive_env)?,
+        );
+    }
+
+    // Continue the caller's trace in the sandbox process.
+    if let Some(traceparent) = crate::telemetry::current_traceparent() {
+        cmd.env(crate::telemetry::TRACEPARENT_ENV, traceparent);
+    }
+
     if mode == SpawnMode::Detached {
         // Detached sandboxes outlive the creating CLI process, so the
         // sandbox must not stay coupled to the foreground job or terminal.
@@ -434,6 +458,42 @@ fn sandbox_cli_args(
         args.push(OsString::from("--idle-timeout"));
         args.push(OsString::from(idle.to_string()));
     }
+    for limit in &sp.resource_limits {
+        args.push(OsString::from("--resource-limit"));
+        args.push(OsString::from(limit.to_string()));
+    }
+    if let Some(check) = &sp.health_check {
+        args.push(OsString::from("--health-check"));
+        args.push(OsString::from(
+            serde_json::to_string(check).expect("health check serializes to JSON"),
+        ));
+    }
+    for mount in &config.socket_mounts {
+        args.push(OsString::from("--socket-mount"));
+        args.push(OsString::from(
+            serde_json::to_string(mount).expect("socket mount serializes to JSON"),
+        ));
+    }
+    if config.restart_count > 0 {
+        args.push(OsString::from("--restart-count"));
+        args.push(OsString::from(config.restart_count.to_string()));
+    }
+
+    let retention = config::config().metrics.retention();
+    args.push(OsString::from("--metrics-raw-retention"));
+    args.push(OsString::from(retention.raw.as_secs().to_string()));
+    args.push(OsString::from("--metrics-minute-retention"));
+    args.push(OsString::from(retention.minute.as_secs().to_string()));
+    args.push(OsString::from("--metrics-hour-retention"));
+    args.push(OsString::from(retention.hour.as_secs().to_string()));
+
+    let telemetry = &config::config().telemetry;
+    if let Some(endpoint) = &telemetry.otlp_endpoint {
+        args.push(OsString::from("--otel-endpoint"));
+        args.push(OsString::from(endpoint));
+        args.push(OsString::from("--otel-service-name"));
+        args.push(OsString::from(&telemetry.service_name));
+    }
 
     args.push(OsString::from("--libkrunfw-path"));
     args.push(libkrunfw_path.as_os_str().to_os_string());
@@ -566,7 +626,12 @@ fn sandbox_cli_args(
         args.push(OsString::from(sandbox_id.to_string()));
     }
 
-    for (key, value) in &config.env {
+    // Sensitive values go through the environment instead; see `spawn_sandbox`.
+    for (key, value) in config
+        .env
+        .iter()
+        .filter(|(k, _)| !config.is_sensitive_env(k))
+    {
         args.push(OsString::from("--env"));
         args.push(OsString::from(format!("{key}={value}")));
     }
@@ -885,6 +950,38 @@ mod tests {
         assert!(!rendered.iter().any(|a| a.starts_with("MSB_TMPFS=")));
     }
 
+    #[test]
+    fn test_sandbox_cli_args_omit_sensitive_env_values() {
+        let config = SandboxBuilder::new("test")
+            .image("/tmp/rootfs")
+            .env("PLAIN", "visible")
+            .sensitive_env("TOKEN", "hunter2")
+            .build()
+            .unwrap();
+
+        let args = sandbox_cli_args(
+            &config,
+            42,
+            Path::new("/tmp/msb.db"),
+            Path::new("/tmp/logs"),
+            Path::new("/tmp/runtime"),
+            Path::new("/tmp/rootfs-base"),
+            Path::new("/tmp/rw"),
+            Path::new("/tmp/staging"),
+            Path::new("/tmp/agent.sock"),
+            Path::new("/tmp/libkrunfw.dylib"),
+            &HashMap::new(),
+        );
+
+        let rendered = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+
+        assert!(rendered.contains(&"PLAIN=visible".to_string()));
+        assert!(!rendered.iter().any(|a| a.contains("hunter2")));
+    }
+
     #[test]
     fn test_sandbox_cli_args_disk_image_with_fstype() {
         let config = SandboxBuilder::new("test")
@@ -0,0 +1,877 @@
+//! Sandbox export and import as portable archives.
+//!
+//! An archive is a zstd-compressed tarball with the following layout:
+//!
+//! - `sandbox.json` — the [`SandboxArchiveManifest`] (config, image pin, bundled contents)
+//! - `rw/` — the overlay upper layer holding every guest-side modification
+//! - `layers/<n>/` — extracted image lower layers, bottom-to-top (only with
+//!   [`ExportOptions::include_layers`])
+//! - `volumes/<name>/` — named volume contents (only with
+//!   [`ExportOptions::include_volumes`])
+//!
+//! Guest ownership and mode bits live in `user.*` extended attributes on the
+//! host, so entries carry their xattrs as PAX `SCHILY.xattr.*` records.
+
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, TransactionTrait};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    MicrosandboxError, MicrosandboxResult,
+    db::entity::sandbox_image as sandbox_image_entity,
+    volume::{Volume, VolumeConfig},
+};
+
+use super::{
+    RootfsSource, Sandbox, SandboxConfig, SandboxHandle, SandboxStatus, VolumeMount,
+    has_pending_lazy_blob, insert_sandbox_record_with_status, load_sandbox_record_reconciled,
+    pinned_image_reference, prepare_create_target, pull_oci_image, remove_dir_if_exists,
+    replace_oci_manifest_pin, validate_platform,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Current archive format version written by [`Sandbox::export`].
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// Archive entry holding the serialized [`SandboxArchiveManifest`].
+const MANIFEST_ENTRY: &str = "sandbox.json";
+
+/// Archive directory holding the overlay upper layer.
+const RW_ENTRY: &str = "rw";
+
+/// Archive directory holding bundled lower layers.
+const LAYERS_ENTRY: &str = "layers";
+
+/// Archive directory holding bundled named volumes.
+const VOLUMES_ENTRY: &str = "volumes";
+
+/// zstd compression level used for exports.
+const ZSTD_LEVEL: i32 = 3;
+
+/// PAX record key prefix for extended attributes.
+const PAX_XATTR_PREFIX: &[u8] = b"SCHILY.xattr.";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Options for [`Sandbox::export`].
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Bundle the contents of every named volume mounted by the sandbox.
+    pub include_volumes: bool,
+
+    /// Bundle the extracted image layers so the archive can be imported
+    /// without registry access.
+    pub include_layers: bool,
+}
+
+/// Options for [`Sandbox::import`].
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Import under a different name than the one recorded in the archive.
+    pub name: Option<String>,
+
+    /// Replace an existing sandbox with the same name.
+    pub replace: bool,
+}
+
+/// Metadata describing the contents of a sandbox archive.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SandboxArchiveManifest {
+    /// Archive format version.
+    pub version: u32,
+
+    /// Sandbox configuration as persisted on the exporting host, with
+    /// host-specific layer paths removed.
+    pub config: SandboxConfig,
+
+    /// The OCI image the sandbox was created from.
+    pub image: ArchiveImage,
+
+    /// Number of lower layers bundled under `layers/` (0 when the image
+    /// must be pulled on import).
+    pub layers: usize,
+
+    /// Named volumes bundled under `volumes/`.
+    pub volumes: Vec<ArchiveVolume>,
+
+    /// When the archive was written.
+    pub exported_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Image reference recorded in a sandbox archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveImage {
+    /// Image reference the sandbox was created from.
+    pub reference: String,
+
+    /// Manifest digest the sandbox was pinned to, if known.
+    pub manifest_digest: Option<String>,
+}
+
+/// Named volume recorded in a sandbox archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveVolume {
+    /// Volume name.
+    pub name: String,
+
+    /// Size quota in MiB.
+    pub quota_mib: Option<u32>,
+
+    /// Volume labels.
+    pub labels: Vec<(String, String)>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Sandbox {
+    /// Export a stopped sandbox to a zstd-compressed tar archive at `dest`.
+    ///
+    /// The archive contains the sandbox config, its writable upper layer,
+    /// and the pinned image reference. Named volumes and image layers are
+    /// bundled when requested in `options`.
+    pub async fn export(
+        name: &str,
+        dest: impl AsRef<Path>,
+        options: ExportOptions,
+    ) -> MicrosandboxResult<()> {
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+        let model = load_sandbox_record_reconciled(db, name).await?;
+
+        if matches!(
+            model.status,
+            SandboxStatus::Running | SandboxStatus::Draining | SandboxStatus::Paused
+        ) {
+            return Err(MicrosandboxError::SandboxStillRunning(format!(
+                "cannot export sandbox '{name}': stop it first"
+            )));
+        }
+
+        let mut config: SandboxConfig = serde_json::from_str(&model.config)?;
+        let RootfsSource::Oci(ref reference) = config.image else {
+            return Err(MicrosandboxError::InvalidConfig(format!(
+                "cannot export sandbox '{name}': only OCI-based sandboxes can be exported"
+            )));
+        };
+
+        let manifest_digest = sandbox_image_entity::Entity::find()
+            .filter(sandbox_image_entity::Column::SandboxId.eq(model.id))
+            .one(db)
+            .await?
+            .map(|pin| pin.manifest_digest);
+        let image = ArchiveImage {
+            reference: reference.clone(),
+            manifest_digest,
+        };
+
+        let global = crate::config::config();
+        let layers = std::mem::take(&mut config.resolved_rootfs_layers);
+        let layers = if options.include_layers {
+            if has_pending_lazy_blob(&layers) {
+                return Err(MicrosandboxError::InvalidConfig(format!(
+                    "cannot export sandbox '{name}': image layers are still downloading; \
+                     start the sandbox to finish them or export without layers"
+                )));
+            }
+            layers
+        } else {
+            Vec::new()
+        };
+
+        let mut volumes = Vec::new();
+        let mut volume_dirs = Vec::new();
+        if options.include_volumes {
+            for mount in &config.mounts {
+                let VolumeMount::Named { name: volume, .. } = mount else {
+                    continue;
+                };
+                if volumes.iter().any(|v: &ArchiveVolume| &v.name == volume) {
+                    continue;
+                }
+
+                let handle = Volume::get(volume).await?;
+                volumes.push(ArchiveVolume {
+                    name: handle.name().to_string(),
+                    quota_mib: handle.quota_mib(),
+                    labels: handle.labels().to_vec(),
+                });
+                volume_dirs.push((volume.clone(), global.volumes_dir().join(volume)));
+            }
+        }
+
+        let manifest = SandboxArchiveManifest {
+            version: ARCHIVE_FORMAT_VERSION,
+            config,
+            image,
+            layers: layers.len(),
+            volumes,
+            exported_at: chrono::Utc::now(),
+        };
+
+        let dest = dest.as_ref().to_path_buf();
+        let rw_dir = global.sandboxes_dir().join(name).join(RW_ENTRY);
+        tokio::task::spawn_blocking(move || {
+            write_archive(&dest, &manifest, &rw_dir, &layers, &volume_dirs)
+        })
+        .await
+        .map_err(|e| MicrosandboxError::Custom(format!("export task panicked: {e}")))?
+    }
+
+    /// Import a sandbox from an archive produced by [`Sandbox::export`].
+    ///
+    /// The sandbox is recreated in the `Stopped` state. Unless the archive
+    /// bundles its image layers, the pinned image is pulled from the
+    /// registry. Bundled named volumes are recreated and must not already
+    /// exist.
+    pub async fn import(
+        src: impl AsRef<Path>,
+        options: ImportOptions,
+    ) -> MicrosandboxResult<SandboxHandle> {
+        let global = crate::config::config();
+        let sandboxes_dir = global.sandboxes_dir();
+        tokio::fs::create_dir_all(&sandboxes_dir).await?;
+
+        // Unpack next to the final sandbox directory so the contents can be
+        // moved into place with a rename.
+        let staging = tempfile::Builder::new()
+            .prefix(".import-")
+            .tempdir_in(&sandboxes_dir)?;
+        let src = src.as_ref().to_path_buf();
+        let unpack_dir = staging.path().to_path_buf();
+        let manifest = tokio::task::spawn_blocking(move || read_archive(&src, &unpack_dir))
+            .await
+            .map_err(|e| MicrosandboxError::Custom(format!("import task panicked: {e}")))??;
+
+        let SandboxArchiveManifest {
+            mut config,
+            image,
+            layers,
+            volumes,
+            ..
+        } = manifest;
+
+        if let Some(name) = options.name {
+            config.name = name;
+        }
+        if config.name.is_empty() {
+            return Err(MicrosandboxError::InvalidConfig(
+                "sandbox name is required".into(),
+            ));
+        }
+        config.replace_existing = options.replace;
+        validate_platform(&config)?;
+
+        for volume in &volumes {
+            if Volume::get(&volume.name).await.is_ok() {
+                return Err(MicrosandboxError::VolumeAlreadyExists(volume.name.clone()));
+            }
+        }
+
+        let db = crate::db::init_global(Some(global.database.max_connections)).await?;
+        let sandbox_dir = sandboxes_dir.join(&config.name);
+        prepare_create_target(db, &config, &sandbox_dir).await?;
+
+        let pin =
+            match import_inner(&mut config, &image, layers, staging.path(), &sandbox_dir).await {
+                Ok(pin) => pin,
+                Err(err) => {
+                    let _ = remove_dir_if_exists(&sandbox_dir);
+                    return Err(err);
+                }
+            };
+
+        let restored = match restore_volumes(
+            db,
+            &global.volumes_dir(),
+            &staging.path().join(VOLUMES_ENTRY),
+            &volumes,
+        )
+        .await
+        {
+            Ok(restored) => restored,
+            Err(err) => {
+                let _ = remove_dir_if_exists(&sandbox_dir);
+                return Err(err);
+            }
+        };
+
+        if let Err(err) = insert_imported_record(db, &config, pin).await {
+            discard_volumes(db, &restored).await;
+            let _ = remove_dir_if_exists(&sandbox_dir);
+            return Err(err);
+        }
+
+        Sandbox::get(&config.name).await
+    }
+}
+
+impl SandboxHandle {
+    /// Export this sandbox to an archive. See [`Sandbox::export`].
+    pub async fn export(
+        &self,
+        dest: impl AsRef<Path>,
+        options: ExportOptions,
+    ) -> MicrosandboxResult<()> {
+        Sandbox::export(self.name(), dest, options).await
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Move the unpacked upper layer and lower layers into place.
+///
+/// Returns the `(reference, manifest digest)` pin when the image was pulled
+/// rather than bundled.
+async fn import_inner(
+    config: &mut SandboxConfig,
+    image: &ArchiveImage,
+    layers: usize,
+    staging: &Path,
+    sandbox_dir: &Path,
+) -> MicrosandboxResult<Option<(String, String)>> {
+    tokio::fs::create_dir_all(sandbox_dir).await?;
+
+    // Resolve the lower layer stack: bundled layers move with the sandbox,
+    // otherwise pull the exact manifest the sandbox was pinned to.
+    let mut pin = None;
+    if layers > 0 {
+        let layers_dir = sandbox_dir.join(LAYERS_ENTRY);
+        tokio::fs::rename(staging.join(LAYERS_ENTRY), &layers_dir).await?;
+        config.resolved_rootfs_layers = (0..layers)
+            .map(|index| layers_dir.join(index.to_string()))
+            .collect();
+    } else {
+        let reference = pinned_reference(image)?;
+        let platform = config.platform.clone().unwrap_or_default();
+        let pull_result = pull_oci_image(
+            &reference,
+            &platform,
+            microsandbox_image::PullPolicy::IfMissing,
+            config.lazy_layers,
+            None,
+            None,
+        )
+        .await?;
+
+        // The imported sandbox starts later, possibly from another process,
+        // so finish any estargz layer downloads now.
+        pull_result.fetches.wait().await?;
+
+        let cache_dir = crate::config::config().cache_dir();
+        if let Ok(cache) = microsandbox_image::GlobalCache::new(&cache_dir)
+            && let Ok(image_ref) = reference.parse::<microsandbox_image::Reference>()
+            && let Ok(Some(metadata)) = cache.read_image_metadata(&image_ref, &platform)
+            && let Err(e) = crate::image::Image::persist(&reference, metadata).await
+        {
+            tracing::warn!(error = %e, "failed to persist image metadata to database");
+        }
+
+        config.resolved_rootfs_layers = pull_result.layers;
+        pin = Some((reference, pull_result.manifest_digest.to_string()));
+    }
+
+    let rw_src = staging.join(RW_ENTRY);
+    let rw_dst = sandbox_dir.join(RW_ENTRY);
+    if rw_src.is_dir() {
+        tokio::fs::rename(&rw_src, &rw_dst).await?;
+    } else {
+        tokio::fs::create_dir_all(&rw_dst).await?;
+    }
+
+    Ok(pin)
+}
+
+/// Recreate bundled named volumes from the staging directory.
+///
+/// Either every volume is restored or none are: on failure, volumes already
+/// created by this call are removed so a retry does not trip over them.
+async fn restore_volumes(
+    db: &sea_orm::DatabaseConnection,
+    volumes_dir: &Path,
+    staging: &Path,
+    volumes: &[ArchiveVolume],
+) -> MicrosandboxResult<Vec<Volume>> {
+    let mut restored = Vec::with_capacity(volumes.len());
+    for volume in volumes {
+        match restore_volume(db, volumes_dir, staging, volume).await {
+            Ok(created) => restored.push(created),
+            Err(err) => {
+                discard_volumes(db, &restored).await;
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(restored)
+}
+
+/// Create one named volume and move its archived contents into place.
+async fn restore_volume(
+    db: &sea_orm::DatabaseConnection,
+    volumes_dir: &Path,
+    staging: &Path,
+    volume: &ArchiveVolume,
+) -> MicrosandboxResult<Volume> {
+    let created = Volume::create_in(
+        db,
+        volumes_dir,
+        VolumeConfig {
+            name: volume.name.clone(),
+            quota_mib: volume.quota_mib,
+            labels: volume.labels.clone(),
+        },
+    )
+    .await?;
+
+    let moved = async {
+        tokio::fs::remove_dir(created.path()).await?;
+        tokio::fs::rename(staging.join(&volume.name), created.path()).await
+    }
+    .await;
+    if let Err(err) = moved {
+        discard_volumes(db, std::slice::from_ref(&created)).await;
+        return Err(err.into());
+    }
+
+    Ok(created)
+}
+
+/// Best-effort removal of volumes created during a failed import.
+async fn discard_volumes(db: &sea_orm::DatabaseConnection, volumes: &[Volume]) {
+    for volume in volumes {
+        if let Err(e) = volume.remove_in(db).await {
+            tracing::warn!(volume = %volume.name(), error = %e, "failed to remove volume after failed import");
+        }
+    }
+}
+
+/// Insert the imported sandbox record and its image pin in one transaction.
+async fn insert_imported_record(
+    db: &sea_orm::DatabaseConnection,
+    config: &SandboxConfig,
+    pin: Option<(String, String)>,
+) -> MicrosandboxResult<()> {
+    let config = config.clone();
+    db.transaction::<_, (), MicrosandboxError>(|txn| {
+        Box::pin(async move {
+            let sandbox_id =
+                insert_sandbox_record_with_status(txn, &config, SandboxStatus::Stopped).await?;
+            if let Some((reference, manifest_digest)) = pin {
+                replace_oci_manifest_pin(txn, sandbox_id, &reference, &manifest_digest).await?;
+            }
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|err| match err {
+        sea_orm::TransactionError::Connection(db_err) => db_err.into(),
+        sea_orm::TransactionError::Transaction(err) => err,
+    })
+}
+
+/// Build a digest-pinned reference for the archived image, falling back to
+/// the recorded reference when no digest was pinned.
+fn pinned_reference(image: &ArchiveImage) -> MicrosandboxResult<String> {
+    pinned_image_reference(&image.reference, image.manifest_digest.as_deref())
+}
+
+/// Write the archive to a temporary file next to `dest` and rename it into
+/// place once complete.
+fn write_archive(
+    dest: &Path,
+    manifest: &SandboxArchiveManifest,
+    rw_dir: &Path,
+    layers: &[PathBuf],
+    volumes: &[(String, PathBuf)],
+) -> MicrosandboxResult<()> {
+    let parent = dest
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut tmp = tempfile::NamedTempFile::new_in(parent)?;
+
+    let encoder = zstd::Encoder::new(tmp.as_file_mut(), ZSTD_LEVEL)?;
+    let mut builder = tar::Builder::new(encoder);
+
+    let manifest_json = serde_json::to_vec_pretty(manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(manifest.exported_at.timestamp().max(0) as u64);
+    builder.append_data(&mut header, MANIFEST_ENTRY, manifest_json.as_slice())?;
+
+    if rw_dir.is_dir() {
+        append_tree(&mut builder, rw_dir, Path::new(RW_ENTRY))?;
+    }
+
+    for (index, layer) in layers.iter().enumerate() {
+        let name = Path::new(LAYERS_ENTRY).join(index.to_string());
+        append_tree(&mut builder, layer, &name)?;
+
+        // Lazy layers also need their table of contents and backing data.
+        for extension in ["index", "toc", "tar", "blob"] {
+            let sidecar = layer.with_extension(extension);
+            if sidecar.is_file() {
+                builder.append_path_with_name(&sidecar, name.with_extension(extension))?;
+            }
+        }
+    }
+
+    for (name, dir) in volumes {
+        append_tree(&mut builder, dir, &Path::new(VOLUMES_ENTRY).join(name))?;
+    }
+
+    builder.into_inner()?.finish()?;
+    tmp.persist(dest).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Unpack an archive into `dest` and return its manifest.
+fn read_archive(src: &Path, dest: &Path) -> MicrosandboxResult<SandboxArchiveManifest> {
+    let decoder = zstd::Decoder::new(File::open(src)?)?;
+    let mut archive = tar::Archive::new(decoder);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    archive.set_unpack_xattrs(true);
+    archive.unpack(dest)?;
+
+    let manifest_path = dest.join(MANIFEST_ENTRY);
+    if !manifest_path.is_file() {
+        return Err(MicrosandboxError::InvalidConfig(format!(
+            "not a sandbox archive (missing {MANIFEST_ENTRY}): {}",
+            src.display()
+        )));
+    }
+
+    let manifest: SandboxArchiveManifest = serde_json::from_slice(&std::fs::read(&manifest_path)?)?;
+    if manifest.version != ARCHIVE_FORMAT_VERSION {
+        return Err(MicrosandboxError::InvalidConfig(format!(
+            "unsupported sandbox archive version {} (expected {ARCHIVE_FORMAT_VERSION})",
+            manifest.version
+        )));
+    }
+
+    Ok(manifest)
+}
+
+/// Recursively append `src` to the archive under `name`, preserving
+/// extended attributes. Special files (sockets, FIFOs, devices) are skipped.
+fn append_tree<W: Write>(builder: &mut tar::Builder<W>, src: &Path, name: &Path) -> io::Result<()> {
+    let metadata = std::fs::symlink_metadata(src)?;
+    let file_type = metadata.file_type();
+    if !file_type.is_dir() && !file_type.is_file() && !file_type.is_symlink() {
+        tracing::warn!(path = %src.display(), "skipping special file in sandbox archive");
+        return Ok(());
+    }
+
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata_in_mode(&metadata, tar::HeaderMode::Complete);
+    append_xattrs(builder, src)?;
+
+    if file_type.is_dir() {
+        builder.append_data(&mut header, name, io::empty())?;
+
+        let mut entries = std::fs::read_dir(src)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            append_tree(builder, &entry.path(), &name.join(entry.file_name()))?;
+        }
+    } else if file_type.is_symlink() {
+        let target = std::fs::read_link(src)?;
+        builder.append_link(&mut header, name, target)?;
+    } else {
+        let mut file = File::open(src)?;
+        builder.append_data(&mut header, name, &mut file)?;
+    }
+
+    Ok(())
+}
+
+/// Append a PAX extended header carrying the extended attributes of `src`,
+/// if it has any. Must be followed by the entry it describes.
+fn append_xattrs<W: Write>(builder: &mut tar::Builder<W>, src: &Path) -> io::Result<()> {
+    let names = match xattr::list(src) {
+        Ok(names) => names,
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    let mut records = Vec::new();
+    for xattr_name in names {
+        let Some(value) = xattr::get(src, &xattr_name)? else {
+            continue;
+        };
+        let mut key = PAX_XATTR_PREFIX.to_vec();
+        key.extend_from_slice(xattr_name.as_encoded_bytes());
+        push_pax_record(&mut records, &key, &value);
+    }
+
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let mut header = tar::Header::new_ustar();
+    header.set_path("././@PaxHeader")?;
+    header.set_entry_type(tar::EntryType::XHeader);
+    header.set_size(records.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append(&header, records.as_slice())
+}
+
+/// Encode a single PAX record (`"<len> <key>=<value>\n"`), where `<len>`
+/// counts the whole record including its own digits.
+fn push_pax_record(out: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+    let base = key.len() + value.len() + 3;
+    let mut len = base + 1;
+    while len.to_string().len() + base != len {
+        len = len.to_string().len() + base;
+    }
+
+    out.extend_from_slice(len.to_string().as_bytes());
+    out.push(b' ');
+    out.extend_from_slice(key);
+    out.push(b'=');
+    out.extend_from_slice(value);
+    out.push(b'\n');
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use sea_orm::EntityTrait;
+
+    use super::{
+        ARCHIVE_FORMAT_VERSION, ArchiveImage, ArchiveVolume, SandboxArchiveManifest,
+        pinned_reference, push_pax_record, read_archive, restore_volumes, write_archive,
+    };
+    use crate::{
+        MicrosandboxError,
+        db::entity::volume as volume_entity,
+        sandbox::{RootfsSource, SandboxConfig},
+        volume::{Volume, VolumeConfig},
+    };
+
+    #[test]
+    fn test_push_pax_record_length_includes_own_digits() {
+        let mut out = Vec::new();
+        push_pax_record(&mut out, b"k", b"v");
+        assert_eq!(out, b"6 k=v\n");
+
+        let mut out = Vec::new();
+        let value = vec![b'x'; 5];
+        push_pax_record(&mut out, b"key", &value);
+        let text = String::from_utf8(out.clone()).unwrap();
+        let (len, _) = text.split_once(' ').unwrap();
+        assert_eq!(len.parse::<usize>().unwrap(), out.len());
+    }
+
+    #[test]
+    fn test_pinned_reference_uses_manifest_digest() {
+        let digest = format!("sha256:{}", "a".repeat(64));
+        let image = ArchiveImage {
+            reference: "docker.io/library/alpine:3.20".into(),
+            manifest_digest: Some(digest.clone()),
+        };
+        assert_eq!(
+            pinned_reference(&image).unwrap(),
+            format!("docker.io/library/alpine@{digest}")
+        );
+
+        let unpinned = ArchiveImage {
+            reference: "alpine:3.20".into(),
+            manifest_digest: None,
+        };
+        assert_eq!(pinned_reference(&unpinned).unwrap(), "alpine:3.20");
+    }
+
+    #[test]
+    fn test_archive_roundtrip_preserves_upper_layer_and_volumes() {
+        let temp = tempdir().unwrap();
+        let rw = temp.path().join("rw");
+        fs::create_dir_all(rw.join("home/user")).unwrap();
+        fs::write(rw.join("home/user/notes.txt"), b"hello").unwrap();
+        fs::write(rw.join(".wh.removed"), b"").unwrap();
+        std::os::unix::fs::symlink("home/user/notes.txt", rw.join("link")).unwrap();
+        let xattrs_supported = xattr::set(
+            rw.join("home/user/notes.txt"),
+            "user.containers.override_stat",
+            b"\x01stat",
+        )
+        .is_ok();
+
+        let volume = temp.path().join("cache");
+        fs::create_dir_all(&volume).unwrap();
+        fs::write(volume.join("data.bin"), b"\x00\x01").unwrap();
+
+        let manifest = SandboxArchiveManifest {
+            version: ARCHIVE_FORMAT_VERSION,
+            config: SandboxConfig {
+                name: "exported".into(),
+                image: RootfsSource::Oci("alpine".into()),
+                ..Default::default()
+            },
+            image: ArchiveImage {
+                reference: "alpine".into(),
+                manifest_digest: None,
+            },
+            layers: 0,
+            volumes: vec![ArchiveVolume {
+                name: "cache".into(),
+                quota_mib: Some(64),
+                labels: vec![("team".into(), "infra".into())],
+            }],
+            exported_at: chrono::Utc::now(),
+        };
+
+        let archive = temp.path().join("out.tar.zst");
+        write_archive(
+            &archive,
+            &manifest,
+            &rw,
+            &[],
+            &[("cache".into(), volume.clone())],
+        )
+        .unwrap();
+
+        let unpacked = temp.path().join("unpacked");
+        let decoded = read_archive(&archive, &unpacked).unwrap();
+        assert_eq!(decoded.config.name, "exported");
+        assert_eq!(decoded.volumes[0].quota_mib, Some(64));
+
+        let notes = unpacked.join("rw/home/user/notes.txt");
+        assert_eq!(fs::read(&notes).unwrap(), b"hello");
+        assert!(unpacked.join("rw/.wh.removed").is_file());
+        assert_eq!(
+            fs::read_link(unpacked.join("rw/link")).unwrap(),
+            std::path::Path::new("home/user/notes.txt")
+        );
+        assert_eq!(
+            fs::read(unpacked.join("volumes/cache/data.bin")).unwrap(),
+            b"\x00\x01"
+        );
+        if xattrs_supported {
+            assert_eq!(
+                xattr::get(&notes, "user.containers.override_stat")
+                    .unwrap()
+                    .as_deref(),
+                Some(b"\x01stat".as_slice())
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_archive_rejects_unknown_version() {
+        let temp = tempdir().unwrap();
+        let manifest = SandboxArchiveManifest {
+            version: ARCHIVE_FORMAT_VERSION + 1,
+            config: SandboxConfig::default(),
+            image: ArchiveImage {
+                reference: "alpine".into(),
+                manifest_digest: None,
+            },
+            layers: 0,
+            volumes: Vec::new(),
+            exported_at: chrono::Utc::now(),
+        };
+
+        let archive = temp.path().join("future.tar.zst");
+        write_archive(&archive, &manifest, &temp.path().join("missing"), &[], &[]).unwrap();
+
+        let err = read_archive(&archive, &temp.path().join("unpacked")).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("unsupported sandbox archive version")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_volumes_removes_created_volumes_on_failure() {
+        let temp = tempdir().unwrap();
+        let db = crate::db::connect_and_migrate(&temp.path().join("db"), 1)
+            .await
+            .unwrap();
+        let volumes_dir = temp.path().join("volumes");
+        let staging = temp.path().join("staging");
+        let archived = |name: &str| ArchiveVolume {
+            name: name.into(),
+            quota_mib: None,
+            labels: Vec::new(),
+        };
+        let volumes = [archived("first"), archived("second")];
+        let stage = || {
+            for volume in &volumes {
+                fs::create_dir_all(staging.join(&volume.name)).unwrap();
+                fs::write(staging.join(&volume.name).join("data"), &volume.name).unwrap();
+            }
+        };
+
+        // The second volume name is already taken, so the import fails
+        // after the first volume has been created.
+        let existing = Volume::create_in(
+            &db,
+            &volumes_dir,
+            VolumeConfig {
+                name: "second".into(),
+                quota_mib: None,
+                labels: Vec::new(),
+            },
+        )
+        .await
+        .unwrap();
+        stage();
+
+        let err = restore_volumes(&db, &volumes_dir, &staging, &volumes)
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, MicrosandboxError::VolumeAlreadyExists(ref name) if name == "second")
+        );
+
+        let names: Vec<_> = volume_entity::Entity::find()
+            .all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|model| model.name)
+            .collect();
+        assert_eq!(names, ["second"]);
+        assert!(!volumes_dir.join("first").exists());
+        assert!(existing.path().is_dir());
+
+        // Once the conflict is gone, a retry restores both volumes.
+        existing.remove_in(&db).await.unwrap();
+        stage();
+        let restored = restore_volumes(&db, &volumes_dir, &staging, &volumes)
+            .await
+            .unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(fs::read(volumes_dir.join("first/data")).unwrap(), b"first");
+        assert_eq!(
+            fs::read(volumes_dir.join("second/data")).unwrap(),
+            b"second"
+        );
+    }
+}
@@ -1,8 +1,10 @@
 //! Interactive attach types for terminal bridging with sandboxes.
 
+use std::path::PathBuf;
+
 use crate::MicrosandboxResult;
 
-use super::exec::Rlimit;
+use super::{env::RedactedEnv, exec::Rlimit};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -13,7 +15,9 @@ use super::exec::Rlimit;
 /// The host terminal is set to raw mode for the duration of the attach session.
 /// The guest process runs in a PTY, enabling terminal features (colors, line
 /// editing, Ctrl+C → SIGINT).
-#[derive(Debug, Clone, Default)]
+///
+/// The `Debug` output hides the values of sensitive env vars.
+#[derive(Clone, Default)]
 pub struct AttachOptions {
     /// Arguments.
     pub(crate) args: Vec<String>,
@@ -21,6 +25,15 @@ pub struct AttachOptions {
     /// Environment variables (merged with sandbox env).
     pub(crate) env: Vec<(String, String)>,
 
+    /// Keys in `env` whose values must not appear in logs.
+    pub(crate) sensitive_env: Vec<String>,
+
+    /// Env files read on the host when the session starts.
+    pub(crate) env_files: Vec<PathBuf>,
+
+    /// Start the session without inheriting the sandbox's environment.
+    pub(crate) clear_env: bool,
+
     /// Working directory (default: sandbox's workdir).
     pub(crate) cwd: Option<String>,
 
@@ -35,6 +48,9 @@ pub struct AttachOptions {
 
     /// Resource limits.
     pub(crate) rlimits: Vec<Rlimit>,
+
+    /// Keep the session running under this name after detaching.
+    pub(crate) persistent: Option<String>,
 }
 
 /// Builder for `AttachOptions`.
@@ -98,6 +114,41 @@ impl AttachOptionsBuilder {
         self
     }
 
+    /// Set an environment variable whose value must not appear in logs,
+    /// such as a token.
+    pub fn sensitive_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        if !self.options.sensitive_env.contains(&key) {
+            self.options.sensitive_env.push(key.clone());
+        }
+        self.options.env.push((key, value.into()));
+        self
+    }
+
+    /// Load environment variables from a file of `KEY=VALUE` lines on the
+    /// host. See [`ExecOptionsBuilder::env_file`](super::ExecOptionsBuilder::env_file).
+    pub fn env_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.env_files.push(path.into());
+        self
+    }
+
+    /// Whether the session inherits the sandbox's environment (default:
+    /// true). When false, it sees only the variables set on it.
+    pub fn inherit_env(mut self, inherit: bool) -> Self {
+        self.options.clear_env = !inherit;
+        self
+    }
+
+    /// Clear the environment: drop the variables set so far and don't
+    /// inherit the sandbox's. Variables set afterwards are still passed.
+    pub fn clear_env(mut self) -> Self {
+        self.options.env.clear();
+        self.options.sensitive_env.clear();
+        self.options.env_files.clear();
+        self.options.clear_env = true;
+        self
+    }
+
     /// Key sequence to detach from the session without stopping it.
     /// Uses Docker-style syntax: `"ctrl-]"` (default), `"ctrl-p,ctrl-q"`,
     /// or a single character like `"q"`.
@@ -131,6 +182,13 @@ impl AttachOptionsBuilder {
         self
     }
 
+    /// Keep the session running under `name` after detaching, so it can be
+    /// resumed with [`Sandbox::attach_session`](super::Sandbox::attach_session).
+    pub fn persistent(mut self, name: impl Into<String>) -> Self {
+        self.options.persistent = Some(name.into());
+        self
+    }
+
     /// Finalize the options. Called automatically when using the closure form.
     pub fn build(self) -> AttachOptions {
         self.options
@@ -207,6 +265,32 @@ impl DetachKeys {
     }
 }
 
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl std::fmt::Debug for AttachOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AttachOptions")
+            .field("args", &self.args)
+            .field(
+                "env",
+                &RedactedEnv {
+                    env: &self.env,
+                    sensitive: &self.sensitive_env,
+                },
+            )
+            .field("env_files", &self.env_files)
+            .field("clear_env", &self.clear_env)
+            .field("cwd", &self.cwd)
+            .field("user", &self.user)
+            .field("detach_keys", &self.detach_keys)
+            .field("rlimits", &self.rlimits)
+            .field("persistent", &self.persistent)
+            .finish()
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Tests
 //--------------------------------------------------------------------------------------------------
@@ -248,6 +332,18 @@ mod tests {
         assert_eq!(keys.sequence(), &[b'q']);
     }
 
+    #[test]
+    fn test_attach_options_debug_redacts_sensitive_env() {
+        let options = AttachOptionsBuilder::default()
+            .env("MODE", "dev")
+            .sensitive_env("TOKEN", "hunter2")
+            .build();
+
+        let debug = format!("{options:?}");
+        assert!(debug.contains("dev"));
+        assert!(!debug.contains("hunter2"));
+    }
+
     #[test]
     fn test_detach_keys_invalid() {
         assert!(DetachKeys::parse("ctrl-").is_err());
@@ -5,15 +5,29 @@ use microsandbox_image::RegistryAuth;
 use microsandbox_network::builder::{NetworkBuilder, SecretBuilder};
 #[cfg(feature = "net")]
 use microsandbox_network::config::{PortProtocol, PublishedPort};
+use microsandbox_runtime::{
+    policy::{HealthCheck, ResourceLimit},
+    sockets::{SocketDirection, SocketMount},
+};
 #[cfg(feature = "net")]
 use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
 
 use super::{
     config::SandboxConfig,
+    restart::RestartPolicy,
     types::{ImageBuilder, IntoImage, MountBuilder, Patch, PatchBuilder, RootfsSource},
 };
 use crate::{LogLevel, MicrosandboxResult, size::Mebibytes};
 
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Guest path of the socket set up by
+/// [`forward_ssh_agent`](SandboxBuilder::forward_ssh_agent).
+pub const SSH_AGENT_GUEST_PATH: &str = "/tmp/ssh-agent.sock";
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -71,10 +85,16 @@ impl SandboxBuilder {
     ///
     /// ```ignore
     /// .image_with(|i| i.disk("./ubuntu.qcow2").fstype("ext4"))
+    /// .image_with(|i| i.oci("python:3.12").platform("linux/amd64"))
     /// ```
     pub fn image_with(mut self, f: impl FnOnce(ImageBuilder) -> ImageBuilder) -> Self {
         match f(ImageBuilder::new()).build() {
-            Ok(rootfs) => self.config.image = rootfs,
+            Ok((rootfs, platform)) => {
+                self.config.image = rootfs;
+                if platform.is_some() {
+                    self.config.platform = platform;
+                }
+            }
             Err(e) => {
                 if self.build_error.is_none() {
                     self.build_error = Some(e);
@@ -172,6 +192,36 @@ impl SandboxBuilder {
         self
     }
 
+    /// Select a platform variant from a multi-platform OCI image
+    /// (e.g. `"linux/amd64"`, `"linux/arm/v7"`). Defaults to the host.
+    ///
+    /// Sandboxes can only run images whose architecture matches the host;
+    /// creating one from a foreign variant fails with
+    /// [`ImageError::UnsupportedPlatform`](microsandbox_image::ImageError::UnsupportedPlatform).
+    pub fn platform(mut self, platform: impl AsRef<str>) -> Self {
+        match platform.as_ref().parse() {
+            Ok(platform) => self.config.platform = Some(platform),
+            Err(e) => {
+                if self.build_error.is_none() {
+                    self.build_error = Some(crate::MicrosandboxError::Image(e));
+                }
+            }
+        }
+        self
+    }
+
+    /// Materialize OCI layers on first access instead of extracting them.
+    ///
+    /// Seekable estargz layers only need their table of contents before the
+    /// sandbox boots; the rest of the blob downloads in the background and
+    /// a file read waits for its chunk to arrive. Other layers are still
+    /// downloaded and indexed from their tar headers during the pull, so for
+    /// them this skips only the extraction.
+    pub fn lazy_layers(mut self, enabled: bool) -> Self {
+        self.config.lazy_layers = enabled;
+        self
+    }
+
     /// Disable all network access for this sandbox.
     ///
     /// Disables the network device entirely and sets the policy to
@@ -308,6 +358,39 @@ impl SandboxBuilder {
         self
     }
 
+    /// Set an environment variable whose value is sensitive, such as a token.
+    ///
+    /// The variable reaches the guest like any other, but its value is kept
+    /// out of the sandbox database, `msb inspect`, and the sandbox process's
+    /// command line.
+    pub fn sensitive_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        if !self.config.is_sensitive_env(&key) {
+            self.config.sensitive_env.push(key.clone());
+        }
+        self.config.env.push((key, value.into()));
+        self
+    }
+
+    /// Set a label on the sandbox. Labels can be matched with a
+    /// [`LabelSelector`](crate::LabelSelector) when listing sandboxes, and
+    /// override image labels with the same key.
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set multiple labels at once. See [`label`](Self::label).
+    pub fn labels(
+        mut self,
+        labels: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        for (k, v) in labels {
+            self.config.labels.insert(k.into(), v.into());
+        }
+        self
+    }
+
     /// Register a script that will be mounted at `/.msb/scripts/<name>` in
     /// the guest. Scripts are added to `PATH` so they can be invoked by name
     /// via [`exec`](super::Sandbox::exec).
@@ -340,6 +423,49 @@ impl SandboxBuilder {
         self
     }
 
+    /// Act when a sampled resource crosses a threshold.
+    ///
+    /// Limits are checked by the sandbox process against each metrics sample.
+    /// A limit with a `drain` or `kill` action ends the run with
+    /// `ResourceLimitExceeded`.
+    ///
+    /// ```ignore
+    /// .resource_limit("cpu=90/30s:drain".parse()?)
+    /// .resource_limit("net-egress=1GiB:kill".parse()?)
+    /// ```
+    pub fn resource_limit(mut self, limit: ResourceLimit) -> Self {
+        self.config.policy.resource_limits.push(limit);
+        self
+    }
+
+    /// Probe the sandbox periodically and record whether it is healthy.
+    ///
+    /// The result is exposed through [`SandboxHandle::health`](super::SandboxHandle::health)
+    /// and [`Sandbox::wait_healthy`](super::Sandbox::wait_healthy).
+    ///
+    /// ```ignore
+    /// .health_check(HealthCheck {
+    ///     retries: 5,
+    ///     on_unhealthy: LimitAction::Kill,
+    ///     ..HealthCheck::new(HealthProbe::Http { port: 8080, path: "/healthz".into() })
+    /// })
+    /// ```
+    pub fn health_check(mut self, check: HealthCheck) -> Self {
+        self.config.policy.health_check = Some(check);
+        self
+    }
+
+    /// Start the sandbox again after it stops, backing off exponentially
+    /// between attempts. Only detached sandboxes can have a restart policy.
+    ///
+    /// ```ignore
+    /// .restart_policy(RestartPolicy::OnFailure { max_retries: Some(5) })
+    /// ```
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.config.restart_policy = policy;
+        self
+    }
+
     /// Add a volume mount using a closure-based builder.
     ///
     /// ```ignore
@@ -364,6 +490,64 @@ impl SandboxBuilder {
         self
     }
 
+    /// Make a host Unix socket available at `guest_path` in the sandbox.
+    ///
+    /// Connections are tunneled through the agent channel, so this works
+    /// without guest networking. The host socket does not need to exist
+    /// until something in the guest connects.
+    ///
+    /// ```ignore
+    /// .socket_mount("/var/run/docker.sock", "/var/run/docker.sock")
+    /// ```
+    pub fn socket_mount(
+        mut self,
+        host_path: impl Into<PathBuf>,
+        guest_path: impl Into<String>,
+    ) -> Self {
+        self.config.socket_mounts.push(SocketMount {
+            host_path: host_path.into(),
+            guest_path: guest_path.into(),
+            direction: SocketDirection::HostToGuest,
+        });
+        self
+    }
+
+    /// Make a Unix socket served inside the sandbox available at `host_path`
+    /// on the host. See [`socket_mount`](Self::socket_mount).
+    pub fn expose_socket(
+        mut self,
+        guest_path: impl Into<String>,
+        host_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.config.socket_mounts.push(SocketMount {
+            host_path: host_path.into(),
+            guest_path: guest_path.into(),
+            direction: SocketDirection::GuestToHost,
+        });
+        self
+    }
+
+    /// Forward the host's ssh-agent into the sandbox.
+    ///
+    /// Mounts the socket named by the host's `SSH_AUTH_SOCK` at
+    /// [`SSH_AGENT_GUEST_PATH`] and points `SSH_AUTH_SOCK` there in the guest,
+    /// so `ssh` and `git` can use host keys without copying them in.
+    pub fn forward_ssh_agent(mut self) -> Self {
+        match std::env::var_os("SSH_AUTH_SOCK") {
+            Some(host_sock) if !host_sock.is_empty() => self
+                .socket_mount(host_sock, SSH_AGENT_GUEST_PATH)
+                .env("SSH_AUTH_SOCK", SSH_AGENT_GUEST_PATH),
+            _ => {
+                if self.build_error.is_none() {
+                    self.build_error = Some(crate::MicrosandboxError::InvalidConfig(
+                        "cannot forward ssh-agent: SSH_AUTH_SOCK is not set".into(),
+                    ));
+                }
+                self
+            }
+        }
+    }
+
     /// Apply rootfs patches using a builder closure.
     ///
     /// Patches are applied before VM start. Only works with OverlayFs and
@@ -464,6 +648,28 @@ impl SandboxBuilder {
             _ => {}
         }
 
+        let mut guest_sockets = std::collections::HashSet::new();
+        for mount in &self.config.socket_mounts {
+            if !mount.guest_path.starts_with('/') {
+                return Err(crate::MicrosandboxError::InvalidConfig(format!(
+                    "socket mount guest path must be absolute: {}",
+                    mount.guest_path
+                )));
+            }
+            if !mount.host_path.is_absolute() {
+                return Err(crate::MicrosandboxError::InvalidConfig(format!(
+                    "socket mount host path must be absolute: {}",
+                    mount.host_path.display()
+                )));
+            }
+            if !guest_sockets.insert(mount.guest_path.as_str()) {
+                return Err(crate::MicrosandboxError::InvalidConfig(format!(
+                    "duplicate socket mount guest path: {}",
+                    mount.guest_path
+                )));
+            }
+        }
+
         Ok(())
     }
 }
@@ -526,6 +732,23 @@ mod tests {
         assert!(config.replace_existing);
     }
 
+    #[test]
+    fn test_builder_rejects_relative_socket_mount_paths() {
+        let err = SandboxBuilder::new("test")
+            .image("alpine")
+            .socket_mount("/tmp/host.sock", "guest.sock")
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("guest path must be absolute"));
+
+        let err = SandboxBuilder::new("test")
+            .image("alpine")
+            .expose_socket("/run/app.sock", "app.sock")
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("host path must be absolute"));
+    }
+
     #[cfg(feature = "net")]
     #[test]
     fn test_builder_ports_are_repeatable() {
@@ -5,12 +5,15 @@ use std::{
     path::PathBuf,
 };
 
-use microsandbox_runtime::{logging::LogLevel, policy::SandboxPolicy};
+use microsandbox_runtime::{logging::LogLevel, policy::SandboxPolicy, sockets::SocketMount};
 use serde::{Deserialize, Serialize};
 
-use microsandbox_image::{ImageConfig, PullPolicy, RegistryAuth};
+use microsandbox_image::{ImageConfig, Platform, PullPolicy, RegistryAuth};
 
-use super::types::{Patch, RootfsSource, SecretsConfig, SshConfig, VolumeMount};
+use super::{
+    restart::RestartPolicy,
+    types::{Patch, RootfsSource, SecretsConfig, SshConfig, VolumeMount},
+};
 
 //--------------------------------------------------------------------------------------------------
 // Constants
@@ -36,7 +39,7 @@ fn default_log_level() -> Option<LogLevel> {
 ///
 /// All config structs derive `Default` for direct construction and
 /// `Serialize`/`Deserialize` for file-based configuration.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SandboxConfig {
     /// Unique sandbox name (required).
     pub name: String,
@@ -75,10 +78,21 @@ pub struct SandboxConfig {
     #[serde(default)]
     pub env: Vec<(String, String)>,
 
+    /// Keys in `env` whose values are sensitive.
+    ///
+    /// Their values are left out of the sandbox database and `msb inspect`,
+    /// and are stored in a file only the owner can read instead.
+    #[serde(default)]
+    pub sensitive_env: Vec<String>,
+
     /// Volume mounts.
     #[serde(default)]
     pub mounts: Vec<VolumeMount>,
 
+    /// Unix sockets shared between the host and the guest.
+    #[serde(default)]
+    pub socket_mounts: Vec<SocketMount>,
+
     /// Rootfs patches applied as overlay layers before VM start.
     #[serde(default)]
     pub patches: Vec<Patch>,
@@ -124,10 +138,25 @@ pub struct SandboxConfig {
     #[serde(default)]
     pub pull_policy: PullPolicy,
 
+    /// Platform variant to select from a multi-platform OCI index.
+    /// Default: the host platform.
+    #[serde(default)]
+    pub platform: Option<Platform>,
+
+    /// Materialize image layers on first access instead of extracting them
+    /// up front. Default: `false`.
+    #[serde(default)]
+    pub lazy_layers: bool,
+
     /// Sandbox lifecycle policy.
     #[serde(default)]
     pub policy: SandboxPolicy,
 
+    /// Whether a detached sandbox is started again after it stops.
+    /// Default: `no`.
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+
     /// Registry authentication for private OCI registries.
     ///
     /// Redacted (set to `None`) before serialization to database — credentials
@@ -152,6 +181,12 @@ pub struct SandboxConfig {
     /// without re-resolving a mutable OCI reference.
     #[serde(default)]
     pub(crate) resolved_rootfs_layers: Vec<PathBuf>,
+    /// Automatic restarts preceding the run being started, recorded on the
+    /// run by the sandbox process.
+    ///
+    /// This is an operation flag, not persisted sandbox state.
+    #[serde(skip)]
+    pub(crate) restart_count: u32,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -199,6 +234,31 @@ impl SandboxConfig {
         merged.extend(self.labels.drain());
         self.labels = merged;
     }
+
+    /// Whether the value of env var `key` is sensitive.
+    pub fn is_sensitive_env(&self, key: &str) -> bool {
+        self.sensitive_env.iter().any(|k| k == key)
+    }
+
+    /// The sensitive env vars and their values.
+    pub(crate) fn sensitive_env_values(&self) -> Vec<(String, String)> {
+        self.env
+            .iter()
+            .filter(|(k, _)| self.is_sensitive_env(k))
+            .cloned()
+            .collect()
+    }
+
+    /// Serialize for the sandbox database, leaving out sensitive env values.
+    pub(crate) fn to_persisted_json(&self) -> serde_json::Result<String> {
+        if self.sensitive_env.is_empty() {
+            return serde_json::to_string(self);
+        }
+
+        let mut persisted = self.clone();
+        persisted.env.retain(|(k, _)| !self.is_sensitive_env(k));
+        serde_json::to_string(&persisted)
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -255,7 +315,9 @@ impl Default for SandboxConfig {
             shell: None,
             scripts: HashMap::new(),
             env: Vec::new(),
+            sensitive_env: Vec::new(),
             mounts: Vec::new(),
+            socket_mounts: Vec::new(),
             patches: Vec::new(),
             #[cfg(feature = "net")]
             network: microsandbox_network::config::NetworkConfig::default(),
@@ -268,10 +330,14 @@ impl Default for SandboxConfig {
             labels: HashMap::new(),
             stop_signal: None,
             pull_policy: PullPolicy::default(),
+            platform: None,
+            lazy_layers: false,
             policy: SandboxPolicy::default(),
+            restart_policy: RestartPolicy::default(),
             registry_auth: None,
             replace_existing: false,
             resolved_rootfs_layers: Vec::new(),
+            restart_count: 0,
         }
     }
 }
@@ -0,0 +1,277 @@
+//! Environment variable helpers: env files and sensitive values.
+//!
+//! Sensitive env vars (see [`SandboxBuilder::sensitive_env`]) are kept out of
+//! the sandbox database. Their values live in a `0600` file in the sandbox
+//! directory so the sandbox can be started again, and are handed to the
+//! sandbox process through its environment rather than its command line.
+//!
+//! [`SandboxBuilder::sensitive_env`]: super::SandboxBuilder::sensitive_env
+
+use std::path::{Path, PathBuf};
+
+use crate::{MicrosandboxError, MicrosandboxResult};
+
+use super::{SandboxConfig, config};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// File in the sandbox directory holding the values of sensitive env vars.
+const SENSITIVE_ENV_FILE: &str = "sensitive-env.json";
+
+/// Shown in place of a sensitive value.
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `Debug` view of env var pairs that hides the values of sensitive keys.
+pub(crate) struct RedactedEnv<'a> {
+    pub(crate) env: &'a [(String, String)],
+    pub(crate) sensitive: &'a [String],
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl std::fmt::Debug for RedactedEnv<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.env.iter().map(|(key, value)| {
+                if self.sensitive.contains(key) {
+                    (key.as_str(), REDACTED)
+                } else {
+                    (key.as_str(), value.as_str())
+                }
+            }))
+            .finish()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Read env files and apply `overrides` on top of them.
+///
+/// Later files override earlier ones by key.
+pub(crate) async fn with_env_files(
+    files: &[PathBuf],
+    overrides: &[(String, String)],
+) -> MicrosandboxResult<Vec<(String, String)>> {
+    let mut merged = Vec::new();
+    for path in files {
+        let contents = tokio::fs::read_to_string(path).await.map_err(|e| {
+            MicrosandboxError::InvalidConfig(format!("env file {}: {e}", path.display()))
+        })?;
+        let vars = parse_env_file(&contents).map_err(|e| {
+            MicrosandboxError::InvalidConfig(format!("env file {}: {e}", path.display()))
+        })?;
+        merged = config::merge_env_pairs(&merged, &vars);
+    }
+
+    Ok(config::merge_env_pairs(&merged, overrides))
+}
+
+/// Parse `KEY=VALUE` lines.
+///
+/// Blank lines and `#` comments are skipped, an `export ` prefix is allowed,
+/// and a value wrapped in matching single or double quotes is unquoted.
+pub(crate) fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected KEY=VALUE", n + 1));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("line {}: invalid variable name '{key}'", n + 1));
+        }
+
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|&q| value.strip_prefix(q)?.strip_suffix(q))
+            .unwrap_or(value);
+        vars.push((key.to_string(), value.to_string()));
+    }
+
+    Ok(vars)
+}
+
+/// Write the values of the config's sensitive env vars to the sandbox
+/// directory, readable only by the owner.
+pub(crate) async fn save_sensitive_env(
+    config: &SandboxConfig,
+    sandbox_dir: &Path,
+) -> MicrosandboxResult<()> {
+    let values = config.sensitive_env_values();
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    tokio::fs::create_dir_all(sandbox_dir).await?;
+    let json = serde_json::to_vec(&values)?;
+    let path = sandbox_dir.join(SENSITIVE_ENV_FILE);
+    tokio::task::spawn_blocking(move || {
+        use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(&json)
+    })
+    .await
+    .map_err(|e| MicrosandboxError::Custom(format!("save sensitive env: {e}")))??;
+
+    Ok(())
+}
+
+/// Put the values of the config's sensitive env vars back after loading it
+/// from the database.
+pub(crate) async fn restore_sensitive_env(
+    config: &mut SandboxConfig,
+    sandbox_dir: &Path,
+) -> MicrosandboxResult<()> {
+    if config.sensitive_env.is_empty() {
+        return Ok(());
+    }
+
+    let path = sandbox_dir.join(SENSITIVE_ENV_FILE);
+    let values: Vec<(String, String)> = match tokio::fs::read(&path).await {
+        Ok(json) => serde_json::from_slice(&json)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::warn!(
+                sandbox = %config.name,
+                "sensitive env values are missing; starting without them"
+            );
+            Vec::new()
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    config.env = config::merge_env_pairs(&config.env, &values);
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_env_file() {
+        let vars = parse_env_file(
+            "# comment\n\nexport A=1\nB = two words \nC=\"quoted # value\"\nD='x=y'\nE=\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vars,
+            [
+                ("A".into(), "1".into()),
+                ("B".into(), "two words".into()),
+                ("C".into(), "quoted # value".into()),
+                ("D".into(), "x=y".into()),
+                ("E".into(), String::new()),
+            ]
+        );
+
+        assert_eq!(
+            parse_env_file("A=1\nnot a var\n").unwrap_err(),
+            "line 2: expected KEY=VALUE"
+        );
+        assert!(parse_env_file("=1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_env_files_are_overridden_by_explicit_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.env");
+        let second = dir.path().join("second.env");
+        std::fs::write(&first, "A=file\nB=first\n").unwrap();
+        std::fs::write(&second, "B=second\nC=file\n").unwrap();
+
+        let env = with_env_files(&[first, second], &[("A".into(), "explicit".into())])
+            .await
+            .unwrap();
+        assert_eq!(
+            env,
+            [
+                ("B".into(), "second".into()),
+                ("C".into(), "file".into()),
+                ("A".into(), "explicit".into()),
+            ]
+        );
+
+        let err = with_env_files(&[dir.path().join("missing.env")], &[])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("missing.env"));
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_env_round_trips_through_sandbox_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SandboxConfig {
+            name: "secrets".into(),
+            env: vec![
+                ("PLAIN".into(), "visible".into()),
+                ("TOKEN".into(), "hunter2".into()),
+            ],
+            sensitive_env: vec!["TOKEN".into()],
+            ..Default::default()
+        };
+
+        save_sensitive_env(&config, dir.path()).await.unwrap();
+        let path = dir.path().join(SENSITIVE_ENV_FILE);
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let json = config.to_persisted_json().unwrap();
+        assert!(!json.contains("hunter2"));
+        let mut loaded: SandboxConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.env, [("PLAIN".into(), "visible".into())]);
+
+        restore_sensitive_env(&mut loaded, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(loaded.env, config.env);
+    }
+
+    #[test]
+    fn test_redacted_env_debug_hides_sensitive_values() {
+        let env = [
+            ("PLAIN".to_string(), "visible".to_string()),
+            ("TOKEN".to_string(), "hunter2".to_string()),
+        ];
+        let sensitive = ["TOKEN".to_string()];
+        let debug = format!(
+            "{:?}",
+            RedactedEnv {
+                env: &env,
+                sensitive: &sensitive,
+            }
+        );
+        assert!(debug.contains("visible"));
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains(REDACTED));
+    }
+}
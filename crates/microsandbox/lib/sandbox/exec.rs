@@ -1,23 +1,30 @@
 //! Execution types for running commands inside sandboxes.
 
-use std::{sync::Arc, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use bytes::Bytes;
 use microsandbox_protocol::{
-    exec::{ExecSignal, ExecStdin},
+    exec::{ExecExited, ExecSignal, ExecStdin},
     message::{Message, MessageType},
 };
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
-use crate::{MicrosandboxResult, agent::AgentClient};
+use crate::{
+    MicrosandboxResult,
+    agent::{AgentClient, CreditReturn},
+};
+
+use super::env::RedactedEnv;
 
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
 
 /// Options for command execution (everything except the command itself).
-#[derive(Debug, Clone, Default)]
+///
+/// The `Debug` output hides the values of [`sensitive_env`](Self::sensitive_env).
+#[derive(Clone, Default)]
 pub struct ExecOptions {
     /// Arguments.
     pub args: Vec<String>,
@@ -31,6 +38,17 @@ pub struct ExecOptions {
     /// Environment variables (merged with sandbox env).
     pub env: Vec<(String, String)>,
 
+    /// Keys in `env` whose values must not appear in logs.
+    pub sensitive_env: Vec<String>,
+
+    /// Env files read on the host when the command starts. Variables in
+    /// `env` take precedence.
+    pub env_files: Vec<PathBuf>,
+
+    /// Start the command with only its own env vars, without inheriting the
+    /// sandbox's or the guest's environment.
+    pub clear_env: bool,
+
     /// Execution timeout. On expiry, SIGKILL is sent.
     pub timeout: Option<Duration>,
 
@@ -42,6 +60,9 @@ pub struct ExecOptions {
 
     /// Resource limits applied before exec via `setrlimit()`.
     pub rlimits: Vec<Rlimit>,
+
+    /// Keep the command running under this name when the client disconnects.
+    pub persistent: Option<String>,
 }
 
 /// Builder for [`ExecOptions`].
@@ -80,11 +101,36 @@ pub struct ExecOutput {
 /// Process exit status.
 #[derive(Debug, Clone, Copy)]
 pub struct ExitStatus {
-    /// Exit code.
+    /// Exit code. `-1` if the process was killed by a signal.
     pub code: i32,
 
-    /// Whether the process exited successfully (code == 0).
+    /// Whether the process exited successfully (code == 0, no signal).
     pub success: bool,
+
+    /// Signal that terminated the process, if any.
+    pub signal: Option<i32>,
+
+    /// Whether the process dumped core when it was killed.
+    pub core_dumped: bool,
+
+    /// Timing and resource usage, if the guest agent reported it.
+    pub usage: Option<ResourceUsage>,
+}
+
+/// Timing and resource usage of a finished command, as measured by the guest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Time from spawn to exit.
+    pub wall_time: Duration,
+
+    /// CPU time spent in user mode.
+    pub user_time: Duration,
+
+    /// CPU time spent in the kernel.
+    pub system_time: Duration,
+
+    /// Peak resident set size, in bytes.
+    pub max_rss_bytes: u64,
 }
 
 /// Handle to a streaming exec session.
@@ -100,6 +146,9 @@ pub struct ExecHandle {
 
     /// Bridge reference for sending signals/stdin.
     client: Arc<AgentClient>,
+
+    /// Returns flow-control credit as output is received.
+    credit: Option<CreditReturn>,
 }
 
 /// Events emitted by a streaming exec session.
@@ -119,8 +168,11 @@ pub enum ExecEvent {
 
     /// Process exited.
     Exited {
-        /// Exit code.
+        /// Exit code. `-1` if the process was killed by a signal.
         code: i32,
+
+        /// Full exit status, including signal and resource usage.
+        status: ExitStatus,
     },
 }
 
@@ -228,6 +280,46 @@ impl ExecOptionsBuilder {
         self
     }
 
+    /// Set an environment variable whose value must not appear in logs,
+    /// such as a token.
+    pub fn sensitive_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        if !self.options.sensitive_env.contains(&key) {
+            self.options.sensitive_env.push(key.clone());
+        }
+        self.options.env.push((key, value.into()));
+        self
+    }
+
+    /// Load environment variables from a file of `KEY=VALUE` lines on the
+    /// host. Blank lines, `#` comments and an `export ` prefix are allowed.
+    ///
+    /// The file is read when the command starts. Variables set with
+    /// [`env`](Self::env) take precedence over file contents, and later
+    /// files over earlier ones.
+    pub fn env_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.env_files.push(path.into());
+        self
+    }
+
+    /// Whether the command inherits the sandbox's environment (default:
+    /// true). When false, the command sees only the variables set on it.
+    pub fn inherit_env(mut self, inherit: bool) -> Self {
+        self.options.clear_env = !inherit;
+        self
+    }
+
+    /// Clear the environment: drop the variables set so far and don't
+    /// inherit the sandbox's, like [`std::process::Command::env_clear`].
+    /// Variables set afterwards are still passed.
+    pub fn clear_env(mut self) -> Self {
+        self.options.env.clear();
+        self.options.sensitive_env.clear();
+        self.options.env_files.clear();
+        self.options.clear_env = true;
+        self
+    }
+
     /// Kill the process with SIGKILL if it hasn't exited within this duration.
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.options.timeout = Some(timeout);
@@ -279,6 +371,16 @@ impl ExecOptionsBuilder {
         self
     }
 
+    /// Keep the command running under `name` if this client disconnects.
+    ///
+    /// Its recent output is buffered in the guest, and the session can be
+    /// resumed with [`Sandbox::reattach`](super::Sandbox::reattach). Names
+    /// are unique per sandbox while the session runs.
+    pub fn persistent(mut self, name: impl Into<String>) -> Self {
+        self.options.persistent = Some(name.into());
+        self
+    }
+
     /// Finalize the options. Called automatically when using the closure form.
     pub fn build(self) -> ExecOptions {
         self.options
@@ -286,11 +388,16 @@ impl ExecOptionsBuilder {
 }
 
 impl ExecOutput {
-    /// Exit code and success flag of the completed process.
+    /// Exit status of the completed process.
     pub fn status(&self) -> ExitStatus {
         self.status
     }
 
+    /// Timing and resource usage of the completed process, if reported.
+    pub fn usage(&self) -> Option<ResourceUsage> {
+        self.status.usage
+    }
+
     /// Get stdout as a UTF-8 string.
     pub fn stdout(&self) -> Result<String, std::string::FromUtf8Error> {
         String::from_utf8(self.stdout.to_vec())
@@ -312,19 +419,34 @@ impl ExecOutput {
     }
 }
 
+impl ExitStatus {
+    /// The exit code as a shell would report it: `128 + signal` when the
+    /// process was killed by a signal.
+    pub fn shell_code(&self) -> i32 {
+        match self.signal {
+            Some(signal) => 128 + signal,
+            None => self.code,
+        }
+    }
+}
+
 impl ExecHandle {
-    /// Create a new exec handle.
+    /// Create a new exec handle for a session opened with a flow-control
+    /// `window`, if any.
     pub(crate) fn new(
         id: u32,
         events: mpsc::UnboundedReceiver<ExecEvent>,
         stdin: Option<ExecSink>,
         client: Arc<AgentClient>,
+        window: Option<u32>,
     ) -> Self {
+        let credit = window.map(|window| CreditReturn::new(Arc::clone(&client), id, window));
         Self {
             id,
             events,
             stdin,
             client,
+            credit,
         }
     }
 
@@ -335,9 +457,19 @@ impl ExecHandle {
 
     /// Receive the next exec event.
     ///
-    /// Returns `None` when the session has ended.
+    /// Returns `None` when the session has ended. Output is flow-controlled:
+    /// the process is paused once a bounded amount of output is waiting to
+    /// be received, and resumes as events are taken.
     pub async fn recv(&mut self) -> Option<ExecEvent> {
-        self.events.recv().await
+        let event = self.events.recv().await?;
+        if let (ExecEvent::Stdout(data) | ExecEvent::Stderr(data), Some(credit)) =
+            (&event, &mut self.credit)
+        {
+            // If the credit can't be sent the connection is gone and the
+            // session ends anyway.
+            let _ = credit.consumed(data.len()).await;
+        }
+        Some(event)
     }
 
     /// Take the stdin sink (if `StdinMode::Pipe` was used).
@@ -349,12 +481,9 @@ impl ExecHandle {
 
     /// Wait for the command to complete and return the exit status.
     pub async fn wait(&mut self) -> MicrosandboxResult<ExitStatus> {
-        while let Some(event) = self.events.recv().await {
-            if let ExecEvent::Exited { code } = event {
-                return Ok(ExitStatus {
-                    code,
-                    success: code == 0,
-                });
+        while let Some(event) = self.recv().await {
+            if let ExecEvent::Exited { status, .. } = event {
+                return Ok(status);
             }
         }
 
@@ -367,9 +496,9 @@ impl ExecHandle {
     pub async fn collect(&mut self) -> MicrosandboxResult<ExecOutput> {
         let mut stdout = Vec::new();
         let mut stderr = Vec::new();
-        let mut exit_code: Option<i32> = None;
+        let mut exit_status: Option<ExitStatus> = None;
 
-        while let Some(event) = self.events.recv().await {
+        while let Some(event) = self.recv().await {
             match event {
                 ExecEvent::Started { pid: _ } => {}
                 ExecEvent::Stdout(data) => {
@@ -378,22 +507,19 @@ impl ExecHandle {
                 ExecEvent::Stderr(data) => {
                     stderr.extend_from_slice(&data);
                 }
-                ExecEvent::Exited { code } => {
-                    exit_code = Some(code);
+                ExecEvent::Exited { status, .. } => {
+                    exit_status = Some(status);
                     break;
                 }
             }
         }
 
-        let code = exit_code.ok_or_else(|| {
+        let status = exit_status.ok_or_else(|| {
             crate::MicrosandboxError::Runtime("exec session ended without exit event".into())
         })?;
 
         Ok(ExecOutput {
-            status: ExitStatus {
-                code,
-                success: code == 0,
-            },
+            status,
             stdout: Bytes::from(stdout),
             stderr: Bytes::from(stderr),
         })
@@ -464,6 +590,47 @@ impl RlimitResource {
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
 
+impl std::fmt::Debug for ExecOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExecOptions")
+            .field("args", &self.args)
+            .field("cwd", &self.cwd)
+            .field("user", &self.user)
+            .field(
+                "env",
+                &RedactedEnv {
+                    env: &self.env,
+                    sensitive: &self.sensitive_env,
+                },
+            )
+            .field("env_files", &self.env_files)
+            .field("clear_env", &self.clear_env)
+            .field("timeout", &self.timeout)
+            .field("stdin", &self.stdin)
+            .field("tty", &self.tty)
+            .field("rlimits", &self.rlimits)
+            .field("persistent", &self.persistent)
+            .finish()
+    }
+}
+
+impl From<&ExecExited> for ExitStatus {
+    fn from(exited: &ExecExited) -> Self {
+        Self {
+            code: exited.code,
+            success: exited.code == 0 && exited.signal.is_none(),
+            signal: exited.signal,
+            core_dumped: exited.core_dumped,
+            usage: exited.usage.map(|usage| ResourceUsage {
+                wall_time: Duration::from_micros(usage.wall_time_us),
+                user_time: Duration::from_micros(usage.user_time_us),
+                system_time: Duration::from_micros(usage.sys_time_us),
+                max_rss_bytes: usage.max_rss_kib.saturating_mul(1024),
+            }),
+        }
+    }
+}
+
 /// String to `RlimitResource` conversion.
 ///
 /// Accepts: `"nofile"`, `"as"`, `"nproc"`, `"cpu"`, etc. (case-insensitive).
@@ -0,0 +1,569 @@
+//! OpenMetrics exporter for persisted sandbox metrics.
+//!
+//! [`render_openmetrics`] encodes the latest runtime samples, sandbox status and
+//! run termination history as OpenMetrics text. [`MetricsServer`] serves that
+//! text over HTTP at `/metrics` so Prometheus-compatible scrapers can collect it.
+
+use std::{collections::BTreeMap, fmt::Write as _, future::Future, net::SocketAddr};
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use crate::{
+    MicrosandboxResult,
+    db::entity::{run as run_entity, sandbox as sandbox_entity},
+};
+
+use super::{SandboxConfig, SandboxMetrics, SandboxStatus};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Content type of the OpenMetrics text exposition format.
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Path the metrics server exposes samples on.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Upper bound on the size of an HTTP request head.
+const MAX_REQUEST_HEAD: usize = 8192;
+
+/// Every sandbox status, in the order it is emitted in the status state set.
+const ALL_STATUSES: [SandboxStatus; 5] = [
+    SandboxStatus::Running,
+    SandboxStatus::Draining,
+    SandboxStatus::Paused,
+    SandboxStatus::Stopped,
+    SandboxStatus::Crashed,
+];
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// An HTTP server exposing sandbox metrics in OpenMetrics text format.
+///
+/// ```rust,no_run
+/// # async fn example() -> microsandbox::MicrosandboxResult<()> {
+/// use microsandbox::sandbox::MetricsServer;
+///
+/// let server = MetricsServer::bind("127.0.0.1:9464").await?;
+/// println!("scrape http://{}/metrics", server.local_addr()?);
+/// server.serve().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MetricsServer {
+    listener: TcpListener,
+}
+
+/// Everything the encoder needs to know about one sandbox.
+#[derive(Debug, Default)]
+struct SandboxSample {
+    name: String,
+    status: Option<SandboxStatus>,
+    labels: BTreeMap<String, String>,
+    metrics: Option<SandboxMetrics>,
+    runs: u64,
+    terminations: BTreeMap<String, u64>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl MetricsServer {
+    /// Bind the metrics server to a socket address.
+    pub async fn bind(addr: impl ToSocketAddrs) -> MicrosandboxResult<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self { listener })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> MicrosandboxResult<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve scrapes until the process exits.
+    pub async fn serve(self) -> MicrosandboxResult<()> {
+        self.serve_with_shutdown(std::future::pending()).await
+    }
+
+    /// Serve scrapes until `shutdown` resolves.
+    pub async fn serve_with_shutdown(
+        self,
+        shutdown: impl Future<Output = ()>,
+    ) -> MicrosandboxResult<()> {
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => return Ok(()),
+                accepted = self.listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!(error = %e, "metrics server: accept failed");
+                            continue;
+                        }
+                    };
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream).await {
+                            tracing::debug!(%peer, error = %e, "metrics server: connection failed");
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Render metrics for every sandbox as OpenMetrics text.
+///
+/// Running sandboxes report their latest resource sample. All sandboxes report
+/// their status and a count of terminated runs by termination reason. User and
+/// image labels are attached to every sample as `label_<name>`.
+pub async fn render_openmetrics() -> MicrosandboxResult<String> {
+    let samples = collect_samples().await?;
+    Ok(encode(&samples))
+}
+
+async fn collect_samples() -> MicrosandboxResult<Vec<SandboxSample>> {
+    let db = crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+    let sandboxes = sandbox_entity::Entity::find()
+        .order_by_asc(sandbox_entity::Column::Name)
+        .all(db)
+        .await?;
+
+    let mut runs_by_sandbox: BTreeMap<i32, Vec<run_entity::Model>> = BTreeMap::new();
+    for run in run_entity::Entity::find()
+        .filter(run_entity::Column::SandboxId.is_in(sandboxes.iter().map(|s| s.id)))
+        .all(db)
+        .await?
+    {
+        runs_by_sandbox.entry(run.sandbox_id).or_default().push(run);
+    }
+
+    let mut samples = Vec::with_capacity(sandboxes.len());
+    for sandbox in sandboxes {
+        let sandbox = if matches!(
+            sandbox.status,
+            SandboxStatus::Running | SandboxStatus::Draining
+        ) {
+            super::reconcile_sandbox_runtime_state(db, sandbox).await?
+        } else {
+            sandbox
+        };
+
+        let config: Option<SandboxConfig> = serde_json::from_str(&sandbox.config).ok();
+        let metrics = match (&config, sandbox.status) {
+            (Some(config), SandboxStatus::Running | SandboxStatus::Draining) => {
+                super::metrics::metrics_for_sandbox(
+                    db,
+                    sandbox.id,
+                    super::metrics::memory_limit_bytes(config),
+                )
+                .await
+                .ok()
+            }
+            _ => None,
+        };
+
+        let runs = runs_by_sandbox.remove(&sandbox.id).unwrap_or_default();
+        let mut terminations = BTreeMap::new();
+        for reason in runs.iter().filter_map(|run| run.termination_reason) {
+            *terminations.entry(reason.to_string()).or_default() += 1;
+        }
+
+        samples.push(SandboxSample {
+            name: sandbox.name,
+            status: Some(sandbox.status),
+            labels: config
+                .map(|c| c.labels.into_iter().collect())
+                .unwrap_or_default(),
+            metrics,
+            runs: runs.len() as u64,
+            terminations,
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Encode samples in OpenMetrics text format.
+fn encode(samples: &[SandboxSample]) -> String {
+    let mut out = String::new();
+
+    family(
+        &mut out,
+        "microsandbox_sandbox_status",
+        "stateset",
+        "Current sandbox status.",
+    );
+    for sample in samples {
+        for status in ALL_STATUSES {
+            let state = format!("{status:?}");
+            let value = u8::from(sample.status == Some(status));
+            line(
+                &mut out,
+                "microsandbox_sandbox_status",
+                sample,
+                &[("microsandbox_sandbox_status", &state)],
+                value,
+            );
+        }
+    }
+
+    gauge(
+        &mut out,
+        samples,
+        "microsandbox_sandbox_cpu_percent",
+        "CPU usage as a percentage across all host CPUs.",
+        |m| m.cpu_percent.to_string(),
+    );
+    gauge(
+        &mut out,
+        samples,
+        "microsandbox_sandbox_memory_bytes",
+        "Resident memory usage in bytes.",
+        |m| m.memory_bytes.to_string(),
+    );
+    gauge(
+        &mut out,
+        samples,
+        "microsandbox_sandbox_memory_limit_bytes",
+        "Configured guest memory limit in bytes.",
+        |m| m.memory_limit_bytes.to_string(),
+    );
+    gauge(
+        &mut out,
+        samples,
+        "microsandbox_sandbox_uptime_seconds",
+        "Sandbox uptime at the moment the sample was taken.",
+        |m| m.uptime.as_secs_f64().to_string(),
+    );
+    counter(
+        &mut out,
+        samples,
+        "microsandbox_sandbox_disk_read_bytes",
+        "Cumulative disk bytes read by the sandbox process.",
+        |m| m.disk_read_bytes,
+    );
+    counter(
+        &mut out,
+        samples,
+        "microsandbox_sandbox_disk_write_bytes",
+        "Cumulative disk bytes written by the sandbox process.",
+        |m| m.disk_write_bytes,
+    );
+    counter(
+        &mut out,
+        samples,
+        "microsandbox_sandbox_network_receive_bytes",
+        "Cumulative network bytes delivered from the runtime to the guest.",
+        |m| m.net_rx_bytes,
+    );
+    counter(
+        &mut out,
+        samples,
+        "microsandbox_sandbox_network_transmit_bytes",
+        "Cumulative network bytes transmitted from the guest into the runtime.",
+        |m| m.net_tx_bytes,
+    );
+
+    family(
+        &mut out,
+        "microsandbox_sandbox_runs",
+        "counter",
+        "Number of recorded runs of the sandbox.",
+    );
+    for sample in samples {
+        line(
+            &mut out,
+            "microsandbox_sandbox_runs_total",
+            sample,
+            &[],
+            sample.runs,
+        );
+    }
+
+    family(
+        &mut out,
+        "microsandbox_sandbox_terminations",
+        "counter",
+        "Number of terminated runs by termination reason.",
+    );
+    for sample in samples {
+        for (reason, count) in &sample.terminations {
+            line(
+                &mut out,
+                "microsandbox_sandbox_terminations_total",
+                sample,
+                &[("reason", reason)],
+                count,
+            );
+        }
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    let _ = writeln!(out, "# HELP {name} {help}");
+}
+
+fn gauge(
+    out: &mut String,
+    samples: &[SandboxSample],
+    name: &str,
+    help: &str,
+    value: impl Fn(&SandboxMetrics) -> String,
+) {
+    family(out, name, "gauge", help);
+    for sample in samples {
+        if let Some(metrics) = &sample.metrics {
+            line(out, name, sample, &[], value(metrics));
+        }
+    }
+}
+
+fn counter(
+    out: &mut String,
+    samples: &[SandboxSample],
+    name: &str,
+    help: &str,
+    value: impl Fn(&SandboxMetrics) -> u64,
+) {
+    family(out, name, "counter", help);
+    let total = format!("{name}_total");
+    for sample in samples {
+        if let Some(metrics) = &sample.metrics {
+            line(out, &total, sample, &[], value(metrics));
+        }
+    }
+}
+
+/// Write one sample line with the sandbox name, user labels and `extra` labels.
+fn line(
+    out: &mut String,
+    name: &str,
+    sample: &SandboxSample,
+    extra: &[(&str, &str)],
+    value: impl std::fmt::Display,
+) {
+    let _ = write!(out, "{name}{{sandbox=\"{}\"", escape(&sample.name));
+    for (key, value) in extra {
+        let _ = write!(out, ",{key}=\"{}\"", escape(value));
+    }
+
+    let mut seen = Vec::new();
+    for (key, value) in &sample.labels {
+        let key = label_name(key);
+        // Distinct user labels can sanitize to the same name; keep the first.
+        if seen.contains(&key) {
+            continue;
+        }
+        let _ = write!(out, ",{key}=\"{}\"", escape(value));
+        seen.push(key);
+    }
+
+    let _ = writeln!(out, "}} {value}");
+}
+
+/// Map a user label key to a valid metric label name.
+fn label_name(key: &str) -> String {
+    let mut name = String::with_capacity(key.len() + 6);
+    name.push_str("label_");
+    name.extend(
+        key.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }),
+    );
+    name
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Serve a single HTTP/1.1 request and close the connection.
+async fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        if buf.len() >= MAX_REQUEST_HEAD {
+            return respond(&mut stream, "431 Request Header Fields Too Large", "", "").await;
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&buf);
+    let mut parts = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+
+    if path != METRICS_PATH {
+        return respond(&mut stream, "404 Not Found", "text/plain", "not found\n").await;
+    }
+    if method != "GET" && method != "HEAD" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n",
+        )
+        .await;
+    }
+
+    match render_openmetrics().await {
+        Ok(body) if method == "HEAD" => {
+            respond_head(&mut stream, "200 OK", OPENMETRICS_CONTENT_TYPE, body.len()).await
+        }
+        Ok(body) => respond(&mut stream, "200 OK", OPENMETRICS_CONTENT_TYPE, &body).await,
+        Err(e) => {
+            tracing::warn!(error = %e, "metrics server: failed to collect metrics");
+            let body = format!("failed to collect metrics: {e}\n");
+            respond(
+                &mut stream,
+                "500 Internal Server Error",
+                "text/plain",
+                &body,
+            )
+            .await
+        }
+    }
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    respond_head(stream, status, content_type, body.len()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn respond_head(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    content_length: usize,
+) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {status}\r\nContent-Length: {content_length}\r\n");
+    if !content_type.is_empty() {
+        head.push_str(&format!("Content-Type: {content_type}\r\n"));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    stream.write_all(head.as_bytes()).await
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn running_sample() -> SandboxSample {
+        SandboxSample {
+            name: "web".into(),
+            status: Some(SandboxStatus::Running),
+            labels: BTreeMap::from([("app.tier".into(), "front \"end\"".into())]),
+            metrics: Some(SandboxMetrics {
+                cpu_percent: 12.5,
+                memory_bytes: 1024,
+                memory_limit_bytes: 4096,
+                disk_read_bytes: 1,
+                disk_write_bytes: 2,
+                net_rx_bytes: 3,
+                net_tx_bytes: 4,
+                uptime: Duration::from_secs(90),
+                timestamp: chrono::Utc::now(),
+            }),
+            runs: 3,
+            terminations: BTreeMap::from([("Completed".into(), 1), ("Failed".into(), 1)]),
+        }
+    }
+
+    #[test]
+    fn test_encode_running_sandbox() {
+        let out = encode(&[running_sample()]);
+        let user = r#"label_app_tier="front \"end\"""#;
+        let labels = format!("sandbox=\"web\",{user}");
+
+        assert!(out.contains(&format!(
+            "microsandbox_sandbox_status{{sandbox=\"web\",microsandbox_sandbox_status=\"Running\",{user}}} 1\n"
+        )));
+        assert!(out.contains("microsandbox_sandbox_status=\"Stopped\""));
+        assert!(out.contains(&format!(
+            "microsandbox_sandbox_cpu_percent{{{labels}}} 12.5\n"
+        )));
+        assert!(out.contains(&format!(
+            "microsandbox_sandbox_network_transmit_bytes_total{{{labels}}} 4\n"
+        )));
+        assert!(out.contains(&format!(
+            "microsandbox_sandbox_uptime_seconds{{{labels}}} 90\n"
+        )));
+        assert!(out.contains(&format!("microsandbox_sandbox_runs_total{{{labels}}} 3\n")));
+        assert!(out.contains(&format!(
+            "microsandbox_sandbox_terminations_total{{sandbox=\"web\",reason=\"Failed\",{user}}} 1\n"
+        )));
+        assert!(out.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_encode_stopped_sandbox_omits_resource_samples() {
+        let sample = SandboxSample {
+            name: "idle".into(),
+            status: Some(SandboxStatus::Stopped),
+            runs: 1,
+            ..Default::default()
+        };
+        let out = encode(&[sample]);
+
+        assert!(out.contains(
+            "microsandbox_sandbox_status{sandbox=\"idle\",microsandbox_sandbox_status=\"Stopped\"} 1\n"
+        ));
+        assert!(out.contains("# TYPE microsandbox_sandbox_cpu_percent gauge\n"));
+        assert!(!out.contains("microsandbox_sandbox_cpu_percent{"));
+        assert!(out.contains("microsandbox_sandbox_runs_total{sandbox=\"idle\"} 1\n"));
+    }
+
+    #[test]
+    fn test_label_name_sanitizes_keys() {
+        assert_eq!(
+            label_name("app.kubernetes.io/name"),
+            "label_app_kubernetes_io_name"
+        );
+        assert_eq!(escape("a\\b\nc"), "a\\\\b\\nc");
+    }
+}
@@ -0,0 +1,498 @@
+//! TCP port forwarding over the agent channel.
+//!
+//! Connections are tunneled through agentd (see
+//! [`microsandbox_protocol::tunnel`]), so forwarding works for sandboxes
+//! without networking and does not depend on published ports.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use microsandbox_protocol::{
+    flow::{Credit, DEFAULT_WINDOW},
+    message::{Message, MessageType},
+    tunnel::{
+        TUNNEL_CHUNK_SIZE, TunnelAccept, TunnelBind, TunnelClose, TunnelData, TunnelEndpoint,
+        TunnelListen, TunnelOpen,
+    },
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc,
+    task::{JoinHandle, JoinSet},
+};
+
+use crate::{
+    MicrosandboxError, MicrosandboxResult,
+    agent::{AgentClient, CreditReturn},
+};
+
+use super::Sandbox;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A running port forward between a host address and a guest port.
+///
+/// Dropping the handle stops the forward and closes its connections.
+pub struct PortForward {
+    host_addr: SocketAddr,
+    guest_port: u16,
+    task: JoinHandle<MicrosandboxResult<()>>,
+}
+
+/// Sends `core.tunnel.close` for a tunnel or listener that is abandoned
+/// before the guest closed it.
+struct CloseGuard {
+    client: Arc<AgentClient>,
+    id: u32,
+    armed: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Sandbox {
+    /// Forward connections to `host_addr` into the sandbox's `guest_port`.
+    ///
+    /// Listens on `host_addr` (port 0 picks a free port; see
+    /// [`PortForward::host_addr`]) and, for each connection, connects to
+    /// `127.0.0.1:guest_port` inside the guest.
+    ///
+    /// ```ignore
+    /// let fwd = sb.forward_port("127.0.0.1:8080", 80).await?;
+    /// ```
+    pub async fn forward_port(
+        &self,
+        host_addr: impl ToSocketAddrs,
+        guest_port: u16,
+    ) -> MicrosandboxResult<PortForward> {
+        let listener = TcpListener::bind(host_addr)
+            .await
+            .map_err(|e| MicrosandboxError::PortForward(format!("listen: {e}")))?;
+        let host_addr = listener.local_addr()?;
+        let client = Arc::clone(&self.client);
+        let task = tokio::spawn(forward_loop(client, listener, guest_port));
+
+        Ok(PortForward {
+            host_addr,
+            guest_port,
+            task,
+        })
+    }
+
+    /// Forward connections to the sandbox's `guest_port` out to `host_addr`.
+    ///
+    /// agentd listens on `127.0.0.1:guest_port` inside the guest and, for
+    /// each connection, the host connects to `host_addr`. This exposes a host
+    /// service to the sandbox.
+    ///
+    /// ```ignore
+    /// let fwd = sb.reverse_forward_port(5432, "127.0.0.1:5432").await?;
+    /// ```
+    pub async fn reverse_forward_port(
+        &self,
+        guest_port: u16,
+        host_addr: impl ToSocketAddrs,
+    ) -> MicrosandboxResult<PortForward> {
+        let host_addr = tokio::net::lookup_host(host_addr)
+            .await?
+            .next()
+            .ok_or_else(|| MicrosandboxError::PortForward("host address not found".into()))?;
+
+        let id = self.client.next_id();
+        let mut rx = self.client.subscribe(id).await;
+        let listen = TunnelListen {
+            endpoint: TunnelEndpoint::Tcp { port: guest_port },
+        };
+        self.client
+            .send(&Message::with_payload(
+                MessageType::TunnelListen,
+                id,
+                &listen,
+            )?)
+            .await?;
+        wait_opened(&mut rx).await?;
+
+        let guard = CloseGuard::new(Arc::clone(&self.client), id);
+        let client = Arc::clone(&self.client);
+        let task = tokio::spawn(reverse_loop(client, guard, rx, host_addr));
+
+        Ok(PortForward {
+            host_addr,
+            guest_port,
+            task,
+        })
+    }
+}
+
+impl PortForward {
+    /// The host side of the forward: the listening address for
+    /// [`forward_port`](Sandbox::forward_port), the target address for
+    /// [`reverse_forward_port`](Sandbox::reverse_forward_port).
+    pub fn host_addr(&self) -> SocketAddr {
+        self.host_addr
+    }
+
+    /// The guest port.
+    pub fn guest_port(&self) -> u16 {
+        self.guest_port
+    }
+
+    /// Wait until the forward stops on its own, such as when the sandbox
+    /// stops or the listener fails.
+    pub async fn wait(&mut self) -> MicrosandboxResult<()> {
+        match (&mut self.task).await {
+            Ok(result) => result,
+            Err(e) if e.is_cancelled() => Ok(()),
+            Err(e) => Err(MicrosandboxError::PortForward(e.to_string())),
+        }
+    }
+
+    /// Stop the forward and close its connections.
+    pub fn close(self) {}
+}
+
+impl CloseGuard {
+    fn new(client: Arc<AgentClient>, id: u32) -> Self {
+        Self {
+            client,
+            id,
+            armed: true,
+        }
+    }
+
+    /// The guest closed the tunnel itself.
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Drop for PortForward {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for CloseGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let client = Arc::clone(&self.client);
+        let id = self.id;
+        runtime.spawn(async move {
+            if let Ok(msg) =
+                Message::with_payload(MessageType::TunnelClose, id, &TunnelClose::default())
+            {
+                let _ = client.send(&msg).await;
+            }
+        });
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Accept host connections and tunnel each one to the guest port.
+async fn forward_loop(
+    client: Arc<AgentClient>,
+    listener: TcpListener,
+    guest_port: u16,
+) -> MicrosandboxResult<()> {
+    let mut conns = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = accepted
+                    .map_err(|e| MicrosandboxError::PortForward(format!("accept: {e}")))?;
+                let client = Arc::clone(&client);
+                conns.spawn(async move {
+                    if let Err(e) = forward_conn(client, stream, guest_port).await {
+                        tracing::debug!(%peer, guest_port, "port forward connection: {e}");
+                    }
+                });
+            }
+            Some(_) = conns.join_next(), if !conns.is_empty() => {}
+        }
+    }
+}
+
+/// Open a tunnel to the guest port and pump one host connection through it.
+async fn forward_conn(
+    client: Arc<AgentClient>,
+    stream: TcpStream,
+    guest_port: u16,
+) -> MicrosandboxResult<()> {
+    let id = client.next_id();
+    let mut rx = client.subscribe(id).await;
+    let open = TunnelOpen {
+        endpoint: TunnelEndpoint::Tcp { port: guest_port },
+        window: Some(DEFAULT_WINDOW),
+    };
+    client
+        .send(&Message::with_payload(MessageType::TunnelOpen, id, &open)?)
+        .await?;
+    let mut guard = CloseGuard::new(Arc::clone(&client), id);
+    wait_opened(&mut rx).await.inspect_err(|_| guard.disarm())?;
+
+    pump(client, &mut guard, stream, rx).await
+}
+
+/// Bind each connection the guest listener accepts and tunnel it to
+/// `host_addr`. Returns when the guest closes the listener.
+async fn reverse_loop(
+    client: Arc<AgentClient>,
+    mut guard: CloseGuard,
+    mut rx: mpsc::UnboundedReceiver<Message>,
+    host_addr: SocketAddr,
+) -> MicrosandboxResult<()> {
+    let listener = guard.id;
+    let mut conns = JoinSet::new();
+    loop {
+        tokio::select! {
+            msg = rx.recv() => {
+                let Some(msg) = msg else {
+                    guard.disarm();
+                    return Err(MicrosandboxError::PortForward("agent connection closed".into()));
+                };
+                match msg.t {
+                    MessageType::TunnelAccept => {
+                        let accept: TunnelAccept = msg.payload()?;
+                        let client = Arc::clone(&client);
+                        conns.spawn(async move {
+                            if let Err(e) = reverse_conn(client, listener, accept.conn, host_addr).await {
+                                tracing::debug!(%host_addr, "reverse port forward connection: {e}");
+                            }
+                        });
+                    }
+                    MessageType::TunnelClose => {
+                        guard.disarm();
+                        let close: TunnelClose = msg.payload()?;
+                        return match close.error {
+                            Some(e) => Err(MicrosandboxError::PortForward(e)),
+                            None => Ok(()),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+            Some(_) = conns.join_next(), if !conns.is_empty() => {}
+        }
+    }
+}
+
+/// Claim a connection accepted by the guest listener and pump it to a new
+/// host connection.
+async fn reverse_conn(
+    client: Arc<AgentClient>,
+    listener: u32,
+    conn: u32,
+    host_addr: SocketAddr,
+) -> MicrosandboxResult<()> {
+    let id = client.next_id();
+    let mut rx = client.subscribe(id).await;
+    let bind = TunnelBind {
+        listener,
+        conn,
+        window: Some(DEFAULT_WINDOW),
+    };
+    client
+        .send(&Message::with_payload(MessageType::TunnelBind, id, &bind)?)
+        .await?;
+    let mut guard = CloseGuard::new(Arc::clone(&client), id);
+    wait_opened(&mut rx).await.inspect_err(|_| guard.disarm())?;
+
+    let stream = TcpStream::connect(host_addr)
+        .await
+        .map_err(|e| MicrosandboxError::PortForward(format!("connect to {host_addr}: {e}")))?;
+    pump(client, &mut guard, stream, rx).await
+}
+
+/// Wait for `core.tunnel.opened`, turning an early close into an error.
+async fn wait_opened(rx: &mut mpsc::UnboundedReceiver<Message>) -> MicrosandboxResult<()> {
+    match rx.recv().await {
+        Some(msg) if msg.t == MessageType::TunnelOpened => Ok(()),
+        Some(msg) if msg.t == MessageType::TunnelClose => {
+            let close: TunnelClose = msg.payload()?;
+            Err(MicrosandboxError::PortForward(
+                close.error.unwrap_or_else(|| "closed by guest".into()),
+            ))
+        }
+        Some(msg) => Err(MicrosandboxError::PortForward(format!(
+            "unexpected {} while opening tunnel",
+            msg.t.as_str()
+        ))),
+        None => Err(MicrosandboxError::PortForward(
+            "agent connection closed".into(),
+        )),
+    }
+}
+
+/// Copy bytes both ways between a host connection and an open tunnel until
+/// the guest closes the tunnel.
+///
+/// Reads from the host connection pause while the guest's window is used
+/// up; data from the guest is acknowledged once written.
+async fn pump(
+    client: Arc<AgentClient>,
+    guard: &mut CloseGuard,
+    stream: TcpStream,
+    mut rx: mpsc::UnboundedReceiver<Message>,
+) -> MicrosandboxResult<()> {
+    let id = guard.id;
+    let (mut reader, mut writer) = stream.into_split();
+    let mut credit = CreditReturn::new(Arc::clone(&client), id, DEFAULT_WINDOW);
+    let mut send_credit = i64::from(DEFAULT_WINDOW);
+    let mut reading = true;
+    let mut chunk = vec![0u8; TUNNEL_CHUNK_SIZE];
+
+    loop {
+        tokio::select! {
+            read = reader.read(&mut chunk), if reading && send_credit > 0 => {
+                let n = read.map_err(|e| MicrosandboxError::PortForward(format!("read: {e}")))?;
+                reading = n > 0;
+                send_credit -= n as i64;
+                let data = TunnelData {
+                    data: chunk[..n].to_vec(),
+                };
+                client
+                    .send(&Message::with_payload(MessageType::TunnelData, id, &data)?)
+                    .await?;
+            }
+            msg = rx.recv() => {
+                let Some(msg) = msg else {
+                    guard.disarm();
+                    return Err(MicrosandboxError::PortForward("agent connection closed".into()));
+                };
+                match msg.t {
+                    MessageType::TunnelData => {
+                        let data: TunnelData = msg.payload()?;
+                        if data.data.is_empty() {
+                            writer.shutdown().await?;
+                        } else {
+                            writer.write_all(&data.data).await?;
+                            credit.consumed(data.data.len()).await?;
+                        }
+                    }
+                    MessageType::Credit => {
+                        let returned: Credit = msg.payload()?;
+                        send_credit += i64::from(returned.bytes);
+                    }
+                    MessageType::TunnelClose => {
+                        guard.disarm();
+                        let close: TunnelClose = msg.payload()?;
+                        return match close.error {
+                            Some(e) => Err(MicrosandboxError::PortForward(e)),
+                            None => Ok(()),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use microsandbox_protocol::{
+        codec,
+        core::{Capabilities, Ready},
+    };
+
+    use super::*;
+
+    /// Stand-in for agentd: accepts one tunnel and echoes its data back in
+    /// upper case, closing once the host half-closes.
+    async fn fake_guest(mut relay: tokio::io::DuplexStream) -> u16 {
+        let messages = [
+            MessageType::Credit,
+            MessageType::TunnelOpen,
+            MessageType::TunnelData,
+            MessageType::TunnelClose,
+        ];
+        let ready = Ready::new(
+            0,
+            0,
+            0,
+            Capabilities {
+                messages: messages.iter().map(|t| t.as_str().into()).collect(),
+                ..Default::default()
+            },
+        );
+        let mut handshake = 0u32.to_be_bytes().to_vec();
+        codec::encode_to_buf(
+            &Message::with_payload(MessageType::Ready, 0, &ready).unwrap(),
+            &mut handshake,
+        )
+        .unwrap();
+        relay.write_all(&handshake).await.unwrap();
+
+        let open = codec::read_message(&mut relay).await.unwrap();
+        assert_eq!(open.t, MessageType::TunnelOpen);
+        let TunnelEndpoint::Tcp { port } = open.payload::<TunnelOpen>().unwrap().endpoint else {
+            panic!("expected a TCP endpoint");
+        };
+        let reply = Message::new(MessageType::TunnelOpened, open.id, Vec::new());
+        codec::write_message(&mut relay, &reply).await.unwrap();
+
+        loop {
+            let msg = codec::read_message(&mut relay).await.unwrap();
+            if msg.t != MessageType::TunnelData {
+                continue;
+            }
+            let mut data: TunnelData = msg.payload().unwrap();
+            let eof = data.data.is_empty();
+            data.data.make_ascii_uppercase();
+            let echo = Message::with_payload(MessageType::TunnelData, open.id, &data).unwrap();
+            codec::write_message(&mut relay, &echo).await.unwrap();
+            if eof {
+                let close = Message::with_payload(
+                    MessageType::TunnelClose,
+                    open.id,
+                    &TunnelClose::default(),
+                )
+                .unwrap();
+                codec::write_message(&mut relay, &close).await.unwrap();
+                return port;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_conn_pumps_both_directions() {
+        let (client_side, relay_side) = tokio::io::duplex(64 * 1024);
+        let guest = tokio::spawn(fake_guest(relay_side));
+        let client = Arc::new(AgentClient::handshake(client_side).await.unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut user = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        let forward = tokio::spawn(forward_conn(client, accepted, 8080));
+
+        user.write_all(b"hello").await.unwrap();
+        user.shutdown().await.unwrap();
+        let mut echoed = Vec::new();
+        user.read_to_end(&mut echoed).await.unwrap();
+        assert_eq!(echoed, b"HELLO");
+
+        forward.await.unwrap().unwrap();
+        assert_eq!(guest.await.unwrap(), 8080);
+    }
+}
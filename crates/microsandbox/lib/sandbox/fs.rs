@@ -7,12 +7,18 @@ use std::{path::Path, sync::Arc};
 
 use bytes::Bytes;
 use microsandbox_protocol::{
-    fs::{FS_CHUNK_SIZE, FsData, FsEntryInfo, FsOp, FsRequest, FsResponse, FsResponseData},
+    flow::Credit,
+    fs::{
+        FS_CHUNK_SIZE, FS_WINDOW, FsData, FsEntryInfo, FsOp, FsRequest, FsResponse, FsResponseData,
+    },
     message::{Message, MessageType},
 };
 use tokio::sync::mpsc;
 
-use crate::{MicrosandboxError, MicrosandboxResult, agent::AgentClient};
+use crate::{
+    MicrosandboxError, MicrosandboxResult,
+    agent::{AgentClient, CreditReturn},
+};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -84,15 +90,29 @@ pub struct FsMetadata {
 }
 
 /// A streaming reader for file data from the sandbox.
+///
+/// The guest pauses reading the file once a bounded amount of data is
+/// waiting to be received, and resumes as chunks are taken.
 pub struct FsReadStream {
     rx: mpsc::UnboundedReceiver<Message>,
+    credit: CreditReturn,
 }
 
 /// A streaming writer for file data to the sandbox.
+///
+/// Writes wait while the guest has a bounded amount of data it has not yet
+/// written to disk.
 pub struct FsWriteSink {
     id: u32,
     client: Arc<AgentClient>,
     rx: mpsc::UnboundedReceiver<Message>,
+    window: SendWindow,
+}
+
+/// Credit the guest has granted for a streaming write.
+struct SendWindow {
+    /// Bytes that may still be sent; negative after a chunk overdraws it.
+    credit: i64,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -110,6 +130,7 @@ impl<'a> SandboxFs<'a> {
     //----------------------------------------------------------------------------------------------
 
     /// Read an entire file from the guest filesystem into memory.
+    #[tracing::instrument(name = "sandbox.fs.read", skip_all, fields(path = %path))]
     pub async fn read(&self, path: &str) -> MicrosandboxResult<Bytes> {
         let id = self.client.next_id();
         let mut rx = self.client.subscribe(id).await;
@@ -117,18 +138,23 @@ impl<'a> SandboxFs<'a> {
         let req = FsRequest {
             op: FsOp::Read {
                 path: path.to_string(),
+                window: self.client.flow_window(FS_WINDOW),
+                compression: self.client.compression(),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, id, &req)?;
         self.client.send(&msg).await?;
 
         // Collect FsData chunks until FsResponse (terminal).
+        let mut credit = CreditReturn::new(Arc::clone(self.client), id, FS_WINDOW);
         let mut data = Vec::new();
         while let Some(msg) = rx.recv().await {
             match msg.t {
                 MessageType::FsData => {
                     let chunk: FsData = msg.payload()?;
                     data.extend_from_slice(&chunk.data);
+                    credit.consumed(chunk.data.len()).await?;
                 }
                 MessageType::FsResponse => {
                     let resp: FsResponse = msg.payload()?;
@@ -156,6 +182,7 @@ impl<'a> SandboxFs<'a> {
     /// Read a file with streaming.
     ///
     /// Returns an [`FsReadStream`] that yields chunks of data as they arrive.
+    #[tracing::instrument(name = "sandbox.fs.read_stream", skip_all, fields(path = %path))]
     pub async fn read_stream(&self, path: &str) -> MicrosandboxResult<FsReadStream> {
         let id = self.client.next_id();
         let rx = self.client.subscribe(id).await;
@@ -163,12 +190,18 @@ impl<'a> SandboxFs<'a> {
         let req = FsRequest {
             op: FsOp::Read {
                 path: path.to_string(),
+                window: self.client.flow_window(FS_WINDOW),
+                compression: self.client.compression(),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, id, &req)?;
         self.client.send(&msg).await?;
 
-        Ok(FsReadStream { rx })
+        Ok(FsReadStream {
+            rx,
+            credit: CreditReturn::new(Arc::clone(self.client), id, FS_WINDOW),
+        })
     }
 
     //----------------------------------------------------------------------------------------------
@@ -176,6 +209,7 @@ impl<'a> SandboxFs<'a> {
     //----------------------------------------------------------------------------------------------
 
     /// Write data to a file in the guest, creating it if it doesn't exist.
+    #[tracing::instrument(name = "sandbox.fs.write", skip_all, fields(path = %path))]
     pub async fn write(&self, path: &str, data: impl AsRef<[u8]>) -> MicrosandboxResult<()> {
         let data = data.as_ref();
         let id = self.client.next_id();
@@ -186,17 +220,23 @@ impl<'a> SandboxFs<'a> {
             op: FsOp::Write {
                 path: path.to_string(),
                 mode: None,
+                window: self.client.flow_window(FS_WINDOW),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, id, &req)?;
         self.client.send(&msg).await?;
 
-        // Send data chunks.
+        // Send data chunks as the guest's window allows.
+        let mut window = SendWindow::new(self.client.flow_window(FS_WINDOW));
+        let compression = self.client.compression();
         for chunk in data.chunks(FS_CHUNK_SIZE) {
+            window.acquire(&mut rx, chunk.len()).await?;
             let fs_data = FsData {
                 data: chunk.to_vec(),
             };
-            let msg = Message::with_payload(MessageType::FsData, id, &fs_data)?;
+            let msg =
+                Message::with_payload(MessageType::FsData, id, &fs_data)?.compress(compression);
             self.client.send(&msg).await?;
         }
 
@@ -213,6 +253,7 @@ impl<'a> SandboxFs<'a> {
     ///
     /// Returns an [`FsWriteSink`] for writing data in chunks. Call
     /// [`FsWriteSink::close`] when done writing.
+    #[tracing::instrument(name = "sandbox.fs.write_stream", skip_all, fields(path = %path))]
     pub async fn write_stream(&self, path: &str) -> MicrosandboxResult<FsWriteSink> {
         let id = self.client.next_id();
 
@@ -223,8 +264,10 @@ impl<'a> SandboxFs<'a> {
             op: FsOp::Write {
                 path: path.to_string(),
                 mode: None,
+                window: self.client.flow_window(FS_WINDOW),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, id, &req)?;
         self.client.send(&msg).await?;
 
@@ -232,6 +275,7 @@ impl<'a> SandboxFs<'a> {
             id,
             client: Arc::clone(self.client),
             rx,
+            window: SendWindow::new(self.client.flow_window(FS_WINDOW)),
         })
     }
 
@@ -240,12 +284,14 @@ impl<'a> SandboxFs<'a> {
     //----------------------------------------------------------------------------------------------
 
     /// List the immediate children of a directory in the guest (non-recursive).
+    #[tracing::instrument(name = "sandbox.fs.list", skip_all, fields(path = %path))]
     pub async fn list(&self, path: &str) -> MicrosandboxResult<Vec<FsEntry>> {
         let req = FsRequest {
             op: FsOp::List {
                 path: path.to_string(),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, 0, &req)?;
         let resp_msg = self.client.request(msg).await?;
         let resp: FsResponse = resp_msg.payload()?;
@@ -265,24 +311,28 @@ impl<'a> SandboxFs<'a> {
     }
 
     /// Create a directory (and parents).
+    #[tracing::instrument(name = "sandbox.fs.mkdir", skip_all, fields(path = %path))]
     pub async fn mkdir(&self, path: &str) -> MicrosandboxResult<()> {
         let req = FsRequest {
             op: FsOp::Mkdir {
                 path: path.to_string(),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, 0, &req)?;
         let resp_msg = self.client.request(msg).await?;
         check_response(resp_msg)
     }
 
     /// Remove a directory recursively.
+    #[tracing::instrument(name = "sandbox.fs.remove_dir", skip_all, fields(path = %path))]
     pub async fn remove_dir(&self, path: &str) -> MicrosandboxResult<()> {
         let req = FsRequest {
             op: FsOp::RemoveDir {
                 path: path.to_string(),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, 0, &req)?;
         let resp_msg = self.client.request(msg).await?;
         check_response(resp_msg)
@@ -293,18 +343,21 @@ impl<'a> SandboxFs<'a> {
     //----------------------------------------------------------------------------------------------
 
     /// Delete a single file. Use [`remove_dir`](Self::remove_dir) for directories.
+    #[tracing::instrument(name = "sandbox.fs.remove", skip_all, fields(path = %path))]
     pub async fn remove(&self, path: &str) -> MicrosandboxResult<()> {
         let req = FsRequest {
             op: FsOp::Remove {
                 path: path.to_string(),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, 0, &req)?;
         let resp_msg = self.client.request(msg).await?;
         check_response(resp_msg)
     }
 
     /// Copy a file within the sandbox.
+    #[tracing::instrument(name = "sandbox.fs.copy", skip_all, fields(from = %from, to = %to))]
     pub async fn copy(&self, from: &str, to: &str) -> MicrosandboxResult<()> {
         let req = FsRequest {
             op: FsOp::Copy {
@@ -312,12 +365,14 @@ impl<'a> SandboxFs<'a> {
                 dst: to.to_string(),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, 0, &req)?;
         let resp_msg = self.client.request(msg).await?;
         check_response(resp_msg)
     }
 
     /// Rename/move a file or directory.
+    #[tracing::instrument(name = "sandbox.fs.rename", skip_all, fields(from = %from, to = %to))]
     pub async fn rename(&self, from: &str, to: &str) -> MicrosandboxResult<()> {
         let req = FsRequest {
             op: FsOp::Rename {
@@ -325,6 +380,7 @@ impl<'a> SandboxFs<'a> {
                 dst: to.to_string(),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, 0, &req)?;
         let resp_msg = self.client.request(msg).await?;
         check_response(resp_msg)
@@ -335,12 +391,14 @@ impl<'a> SandboxFs<'a> {
     //----------------------------------------------------------------------------------------------
 
     /// Get file/directory metadata.
+    #[tracing::instrument(name = "sandbox.fs.stat", skip_all, fields(path = %path))]
     pub async fn stat(&self, path: &str) -> MicrosandboxResult<FsMetadata> {
         let req = FsRequest {
             op: FsOp::Stat {
                 path: path.to_string(),
             },
         };
+        self.client.require_fs_op(&req.op)?;
         let msg = Message::with_payload(MessageType::FsRequest, 0, &req)?;
         let resp_msg = self.client.request(msg).await?;
         let resp: FsResponse = resp_msg.payload()?;
@@ -373,6 +431,7 @@ impl<'a> SandboxFs<'a> {
     //----------------------------------------------------------------------------------------------
 
     /// Copy a file from the host into the sandbox.
+    #[tracing::instrument(name = "sandbox.fs.copy_from_host", skip_all, fields(guest_path = %guest_path))]
     pub async fn copy_from_host(
         &self,
         host_path: impl AsRef<Path>,
@@ -383,6 +442,7 @@ impl<'a> SandboxFs<'a> {
     }
 
     /// Copy a file from the sandbox to the host.
+    #[tracing::instrument(name = "sandbox.fs.copy_to_host", skip_all, fields(guest_path = %guest_path))]
     pub async fn copy_to_host(
         &self,
         guest_path: &str,
@@ -409,6 +469,7 @@ impl FsReadStream {
                 MessageType::FsData => {
                     let chunk: FsData = msg.payload()?;
                     if !chunk.data.is_empty() {
+                        self.credit.consumed(chunk.data.len()).await?;
                         return Ok(Some(Bytes::from(chunk.data)));
                     }
                 }
@@ -443,11 +504,20 @@ impl FsReadStream {
 
 impl FsWriteSink {
     /// Write a chunk of data.
-    pub async fn write(&self, data: impl AsRef<[u8]>) -> MicrosandboxResult<()> {
+    ///
+    /// Waits while the guest is behind. Fails early if the guest has already
+    /// reported a write error.
+    pub async fn write(&mut self, data: impl AsRef<[u8]>) -> MicrosandboxResult<()> {
+        let data = data.as_ref();
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.window.acquire(&mut self.rx, data.len()).await?;
         let fs_data = FsData {
-            data: data.as_ref().to_vec(),
+            data: data.to_vec(),
         };
-        let msg = Message::with_payload(MessageType::FsData, self.id, &fs_data)?;
+        let msg = Message::with_payload(MessageType::FsData, self.id, &fs_data)?
+            .compress(self.client.compression());
         self.client.send(&msg).await
     }
 
@@ -465,6 +535,47 @@ impl FsWriteSink {
     }
 }
 
+impl SendWindow {
+    /// A window of `window` bytes, or an unlimited one for agents without
+    /// flow control.
+    fn new(window: Option<u32>) -> Self {
+        Self {
+            credit: window.map_or(i64::MAX, i64::from),
+        }
+    }
+
+    /// Wait until the guest has granted credit, then take `bytes` of it.
+    ///
+    /// A terminal `FsResponse` while waiting means the write failed in the
+    /// guest; its error is returned.
+    async fn acquire(
+        &mut self,
+        rx: &mut mpsc::UnboundedReceiver<Message>,
+        bytes: usize,
+    ) -> MicrosandboxResult<()> {
+        while self.credit <= 0 {
+            let msg = rx.recv().await.ok_or_else(|| {
+                MicrosandboxError::SandboxFs("channel closed before response".into())
+            })?;
+            match msg.t {
+                MessageType::Credit => {
+                    let credit: Credit = msg.payload()?;
+                    self.credit += i64::from(credit.bytes);
+                }
+                MessageType::FsResponse => {
+                    check_response(msg)?;
+                    return Err(MicrosandboxError::SandboxFs(
+                        "write session ended early".into(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        self.credit -= bytes as i64;
+        Ok(())
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
@@ -2,14 +2,14 @@
 
 use sea_orm::EntityTrait;
 
-use std::sync::Arc;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use crate::{
     MicrosandboxResult, agent::AgentClient, db::entity::sandbox as sandbox_entity,
     runtime::SpawnMode,
 };
 
-use super::{Sandbox, SandboxConfig, SandboxStatus};
+use super::{HealthStatus, Sandbox, SandboxConfig, SandboxStatus};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -28,6 +28,7 @@ pub struct SandboxHandle {
     db_id: i32,
     name: String,
     status: SandboxStatus,
+    health: Option<HealthStatus>,
     config_json: String,
     created_at: Option<chrono::DateTime<chrono::Utc>>,
     updated_at: Option<chrono::DateTime<chrono::Utc>>,
@@ -45,6 +46,7 @@ impl SandboxHandle {
             db_id: model.id,
             name: model.name,
             status: model.status,
+            health: model.health,
             config_json: model.config,
             created_at: model.created_at.map(|dt| dt.and_utc()),
             updated_at: model.updated_at.map(|dt| dt.and_utc()),
@@ -63,6 +65,12 @@ impl SandboxHandle {
         self.status
     }
 
+    /// Health check status from when this handle was created. `None` if the
+    /// sandbox has no health check or is not running.
+    pub fn health(&self) -> Option<HealthStatus> {
+        self.health
+    }
+
     /// The serialized sandbox configuration as stored in the database.
     /// Use [`config()`](Self::config) for a deserialized version.
     pub fn config_json(&self) -> &str {
@@ -75,6 +83,20 @@ impl SandboxHandle {
         Ok(serde_json::from_str(&self.config_json)?)
     }
 
+    /// Labels from the stored configuration. Empty if the configuration
+    /// cannot be parsed.
+    pub fn labels(&self) -> HashMap<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Labels {
+            #[serde(default)]
+            labels: HashMap<String, String>,
+        }
+
+        serde_json::from_str::<Labels>(&self.config_json)
+            .map(|l| l.labels)
+            .unwrap_or_default()
+    }
+
     /// When this sandbox was first created, if recorded.
     pub fn created_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
         self.created_at
@@ -104,6 +126,19 @@ impl SandboxHandle {
         .await
     }
 
+    /// Get aggregated metrics for this sandbox over a time range.
+    ///
+    /// Works for stopped sandboxes too; see [`super::Sandbox::metrics_history`].
+    pub async fn metrics_history(
+        &self,
+        range: std::ops::Range<chrono::DateTime<chrono::Utc>>,
+        step: std::time::Duration,
+    ) -> MicrosandboxResult<Vec<super::MetricsHistoryPoint>> {
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+        super::metrics::metrics_history_for_sandbox(db, self.db_id, range, step).await
+    }
+
     /// Start this sandbox and return a live handle.
     ///
     /// Boots the VM using the persisted configuration and pinned rootfs state.
@@ -125,6 +160,21 @@ impl SandboxHandle {
     /// without owning the process lifecycle. The sandbox will continue
     /// running after this handle is dropped.
     pub async fn connect(&self) -> MicrosandboxResult<Sandbox> {
+        let sock_path = self.relay_socket_path()?;
+        let client = AgentClient::connect(&sock_path).await?;
+        let config: SandboxConfig = serde_json::from_str(&self.config_json)?;
+
+        Ok(Sandbox {
+            db_id: self.db_id,
+            config,
+            handle: None,
+            client: Arc::new(client),
+            remote: false,
+        })
+    }
+
+    /// Path of the running sandbox's agent relay socket.
+    pub(crate) fn relay_socket_path(&self) -> MicrosandboxResult<PathBuf> {
         if self.status != SandboxStatus::Running && self.status != SandboxStatus::Draining {
             return Err(crate::MicrosandboxError::Custom(format!(
                 "sandbox '{}' is not running (status: {:?})",
@@ -132,26 +182,18 @@ impl SandboxHandle {
             )));
         }
 
-        let global = crate::config::config();
-        let sock_path = global
+        Ok(crate::config::config()
             .sandboxes_dir()
             .join(&self.name)
             .join("runtime")
-            .join("agent.sock");
-
-        let client = AgentClient::connect(&sock_path).await?;
-        let config: SandboxConfig = serde_json::from_str(&self.config_json)?;
-
-        Ok(Sandbox {
-            db_id: self.db_id,
-            config,
-            handle: None,
-            client: Arc::new(client),
-        })
+            .join("agent.sock"))
     }
 
     /// Stop the sandbox gracefully (SIGTERM).
+    ///
+    /// Also cancels a pending restart from the sandbox's restart policy.
     pub async fn stop(&self) -> MicrosandboxResult<()> {
+        self.mark_stop_requested().await;
         if self.status != SandboxStatus::Running && self.status != SandboxStatus::Draining {
             return Ok(());
         }
@@ -165,6 +207,7 @@ impl SandboxHandle {
     /// Waits for the process to exit (up to 5 seconds) and marks the
     /// sandbox as `Stopped`.
     pub async fn kill(&mut self) -> MicrosandboxResult<()> {
+        self.mark_stop_requested().await;
         if self.status != SandboxStatus::Running && self.status != SandboxStatus::Draining {
             return Ok(());
         }
@@ -192,6 +235,15 @@ impl SandboxHandle {
         Ok(())
     }
 
+    /// Automatic restarts since the sandbox was last started by hand, as
+    /// recorded on its latest run.
+    pub async fn restart_count(&self) -> MicrosandboxResult<u32> {
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+        let run = super::restart::latest_run(db, self.db_id).await?;
+        Ok(run.map_or(0, |run| u32::try_from(run.restart_count).unwrap_or(0)))
+    }
+
     /// Remove this sandbox from the database and filesystem.
     ///
     /// The sandbox must be stopped first. Use [`stop`](SandboxHandle::stop) or
@@ -211,9 +263,27 @@ impl SandboxHandle {
         sandbox_entity::Entity::delete_by_id(self.db_id)
             .exec(db)
             .await?;
+        crate::events::emit(
+            db,
+            Some(self.db_id),
+            &self.name,
+            crate::events::EventKind::Removed,
+        )
+        .await;
 
         Ok(())
     }
+
+    /// Record the stop on the latest run so the restart policy leaves the
+    /// sandbox stopped.
+    async fn mark_stop_requested(&self) {
+        if self
+            .config()
+            .is_ok_and(|config| config.restart_policy.is_enabled())
+        {
+            super::restart::record_stop_request(self.db_id, &self.name).await;
+        }
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -1,14 +1,26 @@
 //! Sandbox metrics APIs backed by persisted runtime samples.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
 use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 use futures::stream;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use microsandbox_runtime::metrics::{
+    HOUR_ROLLUP_SECS, MINUTE_ROLLUP_SECS, MetricsRetention, MetricsRollup, bucket_start,
+    compact_metrics,
+};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    sea_query::Query,
+};
 
 use crate::{
     MicrosandboxError, MicrosandboxResult,
-    db::entity::{sandbox as sandbox_entity, sandbox_metric as sandbox_metric_entity},
+    db::entity::{
+        sandbox as sandbox_entity, sandbox_metric as sandbox_metric_entity,
+        sandbox_metric_rollup as rollup_entity,
+    },
 };
 
 use super::{Sandbox, SandboxConfig, SandboxStatus};
@@ -40,6 +52,38 @@ pub struct SandboxMetrics {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+/// Aggregated metrics for one step of a [`Sandbox::metrics_history`] query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricsHistoryPoint {
+    /// Start of the step.
+    pub timestamp: DateTime<Utc>,
+    /// Number of raw samples that contributed to this point.
+    pub samples: u64,
+    /// CPU usage as a percentage across all host CPUs.
+    pub cpu_percent: MetricSummary<f32>,
+    /// Resident memory usage in bytes.
+    pub memory_bytes: MetricSummary<u64>,
+    /// Cumulative disk bytes read at the end of the step.
+    pub disk_read_bytes: u64,
+    /// Cumulative disk bytes written at the end of the step.
+    pub disk_write_bytes: u64,
+    /// Cumulative network bytes received at the end of the step.
+    pub net_rx_bytes: u64,
+    /// Cumulative network bytes transmitted at the end of the step.
+    pub net_tx_bytes: u64,
+}
+
+/// Minimum, average and maximum of a gauge over a step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MetricSummary<T> {
+    /// Smallest observed value.
+    pub min: T,
+    /// Sample-weighted average.
+    pub avg: T,
+    /// Largest observed value.
+    pub max: T,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Methods
 //--------------------------------------------------------------------------------------------------
@@ -47,9 +91,28 @@ pub struct SandboxMetrics {
 impl Sandbox {
     /// Get the latest metrics snapshot for this running sandbox.
     pub async fn metrics(&self) -> MicrosandboxResult<SandboxMetrics> {
+        let db_id = self.local_id()?;
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+        metrics_for_sandbox(db, db_id, memory_limit_bytes(&self.config)).await
+    }
+
+    /// Get aggregated metrics for this sandbox over a time range.
+    ///
+    /// The range is split into `step`-wide buckets aligned to the Unix epoch;
+    /// buckets without samples are omitted. Recent data comes from raw
+    /// samples, older data from the 1-minute and 1-hour rollups kept by
+    /// metrics retention, so steps finer than the stored resolution yield one
+    /// point per stored bucket.
+    pub async fn metrics_history(
+        &self,
+        range: Range<DateTime<Utc>>,
+        step: Duration,
+    ) -> MicrosandboxResult<Vec<MetricsHistoryPoint>> {
+        let db_id = self.local_id()?;
         let db =
             crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
-        metrics_for_sandbox(db, self.db_id, memory_limit_bytes(&self.config)).await
+        metrics_history_for_sandbox(db, db_id, range, step).await
     }
 
     /// Stream metrics snapshots at the requested interval.
@@ -57,7 +120,7 @@ impl Sandbox {
         &self,
         interval: Duration,
     ) -> impl futures::Stream<Item = MicrosandboxResult<SandboxMetrics>> + Send + 'static {
-        let db_id = self.db_id;
+        let db_id = self.local_id().ok();
         let memory_limit_bytes = memory_limit_bytes(&self.config);
         let interval = if interval.is_zero() {
             Duration::from_millis(1)
@@ -72,9 +135,14 @@ impl Sandbox {
                 let db =
                     crate::db::init_global(Some(crate::config::config().database.max_connections))
                         .await;
-                let item = match db {
-                    Ok(db) => metrics_for_sandbox(db, db_id, memory_limit_bytes).await,
-                    Err(err) => Err(err),
+                let item = match (db, db_id) {
+                    (Ok(db), Some(db_id)) => {
+                        metrics_for_sandbox(db, db_id, memory_limit_bytes).await
+                    }
+                    (Err(err), _) => Err(err),
+                    (_, None) => Err(crate::MicrosandboxError::Gateway(
+                        "metrics are not available for remote sandboxes".into(),
+                    )),
                 };
                 Some((item, ticker))
             },
@@ -115,6 +183,52 @@ pub async fn all_sandbox_metrics() -> MicrosandboxResult<HashMap<String, Sandbox
     Ok(metrics)
 }
 
+/// Compact metrics for every sandbox whose runtime is not running, and delete
+/// metrics left behind by removed sandboxes.
+///
+/// A running sandbox compacts its own metrics from its sampler. This pass
+/// covers stopped and crashed sandboxes, whose raw samples would otherwise
+/// outlive their retention.
+pub(super) async fn compact_idle_metrics(
+    db: &DatabaseConnection,
+    retention: MetricsRetention,
+    now: chrono::NaiveDateTime,
+) -> MicrosandboxResult<()> {
+    let idle: Vec<i32> = sandbox_entity::Entity::find()
+        .select_only()
+        .column(sandbox_entity::Column::Id)
+        .filter(sandbox_entity::Column::Status.is_not_in([
+            SandboxStatus::Running,
+            SandboxStatus::Draining,
+            SandboxStatus::Paused,
+        ]))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    for sandbox_id in idle {
+        // Best-effort: one sandbox's failure must not stop the others.
+        if let Err(e) = compact_metrics(db, sandbox_id, retention, now).await {
+            tracing::debug!(sandbox_id, error = %e, "failed to compact sandbox metrics");
+        }
+    }
+
+    let sandbox_ids = Query::select()
+        .column(sandbox_entity::Column::Id)
+        .from(sandbox_entity::Entity)
+        .to_owned();
+    sandbox_metric_entity::Entity::delete_many()
+        .filter(sandbox_metric_entity::Column::SandboxId.not_in_subquery(sandbox_ids.clone()))
+        .exec(db)
+        .await?;
+    rollup_entity::Entity::delete_many()
+        .filter(rollup_entity::Column::SandboxId.not_in_subquery(sandbox_ids))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
 pub(super) async fn metrics_for_sandbox(
     db: &sea_orm::DatabaseConnection,
     sandbox_id: i32,
@@ -175,6 +289,106 @@ pub(super) async fn metrics_for_sandbox(
     })
 }
 
+pub(super) async fn metrics_history_for_sandbox(
+    db: &sea_orm::DatabaseConnection,
+    sandbox_id: i32,
+    range: Range<DateTime<Utc>>,
+    step: Duration,
+) -> MicrosandboxResult<Vec<MetricsHistoryPoint>> {
+    let start = range.start.naive_utc();
+    let end = range.end.naive_utc();
+    if start >= end {
+        return Ok(Vec::new());
+    }
+    let step_secs = i64::try_from(step.as_secs()).unwrap_or(i64::MAX).max(1);
+
+    // Each tier only fills the time before the next finer tier begins, so no
+    // sample is counted twice while raw data and its rollups coexist.
+    let raw = sandbox_metric_entity::Entity::find()
+        .filter(sandbox_metric_entity::Column::SandboxId.eq(sandbox_id))
+        .filter(sandbox_metric_entity::Column::SampledAt.gte(start))
+        .filter(sandbox_metric_entity::Column::SampledAt.lt(end))
+        .order_by_asc(sandbox_metric_entity::Column::SampledAt)
+        .order_by_asc(sandbox_metric_entity::Column::Id)
+        .all(db)
+        .await?;
+    let raw_from = raw
+        .first()
+        .and_then(|row| row.sampled_at)
+        .map_or(end, |ts| bucket_start(ts, i64::from(MINUTE_ROLLUP_SECS)));
+
+    let minutes = rollups(db, sandbox_id, MINUTE_ROLLUP_SECS, start, raw_from).await?;
+    let minutes_from = minutes.first().map_or(raw_from, |row| {
+        bucket_start(row.bucket_start, i64::from(HOUR_ROLLUP_SECS))
+    });
+    let hours = rollups(db, sandbox_id, HOUR_ROLLUP_SECS, start, minutes_from).await?;
+
+    let mut buckets: BTreeMap<_, MetricsRollup> = BTreeMap::new();
+    for row in hours.iter().chain(&minutes) {
+        buckets
+            .entry(bucket_start(row.bucket_start, step_secs))
+            .or_default()
+            .add_rollup(row);
+    }
+    for row in &raw {
+        if let Some(sampled_at) = row.sampled_at {
+            buckets
+                .entry(bucket_start(sampled_at, step_secs))
+                .or_default()
+                .add_sample(row);
+        }
+    }
+
+    Ok(buckets
+        .into_iter()
+        .map(|(timestamp, rollup)| history_point(timestamp.and_utc(), &rollup))
+        .collect())
+}
+
+async fn rollups(
+    db: &sea_orm::DatabaseConnection,
+    sandbox_id: i32,
+    resolution_secs: i32,
+    start: chrono::NaiveDateTime,
+    end: chrono::NaiveDateTime,
+) -> MicrosandboxResult<Vec<rollup_entity::Model>> {
+    if start >= end {
+        return Ok(Vec::new());
+    }
+    rollup_entity::Entity::find()
+        .filter(rollup_entity::Column::SandboxId.eq(sandbox_id))
+        .filter(rollup_entity::Column::ResolutionSecs.eq(resolution_secs))
+        .filter(rollup_entity::Column::BucketStart.gte(start))
+        .filter(rollup_entity::Column::BucketStart.lt(end))
+        .order_by_asc(rollup_entity::Column::BucketStart)
+        .all(db)
+        .await
+        .map_err(Into::into)
+}
+
+fn history_point(timestamp: DateTime<Utc>, rollup: &MetricsRollup) -> MetricsHistoryPoint {
+    let (cpu_min, cpu_avg, cpu_max) = rollup.cpu_percent().unwrap_or_default();
+    let (mem_min, mem_avg, mem_max) = rollup.memory_bytes().unwrap_or_default();
+    MetricsHistoryPoint {
+        timestamp,
+        samples: u64::try_from(rollup.sample_count).unwrap_or_default(),
+        cpu_percent: MetricSummary {
+            min: cpu_min,
+            avg: cpu_avg,
+            max: cpu_max,
+        },
+        memory_bytes: MetricSummary {
+            min: i64_to_u64(mem_min),
+            avg: i64_to_u64(mem_avg),
+            max: i64_to_u64(mem_max),
+        },
+        disk_read_bytes: rollup.disk_read_bytes.map_or(0, i64_to_u64),
+        disk_write_bytes: rollup.disk_write_bytes.map_or(0, i64_to_u64),
+        net_rx_bytes: rollup.net_rx_bytes.map_or(0, i64_to_u64),
+        net_tx_bytes: rollup.net_tx_bytes.map_or(0, i64_to_u64),
+    }
+}
+
 async fn latest_metric(
     db: &sea_orm::DatabaseConnection,
     sandbox_id: i32,
@@ -188,10 +402,198 @@ async fn latest_metric(
         .map_err(Into::into)
 }
 
-fn memory_limit_bytes(config: &SandboxConfig) -> u64 {
+pub(super) fn memory_limit_bytes(config: &SandboxConfig) -> u64 {
     u64::from(config.memory_mib) * 1024 * 1024
 }
 
 fn i64_to_u64(value: i64) -> u64 {
     u64::try_from(value).unwrap_or_default()
 }
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, TimeZone};
+    use sea_orm::{ActiveModelTrait, ConnectionTrait, PaginatorTrait, Set};
+
+    use super::*;
+
+    async fn setup() -> (tempfile::TempDir, sea_orm::DatabaseConnection, i32) {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = crate::db::connect_and_migrate(&tmp.path().join("db"), 1)
+            .await
+            .unwrap();
+        let sandbox = sandbox_entity::ActiveModel {
+            name: Set("history".into()),
+            config: Set("{}".into()),
+            status: Set(SandboxStatus::Stopped),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        (tmp, db, sandbox.id)
+    }
+
+    fn at(secs: i64) -> NaiveDateTime {
+        Utc.timestamp_opt(1_700_000_000 - 1_700_000_000 % 3600 + secs, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    /// Insert one raw sample every 10 seconds for `[from, to)`, with CPU equal
+    /// to the minute index and a disk counter equal to the elapsed seconds.
+    async fn insert_samples(db: &sea_orm::DatabaseConnection, sandbox_id: i32, from: i64, to: i64) {
+        for secs in (from..to).step_by(10) {
+            sandbox_metric_entity::ActiveModel {
+                sandbox_id: Set(sandbox_id),
+                cpu_percent: Set(Some((secs / 60) as f32)),
+                memory_bytes: Set(Some(1000 + secs)),
+                disk_read_bytes: Set(Some(secs)),
+                sampled_at: Set(Some(at(secs))),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compaction_rolls_up_and_prunes() {
+        let (_tmp, db, sandbox_id) = setup().await;
+        insert_samples(&db, sandbox_id, 0, 2 * 3600).await;
+
+        let retention = MetricsRetention {
+            raw: Duration::from_secs(1800),
+            ..Default::default()
+        };
+        compact_metrics(&db, sandbox_id, retention, at(2 * 3600 + 30))
+            .await
+            .unwrap();
+        // A second pass must not duplicate buckets.
+        compact_metrics(&db, sandbox_id, retention, at(2 * 3600 + 30))
+            .await
+            .unwrap();
+
+        let raw = sandbox_metric_entity::Entity::find()
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(raw, 1800 / 10);
+
+        let minute = rollup_entity::Entity::find()
+            .filter(rollup_entity::Column::ResolutionSecs.eq(MINUTE_ROLLUP_SECS))
+            .order_by_asc(rollup_entity::Column::BucketStart)
+            .all(&db)
+            .await
+            .unwrap();
+        assert_eq!(minute.len(), 120);
+        assert_eq!(minute[5].sample_count, 6);
+        assert_eq!(minute[5].cpu_percent_max, Some(5.0));
+        assert_eq!(minute[5].memory_bytes_min, Some(1300));
+        assert_eq!(minute[5].memory_bytes_max, Some(1350));
+        assert_eq!(minute[5].disk_read_bytes, Some(350));
+
+        let hour = rollup_entity::Entity::find()
+            .filter(rollup_entity::Column::ResolutionSecs.eq(HOUR_ROLLUP_SECS))
+            .all(&db)
+            .await
+            .unwrap();
+        assert_eq!(hour.len(), 2);
+        assert_eq!(hour[0].sample_count, 360);
+        assert_eq!(hour[0].cpu_percent_min, Some(0.0));
+        assert_eq!(hour[0].cpu_percent_max, Some(59.0));
+    }
+
+    #[tokio::test]
+    async fn test_history_merges_tiers_without_double_counting() {
+        let (_tmp, db, sandbox_id) = setup().await;
+        insert_samples(&db, sandbox_id, 0, 2 * 3600).await;
+        let retention = MetricsRetention {
+            raw: Duration::from_secs(1800),
+            ..Default::default()
+        };
+        compact_metrics(&db, sandbox_id, retention, at(2 * 3600 + 30))
+            .await
+            .unwrap();
+
+        let range = at(0).and_utc()..at(2 * 3600).and_utc();
+        let points = metrics_history_for_sandbox(&db, sandbox_id, range, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].samples, 360);
+        assert_eq!(points[1].samples, 360);
+        assert_eq!(points[1].cpu_percent.max, 119.0);
+        assert_eq!(points[1].memory_bytes.max, 1000 + 7190);
+        assert_eq!(points[1].disk_read_bytes, 7190);
+
+        let range = at(3600).and_utc()..at(3600 + 120).and_utc();
+        let points = metrics_history_for_sandbox(&db, sandbox_id, range, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].timestamp, at(3600).and_utc());
+        assert_eq!(points[1].cpu_percent.avg, 61.0);
+    }
+
+    #[tokio::test]
+    async fn test_compact_idle_metrics_without_running_sampler() {
+        let (_tmp, db, sandbox_id) = setup().await;
+        insert_samples(&db, sandbox_id, 0, 2 * 3600).await;
+
+        // A removed sandbox whose rows were not cascaded away.
+        db.execute_unprepared("PRAGMA foreign_keys = OFF")
+            .await
+            .unwrap();
+        insert_samples(&db, sandbox_id + 1, 0, 600).await;
+        rollup_entity::ActiveModel {
+            sandbox_id: Set(sandbox_id + 1),
+            resolution_secs: Set(MINUTE_ROLLUP_SECS),
+            bucket_start: Set(at(0)),
+            sample_count: Set(6),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let retention = MetricsRetention {
+            raw: Duration::from_secs(1800),
+            ..Default::default()
+        };
+        compact_idle_metrics(&db, retention, at(2 * 3600 + 30))
+            .await
+            .unwrap();
+
+        let raw = sandbox_metric_entity::Entity::find()
+            .filter(sandbox_metric_entity::Column::SandboxId.eq(sandbox_id))
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(raw, 1800 / 10);
+        let minute = rollup_entity::Entity::find()
+            .filter(rollup_entity::Column::SandboxId.eq(sandbox_id))
+            .filter(rollup_entity::Column::ResolutionSecs.eq(MINUTE_ROLLUP_SECS))
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(minute, 120);
+
+        let orphaned = sandbox_metric_entity::Entity::find()
+            .filter(sandbox_metric_entity::Column::SandboxId.eq(sandbox_id + 1))
+            .count(&db)
+            .await
+            .unwrap()
+            + rollup_entity::Entity::find()
+                .filter(rollup_entity::Column::SandboxId.eq(sandbox_id + 1))
+                .count(&db)
+                .await
+                .unwrap();
+        assert_eq!(orphaned, 0);
+    }
+}
@@ -5,22 +5,35 @@
 //! methods (stop, kill, drain, wait) and access to the [`AgentClient`]
 //! for guest communication.
 
+mod archive;
 mod attach;
 mod builder;
 mod config;
+mod env;
 pub mod exec;
+mod exporter;
+mod forward;
 pub mod fs;
 mod handle;
 mod metrics;
 mod patch;
+mod pool;
+mod process;
+mod restart;
+mod stats;
 mod types;
 
-use std::{path::Path, process::ExitStatus, sync::Arc};
+use std::{path::Path, process::ExitStatus, sync::Arc, time::Duration};
 
 use bytes::Bytes;
+use microsandbox_image::LayerFetches;
 use microsandbox_protocol::{
-    exec::{ExecExited, ExecRequest, ExecRlimit, ExecStarted, ExecStderr, ExecStdin, ExecStdout},
-    message::{Message, MessageType},
+    exec::{
+        ExecAttach, ExecExited, ExecRequest, ExecRlimit, ExecSessions, ExecStarted, ExecStderr,
+        ExecStdin, ExecStdout,
+    },
+    flow::DEFAULT_WINDOW,
+    message::{FLAG_PERSISTENT, Message, MessageType},
 };
 use sea_orm::{
     ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
@@ -29,11 +42,13 @@ use sea_orm::{
 use tokio::sync::{Mutex, mpsc};
 
 use crate::{
-    MicrosandboxResult,
+    LabelSelector, MicrosandboxResult,
     agent::AgentClient,
     db::entity::{
         run as run_entity, sandbox as sandbox_entity, sandbox_image as sandbox_image_entity,
     },
+    events::EventKind,
+    gateway::RemoteGateway,
     runtime::{ProcessHandle, SpawnMode, spawn_sandbox},
 };
 
@@ -44,14 +59,20 @@ use self::exec::{ExecEvent, ExecHandle, ExecOptions, ExecSink, StdinMode};
 // Re-Exports
 //--------------------------------------------------------------------------------------------------
 
-pub use crate::db::entity::sandbox::SandboxStatus;
+pub use crate::db::entity::sandbox::{HealthStatus, SandboxStatus};
+pub use archive::{
+    ARCHIVE_FORMAT_VERSION, ArchiveImage, ArchiveVolume, ExportOptions, ImportOptions,
+    SandboxArchiveManifest,
+};
 pub use attach::AttachOptionsBuilder;
-pub use builder::SandboxBuilder;
+pub use builder::{SSH_AGENT_GUEST_PATH, SandboxBuilder};
 pub use config::SandboxConfig;
 pub use exec::{ExecOptionsBuilder, ExecOutput, Rlimit, RlimitResource};
+pub use exporter::{METRICS_PATH, MetricsServer, OPENMETRICS_CONTENT_TYPE, render_openmetrics};
+pub use forward::PortForward;
 pub use fs::{FsEntry, FsEntryKind, FsMetadata, FsReadStream, FsWriteSink, SandboxFs};
 pub use handle::SandboxHandle;
-pub use metrics::{SandboxMetrics, all_sandbox_metrics};
+pub use metrics::{MetricSummary, MetricsHistoryPoint, SandboxMetrics, all_sandbox_metrics};
 pub use microsandbox_image::{PullPolicy, PullProgress, PullProgressHandle};
 #[cfg(feature = "net")]
 pub use microsandbox_network::builder::SecretBuilder;
@@ -59,12 +80,32 @@ pub use microsandbox_network::builder::SecretBuilder;
 pub use microsandbox_network::config::NetworkConfig;
 #[cfg(feature = "net")]
 pub use microsandbox_network::policy::NetworkPolicy;
+pub use microsandbox_protocol::exec::ExecSessionInfo;
+pub use microsandbox_protocol::stats::{
+    GuestFilesystemStats, GuestMemoryStats, GuestProcessStats, GuestStats,
+};
 pub use microsandbox_runtime::logging::LogLevel;
+pub use microsandbox_runtime::policy::{
+    HealthCheck, HealthProbe, LimitAction, LimitMetric, ResourceLimit,
+};
+pub use microsandbox_runtime::sockets::{SocketDirection, SocketMount};
+pub use pool::{
+    LeaseOptions, LeaseOptionsBuilder, POOL_LABEL, POOL_LEASE_LABEL, PooledSandbox, ReleasePolicy,
+    SandboxPool, SandboxPoolBuilder,
+};
+pub use restart::{RestartPolicy, supervise};
 pub use types::{
     DiskImageFormat, ImageBuilder, ImageSource, IntoImage, MountBuilder, Patch, PatchBuilder,
     RootfsSource, SecretsConfig, SshConfig, VolumeMount,
 };
 
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// How often [`Sandbox::wait_healthy`] checks the recorded health status.
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -78,6 +119,20 @@ pub struct Sandbox {
     config: SandboxConfig,
     handle: Option<Arc<Mutex<ProcessHandle>>>,
     client: Arc<AgentClient>,
+    /// Connected through a remote gateway; `db_id` is meaningless locally.
+    remote: bool,
+}
+
+/// Identifies an exec session in the event log.
+struct ExecEventSource {
+    /// Local database ID; `None` for remote sandboxes, whose events are not
+    /// recorded here.
+    sandbox_id: Option<i32>,
+    sandbox_name: String,
+    session_id: u32,
+    cmd: String,
+    /// Resumed persistent session, whose start was already recorded.
+    reattached: bool,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -160,6 +215,7 @@ impl Sandbox {
         Self::start_with_mode(name, SpawnMode::Detached).await
     }
 
+    #[tracing::instrument(name = "sandbox.create", skip_all, fields(sandbox = %config.name))]
     async fn create_with_mode(
         mut config: SandboxConfig,
         mode: SpawnMode,
@@ -176,8 +232,12 @@ impl Sandbox {
 
         let mut pinned_manifest_digest: Option<String> = None;
         let mut pinned_reference: Option<String> = None;
+        let mut fetches = LayerFetches::default();
 
         validate_rootfs_source(&config.image)?;
+        validate_platform(&config)?;
+        validate_restart_policy(&config, mode)?;
+        validate_health_check(&config)?;
 
         // Initialize the database before any expensive image pull so we can
         // fail fast on conflicting persisted sandbox state.
@@ -188,9 +248,12 @@ impl Sandbox {
 
         // Resolve OCI images before spawning the sandbox process.
         if let RootfsSource::Oci(reference) = config.image.clone() {
+            let platform = config.platform.clone().unwrap_or_default();
             let pull_result = pull_oci_image(
                 &reference,
+                &platform,
                 config.pull_policy,
+                config.lazy_layers,
                 config.registry_auth.take(),
                 progress,
             )
@@ -203,12 +266,13 @@ impl Sandbox {
             config.resolved_rootfs_layers = pull_result.layers;
             pinned_manifest_digest = Some(pull_result.manifest_digest.to_string());
             pinned_reference = Some(reference.clone());
+            fetches = pull_result.fetches;
 
             // Persist full image metadata to database.
             let cache_dir = crate::config::config().cache_dir();
             if let Ok(cache) = microsandbox_image::GlobalCache::new(&cache_dir)
                 && let Ok(image_ref) = reference.parse::<microsandbox_image::Reference>()
-                && let Ok(Some(metadata)) = cache.read_image_metadata(&image_ref)
+                && let Ok(Some(metadata)) = cache.read_image_metadata(&image_ref, &platform)
                 && let Err(e) = crate::image::Image::persist(&reference, metadata).await
             {
                 tracing::warn!(error = %e, "failed to persist image metadata to database");
@@ -226,9 +290,13 @@ impl Sandbox {
             .await?;
         }
 
+        // Sensitive env values stay out of the database.
+        env::save_sensitive_env(&config, &sandbox_dir).await?;
+
         // Insert the sandbox record and keep its stable database ID.
         let sandbox_id = insert_sandbox_record(db, &config).await?;
         tracing::debug!(sandbox_id, sandbox = %config.name, "create_with_mode: db record inserted");
+        crate::events::emit(db, Some(sandbox_id), &config.name, EventKind::Created).await;
 
         // Spawn the sandbox process and create the bridge. On failure, mark the sandbox
         // as stopped so it doesn't appear as a phantom "Running" entry.
@@ -262,10 +330,25 @@ impl Sandbox {
             )));
         }
 
+        if mode == SpawnMode::Detached && sandbox.config.restart_policy.is_enabled() {
+            start_supervisor(&sandbox.config.name);
+        }
+
+        finish_layer_fetches(fetches, mode).await;
         Ok(sandbox)
     }
 
     pub(super) async fn start_with_mode(name: &str, mode: SpawnMode) -> MicrosandboxResult<Self> {
+        Self::start_run(name, mode, 0).await
+    }
+
+    /// Start a stopped sandbox. `restart_count` is non-zero when the restart
+    /// supervisor starts it.
+    async fn start_run(
+        name: &str,
+        mode: SpawnMode,
+        restart_count: u32,
+    ) -> MicrosandboxResult<Self> {
         tracing::debug!(sandbox = name, ?mode, "start_with_mode: loading record");
         let db =
             crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
@@ -285,13 +368,29 @@ impl Sandbox {
             )));
         }
 
-        let config: SandboxConfig = serde_json::from_str(&model.config)?;
+        let mut config: SandboxConfig = serde_json::from_str(&model.config)?;
+        let sandbox_dir = crate::config::config().sandboxes_dir().join(name);
         validate_rootfs_source(&config.image)?;
-        validate_start_state(&config, &crate::config::config().sandboxes_dir().join(name))?;
+        validate_restart_policy(&config, mode)?;
+        validate_health_check(&config)?;
+        validate_start_state(&config, &sandbox_dir)?;
+        env::restore_sensitive_env(&mut config, &sandbox_dir).await?;
+        config.restart_count = restart_count;
+        let fetches = resume_lazy_layers(db, model.id, &config).await?;
         update_sandbox_status(db, model.id, SandboxStatus::Running).await?;
 
         match Self::create_inner(config, model.id, mode).await {
-            Ok(sandbox) => Ok(sandbox),
+            Ok(sandbox) => {
+                // The supervisor's own restarts don't need another one.
+                if mode == SpawnMode::Detached
+                    && restart_count == 0
+                    && sandbox.config.restart_policy.is_enabled()
+                {
+                    start_supervisor(name);
+                }
+                finish_layer_fetches(fetches, mode).await;
+                Ok(sandbox)
+            }
             Err(err) => {
                 let _ = update_sandbox_status(db, model.id, SandboxStatus::Stopped).await;
                 Err(err)
@@ -300,6 +399,7 @@ impl Sandbox {
     }
 
     /// Inner create logic separated for error-cleanup wrapper.
+    #[tracing::instrument(name = "sandbox.boot", skip_all, fields(sandbox = %config.name))]
     async fn create_inner(
         config: SandboxConfig,
         sandbox_id: i32,
@@ -317,11 +417,41 @@ impl Sandbox {
             ready_time_ms = ready.ready_time_ns / 1_000_000,
             "sandbox ready",
         );
+
+        if let Ok(db) =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await
+        {
+            crate::events::emit(db, Some(sandbox_id), &config.name, EventKind::Started).await;
+        }
         Ok(Self {
             db_id: sandbox_id,
             config,
             handle: Some(Arc::new(Mutex::new(handle))),
             client: Arc::new(client),
+            remote: false,
+        })
+    }
+
+    /// Connect to a running sandbox on another host through a remote
+    /// gateway (`msb serve`).
+    ///
+    /// The returned handle supports exec, attach, fs and [`stop`](Self::stop).
+    /// Metrics, health and event history live in the remote host's database
+    /// and are not available through it.
+    ///
+    /// ```ignore
+    /// let gateway = RemoteGateway::new("tls://kvm-01:7443")?.token(token);
+    /// let sb = Sandbox::connect_remote(&gateway, "worker").await?;
+    /// ```
+    pub async fn connect_remote(gateway: &RemoteGateway, name: &str) -> MicrosandboxResult<Self> {
+        let (stream, config) = gateway.open_relay(name).await?;
+        let client = AgentClient::handshake(stream).await?;
+        Ok(Self {
+            db_id: 0,
+            config,
+            handle: None,
+            client: Arc::new(client),
+            remote: true,
         })
     }
 
@@ -359,6 +489,18 @@ impl Sandbox {
         Ok(handles)
     }
 
+    /// List sandboxes whose labels match a selector.
+    ///
+    /// ```ignore
+    /// let selector = LabelSelector::parse("session=abc123")?;
+    /// let handles = Sandbox::list_matching(&selector).await?;
+    /// ```
+    pub async fn list_matching(selector: &LabelSelector) -> MicrosandboxResult<Vec<SandboxHandle>> {
+        let mut handles = Self::list().await?;
+        handles.retain(|handle| selector.matches(&handle.labels()));
+        Ok(handles)
+    }
+
     /// Remove a stopped sandbox from the database.
     ///
     /// Convenience method equivalent to `Sandbox::get(name).await?.remove().await`.
@@ -374,6 +516,7 @@ impl Sandbox {
 impl Sandbox {
     /// Remove this sandbox's persisted state after it has fully stopped.
     pub async fn remove_persisted(self) -> MicrosandboxResult<()> {
+        let db_id = self.local_id()?;
         let db =
             crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
 
@@ -382,9 +525,8 @@ impl Sandbox {
                 .sandboxes_dir()
                 .join(&self.config.name),
         )?;
-        sandbox_entity::Entity::delete_by_id(self.db_id)
-            .exec(db)
-            .await?;
+        sandbox_entity::Entity::delete_by_id(db_id).exec(db).await?;
+        crate::events::emit(db, Some(db_id), &self.config.name, EventKind::Removed).await;
 
         Ok(())
     }
@@ -430,6 +572,7 @@ impl Sandbox {
     /// Stop the sandbox gracefully by sending `core.shutdown` to agentd.
     pub async fn stop(&self) -> MicrosandboxResult<()> {
         tracing::debug!(sandbox = %self.config.name, "stop: sending shutdown");
+        self.mark_stop_requested().await;
         let msg = Message::new(MessageType::Shutdown, 0, Vec::new());
         self.client.send(&msg).await
     }
@@ -454,7 +597,10 @@ impl Sandbox {
     /// Kill the sandbox immediately (SIGKILL).
     pub async fn kill(&self) -> MicrosandboxResult<()> {
         match &self.handle {
-            Some(h) => h.lock().await.kill(),
+            Some(h) => {
+                self.mark_stop_requested().await;
+                h.lock().await.kill()
+            }
             None => Err(crate::MicrosandboxError::Runtime(
                 "cannot kill: not the lifecycle owner".into(),
             )),
@@ -464,7 +610,10 @@ impl Sandbox {
     /// Trigger a graceful drain (SIGUSR1).
     pub async fn drain(&self) -> MicrosandboxResult<()> {
         match &self.handle {
-            Some(h) => h.lock().await.drain(),
+            Some(h) => {
+                self.mark_stop_requested().await;
+                h.lock().await.drain()
+            }
             None => Err(crate::MicrosandboxError::Runtime(
                 "cannot drain: not the lifecycle owner".into(),
             )),
@@ -481,6 +630,77 @@ impl Sandbox {
         }
     }
 
+    /// Wait until the sandbox's health check passes.
+    ///
+    /// Fails if the sandbox has no health check, becomes unhealthy, stops, or
+    /// is not healthy within `timeout`.
+    pub async fn wait_healthy(&self, timeout: Duration) -> MicrosandboxResult<()> {
+        let name = &self.config.name;
+        if self.config.policy.health_check.is_none() {
+            return Err(crate::MicrosandboxError::HealthCheck(format!(
+                "sandbox '{name}' has no health check"
+            )));
+        }
+        let db_id = self.local_id()?;
+
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let model = sandbox_entity::Entity::find_by_id(db_id)
+                .one(db)
+                .await?
+                .ok_or_else(|| crate::MicrosandboxError::SandboxNotFound(name.clone()))?;
+
+            if !matches!(
+                model.status,
+                SandboxStatus::Running | SandboxStatus::Draining
+            ) {
+                return Err(crate::MicrosandboxError::HealthCheck(format!(
+                    "sandbox '{name}' stopped before becoming healthy"
+                )));
+            }
+            match model.health {
+                Some(HealthStatus::Healthy) => return Ok(()),
+                Some(HealthStatus::Unhealthy) => {
+                    return Err(crate::MicrosandboxError::HealthCheck(format!(
+                        "sandbox '{name}' is unhealthy"
+                    )));
+                }
+                Some(HealthStatus::Starting) | None => {}
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                return Err(crate::MicrosandboxError::HealthCheck(format!(
+                    "sandbox '{name}' not healthy after {timeout:?}"
+                )));
+            }
+            tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Record the stop on the current run so the restart policy leaves the
+    /// sandbox stopped. Best-effort: the stop itself must still go out.
+    async fn mark_stop_requested(&self) {
+        if self.config.restart_policy.is_enabled()
+            && let Ok(db_id) = self.local_id()
+        {
+            restart::record_stop_request(db_id, &self.config.name).await;
+        }
+    }
+
+    /// The sandbox's ID in the local database. Fails for sandboxes
+    /// connected through a remote gateway.
+    pub(crate) fn local_id(&self) -> MicrosandboxResult<i32> {
+        if self.remote {
+            return Err(crate::MicrosandboxError::Gateway(format!(
+                "sandbox '{}' is remote; its state lives on the gateway host",
+                self.config.name
+            )));
+        }
+        Ok(self.db_id)
+    }
+
     /// Detach this handle without stopping the sandbox.
     ///
     /// Disarms the SIGTERM safety net so the sandbox keeps running after
@@ -531,6 +751,7 @@ impl Sandbox {
         self.exec_stream_inner(cmd.into(), opts).await
     }
 
+    #[tracing::instrument(name = "sandbox.exec.start", skip_all, fields(sandbox = %self.config.name, cmd = %cmd))]
     async fn exec_stream_inner(
         &self,
         cmd: String,
@@ -541,17 +762,28 @@ impl Sandbox {
             cwd,
             user,
             env,
+            sensitive_env,
+            env_files,
+            clear_env,
             rlimits,
             tty,
             stdin: stdin_mode,
             timeout: _,
+            persistent,
         } = opts;
 
+        if let Some(ref name) = persistent {
+            self.check_session_name_free(name).await?;
+        }
+
+        let env = env::with_env_files(&env_files, &env).await?;
+        let sensitive = self.sensitive_env_keys(&sensitive_env);
         tracing::debug!(
             sandbox = %self.config.name,
             cmd = %cmd,
             args = ?args,
             cwd = ?cwd,
+            env = ?env::RedactedEnv { env: &env, sensitive: &sensitive },
             tty,
             "exec_stream"
         );
@@ -559,21 +791,34 @@ impl Sandbox {
         // Allocate correlation ID and subscribe BEFORE sending.
         let id = self.client.next_id();
         let rx = self.client.subscribe(id).await;
+        let source = ExecEventSource {
+            sandbox_id: self.local_id().ok(),
+            sandbox_name: self.config.name.clone(),
+            session_id: id,
+            cmd: std::iter::once(cmd.as_str())
+                .chain(args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" "),
+            reattached: false,
+        };
 
-        let req = build_exec_request(
+        let mut req = build_exec_request(
             &self.config,
             cmd,
             args,
             cwd,
             user,
             &env,
+            clear_env,
             &rlimits,
             tty,
             24,
             80,
         );
-        let msg = Message::with_payload(MessageType::ExecRequest, id, &req)?;
-        self.client.send(&msg).await?;
+        req.persistent = persistent;
+        req.window = self.client.flow_window(DEFAULT_WINDOW);
+        req.compression = self.client.compression();
+        self.client.send(&exec_request_message(id, &req)?).await?;
 
         // Build stdin sink (if Pipe mode).
         let stdin = match &stdin_mode {
@@ -600,13 +845,14 @@ impl Sandbox {
 
         // Transform raw protocol messages into ExecEvents.
         let (event_tx, event_rx) = mpsc::unbounded_channel();
-        tokio::spawn(event_mapper_task(rx, event_tx));
+        tokio::spawn(event_mapper_task(rx, event_tx, source));
 
         Ok(ExecHandle::new(
             id,
             event_rx,
             stdin,
             Arc::clone(&self.client),
+            req.window,
         ))
     }
 
@@ -642,6 +888,7 @@ impl Sandbox {
     }
 
     /// Shared implementation for exec and exec_with.
+    #[tracing::instrument(name = "sandbox.exec", skip_all, fields(sandbox = %self.config.name, cmd = %cmd))]
     async fn exec_with_opts(
         &self,
         cmd: String,
@@ -694,6 +941,112 @@ impl Sandbox {
         };
         self.exec_stream_inner(shell.to_string(), opts).await
     }
+
+    /// List the sandbox's persistent exec sessions.
+    ///
+    /// Includes sessions that have exited but whose exit has not been
+    /// collected by a client yet.
+    pub async fn list_sessions(&self) -> MicrosandboxResult<Vec<ExecSessionInfo>> {
+        let msg = Message::new(MessageType::ExecSessionsRequest, 0, Vec::new());
+        let resp: ExecSessions = self.client.request(msg).await?.payload()?;
+        Ok(resp.sessions)
+    }
+
+    /// Resume a persistent exec session started with
+    /// [`ExecOptionsBuilder::persistent`].
+    ///
+    /// The handle first yields the session's buffered output, then streams
+    /// as for a new session. Stdin is always available through
+    /// [`ExecHandle::take_stdin`].
+    ///
+    /// ```ignore
+    /// let mut handle = sb.reattach("build").await?;
+    /// let output = handle.collect().await?;
+    /// ```
+    pub async fn reattach(&self, session: &str) -> MicrosandboxResult<ExecHandle> {
+        let (id, rx, info) = self.send_attach(session, Some(DEFAULT_WINDOW)).await?;
+        let source = ExecEventSource {
+            sandbox_id: self.local_id().ok(),
+            sandbox_name: self.config.name.clone(),
+            session_id: id,
+            cmd: info.cmd,
+            reattached: true,
+        };
+
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        tokio::spawn(event_mapper_task(rx, event_tx, source));
+
+        Ok(ExecHandle::new(
+            id,
+            event_rx,
+            Some(ExecSink::new(id, Arc::clone(&self.client))),
+            Arc::clone(&self.client),
+            Some(DEFAULT_WINDOW),
+        ))
+    }
+
+    /// Env keys whose values must not be logged for a command: its own
+    /// sensitive vars plus the sandbox's.
+    fn sensitive_env_keys(&self, command: &[String]) -> Vec<String> {
+        self.config
+            .sensitive_env
+            .iter()
+            .chain(command)
+            .cloned()
+            .collect()
+    }
+
+    /// Fail if a persistent session with this name is still running.
+    async fn check_session_name_free(&self, name: &str) -> MicrosandboxResult<()> {
+        if name.is_empty() {
+            return Err(crate::MicrosandboxError::InvalidConfig(
+                "exec session name must not be empty".into(),
+            ));
+        }
+        let running = self
+            .list_sessions()
+            .await?
+            .into_iter()
+            .any(|s| s.name == name && s.exit_code.is_none());
+        if running {
+            return Err(crate::MicrosandboxError::ExecSessionAlreadyExists(
+                name.to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Send `core.exec.attach` for a persistent session, returning the new
+    /// correlation ID and its message stream. The caller must return credit
+    /// if it passes a flow-control `window`.
+    async fn send_attach(
+        &self,
+        session: &str,
+        window: Option<u32>,
+    ) -> MicrosandboxResult<(u32, mpsc::UnboundedReceiver<Message>, ExecSessionInfo)> {
+        let info = self
+            .list_sessions()
+            .await?
+            .into_iter()
+            .find(|s| s.name == session)
+            .ok_or_else(|| crate::MicrosandboxError::ExecSessionNotFound(session.to_string()))?;
+        if info.attached {
+            return Err(crate::MicrosandboxError::Runtime(format!(
+                "exec session '{session}' is attached to another client"
+            )));
+        }
+
+        let id = self.client.next_id();
+        let rx = self.client.subscribe(id).await;
+        let attach = ExecAttach {
+            session: session.to_string(),
+            window,
+            compression: self.client.compression(),
+        };
+        let msg = Message::with_payload(MessageType::ExecAttach, id, &attach)?;
+        self.client.send(&msg).await?;
+        Ok((id, rx, info))
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -734,38 +1087,92 @@ impl Sandbox {
 
     /// Shared implementation for attach and attach_with.
     async fn attach_inner(&self, cmd: String, opts: AttachOptions) -> MicrosandboxResult<i32> {
-        use std::os::fd::AsRawFd;
-
-        use microsandbox_protocol::exec::ExecResize;
-        use tokio::io::{AsyncWriteExt, unix::AsyncFd};
-
         let detach_keys = match &opts.detach_keys {
             Some(spec) => attach::DetachKeys::parse(spec)?,
             None => attach::DetachKeys::default_keys(),
         };
 
+        if let Some(ref name) = opts.persistent {
+            self.check_session_name_free(name).await?;
+        }
+
         // Get terminal size.
         let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
 
         // Allocate ID and subscribe.
         let id = self.client.next_id();
-        let mut rx = self.client.subscribe(id).await;
+        let rx = self.client.subscribe(id).await;
 
         // Build ExecRequest with tty=true.
-        let req = build_exec_request(
+        let env = env::with_env_files(&opts.env_files, &opts.env).await?;
+        let sensitive = self.sensitive_env_keys(&opts.sensitive_env);
+        tracing::debug!(
+            sandbox = %self.config.name,
+            cmd = %cmd,
+            args = ?opts.args,
+            cwd = ?opts.cwd,
+            env = ?env::RedactedEnv { env: &env, sensitive: &sensitive },
+            "attach"
+        );
+        let mut req = build_exec_request(
             &self.config,
             cmd,
             opts.args,
             opts.cwd,
             opts.user,
-            &opts.env,
+            &env,
+            opts.clear_env,
             &opts.rlimits,
             true,
             rows,
             cols,
         );
-        let msg = Message::with_payload(MessageType::ExecRequest, id, &req)?;
-        self.client.send(&msg).await?;
+        req.persistent = opts.persistent;
+        req.compression = self.client.compression();
+        let persistent = req.persistent.is_some();
+        self.client.send(&exec_request_message(id, &req)?).await?;
+
+        self.run_terminal(id, rx, &detach_keys, persistent).await
+    }
+
+    /// Reattach the terminal to a persistent exec session.
+    ///
+    /// Replays the session's buffered output, then behaves like
+    /// [`attach`](Self::attach). Detaching leaves the session running.
+    ///
+    /// ```ignore
+    /// let exit_code = sb.attach_session("shell").await?;
+    /// ```
+    pub async fn attach_session(&self, session: &str) -> MicrosandboxResult<i32> {
+        use microsandbox_protocol::exec::ExecResize;
+
+        let (id, rx, _) = self.send_attach(session, None).await?;
+
+        // The session's PTY still has the size of the terminal it was
+        // started from.
+        if let Ok((cols, rows)) = crossterm::terminal::size() {
+            let payload = ExecResize { rows, cols };
+            let msg = Message::with_payload(MessageType::ExecResize, id, &payload)?;
+            self.client.send(&msg).await?;
+        }
+
+        self.run_terminal(id, rx, &attach::DetachKeys::default_keys(), true)
+            .await
+    }
+
+    /// Bridge the host terminal to the exec session `id` until it exits or
+    /// the user presses the detach keys.
+    async fn run_terminal(
+        &self,
+        id: u32,
+        mut rx: mpsc::UnboundedReceiver<Message>,
+        detach_keys: &attach::DetachKeys,
+        persistent: bool,
+    ) -> MicrosandboxResult<i32> {
+        use std::os::fd::AsRawFd;
+
+        use microsandbox_protocol::exec::ExecResize;
+        use tokio::io::{AsyncWriteExt, unix::AsyncFd};
 
         // Enter raw mode.
         crossterm::terminal::enable_raw_mode()
@@ -830,6 +1237,14 @@ impl Sandbox {
                             }
 
                             if detached {
+                                // Release a persistent session so it can be
+                                // reattached without dropping the connection.
+                                if persistent {
+                                    let _ = self
+                                        .client
+                                        .send(&Message::new(MessageType::ExecDetach, id, Vec::new()))
+                                        .await;
+                                }
                                 break;
                             }
 
@@ -934,6 +1349,16 @@ impl Sandbox {
 // Functions
 //--------------------------------------------------------------------------------------------------
 
+/// Encode an exec request, flagging persistent sessions so the relay
+/// detaches rather than kills them when the client goes away.
+fn exec_request_message(id: u32, req: &ExecRequest) -> MicrosandboxResult<Message> {
+    let mut msg = Message::with_payload(MessageType::ExecRequest, id, req)?;
+    if req.persistent.is_some() {
+        msg.flags |= FLAG_PERSISTENT;
+    }
+    Ok(msg)
+}
+
 /// Wait for the agent relay socket to become available and connect.
 ///
 /// The sandbox process creates the relay socket asynchronously during startup.
@@ -1013,12 +1438,17 @@ fn build_exec_request(
     cwd: Option<String>,
     user: Option<String>,
     env: &[(String, String)],
+    clear_env: bool,
     rlimits: &[Rlimit],
     tty: bool,
     rows: u16,
     cols: u16,
 ) -> ExecRequest {
-    let merged = config::merge_env_pairs(&config.env, env);
+    let merged = if clear_env {
+        env.to_vec()
+    } else {
+        config::merge_env_pairs(&config.env, env)
+    };
     let mut env: Vec<String> = merged.iter().map(|(k, v)| format!("{k}={v}")).collect();
 
     // Inject TERM for TTY sessions if not already set.
@@ -1039,6 +1469,7 @@ fn build_exec_request(
         cmd,
         args,
         env,
+        clear_env,
         cwd: cwd
             .or_else(|| config.workdir.clone())
             .or_else(|| Some("/".to_string())),
@@ -1047,6 +1478,10 @@ fn build_exec_request(
         rows,
         cols,
         rlimits,
+        traceparent: crate::telemetry::current_traceparent(),
+        persistent: None,
+        window: None,
+        compression: None,
     }
 }
 
@@ -1107,15 +1542,29 @@ fn read_from_fd(fd: std::os::fd::RawFd, buf: &mut [u8]) -> std::io::Result<usize
     }
 }
 
-/// Background task that converts raw protocol messages into [`ExecEvent`]s.
+/// Background task that converts raw protocol messages into [`ExecEvent`]s
+/// and records the session's start and exit in the event log.
 async fn event_mapper_task(
     mut rx: mpsc::UnboundedReceiver<Message>,
     tx: mpsc::UnboundedSender<ExecEvent>,
+    source: ExecEventSource,
 ) {
+    let db = crate::db::init_global(Some(crate::config::config().database.max_connections))
+        .await
+        .ok();
+
     while let Some(msg) = rx.recv().await {
         let event = match msg.t {
             MessageType::ExecStarted => {
                 if let Ok(started) = msg.payload::<ExecStarted>() {
+                    let kind = EventKind::ExecStarted {
+                        session_id: source.session_id,
+                        cmd: source.cmd.clone(),
+                        pid: started.pid,
+                    };
+                    if !source.reattached {
+                        record_exec_event(db, &source, kind).await;
+                    }
                     ExecEvent::Started { pid: started.pid }
                 } else {
                     continue;
@@ -1137,7 +1586,15 @@ async fn event_mapper_task(
             }
             MessageType::ExecExited => {
                 if let Ok(exited) = msg.payload::<ExecExited>() {
-                    let _ = tx.send(ExecEvent::Exited { code: exited.code });
+                    let kind = EventKind::ExecExited {
+                        session_id: source.session_id,
+                        exit_code: exited.code,
+                    };
+                    record_exec_event(db, &source, kind).await;
+                    let _ = tx.send(ExecEvent::Exited {
+                        code: exited.code,
+                        status: exec::ExitStatus::from(&exited),
+                    });
                 }
                 break;
             }
@@ -1149,6 +1606,16 @@ async fn event_mapper_task(
     }
 }
 
+async fn record_exec_event(
+    db: Option<&sea_orm::DatabaseConnection>,
+    source: &ExecEventSource,
+    kind: EventKind,
+) {
+    if let (Some(db), Some(sandbox_id)) = (db, source.sandbox_id) {
+        crate::events::emit(db, Some(sandbox_id), &source.sandbox_name, kind).await;
+    }
+}
+
 /// Update the sandbox status in the database.
 pub(super) async fn update_sandbox_status(
     db: &sea_orm::DatabaseConnection,
@@ -1168,6 +1635,28 @@ pub(super) async fn update_sandbox_status(
     Ok(())
 }
 
+/// Replace the persisted sandbox config in the database.
+pub(super) async fn update_sandbox_config(
+    db: &sea_orm::DatabaseConnection,
+    sandbox_id: i32,
+    config: &SandboxConfig,
+) -> MicrosandboxResult<()> {
+    sandbox_entity::Entity::update_many()
+        .col_expr(
+            sandbox_entity::Column::Config,
+            Expr::value(config.to_persisted_json()?),
+        )
+        .col_expr(
+            sandbox_entity::Column::UpdatedAt,
+            Expr::value(chrono::Utc::now().naive_utc()),
+        )
+        .filter(sandbox_entity::Column::Id.eq(sandbox_id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions: Reaper
 //--------------------------------------------------------------------------------------------------
@@ -1176,7 +1665,8 @@ pub(super) async fn update_sandbox_status(
 ///
 /// Queries all sandboxes with status `Running` or `Draining`, checks whether
 /// their process is still alive via `kill(pid, 0)`, and marks dead ones as
-/// `Crashed`.
+/// `Crashed`. Then compacts the metrics of sandboxes that are no longer
+/// running and prunes old events.
 ///
 /// Designed to run once at startup as a fire-and-forget background task so
 /// that crashes (SIGSEGV, SIGKILL, etc.) that prevented the sandbox process
@@ -1198,6 +1688,24 @@ pub async fn reap_stale_sandboxes() -> MicrosandboxResult<()> {
         let _ = reconcile_sandbox_runtime_state(db, sandbox).await;
     }
 
+    if let Err(e) = restart::ensure_supervisors(db).await {
+        tracing::debug!(error = %e, "failed to ensure restart supervisors");
+    }
+
+    if let Err(e) = metrics::compact_idle_metrics(
+        db,
+        crate::config::config().metrics.retention(),
+        chrono::Utc::now().naive_utc(),
+    )
+    .await
+    {
+        tracing::debug!(error = %e, "failed to compact sandbox metrics");
+    }
+
+    if let Err(e) = crate::events::prune(db).await {
+        tracing::debug!(error = %e, "failed to prune sandbox events");
+    }
+
     Ok(())
 }
 
@@ -1261,7 +1769,9 @@ pub(super) async fn reconcile_sandbox_runtime_state(
         return Ok(sandbox);
     }
 
-    mark_sandbox_runtime_stale(db, sandbox.id, Some(run.id)).await?;
+    if mark_sandbox_runtime_stale(db, sandbox.id, Some(run.id)).await? {
+        crate::events::emit(db, Some(sandbox.id), &sandbox.name, EventKind::Crashed).await;
+    }
 
     sandbox_entity::Entity::find_by_id(sandbox.id)
         .one(db)
@@ -1282,11 +1792,13 @@ pub(super) async fn load_active_run(
         .map_err(Into::into)
 }
 
+/// Mark a sandbox whose process is gone as crashed. Returns whether the
+/// sandbox row was changed.
 async fn mark_sandbox_runtime_stale(
     db: &sea_orm::DatabaseConnection,
     sandbox_id: i32,
     run_id: Option<i32>,
-) -> MicrosandboxResult<()> {
+) -> MicrosandboxResult<bool> {
     let txn = db.begin().await?;
     let now = chrono::Utc::now().naive_utc();
 
@@ -1308,11 +1820,15 @@ async fn mark_sandbox_runtime_stale(
 
     // Only mark Crashed if the sandbox is still Running or Draining. This
     // prevents a concurrent start() from having its Running status overwritten.
-    sandbox_entity::Entity::update_many()
+    let result = sandbox_entity::Entity::update_many()
         .col_expr(
             sandbox_entity::Column::Status,
             Expr::value(SandboxStatus::Crashed),
         )
+        .col_expr(
+            sandbox_entity::Column::Health,
+            Expr::value(Option::<HealthStatus>::None),
+        )
         .col_expr(sandbox_entity::Column::UpdatedAt, Expr::value(now))
         .filter(sandbox_entity::Column::Id.eq(sandbox_id))
         .filter(
@@ -1322,7 +1838,7 @@ async fn mark_sandbox_runtime_stale(
         .await?;
 
     txn.commit().await?;
-    Ok(())
+    Ok(result.rows_affected > 0)
 }
 
 pub(super) fn pid_is_alive(pid: i32) -> bool {
@@ -1348,20 +1864,23 @@ pub(super) fn pid_is_alive(pid: i32) -> bool {
 ///
 /// When `progress` is `Some`, uses `pull_with_sender()` to emit per-layer
 /// progress events. The caller must consume the corresponding `PullProgressHandle`.
+#[tracing::instrument(name = "image.pull", skip_all, fields(reference = %reference))]
 async fn pull_oci_image(
     reference: &str,
+    platform: &microsandbox_image::Platform,
     pull_policy: microsandbox_image::PullPolicy,
+    lazy: bool,
     explicit_auth: Option<microsandbox_image::RegistryAuth>,
     progress: Option<microsandbox_image::PullProgressSender>,
 ) -> MicrosandboxResult<microsandbox_image::PullResult> {
     let global = crate::config::config();
     let cache = microsandbox_image::GlobalCache::new(&global.cache_dir())?;
-    let platform = microsandbox_image::Platform::host_linux();
     let image_ref: microsandbox_image::Reference = reference.parse().map_err(|e| {
         crate::MicrosandboxError::InvalidConfig(format!("invalid image reference: {e}"))
     })?;
     let options = microsandbox_image::PullOptions {
         pull_policy,
+        lazy,
         ..Default::default()
     };
 
@@ -1369,7 +1888,7 @@ async fn pull_oci_image(
     // constructing the registry client when the image is already complete
     // in the local cache.
     if let Some((result, metadata)) =
-        microsandbox_image::Registry::pull_cached(&cache, &image_ref, &options)?
+        microsandbox_image::Registry::pull_cached(&cache, &image_ref, platform, &options)?
     {
         if let Some(sender) = progress {
             let reference: std::sync::Arc<str> = reference.to_string().into();
@@ -1400,7 +1919,7 @@ async fn pull_oci_image(
         None => global.resolve_registry_auth(image_ref.registry())?,
     };
 
-    let registry = microsandbox_image::Registry::with_auth(platform, cache, auth)?;
+    let registry = microsandbox_image::Registry::with_auth(platform.clone(), cache, auth)?;
 
     if let Some(sender) = progress {
         let task = registry.pull_with_sender(&image_ref, &options, sender);
@@ -1414,6 +1933,75 @@ async fn pull_oci_image(
     }
 }
 
+/// Restart policies need a supervisor to outlive the caller, which only
+/// detached sandboxes allow.
+fn validate_restart_policy(config: &SandboxConfig, mode: SpawnMode) -> MicrosandboxResult<()> {
+    if mode == SpawnMode::Attached && config.restart_policy.is_enabled() {
+        return Err(crate::MicrosandboxError::InvalidConfig(format!(
+            "restart policy '{}' requires a detached sandbox",
+            config.restart_policy
+        )));
+    }
+    Ok(())
+}
+
+/// TCP and HTTP probes reach the guest through the network stack, so they
+/// need networking enabled.
+fn validate_health_check(config: &SandboxConfig) -> MicrosandboxResult<()> {
+    let Some(check) = &config.policy.health_check else {
+        return Ok(());
+    };
+
+    let needs_network = match &check.probe {
+        HealthProbe::Exec { command } if command.is_empty() => {
+            return Err(crate::MicrosandboxError::InvalidConfig(
+                "health check command is empty".into(),
+            ));
+        }
+        HealthProbe::Exec { .. } => false,
+        HealthProbe::Tcp { .. } | HealthProbe::Http { .. } => true,
+    };
+
+    #[cfg(feature = "net")]
+    let network_enabled = config.network.enabled;
+    #[cfg(not(feature = "net"))]
+    let network_enabled = false;
+
+    if needs_network && !network_enabled {
+        return Err(crate::MicrosandboxError::InvalidConfig(format!(
+            "health check '{}' requires networking",
+            check.probe
+        )));
+    }
+    Ok(())
+}
+
+/// Spawn the restart supervisor for a detached sandbox. Failures are logged:
+/// the reaper retries on the next run.
+fn start_supervisor(name: &str) {
+    if let Err(e) = restart::ensure_supervisor(name) {
+        tracing::warn!(sandbox = name, error = %e, "failed to spawn restart supervisor");
+    }
+}
+
+/// Reject image platforms the host cannot run without emulation.
+///
+/// Foreign variants can still be pulled and inspected; they just can't back
+/// a sandbox.
+fn validate_platform(config: &SandboxConfig) -> MicrosandboxResult<()> {
+    if let Some(platform) = &config.platform
+        && !platform.is_host_native()
+    {
+        return Err(microsandbox_image::ImageError::UnsupportedPlatform {
+            platform: platform.to_string(),
+            host: microsandbox_image::Platform::host_linux().to_string(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
 /// Validate rootfs configuration that depends on host filesystem state.
 fn validate_rootfs_source(rootfs: &RootfsSource) -> MicrosandboxResult<()> {
     match rootfs {
@@ -1574,6 +2162,10 @@ async fn mark_sandbox_stopped_for_replacement(
             sandbox_entity::Column::Status,
             Expr::value(SandboxStatus::Stopped),
         )
+        .col_expr(
+            sandbox_entity::Column::Health,
+            Expr::value(Option::<HealthStatus>::None),
+        )
         .col_expr(sandbox_entity::Column::UpdatedAt, Expr::value(now))
         .filter(sandbox_entity::Column::Id.eq(sandbox_id))
         .exec(&txn)
@@ -1628,14 +2220,23 @@ fn validate_start_state(config: &SandboxConfig, sandbox_dir: &Path) -> Microsand
 async fn insert_sandbox_record(
     db: &sea_orm::DatabaseConnection,
     config: &SandboxConfig,
+) -> MicrosandboxResult<i32> {
+    insert_sandbox_record_with_status(db, config, SandboxStatus::Running).await
+}
+
+/// Insert the sandbox record with an explicit initial status and return its ID.
+async fn insert_sandbox_record_with_status<C: ConnectionTrait>(
+    db: &C,
+    config: &SandboxConfig,
+    status: SandboxStatus,
 ) -> MicrosandboxResult<i32> {
     let now = chrono::Utc::now().naive_utc();
-    let config_json = serde_json::to_string(config)?;
+    let config_json = config.to_persisted_json()?;
 
     let model = sandbox_entity::ActiveModel {
         name: Set(config.name.clone()),
         config: Set(config_json),
-        status: Set(SandboxStatus::Running),
+        status: Set(status),
         created_at: Set(Some(now)),
         updated_at: Set(Some(now)),
         ..Default::default()
@@ -1645,6 +2246,94 @@ async fn insert_sandbox_record(
     Ok(result.last_insert_id)
 }
 
+/// Build a digest-pinned reference, falling back to `reference` when no
+/// digest was pinned.
+pub(super) fn pinned_image_reference(
+    reference: &str,
+    manifest_digest: Option<&str>,
+) -> MicrosandboxResult<String> {
+    let Some(digest) = manifest_digest else {
+        return Ok(reference.to_string());
+    };
+
+    let reference: microsandbox_image::Reference = reference.parse().map_err(|e| {
+        crate::MicrosandboxError::InvalidConfig(format!("invalid image reference: {e}"))
+    })?;
+
+    Ok(microsandbox_image::Reference::with_digest(
+        reference.registry().to_string(),
+        reference.repository().to_string(),
+        digest.to_string(),
+    )
+    .whole())
+}
+
+/// Check whether any lazily pulled estargz layer is still missing part of
+/// its blob.
+pub(super) fn has_pending_lazy_blob(layers: &[std::path::PathBuf]) -> bool {
+    use microsandbox_utils::index::{LayerToc, TOC_BLOB_EXTENSION, TOC_EXTENSION, TocSource};
+
+    layers.iter().any(|layer| {
+        let Ok(meta) = std::fs::metadata(layer.with_extension(TOC_BLOB_EXTENSION)) else {
+            return false;
+        };
+        match LayerToc::read(&layer.with_extension(TOC_EXTENSION)) {
+            Ok(toc) => match toc.source {
+                TocSource::Estargz { blob_size } => meta.len() != blob_size,
+                TocSource::Tar => false,
+            },
+            Err(_) => true,
+        }
+    })
+}
+
+/// Restart background downloads of estargz layers that an earlier run left
+/// incomplete by pulling the manifest the sandbox is pinned to again.
+async fn resume_lazy_layers(
+    db: &sea_orm::DatabaseConnection,
+    sandbox_id: i32,
+    config: &SandboxConfig,
+) -> MicrosandboxResult<LayerFetches> {
+    let RootfsSource::Oci(ref reference) = config.image else {
+        return Ok(LayerFetches::default());
+    };
+    if !config.lazy_layers || !has_pending_lazy_blob(&config.resolved_rootfs_layers) {
+        return Ok(LayerFetches::default());
+    }
+
+    let manifest_digest = sandbox_image_entity::Entity::find()
+        .filter(sandbox_image_entity::Column::SandboxId.eq(sandbox_id))
+        .one(db)
+        .await?
+        .map(|pin| pin.manifest_digest);
+    let reference = pinned_image_reference(reference, manifest_digest.as_deref())?;
+    let platform = config.platform.clone().unwrap_or_default();
+    let pull_result = pull_oci_image(
+        &reference,
+        &platform,
+        microsandbox_image::PullPolicy::IfMissing,
+        true,
+        None,
+        None,
+    )
+    .await?;
+
+    Ok(pull_result.fetches)
+}
+
+/// Let estargz layer downloads run to completion before a detached create
+/// or start returns, since the sandbox outlives this process and reads the
+/// blobs as they arrive. Attached sandboxes keep the downloads running in
+/// the background instead.
+async fn finish_layer_fetches(fetches: LayerFetches, mode: SpawnMode) {
+    if mode != SpawnMode::Detached || fetches.is_empty() {
+        return;
+    }
+    if let Err(e) = fetches.wait().await {
+        tracing::warn!(error = %e, "failed to finish downloading lazy image layers");
+    }
+}
+
 async fn persist_oci_manifest_pin(
     db: &sea_orm::DatabaseConnection,
     sandbox_id: i32,
@@ -0,0 +1,625 @@
+//! Warm pool of pre-booted sandboxes.
+//!
+//! A [`SandboxPool`] keeps a fixed number of booted sandboxes created from a
+//! shared [`SandboxConfig`] template, so acquiring one skips image resolution,
+//! rootfs setup, process spawn, and the relay handshake. Each lease layers its
+//! own env and labels over the template. On release the sandbox is either
+//! recycled (stopped, upper layer reset, and rebooted in the background) or
+//! destroyed, and the pool tops itself back up.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::Deref,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+use rand::RngExt;
+
+use super::{Sandbox, SandboxConfig, config::merge_env_pairs, patch, types::RootfsSource};
+use crate::{MicrosandboxResult, runtime::SpawnMode};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Label set on every pool member, holding the pool's name.
+pub const POOL_LABEL: &str = "microsandbox.pool";
+
+/// Label holding the name given to the current lease, if any.
+pub const POOL_LEASE_LABEL: &str = "microsandbox.pool.lease";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A pool of pre-booted sandboxes sharing one config template.
+///
+/// ```ignore
+/// let template = Sandbox::builder("worker").image("python:3.12").build()?;
+/// let pool = SandboxPool::builder(template).size(8).build().await?;
+///
+/// let sb = pool.acquire_with(|l| l.name("job-42").env("JOB_ID", "42")).await?;
+/// let output = sb.shell("python -c 'print(1)'").await?;
+/// sb.release().await?;
+/// ```
+pub struct SandboxPool {
+    inner: Arc<PoolInner>,
+}
+
+/// Builder for [`SandboxPool`].
+pub struct SandboxPoolBuilder {
+    template: SandboxConfig,
+    size: usize,
+    release_policy: ReleasePolicy,
+}
+
+/// What happens to a sandbox when its lease ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReleasePolicy {
+    /// Stop the sandbox, discard its writable upper layer, and boot it again
+    /// for the next lease. Only OCI rootfs sandboxes can be recycled; others
+    /// are destroyed.
+    #[default]
+    Recycle,
+
+    /// Stop and remove the sandbox, then boot a fresh one from the template.
+    Destroy,
+}
+
+/// Per-lease overrides applied on [`SandboxPool::acquire_with`].
+#[derive(Debug, Clone, Default)]
+pub struct LeaseOptions {
+    /// Name recorded for this lease under [`POOL_LEASE_LABEL`].
+    pub name: Option<String>,
+
+    /// Environment variables layered over the template env.
+    pub env: Vec<(String, String)>,
+
+    /// Labels layered over the template labels.
+    pub labels: HashMap<String, String>,
+}
+
+/// Builder for [`LeaseOptions`].
+#[derive(Default)]
+pub struct LeaseOptionsBuilder {
+    options: LeaseOptions,
+}
+
+/// A sandbox leased from a [`SandboxPool`].
+///
+/// Dereferences to [`Sandbox`]. Call [`release`](Self::release) to hand it
+/// back; dropping the lease releases it in the background instead.
+pub struct PooledSandbox {
+    sandbox: Option<Sandbox>,
+    base: SandboxConfig,
+    lease_name: Option<String>,
+    pool: Arc<PoolInner>,
+}
+
+/// Shared pool state.
+struct PoolInner {
+    template: SandboxConfig,
+    size: usize,
+    release_policy: ReleasePolicy,
+    idle: Mutex<VecDeque<PoolMember>>,
+    pending: AtomicUsize,
+    closed: AtomicBool,
+}
+
+/// A booted sandbox together with the config it was booted with.
+struct PoolMember {
+    sandbox: Sandbox,
+    base: SandboxConfig,
+}
+
+/// Counts a member being booted or recycled until dropped.
+struct PendingGuard(Arc<PoolInner>);
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl SandboxPool {
+    /// Start building a pool from a sandbox config template.
+    ///
+    /// The template's name is used as the pool name: members are named
+    /// `<name>-<suffix>` and carry a [`POOL_LABEL`] label with it.
+    pub fn builder(template: SandboxConfig) -> SandboxPoolBuilder {
+        SandboxPoolBuilder::new(template)
+    }
+
+    /// Name of the pool (the template's sandbox name).
+    pub fn name(&self) -> &str {
+        &self.inner.template.name
+    }
+
+    /// Number of sandboxes the pool keeps booted.
+    pub fn size(&self) -> usize {
+        self.inner.size
+    }
+
+    /// Number of booted sandboxes currently waiting to be leased.
+    pub fn idle(&self) -> usize {
+        self.inner.idle.lock().unwrap().len()
+    }
+
+    /// Lease a sandbox with the template config unchanged.
+    pub async fn acquire(&self) -> MicrosandboxResult<PooledSandbox> {
+        self.acquire_with_opts(LeaseOptions::default()).await
+    }
+
+    /// Lease a sandbox with per-lease overrides.
+    ///
+    /// ```ignore
+    /// let sb = pool.acquire_with(|l| l.name("job-42").label("tenant", "acme")).await?;
+    /// ```
+    pub async fn acquire_with(
+        &self,
+        f: impl FnOnce(LeaseOptionsBuilder) -> LeaseOptionsBuilder,
+    ) -> MicrosandboxResult<PooledSandbox> {
+        self.acquire_with_opts(f(LeaseOptionsBuilder::default()).build())
+            .await
+    }
+
+    /// Lease a sandbox with prebuilt [`LeaseOptions`].
+    ///
+    /// Takes an idle member when one is available; otherwise boots a new
+    /// sandbox inline. Either way, the pool is refilled in the background.
+    pub async fn acquire_with_opts(&self, opts: LeaseOptions) -> MicrosandboxResult<PooledSandbox> {
+        if self.inner.closed.load(Ordering::Acquire) {
+            return Err(crate::MicrosandboxError::Custom(format!(
+                "sandbox pool '{}' is shut down",
+                self.name()
+            )));
+        }
+
+        let member = self.inner.idle.lock().unwrap().pop_front();
+        PoolInner::refill(&self.inner);
+        let member = match member {
+            Some(member) => member,
+            None => {
+                tracing::debug!(pool = %self.name(), "acquire: no idle sandbox, booting inline");
+                self.inner.create_member().await?
+            }
+        };
+
+        PoolInner::lease(&self.inner, member, opts).await
+    }
+
+    /// Stop accepting leases and destroy all idle sandboxes.
+    ///
+    /// Outstanding leases are destroyed when released. Dropping the pool
+    /// without calling this stops idle sandboxes but leaves their persisted
+    /// state behind.
+    pub async fn shutdown(self) -> MicrosandboxResult<()> {
+        self.inner.closed.store(true, Ordering::Release);
+        let members: Vec<_> = self.inner.idle.lock().unwrap().drain(..).collect();
+
+        let results =
+            futures::future::join_all(members.into_iter().map(|m| destroy(m.sandbox))).await;
+        results.into_iter().collect()
+    }
+}
+
+impl SandboxPoolBuilder {
+    /// Start building a pool from a sandbox config template.
+    pub fn new(template: SandboxConfig) -> Self {
+        Self {
+            template,
+            size: 1,
+            release_policy: ReleasePolicy::default(),
+        }
+    }
+
+    /// Number of sandboxes to keep booted (default: 1).
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// What to do with a sandbox when its lease ends (default: recycle).
+    pub fn release_policy(mut self, policy: ReleasePolicy) -> Self {
+        self.release_policy = policy;
+        self
+    }
+
+    /// Boot the initial members and return the pool.
+    ///
+    /// If any member fails to boot, the ones that did are destroyed and the
+    /// error is returned.
+    pub async fn build(self) -> MicrosandboxResult<SandboxPool> {
+        if self.template.name.is_empty() {
+            return Err(crate::MicrosandboxError::InvalidConfig(
+                "sandbox pool template must have a name".into(),
+            ));
+        }
+
+        let inner = Arc::new(PoolInner {
+            template: self.template,
+            size: self.size,
+            release_policy: self.release_policy,
+            idle: Mutex::new(VecDeque::with_capacity(self.size)),
+            pending: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        });
+
+        let results =
+            futures::future::join_all((0..inner.size).map(|_| inner.create_member())).await;
+
+        let mut members = Vec::with_capacity(results.len());
+        let mut first_error = None;
+        for result in results {
+            match result {
+                Ok(member) => members.push(member),
+                Err(e) if first_error.is_none() => first_error = Some(e),
+                Err(e) => tracing::warn!(error = %e, "sandbox pool: member failed to boot"),
+            }
+        }
+
+        if let Some(e) = first_error {
+            futures::future::join_all(members.into_iter().map(|m| destroy(m.sandbox))).await;
+            return Err(e);
+        }
+
+        inner.idle.lock().unwrap().extend(members);
+        Ok(SandboxPool { inner })
+    }
+}
+
+impl LeaseOptionsBuilder {
+    /// Name this lease. Recorded under the [`POOL_LEASE_LABEL`] label.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.options.name = Some(name.into());
+        self
+    }
+
+    /// Set an environment variable for commands run during this lease.
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Set multiple environment variables.
+    pub fn envs(
+        mut self,
+        vars: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.options
+            .env
+            .extend(vars.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Set a label for this lease.
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Build the lease options.
+    pub fn build(self) -> LeaseOptions {
+        self.options
+    }
+}
+
+impl PooledSandbox {
+    /// Name given to this lease, if any.
+    pub fn lease_name(&self) -> Option<&str> {
+        self.lease_name.as_deref()
+    }
+
+    /// Hand the sandbox back to the pool.
+    ///
+    /// Waits for the sandbox to stop. Recycling and replacement boots run in
+    /// the background.
+    pub async fn release(mut self) -> MicrosandboxResult<()> {
+        let sandbox = self.sandbox.take().expect("lease holds a sandbox");
+        let base = std::mem::take(&mut self.base);
+        PoolInner::reclaim(&self.pool, sandbox, base).await
+    }
+}
+
+impl PendingGuard {
+    fn new(inner: &Arc<PoolInner>) -> Self {
+        inner.pending.fetch_add(1, Ordering::AcqRel);
+        Self(Arc::clone(inner))
+    }
+}
+
+impl PoolInner {
+    /// Boot a new member from the template.
+    async fn create_member(&self) -> MicrosandboxResult<PoolMember> {
+        let mut config = self.template.clone();
+        config.name = member_name(&self.template.name);
+        config
+            .labels
+            .insert(POOL_LABEL.into(), self.template.name.clone());
+
+        let sandbox = Sandbox::create(config).await?;
+        let base = sandbox.config.clone();
+        Ok(PoolMember { sandbox, base })
+    }
+
+    /// Boot members in the background until idle plus pending reaches `size`.
+    fn refill(self: &Arc<Self>) {
+        if self.closed.load(Ordering::Acquire) {
+            return;
+        }
+
+        let idle = self.idle.lock().unwrap().len();
+        let pending = self.pending.load(Ordering::Acquire);
+        let deficit = self.size.saturating_sub(idle + pending);
+        for _ in 0..deficit {
+            let guard = PendingGuard::new(self);
+            let inner = Arc::clone(self);
+            tokio::spawn(async move {
+                let result = inner.create_member().await;
+                drop(guard);
+                match result {
+                    Ok(member) => inner.return_idle(member).await,
+                    Err(e) => tracing::warn!(
+                        pool = %inner.template.name,
+                        error = %e,
+                        "sandbox pool: failed to boot replacement"
+                    ),
+                }
+            });
+        }
+    }
+
+    /// Put a booted member back on the idle queue, or destroy it if the pool
+    /// is closed or already full.
+    async fn return_idle(&self, member: PoolMember) {
+        let surplus = {
+            let mut idle = self.idle.lock().unwrap();
+            if self.closed.load(Ordering::Acquire) || idle.len() >= self.size {
+                Some(member)
+            } else {
+                idle.push_back(member);
+                None
+            }
+        };
+
+        if let Some(member) = surplus
+            && let Err(e) = destroy(member.sandbox).await
+        {
+            tracing::warn!(error = %e, "sandbox pool: failed to destroy surplus sandbox");
+        }
+    }
+
+    /// Apply lease overrides to a member and wrap it.
+    async fn lease(
+        self: &Arc<Self>,
+        member: PoolMember,
+        opts: LeaseOptions,
+    ) -> MicrosandboxResult<PooledSandbox> {
+        let PoolMember { mut sandbox, base } = member;
+        let lease_name = opts.name.clone();
+        let config = apply_lease(&base, opts);
+
+        let db =
+            crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+        if let Err(e) = super::update_sandbox_config(db, sandbox.db_id, &config).await {
+            let _ = destroy(sandbox).await;
+            return Err(e);
+        }
+        sandbox.config = config;
+
+        Ok(PooledSandbox {
+            sandbox: Some(sandbox),
+            base,
+            lease_name,
+            pool: Arc::clone(self),
+        })
+    }
+
+    /// Recycle or destroy a released sandbox according to the pool policy.
+    async fn reclaim(
+        self: &Arc<Self>,
+        sandbox: Sandbox,
+        base: SandboxConfig,
+    ) -> MicrosandboxResult<()> {
+        let recyclable = matches!(base.image, RootfsSource::Oci(_));
+        if self.closed.load(Ordering::Acquire)
+            || self.release_policy == ReleasePolicy::Destroy
+            || !recyclable
+        {
+            let result = destroy(sandbox).await;
+            self.refill();
+            return result;
+        }
+
+        let guard = PendingGuard::new(self);
+        if let Err(e) = reset(&sandbox, &base).await {
+            drop(guard);
+            let _ = destroy(sandbox).await;
+            self.refill();
+            return Err(e);
+        }
+        drop(sandbox);
+
+        // Boot in the background so `release` returns once the old VM is down.
+        let inner = Arc::clone(self);
+        tokio::spawn(async move {
+            let result = Sandbox::start_with_mode(&base.name, SpawnMode::Attached).await;
+            drop(guard);
+            match result {
+                Ok(sandbox) => inner.return_idle(PoolMember { sandbox, base }).await,
+                Err(e) => {
+                    tracing::warn!(
+                        sandbox = %base.name,
+                        error = %e,
+                        "sandbox pool: failed to reboot recycled sandbox"
+                    );
+                    let _ = Sandbox::remove(&base.name).await;
+                    inner.refill();
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Deref for PooledSandbox {
+    type Target = Sandbox;
+
+    fn deref(&self) -> &Sandbox {
+        self.sandbox.as_ref().expect("lease holds a sandbox")
+    }
+}
+
+impl Drop for PooledSandbox {
+    fn drop(&mut self) {
+        let Some(sandbox) = self.sandbox.take() else {
+            return;
+        };
+
+        // Without a runtime the sandbox handle's own drop sends SIGTERM.
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let pool = Arc::clone(&self.pool);
+        let base = std::mem::take(&mut self.base);
+        runtime.spawn(async move {
+            if let Err(e) = PoolInner::reclaim(&pool, sandbox, base).await {
+                tracing::warn!(error = %e, "sandbox pool: failed to reclaim dropped lease");
+            }
+        });
+    }
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Generate a unique member name under the pool name.
+fn member_name(pool: &str) -> String {
+    let suffix: u32 = rand::rng().random();
+    format!("{pool}-{suffix:08x}")
+}
+
+/// Layer lease overrides over a member's base config.
+fn apply_lease(base: &SandboxConfig, opts: LeaseOptions) -> SandboxConfig {
+    let mut config = base.clone();
+    config.env = merge_env_pairs(&base.env, &opts.env);
+    config.labels.extend(opts.labels);
+    if let Some(name) = opts.name {
+        config.labels.insert(POOL_LEASE_LABEL.into(), name);
+    }
+    config
+}
+
+/// Stop a member and restore its on-disk state to the freshly created one.
+///
+/// Restores the base config, discards the overlay upper and staging dirs,
+/// and re-applies rootfs patches.
+async fn reset(sandbox: &Sandbox, base: &SandboxConfig) -> MicrosandboxResult<()> {
+    sandbox.stop_and_wait().await?;
+
+    let db = crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+    super::update_sandbox_config(db, sandbox.db_id, base).await?;
+
+    let sandbox_dir = crate::config::config().sandboxes_dir().join(&base.name);
+    for dir in ["rw", "staging"] {
+        let path = sandbox_dir.join(dir);
+        super::remove_dir_if_exists(&path)?;
+        tokio::fs::create_dir_all(&path).await?;
+    }
+
+    patch::apply_patches(
+        &base.image,
+        &base.patches,
+        &sandbox_dir,
+        &base.resolved_rootfs_layers,
+    )
+    .await
+}
+
+/// Stop a sandbox and remove its persisted state.
+async fn destroy(sandbox: Sandbox) -> MicrosandboxResult<()> {
+    if sandbox.stop_and_wait().await.is_err() {
+        let _ = sandbox.kill().await;
+        let _ = sandbox.wait().await;
+    }
+    sandbox.remove_persisted().await
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_lease_layers_env_and_labels() {
+        let base = SandboxConfig {
+            name: "worker-0000abcd".into(),
+            env: vec![
+                ("PATH".into(), "/usr/bin".into()),
+                ("MODE".into(), "idle".into()),
+            ],
+            labels: HashMap::from([(POOL_LABEL.into(), "worker".into())]),
+            ..Default::default()
+        };
+
+        let opts = LeaseOptionsBuilder::default()
+            .name("job-42")
+            .env("MODE", "run")
+            .env("JOB_ID", "42")
+            .label("tenant", "acme")
+            .build();
+        let config = apply_lease(&base, opts);
+
+        assert_eq!(config.name, "worker-0000abcd");
+        assert_eq!(
+            config.env,
+            vec![
+                ("PATH".into(), "/usr/bin".into()),
+                ("MODE".into(), "run".into()),
+                ("JOB_ID".into(), "42".into()),
+            ]
+        );
+        assert_eq!(config.labels.get(POOL_LABEL).unwrap(), "worker");
+        assert_eq!(config.labels.get(POOL_LEASE_LABEL).unwrap(), "job-42");
+        assert_eq!(config.labels.get("tenant").unwrap(), "acme");
+    }
+
+    #[test]
+    fn test_apply_lease_without_overrides_keeps_base() {
+        let base = SandboxConfig {
+            name: "worker-0000abcd".into(),
+            env: vec![("A".into(), "1".into())],
+            ..Default::default()
+        };
+
+        let config = apply_lease(&base, LeaseOptions::default());
+        assert_eq!(config.env, base.env);
+        assert!(!config.labels.contains_key(POOL_LEASE_LABEL));
+    }
+
+    #[test]
+    fn test_member_name_is_prefixed_and_unique() {
+        let a = member_name("worker");
+        let b = member_name("worker");
+        assert!(a.starts_with("worker-"));
+        assert_eq!(a.len(), "worker-".len() + 8);
+        assert_ne!(a, b);
+    }
+}
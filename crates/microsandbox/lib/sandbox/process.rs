@@ -0,0 +1,53 @@
+//! Listing and signalling guest processes by PID.
+
+use microsandbox_protocol::{
+    message::{Message, MessageType},
+    process::{ProcessOp, ProcessRequest, ProcessResponse},
+    stats::GuestProcessStats,
+};
+
+use crate::{MicrosandboxError, MicrosandboxResult};
+
+use super::Sandbox;
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Sandbox {
+    /// List every process running in the guest, sorted by PID.
+    ///
+    /// This includes processes the host did not start, such as background
+    /// jobs left behind by a shell. CPU usage is measured the same way as
+    /// in [`guest_stats`](Self::guest_stats) and shares its baseline.
+    pub async fn processes(&self) -> MicrosandboxResult<Vec<GuestProcessStats>> {
+        self.process_request(ProcessOp::List).await
+    }
+
+    /// Send a Unix signal (e.g. `libc::SIGTERM`) to any guest process.
+    ///
+    /// Unlike [`ExecHandle::signal`](super::exec::ExecHandle::signal), the
+    /// target does not need to belong to an exec session. Signal `0` only
+    /// checks that the process exists. Process groups (`pid <= 0`) and
+    /// agentd itself cannot be targeted.
+    pub async fn signal_pid(&self, pid: i32, signal: i32) -> MicrosandboxResult<()> {
+        self.process_request(ProcessOp::Signal { pid, signal })
+            .await
+            .map(drop)
+    }
+
+    async fn process_request(&self, op: ProcessOp) -> MicrosandboxResult<Vec<GuestProcessStats>> {
+        let req = ProcessRequest { op };
+        let msg = Message::with_payload(MessageType::ProcessRequest, 0, &req)?;
+        let resp: ProcessResponse = self.client.request(msg).await?.payload()?;
+
+        if !resp.ok {
+            return Err(MicrosandboxError::Runtime(format!(
+                "guest process: {}",
+                resp.error.unwrap_or_else(|| "unknown error".into())
+            )));
+        }
+
+        Ok(resp.processes)
+    }
+}
@@ -0,0 +1,666 @@
+//! Restart policies for detached sandboxes.
+//!
+//! A detached sandbox whose [`RestartPolicy`] is not `no` gets a supervisor:
+//! a background `msb supervise` process that waits for the sandbox to stop
+//! and starts it again when the policy allows, backing off exponentially
+//! between attempts. One supervisor runs per sandbox, guarded by a lock file
+//! in the sandbox directory. The reaper re-spawns supervisors that are
+//! missing, e.g. after a host reboot.
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    os::fd::AsRawFd,
+    path::Path,
+    process::Stdio,
+    str::FromStr,
+    time::Duration,
+};
+
+use chrono::NaiveDateTime;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    MicrosandboxError, MicrosandboxResult,
+    db::entity::{
+        run::{self as run_entity, TerminationReason},
+        sandbox as sandbox_entity,
+    },
+    runtime::SpawnMode,
+};
+
+use super::{Sandbox, SandboxConfig, SandboxStatus};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Delay before the first automatic restart.
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+/// Upper bound on the delay between automatic restarts.
+const BACKOFF_MAX: Duration = Duration::from_secs(300);
+
+/// A run that stayed up at least this long restarts after [`BACKOFF_BASE`]
+/// again instead of continuing the backoff.
+const BACKOFF_RESET_AFTER: Duration = Duration::from_secs(60);
+
+/// How often the supervisor checks whether a running sandbox has exited.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lock file held by the sandbox's supervisor, relative to the sandbox directory.
+const SUPERVISOR_LOCK: &str = "supervisor.lock";
+
+/// Supervisor log file, relative to the sandbox log directory.
+const SUPERVISOR_LOG: &str = "supervisor.log";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Whether a detached sandbox is started again after it stops.
+///
+/// Written as `no`, `on-failure`, `on-failure:MAX`, `always` or
+/// `unless-stopped`. A stop requested through `msb stop` or the SDK is never
+/// undone, except that `always` starts the sandbox again after a host reboot.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum RestartPolicy {
+    /// Never restart.
+    #[default]
+    No,
+
+    /// Restart when the run failed: a non-zero exit, a crash, or a run ended
+    /// by `max_duration` or a resource limit.
+    OnFailure {
+        /// Give up after this many consecutive automatic restarts.
+        /// `None` retries forever.
+        max_retries: Option<u32>,
+    },
+
+    /// Restart whenever the sandbox stops, including after a host reboot.
+    Always,
+
+    /// Restart whenever the sandbox stops, unless the stop was requested.
+    UnlessStopped,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RestartPolicy {
+    /// Whether the policy ever restarts the sandbox.
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::No
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::No => f.write_str("no"),
+            Self::OnFailure { max_retries: None } => f.write_str("on-failure"),
+            Self::OnFailure {
+                max_retries: Some(max),
+            } => write!(f, "on-failure:{max}"),
+            Self::Always => f.write_str("always"),
+            Self::UnlessStopped => f.write_str("unless-stopped"),
+        }
+    }
+}
+
+impl FromStr for RestartPolicy {
+    type Err = MicrosandboxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, max) = match s.trim().split_once(':') {
+            Some((name, max)) => (name, Some(max)),
+            None => (s.trim(), None),
+        };
+
+        match (name, max) {
+            ("no", None) => Ok(Self::No),
+            ("always", None) => Ok(Self::Always),
+            ("unless-stopped", None) => Ok(Self::UnlessStopped),
+            ("on-failure", None) => Ok(Self::OnFailure { max_retries: None }),
+            ("on-failure", Some(max)) => {
+                let max = max.trim().parse().map_err(|_| {
+                    MicrosandboxError::InvalidConfig(format!(
+                        "invalid restart policy '{s}': max retries must be a non-negative integer"
+                    ))
+                })?;
+                Ok(Self::OnFailure {
+                    max_retries: Some(max),
+                })
+            }
+            _ => Err(MicrosandboxError::InvalidConfig(format!(
+                "invalid restart policy '{s}': expected no, on-failure[:MAX], always or unless-stopped"
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for RestartPolicy {
+    type Error = MicrosandboxError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<RestartPolicy> for String {
+    fn from(policy: RestartPolicy) -> Self {
+        policy.to_string()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Supervise a sandbox: wait for it to stop and start it again while its
+/// restart policy allows.
+///
+/// Returns once the policy gives up, the stop was requested, or the sandbox
+/// is removed. Returns immediately if another supervisor already holds the
+/// sandbox's lock. This is the body of the hidden `msb supervise` command.
+pub async fn supervise(name: &str) -> MicrosandboxResult<()> {
+    let sandbox_dir = crate::config::config().sandboxes_dir().join(name);
+    if !sandbox_dir.is_dir() {
+        return Ok(());
+    }
+
+    let Some(_lock) = try_lock(&sandbox_dir.join(SUPERVISOR_LOCK))? else {
+        tracing::debug!(sandbox = name, "supervisor already running");
+        return Ok(());
+    };
+
+    let db = crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+    loop {
+        let sandbox = match super::load_sandbox_record_reconciled(db, name).await {
+            Ok(sandbox) => sandbox,
+            Err(MicrosandboxError::SandboxNotFound(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let config: SandboxConfig = serde_json::from_str(&sandbox.config)?;
+        if !config.restart_policy.is_enabled() {
+            return Ok(());
+        }
+
+        if !matches!(
+            sandbox.status,
+            SandboxStatus::Stopped | SandboxStatus::Crashed
+        ) {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            continue;
+        }
+
+        let Some(run) = latest_run(db, sandbox.id).await? else {
+            return Ok(());
+        };
+        let now = chrono::Utc::now().naive_utc();
+        let Some(delay) = restart_delay(
+            config.restart_policy,
+            sandbox.status,
+            &run,
+            now,
+            host_boot_time(),
+        ) else {
+            tracing::info!(sandbox = name, policy = %config.restart_policy, "not restarting");
+            return Ok(());
+        };
+
+        // Sleep, then re-evaluate: the sandbox may have been stopped,
+        // started or removed in the meantime.
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+            continue;
+        }
+
+        let restart_count = u32::try_from(consecutive_restarts(&run)).unwrap_or(0) + 1;
+        tracing::info!(sandbox = name, restart_count, "restarting sandbox");
+        match Sandbox::start_run(name, SpawnMode::Detached, restart_count).await {
+            Ok(sandbox) => sandbox.detach().await,
+            Err(MicrosandboxError::SandboxStillRunning(_)) => {}
+            Err(e) => {
+                // The failed attempt left no run behind, so back off by hand.
+                tracing::warn!(sandbox = name, error = %e, "restart failed");
+                tokio::time::sleep(BACKOFF_MAX).await;
+            }
+        }
+    }
+}
+
+/// Spawn a supervisor for the sandbox unless one is already running.
+pub(super) fn ensure_supervisor(name: &str) -> MicrosandboxResult<()> {
+    let global = crate::config::config();
+    let sandbox_dir = global.sandboxes_dir().join(name);
+    if try_lock(&sandbox_dir.join(SUPERVISOR_LOCK))?.is_none() {
+        return Ok(());
+    }
+
+    let log_dir = sandbox_dir.join("logs");
+    std::fs::create_dir_all(&log_dir)?;
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_dir.join(SUPERVISOR_LOG))?;
+
+    // Tokio reaps the child in the background once it is dropped, so
+    // long-lived SDK processes don't collect zombies.
+    tokio::process::Command::new(crate::config::resolve_msb_path()?)
+        .arg("supervise")
+        .arg(name)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(log)
+        .process_group(0)
+        .spawn()?;
+
+    tracing::debug!(sandbox = name, "spawned restart supervisor");
+    Ok(())
+}
+
+/// Spawn supervisors for sandboxes whose restart policy still has work to
+/// do: running sandboxes and stopped ones due for a restart.
+pub(super) async fn ensure_supervisors(db: &DatabaseConnection) -> MicrosandboxResult<()> {
+    let now = chrono::Utc::now().naive_utc();
+    let boot_time = host_boot_time();
+
+    for sandbox in sandbox_entity::Entity::find().all(db).await? {
+        let Ok(config) = serde_json::from_str::<SandboxConfig>(&sandbox.config) else {
+            continue;
+        };
+        if !config.restart_policy.is_enabled() {
+            continue;
+        }
+
+        let wanted = match sandbox.status {
+            SandboxStatus::Running | SandboxStatus::Draining => true,
+            SandboxStatus::Stopped | SandboxStatus::Crashed => {
+                latest_run(db, sandbox.id).await?.is_some_and(|run| {
+                    restart_delay(config.restart_policy, sandbox.status, &run, now, boot_time)
+                        .is_some()
+                })
+            }
+            _ => false,
+        };
+
+        if wanted && let Err(e) = ensure_supervisor(&sandbox.name) {
+            tracing::debug!(sandbox = %sandbox.name, error = %e, "failed to spawn restart supervisor");
+        }
+    }
+
+    Ok(())
+}
+
+/// Best-effort [`mark_stop_requested`] for stop paths: failures are logged
+/// so the stop itself still goes out.
+pub(super) async fn record_stop_request(sandbox_id: i32, name: &str) {
+    let result = match crate::db::init_global(Some(
+        crate::config::config().database.max_connections,
+    ))
+    .await
+    {
+        Ok(db) => mark_stop_requested(db, sandbox_id).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        tracing::warn!(sandbox = name, error = %e, "failed to record stop request");
+    }
+}
+
+/// Record that the sandbox's latest run was stopped on request, so its
+/// restart policy leaves it stopped. Also cancels a pending restart.
+async fn mark_stop_requested(db: &DatabaseConnection, sandbox_id: i32) -> MicrosandboxResult<()> {
+    if let Some(run) = latest_run(db, sandbox_id).await?
+        && !run.stop_requested
+    {
+        let mut run: run_entity::ActiveModel = run.into();
+        run.stop_requested = Set(true);
+        run_entity::Entity::update(run).exec(db).await?;
+    }
+
+    Ok(())
+}
+
+/// The most recent run of a sandbox, whatever its status.
+pub(super) async fn latest_run(
+    db: &DatabaseConnection,
+    sandbox_id: i32,
+) -> MicrosandboxResult<Option<run_entity::Model>> {
+    run_entity::Entity::find()
+        .filter(run_entity::Column::SandboxId.eq(sandbox_id))
+        .order_by_desc(run_entity::Column::Id)
+        .one(db)
+        .await
+        .map_err(Into::into)
+}
+
+/// How long to wait before restarting a sandbox whose latest run is `run`,
+/// or `None` if the policy leaves it stopped.
+fn restart_delay(
+    policy: RestartPolicy,
+    status: SandboxStatus,
+    run: &run_entity::Model,
+    now: NaiveDateTime,
+    boot_time: Option<NaiveDateTime>,
+) -> Option<Duration> {
+    let ended_at = run.terminated_at.or(run.started_at).unwrap_or(now);
+
+    if run.stop_requested {
+        let stopped_before_boot = boot_time.is_some_and(|boot| ended_at < boot);
+        if !(policy == RestartPolicy::Always && stopped_before_boot) {
+            return None;
+        }
+    }
+
+    match policy {
+        RestartPolicy::No => return None,
+        RestartPolicy::OnFailure { max_retries } => {
+            if !is_failure(status, run) {
+                return None;
+            }
+            if max_retries.is_some_and(|max| i64::from(consecutive_restarts(run)) >= i64::from(max))
+            {
+                return None;
+            }
+        }
+        RestartPolicy::Always | RestartPolicy::UnlessStopped => {}
+    }
+
+    let elapsed = (now - ended_at).to_std().unwrap_or_default();
+    Some(backoff(run.restart_count, uptime(run)).saturating_sub(elapsed))
+}
+
+/// Automatic restarts in a row that led up to `run`. A run that stayed up
+/// for [`BACKOFF_RESET_AFTER`] starts a new streak.
+fn consecutive_restarts(run: &run_entity::Model) -> i32 {
+    if uptime(run) >= BACKOFF_RESET_AFTER {
+        0
+    } else {
+        run.restart_count
+    }
+}
+
+/// How long `run` stayed up before it ended.
+fn uptime(run: &run_entity::Model) -> Duration {
+    match (run.started_at, run.terminated_at) {
+        (Some(started), Some(ended)) => (ended - started).to_std().unwrap_or_default(),
+        _ => Duration::ZERO,
+    }
+}
+
+/// Delay before the next restart after a run that was itself restart number
+/// `restart_count` and stayed up for `uptime`.
+fn backoff(restart_count: i32, uptime: Duration) -> Duration {
+    if uptime >= BACKOFF_RESET_AFTER {
+        return BACKOFF_BASE;
+    }
+
+    let exponent = restart_count.clamp(0, 16) as u32;
+    BACKOFF_BASE.saturating_mul(1 << exponent).min(BACKOFF_MAX)
+}
+
+/// Whether a run counts as failed for `on-failure`.
+fn is_failure(status: SandboxStatus, run: &run_entity::Model) -> bool {
+    status == SandboxStatus::Crashed
+        || matches!(
+            run.termination_reason,
+            None | Some(
+                TerminationReason::Failed
+                    | TerminationReason::MaxDurationExceeded
+                    | TerminationReason::ResourceLimitExceeded
+                    | TerminationReason::Unhealthy
+                    | TerminationReason::InternalError
+            )
+        )
+}
+
+/// Take an exclusive lock on `path` without blocking. Returns `None` if
+/// another process holds it.
+fn try_lock(path: &Path) -> MicrosandboxResult<Option<File>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+
+    let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if ret == 0 {
+        return Ok(Some(file));
+    }
+
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(None)
+    } else {
+        Err(err.into())
+    }
+}
+
+/// When the host last booted.
+#[cfg(target_os = "linux")]
+fn host_boot_time() -> Option<NaiveDateTime> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let secs = stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    chrono::DateTime::from_timestamp(secs, 0).map(|t| t.naive_utc())
+}
+
+/// When the host last booted.
+#[cfg(target_os = "macos")]
+fn host_boot_time() -> Option<NaiveDateTime> {
+    let mut boot = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    let mut len = std::mem::size_of::<libc::timeval>();
+    let ret = unsafe {
+        libc::sysctlbyname(
+            c"kern.boottime".as_ptr(),
+            (&mut boot as *mut libc::timeval).cast(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return None;
+    }
+    chrono::DateTime::from_timestamp(boot.tv_sec, 0).map(|t| t.naive_utc())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(1_760_000_000 + secs, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    /// A run that started at `at(0)` and ended at `at(uptime)`.
+    fn run(reason: TerminationReason, uptime: i64, restart_count: i32) -> run_entity::Model {
+        run_entity::Model {
+            id: 1,
+            sandbox_id: 1,
+            pid: Some(42),
+            status: run_entity::RunStatus::Terminated,
+            exit_code: None,
+            exit_signal: None,
+            termination_reason: Some(reason),
+            termination_detail: None,
+            signals_sent: None,
+            started_at: Some(at(0)),
+            terminated_at: Some(at(uptime)),
+            restart_count,
+            stop_requested: false,
+        }
+    }
+
+    #[test]
+    fn test_restart_policy_parses_and_displays() {
+        for spec in [
+            "no",
+            "on-failure",
+            "on-failure:5",
+            "always",
+            "unless-stopped",
+        ] {
+            let policy: RestartPolicy = spec.parse().unwrap();
+            assert_eq!(policy.to_string(), spec);
+        }
+        assert_eq!(
+            "on-failure:3".parse::<RestartPolicy>().unwrap(),
+            RestartPolicy::OnFailure {
+                max_retries: Some(3)
+            }
+        );
+        assert!("sometimes".parse::<RestartPolicy>().is_err());
+        assert!("on-failure:-1".parse::<RestartPolicy>().is_err());
+        assert!("always:3".parse::<RestartPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_restart_policy_serializes_as_string() {
+        let json = serde_json::to_string(&RestartPolicy::OnFailure {
+            max_retries: Some(2),
+        })
+        .unwrap();
+        assert_eq!(json, r#""on-failure:2""#);
+        assert_eq!(
+            serde_json::from_str::<RestartPolicy>(r#""unless-stopped""#).unwrap(),
+            RestartPolicy::UnlessStopped
+        );
+    }
+
+    #[test]
+    fn test_on_failure_restarts_only_failed_runs() {
+        let policy = RestartPolicy::OnFailure { max_retries: None };
+        let stopped = SandboxStatus::Stopped;
+
+        for reason in [
+            TerminationReason::Failed,
+            TerminationReason::MaxDurationExceeded,
+            TerminationReason::ResourceLimitExceeded,
+        ] {
+            assert!(restart_delay(policy, stopped, &run(reason, 5, 0), at(100), None).is_some());
+        }
+        for reason in [TerminationReason::Completed, TerminationReason::IdleTimeout] {
+            assert!(restart_delay(policy, stopped, &run(reason, 5, 0), at(100), None).is_none());
+        }
+
+        let crashed = run(TerminationReason::InternalError, 5, 0);
+        assert!(restart_delay(policy, SandboxStatus::Crashed, &crashed, at(100), None).is_some());
+    }
+
+    #[test]
+    fn test_on_failure_gives_up_after_max_retries() {
+        let policy = RestartPolicy::OnFailure {
+            max_retries: Some(2),
+        };
+        let stopped = SandboxStatus::Stopped;
+
+        let second = run(TerminationReason::Failed, 5, 1);
+        assert!(restart_delay(policy, stopped, &second, at(100), None).is_some());
+        let third = run(TerminationReason::Failed, 5, 2);
+        assert!(restart_delay(policy, stopped, &third, at(100), None).is_none());
+    }
+
+    #[test]
+    fn test_long_run_resets_restart_count() {
+        let policy = RestartPolicy::OnFailure {
+            max_retries: Some(2),
+        };
+        let stopped = SandboxStatus::Stopped;
+
+        // Restart number 2 stayed up for two minutes before failing.
+        let settled = run(TerminationReason::Failed, 120, 2);
+        assert_eq!(consecutive_restarts(&settled), 0);
+        assert_eq!(
+            restart_delay(policy, stopped, &settled, at(120), None),
+            Some(BACKOFF_BASE)
+        );
+
+        let quick = run(TerminationReason::Failed, 5, 2);
+        assert_eq!(consecutive_restarts(&quick), 2);
+
+        let huge = RestartPolicy::OnFailure {
+            max_retries: Some(u32::MAX),
+        };
+        let many = run(TerminationReason::Failed, 5, i32::MAX);
+        assert!(restart_delay(huge, stopped, &many, at(100), None).is_some());
+    }
+
+    #[test]
+    fn test_requested_stop_is_honoured_except_always_after_reboot() {
+        let mut stopped = run(TerminationReason::Completed, 5, 0);
+        stopped.stop_requested = true;
+        let status = SandboxStatus::Stopped;
+        let boot = Some(at(50));
+
+        for policy in [
+            RestartPolicy::OnFailure { max_retries: None },
+            RestartPolicy::Always,
+            RestartPolicy::UnlessStopped,
+        ] {
+            assert!(restart_delay(policy, status, &stopped, at(100), None).is_none());
+        }
+
+        // Stopped before the host rebooted at t=50.
+        assert!(restart_delay(RestartPolicy::Always, status, &stopped, at(100), boot).is_some());
+        assert!(
+            restart_delay(
+                RestartPolicy::UnlessStopped,
+                status,
+                &stopped,
+                at(100),
+                boot
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let quick = Duration::from_secs(5);
+        assert_eq!(backoff(0, quick), Duration::from_secs(1));
+        assert_eq!(backoff(1, quick), Duration::from_secs(2));
+        assert_eq!(backoff(4, quick), Duration::from_secs(16));
+        assert_eq!(backoff(20, quick), BACKOFF_MAX);
+        assert_eq!(backoff(20, BACKOFF_RESET_AFTER), BACKOFF_BASE);
+    }
+
+    #[test]
+    fn test_restart_delay_counts_time_already_waited() {
+        let failed = run(TerminationReason::Failed, 5, 3);
+        let policy = RestartPolicy::Always;
+        let status = SandboxStatus::Stopped;
+
+        // Ended at t=5 with an 8s backoff.
+        assert_eq!(
+            restart_delay(policy, status, &failed, at(8), None),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            restart_delay(policy, status, &failed, at(60), None),
+            Some(Duration::ZERO)
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! Guest-side resource statistics reported by agentd.
+
+use microsandbox_protocol::{
+    message::{Message, MessageType},
+    stats::{GuestStats, StatsRequest, StatsResponse},
+};
+
+use crate::{MicrosandboxError, MicrosandboxResult};
+
+use super::Sandbox;
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Sandbox {
+    /// Get a snapshot of resource usage as seen from inside the guest.
+    ///
+    /// Unlike [`metrics`](Self::metrics), which measures the sandbox process
+    /// from the host, this asks agentd for guest memory, load average,
+    /// filesystem usage and a per-process table read from `/proc`. Process
+    /// CPU usage is measured since the previous call on any handle, so the
+    /// first snapshot reports each process's lifetime average.
+    pub async fn guest_stats(&self) -> MicrosandboxResult<GuestStats> {
+        let req = StatsRequest { processes: true };
+        let msg = Message::with_payload(MessageType::StatsRequest, 0, &req)?;
+        let resp: StatsResponse = self.client.request(msg).await?.payload()?;
+
+        if !resp.ok {
+            return Err(MicrosandboxError::Runtime(format!(
+                "guest stats: {}",
+                resp.error.unwrap_or_else(|| "unknown error".into())
+            )));
+        }
+
+        resp.stats.ok_or_else(|| {
+            MicrosandboxError::Runtime("guest stats: response missing snapshot".into())
+        })
+    }
+}
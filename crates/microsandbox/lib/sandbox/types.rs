@@ -62,16 +62,18 @@ pub enum ImageSource {
     Path(PathBuf),
 }
 
-/// Builder for configuring a disk image rootfs.
+/// Builder for configuring an OCI or disk image rootfs.
 ///
 /// Used with [`crate::sandbox::SandboxBuilder::image_with`]:
 ///
 /// ```ignore
 /// .image_with(|i| i.disk("./ubuntu.qcow2").fstype("ext4"))
+/// .image_with(|i| i.oci("python:3.12").platform("linux/amd64"))
 /// ```
 #[derive(Default)]
 pub struct ImageBuilder {
     source: Option<RootfsSource>,
+    platform: Option<microsandbox_image::Platform>,
     error: Option<crate::MicrosandboxError>,
 }
 
@@ -86,6 +88,7 @@ pub trait IntoImage {
 }
 
 /// A volume mount specification for a sandbox.
+#[derive(Clone)]
 pub enum VolumeMount {
     /// Bind mount a host directory into the guest.
     Bind {
@@ -543,6 +546,34 @@ impl ImageBuilder {
         Self::default()
     }
 
+    /// Use an OCI image reference as the root filesystem.
+    ///
+    /// ```ignore
+    /// .image_with(|i| i.oci("python:3.12"))
+    /// ```
+    pub fn oci(mut self, reference: impl Into<String>) -> Self {
+        self.source = Some(RootfsSource::Oci(reference.into()));
+        self
+    }
+
+    /// Select a platform variant from a multi-platform OCI image
+    /// (e.g. `"linux/amd64"`). Requires [`oci`](Self::oci).
+    ///
+    /// ```ignore
+    /// .image_with(|i| i.oci("python:3.12").platform("linux/arm64"))
+    /// ```
+    pub fn platform(mut self, platform: impl AsRef<str>) -> Self {
+        match platform.as_ref().parse() {
+            Ok(platform) => self.platform = Some(platform),
+            Err(e) => {
+                if self.error.is_none() {
+                    self.error = Some(crate::MicrosandboxError::Image(e));
+                }
+            }
+        }
+        self
+    }
+
     /// Use a disk image file as the root filesystem.
     ///
     /// The format is derived from the file extension:
@@ -603,16 +634,25 @@ impl ImageBuilder {
         self
     }
 
-    /// Consume the builder and return the resolved [`RootfsSource`].
-    pub(crate) fn build(self) -> crate::MicrosandboxResult<RootfsSource> {
+    /// Consume the builder and return the resolved [`RootfsSource`] along
+    /// with the selected platform, if any.
+    pub(crate) fn build(
+        self,
+    ) -> crate::MicrosandboxResult<(RootfsSource, Option<microsandbox_image::Platform>)> {
         if let Some(e) = self.error {
             return Err(e);
         }
-        self.source.ok_or_else(|| {
+        let source = self.source.ok_or_else(|| {
             crate::MicrosandboxError::InvalidConfig(
-                "ImageBuilder: no image source set (call .disk())".into(),
+                "ImageBuilder: no image source set (call .oci() or .disk())".into(),
             )
-        })
+        })?;
+        if self.platform.is_some() && !matches!(source, RootfsSource::Oci(_)) {
+            return Err(crate::MicrosandboxError::InvalidConfig(
+                "platform() only applies to OCI images".into(),
+            ));
+        }
+        Ok((source, self.platform))
     }
 }
 
@@ -890,7 +930,7 @@ mod tests {
 
     #[test]
     fn test_image_builder_disk_with_fstype() {
-        let rootfs = ImageBuilder::new()
+        let (rootfs, _) = ImageBuilder::new()
             .disk("./test.qcow2")
             .fstype("ext4")
             .build()
@@ -906,7 +946,7 @@ mod tests {
 
     #[test]
     fn test_image_builder_disk_without_fstype() {
-        let rootfs = ImageBuilder::new().disk("./test.raw").build().unwrap();
+        let (rootfs, _) = ImageBuilder::new().disk("./test.raw").build().unwrap();
         match rootfs {
             RootfsSource::DiskImage { format, fstype, .. } => {
                 assert_eq!(format, DiskImageFormat::Raw);
@@ -916,6 +956,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_image_builder_oci_with_platform() {
+        let (rootfs, platform) = ImageBuilder::new()
+            .oci("python:3.12")
+            .platform("linux/amd64")
+            .build()
+            .unwrap();
+        assert!(matches!(rootfs, RootfsSource::Oci(ref r) if r == "python:3.12"));
+        assert_eq!(platform.unwrap().to_string(), "linux/amd64");
+    }
+
+    #[test]
+    fn test_image_builder_platform_rejects_disk_and_malformed() {
+        let result = ImageBuilder::new()
+            .disk("./test.raw")
+            .platform("linux/amd64")
+            .build();
+        assert!(result.is_err());
+
+        let result = ImageBuilder::new().oci("alpine").platform("amd64").build();
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_image_builder_bad_extension_errors() {
         let result = ImageBuilder::new().disk("./test.txt").build();
@@ -0,0 +1,359 @@
+//! Label selectors for filtering sandboxes, volumes, and images.
+//!
+//! A selector is a comma-separated list of requirements that must all hold:
+//!
+//! - `key=value` (or `key==value`): the label is present with that value.
+//! - `key!=value`: the label is absent or has a different value.
+//! - `key`: the label is present.
+//! - `!key`: the label is absent.
+//! - `key in (a, b)`: the label is present with one of the values.
+//! - `key notin (a, b)`: the label is absent or has none of the values.
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::{MicrosandboxError, MicrosandboxResult};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A parsed label selector. The empty selector matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    requirements: Vec<LabelRequirement>,
+}
+
+/// A single requirement within a [`LabelSelector`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelRequirement {
+    /// The label is present with the given value.
+    Equals(String, String),
+
+    /// The label is absent or has a different value.
+    NotEquals(String, String),
+
+    /// The label is present.
+    Exists(String),
+
+    /// The label is absent.
+    NotExists(String),
+
+    /// The label is present with one of the given values.
+    In(String, Vec<String>),
+
+    /// The label is absent or has none of the given values.
+    NotIn(String, Vec<String>),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl LabelSelector {
+    /// Parse a selector expression.
+    pub fn parse(expr: &str) -> MicrosandboxResult<Self> {
+        let mut requirements = Vec::new();
+        for clause in split_clauses(expr)? {
+            requirements.push(parse_requirement(clause)?);
+        }
+        Ok(Self { requirements })
+    }
+
+    /// The requirements in this selector.
+    pub fn requirements(&self) -> &[LabelRequirement] {
+        &self.requirements
+    }
+
+    /// Whether this selector has no requirements.
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    /// Add all requirements from `other` to this selector.
+    pub fn and(mut self, other: LabelSelector) -> Self {
+        self.requirements.extend(other.requirements);
+        self
+    }
+
+    /// Whether the given labels satisfy every requirement.
+    pub fn matches<K, V>(&self, labels: impl IntoIterator<Item = (K, V)>) -> bool
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        if self.requirements.is_empty() {
+            return true;
+        }
+
+        let labels: HashMap<String, String> = labels
+            .into_iter()
+            .map(|(k, v)| (k.as_ref().to_string(), v.as_ref().to_string()))
+            .collect();
+        self.requirements.iter().all(|req| req.matches(&labels))
+    }
+}
+
+impl LabelRequirement {
+    /// Whether the given labels satisfy this requirement.
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        match self {
+            Self::Equals(key, value) => labels.get(key) == Some(value),
+            Self::NotEquals(key, value) => labels.get(key) != Some(value),
+            Self::Exists(key) => labels.contains_key(key),
+            Self::NotExists(key) => !labels.contains_key(key),
+            Self::In(key, values) => labels.get(key).is_some_and(|v| values.contains(v)),
+            Self::NotIn(key, values) => !labels.get(key).is_some_and(|v| values.contains(v)),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl FromStr for LabelSelector {
+    type Err = MicrosandboxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, req) in self.requirements.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{req}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LabelRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Equals(key, value) => write!(f, "{key}={value}"),
+            Self::NotEquals(key, value) => write!(f, "{key}!={value}"),
+            Self::Exists(key) => write!(f, "{key}"),
+            Self::NotExists(key) => write!(f, "!{key}"),
+            Self::In(key, values) => write!(f, "{key} in ({})", values.join(",")),
+            Self::NotIn(key, values) => write!(f, "{key} notin ({})", values.join(",")),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Split a selector on top-level commas, keeping `in (...)` value lists intact.
+fn split_clauses(expr: &str) -> MicrosandboxResult<Vec<&str>> {
+    let mut clauses = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, c) in expr.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| invalid(expr, "unbalanced ')'"))?;
+            }
+            ',' if depth == 0 => {
+                clauses.push(&expr[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 0 {
+        return Err(invalid(expr, "unbalanced '('"));
+    }
+    clauses.push(&expr[start..]);
+
+    if clauses.len() == 1 && clauses[0].trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(clauses)
+}
+
+/// Parse one requirement clause.
+fn parse_requirement(clause: &str) -> MicrosandboxResult<LabelRequirement> {
+    let clause = clause.trim();
+    if clause.is_empty() {
+        return Err(invalid(clause, "empty requirement"));
+    }
+
+    if let Some(key) = clause.strip_prefix('!') {
+        return Ok(LabelRequirement::NotExists(parse_key(key, clause)?));
+    }
+
+    if let Some((key, value)) = clause.split_once("!=") {
+        return Ok(LabelRequirement::NotEquals(
+            parse_key(key, clause)?,
+            parse_value(value, clause)?,
+        ));
+    }
+
+    if let Some((key, value)) = clause.split_once("==").or_else(|| clause.split_once('=')) {
+        return Ok(LabelRequirement::Equals(
+            parse_key(key, clause)?,
+            parse_value(value, clause)?,
+        ));
+    }
+
+    if let Some(open) = clause.find('(') {
+        let head = clause[..open].trim();
+        let list = clause[open + 1..]
+            .strip_suffix(')')
+            .ok_or_else(|| invalid(clause, "expected ')' at end of value list"))?;
+        let values = list
+            .split(',')
+            .map(|v| parse_value(v, clause))
+            .collect::<MicrosandboxResult<Vec<_>>>()?;
+
+        let (key, op) = head
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(|| invalid(clause, "expected 'in' or 'notin'"))?;
+        let key = parse_key(key, clause)?;
+        return match op {
+            "in" => Ok(LabelRequirement::In(key, values)),
+            "notin" => Ok(LabelRequirement::NotIn(key, values)),
+            _ => Err(invalid(clause, "expected 'in' or 'notin'")),
+        };
+    }
+
+    Ok(LabelRequirement::Exists(parse_key(clause, clause)?))
+}
+
+fn parse_key(key: &str, clause: &str) -> MicrosandboxResult<String> {
+    let key = key.trim();
+    let valid = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '/'));
+    if !valid {
+        return Err(invalid(clause, &format!("invalid label key '{key}'")));
+    }
+    Ok(key.to_string())
+}
+
+fn parse_value(value: &str, clause: &str) -> MicrosandboxResult<String> {
+    let value = value.trim();
+    if value
+        .chars()
+        .any(|c| matches!(c, '(' | ')' | ',' | '=' | '!'))
+    {
+        return Err(invalid(clause, &format!("invalid label value '{value}'")));
+    }
+    Ok(value.to_string())
+}
+
+fn invalid(expr: &str, reason: &str) -> MicrosandboxError {
+    MicrosandboxError::InvalidLabelSelector(format!("{reason} in '{expr}'"))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_all_requirement_forms() {
+        let selector = LabelSelector::parse(
+            "env=prod, tier==web,team!=ops,session,!canary,region in (us, eu),zone notin (a)",
+        )
+        .unwrap();
+        assert_eq!(
+            selector.requirements(),
+            &[
+                LabelRequirement::Equals("env".into(), "prod".into()),
+                LabelRequirement::Equals("tier".into(), "web".into()),
+                LabelRequirement::NotEquals("team".into(), "ops".into()),
+                LabelRequirement::Exists("session".into()),
+                LabelRequirement::NotExists("canary".into()),
+                LabelRequirement::In("region".into(), vec!["us".into(), "eu".into()]),
+                LabelRequirement::NotIn("zone".into(), vec!["a".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_selector_matches_everything() {
+        let selector = LabelSelector::parse("  ").unwrap();
+        assert!(selector.is_empty());
+        assert!(selector.matches(labels(&[])));
+        assert!(selector.matches(labels(&[("a", "b")])));
+    }
+
+    #[test]
+    fn test_matches_equality_and_existence() {
+        let selector = LabelSelector::parse("session=abc,!canary").unwrap();
+        assert!(selector.matches(labels(&[("session", "abc")])));
+        assert!(!selector.matches(labels(&[("session", "abc"), ("canary", "")])));
+        assert!(!selector.matches(labels(&[("session", "xyz")])));
+        assert!(!selector.matches(labels(&[])));
+    }
+
+    #[test]
+    fn test_not_equals_matches_missing_label() {
+        let selector = LabelSelector::parse("team!=ops").unwrap();
+        assert!(selector.matches(labels(&[])));
+        assert!(selector.matches(labels(&[("team", "dev")])));
+        assert!(!selector.matches(labels(&[("team", "ops")])));
+    }
+
+    #[test]
+    fn test_matches_set_based() {
+        let selector = LabelSelector::parse("region in (us,eu),zone notin (b)").unwrap();
+        assert!(selector.matches(labels(&[("region", "eu"), ("zone", "a")])));
+        assert!(selector.matches(labels(&[("region", "us")])));
+        assert!(!selector.matches(labels(&[("region", "ap")])));
+        assert!(!selector.matches(labels(&[("region", "us"), ("zone", "b")])));
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let expr = "env=prod,team!=ops,session,!canary,region in (us,eu)";
+        let selector = LabelSelector::parse(expr).unwrap();
+        assert_eq!(selector.to_string(), expr);
+        assert_eq!(
+            LabelSelector::parse(&selector.to_string()).unwrap(),
+            selector
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        for expr in [
+            "region in (us",
+            "region in us)",
+            "region maybe (us)",
+            "=prod",
+            "env=prod,,team=ops",
+            "bad key=1",
+        ] {
+            assert!(
+                matches!(
+                    LabelSelector::parse(expr),
+                    Err(MicrosandboxError::InvalidLabelSelector(_))
+                ),
+                "expected error for {expr:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! OpenTelemetry trace export for sandbox operations.
+//!
+//! Sandbox operations run inside `tracing` spans with stable names:
+//! `sandbox.create`, `image.pull`, `sandbox.boot`, `sandbox.exec` and
+//! `sandbox.fs.*`. Any `tracing-opentelemetry` layer picks them up, and
+//! `Sandbox::exec` forwards the current trace context to the guest process as
+//! `TRACEPARENT`.
+//!
+//! Applications without their own OpenTelemetry setup can export through
+//! [`layer`] (requires the `otel` feature):
+//!
+//! ```ignore
+//! use tracing_subscriber::prelude::*;
+//!
+//! let telemetry = &microsandbox::config::config().telemetry;
+//! tracing_subscriber::registry()
+//!     .with(microsandbox::telemetry::layer(telemetry)?)
+//!     .init();
+//! // ...
+//! microsandbox::telemetry::shutdown();
+//! ```
+
+#[cfg(feature = "otel")]
+use tracing_subscriber::{Layer, registry::LookupSpan};
+
+#[cfg(feature = "otel")]
+use crate::{MicrosandboxError, MicrosandboxResult, config::TelemetryConfig};
+
+//--------------------------------------------------------------------------------------------------
+// Exports
+//--------------------------------------------------------------------------------------------------
+
+pub use microsandbox_runtime::telemetry::{TRACEPARENT_ENV, current_traceparent, shutdown};
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Build a layer exporting spans to the configured OTLP endpoint, or `None`
+/// when no endpoint is configured.
+#[cfg(feature = "otel")]
+pub fn layer<S>(telemetry: &TelemetryConfig) -> MicrosandboxResult<Option<impl Layer<S>>>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    let Some(endpoint) = telemetry.otlp_endpoint.as_deref() else {
+        return Ok(None);
+    };
+
+    microsandbox_runtime::telemetry::layer(endpoint, &telemetry.service_name)
+        .map(Some)
+        .map_err(|e| MicrosandboxError::InvalidConfig(format!("telemetry: {e}")))
+}
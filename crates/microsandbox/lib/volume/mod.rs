@@ -6,12 +6,13 @@
 pub mod fs;
 pub use fs::{VolumeFs, VolumeFsReadStream, VolumeFsWriteSink};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
 
 use crate::{
-    MicrosandboxError, MicrosandboxResult, db::entity::volume as volume_entity, size::Mebibytes,
+    LabelSelector, MicrosandboxError, MicrosandboxResult, db::entity::volume as volume_entity,
+    size::Mebibytes,
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -156,11 +157,19 @@ impl Volume {
     /// Fails with [`MicrosandboxError::VolumeAlreadyExists`] if a volume
     /// with the same name already exists.
     pub async fn create(config: VolumeConfig) -> MicrosandboxResult<Self> {
-        tracing::debug!(name = %config.name, quota_mib = ?config.quota_mib, "Volume::create");
-        validate_volume_name(&config.name)?;
-
         let db =
             crate::db::init_global(Some(crate::config::config().database.max_connections)).await?;
+        Self::create_in(db, &crate::config::config().volumes_dir(), config).await
+    }
+
+    /// Provision a volume against an explicit database and volumes directory.
+    pub(crate) async fn create_in(
+        db: &sea_orm::DatabaseConnection,
+        volumes_dir: &Path,
+        config: VolumeConfig,
+    ) -> MicrosandboxResult<Self> {
+        tracing::debug!(name = %config.name, quota_mib = ?config.quota_mib, "Volume::create");
+        validate_volume_name(&config.name)?;
 
         // Check for existing volume.
         let existing = volume_entity::Entity::find()
@@ -194,7 +203,6 @@ impl Volume {
         volume_entity::Entity::insert(model).exec(db).await?;
 
         // Create the volume directory. If this fails, clean up the DB record.
-        let volumes_dir = crate::config::config().volumes_dir();
         let path = volumes_dir.join(&config.name);
 
         if let Err(e) = tokio::fs::create_dir_all(&path).await {
@@ -240,6 +248,13 @@ impl Volume {
         Ok(models.into_iter().map(VolumeHandle::from_model).collect())
     }
 
+    /// List volumes whose labels match a selector.
+    pub async fn list_matching(selector: &LabelSelector) -> MicrosandboxResult<Vec<VolumeHandle>> {
+        let mut handles = Self::list().await?;
+        handles.retain(|handle| selector.matches(handle.labels().iter().map(|(k, v)| (k, v))));
+        Ok(handles)
+    }
+
     /// Delete a volume's database record and host directory.
     /// Fails with [`MicrosandboxError::VolumeNotFound`] if no such volume exists.
     pub async fn remove(name: &str) -> MicrosandboxResult<()> {
@@ -263,6 +278,24 @@ impl Volume {
         &self.path
     }
 
+    /// Remove a volume created by [`Volume::create_in`] from the same
+    /// database, record first and then directory.
+    pub(crate) async fn remove_in(
+        &self,
+        db: &sea_orm::DatabaseConnection,
+    ) -> MicrosandboxResult<()> {
+        volume_entity::Entity::delete_many()
+            .filter(volume_entity::Column::Name.eq(&self.name))
+            .exec(db)
+            .await?;
+
+        if self.path.exists() {
+            tokio::fs::remove_dir_all(&self.path).await?;
+        }
+
+        Ok(())
+    }
+
     /// Operate on the volume's host-side directory (read, write, list files)
     /// without needing a running sandbox.
     pub fn fs(&self) -> fs::VolumeFs<'_> {
@@ -4,6 +4,12 @@ mod m20260305_000001_create_image_tables;
 mod m20260305_000002_create_sandbox_tables;
 mod m20260305_000003_create_storage_tables;
 mod m20260305_000004_create_sandbox_images_table;
+mod m20261018_000001_add_config_labels;
+mod m20261018_000002_create_sandbox_metric_rollup_table;
+mod m20261018_000003_create_sandbox_event_table;
+mod m20261018_000004_add_run_restart_columns;
+mod m20261018_000005_add_sandbox_health;
+mod m20261018_000006_create_network_table;
 
 use sea_orm_migration::prelude::*;
 
@@ -32,6 +38,12 @@ impl MigratorTrait for Migrator {
             Box::new(m20260305_000002_create_sandbox_tables::Migration),
             Box::new(m20260305_000003_create_storage_tables::Migration),
             Box::new(m20260305_000004_create_sandbox_images_table::Migration),
+            Box::new(m20261018_000001_add_config_labels::Migration),
+            Box::new(m20261018_000002_create_sandbox_metric_rollup_table::Migration),
+            Box::new(m20261018_000003_create_sandbox_event_table::Migration),
+            Box::new(m20261018_000004_add_run_restart_columns::Migration),
+            Box::new(m20261018_000005_add_sandbox_health::Migration),
+            Box::new(m20261018_000006_create_network_table::Migration),
         ]
     }
 }
@@ -0,0 +1,128 @@
+//! Migration: Create the sandbox_metric_rollup table for downsampled metrics history.
+
+use sea_orm_migration::prelude::*;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+pub struct Migration;
+
+//--------------------------------------------------------------------------------------------------
+// Types: Identifiers
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Iden)]
+enum Sandbox {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum SandboxMetricRollup {
+    Table,
+    Id,
+    SandboxId,
+    ResolutionSecs,
+    BucketStart,
+    SampleCount,
+    CpuPercentMin,
+    CpuPercentAvg,
+    CpuPercentMax,
+    MemoryBytesMin,
+    MemoryBytesAvg,
+    MemoryBytesMax,
+    DiskReadBytes,
+    DiskWriteBytes,
+    NetRxBytes,
+    NetTxBytes,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000002_create_sandbox_metric_rollup_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SandboxMetricRollup::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SandboxMetricRollup::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(SandboxMetricRollup::SandboxId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SandboxMetricRollup::ResolutionSecs)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SandboxMetricRollup::BucketStart)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SandboxMetricRollup::SampleCount)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(SandboxMetricRollup::CpuPercentMin).float())
+                    .col(ColumnDef::new(SandboxMetricRollup::CpuPercentAvg).float())
+                    .col(ColumnDef::new(SandboxMetricRollup::CpuPercentMax).float())
+                    .col(ColumnDef::new(SandboxMetricRollup::MemoryBytesMin).big_integer())
+                    .col(ColumnDef::new(SandboxMetricRollup::MemoryBytesAvg).big_integer())
+                    .col(ColumnDef::new(SandboxMetricRollup::MemoryBytesMax).big_integer())
+                    .col(ColumnDef::new(SandboxMetricRollup::DiskReadBytes).big_integer())
+                    .col(ColumnDef::new(SandboxMetricRollup::DiskWriteBytes).big_integer())
+                    .col(ColumnDef::new(SandboxMetricRollup::NetRxBytes).big_integer())
+                    .col(ColumnDef::new(SandboxMetricRollup::NetTxBytes).big_integer())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(SandboxMetricRollup::Table, SandboxMetricRollup::SandboxId)
+                            .to(Sandbox::Table, Sandbox::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // One rollup row per sandbox, resolution and bucket.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_sandbox_metric_rollup_bucket")
+                    .table(SandboxMetricRollup::Table)
+                    .col(SandboxMetricRollup::SandboxId)
+                    .col(SandboxMetricRollup::ResolutionSecs)
+                    .col(SandboxMetricRollup::BucketStart)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SandboxMetricRollup::Table).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,83 @@
+//! Migration: Create the sandbox_event table for lifecycle event history.
+
+use sea_orm_migration::prelude::*;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+pub struct Migration;
+
+//--------------------------------------------------------------------------------------------------
+// Types: Identifiers
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Iden)]
+enum SandboxEvent {
+    Table,
+    Id,
+    SandboxId,
+    SandboxName,
+    EventType,
+    Payload,
+    CreatedAt,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000003_create_sandbox_event_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // No foreign key to sandbox: events outlive the sandbox they describe.
+        manager
+            .create_table(
+                Table::create()
+                    .table(SandboxEvent::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SandboxEvent::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SandboxEvent::SandboxId).integer())
+                    .col(ColumnDef::new(SandboxEvent::SandboxName).text().not_null())
+                    .col(ColumnDef::new(SandboxEvent::EventType).text().not_null())
+                    .col(ColumnDef::new(SandboxEvent::Payload).text().not_null())
+                    .col(
+                        ColumnDef::new(SandboxEvent::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_sandbox_event_created_at")
+                    .table(SandboxEvent::Table)
+                    .col(SandboxEvent::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SandboxEvent::Table).to_owned())
+            .await
+    }
+}
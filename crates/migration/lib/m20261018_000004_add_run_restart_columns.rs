@@ -0,0 +1,84 @@
+//! Migration: Add restart tracking columns to the run table for restart policies.
+
+use sea_orm_migration::prelude::*;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+pub struct Migration;
+
+//--------------------------------------------------------------------------------------------------
+// Types: Identifiers
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Iden)]
+enum Run {
+    Table,
+    RestartCount,
+    StopRequested,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000004_add_run_restart_columns"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite allows one column per ALTER TABLE statement.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Run::Table)
+                    .add_column(
+                        ColumnDef::new(Run::RestartCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Run::Table)
+                    .add_column(
+                        ColumnDef::new(Run::StopRequested)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Run::Table)
+                    .drop_column(Run::StopRequested)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Run::Table)
+                    .drop_column(Run::RestartCount)
+                    .to_owned(),
+            )
+            .await
+    }
+}
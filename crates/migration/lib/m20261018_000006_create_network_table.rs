@@ -0,0 +1,71 @@
+//! Migration: Create the network table for private sandbox networks.
+
+use sea_orm_migration::prelude::*;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+pub struct Migration;
+
+//--------------------------------------------------------------------------------------------------
+// Types: Identifiers
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Iden)]
+enum Network {
+    Table,
+    Id,
+    Name,
+    Subnet,
+    Labels,
+    CreatedAt,
+    UpdatedAt,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20261018_000006_create_network_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Network::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Network::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Network::Name).text().not_null().unique_key())
+                    .col(
+                        ColumnDef::new(Network::Subnet)
+                            .text()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Network::Labels).text())
+                    .col(ColumnDef::new(Network::CreatedAt).date_time())
+                    .col(ColumnDef::new(Network::UpdatedAt).date_time())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Network::Table).to_owned())
+            .await
+    }
+}
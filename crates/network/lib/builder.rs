@@ -5,7 +5,9 @@
 use std::net::IpAddr;
 use std::path::PathBuf;
 
-use crate::config::{InterfaceOverrides, NetworkConfig, PortProtocol, PublishedPort};
+use crate::config::{
+    InterfaceOverrides, NetworkAttachment, NetworkConfig, PortProtocol, PublishedPort,
+};
 use crate::policy::NetworkPolicy;
 use crate::secrets::config::{HostPattern, SecretEntry, SecretInjection, ViolationAction};
 use crate::tls::TlsConfig;
@@ -169,6 +171,31 @@ impl NetworkBuilder {
         self
     }
 
+    /// Join a private network shared with other sandboxes.
+    ///
+    /// Members reach each other by name on the network's subnet, subject to
+    /// each sandbox's policy. The network must already exist.
+    pub fn attach(mut self, network: impl Into<String>) -> Self {
+        self.config.attachment = Some(NetworkAttachment {
+            name: network.into(),
+            aliases: Vec::new(),
+            subnet: None,
+            dir: None,
+            member_name: None,
+        });
+        self
+    }
+
+    /// Add a name other members of the private network can resolve this
+    /// sandbox by, in addition to the sandbox name. Has no effect unless
+    /// [`Self::attach`] was called first.
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        if let Some(ref mut attachment) = self.config.attachment {
+            attachment.aliases.push(alias.into());
+        }
+        self
+    }
+
     /// Consume the builder and return the configuration.
     pub fn build(self) -> NetworkConfig {
         self.config
@@ -4,7 +4,9 @@
 //! for sandbox networking. Designed for the smoltcp in-process engine.
 
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
 
+use ipnetwork::Ipv4Network;
 use serde::{Deserialize, Serialize};
 
 use crate::policy::NetworkPolicy;
@@ -54,6 +56,36 @@ pub struct NetworkConfig {
     /// Max concurrent guest connections. Default: 256.
     #[serde(default)]
     pub max_connections: Option<usize>,
+
+    /// Private network shared with other sandboxes, if any.
+    #[serde(default)]
+    pub attachment: Option<NetworkAttachment>,
+}
+
+/// Membership in a private network shared between sandboxes.
+///
+/// Only `name` and `aliases` are set by the user. The remaining fields are
+/// resolved by the host library before the sandbox is spawned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkAttachment {
+    /// Name of the network to join.
+    pub name: String,
+
+    /// Extra names other members can resolve this sandbox by.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    /// Network subnet. Members get consecutive `/30` blocks within it.
+    #[serde(default)]
+    pub subnet: Option<Ipv4Network>,
+
+    /// Host directory where members rendezvous (peer sockets and names).
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+
+    /// Sandbox name, which other members can resolve this sandbox by.
+    #[serde(default)]
+    pub member_name: Option<String>,
 }
 
 /// Optional overrides for the guest interface.
@@ -138,6 +170,7 @@ impl Default for NetworkConfig {
             tls: TlsConfig::default(),
             secrets: SecretsConfig::default(),
             max_connections: None,
+            attachment: None,
         }
     }
 }
@@ -1,9 +1,10 @@
 //! DNS query interception, filtering, and resolution.
 //!
 //! The [`DnsInterceptor`] bridges the smoltcp UDP socket (bound to gateway:53)
-//! and the host DNS resolvers. Queries are read from the socket, checked
-//! against the domain block list, forwarded to hickory-resolver for
-//! resolution, and responses are sent back through the socket.
+//! and the host DNS resolvers. Queries are read from the socket, answered
+//! directly if they name a private network peer, otherwise checked against
+//! the domain block list and forwarded to hickory-resolver for resolution.
+//! Responses are sent back through the socket.
 //!
 //! Because resolution is async and the poll loop is sync, queries are sent to
 //! a background tokio task via a channel. Responses come back through another
@@ -21,6 +22,7 @@ use tokio::sync::mpsc;
 
 use crate::config::DnsConfig;
 use crate::shared::SharedState;
+use crate::switch::MemberDirectory;
 
 //--------------------------------------------------------------------------------------------------
 // Constants
@@ -38,6 +40,10 @@ const DNS_SOCKET_PACKET_SLOTS: usize = 16;
 /// Capacity of the query/response channels.
 const CHANNEL_CAPACITY: usize = 64;
 
+/// TTL for answers naming private network peers. Short, since members come
+/// and go.
+const MEMBER_TTL_SECS: u32 = 5;
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -69,6 +75,8 @@ struct NormalizedDnsConfig {
     /// Dot-prefixed lowercased suffixes (for `ends_with` matching without per-query `format!`).
     blocked_suffixes_dotted: Vec<String>,
     rebind_protection: bool,
+    /// Names of private network peers, answered locally.
+    members: Option<MemberDirectory>,
 }
 
 /// A DNS query extracted from the smoltcp socket.
@@ -95,10 +103,12 @@ impl DnsInterceptor {
     /// Create the DNS interceptor.
     ///
     /// Binds a smoltcp UDP socket to port 53, creates the channel pair, and
-    /// spawns the background resolver task.
+    /// spawns the background resolver task. Names in `members` resolve to
+    /// their private network addresses.
     pub fn new(
         sockets: &mut SocketSet<'_>,
         dns_config: DnsConfig,
+        members: Option<MemberDirectory>,
         shared: Arc<SharedState>,
         tokio_handle: &tokio::runtime::Handle,
     ) -> Self {
@@ -141,6 +151,7 @@ impl DnsInterceptor {
             blocked_suffixes: suffixes,
             blocked_suffixes_dotted: suffixes_dotted,
             rebind_protection: dns_config.rebind_protection,
+            members,
         });
 
         // Spawn background resolver task.
@@ -254,24 +265,44 @@ async fn dns_resolver_task(
 
 /// Resolve a single DNS query. Returns `None` if the domain is blocked
 /// or contains rebind-protected addresses.
+///
+/// Names of private network peers resolve to the peer's address without
+/// consulting the block list or the host resolver.
 async fn resolve_query(
     raw_query: &[u8],
     dns_config: &NormalizedDnsConfig,
     resolver: &hickory_resolver::TokioResolver,
 ) -> Option<Bytes> {
     use hickory_proto::op::Message;
-    use hickory_proto::rr::RData;
+    use hickory_proto::rr::{RData, Record, RecordType};
     use hickory_proto::serialize::binary::BinDecodable;
 
     // Parse the DNS query.
     let query_msg = Message::from_bytes(raw_query).ok()?;
-    let query_id = query_msg.id();
 
     // Extract the queried domain name.
     let question = query_msg.queries().first()?;
     let domain = question.name().to_string();
     let domain = domain.trim_end_matches('.');
 
+    // Private network peers are answered locally and never leave the host.
+    if let Some(address) = dns_config
+        .members
+        .as_ref()
+        .and_then(|members| members.lookup(domain))
+    {
+        let answers = match question.query_type() {
+            RecordType::A => vec![Record::from_rdata(
+                question.name().clone(),
+                MEMBER_TTL_SECS,
+                RData::A(address.into()),
+            )],
+            // Members only have IPv4 addresses on the network.
+            _ => Vec::new(),
+        };
+        return build_response(&query_msg, answers);
+    }
+
     // Check domain block lists.
     if is_domain_blocked(domain, dns_config) {
         tracing::debug!(domain = %domain, "DNS query blocked");
@@ -305,22 +336,31 @@ async fn resolve_query(
         }
     }
 
+    build_response(&query_msg, lookup.records().to_vec())
+}
+
+/// Build a NOERROR response to the first question of `query_msg`.
+fn build_response(
+    query_msg: &hickory_proto::op::Message,
+    answers: Vec<hickory_proto::rr::Record>,
+) -> Option<Bytes> {
+    use hickory_proto::op::Message;
+    use hickory_proto::serialize::binary::BinEncodable;
+
     // Build a fresh DNS response (avoids cloning the entire query message).
     let mut response_msg = Message::new();
-    response_msg.set_id(query_id);
+    response_msg.set_id(query_msg.id());
     response_msg.set_message_type(hickory_proto::op::MessageType::Response);
     response_msg.set_op_code(query_msg.op_code());
     response_msg.set_response_code(hickory_proto::op::ResponseCode::NoError);
     response_msg.set_recursion_desired(query_msg.recursion_desired());
     response_msg.set_recursion_available(true);
-    response_msg.add_query(question.clone());
+    response_msg.add_query(query_msg.queries().first()?.clone());
 
     // Add answer records.
-    let answers: Vec<_> = lookup.records().to_vec();
     response_msg.insert_answers(answers);
 
     // Serialize the response.
-    use hickory_proto::serialize::binary::BinEncodable;
     let response_bytes = response_msg.to_bytes().ok()?;
 
     Some(Bytes::from(response_bytes))
@@ -417,6 +457,7 @@ mod tests {
                 .collect::<HashSet<_>>(),
             blocked_suffixes,
             blocked_suffixes_dotted,
+            members: None,
             rebind_protection: false,
         }
     }
@@ -444,4 +485,39 @@ mod tests {
         let config = normalized(vec![], vec![]);
         assert!(!is_domain_blocked("anything.com", &config));
     }
+
+    #[tokio::test]
+    async fn test_member_name_answered_locally() {
+        use hickory_proto::op::{Message, Query};
+        use hickory_proto::rr::{Name, RData, RecordType};
+        use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("db"), "198.18.0.6").unwrap();
+        let mut config = normalized(vec!["db"], vec![]);
+        config.members = Some(MemberDirectory::new(dir.path()));
+
+        let resolver = hickory_resolver::Resolver::builder_with_config(
+            hickory_resolver::config::ResolverConfig::default(),
+            hickory_resolver::name_server::TokioConnectionProvider::default(),
+        )
+        .build();
+
+        let mut query = Message::new();
+        query.set_id(7);
+        query.add_query(Query::query(
+            Name::from_ascii("DB.").unwrap(),
+            RecordType::A,
+        ));
+        let response = resolve_query(&query.to_bytes().unwrap(), &config, &resolver)
+            .await
+            .unwrap();
+
+        let response = Message::from_bytes(&response).unwrap();
+        assert_eq!(response.id(), 7);
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::A("198.18.0.6".parse::<std::net::Ipv4Addr>().unwrap().into())
+        );
+    }
 }
@@ -17,7 +17,7 @@ use smoltcp::wire::{
 };
 
 use crate::policy::{NetworkPolicy, Protocol};
-use crate::shared::SharedState;
+use crate::shared::{NetworkEvent, SharedState};
 use crate::stack::PollLoopConfig;
 
 //--------------------------------------------------------------------------------------------------
@@ -172,6 +172,11 @@ impl IcmpRelay {
             .evaluate_egress_ip(IpAddr::V4(dst_ip), Protocol::Icmpv4)
             .is_deny()
         {
+            self.shared.emit_event(NetworkEvent::PolicyDenial {
+                destination: IpAddr::V4(dst_ip),
+                port: None,
+                protocol: Protocol::Icmpv4,
+            });
             tracing::debug!(dst = %dst_ip, "ICMP echo denied by policy");
             return true; // Consumed (silently dropped by policy).
         }
@@ -248,6 +253,11 @@ impl IcmpRelay {
             .evaluate_egress_ip(IpAddr::V6(dst_ip), Protocol::Icmpv6)
             .is_deny()
         {
+            self.shared.emit_event(NetworkEvent::PolicyDenial {
+                destination: IpAddr::V6(dst_ip),
+                port: None,
+                protocol: Protocol::Icmpv6,
+            });
             tracing::debug!(dst = %dst_ip, "ICMPv6 echo denied by policy");
             return true;
         }
@@ -649,7 +659,7 @@ fn extract_ipv4_icmp_payload(buf: &[u8]) -> std::io::Result<&[u8]> {
             "host ICMPv4 reply did not contain a usable IPv4 header",
         ));
     }
-    if buf[9] != IpProtocol::Icmp.into() {
+    if buf[9] != u8::from(IpProtocol::Icmp) {
         return Err(std::io::Error::new(
             std::io::ErrorKind::InvalidData,
             "host ICMPv4 reply did not contain an ICMP payload",
@@ -675,7 +685,7 @@ fn extract_ipv6_icmp_payload(buf: &[u8]) -> std::io::Result<&[u8]> {
             "host ICMPv6 reply did not contain a usable IPv6 header",
         ));
     }
-    if buf[6] != IpProtocol::Icmpv6.into() {
+    if buf[6] != u8::from(IpProtocol::Icmpv6) {
         return Err(std::io::Error::new(
             std::io::ErrorKind::InvalidData,
             "host ICMPv6 reply did not contain an ICMPv6 payload",
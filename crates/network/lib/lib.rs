@@ -15,5 +15,6 @@ pub mod publisher;
 pub mod secrets;
 pub mod shared;
 pub mod stack;
+pub mod switch;
 pub mod tls;
 pub mod udp_relay;
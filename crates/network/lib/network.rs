@@ -13,8 +13,10 @@ use msb_krun::backends::net::NetBackend;
 
 use crate::backend::SmoltcpBackend;
 use crate::config::NetworkConfig;
-use crate::shared::{DEFAULT_QUEUE_CAPACITY, SharedState};
+use crate::publisher::{GuestConnector, InboundQueue};
+use crate::shared::{DEFAULT_QUEUE_CAPACITY, NetworkEventHook, SharedState};
 use crate::stack::{self, PollLoopConfig};
+use crate::switch::SwitchPort;
 use crate::tls::state::TlsState;
 
 //--------------------------------------------------------------------------------------------------
@@ -42,6 +44,9 @@ pub struct SmoltcpNetwork {
     shared: Arc<SharedState>,
     backend: Option<SmoltcpBackend>,
     poll_handle: Option<JoinHandle<()>>,
+    guest_connector: GuestConnector,
+    inbound: Option<InboundQueue>,
+    switch: Option<SwitchPort>,
 
     // Resolved from config + slot.
     guest_mac: [u8; 6],
@@ -62,6 +67,12 @@ pub struct TerminationHandle {
     shared: Arc<SharedState>,
 }
 
+/// Handle for receiving security-relevant network events.
+#[derive(Clone)]
+pub struct EventHandle {
+    shared: Arc<SharedState>,
+}
+
 /// Read-only view of aggregate network byte counters.
 #[derive(Clone)]
 pub struct MetricsHandle {
@@ -108,6 +119,7 @@ impl SmoltcpNetwork {
             .max(DEFAULT_QUEUE_CAPACITY);
         let shared = Arc::new(SharedState::new(queue_capacity));
         let backend = SmoltcpBackend::new(shared.clone());
+        let (guest_connector, inbound) = GuestConnector::new(shared.clone());
 
         let tls_state = if config.tls.enabled {
             Some(Arc::new(TlsState::new(
@@ -123,6 +135,9 @@ impl SmoltcpNetwork {
             shared,
             backend: Some(backend),
             poll_handle: None,
+            guest_connector,
+            inbound: Some(inbound),
+            switch: None,
             guest_mac,
             gateway_mac,
             mtu,
@@ -134,6 +149,23 @@ impl SmoltcpNetwork {
         }
     }
 
+    /// Join the private network named in the config, if any.
+    ///
+    /// Takes a free address on the network's subnet, replacing the
+    /// slot-derived guest IPv4. Must be called before [`Self::start`] and
+    /// [`Self::guest_env_vars`].
+    pub fn join_network(&mut self) -> std::io::Result<()> {
+        let Some(ref attachment) = self.config.attachment else {
+            return Ok(());
+        };
+
+        let port = SwitchPort::bind(attachment, self.gateway_mac, self.guest_mac)?;
+        self.guest_ipv4 = port.address();
+        self.gateway_ipv4 = gateway_from_guest_ipv4(self.guest_ipv4);
+        self.switch = Some(port);
+        Ok(())
+    }
+
     /// Start the smoltcp poll thread.
     ///
     /// Must be called before VM boot. Requires a tokio runtime handle for
@@ -153,6 +185,8 @@ impl SmoltcpNetwork {
         let tls_state = self.tls_state.clone();
         let published_ports = self.config.ports.clone();
         let max_connections = self.config.max_connections;
+        let inbound = self.inbound.take().expect("network already started");
+        let switch = self.switch.take();
 
         self.poll_handle = Some(
             std::thread::Builder::new()
@@ -165,6 +199,8 @@ impl SmoltcpNetwork {
                         dns_config,
                         tls_state,
                         published_ports,
+                        inbound,
+                        switch,
                         max_connections,
                         tokio_handle,
                     );
@@ -235,6 +271,18 @@ impl SmoltcpNetwork {
         }
     }
 
+    /// Create a handle for wiring network events into the runtime.
+    pub fn event_handle(&self) -> EventHandle {
+        EventHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Create a connector for opening TCP connections to guest ports.
+    pub fn guest_connector(&self) -> GuestConnector {
+        self.guest_connector.clone()
+    }
+
     /// Create a handle for reading aggregate network byte counters.
     pub fn metrics_handle(&self) -> MetricsHandle {
         MetricsHandle {
@@ -250,6 +298,13 @@ impl TerminationHandle {
     }
 }
 
+impl EventHandle {
+    /// Install the event hook.
+    pub fn set_hook(&self, hook: NetworkEventHook) {
+        self.shared.set_event_hook(hook);
+    }
+}
+
 impl MetricsHandle {
     /// Total guest -> runtime bytes observed at the virtio-net boundary.
     pub fn tx_bytes(&self) -> u64 {
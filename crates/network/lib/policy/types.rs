@@ -1,6 +1,6 @@
 //! Policy types: rules, actions, destinations, and protocol matching.
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
 use ipnetwork::IpNetwork;
 use serde::{Deserialize, Serialize};
@@ -64,7 +64,7 @@ pub enum Direction {
     /// Outbound (guest → internet).
     Outbound,
 
-    /// Inbound (internet → guest).
+    /// Inbound (private network peer → guest).
     Inbound,
 }
 
@@ -185,26 +185,7 @@ impl NetworkPolicy {
     /// Returns the action from the first matching rule, or the default
     /// action if no rule matches.
     pub fn evaluate_egress(&self, dst: SocketAddr, protocol: Protocol) -> Action {
-        for rule in &self.rules {
-            if rule.direction != Direction::Outbound {
-                continue;
-            }
-            if let Some(ref rule_proto) = rule.protocol
-                && *rule_proto != protocol
-            {
-                continue;
-            }
-            if let Some(ref ports) = rule.ports
-                && !ports.contains(dst.port())
-            {
-                continue;
-            }
-            if !matches_destination(&rule.destination, dst.ip()) {
-                continue;
-            }
-            return rule.action;
-        }
-        self.default_action
+        self.evaluate(Direction::Outbound, dst.ip(), Some(dst.port()), protocol)
     }
 
     /// Evaluate an outbound ICMP packet against the policy.
@@ -213,9 +194,33 @@ impl NetworkPolicy {
     /// matching — ICMP has no ports. Rules with a `ports` filter are
     /// skipped since applying a port range to a portless protocol would
     /// be semantically incorrect.
-    pub fn evaluate_egress_ip(&self, dst: std::net::IpAddr, protocol: Protocol) -> Action {
+    pub fn evaluate_egress_ip(&self, dst: IpAddr, protocol: Protocol) -> Action {
+        self.evaluate(Direction::Outbound, dst, None, protocol)
+    }
+
+    /// Evaluate an inbound connection from a peer on a private network.
+    ///
+    /// `Inbound` rules match the peer's address as their destination and
+    /// the guest's port as their port range.
+    pub fn evaluate_ingress(&self, src: IpAddr, port: u16, protocol: Protocol) -> Action {
+        self.evaluate(Direction::Inbound, src, Some(port), protocol)
+    }
+
+    /// Evaluate an inbound ICMP packet. Rules with a `ports` filter are
+    /// skipped, as in [`Self::evaluate_egress_ip`].
+    pub fn evaluate_ingress_ip(&self, src: IpAddr, protocol: Protocol) -> Action {
+        self.evaluate(Direction::Inbound, src, None, protocol)
+    }
+
+    fn evaluate(
+        &self,
+        direction: Direction,
+        addr: IpAddr,
+        port: Option<u16>,
+        protocol: Protocol,
+    ) -> Action {
         for rule in &self.rules {
-            if rule.direction != Direction::Outbound {
+            if rule.direction != direction {
                 continue;
             }
             if let Some(ref rule_proto) = rule.protocol
@@ -223,10 +228,12 @@ impl NetworkPolicy {
             {
                 continue;
             }
-            if rule.ports.is_some() {
-                continue;
+            match (rule.ports, port) {
+                (Some(ports), Some(port)) if !ports.contains(port) => continue,
+                (Some(_), None) => continue,
+                _ => {}
             }
-            if !matches_destination(&rule.destination, dst) {
+            if !matches_destination(&rule.destination, addr) {
                 continue;
             }
             return rule.action;
@@ -302,7 +309,7 @@ impl PortRange {
 //--------------------------------------------------------------------------------------------------
 
 /// Check if an IP address matches a destination specification.
-fn matches_destination(dest: &Destination, addr: std::net::IpAddr) -> bool {
+fn matches_destination(dest: &Destination, addr: IpAddr) -> bool {
     match dest {
         Destination::Any => true,
         Destination::Cidr(network) => matches_cidr(network, addr),
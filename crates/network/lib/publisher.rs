@@ -5,6 +5,9 @@
 //! on the host. When a connection arrives, the poll loop creates a smoltcp
 //! socket that connects to the guest, and a relay task bridges the host
 //! socket to the smoltcp socket via channels.
+//!
+//! [`GuestConnector`] opens the same kind of connection from inside the
+//! sandbox process, without binding a host port.
 
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
@@ -14,9 +17,9 @@ use bytes::Bytes;
 use smoltcp::iface::{Interface, SocketHandle, SocketSet};
 use smoltcp::socket::tcp;
 use smoltcp::wire::IpEndpoint;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::mpsc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
 
 use crate::config::{PortProtocol, PublishedPort};
 use crate::shared::SharedState;
@@ -35,6 +38,9 @@ const CHANNEL_CAPACITY: usize = 32;
 /// Buffer size for reading from host sockets.
 const RELAY_BUF_SIZE: usize = 16384;
 
+/// Capacity of the queue of connections waiting for the poll loop.
+const INBOUND_QUEUE_CAPACITY: usize = 64;
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -59,12 +65,31 @@ pub struct PortPublisher {
     max_inbound: usize,
 }
 
-/// An accepted host-side connection waiting to be wired to the guest.
+/// Opens TCP connections to guest ports from the sandbox process.
+#[derive(Clone)]
+pub struct GuestConnector {
+    inbound_tx: mpsc::Sender<InboundConnection>,
+    shared: Arc<SharedState>,
+}
+
+/// The inbound connection queue served by the poll loop.
+pub struct InboundQueue {
+    tx: mpsc::Sender<InboundConnection>,
+    rx: mpsc::Receiver<InboundConnection>,
+}
+
+/// A host-side stream that can be relayed into the guest.
+trait HostStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+/// A host-side connection waiting to be wired to the guest.
 struct InboundConnection {
-    /// The accepted host-side TCP stream.
-    stream: TcpStream,
+    /// The accepted host-side TCP stream, or the local end of a
+    /// [`GuestConnector`] connection.
+    stream: Box<dyn HostStream>,
     /// Guest port to connect to.
     guest_port: u16,
+    /// Notified once the guest accepts the connection. Dropped if it refuses.
+    connected: Option<oneshot::Sender<()>>,
 }
 
 /// Maximum number of poll iterations to attempt flushing remaining data
@@ -82,20 +107,66 @@ struct InboundRelay {
     write_buf: Option<(Bytes, usize)>,
     /// Counter for deferred close attempts (prevents stalling forever).
     close_attempts: u16,
+    /// Notified once the guest accepts the connection.
+    connected: Option<oneshot::Sender<()>>,
 }
 
 //--------------------------------------------------------------------------------------------------
 // Methods
 //--------------------------------------------------------------------------------------------------
 
+impl GuestConnector {
+    /// Create a connector and the queue the poll loop serves it from.
+    pub(crate) fn new(shared: Arc<SharedState>) -> (Self, InboundQueue) {
+        let (tx, rx) = mpsc::channel(INBOUND_QUEUE_CAPACITY);
+        let connector = Self {
+            inbound_tx: tx.clone(),
+            shared,
+        };
+        (connector, InboundQueue { tx, rx })
+    }
+
+    /// Connect to a TCP port in the guest.
+    ///
+    /// Resolves once the guest accepts the connection; fails if it refuses.
+    /// Callers should apply their own timeout, since a guest that never
+    /// answers leaves the connection pending.
+    pub async fn connect(&self, guest_port: u16) -> std::io::Result<DuplexStream> {
+        let (local, remote) = tokio::io::duplex(RELAY_BUF_SIZE);
+        let (connected_tx, connected_rx) = oneshot::channel();
+        let conn = InboundConnection {
+            stream: Box::new(remote),
+            guest_port,
+            connected: Some(connected_tx),
+        };
+
+        self.inbound_tx.send(conn).await.map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::NotConnected, "network stack stopped")
+        })?;
+        self.shared.proxy_wake.wake();
+
+        connected_rx.await.map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                format!("guest refused connection on port {guest_port}"),
+            )
+        })?;
+        Ok(local)
+    }
+}
+
 impl PortPublisher {
     /// Create a new publisher and spawn listeners for all published ports.
-    pub fn new(
+    pub(crate) fn new(
         ports: &[PublishedPort],
         guest_ipv4: Ipv4Addr,
+        inbound: InboundQueue,
         tokio_handle: &tokio::runtime::Handle,
     ) -> Self {
-        let (inbound_tx, inbound_rx) = mpsc::channel(64);
+        let InboundQueue {
+            tx: inbound_tx,
+            rx: inbound_rx,
+        } = inbound;
 
         // Spawn a listener for each published TCP port.
         for port in ports {
@@ -178,6 +249,7 @@ impl PortPublisher {
                 from_host: from_host_rx,
                 write_buf: None,
                 close_attempts: 0,
+                connected: conn.connected,
             });
         }
     }
@@ -189,6 +261,12 @@ impl PortPublisher {
         for relay in &mut self.connections {
             let socket = sockets.get_mut::<tcp::Socket>(relay.handle);
 
+            if socket.may_send()
+                && let Some(connected) = relay.connected.take()
+            {
+                let _ = connected.send(());
+            }
+
             // Detect relay task exit — close the smoltcp socket.
             if relay.to_host.is_closed() {
                 write_host_data(socket, relay);
@@ -253,6 +331,12 @@ impl PortPublisher {
     }
 }
 
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> HostStream for T {}
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
@@ -268,7 +352,11 @@ async fn tcp_listener_task(
 
     loop {
         let (stream, _peer) = listener.accept().await?;
-        let conn = InboundConnection { stream, guest_port };
+        let conn = InboundConnection {
+            stream: Box::new(stream),
+            guest_port,
+            connected: None,
+        };
         if inbound_tx.send(conn).await.is_err() {
             break; // Publisher dropped.
         }
@@ -277,14 +365,14 @@ async fn tcp_listener_task(
     Ok(())
 }
 
-/// Relay task: bridges a host TcpStream to channels connected to smoltcp.
+/// Relay task: bridges a host stream to channels connected to smoltcp.
 async fn inbound_relay_task(
-    stream: TcpStream,
+    stream: Box<dyn HostStream>,
     mut to_host_rx: mpsc::Receiver<Bytes>,
     from_host_tx: mpsc::Sender<Bytes>,
     shared: Arc<SharedState>,
 ) -> std::io::Result<()> {
-    let (mut rx, mut tx) = stream.into_split();
+    let (mut rx, mut tx) = tokio::io::split(stream);
     let mut buf = vec![0u8; RELAY_BUF_SIZE];
 
     loop {
@@ -6,11 +6,16 @@
 
 use crossbeam_queue::ArrayQueue;
 pub use microsandbox_utils::wake_pipe::WakePipe;
-use std::sync::{
-    Arc, Mutex,
-    atomic::{AtomicU64, Ordering},
+use std::{
+    net::IpAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
 };
 
+use crate::policy::Protocol;
+
 //--------------------------------------------------------------------------------------------------
 // Constants
 //--------------------------------------------------------------------------------------------------
@@ -59,10 +64,38 @@ pub struct SharedState {
     /// Optional host-side termination hook used for fatal policy violations.
     termination_hook: Mutex<Option<Arc<dyn Fn() + Send + Sync>>>,
 
+    /// Optional host-side hook notified of [`NetworkEvent`]s.
+    event_hook: Mutex<Option<NetworkEventHook>>,
+
     /// Aggregate network byte counters at the guest/runtime boundary.
     metrics: NetworkMetrics,
 }
 
+/// Callback receiving [`NetworkEvent`]s.
+pub type NetworkEventHook = Arc<dyn Fn(NetworkEvent) + Send + Sync>;
+
+/// Security-relevant network activity reported to the runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkEvent {
+    /// A secret placeholder was sent to a host not allowed to receive it.
+    SecretViolation {
+        /// SNI of the intercepted connection.
+        host: String,
+    },
+
+    /// The egress policy denied an outbound packet or connection.
+    PolicyDenial {
+        /// Destination address.
+        destination: IpAddr,
+
+        /// Destination port, if the protocol has one.
+        port: Option<u16>,
+
+        /// Transport protocol.
+        protocol: Protocol,
+    },
+}
+
 /// Aggregate network byte counters shared with the runtime metrics sampler.
 pub struct NetworkMetrics {
     tx_bytes: AtomicU64,
@@ -83,6 +116,7 @@ impl SharedState {
             tx_wake: WakePipe::new(),
             proxy_wake: WakePipe::new(),
             termination_hook: Mutex::new(None),
+            event_hook: Mutex::new(None),
             metrics: NetworkMetrics::default(),
         }
     }
@@ -100,6 +134,19 @@ impl SharedState {
         }
     }
 
+    /// Install a host-side network event hook.
+    pub fn set_event_hook(&self, hook: NetworkEventHook) {
+        *self.event_hook.lock().unwrap() = Some(hook);
+    }
+
+    /// Report a network event if a hook is installed.
+    pub fn emit_event(&self, event: NetworkEvent) {
+        let hook = self.event_hook.lock().unwrap().clone();
+        if let Some(hook) = hook {
+            hook(event);
+        }
+    }
+
     /// Increment the guest -> runtime byte counter.
     pub fn add_tx_bytes(&self, bytes: usize) {
         self.metrics
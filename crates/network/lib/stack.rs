@@ -25,8 +25,9 @@ use crate::dns::interceptor::DnsInterceptor;
 use crate::icmp_relay::IcmpRelay;
 use crate::policy::{NetworkPolicy, Protocol};
 use crate::proxy;
-use crate::publisher::PortPublisher;
-use crate::shared::SharedState;
+use crate::publisher::{InboundQueue, PortPublisher};
+use crate::shared::{NetworkEvent, SharedState};
+use crate::switch::SwitchPort;
 use crate::tls::{proxy as tls_proxy, state::TlsState};
 use crate::udp_relay::UdpRelay;
 
@@ -144,11 +145,13 @@ pub fn create_interface(device: &mut SmoltcpDevice, config: &PollLoopConfig) ->
 /// # Phases per iteration
 ///
 /// 1. **Drain guest frames** — pop from `tx_ring`, classify, pre-inspect.
+///    Frames for private network peers go to the switch port instead, and
+///    frames from peers are delivered to the guest.
 /// 2. **smoltcp egress + maintenance** — transmit queued packets, run timers.
 /// 3. **Service connections** — relay data between smoltcp sockets and proxy
 ///    tasks (added by later tasks).
-/// 4. **Sleep** — `poll(2)` on `tx_wake` + `proxy_wake` pipes with smoltcp's
-///    requested timeout.
+/// 4. **Sleep** — `poll(2)` on `tx_wake` + `proxy_wake` pipes (and the switch
+///    port socket) with smoltcp's requested timeout.
 #[allow(clippy::too_many_arguments)]
 pub fn smoltcp_poll_loop(
     shared: Arc<SharedState>,
@@ -157,6 +160,8 @@ pub fn smoltcp_poll_loop(
     dns_config: DnsConfig,
     tls_state: Option<Arc<TlsState>>,
     published_ports: Vec<PublishedPort>,
+    inbound: InboundQueue,
+    mut switch: Option<SwitchPort>,
     max_connections: Option<usize>,
     tokio_handle: tokio::runtime::Handle,
 ) {
@@ -165,9 +170,15 @@ pub fn smoltcp_poll_loop(
     let mut sockets = SocketSet::new(vec![]);
     let mut conn_tracker = ConnectionTracker::new(max_connections);
 
-    let mut dns_interceptor =
-        DnsInterceptor::new(&mut sockets, dns_config, shared.clone(), &tokio_handle);
-    let mut port_publisher = PortPublisher::new(&published_ports, config.guest_ipv4, &tokio_handle);
+    let mut dns_interceptor = DnsInterceptor::new(
+        &mut sockets,
+        dns_config,
+        switch.as_ref().map(SwitchPort::members),
+        shared.clone(),
+        &tokio_handle,
+    );
+    let mut port_publisher =
+        PortPublisher::new(&published_ports, config.guest_ipv4, inbound, &tokio_handle);
     let mut udp_relay = UdpRelay::new(
         shared.clone(),
         config.gateway_mac,
@@ -196,6 +207,12 @@ pub fn smoltcp_poll_loop(
             events: libc::POLLIN,
             revents: 0,
         },
+        // Negative fds are ignored by poll(2).
+        libc::pollfd {
+            fd: switch.as_ref().map_or(-1, SwitchPort::as_raw_fd),
+            events: libc::POLLIN,
+            revents: 0,
+        },
     ];
 
     loop {
@@ -208,6 +225,14 @@ pub fn smoltcp_poll_loop(
                 continue;
             }
 
+            if let Some(ref mut switch) = switch
+                && switch.routes(frame, config.gateway_ipv4)
+            {
+                switch.forward(frame, &network_policy, &shared);
+                device.drop_staged_frame();
+                continue;
+            }
+
             if icmp_relay.relay_outbound_if_echo(frame, &config, &network_policy) {
                 device.drop_staged_frame();
                 continue;
@@ -216,11 +241,13 @@ pub fn smoltcp_poll_loop(
             match classify_frame(frame) {
                 FrameAction::TcpSyn { src, dst } => {
                     // Policy check before socket creation.
-                    if network_policy
-                        .evaluate_egress(dst, Protocol::Tcp)
-                        .is_allow()
-                        && !conn_tracker.has_socket_for(&src, &dst)
-                    {
+                    if network_policy.evaluate_egress(dst, Protocol::Tcp).is_deny() {
+                        shared.emit_event(NetworkEvent::PolicyDenial {
+                            destination: dst.ip(),
+                            port: Some(dst.port()),
+                            protocol: Protocol::Tcp,
+                        });
+                    } else if !conn_tracker.has_socket_for(&src, &dst) {
                         conn_tracker.create_tcp_socket(src, dst, &mut sockets);
                     }
                     // Let smoltcp process — matching socket completes
@@ -241,6 +268,11 @@ pub fn smoltcp_poll_loop(
 
                     // Policy check.
                     if network_policy.evaluate_egress(dst, Protocol::Udp).is_deny() {
+                        shared.emit_event(NetworkEvent::PolicyDenial {
+                            destination: dst.ip(),
+                            port: Some(dst.port()),
+                            protocol: Protocol::Udp,
+                        });
                         device.drop_staged_frame();
                         continue;
                     }
@@ -256,6 +288,12 @@ pub fn smoltcp_poll_loop(
             }
         }
 
+        if let Some(ref mut switch) = switch
+            && switch.deliver(&network_policy, &shared)
+        {
+            shared.rx_wake.wake();
+        }
+
         // ── Phase 2: Ingress egress + maintenance ─────────────────────────
         // Flush frames generated by Phase 1 ingress (ACKs, SYN-ACKs, etc.)
         // before relaying data so smoltcp has up-to-date state.
@@ -320,6 +358,9 @@ pub fn smoltcp_poll_loop(
             conn_tracker.cleanup_closed(&mut sockets);
             port_publisher.cleanup_closed(&mut sockets);
             udp_relay.cleanup_expired();
+            if let Some(ref mut switch) = switch {
+                switch.cleanup_expired();
+            }
             last_cleanup = std::time::Instant::now();
         }
 
@@ -0,0 +1,687 @@
+//! Private networks shared between sandboxes.
+//!
+//! Every member of a network runs its own poll loop in its own process. A
+//! [`SwitchPort`] connects that loop to the other members: it binds a
+//! non-blocking unix datagram socket at `<dir>/peers/<ipv4>` and exchanges raw
+//! ethernet frames with the sockets of the other members. Frames for an
+//! address on the network never reach smoltcp or the host network.
+//!
+//! Members are addressed like standalone sandboxes: each one takes the next
+//! free `/30` block of the network subnet (guest at +2, gateway at +1), so
+//! the guest routes peer traffic through its gateway and the port only has
+//! to rewrite MAC addresses on delivery. Members also publish their name in
+//! `<dir>/names/` for the DNS interceptor to answer with.
+//!
+//! A member that exits without dropping its port leaves its socket and name
+//! behind; the next member to take the address reclaims both.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use ipnetwork::Ipv4Network;
+use smoltcp::wire::{
+    EthernetAddress, EthernetFrame, EthernetProtocol, IpProtocol, Ipv4Packet, TcpPacket, UdpPacket,
+};
+
+use crate::config::NetworkAttachment;
+use crate::policy::{NetworkPolicy, Protocol};
+use crate::shared::{NetworkEvent, SharedState};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Idle timeout for UDP flows admitted by policy.
+const UDP_FLOW_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Receive buffer size. Frames never exceed the interface MTU plus the
+/// ethernet header, so this leaves plenty of headroom.
+const RECV_BUF_SIZE: usize = 65536;
+
+/// ICMPv4 echo request type.
+const ICMP_ECHO_REQUEST: u8 = 8;
+
+/// Subdirectory holding one socket per member, named by its IPv4 address.
+const PEERS_SUBDIR: &str = "peers";
+
+/// Subdirectory holding one file per member name, containing its address.
+const NAMES_SUBDIR: &str = "names";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// One sandbox's connection to a private network.
+pub struct SwitchPort {
+    socket: UnixDatagram,
+    dir: PathBuf,
+    subnet: Ipv4Network,
+    address: Ipv4Addr,
+    gateway_mac: [u8; 6],
+    guest_mac: [u8; 6],
+    name_paths: Vec<PathBuf>,
+    udp_flows: HashMap<UdpFlow, Instant>,
+    recv_buf: Vec<u8>,
+}
+
+/// Read-only view of the names published by the members of a network.
+#[derive(Debug, Clone)]
+pub struct MemberDirectory {
+    dir: PathBuf,
+}
+
+/// A UDP conversation seen from this member: local port, peer address, peer port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct UdpFlow {
+    local_port: u16,
+    peer: Ipv4Addr,
+    peer_port: u16,
+}
+
+/// Addressing of an IPv4 packet crossing the switch.
+struct Packet {
+    src_mac: EthernetAddress,
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    kind: PacketKind,
+}
+
+/// What policy needs to know about a packet.
+enum PacketKind {
+    /// TCP SYN without ACK: opens a connection.
+    TcpOpen { dst_port: u16 },
+
+    /// Any other TCP segment of an admitted connection.
+    Tcp,
+
+    /// UDP datagram.
+    Udp { src_port: u16, dst_port: u16 },
+
+    /// ICMP echo request.
+    IcmpEcho,
+
+    /// Other ICMP (replies, errors).
+    Icmp,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl SwitchPort {
+    /// Join the network described by `attachment`, taking the first free
+    /// member address.
+    ///
+    /// The attachment must have been resolved by the host (subnet and
+    /// directory set). Sockets left behind by members that died without
+    /// cleaning up are reclaimed.
+    pub fn bind(
+        attachment: &NetworkAttachment,
+        gateway_mac: [u8; 6],
+        guest_mac: [u8; 6],
+    ) -> io::Result<Self> {
+        let (Some(subnet), Some(dir)) = (attachment.subnet, attachment.dir.clone()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("network '{}' has not been resolved", attachment.name),
+            ));
+        };
+
+        let peers_dir = dir.join(PEERS_SUBDIR);
+        std::fs::create_dir_all(&peers_dir)?;
+        std::fs::create_dir_all(dir.join(NAMES_SUBDIR))?;
+
+        let (socket, address) = (0..member_capacity(subnet))
+            .map(|index| member_address(subnet, index))
+            .find_map(|address| {
+                bind_peer_socket(&peers_dir.join(address.to_string()))
+                    .map(|socket| (socket, address))
+            })
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("network '{}' has no free addresses", attachment.name),
+                )
+            })?;
+        socket.set_nonblocking(true)?;
+
+        let mut port = Self {
+            socket,
+            dir,
+            subnet,
+            address,
+            gateway_mac,
+            guest_mac,
+            name_paths: Vec::new(),
+            udp_flows: HashMap::new(),
+            recv_buf: vec![0u8; RECV_BUF_SIZE],
+        };
+
+        // Names still pointing at this address belong to a member that is gone.
+        let names_dir = port.dir.join(NAMES_SUBDIR);
+        for entry in std::fs::read_dir(&names_dir)?.flatten() {
+            if std::fs::read_to_string(entry.path())
+                .is_ok_and(|addr| addr.trim() == address.to_string())
+            {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+
+        for name in attachment.member_name.iter().chain(&attachment.aliases) {
+            let path = names_dir.join(name.to_ascii_lowercase());
+            std::fs::write(&path, address.to_string())?;
+            port.name_paths.push(path);
+        }
+
+        Ok(port)
+    }
+
+    /// The guest address on the network.
+    pub fn address(&self) -> Ipv4Addr {
+        self.address
+    }
+
+    /// The names published by the members of this network.
+    pub fn members(&self) -> MemberDirectory {
+        MemberDirectory {
+            dir: self.dir.join(NAMES_SUBDIR),
+        }
+    }
+
+    /// File descriptor that becomes readable when a peer sends a frame.
+    pub fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+
+    /// Returns `true` if the guest frame is addressed to another member of
+    /// the network and must be handled by [`Self::forward`].
+    ///
+    /// `gateway` is the guest's own gateway, which stays with smoltcp.
+    pub fn routes(&self, frame: &[u8], gateway: Ipv4Addr) -> bool {
+        let Ok(eth) = EthernetFrame::new_checked(frame) else {
+            return false;
+        };
+        if eth.ethertype() != EthernetProtocol::Ipv4 {
+            return false;
+        }
+        let Ok(ipv4) = Ipv4Packet::new_checked(eth.payload()) else {
+            return false;
+        };
+        let dst = ipv4.dst_addr();
+        dst != gateway && self.subnet.contains(dst)
+    }
+
+    /// Send a guest frame to the member it is addressed to.
+    ///
+    /// Connection-opening packets are checked against the sender's egress
+    /// policy. Frames for addresses without a member are dropped, as are
+    /// frames whose source is not this member: peers apply their ingress
+    /// policy to the source address, so it must not be spoofable.
+    pub fn forward(&mut self, frame: &[u8], policy: &NetworkPolicy, shared: &SharedState) {
+        let Some(packet) = parse_packet(frame) else {
+            return;
+        };
+        if packet.src != self.address || packet.src_mac != EthernetAddress(self.guest_mac) {
+            tracing::trace!(
+                src = %packet.src,
+                src_mac = %packet.src_mac,
+                "dropping guest frame with spoofed source"
+            );
+            return;
+        }
+
+        if let Some((protocol, port)) = self.egress_check(&packet)
+            && policy_denies(policy, IpAddr::V4(packet.dst), port, protocol, false)
+        {
+            shared.emit_event(NetworkEvent::PolicyDenial {
+                destination: IpAddr::V4(packet.dst),
+                port,
+                protocol,
+            });
+            return;
+        }
+
+        let peer = self.dir.join(PEERS_SUBDIR).join(packet.dst.to_string());
+        if let Err(e) = self.socket.send_to(frame, &peer) {
+            tracing::trace!(dst = %packet.dst, error = %e, "dropping frame for unreachable peer");
+        }
+    }
+
+    /// Deliver frames received from peers to the guest.
+    ///
+    /// Connection-opening packets are checked against the receiver's ingress
+    /// policy. Returns `true` if any frame was queued for the guest.
+    pub fn deliver(&mut self, policy: &NetworkPolicy, shared: &SharedState) -> bool {
+        let mut delivered = false;
+        loop {
+            let len = match self.socket.recv(&mut self.recv_buf) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    tracing::debug!(error = %e, "switch port receive failed");
+                    break;
+                }
+            };
+
+            let mut frame = self.recv_buf[..len].to_vec();
+            let Some(packet) = parse_packet(&frame) else {
+                continue;
+            };
+            if packet.dst != self.address || !self.subnet.contains(packet.src) {
+                continue;
+            }
+            if let Some((protocol, port)) = self.ingress_check(&packet)
+                && policy_denies(policy, IpAddr::V4(packet.src), port, protocol, true)
+            {
+                tracing::debug!(src = %packet.src, ?port, ?protocol, "ingress denied by policy");
+                continue;
+            }
+
+            let mut eth = EthernetFrame::new_unchecked(&mut frame);
+            eth.set_src_addr(EthernetAddress(self.gateway_mac));
+            eth.set_dst_addr(EthernetAddress(self.guest_mac));
+
+            let frame_len = frame.len();
+            if shared.rx_ring.push(frame).is_ok() {
+                shared.add_rx_bytes(frame_len);
+                delivered = true;
+            }
+        }
+        delivered
+    }
+
+    /// Forget UDP flows idle for longer than the flow timeout.
+    pub fn cleanup_expired(&mut self) {
+        self.udp_flows
+            .retain(|_, last_active| last_active.elapsed() < UDP_FLOW_TIMEOUT);
+    }
+
+    /// Decide whether an outbound packet needs an egress check, returning
+    /// the protocol and port to check.
+    fn egress_check(&mut self, packet: &Packet) -> Option<(Protocol, Option<u16>)> {
+        match packet.kind {
+            PacketKind::TcpOpen { dst_port } => Some((Protocol::Tcp, Some(dst_port))),
+            PacketKind::Udp { src_port, dst_port } => self.udp_check(
+                UdpFlow {
+                    local_port: src_port,
+                    peer: packet.dst,
+                    peer_port: dst_port,
+                },
+                dst_port,
+            ),
+            PacketKind::IcmpEcho => Some((Protocol::Icmpv4, None)),
+            PacketKind::Tcp | PacketKind::Icmp => None,
+        }
+    }
+
+    /// Decide whether an inbound packet needs an ingress check, returning
+    /// the protocol and port to check.
+    fn ingress_check(&mut self, packet: &Packet) -> Option<(Protocol, Option<u16>)> {
+        match packet.kind {
+            PacketKind::TcpOpen { dst_port } => Some((Protocol::Tcp, Some(dst_port))),
+            PacketKind::Udp { src_port, dst_port } => self.udp_check(
+                UdpFlow {
+                    local_port: dst_port,
+                    peer: packet.src,
+                    peer_port: src_port,
+                },
+                dst_port,
+            ),
+            PacketKind::IcmpEcho => Some((Protocol::Icmpv4, None)),
+            PacketKind::Tcp | PacketKind::Icmp => None,
+        }
+    }
+
+    /// Packets of a known UDP flow skip the policy check; the first packet
+    /// of a flow is checked and the flow is recorded.
+    ///
+    /// The flow is recorded before the check result is known, which is
+    /// harmless: a denied first packet is dropped and its flow expires.
+    fn udp_check(&mut self, flow: UdpFlow, port: u16) -> Option<(Protocol, Option<u16>)> {
+        let now = Instant::now();
+        match self.udp_flows.insert(flow, now) {
+            Some(last_active) if now.duration_since(last_active) < UDP_FLOW_TIMEOUT => None,
+            _ => Some((Protocol::Udp, Some(port))),
+        }
+    }
+}
+
+impl MemberDirectory {
+    /// Create a view of the names in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Look up the address of a member by name (case-insensitive).
+    pub fn lookup(&self, name: &str) -> Option<Ipv4Addr> {
+        let name = name.to_ascii_lowercase();
+        if name.is_empty() || name.starts_with('.') || name.contains('/') {
+            return None;
+        }
+        std::fs::read_to_string(self.dir.join(name))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Drop for SwitchPort {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(self.dir.join(PEERS_SUBDIR).join(self.address.to_string()));
+
+        // Only remove names that still point at us; a replacement sandbox
+        // with the same name may already have claimed them.
+        for path in &self.name_paths {
+            if std::fs::read_to_string(path)
+                .is_ok_and(|addr| addr.trim() == self.address.to_string())
+            {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Number of members a subnet can hold: one `/30` block each.
+pub fn member_capacity(subnet: Ipv4Network) -> u32 {
+    (subnet.size() / 4).max(1)
+}
+
+/// Guest address of the member at `index`: offset +2 in its `/30` block.
+pub fn member_address(subnet: Ipv4Network, index: u32) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from(subnet.network()) + index * 4 + 2)
+}
+
+/// Bind a member socket, reclaiming it if its owner is gone.
+///
+/// Returns `None` if the address is held by a live member.
+fn bind_peer_socket(path: &Path) -> Option<UnixDatagram> {
+    match UnixDatagram::bind(path) {
+        Ok(socket) => Some(socket),
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse && is_stale(path) => {
+            std::fs::remove_file(path).ok()?;
+            UnixDatagram::bind(path).ok()
+        }
+        Err(_) => None,
+    }
+}
+
+/// A socket file nobody is bound to refuses connections.
+fn is_stale(path: &Path) -> bool {
+    UnixDatagram::unbound()
+        .and_then(|probe| probe.connect(path))
+        .is_err_and(|e| e.kind() == io::ErrorKind::ConnectionRefused)
+}
+
+/// Evaluate a packet against one side's policy.
+fn policy_denies(
+    policy: &NetworkPolicy,
+    addr: IpAddr,
+    port: Option<u16>,
+    protocol: Protocol,
+    inbound: bool,
+) -> bool {
+    match (port, inbound) {
+        (Some(port), false) => policy
+            .evaluate_egress(std::net::SocketAddr::new(addr, port), protocol)
+            .is_deny(),
+        (Some(port), true) => policy.evaluate_ingress(addr, port, protocol).is_deny(),
+        (None, false) => policy.evaluate_egress_ip(addr, protocol).is_deny(),
+        (None, true) => policy.evaluate_ingress_ip(addr, protocol).is_deny(),
+    }
+}
+
+/// Parse the IPv4 addressing and policy-relevant header bits of a frame.
+///
+/// Returns `None` for anything but TCP, UDP and ICMP, which are never
+/// forwarded.
+fn parse_packet(frame: &[u8]) -> Option<Packet> {
+    let eth = EthernetFrame::new_checked(frame).ok()?;
+    if eth.ethertype() != EthernetProtocol::Ipv4 {
+        return None;
+    }
+    let ipv4 = Ipv4Packet::new_checked(eth.payload()).ok()?;
+
+    let kind = match ipv4.next_header() {
+        IpProtocol::Tcp => {
+            let tcp = TcpPacket::new_checked(ipv4.payload()).ok()?;
+            if tcp.syn() && !tcp.ack() {
+                PacketKind::TcpOpen {
+                    dst_port: tcp.dst_port(),
+                }
+            } else {
+                PacketKind::Tcp
+            }
+        }
+        IpProtocol::Udp => {
+            let udp = UdpPacket::new_checked(ipv4.payload()).ok()?;
+            PacketKind::Udp {
+                src_port: udp.src_port(),
+                dst_port: udp.dst_port(),
+            }
+        }
+        IpProtocol::Icmp => match ipv4.payload().first() {
+            Some(&ICMP_ECHO_REQUEST) => PacketKind::IcmpEcho,
+            Some(_) => PacketKind::Icmp,
+            None => return None,
+        },
+        _ => return None,
+    };
+
+    Some(Packet {
+        src_mac: eth.src_addr(),
+        src: ipv4.src_addr(),
+        dst: ipv4.dst_addr(),
+        kind,
+    })
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipnetwork::IpNetwork;
+
+    use super::*;
+    use crate::policy::{Action, Destination, Direction, PortRange, Rule};
+
+    fn attachment(dir: &Path, name: &str) -> NetworkAttachment {
+        NetworkAttachment {
+            name: "backend".into(),
+            aliases: vec![format!("{name}-alias")],
+            subnet: Some("198.18.0.0/24".parse().unwrap()),
+            dir: Some(dir.to_path_buf()),
+            member_name: Some(name.into()),
+        }
+    }
+
+    fn tcp_syn(src: Ipv4Addr, dst: Ipv4Addr, dst_port: u16) -> Vec<u8> {
+        let mut frame = vec![0u8; 14 + 20 + 20];
+        let mut eth = EthernetFrame::new_unchecked(&mut frame);
+        eth.set_ethertype(EthernetProtocol::Ipv4);
+        let mut ip = Ipv4Packet::new_unchecked(&mut frame[14..]);
+        ip.set_version(4);
+        ip.set_header_len(20);
+        ip.set_total_len(40);
+        ip.set_hop_limit(64);
+        ip.set_next_header(IpProtocol::Tcp);
+        ip.set_src_addr(src);
+        ip.set_dst_addr(dst);
+        ip.fill_checksum();
+        let mut tcp = TcpPacket::new_unchecked(&mut frame[34..]);
+        tcp.set_src_port(40000);
+        tcp.set_dst_port(dst_port);
+        tcp.set_header_len(20);
+        tcp.set_syn(true);
+        frame
+    }
+
+    #[test]
+    fn test_member_addresses_use_consecutive_blocks() {
+        let subnet: Ipv4Network = "198.18.0.0/24".parse().unwrap();
+        assert_eq!(member_capacity(subnet), 64);
+        assert_eq!(member_address(subnet, 0), Ipv4Addr::new(198, 18, 0, 2));
+        assert_eq!(member_address(subnet, 1), Ipv4Addr::new(198, 18, 0, 6));
+        assert_eq!(member_address(subnet, 63), Ipv4Addr::new(198, 18, 0, 254));
+    }
+
+    #[test]
+    fn test_bind_takes_next_free_address_and_publishes_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = SwitchPort::bind(&attachment(dir.path(), "api"), [0; 6], [0; 6]).unwrap();
+        let b = SwitchPort::bind(&attachment(dir.path(), "DB"), [0; 6], [0; 6]).unwrap();
+        assert_eq!(a.address(), Ipv4Addr::new(198, 18, 0, 2));
+        assert_eq!(b.address(), Ipv4Addr::new(198, 18, 0, 6));
+
+        let members = a.members();
+        assert_eq!(members.lookup("db"), Some(b.address()));
+        assert_eq!(members.lookup("API"), Some(a.address()));
+        assert_eq!(members.lookup("db-alias"), Some(b.address()));
+        assert_eq!(members.lookup("../api"), None);
+
+        drop(a);
+        assert_eq!(members.lookup("api"), None);
+        let c = SwitchPort::bind(&attachment(dir.path(), "web"), [0; 6], [0; 6]).unwrap();
+        assert_eq!(c.address(), Ipv4Addr::new(198, 18, 0, 2));
+    }
+
+    #[test]
+    fn test_bind_reclaims_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(PEERS_SUBDIR)).unwrap();
+        let stale = UnixDatagram::bind(dir.path().join(PEERS_SUBDIR).join("198.18.0.2")).unwrap();
+        drop(stale);
+
+        let port = SwitchPort::bind(&attachment(dir.path(), "api"), [0; 6], [0; 6]).unwrap();
+        assert_eq!(port.address(), Ipv4Addr::new(198, 18, 0, 2));
+    }
+
+    #[test]
+    fn test_frames_cross_the_switch_subject_to_ingress_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut a = SwitchPort::bind(&attachment(dir.path(), "a"), [0; 6], [0; 6]).unwrap();
+        let mut b = SwitchPort::bind(&attachment(dir.path(), "b"), [1; 6], [2; 6]).unwrap();
+        let shared = SharedState::new(16);
+
+        // Only port 5432 is open on b.
+        let policy = NetworkPolicy {
+            default_action: Action::Allow,
+            rules: vec![
+                Rule {
+                    direction: Direction::Inbound,
+                    destination: Destination::Any,
+                    protocol: Some(Protocol::Tcp),
+                    ports: Some(PortRange::single(5432)),
+                    action: Action::Allow,
+                },
+                Rule {
+                    direction: Direction::Inbound,
+                    destination: Destination::Any,
+                    protocol: None,
+                    ports: None,
+                    action: Action::Deny,
+                },
+            ],
+        };
+
+        let allow_all = NetworkPolicy::allow_all();
+        a.forward(&tcp_syn(a.address(), b.address(), 22), &allow_all, &shared);
+        a.forward(
+            &tcp_syn(a.address(), b.address(), 5432),
+            &allow_all,
+            &shared,
+        );
+        assert!(b.deliver(&policy, &shared));
+
+        let frame = shared.rx_ring.pop().unwrap();
+        assert!(shared.rx_ring.pop().is_none());
+        let eth = EthernetFrame::new_checked(&frame[..]).unwrap();
+        assert_eq!(eth.src_addr(), EthernetAddress([1; 6]));
+        assert_eq!(eth.dst_addr(), EthernetAddress([2; 6]));
+        let ip = Ipv4Packet::new_checked(eth.payload()).unwrap();
+        assert_eq!(
+            TcpPacket::new_checked(ip.payload()).unwrap().dst_port(),
+            5432
+        );
+    }
+
+    #[test]
+    fn test_spoofed_source_is_not_forwarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = SwitchPort::bind(&attachment(dir.path(), "a"), [0; 6], [0; 6]).unwrap();
+        let mut b = SwitchPort::bind(&attachment(dir.path(), "b"), [0; 6], [0; 6]).unwrap();
+        let mut c = SwitchPort::bind(&attachment(dir.path(), "c"), [0; 6], [3; 6]).unwrap();
+        let shared = SharedState::new(16);
+
+        // b only accepts connections from a.
+        let policy = NetworkPolicy {
+            default_action: Action::Deny,
+            rules: vec![Rule {
+                direction: Direction::Inbound,
+                destination: Destination::Cidr(IpNetwork::from(IpAddr::V4(a.address()))),
+                protocol: None,
+                ports: None,
+                action: Action::Allow,
+            }],
+        };
+
+        // c claims a's address.
+        let mut spoofed_ip = tcp_syn(a.address(), b.address(), 80);
+        EthernetFrame::new_unchecked(&mut spoofed_ip).set_src_addr(EthernetAddress([3; 6]));
+        c.forward(&spoofed_ip, &NetworkPolicy::allow_all(), &shared);
+
+        // c uses its own address but another member's MAC.
+        c.forward(
+            &tcp_syn(c.address(), b.address(), 80),
+            &NetworkPolicy::allow_all(),
+            &shared,
+        );
+
+        assert!(!b.deliver(&policy, &shared));
+        assert!(shared.rx_ring.pop().is_none());
+    }
+
+    #[test]
+    fn test_egress_policy_blocks_forwarding() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut a = SwitchPort::bind(&attachment(dir.path(), "a"), [0; 6], [0; 6]).unwrap();
+        let mut b = SwitchPort::bind(&attachment(dir.path(), "b"), [0; 6], [0; 6]).unwrap();
+        let shared = SharedState::new(16);
+
+        a.forward(
+            &tcp_syn(a.address(), b.address(), 80),
+            &NetworkPolicy::none(),
+            &shared,
+        );
+        assert!(!b.deliver(&NetworkPolicy::allow_all(), &shared));
+    }
+
+    #[test]
+    fn test_routes_only_peer_addresses() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = SwitchPort::bind(&attachment(dir.path(), "a"), [0; 6], [0; 6]).unwrap();
+        let gateway = Ipv4Addr::new(198, 18, 0, 1);
+
+        let peer = tcp_syn(a.address(), Ipv4Addr::new(198, 18, 0, 6), 80);
+        let internet = tcp_syn(a.address(), Ipv4Addr::new(1, 1, 1, 1), 80);
+        let to_gateway = tcp_syn(a.address(), gateway, 53);
+        assert!(a.routes(&peer, gateway));
+        assert!(!a.routes(&internet, gateway));
+        assert!(!a.routes(&to_gateway, gateway));
+    }
+}
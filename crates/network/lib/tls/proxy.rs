@@ -18,7 +18,7 @@ use tokio::sync::mpsc;
 use super::sni;
 use super::state::TlsState;
 use crate::secrets::handler::SecretsHandler;
-use crate::shared::SharedState;
+use crate::shared::{NetworkEvent, SharedState};
 
 //--------------------------------------------------------------------------------------------------
 // Constants
@@ -208,6 +208,7 @@ async fn intercept_relay(
         &mut server_tls,
         &secrets_handler,
         &shared,
+        sni_name,
         &mut plaintext_buf,
     )
     .await?;
@@ -236,6 +237,7 @@ async fn intercept_relay(
                     &mut server_tls,
                     &secrets_handler,
                     &shared,
+                    sni_name,
                     &mut plaintext_buf,
                 )
                 .await?;
@@ -293,6 +295,7 @@ async fn forward_plaintext(
     server_tls: &mut tokio_rustls::client::TlsStream<TcpStream>,
     secrets_handler: &SecretsHandler,
     shared: &SharedState,
+    sni_name: &str,
     buf: &mut [u8],
 ) -> io::Result<()> {
     loop {
@@ -315,6 +318,9 @@ async fn forward_plaintext(
         }
 
         // Violation: placeholder going to disallowed host. Drop the connection.
+        shared.emit_event(NetworkEvent::SecretViolation {
+            host: sni_name.to_string(),
+        });
         if secrets_handler.terminates_on_violation() {
             shared.trigger_termination();
         }
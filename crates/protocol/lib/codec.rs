@@ -3,11 +3,14 @@
 //! Wire format: `[len: u32 BE][id: u32 BE][flags: u8][CBOR(v, t, p)]`
 //!
 //! The correlation ID and flags sit in a fixed-position binary header so that
-//! relay intermediaries can route frames without CBOR parsing.
+//! relay intermediaries can route frames without CBOR parsing. A compressed
+//! frame sets a compression flag and carries the compressed CBOR instead
+//! (see [`crate::compress`]).
 
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::{
+    compress::{COMPRESSION_THRESHOLD, Compression, FLAG_COMPRESSED},
     error::{ProtocolError, ProtocolResult},
     message::{FRAME_HEADER_SIZE, Message},
 };
@@ -29,29 +32,34 @@ pub const MAX_FRAME_SIZE: u32 = 4 * 1024 * 1024;
 /// Encodes a message to a byte buffer using the length-prefixed frame format.
 ///
 /// Frame format: `[len: u32 BE][id: u32 BE][flags: u8][CBOR(v, t, p)]`
+///
+/// If the message is marked for compression, the CBOR body is compressed
+/// when that makes it smaller; otherwise the compression flag is cleared.
 pub fn encode_to_buf(msg: &Message, buf: &mut Vec<u8>) -> ProtocolResult<()> {
     // Serialize the CBOR body (v, t, p — id and flags are excluded via serde(skip)).
     let mut cbor = Vec::new();
     ciborium::into_writer(msg, &mut cbor)?;
 
-    // Total frame payload = id (4) + flags (1) + CBOR body.
-    let frame_len = u32::try_from(FRAME_HEADER_SIZE + cbor.len()).map_err(|_| {
-        ProtocolError::FrameTooLarge {
-            size: u32::MAX,
-            max: MAX_FRAME_SIZE,
+    // The uncompressed body must fit in a frame so the peer can decompress it.
+    check_frame_len(FRAME_HEADER_SIZE + cbor.len())?;
+
+    let mut flags = msg.flags & !FLAG_COMPRESSED;
+    if let Some(codec) = Compression::from_flags(msg.flags)?
+        && cbor.len() >= COMPRESSION_THRESHOLD
+    {
+        let compressed = codec.compress(&cbor)?;
+        if compressed.len() < cbor.len() {
+            cbor = compressed;
+            flags |= codec.flag();
         }
-    })?;
-
-    if frame_len > MAX_FRAME_SIZE {
-        return Err(ProtocolError::FrameTooLarge {
-            size: frame_len,
-            max: MAX_FRAME_SIZE,
-        });
     }
 
+    // Total frame payload = id (4) + flags (1) + CBOR body.
+    let frame_len = check_frame_len(FRAME_HEADER_SIZE + cbor.len())?;
+
     buf.extend_from_slice(&frame_len.to_be_bytes());
     buf.extend_from_slice(&msg.id.to_be_bytes());
-    buf.push(msg.flags);
+    buf.push(flags);
     buf.extend_from_slice(&cbor);
     Ok(())
 }
@@ -90,15 +98,7 @@ pub fn try_decode_from_buf(buf: &mut Vec<u8>) -> ProtocolResult<Option<Message>>
         });
     }
 
-    // Extract header fields.
-    let id = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
-    let flags = buf[8];
-
-    // Deserialize the CBOR body.
-    let cbor = &buf[4 + FRAME_HEADER_SIZE..total];
-    let mut msg: Message = ciborium::from_reader(cbor)?;
-    msg.id = id;
-    msg.flags = flags;
+    let msg = decode_frame(&buf[4..total])?;
 
     buf.drain(..total);
     Ok(Some(msg))
@@ -140,17 +140,7 @@ pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> ProtocolResul
     let mut payload = vec![0u8; frame_len];
     reader.read_exact(&mut payload).await?;
 
-    // Extract header fields.
-    let id = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]);
-    let flags = payload[4];
-
-    // Deserialize the CBOR body.
-    let cbor = &payload[FRAME_HEADER_SIZE..];
-    let mut msg: Message = ciborium::from_reader(cbor)?;
-    msg.id = id;
-    msg.flags = flags;
-
-    Ok(msg)
+    decode_frame(&payload)
 }
 
 /// Writes a length-prefixed message to the given writer.
@@ -167,6 +157,49 @@ pub async fn write_message<W: AsyncWrite + Unpin>(
     Ok(())
 }
 
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Checks a frame length (everything after the length prefix) against
+/// [`MAX_FRAME_SIZE`].
+fn check_frame_len(len: usize) -> ProtocolResult<u32> {
+    let len = u32::try_from(len).map_err(|_| ProtocolError::FrameTooLarge {
+        size: u32::MAX,
+        max: MAX_FRAME_SIZE,
+    })?;
+    if len > MAX_FRAME_SIZE {
+        return Err(ProtocolError::FrameTooLarge {
+            size: len,
+            max: MAX_FRAME_SIZE,
+        });
+    }
+    Ok(len)
+}
+
+/// Decodes a frame's `[id][flags][body]`, decompressing the body if a
+/// compression flag is set. The returned message's flags have the
+/// compression bits cleared.
+fn decode_frame(frame: &[u8]) -> ProtocolResult<Message> {
+    // Extract header fields.
+    let id = u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]);
+    let flags = frame[4];
+
+    // Deserialize the CBOR body.
+    let body = &frame[FRAME_HEADER_SIZE..];
+    let mut msg: Message = match Compression::from_flags(flags)? {
+        Some(codec) => {
+            let cbor = codec.decompress(body, MAX_FRAME_SIZE as usize - FRAME_HEADER_SIZE)?;
+            ciborium::from_reader(&cbor[..])?
+        }
+        None => ciborium::from_reader(body)?,
+    };
+    msg.id = id;
+    msg.flags = flags & !FLAG_COMPRESSED;
+
+    Ok(msg)
+}
+
 //--------------------------------------------------------------------------------------------------
 // Tests
 //--------------------------------------------------------------------------------------------------
@@ -174,7 +207,9 @@ pub async fn write_message<W: AsyncWrite + Unpin>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::message::{FLAG_SESSION_START, FLAG_TERMINAL, MessageType, PROTOCOL_VERSION};
+    use crate::message::{
+        FLAG_COMPRESSED_ZSTD, FLAG_SESSION_START, FLAG_TERMINAL, MessageType, PROTOCOL_VERSION,
+    };
 
     #[tokio::test]
     async fn test_codec_roundtrip_empty_payload() {
@@ -196,8 +231,15 @@ mod tests {
     async fn test_codec_roundtrip_with_payload() {
         use crate::exec::ExecExited;
 
-        let msg =
-            Message::with_payload(MessageType::ExecExited, 7, &ExecExited { code: 42 }).unwrap();
+        let msg = Message::with_payload(
+            MessageType::ExecExited,
+            7,
+            &ExecExited {
+                code: 42,
+                ..Default::default()
+            },
+        )
+        .unwrap();
 
         let mut buf = Vec::new();
         write_message(&mut buf, &msg).await.unwrap();
@@ -247,8 +289,15 @@ mod tests {
     fn test_sync_encode_decode_roundtrip() {
         use crate::exec::ExecExited;
 
-        let msg =
-            Message::with_payload(MessageType::ExecExited, 5, &ExecExited { code: 0 }).unwrap();
+        let msg = Message::with_payload(
+            MessageType::ExecExited,
+            5,
+            &ExecExited {
+                code: 0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
 
         let mut buf = Vec::new();
         encode_to_buf(&msg, &mut buf).unwrap();
@@ -333,4 +382,87 @@ mod tests {
         let result = try_decode_from_buf(&mut buf);
         assert!(matches!(result, Err(ProtocolError::FrameTooShort { .. })));
     }
+
+    #[test]
+    fn test_compressed_roundtrip() {
+        use crate::fs::FsData;
+
+        for codec in Compression::ALL {
+            let data = b"INFO request handled in 3ms\n".repeat(512);
+            let msg = Message::with_payload(MessageType::FsData, 9, &FsData { data: data.clone() })
+                .unwrap()
+                .compress(Some(codec));
+
+            let mut buf = Vec::new();
+            encode_to_buf(&msg, &mut buf).unwrap();
+            assert!(buf.len() < data.len() / 4);
+            assert_eq!(buf[8], codec.flag());
+
+            let decoded = try_decode_from_buf(&mut buf).unwrap().unwrap();
+            assert_eq!(decoded.t, MessageType::FsData);
+            assert_eq!(decoded.id, 9);
+            assert_eq!(decoded.flags, 0);
+            assert_eq!(decoded.payload::<FsData>().unwrap().data, data);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compression_keeps_routing_flags() {
+        use crate::fs::FsResponse;
+
+        let resp = FsResponse {
+            ok: false,
+            error: Some("x".repeat(4096)),
+            data: None,
+        };
+        let msg = Message::with_payload(MessageType::FsResponse, 3, &resp)
+            .unwrap()
+            .compress(Some(Compression::Zstd));
+
+        let mut buf = Vec::new();
+        write_message(&mut buf, &msg).await.unwrap();
+        assert_eq!(buf[8], FLAG_TERMINAL | FLAG_COMPRESSED_ZSTD);
+
+        let decoded = read_message(&mut &buf[..]).await.unwrap();
+        assert_eq!(decoded.flags, FLAG_TERMINAL);
+        assert_eq!(decoded.payload::<FsResponse>().unwrap().error, resp.error);
+    }
+
+    #[test]
+    fn test_compression_skipped_when_not_worthwhile() {
+        use crate::exec::ExecStdout;
+
+        // Too small to bother.
+        let small = Message::with_payload(
+            MessageType::ExecStdout,
+            1,
+            &ExecStdout {
+                data: b"ok\n".to_vec(),
+            },
+        )
+        .unwrap()
+        .compress(Some(Compression::Zstd));
+
+        // Large but incompressible.
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let noise: Vec<u8> = (0..8192)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let noisy = Message::with_payload(MessageType::ExecStdout, 2, &ExecStdout { data: noise })
+            .unwrap()
+            .compress(Some(Compression::Lz4));
+
+        for msg in [small, noisy] {
+            let mut buf = Vec::new();
+            encode_to_buf(&msg, &mut buf).unwrap();
+            assert_eq!(buf[8] & FLAG_COMPRESSED, 0);
+            let decoded = try_decode_from_buf(&mut buf).unwrap().unwrap();
+            assert_eq!(decoded.p, msg.p);
+        }
+    }
 }
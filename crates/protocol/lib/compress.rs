@@ -0,0 +1,150 @@
+//! Frame body compression for bulk transfers.
+//!
+//! A sender marks a [`Message`](crate::message::Message) for compression
+//! with [`Message::compress`](crate::message::Message::compress). The codec
+//! then compresses the frame's CBOR body and sets the codec's flag bit in
+//! the frame header, leaving the length, correlation ID and other flags in
+//! the clear so relays keep routing frames without decoding them. Bodies
+//! below [`COMPRESSION_THRESHOLD`], or that don't shrink, are sent as-is.
+//!
+//! Receivers decompress by the flag alone; a sender only compresses with a
+//! codec the peer advertised in [`Capabilities`](crate::core::Capabilities).
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ProtocolError, ProtocolResult},
+    message::{FLAG_COMPRESSED_LZ4, FLAG_COMPRESSED_ZSTD},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Smallest frame body worth compressing (1 KiB).
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Every compression flag bit.
+pub const FLAG_COMPRESSED: u8 = FLAG_COMPRESSED_ZSTD | FLAG_COMPRESSED_LZ4;
+
+/// zstd level: the fastest setting still compresses source and logs well.
+const ZSTD_LEVEL: i32 = 1;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A frame compression codec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    /// Zstandard: better ratio, for data crossing a slow channel.
+    Zstd,
+
+    /// LZ4 block format: cheaper to produce, for CPU-starved guests.
+    Lz4,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Compression {
+    /// Every codec, most preferred first.
+    pub const ALL: [Self; 2] = [Self::Zstd, Self::Lz4];
+
+    /// Name advertised in [`Capabilities`](crate::core::Capabilities).
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Lz4 => "lz4",
+        }
+    }
+
+    /// Look up a codec by its advertised name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Frame header flag marking a body compressed with this codec.
+    pub fn flag(self) -> u8 {
+        match self {
+            Self::Zstd => FLAG_COMPRESSED_ZSTD,
+            Self::Lz4 => FLAG_COMPRESSED_LZ4,
+        }
+    }
+
+    /// The codec a frame's flags say its body is compressed with.
+    pub fn from_flags(flags: u8) -> ProtocolResult<Option<Self>> {
+        match flags & FLAG_COMPRESSED {
+            0 => Ok(None),
+            FLAG_COMPRESSED_ZSTD => Ok(Some(Self::Zstd)),
+            FLAG_COMPRESSED_LZ4 => Ok(Some(Self::Lz4)),
+            _ => Err(ProtocolError::Compression(format!(
+                "conflicting compression flags {flags:#010b}"
+            ))),
+        }
+    }
+
+    /// Compress `data`.
+    pub fn compress(self, data: &[u8]) -> ProtocolResult<Vec<u8>> {
+        match self {
+            Self::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL)
+                .map_err(|e| ProtocolError::Compression(format!("zstd: {e}"))),
+            Self::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        }
+    }
+
+    /// Decompress `data`, failing if it would expand past `max` bytes.
+    pub fn decompress(self, data: &[u8], max: usize) -> ProtocolResult<Vec<u8>> {
+        match self {
+            Self::Zstd => zstd::bulk::decompress(data, max)
+                .map_err(|e| ProtocolError::Compression(format!("zstd: {e}"))),
+            Self::Lz4 => {
+                let size = data
+                    .get(..4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                    .ok_or_else(|| ProtocolError::Compression("lz4: truncated".into()))?;
+                if size > max {
+                    return Err(ProtocolError::Compression(format!(
+                        "lz4: body expands to {size} bytes (max {max})"
+                    )));
+                }
+                lz4_flex::decompress_size_prepended(data)
+                    .map_err(|e| ProtocolError::Compression(format!("lz4: {e}")))
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_every_codec() {
+        let data = b"the quick brown fox jumps over the lazy dog\n".repeat(100);
+        for codec in Compression::ALL {
+            let compressed = codec.compress(&data).unwrap();
+            assert!(
+                compressed.len() < data.len(),
+                "{} did not shrink",
+                codec.name()
+            );
+            assert_eq!(codec.decompress(&compressed, data.len()).unwrap(), data);
+            assert!(codec.decompress(&compressed, data.len() - 1).is_err());
+            assert_eq!(Compression::from_name(codec.name()), Some(codec));
+            assert_eq!(Compression::from_flags(codec.flag()).unwrap(), Some(codec));
+        }
+    }
+
+    #[test]
+    fn test_from_flags_rejects_both_codecs() {
+        assert_eq!(Compression::from_flags(0).unwrap(), None);
+        assert!(Compression::from_flags(FLAG_COMPRESSED).is_err());
+    }
+}
@@ -1,7 +1,42 @@
 //! Core protocol message payloads.
 
+use std::ops::RangeInclusive;
+
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    compress::Compression,
+    message::{MIN_PROTOCOL_VERSION, MessageType, PROTOCOL_VERSION},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Messages handled by agents that predate capability discovery.
+const LEGACY_MESSAGES: &[MessageType] = &[
+    MessageType::Shutdown,
+    MessageType::ExecRequest,
+    MessageType::ExecStdin,
+    MessageType::ExecResize,
+    MessageType::ExecSignal,
+    MessageType::FsRequest,
+    MessageType::FsData,
+];
+
+/// Filesystem operations handled by agents that predate capability discovery.
+const LEGACY_FS_OPS: &[&str] = &[
+    "stat",
+    "list",
+    "read",
+    "write",
+    "mkdir",
+    "remove",
+    "remove_dir",
+    "copy",
+    "rename",
+];
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -10,7 +45,8 @@ use serde::{Deserialize, Serialize};
 ///
 /// Sent by the guest agent to signal that it has finished initialization
 /// and is ready to receive commands. Includes timing data for boot
-/// performance measurement.
+/// performance measurement, and the protocol versions and features the
+/// agent supports so hosts can work with older guest images.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Ready {
     /// `CLOCK_BOOTTIME` nanoseconds captured at the start of `main()`.
@@ -25,4 +61,189 @@ pub struct Ready {
     ///
     /// Represents total time from kernel boot to agent readiness.
     pub ready_time_ns: u64,
+
+    /// Oldest protocol version the agent speaks.
+    #[serde(default = "legacy_version")]
+    pub min_version: u8,
+
+    /// Newest protocol version the agent speaks.
+    #[serde(default = "legacy_version")]
+    pub max_version: u8,
+
+    /// Features the agent supports. `None` for agents that predate
+    /// capability discovery, which support the original exec and
+    /// filesystem messages only.
+    #[serde(default)]
+    pub capabilities: Option<Capabilities>,
+}
+
+/// Features supported by a guest agent.
+///
+/// Entries are wire names rather than enums so that a host can read the
+/// capabilities of a newer agent that knows names it doesn't.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Wire names of the message types the agent accepts from the host,
+    /// such as `core.exec.request`.
+    #[serde(default)]
+    pub messages: Vec<String>,
+
+    /// Filesystem operations the agent implements, as returned by
+    /// [`FsOp::name`](crate::fs::FsOp::name).
+    #[serde(default)]
+    pub fs_ops: Vec<String>,
+
+    /// Frame compression codecs the agent can decode and produce.
+    #[serde(default)]
+    pub compression: Vec<String>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Ready {
+    /// Build a ready payload advertising this build's protocol versions and
+    /// the given capabilities.
+    pub fn new(
+        boot_time_ns: u64,
+        init_time_ns: u64,
+        ready_time_ns: u64,
+        capabilities: Capabilities,
+    ) -> Self {
+        Self {
+            boot_time_ns,
+            init_time_ns,
+            ready_time_ns,
+            min_version: MIN_PROTOCOL_VERSION,
+            max_version: PROTOCOL_VERSION,
+            capabilities: Some(capabilities),
+        }
+    }
+
+    /// Protocol versions the agent speaks.
+    pub fn versions(&self) -> RangeInclusive<u8> {
+        self.min_version..=self.max_version
+    }
+
+    /// The newest protocol version both this build and the agent speak, or
+    /// `None` if their ranges don't overlap.
+    pub fn negotiate(&self) -> Option<u8> {
+        let version = self.max_version.min(PROTOCOL_VERSION);
+        (version >= self.min_version.max(MIN_PROTOCOL_VERSION)).then_some(version)
+    }
+
+    /// Whether the agent accepts this message type from the host.
+    pub fn supports(&self, t: &MessageType) -> bool {
+        match &self.capabilities {
+            Some(caps) => caps.messages.iter().any(|name| name == t.as_str()),
+            None => LEGACY_MESSAGES.contains(t),
+        }
+    }
+
+    /// Whether the agent implements this filesystem operation.
+    pub fn supports_fs_op(&self, name: &str) -> bool {
+        match &self.capabilities {
+            Some(caps) => caps.fs_ops.iter().any(|op| op == name),
+            None => LEGACY_FS_OPS.contains(&name),
+        }
+    }
+
+    /// Whether the agent supports this frame compression codec.
+    pub fn supports_compression(&self, codec: &str) -> bool {
+        self.capabilities
+            .as_ref()
+            .is_some_and(|caps| caps.compression.iter().any(|c| c == codec))
+    }
+
+    /// The preferred compression codec the agent supports, if any.
+    pub fn compression(&self) -> Option<Compression> {
+        Compression::ALL
+            .into_iter()
+            .find(|codec| self.supports_compression(codec.name()))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+fn legacy_version() -> u8 {
+    1
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `core.ready` payload sent by agents before capability discovery.
+    #[derive(Serialize)]
+    struct LegacyReady {
+        boot_time_ns: u64,
+        init_time_ns: u64,
+        ready_time_ns: u64,
+    }
+
+    fn roundtrip<T: Serialize>(value: &T) -> Ready {
+        let mut buf = Vec::new();
+        ciborium::into_writer(value, &mut buf).unwrap();
+        ciborium::from_reader(&buf[..]).unwrap()
+    }
+
+    #[test]
+    fn test_legacy_ready_supports_original_features_only() {
+        let ready = roundtrip(&LegacyReady {
+            boot_time_ns: 1,
+            init_time_ns: 2,
+            ready_time_ns: 3,
+        });
+
+        assert_eq!(ready.versions(), 1..=1);
+        assert_eq!(ready.negotiate(), Some(1));
+        assert!(ready.supports(&MessageType::ExecRequest));
+        assert!(ready.supports_fs_op("rename"));
+        assert!(!ready.supports(&MessageType::TunnelOpen));
+        assert!(!ready.supports(&MessageType::Credit));
+        assert!(!ready.supports_compression("zstd"));
+        assert_eq!(ready.compression(), None);
+    }
+
+    #[test]
+    fn test_ready_advertises_capabilities() {
+        let ready = roundtrip(&Ready::new(
+            1,
+            2,
+            3,
+            Capabilities {
+                messages: vec!["core.tunnel.open".into(), "core.future.thing".into()],
+                fs_ops: vec!["stat".into()],
+                compression: vec!["zstd".into()],
+            },
+        ));
+
+        assert_eq!(ready.versions(), MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION);
+        assert!(ready.supports(&MessageType::TunnelOpen));
+        assert!(!ready.supports(&MessageType::ExecRequest));
+        assert!(ready.supports_fs_op("stat"));
+        assert!(!ready.supports_fs_op("rename"));
+        assert!(ready.supports_compression("zstd"));
+        assert_eq!(ready.compression(), Some(Compression::Zstd));
+    }
+
+    #[test]
+    fn test_negotiate_requires_overlapping_versions() {
+        let mut ready = Ready::new(0, 0, 0, Capabilities::default());
+        assert_eq!(ready.negotiate(), Some(PROTOCOL_VERSION));
+
+        ready.min_version = PROTOCOL_VERSION + 1;
+        ready.max_version = PROTOCOL_VERSION + 3;
+        assert_eq!(ready.negotiate(), None);
+
+        ready.min_version = MIN_PROTOCOL_VERSION;
+        assert_eq!(ready.negotiate(), Some(PROTOCOL_VERSION));
+    }
 }
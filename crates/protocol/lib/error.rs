@@ -42,6 +42,10 @@ pub enum ProtocolError {
         min: u32,
     },
 
+    /// A frame body could not be compressed or decompressed.
+    #[error("compression error: {0}")]
+    Compression(String),
+
     /// Unexpected end of stream.
     #[error("unexpected end of stream")]
     UnexpectedEof,
@@ -2,6 +2,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::compress::Compression;
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -20,6 +22,11 @@ pub struct ExecRequest {
     #[serde(default)]
     pub env: Vec<String>,
 
+    /// Start the process with only `env` instead of on top of the guest's
+    /// environment.
+    #[serde(default)]
+    pub clear_env: bool,
+
     /// Working directory for the command.
     #[serde(default)]
     pub cwd: Option<String>,
@@ -43,6 +50,31 @@ pub struct ExecRequest {
     /// POSIX resource limits to apply to the spawned process via `setrlimit()`.
     #[serde(default)]
     pub rlimits: Vec<ExecRlimit>,
+
+    /// W3C trace context of the host span that started the command, exported
+    /// to the process as `TRACEPARENT` unless `env` already sets it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+
+    /// Name under which the session outlives the client that started it.
+    ///
+    /// Persistent sessions keep running when the client disconnects, buffer
+    /// their recent output, and can be resumed with [`ExecAttach`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistent: Option<String>,
+
+    /// Flow-control window for the session's output, in bytes.
+    ///
+    /// When set, the guest pauses reading the process's output once this
+    /// many bytes are unacknowledged, until the host returns
+    /// [`Credit`](crate::flow::Credit). `None` disables flow control.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<u32>,
+
+    /// Codec for large stdout and stderr chunks. `None` sends them
+    /// uncompressed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
 }
 
 /// A POSIX resource limit to apply to a spawned process.
@@ -90,10 +122,39 @@ pub struct ExecStderr {
 }
 
 /// Notification that a command has exited.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExecExited {
-    /// The exit code of the process.
+    /// The exit code of the process, or -1 if it did not exit normally.
     pub code: i32,
+
+    /// The signal that terminated the process, if it was killed by one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+
+    /// Whether the process dumped core when it was terminated.
+    #[serde(default)]
+    pub core_dumped: bool,
+
+    /// Resource usage of the process and its reaped children. Absent when
+    /// the process could not be waited on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ExecUsage>,
+}
+
+/// Resource usage of an exited command, from `wait4(2)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecUsage {
+    /// Time from spawn to exit, in microseconds.
+    pub wall_time_us: u64,
+
+    /// CPU time spent in user mode, in microseconds.
+    pub user_time_us: u64,
+
+    /// CPU time spent in kernel mode, in microseconds.
+    pub sys_time_us: u64,
+
+    /// Peak resident set size, in KiB.
+    pub max_rss_kib: u64,
 }
 
 /// Request to resize the PTY of a running command.
@@ -113,6 +174,63 @@ pub struct ExecSignal {
     pub signal: i32,
 }
 
+/// Request to resume a persistent exec session under a new correlation ID.
+///
+/// The guest replies with `ExecStarted`, replays the session's buffered
+/// output, then streams as for a new session. If the session does not exist
+/// or is attached to another client, the guest replies with `ExecExited`
+/// (code -1) only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecAttach {
+    /// Name the session was started with.
+    pub session: String,
+
+    /// Flow-control window for the session's output, as for
+    /// [`ExecRequest::window`]. Replayed output counts against it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<u32>,
+
+    /// Output codec, as for [`ExecRequest::compression`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+}
+
+/// Response listing the guest's persistent exec sessions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecSessions {
+    /// Known sessions, running or exited but not yet collected.
+    pub sessions: Vec<ExecSessionInfo>,
+}
+
+/// A persistent exec session as reported by the guest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecSessionInfo {
+    /// Session name.
+    pub name: String,
+
+    /// PID of the session's process.
+    pub pid: u32,
+
+    /// Command line the session was started with.
+    pub cmd: String,
+
+    /// Whether the session runs in a PTY.
+    pub tty: bool,
+
+    /// Whether a client is currently attached.
+    pub attached: bool,
+
+    /// Exit code, once the process has exited.
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+
+    /// Bytes of output currently buffered for replay.
+    pub buffered: u64,
+
+    /// When the session was started, in seconds since the Unix epoch.
+    pub started_at: i64,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
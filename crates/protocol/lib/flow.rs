@@ -0,0 +1,31 @@
+//! Credit-based flow control for streamed data.
+//!
+//! A stream opts in by carrying a `window` in the request that opens it
+//! (`ExecRequest`, `ExecAttach`, `FsOp::Read`, `FsOp::Write`). The sender of
+//! the stream's data may then have at most that many payload bytes
+//! outstanding; the receiver returns credit with [`Credit`] messages on the
+//! same correlation ID as it consumes the data.
+//!
+//! Exec output and file reads are flow-controlled by the host; file writes
+//! by the guest. Streams opened without a window are not flow-controlled.
+
+use serde::{Deserialize, Serialize};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Default window for exec output streams (256 KiB).
+pub const DEFAULT_WINDOW: u32 = 256 * 1024;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Window update: the receiver has consumed `bytes` more payload bytes and
+/// the sender may send that many more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credit {
+    /// Payload bytes returned to the sender's window.
+    pub bytes: u32,
+}
@@ -2,6 +2,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::compress::Compression;
+
 //--------------------------------------------------------------------------------------------------
 // Constants
 //--------------------------------------------------------------------------------------------------
@@ -11,6 +13,10 @@ use serde::{Deserialize, Serialize};
 /// This stays safely under the 4 MiB frame limit after CBOR envelope overhead.
 pub const FS_CHUNK_SIZE: usize = 3 * 1024 * 1024;
 
+/// Default flow-control window for streaming reads and writes: two chunks in
+/// flight.
+pub const FS_WINDOW: u32 = 2 * FS_CHUNK_SIZE as u32;
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -34,6 +40,12 @@ pub enum FsOp {
     Read {
         /// Guest file path to read.
         path: String,
+        /// Flow-control window for the `FsData` chunks, in bytes.
+        #[serde(default)]
+        window: Option<u32>,
+        /// Codec for the `FsData` chunks. `None` sends them uncompressed.
+        #[serde(default)]
+        compression: Option<Compression>,
     },
 
     /// Write a file (streaming: host sends FsData chunks, guest replies with FsResponse).
@@ -43,6 +55,10 @@ pub enum FsOp {
         /// Permission bits to set on creation (e.g. 0o644).
         #[serde(default)]
         mode: Option<u32>,
+        /// Flow-control window for the host's `FsData` chunks, in bytes.
+        /// The guest returns credit as it writes them to disk.
+        #[serde(default)]
+        window: Option<u32>,
     },
 
     /// Create a directory (and parents).
@@ -144,3 +160,38 @@ pub struct FsData {
     #[serde(with = "serde_bytes")]
     pub data: Vec<u8>,
 }
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl FsOp {
+    /// Names of every operation, as returned by [`name`](Self::name).
+    pub const NAMES: &[&str] = &[
+        "stat",
+        "list",
+        "read",
+        "write",
+        "mkdir",
+        "remove",
+        "remove_dir",
+        "copy",
+        "rename",
+    ];
+
+    /// Short name of the operation, used to advertise support in
+    /// [`Capabilities`](crate::core::Capabilities).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Stat { .. } => "stat",
+            Self::List { .. } => "list",
+            Self::Read { .. } => "read",
+            Self::Write { .. } => "write",
+            Self::Mkdir { .. } => "mkdir",
+            Self::Remove { .. } => "remove",
+            Self::RemoveDir { .. } => "remove_dir",
+            Self::Copy { .. } => "copy",
+            Self::Rename { .. } => "rename",
+        }
+    }
+}
@@ -168,10 +168,15 @@ pub const GUEST_TLS_CA_PATH: &str = "/.msb/tls/ca.pem";
 //--------------------------------------------------------------------------------------------------
 
 pub mod codec;
+pub mod compress;
 pub mod core;
 pub mod exec;
+pub mod flow;
 pub mod fs;
 pub mod heartbeat;
 pub mod message;
+pub mod process;
+pub mod stats;
+pub mod tunnel;
 
 pub use error::*;
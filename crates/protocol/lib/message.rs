@@ -2,7 +2,10 @@
 
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-use crate::error::ProtocolResult;
+use crate::{
+    compress::{Compression, FLAG_COMPRESSED},
+    error::ProtocolResult,
+};
 
 //--------------------------------------------------------------------------------------------------
 // Constants
@@ -11,6 +14,9 @@ use crate::error::ProtocolResult;
 /// Current protocol version.
 pub const PROTOCOL_VERSION: u8 = 1;
 
+/// Oldest protocol version this build can still speak.
+pub const MIN_PROTOCOL_VERSION: u8 = 1;
+
 /// Frame flag: this is the last message for the given correlation ID.
 ///
 /// Set on terminal message types such as `ExecExited` and `FsResponse`.
@@ -27,6 +33,23 @@ pub const FLAG_SESSION_START: u8 = 0b0000_0010;
 /// drain escalation (SIGTERM → SIGKILL) if the guest doesn't exit voluntarily.
 pub const FLAG_SHUTDOWN: u8 = 0b0000_0100;
 
+/// Frame flag: the session started by this frame outlives its client.
+///
+/// Set on `ExecRequest` frames for persistent sessions and on `ExecAttach`.
+/// On client disconnect the relay sends `ExecDetach` for these sessions
+/// instead of killing them.
+pub const FLAG_PERSISTENT: u8 = 0b0000_1000;
+
+/// Frame flag: the CBOR body is zstd-compressed.
+///
+/// Set by the codec on messages marked with [`Message::compress`]; see
+/// [`crate::compress`].
+pub const FLAG_COMPRESSED_ZSTD: u8 = 0b0001_0000;
+
+/// Frame flag: the CBOR body is LZ4-compressed (block format, size
+/// prepended).
+pub const FLAG_COMPRESSED_LZ4: u8 = 0b0010_0000;
+
 /// Size of the frame header fields that sit between the length prefix and the
 /// CBOR payload: `[id: u32 BE][flags: u8]` = 5 bytes.
 pub const FRAME_HEADER_SIZE: usize = 5;
@@ -58,9 +81,11 @@ pub struct Message {
     #[serde(skip)]
     pub id: u32,
 
-    /// Frame flags computed from the message type.
+    /// Frame flags computed from the message type, plus a compression flag
+    /// if the message is marked for compression.
     ///
-    /// Serialized in the binary frame header, not in CBOR.
+    /// Serialized in the binary frame header, not in CBOR. Decoded messages
+    /// never carry a compression flag.
     #[serde(skip)]
     pub flags: u8,
 
@@ -78,6 +103,9 @@ pub enum MessageType {
     /// Host requests shutdown.
     Shutdown,
 
+    /// Receiver of a flow-controlled stream returns window credit (bidirectional).
+    Credit,
+
     /// Host requests command execution.
     ExecRequest,
 
@@ -102,6 +130,21 @@ pub enum MessageType {
     /// Host sends signal to process.
     ExecSignal,
 
+    /// Host resumes a persistent exec session.
+    ExecAttach,
+
+    /// Host relay reports that the client of a session went away.
+    ///
+    /// Persistent sessions keep running detached; others stop waiting for
+    /// flow-control credit so they can drain and exit.
+    ExecDetach,
+
+    /// Host requests the list of persistent exec sessions.
+    ExecSessionsRequest,
+
+    /// Guest sends a terminal persistent session list.
+    ExecSessionsResponse,
+
     /// Host requests a filesystem operation.
     FsRequest,
 
@@ -110,6 +153,39 @@ pub enum MessageType {
 
     /// Streaming file data chunk (bidirectional).
     FsData,
+
+    /// Host requests a guest resource statistics snapshot.
+    StatsRequest,
+
+    /// Guest sends a terminal statistics response.
+    StatsResponse,
+
+    /// Host requests a guest process operation.
+    ProcessRequest,
+
+    /// Guest sends a terminal process operation response.
+    ProcessResponse,
+
+    /// Host opens a tunnel to a guest socket.
+    TunnelOpen,
+
+    /// Host asks the guest to listen on a socket and report connections.
+    TunnelListen,
+
+    /// Guest confirms a tunnel or listener is open.
+    TunnelOpened,
+
+    /// Guest reports a connection accepted by a listener.
+    TunnelAccept,
+
+    /// Host claims an accepted connection on a new correlation ID.
+    TunnelBind,
+
+    /// Tunneled stream data (bidirectional).
+    TunnelData,
+
+    /// Tunnel or listener closed (bidirectional, terminal).
+    TunnelClose,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -151,14 +227,37 @@ impl Message {
     pub fn payload<T: DeserializeOwned>(&self) -> ProtocolResult<T> {
         Ok(ciborium::from_reader(&self.p[..])?)
     }
+
+    /// Marks the message to be compressed with `codec` when encoded, if
+    /// its body is large enough to benefit. `None` leaves it uncompressed.
+    pub fn compress(mut self, codec: Option<Compression>) -> Self {
+        self.flags &= !FLAG_COMPRESSED;
+        if let Some(codec) = codec {
+            self.flags |= codec.flag();
+        }
+        self
+    }
 }
 
 impl MessageType {
     /// Computes the frame flags byte for this message type.
     pub fn flags(&self) -> u8 {
         match self {
-            Self::ExecExited | Self::FsResponse => FLAG_TERMINAL,
-            Self::ExecRequest | Self::FsRequest => FLAG_SESSION_START,
+            Self::ExecExited
+            | Self::ExecSessionsResponse
+            | Self::FsResponse
+            | Self::StatsResponse
+            | Self::ProcessResponse
+            | Self::TunnelClose => FLAG_TERMINAL,
+            Self::ExecRequest
+            | Self::ExecSessionsRequest
+            | Self::FsRequest
+            | Self::StatsRequest
+            | Self::ProcessRequest
+            | Self::TunnelOpen
+            | Self::TunnelListen
+            | Self::TunnelBind => FLAG_SESSION_START,
+            Self::ExecAttach => FLAG_SESSION_START | FLAG_PERSISTENT,
             Self::Shutdown => FLAG_SHUTDOWN,
             _ => 0,
         }
@@ -169,6 +268,7 @@ impl MessageType {
         match self {
             Self::Ready => "core.ready",
             Self::Shutdown => "core.shutdown",
+            Self::Credit => "core.credit",
             Self::ExecRequest => "core.exec.request",
             Self::ExecStarted => "core.exec.started",
             Self::ExecStdin => "core.exec.stdin",
@@ -177,9 +277,24 @@ impl MessageType {
             Self::ExecExited => "core.exec.exited",
             Self::ExecResize => "core.exec.resize",
             Self::ExecSignal => "core.exec.signal",
+            Self::ExecAttach => "core.exec.attach",
+            Self::ExecDetach => "core.exec.detach",
+            Self::ExecSessionsRequest => "core.exec.sessions.request",
+            Self::ExecSessionsResponse => "core.exec.sessions.response",
             Self::FsRequest => "core.fs.request",
             Self::FsResponse => "core.fs.response",
             Self::FsData => "core.fs.data",
+            Self::StatsRequest => "core.stats.request",
+            Self::StatsResponse => "core.stats.response",
+            Self::ProcessRequest => "core.process.request",
+            Self::ProcessResponse => "core.process.response",
+            Self::TunnelOpen => "core.tunnel.open",
+            Self::TunnelListen => "core.tunnel.listen",
+            Self::TunnelOpened => "core.tunnel.opened",
+            Self::TunnelAccept => "core.tunnel.accept",
+            Self::TunnelBind => "core.tunnel.bind",
+            Self::TunnelData => "core.tunnel.data",
+            Self::TunnelClose => "core.tunnel.close",
         }
     }
 
@@ -188,6 +303,7 @@ impl MessageType {
         match s {
             "core.ready" => Some(Self::Ready),
             "core.shutdown" => Some(Self::Shutdown),
+            "core.credit" => Some(Self::Credit),
             "core.exec.request" => Some(Self::ExecRequest),
             "core.exec.started" => Some(Self::ExecStarted),
             "core.exec.stdin" => Some(Self::ExecStdin),
@@ -196,9 +312,24 @@ impl MessageType {
             "core.exec.exited" => Some(Self::ExecExited),
             "core.exec.resize" => Some(Self::ExecResize),
             "core.exec.signal" => Some(Self::ExecSignal),
+            "core.exec.attach" => Some(Self::ExecAttach),
+            "core.exec.detach" => Some(Self::ExecDetach),
+            "core.exec.sessions.request" => Some(Self::ExecSessionsRequest),
+            "core.exec.sessions.response" => Some(Self::ExecSessionsResponse),
             "core.fs.request" => Some(Self::FsRequest),
             "core.fs.response" => Some(Self::FsResponse),
             "core.fs.data" => Some(Self::FsData),
+            "core.stats.request" => Some(Self::StatsRequest),
+            "core.stats.response" => Some(Self::StatsResponse),
+            "core.process.request" => Some(Self::ProcessRequest),
+            "core.process.response" => Some(Self::ProcessResponse),
+            "core.tunnel.open" => Some(Self::TunnelOpen),
+            "core.tunnel.listen" => Some(Self::TunnelListen),
+            "core.tunnel.opened" => Some(Self::TunnelOpened),
+            "core.tunnel.accept" => Some(Self::TunnelAccept),
+            "core.tunnel.bind" => Some(Self::TunnelBind),
+            "core.tunnel.data" => Some(Self::TunnelData),
+            "core.tunnel.close" => Some(Self::TunnelClose),
             _ => None,
         }
     }
@@ -241,6 +372,7 @@ mod tests {
         let types = [
             (MessageType::Ready, "core.ready"),
             (MessageType::Shutdown, "core.shutdown"),
+            (MessageType::Credit, "core.credit"),
             (MessageType::ExecRequest, "core.exec.request"),
             (MessageType::ExecStarted, "core.exec.started"),
             (MessageType::ExecStdin, "core.exec.stdin"),
@@ -249,9 +381,30 @@ mod tests {
             (MessageType::ExecExited, "core.exec.exited"),
             (MessageType::ExecResize, "core.exec.resize"),
             (MessageType::ExecSignal, "core.exec.signal"),
+            (MessageType::ExecAttach, "core.exec.attach"),
+            (MessageType::ExecDetach, "core.exec.detach"),
+            (
+                MessageType::ExecSessionsRequest,
+                "core.exec.sessions.request",
+            ),
+            (
+                MessageType::ExecSessionsResponse,
+                "core.exec.sessions.response",
+            ),
             (MessageType::FsRequest, "core.fs.request"),
             (MessageType::FsResponse, "core.fs.response"),
             (MessageType::FsData, "core.fs.data"),
+            (MessageType::StatsRequest, "core.stats.request"),
+            (MessageType::StatsResponse, "core.stats.response"),
+            (MessageType::ProcessRequest, "core.process.request"),
+            (MessageType::ProcessResponse, "core.process.response"),
+            (MessageType::TunnelOpen, "core.tunnel.open"),
+            (MessageType::TunnelListen, "core.tunnel.listen"),
+            (MessageType::TunnelOpened, "core.tunnel.opened"),
+            (MessageType::TunnelAccept, "core.tunnel.accept"),
+            (MessageType::TunnelBind, "core.tunnel.bind"),
+            (MessageType::TunnelData, "core.tunnel.data"),
+            (MessageType::TunnelClose, "core.tunnel.close"),
         ];
 
         for (mt, expected_str) in &types {
@@ -265,6 +418,7 @@ mod tests {
         let types = [
             MessageType::Ready,
             MessageType::Shutdown,
+            MessageType::Credit,
             MessageType::ExecRequest,
             MessageType::ExecStarted,
             MessageType::ExecStdin,
@@ -273,9 +427,24 @@ mod tests {
             MessageType::ExecExited,
             MessageType::ExecResize,
             MessageType::ExecSignal,
+            MessageType::ExecAttach,
+            MessageType::ExecDetach,
+            MessageType::ExecSessionsRequest,
+            MessageType::ExecSessionsResponse,
             MessageType::FsRequest,
             MessageType::FsResponse,
             MessageType::FsData,
+            MessageType::StatsRequest,
+            MessageType::StatsResponse,
+            MessageType::ProcessRequest,
+            MessageType::ProcessResponse,
+            MessageType::TunnelOpen,
+            MessageType::TunnelListen,
+            MessageType::TunnelOpened,
+            MessageType::TunnelAccept,
+            MessageType::TunnelBind,
+            MessageType::TunnelData,
+            MessageType::TunnelClose,
         ];
 
         for mt in &types {
@@ -295,8 +464,15 @@ mod tests {
     fn test_message_with_payload_roundtrip() {
         use crate::exec::ExecExited;
 
-        let msg =
-            Message::with_payload(MessageType::ExecExited, 7, &ExecExited { code: 42 }).unwrap();
+        let msg = Message::with_payload(
+            MessageType::ExecExited,
+            7,
+            &ExecExited {
+                code: 42,
+                ..Default::default()
+            },
+        )
+        .unwrap();
 
         assert_eq!(msg.t, MessageType::ExecExited);
         assert_eq!(msg.id, 7);
@@ -306,12 +482,50 @@ mod tests {
         assert_eq!(payload.code, 42);
     }
 
+    #[test]
+    fn test_exec_exited_details_are_optional() {
+        use crate::exec::{ExecExited, ExecUsage};
+
+        // Payload from an agent that only reports the exit code.
+        #[derive(Serialize)]
+        struct CodeOnly {
+            code: i32,
+        }
+        let msg = Message::with_payload(MessageType::ExecExited, 1, &CodeOnly { code: 3 }).unwrap();
+        let payload: ExecExited = msg.payload().unwrap();
+        assert_eq!(payload.code, 3);
+        assert_eq!(payload.signal, None);
+        assert!(!payload.core_dumped);
+        assert_eq!(payload.usage, None);
+
+        let usage = ExecUsage {
+            wall_time_us: 1_500_000,
+            user_time_us: 900_000,
+            sys_time_us: 100_000,
+            max_rss_kib: 65_536,
+        };
+        let exited = ExecExited {
+            code: -1,
+            signal: Some(9),
+            core_dumped: false,
+            usage: Some(usage),
+        };
+        let msg = Message::with_payload(MessageType::ExecExited, 1, &exited).unwrap();
+        let payload: ExecExited = msg.payload().unwrap();
+        assert_eq!(payload.signal, Some(9));
+        assert_eq!(payload.usage, Some(usage));
+    }
+
     #[test]
     fn test_message_type_flags() {
         assert_eq!(MessageType::ExecExited.flags(), FLAG_TERMINAL);
         assert_eq!(MessageType::FsResponse.flags(), FLAG_TERMINAL);
+        assert_eq!(MessageType::StatsResponse.flags(), FLAG_TERMINAL);
+        assert_eq!(MessageType::ProcessResponse.flags(), FLAG_TERMINAL);
         assert_eq!(MessageType::ExecRequest.flags(), FLAG_SESSION_START);
         assert_eq!(MessageType::FsRequest.flags(), FLAG_SESSION_START);
+        assert_eq!(MessageType::StatsRequest.flags(), FLAG_SESSION_START);
+        assert_eq!(MessageType::ProcessRequest.flags(), FLAG_SESSION_START);
         assert_eq!(MessageType::Ready.flags(), 0);
         assert_eq!(MessageType::Shutdown.flags(), FLAG_SHUTDOWN);
         assert_eq!(MessageType::ExecStarted.flags(), 0);
@@ -321,6 +535,20 @@ mod tests {
         assert_eq!(MessageType::ExecResize.flags(), 0);
         assert_eq!(MessageType::ExecSignal.flags(), 0);
         assert_eq!(MessageType::FsData.flags(), 0);
+        assert_eq!(
+            MessageType::ExecAttach.flags(),
+            FLAG_SESSION_START | FLAG_PERSISTENT
+        );
+        assert_eq!(MessageType::ExecDetach.flags(), 0);
+        assert_eq!(MessageType::ExecSessionsRequest.flags(), FLAG_SESSION_START);
+        assert_eq!(MessageType::ExecSessionsResponse.flags(), FLAG_TERMINAL);
+        assert_eq!(MessageType::TunnelOpen.flags(), FLAG_SESSION_START);
+        assert_eq!(MessageType::TunnelListen.flags(), FLAG_SESSION_START);
+        assert_eq!(MessageType::TunnelBind.flags(), FLAG_SESSION_START);
+        assert_eq!(MessageType::TunnelOpened.flags(), 0);
+        assert_eq!(MessageType::TunnelAccept.flags(), 0);
+        assert_eq!(MessageType::TunnelData.flags(), 0);
+        assert_eq!(MessageType::TunnelClose.flags(), FLAG_TERMINAL);
     }
 
     #[test]
@@ -0,0 +1,51 @@
+//! Guest process management protocol message payloads.
+//!
+//! Unlike exec signals, which target a session the host started, these
+//! operate on any process in the guest by PID.
+
+use serde::{Deserialize, Serialize};
+
+use crate::stats::GuestProcessStats;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A process operation requested by the host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProcessOp {
+    /// List every process in the guest.
+    List,
+
+    /// Send a signal to a process.
+    Signal {
+        /// Target process ID. Must be positive; process groups and
+        /// broadcast targets are rejected.
+        pid: i32,
+
+        /// Signal number.
+        signal: i32,
+    },
+}
+
+/// Request to perform a process operation in the guest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessRequest {
+    /// The operation to perform.
+    pub op: ProcessOp,
+}
+
+/// Terminal response to a [`ProcessRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessResponse {
+    /// Whether the operation succeeded.
+    pub ok: bool,
+
+    /// Error message if `ok` is false.
+    #[serde(default)]
+    pub error: Option<String>,
+
+    /// Processes sorted by PID, for [`ProcessOp::List`].
+    #[serde(default)]
+    pub processes: Vec<GuestProcessStats>,
+}
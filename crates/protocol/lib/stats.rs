@@ -0,0 +1,152 @@
+//! Guest resource statistics protocol message payloads.
+
+use serde::{Deserialize, Serialize};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Request for a snapshot of guest-level resource statistics.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsRequest {
+    /// Whether to include the per-process table.
+    #[serde(default)]
+    pub processes: bool,
+}
+
+/// Terminal response to a [`StatsRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsResponse {
+    /// Whether the snapshot was collected.
+    pub ok: bool,
+
+    /// Error message if `ok` is false.
+    #[serde(default)]
+    pub error: Option<String>,
+
+    /// The collected snapshot.
+    #[serde(default)]
+    pub stats: Option<GuestStats>,
+}
+
+/// A snapshot of resource usage as seen from inside the guest.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GuestStats {
+    /// Seconds since the guest kernel booted.
+    pub uptime_secs: f64,
+
+    /// Number of online guest CPUs.
+    pub cpu_count: u32,
+
+    /// Load averages over 1, 5 and 15 minutes.
+    pub load_average: [f64; 3],
+
+    /// Guest memory usage from `/proc/meminfo`.
+    pub memory: GuestMemoryStats,
+
+    /// Usage of each mounted filesystem backed by storage.
+    pub filesystems: Vec<GuestFilesystemStats>,
+
+    /// Per-process usage. Empty unless requested.
+    #[serde(default)]
+    pub processes: Vec<GuestProcessStats>,
+}
+
+/// Guest memory usage in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GuestMemoryStats {
+    /// Total usable memory.
+    pub total_bytes: u64,
+
+    /// Completely unused memory.
+    pub free_bytes: u64,
+
+    /// Memory available for new allocations without swapping.
+    pub available_bytes: u64,
+
+    /// Page cache memory.
+    pub cached_bytes: u64,
+
+    /// Block device buffer memory.
+    pub buffers_bytes: u64,
+
+    /// Total swap space.
+    pub swap_total_bytes: u64,
+
+    /// Unused swap space.
+    pub swap_free_bytes: u64,
+}
+
+/// Usage of a single mounted filesystem.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GuestFilesystemStats {
+    /// Mount point inside the guest.
+    pub mount_point: String,
+
+    /// Mount source (device or virtiofs tag).
+    pub source: String,
+
+    /// Filesystem type.
+    pub fs_type: String,
+
+    /// Total size in bytes.
+    pub total_bytes: u64,
+
+    /// Bytes in use.
+    pub used_bytes: u64,
+
+    /// Bytes available to unprivileged users.
+    pub available_bytes: u64,
+
+    /// Total inodes.
+    pub total_inodes: u64,
+
+    /// Free inodes.
+    pub free_inodes: u64,
+}
+
+/// Usage of a single guest process.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GuestProcessStats {
+    /// Process ID.
+    pub pid: i32,
+
+    /// Parent process ID.
+    pub ppid: i32,
+
+    /// Real user ID.
+    pub uid: u32,
+
+    /// User name for `uid` from the guest's `/etc/passwd`, or empty if it
+    /// has no entry.
+    #[serde(default)]
+    pub user: String,
+
+    /// Short command name.
+    pub name: String,
+
+    /// Full command line, space-separated. Empty for kernel threads.
+    pub cmdline: String,
+
+    /// Scheduler state (`R`, `S`, `D`, `Z`, ...).
+    pub state: String,
+
+    /// Number of threads.
+    pub threads: u32,
+
+    /// Resident set size in bytes.
+    pub rss_bytes: u64,
+
+    /// Virtual memory size in bytes.
+    pub vsize_bytes: u64,
+
+    /// Cumulative user and system CPU time in milliseconds.
+    pub cpu_time_ms: u64,
+
+    /// CPU usage as a percentage of one CPU since the previous stats
+    /// request, or since process start for processes not seen before.
+    pub cpu_percent: f32,
+
+    /// Seconds since guest boot at which the process started.
+    pub start_time_secs: f64,
+}
@@ -0,0 +1,106 @@
+//! Tunnel message payloads: byte streams between a host socket and a guest
+//! socket, carried over the agent channel.
+//!
+//! Tunnels work without a guest network stack. Each tunneled connection has
+//! its own correlation ID:
+//!
+//! - **Forward**: the host sends [`TunnelOpen`]; agentd connects to the
+//!   guest endpoint and replies `core.tunnel.opened`, or
+//!   [`TunnelClose`] with an error.
+//! - **Reverse**: the host sends [`TunnelListen`]; agentd listens on the
+//!   guest endpoint, replies `core.tunnel.opened`, and reports each accepted
+//!   connection with [`TunnelAccept`] on the listener's ID. The host picks a
+//!   new ID for the connection and claims it with [`TunnelBind`].
+//!
+//! Either side sends [`TunnelData`]; an empty chunk means the sender will
+//! write no more (half-close). The guest sends [`TunnelClose`] once both
+//! directions have finished or on error; the host sends it to abort.
+//! Data is flow-controlled in both directions by the `window` given when
+//! the connection is opened or bound (see [`crate::flow`]).
+
+use serde::{Deserialize, Serialize};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Maximum payload of a single [`TunnelData`] chunk (64 KiB).
+pub const TUNNEL_CHUNK_SIZE: usize = 64 * 1024;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A socket inside the guest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TunnelEndpoint {
+    /// A TCP port on the guest's loopback interface.
+    Tcp {
+        /// Port number.
+        port: u16,
+    },
+
+    /// A Unix domain socket in the guest filesystem.
+    Unix {
+        /// Absolute socket path.
+        path: String,
+    },
+}
+
+/// Request to connect to a guest endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelOpen {
+    /// Where to connect.
+    pub endpoint: TunnelEndpoint,
+
+    /// Flow-control window for each direction, in bytes. `None` disables
+    /// flow control.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<u32>,
+}
+
+/// Request to listen on a guest endpoint and report incoming connections.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelListen {
+    /// Where to listen.
+    pub endpoint: TunnelEndpoint,
+}
+
+/// A guest listener accepted a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TunnelAccept {
+    /// Guest-assigned number of the connection, unique per listener.
+    pub conn: u32,
+}
+
+/// Claim an accepted connection, carrying it on this message's correlation
+/// ID from now on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelBind {
+    /// Correlation ID of the listener that accepted the connection.
+    pub listener: u32,
+
+    /// Connection number from [`TunnelAccept`].
+    pub conn: u32,
+
+    /// Flow-control window for each direction, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<u32>,
+}
+
+/// A chunk of a tunneled stream. Empty data ends the sender's direction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelData {
+    /// Stream bytes.
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// The tunnel or listener is closed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TunnelClose {
+    /// Why, if it closed because of an error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -0,0 +1,313 @@
+//! Sandbox lifecycle events.
+//!
+//! Both the host library and the sandbox process append [`EventKind`]s to the
+//! `sandbox_event` table; subscribers tail the table to observe every sandbox
+//! without polling its status.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use microsandbox_db::entity::{
+    run::TerminationReason, sandbox::HealthStatus, sandbox_event as event_entity,
+};
+use sea_orm::{ActiveValue::Set, DatabaseConnection, DbErr, EntityTrait};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Window during which repeated policy denials for the same destination are
+/// recorded once. Guests retry blocked connections, and each retry is denied.
+const POLICY_DENIAL_DEDUP_WINDOW: Duration = Duration::from_secs(60);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// What happened to a sandbox.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    /// The sandbox record was created.
+    Created,
+
+    /// The sandbox booted and its agent is ready.
+    Started,
+
+    /// The guest was asked to shut down.
+    Draining {
+        /// Why the drain was requested.
+        reason: String,
+    },
+
+    /// The sandbox process exited.
+    Stopped {
+        /// Why the run ended.
+        reason: TerminationReason,
+
+        /// Exit code reported by the VM.
+        exit_code: Option<i32>,
+
+        /// Extra context, e.g. which resource limit was hit.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<String>,
+    },
+
+    /// The sandbox process died without recording its exit.
+    Crashed,
+
+    /// The sandbox and its state were removed.
+    Removed,
+
+    /// A command started in the guest.
+    ExecStarted {
+        /// Session ID of the exec.
+        session_id: u32,
+
+        /// Command that was run.
+        cmd: String,
+
+        /// Guest PID of the process.
+        pid: u32,
+    },
+
+    /// A command in the guest exited.
+    ExecExited {
+        /// Session ID of the exec.
+        session_id: u32,
+
+        /// Exit code of the process.
+        exit_code: i32,
+    },
+
+    /// A resource limit from the sandbox policy was hit.
+    ResourceLimit {
+        /// The limit spec.
+        limit: String,
+
+        /// Human-readable description of the breach.
+        detail: String,
+    },
+
+    /// The sandbox's health check status changed.
+    HealthChanged {
+        /// The new status.
+        status: HealthStatus,
+
+        /// Why the last probe failed, if it did.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<String>,
+    },
+
+    /// A secret placeholder was sent to a host not allowed to receive it.
+    SecretViolation {
+        /// Host the request was addressed to.
+        host: String,
+    },
+
+    /// The network policy denied an outbound connection.
+    PolicyDenial {
+        /// Denied destination address.
+        destination: String,
+
+        /// Transport protocol.
+        protocol: String,
+    },
+}
+
+/// Buffers runtime events and writes them to the database in order.
+#[derive(Clone)]
+pub struct EventSink {
+    tx: mpsc::UnboundedSender<EventKind>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl EventKind {
+    /// The `type` tag of this event, also stored in the `event_type` column.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Started => "started",
+            Self::Draining { .. } => "draining",
+            Self::Stopped { .. } => "stopped",
+            Self::Crashed => "crashed",
+            Self::Removed => "removed",
+            Self::ExecStarted { .. } => "exec_started",
+            Self::ExecExited { .. } => "exec_exited",
+            Self::ResourceLimit { .. } => "resource_limit",
+            Self::HealthChanged { .. } => "health_changed",
+            Self::SecretViolation { .. } => "secret_violation",
+            Self::PolicyDenial { .. } => "policy_denial",
+        }
+    }
+}
+
+impl EventSink {
+    /// Spawn the writer task for a sandbox and return a sink feeding it.
+    pub fn spawn(
+        handle: &tokio::runtime::Handle,
+        db: DatabaseConnection,
+        sandbox_id: i32,
+        sandbox_name: String,
+    ) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        handle.spawn(run_event_writer(db, sandbox_id, sandbox_name, rx));
+        Self { tx }
+    }
+
+    /// Queue an event. Never blocks; safe to call from non-async threads.
+    pub fn emit(&self, event: EventKind) {
+        let _ = self.tx.send(event);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Append an event to the `sandbox_event` table.
+pub async fn record_event(
+    db: &DatabaseConnection,
+    sandbox_id: Option<i32>,
+    sandbox_name: &str,
+    event: &EventKind,
+) -> Result<(), DbErr> {
+    let payload = serde_json::to_string(event).map_err(|e| DbErr::Custom(e.to_string()))?;
+    let record = event_entity::ActiveModel {
+        sandbox_id: Set(sandbox_id),
+        sandbox_name: Set(sandbox_name.to_string()),
+        event_type: Set(event.name().to_string()),
+        payload: Set(payload),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    };
+    event_entity::Entity::insert(record).exec(db).await?;
+    Ok(())
+}
+
+async fn run_event_writer(
+    db: DatabaseConnection,
+    sandbox_id: i32,
+    sandbox_name: String,
+    mut rx: mpsc::UnboundedReceiver<EventKind>,
+) {
+    let mut recent_denials: HashMap<(String, String), Instant> = HashMap::new();
+
+    while let Some(event) = rx.recv().await {
+        if let EventKind::PolicyDenial {
+            destination,
+            protocol,
+        } = &event
+        {
+            let key = (destination.clone(), protocol.clone());
+            if !should_record_denial(&mut recent_denials, key, Instant::now()) {
+                continue;
+            }
+        }
+
+        if let Err(e) = record_event(&db, Some(sandbox_id), &sandbox_name, &event).await {
+            tracing::warn!(event = event.name(), error = %e, "failed to record sandbox event");
+        }
+    }
+}
+
+/// Whether a policy denial for `key` at `now` should be recorded. Each
+/// destination is recorded at most once per [`POLICY_DENIAL_DEDUP_WINDOW`],
+/// measured from the last recorded denial rather than the last suppressed one.
+fn should_record_denial(
+    recent: &mut HashMap<(String, String), Instant>,
+    key: (String, String),
+    now: Instant,
+) -> bool {
+    recent.retain(|_, at| now.duration_since(*at) < POLICY_DENIAL_DEDUP_WINDOW);
+    match recent.get(&key) {
+        Some(at) if now.duration_since(*at) < POLICY_DENIAL_DEDUP_WINDOW => false,
+        _ => {
+            recent.insert(key, now);
+            true
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_denial_recorded_once_per_window() {
+        let mut recent = HashMap::new();
+        let key = ("10.0.0.1:443".to_string(), "tcp".to_string());
+        let start = Instant::now();
+
+        // A guest retrying every 10s for two and a half minutes.
+        let recorded: Vec<u64> = (0..=15)
+            .map(|i| i * 10)
+            .filter(|secs| {
+                should_record_denial(&mut recent, key.clone(), start + Duration::from_secs(*secs))
+            })
+            .collect();
+        assert_eq!(recorded, [0, 60, 120]);
+
+        let other = ("10.0.0.2:443".to_string(), "tcp".to_string());
+        assert!(should_record_denial(
+            &mut recent,
+            other,
+            start + Duration::from_secs(150)
+        ));
+    }
+
+    #[test]
+    fn test_event_json_is_tagged_with_name() {
+        let events = [
+            EventKind::Created,
+            EventKind::Stopped {
+                reason: TerminationReason::IdleTimeout,
+                exit_code: Some(0),
+                detail: None,
+            },
+            EventKind::ExecExited {
+                session_id: 3,
+                exit_code: 1,
+            },
+            EventKind::PolicyDenial {
+                destination: "10.0.0.1:443".into(),
+                protocol: "tcp".into(),
+            },
+            EventKind::HealthChanged {
+                status: HealthStatus::Unhealthy,
+                detail: Some("command exited with code 1".into()),
+            },
+        ];
+
+        for event in events {
+            let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+            assert_eq!(json["type"], event.name());
+            assert_eq!(serde_json::from_value::<EventKind>(json).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn test_stopped_event_serializes_reason() {
+        let json = serde_json::to_string(&EventKind::Stopped {
+            reason: TerminationReason::ResourceLimitExceeded,
+            exit_code: None,
+            detail: Some("memory over".into()),
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"stopped","reason":"ResourceLimitExceeded","exit_code":null,"detail":"memory over"}"#
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Sandbox health checks.
+//!
+//! [`run_probe`] performs one probe against the guest; a [`HealthMonitor`]
+//! turns the stream of probe results into health status transitions for the
+//! runtime to record and act on.
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use microsandbox_db::entity::sandbox::HealthStatus;
+use microsandbox_protocol::{
+    codec,
+    exec::{ExecExited, ExecRequest, ExecStdin},
+    message::{Message, MessageType},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream},
+    net::UnixStream,
+};
+
+use crate::policy::{HealthCheck, HealthProbe};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Opens connections to guest ports for TCP and HTTP probes.
+#[cfg(feature = "net")]
+pub type GuestConnector = microsandbox_network::publisher::GuestConnector;
+
+/// Opens connections to guest ports for TCP and HTTP probes.
+#[cfg(not(feature = "net"))]
+pub type GuestConnector = ();
+
+/// Tracks consecutive probe failures for one health check.
+#[derive(Debug)]
+pub struct HealthMonitor {
+    retries: u32,
+    grace_until: Instant,
+    failures: u32,
+    status: HealthStatus,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl HealthMonitor {
+    /// Create a monitor for a check on a sandbox that booted at `started`.
+    pub fn new(check: &HealthCheck, started: Instant) -> Self {
+        Self {
+            retries: check.retries.max(1),
+            grace_until: started + Duration::from_secs(check.start_period_secs),
+            failures: 0,
+            status: HealthStatus::Starting,
+        }
+    }
+
+    /// The current health status.
+    pub fn status(&self) -> HealthStatus {
+        self.status
+    }
+
+    /// Record a probe result taken at `now`, returning the new status if it
+    /// changed.
+    ///
+    /// Failures during the start period are ignored until the first probe
+    /// passes.
+    pub fn record(&mut self, passed: bool, now: Instant) -> Option<HealthStatus> {
+        let next = if passed {
+            self.failures = 0;
+            HealthStatus::Healthy
+        } else {
+            if self.status == HealthStatus::Starting && now < self.grace_until {
+                return None;
+            }
+            self.failures = self.failures.saturating_add(1);
+            if self.failures < self.retries {
+                return None;
+            }
+            HealthStatus::Unhealthy
+        };
+
+        if next == self.status {
+            return None;
+        }
+        self.status = next;
+        Some(next)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Run one probe, failing with a description if it does not pass within
+/// `timeout`.
+pub async fn run_probe(
+    probe: &HealthProbe,
+    timeout: Duration,
+    agent_sock: &Path,
+    connector: Option<&GuestConnector>,
+) -> Result<(), String> {
+    let probe_result = async {
+        match probe {
+            HealthProbe::Exec { command } => probe_exec(agent_sock, command).await,
+            HealthProbe::Tcp { port } => connect_guest(connector, *port).await.map(drop),
+            HealthProbe::Http { port, path } => {
+                probe_http(connect_guest(connector, *port).await?, *port, path).await
+            }
+        }
+    };
+
+    tokio::time::timeout(timeout, probe_result)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {}s", timeout.as_secs())))
+}
+
+/// Run a command in the guest through the agent relay and check its exit code.
+async fn probe_exec(agent_sock: &Path, command: &[String]) -> Result<(), String> {
+    let (cmd, args) = command
+        .split_first()
+        .ok_or_else(|| "empty health check command".to_string())?;
+
+    let mut stream = UnixStream::connect(agent_sock)
+        .await
+        .map_err(|e| format!("connect to agent: {e}"))?;
+
+    // Handshake: [id_offset: u32 BE][core.ready frame].
+    let id_offset = stream
+        .read_u32()
+        .await
+        .map_err(|e| format!("agent handshake: {e}"))?;
+    codec::read_message(&mut stream)
+        .await
+        .map_err(|e| format!("agent handshake: {e}"))?;
+
+    let id = id_offset + 1;
+    let request = ExecRequest {
+        cmd: cmd.clone(),
+        args: args.to_vec(),
+        env: Vec::new(),
+        clear_env: false,
+        cwd: None,
+        user: None,
+        tty: false,
+        rows: 24,
+        cols: 80,
+        rlimits: Vec::new(),
+        traceparent: None,
+        persistent: None,
+        window: None,
+        compression: None,
+    };
+    send(&mut stream, MessageType::ExecRequest, id, &request).await?;
+
+    loop {
+        let msg = codec::read_message(&mut stream)
+            .await
+            .map_err(|e| format!("read from agent: {e}"))?;
+        if msg.id != id {
+            continue;
+        }
+
+        match msg.t {
+            // Close stdin so probes that read it don't hang.
+            MessageType::ExecStarted => {
+                send(
+                    &mut stream,
+                    MessageType::ExecStdin,
+                    id,
+                    &ExecStdin { data: Vec::new() },
+                )
+                .await?;
+            }
+            MessageType::ExecExited => {
+                let exited: ExecExited = msg
+                    .payload()
+                    .map_err(|e| format!("decode exit status: {e}"))?;
+                return match exited.code {
+                    0 => Ok(()),
+                    code => Err(format!("command exited with code {code}")),
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Send a GET request and check the response status line.
+async fn probe_http(stream: DuplexStream, port: u16, path: &str) -> Result<(), String> {
+    let mut stream = BufReader::new(stream);
+    let request =
+        format!("GET {path} HTTP/1.1\r\nHost: localhost:{port}\r\nConnection: close\r\n\r\n");
+    stream
+        .get_mut()
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| format!("send request: {e}"))?;
+
+    let mut status_line = String::new();
+    stream
+        .read_line(&mut status_line)
+        .await
+        .map_err(|e| format!("read response: {e}"))?;
+
+    match parse_status_code(&status_line) {
+        Some(200..=399) => Ok(()),
+        Some(code) => Err(format!("GET {path} returned {code}")),
+        None => Err(format!("GET {path} returned no HTTP status")),
+    }
+}
+
+#[cfg(feature = "net")]
+async fn connect_guest(
+    connector: Option<&GuestConnector>,
+    port: u16,
+) -> Result<DuplexStream, String> {
+    let connector = connector.ok_or_else(|| "networking is disabled".to_string())?;
+    connector
+        .connect(port)
+        .await
+        .map_err(|e| format!("connect to port {port}: {e}"))
+}
+
+#[cfg(not(feature = "net"))]
+async fn connect_guest(
+    _connector: Option<&GuestConnector>,
+    _port: u16,
+) -> Result<DuplexStream, String> {
+    Err("networking is disabled".into())
+}
+
+async fn send<T: serde::Serialize>(
+    stream: &mut UnixStream,
+    t: MessageType,
+    id: u32,
+    payload: &T,
+) -> Result<(), String> {
+    let msg = Message::with_payload(t, id, payload).map_err(|e| format!("encode message: {e}"))?;
+    codec::write_message(stream, &msg)
+        .await
+        .map_err(|e| format!("write to agent: {e}"))
+}
+
+/// Extract the status code from an HTTP/1.x status line.
+fn parse_status_code(line: &str) -> Option<u16> {
+    let mut parts = line.split_whitespace();
+    parts
+        .next()
+        .filter(|version| version.starts_with("HTTP/"))?;
+    parts.next()?.parse().ok()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(retries: u32, start_period_secs: u64) -> HealthCheck {
+        HealthCheck {
+            retries,
+            start_period_secs,
+            ..HealthCheck::new(HealthProbe::Tcp { port: 80 })
+        }
+    }
+
+    #[test]
+    fn test_unhealthy_after_retries_consecutive_failures() {
+        let now = Instant::now();
+        let mut monitor = HealthMonitor::new(&check(3, 0), now);
+
+        assert_eq!(monitor.record(false, now), None);
+        assert_eq!(monitor.record(false, now), None);
+        assert_eq!(monitor.record(false, now), Some(HealthStatus::Unhealthy));
+        assert_eq!(monitor.record(false, now), None);
+        assert_eq!(monitor.record(true, now), Some(HealthStatus::Healthy));
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let now = Instant::now();
+        let mut monitor = HealthMonitor::new(&check(2, 0), now);
+
+        assert_eq!(monitor.record(true, now), Some(HealthStatus::Healthy));
+        assert_eq!(monitor.record(false, now), None);
+        assert_eq!(monitor.record(true, now), None);
+        assert_eq!(monitor.record(false, now), None);
+        assert_eq!(monitor.status(), HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn test_start_period_ignores_failures_until_first_pass() {
+        let start = Instant::now();
+        let mut monitor = HealthMonitor::new(&check(1, 30), start);
+
+        assert_eq!(monitor.record(false, start), None);
+        assert_eq!(monitor.record(false, start + Duration::from_secs(29)), None);
+        assert_eq!(monitor.status(), HealthStatus::Starting);
+        assert_eq!(
+            monitor.record(false, start + Duration::from_secs(30)),
+            Some(HealthStatus::Unhealthy)
+        );
+    }
+
+    #[test]
+    fn test_start_period_ends_at_first_pass() {
+        let start = Instant::now();
+        let mut monitor = HealthMonitor::new(&check(1, 30), start);
+
+        assert_eq!(monitor.record(true, start), Some(HealthStatus::Healthy));
+        assert_eq!(
+            monitor.record(false, start + Duration::from_secs(1)),
+            Some(HealthStatus::Unhealthy)
+        );
+    }
+
+    #[test]
+    fn test_parse_status_code_reads_status_line() {
+        assert_eq!(parse_status_code("HTTP/1.1 204 No Content\r\n"), Some(204));
+        assert_eq!(parse_status_code("HTTP/1.0 503\r\n"), Some(503));
+        assert_eq!(parse_status_code("SSH-2.0-OpenSSH\r\n"), None);
+        assert_eq!(parse_status_code(""), None);
+    }
+}
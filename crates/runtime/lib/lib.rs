@@ -11,11 +11,16 @@ mod error;
 //--------------------------------------------------------------------------------------------------
 
 pub mod console;
+pub mod events;
+pub mod health;
 pub mod heartbeat;
+pub mod limits;
 pub mod logging;
 pub mod metrics;
 pub mod policy;
 pub mod relay;
+pub mod sockets;
+pub mod telemetry;
 pub mod vm;
 
 pub use error::*;
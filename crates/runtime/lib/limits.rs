@@ -0,0 +1,239 @@
+//! Resource limit evaluation against metrics samples.
+//!
+//! The metrics sampler publishes a [`ResourceReading`] per sample; a
+//! [`LimitMonitor`] turns those into [`LimitBreach`]es for the runtime to act on.
+
+use std::time::{Duration, Instant};
+
+use crate::policy::{LimitAction, LimitMetric, ResourceLimit};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// One metrics sample as seen by the limit monitor.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResourceReading {
+    /// CPU usage as a percentage of one host CPU.
+    pub cpu_percent: f32,
+
+    /// Resident memory in bytes.
+    pub memory_bytes: u64,
+
+    /// Cumulative disk bytes written.
+    pub disk_write_bytes: u64,
+
+    /// Cumulative network bytes transmitted by the guest.
+    pub net_egress_bytes: u64,
+}
+
+/// A limit that was hit by a reading.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LimitBreach {
+    /// The limit that was hit.
+    pub limit: ResourceLimit,
+
+    /// The reading value that crossed the threshold.
+    pub value: u64,
+}
+
+/// Tracks how long each limit has been exceeded.
+///
+/// A limit fires once when it has been over its threshold for its sustained
+/// window, then re-arms only after a reading drops back to or below the
+/// threshold.
+#[derive(Debug)]
+pub struct LimitMonitor {
+    limits: Vec<LimitState>,
+}
+
+#[derive(Debug)]
+struct LimitState {
+    limit: ResourceLimit,
+    over_since: Option<Instant>,
+    fired: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ResourceReading {
+    /// The reading value for a metric, in the unit of its thresholds.
+    pub fn value(&self, metric: LimitMetric) -> u64 {
+        match metric {
+            LimitMetric::CpuPercent => self.cpu_percent.max(0.0) as u64,
+            LimitMetric::MemoryBytes => self.memory_bytes,
+            LimitMetric::DiskWriteBytes => self.disk_write_bytes,
+            LimitMetric::NetEgressBytes => self.net_egress_bytes,
+        }
+    }
+}
+
+impl LimitBreach {
+    /// Human-readable description, used for logs and the run's termination detail.
+    pub fn detail(&self) -> String {
+        let ResourceLimit {
+            metric,
+            threshold,
+            sustained_secs,
+            ..
+        } = &self.limit;
+        let unit = match metric {
+            LimitMetric::CpuPercent => "%",
+            _ => " bytes",
+        };
+        let window = if *sustained_secs > 0 {
+            format!(" for {sustained_secs}s")
+        } else {
+            String::new()
+        };
+        format!(
+            "{metric} {}{unit} exceeded {threshold}{unit}{window}",
+            self.value
+        )
+    }
+}
+
+impl LimitMonitor {
+    /// Create a monitor for the given limits.
+    pub fn new(limits: impl IntoIterator<Item = ResourceLimit>) -> Self {
+        Self {
+            limits: limits
+                .into_iter()
+                .map(|limit| LimitState {
+                    limit,
+                    over_since: None,
+                    fired: false,
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether there are no limits to check.
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Check a reading taken at `now`, returning the limits that fire.
+    ///
+    /// Terminal breaches ([`LimitAction::Drain`], [`LimitAction::Kill`]) are
+    /// ordered before non-terminal ones.
+    pub fn check(&mut self, reading: &ResourceReading, now: Instant) -> Vec<LimitBreach> {
+        let mut breaches = Vec::new();
+
+        for state in &mut self.limits {
+            let value = reading.value(state.limit.metric);
+            if value <= state.limit.threshold {
+                state.over_since = None;
+                state.fired = false;
+                continue;
+            }
+
+            let since = *state.over_since.get_or_insert(now);
+            let window = Duration::from_secs(state.limit.sustained_secs);
+            if !state.fired && now.saturating_duration_since(since) >= window {
+                state.fired = true;
+                breaches.push(LimitBreach {
+                    limit: state.limit.clone(),
+                    value,
+                });
+            }
+        }
+
+        breaches.sort_by_key(|breach| match breach.limit.action {
+            LimitAction::Kill => 0,
+            LimitAction::Drain => 1,
+            _ => 2,
+        });
+        breaches
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu(percent: f32) -> ResourceReading {
+        ResourceReading {
+            cpu_percent: percent,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sustained_limit_waits_for_window() {
+        let mut monitor = LimitMonitor::new(["cpu=80/3s:drain".parse().unwrap()]);
+        let start = Instant::now();
+
+        assert!(monitor.check(&cpu(95.0), start).is_empty());
+        assert!(
+            monitor
+                .check(&cpu(95.0), start + Duration::from_secs(2))
+                .is_empty()
+        );
+
+        let breaches = monitor.check(&cpu(97.0), start + Duration::from_secs(3));
+        assert_eq!(breaches.len(), 1);
+        assert_eq!(breaches[0].value, 97);
+        assert_eq!(breaches[0].limit.action, LimitAction::Drain);
+        assert_eq!(breaches[0].detail(), "cpu 97% exceeded 80% for 3s");
+    }
+
+    #[test]
+    fn test_dip_below_threshold_resets_window() {
+        let mut monitor = LimitMonitor::new(["cpu=80/3s".parse().unwrap()]);
+        let start = Instant::now();
+
+        monitor.check(&cpu(95.0), start);
+        monitor.check(&cpu(10.0), start + Duration::from_secs(2));
+        assert!(
+            monitor
+                .check(&cpu(95.0), start + Duration::from_secs(3))
+                .is_empty()
+        );
+        assert_eq!(
+            monitor
+                .check(&cpu(95.0), start + Duration::from_secs(6))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_limit_fires_once_until_rearmed() {
+        let mut monitor = LimitMonitor::new(["memory=100:log".parse().unwrap()]);
+        let reading = |memory_bytes| ResourceReading {
+            memory_bytes,
+            ..Default::default()
+        };
+        let now = Instant::now();
+
+        assert_eq!(monitor.check(&reading(150), now).len(), 1);
+        assert!(monitor.check(&reading(200), now).is_empty());
+        assert!(monitor.check(&reading(100), now).is_empty());
+        assert_eq!(monitor.check(&reading(101), now).len(), 1);
+    }
+
+    #[test]
+    fn test_terminal_breaches_come_first() {
+        let mut monitor = LimitMonitor::new([
+            "disk-write=10:log".parse().unwrap(),
+            "net-egress=10:kill".parse().unwrap(),
+        ]);
+        let breaches = monitor.check(
+            &ResourceReading {
+                disk_write_bytes: 11,
+                net_egress_bytes: 11,
+                ..Default::default()
+            },
+            Instant::now(),
+        );
+        let actions: Vec<_> = breaches.iter().map(|b| b.limit.action).collect();
+        assert_eq!(actions, [LimitAction::Kill, LimitAction::Log]);
+    }
+}
@@ -2,10 +2,18 @@
 
 use std::time::{Duration, Instant};
 
-use microsandbox_db::entity::sandbox_metric as sandbox_metric_entity;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+use chrono::NaiveDateTime;
+use microsandbox_db::entity::{
+    sandbox_metric as sandbox_metric_entity, sandbox_metric_rollup as rollup_entity,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Set,
+};
 
-use crate::{RuntimeError, RuntimeResult};
+use tokio::sync::mpsc;
+
+use crate::{RuntimeError, RuntimeResult, limits::ResourceReading};
 
 //--------------------------------------------------------------------------------------------------
 // Constants
@@ -14,6 +22,18 @@ use crate::{RuntimeError, RuntimeResult};
 /// Fixed sampling interval for persisted sandbox metrics.
 pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
 
+/// Interval between metrics compaction passes while a sandbox is running.
+pub const COMPACTION_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Resolution of the fine-grained metrics rollup, in seconds.
+pub const MINUTE_ROLLUP_SECS: i32 = 60;
+
+/// Resolution of the coarse-grained metrics rollup, in seconds.
+pub const HOUR_ROLLUP_SECS: i32 = 3600;
+
+/// Number of rows fetched or inserted per batch during compaction.
+const COMPACTION_BATCH: u64 = 5000;
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -48,6 +68,60 @@ impl NetworkMetrics for microsandbox_network::network::MetricsHandle {
     }
 }
 
+/// How long persisted metrics are kept at each resolution.
+///
+/// Raw per-second samples are rolled up into 1-minute buckets, which are in
+/// turn rolled up into 1-hour buckets. Each tier is pruned once it is older
+/// than its retention.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MetricsRetention {
+    /// How long raw per-second samples are kept.
+    pub raw: Duration,
+
+    /// How long 1-minute rollups are kept.
+    pub minute: Duration,
+
+    /// How long 1-hour rollups are kept.
+    pub hour: Duration,
+}
+
+/// Running aggregate over raw samples or rollups that fall in one bucket.
+///
+/// Gauges keep min, max and a sample-weighted average. Cumulative counters keep
+/// the last value seen, so inputs must be added in chronological order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsRollup {
+    /// Number of raw samples aggregated into this bucket.
+    pub sample_count: i64,
+    cpu: Gauge<f32>,
+    memory: Gauge<i64>,
+    /// Last cumulative disk read byte count.
+    pub disk_read_bytes: Option<i64>,
+    /// Last cumulative disk write byte count.
+    pub disk_write_bytes: Option<i64>,
+    /// Last cumulative network receive byte count.
+    pub net_rx_bytes: Option<i64>,
+    /// Last cumulative network transmit byte count.
+    pub net_tx_bytes: Option<i64>,
+}
+
+/// Min/max/weighted-sum accumulator for a gauge.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Gauge<T> {
+    min: Option<T>,
+    max: Option<T>,
+    sum: f64,
+    weight: i64,
+}
+
+/// Accumulates consecutive buckets and inserts them in batches.
+struct RollupWriter {
+    sandbox_id: i32,
+    resolution: i32,
+    current: Option<(NaiveDateTime, MetricsRollup)>,
+    pending: Vec<rollup_entity::ActiveModel>,
+}
+
 /// Process metrics sampled from the host OS.
 #[derive(Clone, Copy, Debug)]
 struct ProcessSample {
@@ -57,19 +131,215 @@ struct ProcessSample {
     disk_write_bytes: u64,
 }
 
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl MetricsRollup {
+    /// Add a raw sample.
+    pub fn add_sample(&mut self, sample: &sandbox_metric_entity::Model) {
+        self.sample_count += 1;
+        if let Some(cpu) = sample.cpu_percent {
+            self.cpu.add(cpu, cpu, f64::from(cpu), 1);
+        }
+        if let Some(memory) = sample.memory_bytes {
+            self.memory.add(memory, memory, memory as f64, 1);
+        }
+        self.add_counters(
+            sample.disk_read_bytes,
+            sample.disk_write_bytes,
+            sample.net_rx_bytes,
+            sample.net_tx_bytes,
+        );
+    }
+
+    /// Add a rollup bucket, weighting its averages by its sample count.
+    pub fn add_rollup(&mut self, rollup: &rollup_entity::Model) {
+        let weight = rollup.sample_count.max(1);
+        self.sample_count += rollup.sample_count;
+        if let (Some(min), Some(avg), Some(max)) = (
+            rollup.cpu_percent_min,
+            rollup.cpu_percent_avg,
+            rollup.cpu_percent_max,
+        ) {
+            self.cpu
+                .add(min, max, f64::from(avg) * weight as f64, weight);
+        }
+        if let (Some(min), Some(avg), Some(max)) = (
+            rollup.memory_bytes_min,
+            rollup.memory_bytes_avg,
+            rollup.memory_bytes_max,
+        ) {
+            self.memory
+                .add(min, max, avg as f64 * weight as f64, weight);
+        }
+        self.add_counters(
+            rollup.disk_read_bytes,
+            rollup.disk_write_bytes,
+            rollup.net_rx_bytes,
+            rollup.net_tx_bytes,
+        );
+    }
+
+    /// CPU usage as `(min, avg, max)`, if any sample reported it.
+    pub fn cpu_percent(&self) -> Option<(f32, f32, f32)> {
+        let (min, max, avg) = (self.cpu.min?, self.cpu.max?, self.cpu.avg()?);
+        Some((min, avg as f32, max))
+    }
+
+    /// Resident memory as `(min, avg, max)`, if any sample reported it.
+    pub fn memory_bytes(&self) -> Option<(i64, i64, i64)> {
+        let (min, max, avg) = (self.memory.min?, self.memory.max?, self.memory.avg()?);
+        Some((min, avg.round() as i64, max))
+    }
+
+    /// Convert into a rollup row for insertion.
+    pub fn into_active_model(
+        self,
+        sandbox_id: i32,
+        resolution_secs: i32,
+        bucket_start: NaiveDateTime,
+    ) -> rollup_entity::ActiveModel {
+        let cpu = self.cpu_percent();
+        let memory = self.memory_bytes();
+        rollup_entity::ActiveModel {
+            sandbox_id: Set(sandbox_id),
+            resolution_secs: Set(resolution_secs),
+            bucket_start: Set(bucket_start),
+            sample_count: Set(self.sample_count),
+            cpu_percent_min: Set(cpu.map(|c| c.0)),
+            cpu_percent_avg: Set(cpu.map(|c| c.1)),
+            cpu_percent_max: Set(cpu.map(|c| c.2)),
+            memory_bytes_min: Set(memory.map(|m| m.0)),
+            memory_bytes_avg: Set(memory.map(|m| m.1)),
+            memory_bytes_max: Set(memory.map(|m| m.2)),
+            disk_read_bytes: Set(self.disk_read_bytes),
+            disk_write_bytes: Set(self.disk_write_bytes),
+            net_rx_bytes: Set(self.net_rx_bytes),
+            net_tx_bytes: Set(self.net_tx_bytes),
+            ..Default::default()
+        }
+    }
+
+    fn add_counters(
+        &mut self,
+        disk_read_bytes: Option<i64>,
+        disk_write_bytes: Option<i64>,
+        net_rx_bytes: Option<i64>,
+        net_tx_bytes: Option<i64>,
+    ) {
+        self.disk_read_bytes = disk_read_bytes.or(self.disk_read_bytes);
+        self.disk_write_bytes = disk_write_bytes.or(self.disk_write_bytes);
+        self.net_rx_bytes = net_rx_bytes.or(self.net_rx_bytes);
+        self.net_tx_bytes = net_tx_bytes.or(self.net_tx_bytes);
+    }
+}
+
+impl<T: PartialOrd + Copy> Gauge<T> {
+    fn add(&mut self, min: T, max: T, weighted_sum: f64, weight: i64) {
+        if self.min.is_none_or(|current| min < current) {
+            self.min = Some(min);
+        }
+        if self.max.is_none_or(|current| max > current) {
+            self.max = Some(max);
+        }
+        self.sum += weighted_sum;
+        self.weight += weight;
+    }
+
+    fn avg(&self) -> Option<f64> {
+        (self.weight > 0).then(|| self.sum / self.weight as f64)
+    }
+}
+
+impl RollupWriter {
+    fn new(sandbox_id: i32, resolution: i32) -> Self {
+        Self {
+            sandbox_id,
+            resolution,
+            current: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// The accumulator for the bucket containing `ts`, flushing the previous one.
+    async fn bucket(
+        &mut self,
+        db: &DatabaseConnection,
+        ts: NaiveDateTime,
+    ) -> RuntimeResult<&mut MetricsRollup> {
+        let start = bucket_start(ts, i64::from(self.resolution));
+        if self.current.as_ref().is_some_and(|(s, _)| *s != start) {
+            self.flush_current();
+            if self.pending.len() as u64 >= COMPACTION_BATCH / 10 {
+                self.insert_pending(db).await?;
+            }
+        }
+        Ok(&mut self
+            .current
+            .get_or_insert_with(|| (start, MetricsRollup::default()))
+            .1)
+    }
+
+    async fn finish(mut self, db: &DatabaseConnection) -> RuntimeResult<()> {
+        self.flush_current();
+        self.insert_pending(db).await
+    }
+
+    fn flush_current(&mut self) {
+        if let Some((start, rollup)) = self.current.take() {
+            self.pending
+                .push(rollup.into_active_model(self.sandbox_id, self.resolution, start));
+        }
+    }
+
+    async fn insert_pending(&mut self, db: &DatabaseConnection) -> RuntimeResult<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        rollup_entity::Entity::insert_many(std::mem::take(&mut self.pending))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for MetricsRetention {
+    fn default() -> Self {
+        Self {
+            raw: Duration::from_secs(6 * 3600),
+            minute: Duration::from_secs(7 * 24 * 3600),
+            hour: Duration::from_secs(90 * 24 * 3600),
+        }
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
 
 /// Run the background metrics sampler until the sandbox process exits.
+///
+/// Persisted metrics for the sandbox are compacted according to `retention`
+/// on start, every [`COMPACTION_INTERVAL`], and once more on exit. When
+/// `readings` is set, each sample is also published there for limit checks.
 pub async fn run_metrics_sampler(
     db: DatabaseConnection,
     sandbox_id: i32,
     pid: u32,
     network_metrics: Option<Box<dyn NetworkMetrics>>,
+    retention: MetricsRetention,
+    readings: Option<mpsc::UnboundedSender<ResourceReading>>,
 ) {
     let pid = pid as i32;
 
+    run_compaction(&db, sandbox_id, retention).await;
+    let mut last_compaction = Instant::now();
+
     let mut previous = match sample_process(pid) {
         Ok(sample) => sample,
         Err(err) => {
@@ -119,9 +389,163 @@ pub async fn run_metrics_sampler(
             tracing::warn!(sandbox_id, pid, error = %err, "failed to persist sandbox metrics");
         }
 
+        if let Some(readings) = &readings {
+            let _ = readings.send(ResourceReading {
+                cpu_percent: cpu_percent as f32,
+                memory_bytes: current.memory_bytes,
+                disk_write_bytes: current.disk_write_bytes,
+                net_egress_bytes: network_metrics.as_ref().map_or(0, |m| m.tx_bytes()),
+            });
+        }
+
         previous = current;
         previous_instant = now;
+
+        if now.duration_since(last_compaction) >= COMPACTION_INTERVAL {
+            run_compaction(&db, sandbox_id, retention).await;
+            last_compaction = now;
+        }
+    }
+
+    run_compaction(&db, sandbox_id, retention).await;
+}
+
+/// Roll up and prune persisted metrics for a sandbox.
+///
+/// Closed minutes of raw samples become 1-minute rollups and closed hours of
+/// 1-minute rollups become 1-hour rollups. Each tier is then pruned to its
+/// retention. Buckets are only rolled up once, so the pass is cheap to repeat.
+pub async fn compact_metrics(
+    db: &DatabaseConnection,
+    sandbox_id: i32,
+    retention: MetricsRetention,
+    now: NaiveDateTime,
+) -> RuntimeResult<()> {
+    rollup_samples(db, sandbox_id, now).await?;
+    rollup_minutes(db, sandbox_id, now).await?;
+
+    // Cutoffs are aligned to the next coarser resolution so each tier starts
+    // on a bucket boundary of the tier behind it, letting readers switch tiers
+    // without dropping a partial bucket.
+    sandbox_metric_entity::Entity::delete_many()
+        .filter(sandbox_metric_entity::Column::SandboxId.eq(sandbox_id))
+        .filter(sandbox_metric_entity::Column::SampledAt.lt(cutoff(
+            now,
+            retention.raw,
+            MINUTE_ROLLUP_SECS,
+        )))
+        .exec(db)
+        .await?;
+    for (resolution, keep) in [
+        (MINUTE_ROLLUP_SECS, retention.minute),
+        (HOUR_ROLLUP_SECS, retention.hour),
+    ] {
+        rollup_entity::Entity::delete_many()
+            .filter(rollup_entity::Column::SandboxId.eq(sandbox_id))
+            .filter(rollup_entity::Column::ResolutionSecs.eq(resolution))
+            .filter(rollup_entity::Column::BucketStart.lt(cutoff(now, keep, HOUR_ROLLUP_SECS)))
+            .exec(db)
+            .await?;
     }
+
+    Ok(())
+}
+
+/// Align a timestamp to the start of its `secs`-wide bucket.
+pub fn bucket_start(ts: NaiveDateTime, secs: i64) -> NaiveDateTime {
+    let secs = secs.max(1);
+    let timestamp = ts.and_utc().timestamp();
+    chrono::DateTime::from_timestamp(timestamp - timestamp.rem_euclid(secs), 0)
+        .map(|dt| dt.naive_utc())
+        .unwrap_or(ts)
+}
+
+async fn run_compaction(db: &DatabaseConnection, sandbox_id: i32, retention: MetricsRetention) {
+    let now = chrono::Utc::now().naive_utc();
+    if let Err(err) = compact_metrics(db, sandbox_id, retention, now).await {
+        tracing::warn!(sandbox_id, error = %err, "failed to compact sandbox metrics");
+    }
+}
+
+/// Roll closed minutes of raw samples into 1-minute buckets.
+async fn rollup_samples(
+    db: &DatabaseConnection,
+    sandbox_id: i32,
+    now: NaiveDateTime,
+) -> RuntimeResult<()> {
+    let end = bucket_start(now, i64::from(MINUTE_ROLLUP_SECS));
+    let mut query = sandbox_metric_entity::Entity::find()
+        .filter(sandbox_metric_entity::Column::SandboxId.eq(sandbox_id))
+        .filter(sandbox_metric_entity::Column::SampledAt.lt(end));
+    if let Some(start) = next_bucket(db, sandbox_id, MINUTE_ROLLUP_SECS).await? {
+        query = query.filter(sandbox_metric_entity::Column::SampledAt.gte(start));
+    }
+
+    let mut pages = query
+        .order_by_asc(sandbox_metric_entity::Column::SampledAt)
+        .order_by_asc(sandbox_metric_entity::Column::Id)
+        .paginate(db, COMPACTION_BATCH);
+    let mut writer = RollupWriter::new(sandbox_id, MINUTE_ROLLUP_SECS);
+    while let Some(rows) = pages.fetch_and_next().await? {
+        for row in &rows {
+            if let Some(sampled_at) = row.sampled_at {
+                writer.bucket(db, sampled_at).await?.add_sample(row);
+            }
+        }
+    }
+    writer.finish(db).await
+}
+
+/// Roll closed hours of 1-minute buckets into 1-hour buckets.
+async fn rollup_minutes(
+    db: &DatabaseConnection,
+    sandbox_id: i32,
+    now: NaiveDateTime,
+) -> RuntimeResult<()> {
+    let end = bucket_start(now, i64::from(HOUR_ROLLUP_SECS));
+    let mut query = rollup_entity::Entity::find()
+        .filter(rollup_entity::Column::SandboxId.eq(sandbox_id))
+        .filter(rollup_entity::Column::ResolutionSecs.eq(MINUTE_ROLLUP_SECS))
+        .filter(rollup_entity::Column::BucketStart.lt(end));
+    if let Some(start) = next_bucket(db, sandbox_id, HOUR_ROLLUP_SECS).await? {
+        query = query.filter(rollup_entity::Column::BucketStart.gte(start));
+    }
+
+    let mut pages = query
+        .order_by_asc(rollup_entity::Column::BucketStart)
+        .paginate(db, COMPACTION_BATCH);
+    let mut writer = RollupWriter::new(sandbox_id, HOUR_ROLLUP_SECS);
+    while let Some(rows) = pages.fetch_and_next().await? {
+        for row in &rows {
+            writer.bucket(db, row.bucket_start).await?.add_rollup(row);
+        }
+    }
+    writer.finish(db).await
+}
+
+/// Start of the first bucket at `resolution` that has not been rolled up yet.
+async fn next_bucket(
+    db: &DatabaseConnection,
+    sandbox_id: i32,
+    resolution: i32,
+) -> RuntimeResult<Option<NaiveDateTime>> {
+    let latest = rollup_entity::Entity::find()
+        .filter(rollup_entity::Column::SandboxId.eq(sandbox_id))
+        .filter(rollup_entity::Column::ResolutionSecs.eq(resolution))
+        .order_by_desc(rollup_entity::Column::BucketStart)
+        .one(db)
+        .await?;
+    Ok(latest.map(|row| row.bucket_start + chrono::Duration::seconds(i64::from(resolution))))
+}
+
+/// The instant `keep` before `now`, aligned down to an `align_secs` boundary.
+fn cutoff(now: NaiveDateTime, keep: Duration, align_secs: i32) -> NaiveDateTime {
+    chrono::Duration::from_std(keep)
+        .ok()
+        .and_then(|keep| now.checked_sub_signed(keep))
+        .map_or(NaiveDateTime::MIN, |ts| {
+            bucket_start(ts, i64::from(align_secs))
+        })
 }
 
 async fn persist_sample(
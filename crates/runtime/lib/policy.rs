@@ -1,7 +1,11 @@
 //! Sandbox lifecycle policies.
 
+use std::{fmt, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
+use crate::RuntimeError;
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -14,6 +18,332 @@ pub struct SandboxPolicy {
 
     /// Idle timeout in seconds. `None` = no idle detection.
     pub idle_timeout_secs: Option<u64>,
+
+    /// Resource thresholds checked against each metrics sample.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resource_limits: Vec<ResourceLimit>,
+
+    /// Probe run periodically to decide whether the sandbox is healthy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheck>,
+}
+
+/// A threshold on one sampled resource and the action to take when it is crossed.
+///
+/// Written as `METRIC=THRESHOLD[/WINDOW][:ACTION]`, for example
+/// `cpu=90/30s:drain`, `memory=2GiB:kill` or `net-egress=1GiB:signal=15`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimit {
+    /// The sampled resource.
+    pub metric: LimitMetric,
+
+    /// Threshold the sample must exceed: a CPU percentage for
+    /// [`LimitMetric::CpuPercent`], bytes otherwise.
+    pub threshold: u64,
+
+    /// How long the threshold must be continuously exceeded before the action
+    /// fires. `0` fires on the first sample over the threshold.
+    #[serde(default)]
+    pub sustained_secs: u64,
+
+    /// What to do when the limit is hit.
+    #[serde(default)]
+    pub action: LimitAction,
+}
+
+/// A resource that can be limited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitMetric {
+    /// CPU usage of the sandbox process, as a percentage of one host CPU.
+    CpuPercent,
+
+    /// Resident memory of the sandbox process in bytes.
+    MemoryBytes,
+
+    /// Cumulative bytes written to disk by the sandbox process.
+    DiskWriteBytes,
+
+    /// Cumulative bytes transmitted by the guest onto the network.
+    NetEgressBytes,
+}
+
+/// Action taken when a [`ResourceLimit`] is hit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitAction {
+    /// Log a warning and keep running.
+    #[default]
+    Log,
+
+    /// Send the signal to every active exec session in the guest.
+    Signal(i32),
+
+    /// Ask the guest to shut down, stopping the VM if it does not exit in time.
+    Drain,
+
+    /// Stop the VM immediately.
+    Kill,
+}
+
+/// A health check run against the guest while the sandbox is running.
+///
+/// The sandbox starts out `starting`, becomes `healthy` on the first passing
+/// probe and `unhealthy` once `retries` probes in a row have failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthCheck {
+    /// What to probe.
+    pub probe: HealthProbe,
+
+    /// Seconds between probes.
+    #[serde(default = "default_health_interval")]
+    pub interval_secs: u64,
+
+    /// Seconds a single probe may take before it counts as failed.
+    #[serde(default = "default_health_timeout")]
+    pub timeout_secs: u64,
+
+    /// Consecutive failures before the sandbox is marked unhealthy.
+    #[serde(default = "default_health_retries")]
+    pub retries: u32,
+
+    /// Seconds after boot during which failures are not counted.
+    #[serde(default)]
+    pub start_period_secs: u64,
+
+    /// What to do when the sandbox becomes unhealthy.
+    #[serde(default)]
+    pub on_unhealthy: LimitAction,
+}
+
+/// How a [`HealthCheck`] probes the guest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HealthProbe {
+    /// Run a command in the guest; exit code 0 passes.
+    Exec {
+        /// Program and arguments.
+        command: Vec<String>,
+    },
+
+    /// Open a TCP connection to a guest port.
+    Tcp {
+        /// Guest port.
+        port: u16,
+    },
+
+    /// Send an HTTP GET to a guest port; a 2xx or 3xx status passes.
+    Http {
+        /// Guest port.
+        port: u16,
+
+        /// Request path.
+        #[serde(default = "default_health_path")]
+        path: String,
+    },
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl HealthCheck {
+    /// Create a health check with the default interval, timeout and retries.
+    pub fn new(probe: HealthProbe) -> Self {
+        Self {
+            probe,
+            interval_secs: default_health_interval(),
+            timeout_secs: default_health_timeout(),
+            retries: default_health_retries(),
+            start_period_secs: 0,
+            on_unhealthy: LimitAction::default(),
+        }
+    }
+}
+
+impl LimitMetric {
+    /// The name used in limit specs and termination details.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CpuPercent => "cpu",
+            Self::MemoryBytes => "memory",
+            Self::DiskWriteBytes => "disk-write",
+            Self::NetEgressBytes => "net-egress",
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for ResourceLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.metric, self.threshold)?;
+        if self.sustained_secs > 0 {
+            write!(f, "/{}s", self.sustained_secs)?;
+        }
+        write!(f, ":{}", self.action)
+    }
+}
+
+impl FromStr for ResourceLimit {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| RuntimeError::Custom(format!("invalid limit '{s}': {reason}"));
+
+        let (metric, rest) = s
+            .split_once('=')
+            .ok_or_else(|| invalid("expected METRIC=THRESHOLD"))?;
+        let metric: LimitMetric = metric.trim().parse()?;
+
+        let (rest, action) = match rest.split_once(':') {
+            Some((rest, action)) => (rest, action.trim().parse()?),
+            None => (rest, LimitAction::default()),
+        };
+        let (threshold, sustained_secs) = match rest.split_once('/') {
+            Some((threshold, window)) => (
+                threshold,
+                parse_secs(window.trim()).ok_or_else(|| invalid("invalid window"))?,
+            ),
+            None => (rest, 0),
+        };
+
+        let threshold = threshold.trim();
+        let threshold = match metric {
+            LimitMetric::CpuPercent => threshold.trim_end_matches('%').parse().ok(),
+            _ => parse_bytes(threshold),
+        }
+        .ok_or_else(|| invalid("invalid threshold"))?;
+
+        Ok(Self {
+            metric,
+            threshold,
+            sustained_secs,
+            action,
+        })
+    }
+}
+
+impl fmt::Display for HealthProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exec { command } => write!(f, "exec {}", command.join(" ")),
+            Self::Tcp { port } => write!(f, "tcp {port}"),
+            Self::Http { port, path } => write!(f, "http {port}{path}"),
+        }
+    }
+}
+
+impl fmt::Display for LimitMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LimitMetric {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cpu" => Ok(Self::CpuPercent),
+            "memory" | "mem" => Ok(Self::MemoryBytes),
+            "disk-write" => Ok(Self::DiskWriteBytes),
+            "net-egress" => Ok(Self::NetEgressBytes),
+            _ => Err(RuntimeError::Custom(format!(
+                "unknown limit metric '{s}' (expected cpu, memory, disk-write or net-egress)"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for LimitAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Log => f.write_str("log"),
+            Self::Signal(signal) => write!(f, "signal={signal}"),
+            Self::Drain => f.write_str("drain"),
+            Self::Kill => f.write_str("kill"),
+        }
+    }
+}
+
+impl FromStr for LimitAction {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "log" => Ok(Self::Log),
+            "drain" => Ok(Self::Drain),
+            "kill" => Ok(Self::Kill),
+            _ => s
+                .strip_prefix("signal=")
+                .and_then(|signal| signal.parse::<i32>().ok())
+                .filter(|signal| *signal > 0)
+                .map(Self::Signal)
+                .ok_or_else(|| {
+                    RuntimeError::Custom(format!(
+                        "unknown limit action '{s}' (expected log, signal=N, drain or kill)"
+                    ))
+                }),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+fn default_health_interval() -> u64 {
+    10
+}
+
+fn default_health_timeout() -> u64 {
+    5
+}
+
+fn default_health_retries() -> u32 {
+    3
+}
+
+fn default_health_path() -> String {
+    "/".into()
+}
+
+/// Parse a byte count with an optional binary suffix (`K`, `M`, `G`, `T`,
+/// optionally followed by `i`/`iB`/`B`).
+fn parse_bytes(s: &str) -> Option<u64> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, suffix) = s.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let suffix = suffix.trim();
+    let unit = suffix
+        .strip_suffix("iB")
+        .or_else(|| suffix.strip_suffix('B'))
+        .or_else(|| suffix.strip_suffix('i'))
+        .unwrap_or(suffix);
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+    number.checked_mul(multiplier)
+}
+
+/// Parse a duration in seconds with an optional `s`, `m` or `h` suffix.
+fn parse_secs(s: &str) -> Option<u64> {
+    let (number, multiplier) = if let Some(n) = s.strip_suffix('h') {
+        (n, 3600)
+    } else if let Some(n) = s.strip_suffix('m') {
+        (n, 60)
+    } else {
+        (s.strip_suffix('s').unwrap_or(s), 1)
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -29,6 +359,8 @@ mod tests {
         let policy = SandboxPolicy {
             max_duration_secs: Some(3600),
             idle_timeout_secs: Some(120),
+            resource_limits: vec!["cpu=90/30s:drain".parse().unwrap()],
+            health_check: Some(HealthCheck::new(HealthProbe::Tcp { port: 8080 })),
         };
 
         let json = serde_json::to_string(&policy).unwrap();
@@ -36,6 +368,8 @@ mod tests {
 
         assert_eq!(decoded.max_duration_secs, Some(3600));
         assert_eq!(decoded.idle_timeout_secs, Some(120));
+        assert_eq!(decoded.resource_limits, policy.resource_limits);
+        assert_eq!(decoded.health_check, policy.health_check);
     }
 
     #[test]
@@ -43,5 +377,96 @@ mod tests {
         let policy = SandboxPolicy::default();
         assert!(policy.max_duration_secs.is_none());
         assert!(policy.idle_timeout_secs.is_none());
+        assert!(policy.resource_limits.is_empty());
+    }
+
+    #[test]
+    fn policy_without_limits_deserializes() {
+        let decoded: SandboxPolicy =
+            serde_json::from_str(r#"{"max_duration_secs":null,"idle_timeout_secs":60}"#).unwrap();
+        assert!(decoded.resource_limits.is_empty());
+        assert!(decoded.health_check.is_none());
+    }
+
+    #[test]
+    fn health_check_fills_defaults() {
+        let check: HealthCheck =
+            serde_json::from_str(r#"{"probe":{"type":"http","port":8080}}"#).unwrap();
+        assert_eq!(
+            check,
+            HealthCheck::new(HealthProbe::Http {
+                port: 8080,
+                path: "/".into(),
+            })
+        );
+        assert_eq!(check.interval_secs, 10);
+        assert_eq!(check.timeout_secs, 5);
+        assert_eq!(check.retries, 3);
+        assert_eq!(check.on_unhealthy, LimitAction::Log);
+    }
+
+    #[test]
+    fn parse_resource_limits() {
+        assert_eq!(
+            "cpu=90%/30s:drain".parse::<ResourceLimit>().unwrap(),
+            ResourceLimit {
+                metric: LimitMetric::CpuPercent,
+                threshold: 90,
+                sustained_secs: 30,
+                action: LimitAction::Drain,
+            }
+        );
+        assert_eq!(
+            "memory=2GiB:kill".parse::<ResourceLimit>().unwrap(),
+            ResourceLimit {
+                metric: LimitMetric::MemoryBytes,
+                threshold: 2 << 30,
+                sustained_secs: 0,
+                action: LimitAction::Kill,
+            }
+        );
+        assert_eq!(
+            "net-egress=512M/1m:signal=15"
+                .parse::<ResourceLimit>()
+                .unwrap(),
+            ResourceLimit {
+                metric: LimitMetric::NetEgressBytes,
+                threshold: 512 << 20,
+                sustained_secs: 60,
+                action: LimitAction::Signal(15),
+            }
+        );
+        assert_eq!(
+            "disk-write=1000".parse::<ResourceLimit>().unwrap().action,
+            LimitAction::Log
+        );
+    }
+
+    #[test]
+    fn resource_limit_display_roundtrips() {
+        for spec in [
+            "cpu=90/30s:drain",
+            "memory=1024:kill",
+            "net-egress=5:signal=10",
+        ] {
+            let limit: ResourceLimit = spec.parse().unwrap();
+            assert_eq!(limit.to_string(), spec);
+            assert_eq!(limit.to_string().parse::<ResourceLimit>().unwrap(), limit);
+        }
+    }
+
+    #[test]
+    fn parse_resource_limit_rejects_malformed() {
+        for spec in [
+            "cpu",
+            "gpu=10",
+            "cpu=lots",
+            "memory=2XB",
+            "memory=1G/forever",
+            "memory=1G:explode",
+            "memory=1G:signal=0",
+        ] {
+            assert!(spec.parse::<ResourceLimit>().is_err(), "{spec}");
+        }
     }
 }
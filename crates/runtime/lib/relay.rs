@@ -8,6 +8,17 @@
 //! Each client is assigned a non-overlapping correlation ID range during
 //! handshake so that the relay can route agent responses back to the correct
 //! client without rewriting frame headers.
+//!
+//! When a client disconnects, its exec sessions are killed, except persistent
+//! ones (started with `FLAG_PERSISTENT`), which are detached so another client
+//! can resume them later. Every session of the departed client is also sent
+//! `core.exec.detach`, which releases its flow-control window so a producer
+//! waiting for credit from the gone client can drain and finish.
+//!
+//! Flow-control credit (`core.credit`) needs no special handling: it is routed
+//! by correlation ID like any other frame. Because a flow-controlled session
+//! never has more than its window in flight, a slow client cannot make the
+//! relay buffer without bound.
 
 use std::collections::{HashMap, HashSet};
 use std::os::fd::RawFd;
@@ -16,9 +27,11 @@ use std::sync::Arc;
 
 use bytes::{Bytes, BytesMut};
 use microsandbox_protocol::codec::{self, MAX_FRAME_SIZE};
+use microsandbox_protocol::core::Ready;
 use microsandbox_protocol::exec::ExecSignal;
 use microsandbox_protocol::message::{
-    FLAG_SESSION_START, FLAG_SHUTDOWN, FLAG_TERMINAL, FRAME_HEADER_SIZE, Message, MessageType,
+    FLAG_PERSISTENT, FLAG_SESSION_START, FLAG_SHUTDOWN, FLAG_TERMINAL, FRAME_HEADER_SIZE, Message,
+    MessageType,
 };
 use tokio::io::{AsyncReadExt, AsyncWriteExt, unix::AsyncFd};
 use tokio::net::UnixListener;
@@ -26,6 +39,7 @@ use tokio::net::unix::OwnedReadHalf;
 use tokio::sync::{Mutex, mpsc, watch};
 
 use crate::console::ConsoleSharedState;
+use crate::sockets::{self, SocketMount};
 use crate::{RuntimeError, RuntimeResult};
 
 //--------------------------------------------------------------------------------------------------
@@ -49,6 +63,9 @@ const LEN_PREFIX_SIZE: usize = 4;
 struct ClientState {
     /// Active session IDs owned by this client (tracked for disconnect cleanup).
     active_sessions: HashSet<u32>,
+    /// The subset of `active_sessions` that are persistent and are detached
+    /// rather than killed on disconnect.
+    persistent_sessions: HashSet<u32>,
     /// Channel for sending frames to this client's writer task.
     /// Using a channel avoids holding the client mutex across async writes.
     /// Uses `Bytes` for zero-copy frame forwarding from the ring buffer.
@@ -72,6 +89,20 @@ pub struct AgentRelay {
     sock_path: PathBuf,
     /// Cached `core.ready` frame bytes (length-prefixed wire format).
     ready_frame: Option<Vec<u8>>,
+    /// Decoded `core.ready` payload, for the agent's capabilities.
+    ready: Option<Ready>,
+    /// Unix sockets shared with the guest, served by the relay itself.
+    socket_mounts: Vec<SocketMount>,
+}
+
+/// A message the runtime itself injects into the guest through the relay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayCommand {
+    /// Send the signal to every exec session opened by a connected client.
+    SignalSessions(i32),
+
+    /// Send `core.shutdown` to agentd.
+    Shutdown,
 }
 
 /// A frame extracted from the byte stream, kept as raw bytes for transparent
@@ -117,9 +148,19 @@ impl AgentRelay {
             listener,
             sock_path: agent_sock_path.to_path_buf(),
             ready_frame: None,
+            ready: None,
+            socket_mounts: Vec::new(),
         })
     }
 
+    /// Serve these Unix socket mounts once [`run()`](Self::run) starts.
+    ///
+    /// The mounts take the last client slot, leaving one fewer for
+    /// connecting clients.
+    pub fn set_socket_mounts(&mut self, mounts: Vec<SocketMount>) {
+        self.socket_mounts = mounts;
+    }
+
     /// Read frames from the console ring buffer until `core.ready` is
     /// received.
     ///
@@ -147,6 +188,7 @@ impl AgentRelay {
 
                 if msg.t == MessageType::Ready {
                     tracing::info!("agent relay: received core.ready from agentd");
+                    self.ready = msg.payload().ok();
                     self.ready_frame = Some(raw_data);
                     return Ok(());
                 }
@@ -180,11 +222,13 @@ impl AgentRelay {
     ///
     /// If a client sends a `core.shutdown` message (identified by
     /// `FLAG_SHUTDOWN` in the frame header), the relay notifies the caller
-    /// via `drain_tx`.
+    /// via `drain_tx`. Messages received on `commands` are injected into the
+    /// guest on behalf of the runtime.
     pub async fn run(
         self,
         mut shutdown: watch::Receiver<bool>,
         drain_tx: mpsc::Sender<()>,
+        mut commands: mpsc::Receiver<RelayCommand>,
     ) -> RuntimeResult<()> {
         let ready_frame = self.ready_frame.ok_or_else(|| {
             RuntimeError::Custom("agent relay: run() called before wait_ready()".into())
@@ -210,6 +254,44 @@ impl AgentRelay {
         let ring_reader_handle =
             tokio::spawn(ring_reader_task(shared_for_reader, clients_for_reader));
 
+        // Serve socket mounts as an internal client in the last slot, before
+        // accepting clients so the sockets exist by the time anything runs.
+        // Only send agentd message types it advertised; older agents drop
+        // the connection on types they don't know.
+        let supports = |t: MessageType| self.ready.as_ref().is_some_and(|r| r.supports(&t));
+        let tunnels_supported = supports(MessageType::TunnelListen);
+        let detach_supported = supports(MessageType::ExecDetach);
+        let _socket_mount_tasks = if self.socket_mounts.is_empty() {
+            None
+        } else if !tunnels_supported {
+            tracing::error!(
+                "agent relay: guest agent does not support tunnels, skipping socket mounts"
+            );
+            None
+        } else {
+            let slot = MAX_CLIENTS - 1;
+            used_slots.lock().await.insert(slot);
+            let (write_tx, write_rx) = mpsc::channel::<Bytes>(CLIENT_WRITE_CHANNEL_CAPACITY);
+            clients.lock().await.insert(
+                slot,
+                ClientState {
+                    active_sessions: HashSet::new(),
+                    persistent_sessions: HashSet::new(),
+                    write_tx,
+                },
+            );
+            Some(
+                sockets::start(
+                    self.socket_mounts,
+                    slot * ID_RANGE_STEP,
+                    ID_RANGE_STEP,
+                    write_rx,
+                    agent_tx.clone(),
+                )
+                .await,
+            )
+        };
+
         // Accept loop.
         loop {
             tokio::select! {
@@ -279,6 +361,7 @@ impl AgentRelay {
                                 let mut map = clients.lock().await;
                                 map.insert(slot, ClientState {
                                     active_sessions: HashSet::new(),
+                                    persistent_sessions: HashSet::new(),
                                     write_tx,
                                 });
                             }
@@ -296,6 +379,7 @@ impl AgentRelay {
                                 clients_clone,
                                 used_slots_clone,
                                 drain_tx_clone,
+                                detach_supported,
                             ));
                         }
                         Err(e) => {
@@ -303,6 +387,9 @@ impl AgentRelay {
                         }
                     }
                 }
+                Some(command) = commands.recv() => {
+                    inject_command(command, &clients, &agent_tx).await;
+                }
                 _ = shutdown.changed() => {
                     if *shutdown.borrow() {
                         tracing::info!("agent relay: shutdown signal received");
@@ -483,6 +570,7 @@ async fn ring_reader_task(
                 if let Some(client) = map.get_mut(&client_slot) {
                     if is_terminal {
                         client.active_sessions.remove(&frame.id);
+                        client.persistent_sessions.remove(&frame.id);
                     }
                     Ok(client.write_tx.clone())
                 } else {
@@ -556,6 +644,51 @@ async fn read_raw_frame<R: AsyncReadExt + Unpin>(reader: &mut R) -> RuntimeResul
     })
 }
 
+/// Encode a runtime-originated command and queue it for the guest.
+async fn inject_command(
+    command: RelayCommand,
+    clients: &Mutex<HashMap<u32, ClientState>>,
+    agent_tx: &mpsc::Sender<Vec<u8>>,
+) {
+    let messages = match command {
+        RelayCommand::Shutdown => vec![Message::new(MessageType::Shutdown, 0, Vec::new())],
+        RelayCommand::SignalSessions(signal) => {
+            let session_ids: Vec<u32> = clients
+                .lock()
+                .await
+                .values()
+                .flat_map(|client| client.active_sessions.iter().copied())
+                .collect();
+            let mut messages = Vec::with_capacity(session_ids.len());
+            for session_id in session_ids {
+                match Message::with_payload(
+                    MessageType::ExecSignal,
+                    session_id,
+                    &ExecSignal { signal },
+                ) {
+                    Ok(msg) => messages.push(msg),
+                    Err(e) => tracing::error!(
+                        "agent relay: failed to encode signal {signal} for session {session_id}: {e}"
+                    ),
+                }
+            }
+            messages
+        }
+    };
+
+    for msg in messages {
+        let mut buf = Vec::new();
+        if let Err(e) = codec::encode_to_buf(&msg, &mut buf) {
+            tracing::error!("agent relay: failed to encode {command:?} frame: {e}");
+            continue;
+        }
+        if agent_tx.send(buf).await.is_err() {
+            tracing::error!("agent relay: ring writer channel closed");
+            break;
+        }
+    }
+}
+
 /// Background task that reads frames from a client and forwards them to the
 /// ring writer channel. Handles client disconnect with session cleanup.
 async fn client_reader_task(
@@ -565,6 +698,7 @@ async fn client_reader_task(
     clients: Arc<Mutex<HashMap<u32, ClientState>>>,
     used_slots: Arc<Mutex<HashSet<u32>>>,
     drain_tx: mpsc::Sender<()>,
+    send_detach: bool,
 ) {
     loop {
         let frame = match read_raw_frame(&mut reader).await {
@@ -577,6 +711,7 @@ async fn client_reader_task(
 
         // Track session starts for disconnect cleanup.
         let is_session_start = (frame.flags & FLAG_SESSION_START) != 0;
+        let is_persistent = (frame.flags & FLAG_PERSISTENT) != 0;
         let is_terminal = (frame.flags & FLAG_TERMINAL) != 0;
         let is_shutdown = (frame.flags & FLAG_SHUTDOWN) != 0;
 
@@ -593,9 +728,13 @@ async fn client_reader_task(
             if let Some(client) = map.get_mut(&slot) {
                 if is_session_start {
                     client.active_sessions.insert(frame.id);
+                    if is_persistent {
+                        client.persistent_sessions.insert(frame.id);
+                    }
                 }
                 if is_terminal {
                     client.active_sessions.remove(&frame.id);
+                    client.persistent_sessions.remove(&frame.id);
                 }
             }
         }
@@ -607,48 +746,59 @@ async fn client_reader_task(
         }
     }
 
-    // Client disconnected — send SIGKILL for each active session.
-    let active_sessions = {
+    // Client disconnected — send SIGKILL to non-persistent sessions, then
+    // detach every session.
+    let (active_sessions, persistent_sessions) = {
         let mut map = clients.lock().await;
         if let Some(client) = map.remove(&slot) {
-            client.active_sessions
+            (client.active_sessions, client.persistent_sessions)
         } else {
-            HashSet::new()
+            (HashSet::new(), HashSet::new())
         }
     };
 
     if !active_sessions.is_empty() {
         tracing::info!(
-            "agent relay: cleaning up {} active sessions for slot={slot}",
-            active_sessions.len()
+            "agent relay: cleaning up {} active sessions for slot={slot} ({} persistent)",
+            active_sessions.len(),
+            persistent_sessions.len()
         );
 
-        for session_id in active_sessions {
-            let kill_msg = match Message::with_payload(
-                MessageType::ExecSignal,
-                session_id,
-                &ExecSignal { signal: 9 }, // SIGKILL
-            ) {
-                Ok(msg) => msg,
-                Err(e) => {
+        'sessions: for session_id in active_sessions {
+            let mut messages = Vec::with_capacity(2);
+            if !persistent_sessions.contains(&session_id) {
+                match Message::with_payload(
+                    MessageType::ExecSignal,
+                    session_id,
+                    &ExecSignal { signal: 9 }, // SIGKILL
+                ) {
+                    Ok(msg) => messages.push(msg),
+                    Err(e) => tracing::error!(
+                        "agent relay: failed to encode cleanup for session {session_id}: {e}"
+                    ),
+                }
+            }
+            if send_detach {
+                messages.push(Message::new(
+                    MessageType::ExecDetach,
+                    session_id,
+                    Vec::new(),
+                ));
+            }
+
+            for msg in messages {
+                let mut buf = Vec::new();
+                if let Err(e) = codec::encode_to_buf(&msg, &mut buf) {
                     tracing::error!(
-                        "agent relay: failed to encode SIGKILL for session {session_id}: {e}"
+                        "agent relay: failed to encode cleanup frame for session {session_id}: {e}"
                     );
                     continue;
                 }
-            };
 
-            let mut buf = Vec::new();
-            if let Err(e) = codec::encode_to_buf(&kill_msg, &mut buf) {
-                tracing::error!(
-                    "agent relay: failed to encode SIGKILL frame for session {session_id}: {e}"
-                );
-                continue;
-            }
-
-            if agent_tx.send(buf).await.is_err() {
-                tracing::error!("agent relay: ring writer channel closed during cleanup");
-                break;
+                if agent_tx.send(buf).await.is_err() {
+                    tracing::error!("agent relay: ring writer channel closed during cleanup");
+                    break 'sessions;
+                }
             }
         }
     }
@@ -0,0 +1,566 @@
+//! Unix socket passthrough between the host and the guest.
+//!
+//! A [`SocketMount`] makes a Unix socket on one side reachable at a path on
+//! the other, for example to forward the host's ssh-agent into the sandbox.
+//! The relay serves socket mounts itself, as an internal client with its own
+//! correlation ID range: every connection is tunneled through agentd with
+//! `core.tunnel.*` messages (see [`microsandbox_protocol::tunnel`]), so no
+//! guest networking is involved.
+
+use std::{
+    collections::HashMap,
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use microsandbox_protocol::{
+    codec,
+    flow::{Credit, DEFAULT_WINDOW},
+    message::{FLAG_TERMINAL, Message, MessageType},
+    tunnel::{
+        TUNNEL_CHUNK_SIZE, TunnelAccept, TunnelBind, TunnelClose, TunnelData, TunnelEndpoint,
+        TunnelListen, TunnelOpen,
+    },
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+    task::JoinSet,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// How long to wait for agentd to start listening on a guest socket path.
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A Unix socket shared between the host and the guest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketMount {
+    /// Socket path on the host.
+    pub host_path: PathBuf,
+
+    /// Absolute socket path in the guest.
+    pub guest_path: String,
+
+    /// Which side serves the socket.
+    #[serde(default)]
+    pub direction: SocketDirection,
+}
+
+/// Which side of a [`SocketMount`] has the listening socket.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SocketDirection {
+    /// A host service is exposed in the guest: connections to `guest_path`
+    /// inside the sandbox reach the socket at `host_path`.
+    #[default]
+    HostToGuest,
+
+    /// A guest service is exposed on the host: connections to `host_path`
+    /// reach the socket at `guest_path` inside the sandbox.
+    GuestToHost,
+}
+
+/// Tunnel client multiplexed over the relay's internal slot.
+struct Mux {
+    next_id: AtomicU32,
+    first_id: u32,
+    id_max: u32,
+
+    /// Receivers for frames from the guest, keyed by correlation ID.
+    routes: Mutex<HashMap<u32, mpsc::UnboundedSender<Message>>>,
+
+    /// Frames for the guest, written to the ring by the relay.
+    to_guest: mpsc::Sender<Vec<u8>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Mux {
+    fn next_id(&self) -> u32 {
+        loop {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            if id != 0 && id < self.id_max {
+                return id;
+            }
+            self.next_id.store(self.first_id, Ordering::Relaxed);
+        }
+    }
+
+    fn subscribe(&self, id: u32) -> mpsc::UnboundedReceiver<Message> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.lock_routes().insert(id, tx);
+        rx
+    }
+
+    async fn send<T: Serialize>(&self, t: MessageType, id: u32, payload: &T) -> Result<(), String> {
+        let msg = Message::with_payload(t, id, payload).map_err(|e| format!("encode: {e}"))?;
+        let mut buf = Vec::new();
+        codec::encode_to_buf(&msg, &mut buf).map_err(|e| format!("encode: {e}"))?;
+        self.to_guest
+            .send(buf)
+            .await
+            .map_err(|_| "relay closed".to_string())
+    }
+
+    /// Route a frame from the guest to its tunnel.
+    fn dispatch(&self, frame: Bytes) {
+        let mut buf = frame.to_vec();
+        let msg = match codec::try_decode_from_buf(&mut buf) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("socket mounts: decode frame: {e}");
+                return;
+            }
+        };
+
+        let terminal = msg.flags & FLAG_TERMINAL != 0;
+        let mut routes = self.lock_routes();
+        let id = msg.id;
+        if let Some(tx) = routes.get(&id)
+            && (tx.send(msg).is_err() || terminal)
+        {
+            routes.remove(&id);
+        }
+    }
+
+    fn lock_routes(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<u32, mpsc::UnboundedSender<Message>>> {
+        self.routes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Start serving socket mounts as the relay client that owns IDs
+/// `id_offset + 1 .. id_offset + id_range`.
+///
+/// `from_guest` receives the guest's frames for that range and `to_guest`
+/// queues frames for the guest. Returns once every mount is listening;
+/// mounts that cannot be set up are logged and skipped. Dropping the
+/// returned set stops serving.
+pub(crate) async fn start(
+    mounts: Vec<SocketMount>,
+    id_offset: u32,
+    id_range: u32,
+    mut from_guest: mpsc::Receiver<Bytes>,
+    to_guest: mpsc::Sender<Vec<u8>>,
+) -> JoinSet<()> {
+    let mux = Arc::new(Mux {
+        next_id: AtomicU32::new(id_offset + 1),
+        first_id: id_offset + 1,
+        id_max: id_offset.saturating_add(id_range),
+        routes: Mutex::new(HashMap::new()),
+        to_guest,
+    });
+
+    let mut tasks = JoinSet::new();
+    let dispatcher = Arc::clone(&mux);
+    tasks.spawn(async move {
+        while let Some(frame) = from_guest.recv().await {
+            dispatcher.dispatch(frame);
+        }
+    });
+
+    for mount in mounts {
+        let result = match mount.direction {
+            SocketDirection::HostToGuest => listen_in_guest(&mux, &mount, &mut tasks).await,
+            SocketDirection::GuestToHost => listen_on_host(&mux, &mount, &mut tasks),
+        };
+        match result {
+            Ok(()) => tracing::info!(
+                host = %mount.host_path.display(),
+                guest = %mount.guest_path,
+                direction = ?mount.direction,
+                "socket mount ready"
+            ),
+            Err(e) => tracing::error!(
+                host = %mount.host_path.display(),
+                guest = %mount.guest_path,
+                "socket mount failed: {e}"
+            ),
+        }
+    }
+
+    tasks
+}
+
+/// Have agentd listen on the guest path and tunnel each connection to the
+/// host socket.
+async fn listen_in_guest(
+    mux: &Arc<Mux>,
+    mount: &SocketMount,
+    tasks: &mut JoinSet<()>,
+) -> Result<(), String> {
+    let listener = mux.next_id();
+    let mut rx = mux.subscribe(listener);
+    let listen = TunnelListen {
+        endpoint: TunnelEndpoint::Unix {
+            path: mount.guest_path.clone(),
+        },
+    };
+    mux.send(MessageType::TunnelListen, listener, &listen)
+        .await?;
+    tokio::time::timeout(LISTEN_TIMEOUT, wait_opened(&mut rx))
+        .await
+        .map_err(|_| "timed out waiting for the guest to listen".to_string())??;
+
+    let mux = Arc::clone(mux);
+    let host_path = mount.host_path.clone();
+    tasks.spawn(async move {
+        let mut conns = JoinSet::new();
+        while let Some(msg) = rx.recv().await {
+            match msg.t {
+                MessageType::TunnelAccept => {
+                    let Ok(accept) = msg.payload::<TunnelAccept>() else {
+                        continue;
+                    };
+                    let mux = Arc::clone(&mux);
+                    let host_path = host_path.clone();
+                    conns.spawn(async move {
+                        if let Err(e) = bind_accepted(&mux, listener, accept.conn, &host_path).await
+                        {
+                            tracing::debug!(host = %host_path.display(), "socket mount connection: {e}");
+                        }
+                    });
+                }
+                MessageType::TunnelClose => {
+                    let error = msg.payload::<TunnelClose>().ok().and_then(|c| c.error);
+                    tracing::warn!(
+                        host = %host_path.display(),
+                        "guest socket listener closed: {}",
+                        error.as_deref().unwrap_or("no error")
+                    );
+                    return;
+                }
+                _ => {}
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Claim a connection accepted in the guest and pump it to a new connection
+/// to the host socket.
+async fn bind_accepted(
+    mux: &Mux,
+    listener: u32,
+    conn: u32,
+    host_path: &Path,
+) -> Result<(), String> {
+    let id = mux.next_id();
+    let mut rx = mux.subscribe(id);
+    let bind = TunnelBind {
+        listener,
+        conn,
+        window: Some(DEFAULT_WINDOW),
+    };
+    mux.send(MessageType::TunnelBind, id, &bind).await?;
+    wait_opened(&mut rx).await?;
+
+    match UnixStream::connect(host_path).await {
+        Ok(stream) => pump(mux, id, stream, rx).await,
+        Err(e) => {
+            let error = format!("connect to {}: {e}", host_path.display());
+            let close = TunnelClose {
+                error: Some(error.clone()),
+            };
+            let _ = mux.send(MessageType::TunnelClose, id, &close).await;
+            Err(error)
+        }
+    }
+}
+
+/// Listen on the host path and tunnel each connection to the guest socket.
+fn listen_on_host(
+    mux: &Arc<Mux>,
+    mount: &SocketMount,
+    tasks: &mut JoinSet<()>,
+) -> Result<(), String> {
+    let path = &mount.host_path;
+    let err = |e: std::io::Error| format!("listen on {}: {e}", path.display());
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(err)?;
+    }
+    if let Ok(meta) = std::fs::symlink_metadata(path)
+        && meta.file_type().is_socket()
+    {
+        std::fs::remove_file(path).map_err(err)?;
+    }
+    let listener = UnixListener::bind(path).map_err(err)?;
+
+    let mux = Arc::clone(mux);
+    let guest_path = mount.guest_path.clone();
+    tasks.spawn(async move {
+        let mut conns = JoinSet::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let stream = match accepted {
+                        Ok((stream, _)) => stream,
+                        Err(e) => {
+                            tracing::warn!(guest = %guest_path, "socket mount accept: {e}");
+                            return;
+                        }
+                    };
+                    let mux = Arc::clone(&mux);
+                    let guest_path = guest_path.clone();
+                    conns.spawn(async move {
+                        if let Err(e) = open_in_guest(&mux, &guest_path, stream).await {
+                            tracing::debug!(guest = %guest_path, "socket mount connection: {e}");
+                        }
+                    });
+                }
+                Some(_) = conns.join_next(), if !conns.is_empty() => {}
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Connect to the guest socket and pump one host connection through it.
+async fn open_in_guest(mux: &Mux, guest_path: &str, stream: UnixStream) -> Result<(), String> {
+    let id = mux.next_id();
+    let mut rx = mux.subscribe(id);
+    let open = TunnelOpen {
+        endpoint: TunnelEndpoint::Unix {
+            path: guest_path.to_string(),
+        },
+        window: Some(DEFAULT_WINDOW),
+    };
+    mux.send(MessageType::TunnelOpen, id, &open).await?;
+    wait_opened(&mut rx).await?;
+    pump(mux, id, stream, rx).await
+}
+
+/// Wait for `core.tunnel.opened`, turning an early close into an error.
+async fn wait_opened(rx: &mut mpsc::UnboundedReceiver<Message>) -> Result<(), String> {
+    match rx.recv().await {
+        Some(msg) if msg.t == MessageType::TunnelOpened => Ok(()),
+        Some(msg) if msg.t == MessageType::TunnelClose => Err(msg
+            .payload::<TunnelClose>()
+            .ok()
+            .and_then(|close| close.error)
+            .unwrap_or_else(|| "closed by guest".into())),
+        Some(msg) => Err(format!(
+            "unexpected {} while opening tunnel",
+            msg.t.as_str()
+        )),
+        None => Err("relay closed".into()),
+    }
+}
+
+/// Copy bytes both ways between a host socket and an open tunnel until the
+/// guest closes the tunnel. A host-side failure aborts the tunnel.
+async fn pump(
+    mux: &Mux,
+    id: u32,
+    stream: UnixStream,
+    rx: mpsc::UnboundedReceiver<Message>,
+) -> Result<(), String> {
+    let result = pump_inner(mux, id, stream, rx).await;
+    if let Err(PumpError::Host(e)) = &result {
+        let close = TunnelClose {
+            error: Some(e.clone()),
+        };
+        let _ = mux.send(MessageType::TunnelClose, id, &close).await;
+    }
+    result.map_err(|e| match e {
+        PumpError::Host(e) | PumpError::Guest(e) => e,
+    })
+}
+
+/// Why a tunnel stopped early: a failure on the host side, which the guest
+/// still has to hear about, or one reported by the guest.
+enum PumpError {
+    Host(String),
+    Guest(String),
+}
+
+async fn pump_inner(
+    mux: &Mux,
+    id: u32,
+    stream: UnixStream,
+    mut rx: mpsc::UnboundedReceiver<Message>,
+) -> Result<(), PumpError> {
+    let (mut reader, mut writer) = stream.into_split();
+    let mut send_credit = i64::from(DEFAULT_WINDOW);
+    let mut unreturned = 0u32;
+    let mut reading = true;
+    let mut chunk = vec![0u8; TUNNEL_CHUNK_SIZE];
+
+    loop {
+        tokio::select! {
+            read = reader.read(&mut chunk), if reading && send_credit > 0 => {
+                let n = read.map_err(|e| PumpError::Host(format!("read: {e}")))?;
+                reading = n > 0;
+                send_credit -= n as i64;
+                let data = TunnelData {
+                    data: chunk[..n].to_vec(),
+                };
+                mux.send(MessageType::TunnelData, id, &data)
+                    .await
+                    .map_err(PumpError::Guest)?;
+            }
+            msg = rx.recv() => {
+                let Some(msg) = msg else {
+                    return Err(PumpError::Guest("relay closed".into()));
+                };
+                match msg.t {
+                    MessageType::TunnelData => {
+                        let Ok(data) = msg.payload::<TunnelData>() else {
+                            continue;
+                        };
+                        if data.data.is_empty() {
+                            writer
+                                .shutdown()
+                                .await
+                                .map_err(|e| PumpError::Host(format!("shutdown: {e}")))?;
+                            continue;
+                        }
+                        writer
+                            .write_all(&data.data)
+                            .await
+                            .map_err(|e| PumpError::Host(format!("write: {e}")))?;
+
+                        // Return credit in batches of half the window.
+                        unreturned += data.data.len() as u32;
+                        if unreturned >= DEFAULT_WINDOW / 2 {
+                            let credit = Credit {
+                                bytes: std::mem::take(&mut unreturned),
+                            };
+                            mux.send(MessageType::Credit, id, &credit)
+                                .await
+                                .map_err(PumpError::Guest)?;
+                        }
+                    }
+                    MessageType::Credit => {
+                        if let Ok(credit) = msg.payload::<Credit>() {
+                            send_credit += i64::from(credit.bytes);
+                        }
+                    }
+                    MessageType::TunnelClose => {
+                        let close = msg.payload::<TunnelClose>().unwrap_or_default();
+                        return match close.error {
+                            Some(e) => Err(PumpError::Guest(e)),
+                            None => Ok(()),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Receive and decode the next frame the relay would write to the guest.
+    async fn next_frame(rx: &mut mpsc::Receiver<Vec<u8>>) -> Message {
+        let mut buf = rx.recv().await.unwrap();
+        codec::try_decode_from_buf(&mut buf).unwrap().unwrap()
+    }
+
+    /// Encode a frame as the guest would send it.
+    fn guest_frame<T: Serialize>(t: MessageType, id: u32, payload: &T) -> Bytes {
+        let mut buf = Vec::new();
+        let msg = Message::with_payload(t, id, payload).unwrap();
+        codec::encode_to_buf(&msg, &mut buf).unwrap();
+        Bytes::from(buf)
+    }
+
+    #[test]
+    fn test_socket_mount_direction_defaults_to_host_to_guest() {
+        let mount: SocketMount =
+            serde_json::from_str(r#"{"host_path":"/tmp/a.sock","guest_path":"/run/a.sock"}"#)
+                .unwrap();
+        assert_eq!(mount.direction, SocketDirection::HostToGuest);
+    }
+
+    #[tokio::test]
+    async fn test_exposed_socket_tunnels_host_connections_to_guest() {
+        let dir = tempfile::tempdir().unwrap();
+        let host_path = dir.path().join("nested/app.sock");
+        let mount = SocketMount {
+            host_path: host_path.clone(),
+            guest_path: "/run/app.sock".into(),
+            direction: SocketDirection::GuestToHost,
+        };
+
+        let (from_guest_tx, from_guest_rx) = mpsc::channel(16);
+        let (to_guest_tx, mut to_guest_rx) = mpsc::channel(16);
+        let _tasks = start(vec![mount], 1000, 100, from_guest_rx, to_guest_tx).await;
+
+        let mut client = UnixStream::connect(&host_path).await.unwrap();
+        client.write_all(b"ping").await.unwrap();
+
+        let open = next_frame(&mut to_guest_rx).await;
+        assert_eq!(open.t, MessageType::TunnelOpen);
+        assert!((1001..1100).contains(&open.id));
+        let TunnelEndpoint::Unix { path } = open.payload::<TunnelOpen>().unwrap().endpoint else {
+            panic!("expected a Unix endpoint");
+        };
+        assert_eq!(path, "/run/app.sock");
+        from_guest_tx
+            .send(guest_frame(MessageType::TunnelOpened, open.id, &()))
+            .await
+            .unwrap();
+
+        let data = next_frame(&mut to_guest_rx).await;
+        assert_eq!(data.t, MessageType::TunnelData);
+        assert_eq!(data.payload::<TunnelData>().unwrap().data, b"ping");
+
+        let pong = TunnelData {
+            data: b"pong".to_vec(),
+        };
+        from_guest_tx
+            .send(guest_frame(MessageType::TunnelData, open.id, &pong))
+            .await
+            .unwrap();
+        let mut reply = [0u8; 4];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"pong");
+
+        from_guest_tx
+            .send(guest_frame(
+                MessageType::TunnelClose,
+                open.id,
+                &TunnelClose::default(),
+            ))
+            .await
+            .unwrap();
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+//! OpenTelemetry trace export and W3C trace-context propagation.
+//!
+//! With the `otel` feature, [`layer`] builds a `tracing` layer that exports
+//! spans to an OTLP/HTTP collector. The `traceparent` helpers carry a trace
+//! across the host → sandbox process → guest boundaries; without the feature
+//! they are no-ops, so callers don't need their own `cfg` gates.
+
+use tracing::Span;
+
+#[cfg(feature = "otel")]
+use std::{collections::HashMap, sync::OnceLock};
+
+#[cfg(feature = "otel")]
+use opentelemetry::{
+    propagation::TextMapPropagator,
+    trace::{TraceContextExt, TracerProvider as _},
+};
+#[cfg(feature = "otel")]
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+#[cfg(feature = "otel")]
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+#[cfg(feature = "otel")]
+use tracing_subscriber::registry::LookupSpan;
+
+#[cfg(feature = "otel")]
+use crate::{RuntimeError, RuntimeResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Environment variable carrying a W3C `traceparent` into child processes.
+pub const TRACEPARENT_ENV: &str = "TRACEPARENT";
+
+/// Service name reported when none is configured.
+pub const DEFAULT_SERVICE_NAME: &str = "microsandbox";
+
+/// OTLP/HTTP path for trace export, appended to bare collector endpoints.
+#[cfg(feature = "otel")]
+const OTLP_TRACES_PATH: &str = "/v1/traces";
+
+/// Key of the trace context header in a propagation carrier.
+#[cfg(feature = "otel")]
+const TRACEPARENT_KEY: &str = "traceparent";
+
+//--------------------------------------------------------------------------------------------------
+// Statics
+//--------------------------------------------------------------------------------------------------
+
+/// Provider backing the installed layer, kept so [`shutdown`] can flush it.
+#[cfg(feature = "otel")]
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Build a `tracing` layer that exports spans to the OTLP/HTTP collector at
+/// `endpoint` (e.g. `http://localhost:4318`).
+///
+/// Spans are batched on a background thread; call [`shutdown`] before the
+/// process exits so the last batch is not lost.
+#[cfg(feature = "otel")]
+pub fn layer<S>(
+    endpoint: &str,
+    service_name: &str,
+) -> RuntimeResult<OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_endpoint(endpoint))
+        .build()
+        .map_err(|e| RuntimeError::Custom(format!("failed to build OTLP exporter: {e}")))?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer(DEFAULT_SERVICE_NAME);
+    let _ = PROVIDER.set(provider);
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Flush and stop the exporter installed by [`layer`], if any.
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    if let Some(provider) = PROVIDER.get()
+        && let Err(e) = provider.shutdown()
+    {
+        tracing::debug!(error = %e, "failed to flush OTLP spans");
+    }
+}
+
+/// The W3C `traceparent` of the current span, if it belongs to a sampled
+/// OpenTelemetry trace.
+pub fn current_traceparent() -> Option<String> {
+    #[cfg(feature = "otel")]
+    {
+        let mut carrier = HashMap::new();
+        TraceContextPropagator::new().inject_context(&Span::current().context(), &mut carrier);
+        carrier.remove(TRACEPARENT_KEY)
+    }
+
+    #[cfg(not(feature = "otel"))]
+    None
+}
+
+/// Make `span` a child of the remote span identified by `traceparent`.
+///
+/// Malformed values are ignored and the span stays a root.
+pub fn set_parent(span: &Span, traceparent: &str) {
+    #[cfg(feature = "otel")]
+    {
+        let carrier = HashMap::from([(TRACEPARENT_KEY.to_string(), traceparent.to_string())]);
+        let cx = TraceContextPropagator::new().extract(&carrier);
+        if cx.span().span_context().is_valid() {
+            let _ = span.set_parent(cx);
+        }
+    }
+
+    #[cfg(not(feature = "otel"))]
+    let _ = (span, traceparent);
+}
+
+/// Parent `span` on the trace context inherited through [`TRACEPARENT_ENV`].
+pub fn set_parent_from_env(span: &Span) {
+    if let Ok(traceparent) = std::env::var(TRACEPARENT_ENV) {
+        set_parent(span, &traceparent);
+    }
+}
+
+/// Append the OTLP traces path unless the endpoint already names it.
+#[cfg(feature = "otel")]
+fn traces_endpoint(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with(OTLP_TRACES_PATH) {
+        endpoint.to_string()
+    } else {
+        format!("{endpoint}{OTLP_TRACES_PATH}")
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_is_absent_outside_a_trace() {
+        assert_eq!(current_traceparent(), None);
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_traces_endpoint_appends_path_once() {
+        assert_eq!(
+            traces_endpoint("http://localhost:4318"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_endpoint("http://collector:4318/v1/traces/"),
+            "http://collector:4318/v1/traces"
+        );
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_traceparent_roundtrips_through_span() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let tracer = SdkTracerProvider::builder().build().tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("sandbox.exec");
+            set_parent(&span, traceparent);
+            let _guard = span.enter();
+
+            let current = current_traceparent().unwrap();
+            assert!(current.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+            assert!(!current.contains("00f067aa0ba902b7"));
+        });
+    }
+}
@@ -9,19 +9,25 @@ use std::io::Write;
 use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use microsandbox_db::entity::run as run_entity;
+use microsandbox_db::entity::{run as run_entity, sandbox as sandbox_entity};
 use microsandbox_filesystem::{DynFileSystem, OverlayFs, PassthroughConfig, PassthroughFs};
 use msb_krun::VmBuilder;
 use sea_orm::{ColumnTrait, ConnectOptions, Database, DatabaseConnection, EntityTrait, Set};
 use serde::Serialize;
 
 use crate::console::{AgentConsoleBackend, ConsoleSharedState};
+use crate::events::{self, EventKind, EventSink};
+use crate::health::{self, GuestConnector, HealthMonitor};
 use crate::heartbeat::HeartbeatReader;
+use crate::limits::{LimitMonitor, ResourceReading};
 use crate::logging::LogLevel;
-use crate::metrics::run_metrics_sampler;
-use crate::relay::AgentRelay;
+use crate::metrics::{MetricsRetention, run_metrics_sampler};
+use crate::policy::{HealthCheck, LimitAction, ResourceLimit};
+use crate::relay::{AgentRelay, RelayCommand};
+use crate::sockets::SocketMount;
+use crate::telemetry;
 use crate::{RuntimeError, RuntimeResult};
 
 //--------------------------------------------------------------------------------------------------
@@ -33,6 +39,17 @@ const EXIT_REASON_COMPLETED: u8 = 0;
 const EXIT_REASON_IDLE_TIMEOUT: u8 = 1;
 const EXIT_REASON_MAX_DURATION: u8 = 2;
 const EXIT_REASON_SIGNAL: u8 = 3;
+const EXIT_REASON_RESOURCE_LIMIT: u8 = 4;
+const EXIT_REASON_UNHEALTHY: u8 = 5;
+
+/// How long a `drain` resource-limit or unhealthy action waits for the guest
+/// to shut down before stopping the VM.
+const LIMIT_DRAIN_GRACE: Duration = Duration::from_secs(10);
+
+/// Environment variable carrying sensitive guest env vars to the sandbox
+/// process as a JSON array of `KEY=VALUE` strings, so their values don't
+/// appear on its command line.
+pub const SENSITIVE_ENV_VAR: &str = "MSB_SENSITIVE_ENV";
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -74,6 +91,21 @@ pub struct Config {
     /// Maximum sandbox lifetime in seconds (None = no limit).
     pub max_duration_secs: Option<u64>,
 
+    /// Retention for persisted metrics samples and rollups.
+    pub metrics_retention: MetricsRetention,
+
+    /// Resource limits checked against each metrics sample.
+    pub resource_limits: Vec<ResourceLimit>,
+
+    /// Health check probed while the sandbox runs.
+    pub health_check: Option<HealthCheck>,
+
+    /// Unix sockets shared between the host and the guest.
+    pub socket_mounts: Vec<SocketMount>,
+
+    /// Automatic restarts preceding this run, recorded on the run row.
+    pub restart_count: u32,
+
     /// VM hardware and rootfs configuration.
     pub vm: VmConfig,
 }
@@ -158,6 +190,24 @@ type NetworkMetricsHandle = microsandbox_network::network::MetricsHandle;
 #[cfg(not(feature = "net"))]
 type NetworkMetricsHandle = ();
 
+/// Handles the resource limit and health monitors use to act on the VM.
+#[derive(Clone)]
+struct VmControl {
+    relay_commands: tokio::sync::mpsc::Sender<RelayCommand>,
+    event_sink: EventSink,
+    exit_handle: msb_krun::ExitHandle,
+    exit_reason: Arc<std::sync::atomic::AtomicU8>,
+    exit_detail: Arc<std::sync::Mutex<Option<String>>>,
+}
+
+/// The VM plus the network handles the runtime wires up before entering it.
+type BuiltVm = (
+    msb_krun::Vm,
+    Option<NetworkTerminationHandle>,
+    Option<NetworkMetricsHandle>,
+    Option<GuestConnector>,
+);
+
 //--------------------------------------------------------------------------------------------------
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
@@ -178,7 +228,14 @@ impl std::fmt::Debug for VmConfig {
             .field("mounts", &self.mounts)
             .field("backends", &format!("[{} backend(s)]", self.backends.len()))
             .field("init_path", &self.init_path)
-            .field("env", &self.env)
+            .field(
+                "env",
+                &self
+                    .env
+                    .iter()
+                    .map(|var| var.split_once('=').map_or(var.as_str(), |(key, _)| key))
+                    .collect::<Vec<_>>(),
+            )
             .field("workdir", &self.workdir)
             .field("exec_path", &self.exec_path)
             .field("exec_args", &self.exec_args)
@@ -201,6 +258,7 @@ pub fn enter(config: Config) -> ! {
         Ok(infallible) => match infallible {},
         Err(e) => {
             eprintln!("sandbox error: {e}");
+            telemetry::shutdown();
             std::process::exit(1);
         }
     }
@@ -219,6 +277,11 @@ fn run(config: Config) -> RuntimeResult<std::convert::Infallible> {
 
     tracing::info!(sandbox = %config.sandbox_name, "sandbox starting");
 
+    // Continue the spawning process's `sandbox.boot` trace up to VM entry.
+    let setup_span = tracing::info_span!("sandbox.boot.setup", sandbox = %config.sandbox_name);
+    telemetry::set_parent_from_env(&setup_span);
+    let setup_span = setup_span.entered();
+
     // Create console shared state (ring buffers + wake pipes).
     let shared = Arc::new(ConsoleSharedState::new());
     let console_backend = AgentConsoleBackend::new(Arc::clone(&shared));
@@ -235,6 +298,7 @@ fn run(config: Config) -> RuntimeResult<std::convert::Infallible> {
         &config.agent_sock_path,
         Arc::clone(&shared),
     ))?;
+    relay.set_socket_mounts(config.socket_mounts.clone());
 
     // Set up runtime directory.
     std::fs::create_dir_all(&config.runtime_dir)?;
@@ -242,26 +306,40 @@ fn run(config: Config) -> RuntimeResult<std::convert::Infallible> {
 
     // Connect to DB and insert records.
     let db = tokio_rt.block_on(connect_db(&config.sandbox_db_path))?;
-    let run_db_id = tokio_rt.block_on(insert_run(&db, config.sandbox_id, pid))?;
+    let run_db_id = tokio_rt.block_on(insert_run(
+        &db,
+        config.sandbox_id,
+        pid,
+        config.restart_count,
+    ))?;
+    let event_sink = EventSink::spawn(
+        tokio_rt.handle(),
+        db.clone(),
+        config.sandbox_id,
+        config.sandbox_name.clone(),
+    );
 
     // Shared termination reason — background tasks store the reason before
     // triggering exit; the exit observer reads it for the DB update.
     let exit_reason: Arc<std::sync::atomic::AtomicU8> =
         Arc::new(std::sync::atomic::AtomicU8::new(EXIT_REASON_COMPLETED));
+    let exit_detail: Arc<std::sync::Mutex<Option<String>>> = Arc::new(std::sync::Mutex::new(None));
 
     // Build the VM with an exit observer for DB cleanup and socket removal.
     // The on_exit closure runs synchronously on the VMM thread before _exit().
     let rt_handle = tokio_rt.handle().clone();
     let exit_db = db.clone();
     let exit_sandbox_id = config.sandbox_id;
+    let exit_sandbox_name = config.sandbox_name.clone();
     let exit_run_id = run_db_id;
     let exit_reason_for_observer = Arc::clone(&exit_reason);
+    let exit_detail_for_observer = Arc::clone(&exit_detail);
     let exit_sock_path = config.agent_sock_path.clone();
-    let (vm, _network_termination_handle, network_metrics_handle) = match build_vm(
+    let (vm, _network_termination_handle, network_metrics_handle, guest_connector) = match build_vm(
         &config,
         console_backend,
+        &event_sink,
         move |exit_code: i32| {
-            use microsandbox_db::entity::sandbox as sandbox_entity;
             use sea_orm::QueryFilter;
             use sea_orm::sea_query::Expr;
 
@@ -271,10 +349,18 @@ fn run(config: Config) -> RuntimeResult<std::convert::Infallible> {
                 EXIT_REASON_IDLE_TIMEOUT => run_entity::TerminationReason::IdleTimeout,
                 EXIT_REASON_MAX_DURATION => run_entity::TerminationReason::MaxDurationExceeded,
                 EXIT_REASON_SIGNAL => run_entity::TerminationReason::Signal,
+                EXIT_REASON_RESOURCE_LIMIT => run_entity::TerminationReason::ResourceLimitExceeded,
+                EXIT_REASON_UNHEALTHY => run_entity::TerminationReason::Unhealthy,
                 _ if exit_code == 0 => run_entity::TerminationReason::Completed,
                 _ => run_entity::TerminationReason::Failed,
             };
 
+            let detail = exit_detail_for_observer
+                .lock()
+                .ok()
+                .and_then(|mut detail| detail.take());
+
+            let stopped_detail = detail.clone();
             rt_handle.block_on(async {
                 let now = chrono::Utc::now().naive_utc();
 
@@ -285,27 +371,50 @@ fn run(config: Config) -> RuntimeResult<std::convert::Infallible> {
                         Expr::value(run_entity::RunStatus::Terminated),
                     )
                     .col_expr(run_entity::Column::TerminationReason, Expr::value(reason))
+                    .col_expr(run_entity::Column::TerminationDetail, Expr::value(detail))
                     .col_expr(run_entity::Column::ExitCode, Expr::value(exit_code))
                     .col_expr(run_entity::Column::TerminatedAt, Expr::value(now))
                     .filter(run_entity::Column::Id.eq(exit_run_id))
                     .exec(&exit_db)
                     .await;
 
-                // Mark sandbox as stopped.
+                // Mark sandbox as stopped. Health only applies while running.
                 let _ = sandbox_entity::Entity::update_many()
                     .col_expr(
                         sandbox_entity::Column::Status,
                         Expr::value(sandbox_entity::SandboxStatus::Stopped),
                     )
+                    .col_expr(
+                        sandbox_entity::Column::Health,
+                        Expr::value(Option::<sandbox_entity::HealthStatus>::None),
+                    )
                     .col_expr(sandbox_entity::Column::UpdatedAt, Expr::value(now))
                     .filter(sandbox_entity::Column::Id.eq(exit_sandbox_id))
                     .exec(&exit_db)
                     .await;
+
+                // Recorded inline: queued events are lost once _exit() runs.
+                let stopped = EventKind::Stopped {
+                    reason,
+                    exit_code: Some(exit_code),
+                    detail: stopped_detail,
+                };
+                if let Err(e) = events::record_event(
+                    &exit_db,
+                    Some(exit_sandbox_id),
+                    &exit_sandbox_name,
+                    &stopped,
+                )
+                .await
+                {
+                    tracing::warn!(error = %e, "failed to record stopped event");
+                }
             });
 
             // Clean up agent.sock — the relay's async cleanup won't run because
             // _exit() is called immediately after this observer returns.
             let _ = std::fs::remove_file(&exit_sock_path);
+            telemetry::shutdown();
         },
         tokio_rt.handle().clone(),
     ) {
@@ -328,18 +437,54 @@ fn run(config: Config) -> RuntimeResult<std::convert::Infallible> {
         }));
     }
 
+    // Spawn background tasks.
+    let (_relay_shutdown_tx, relay_shutdown_rx) = tokio::sync::watch::channel(false);
+    let (relay_drain_tx, mut relay_drain_rx) = tokio::sync::mpsc::channel::<()>(1);
+    let (relay_command_tx, relay_command_rx) = tokio::sync::mpsc::channel::<RelayCommand>(8);
+
+    let control = VmControl {
+        relay_commands: relay_command_tx,
+        event_sink: event_sink.clone(),
+        exit_handle: exit_handle.clone(),
+        exit_reason: Arc::clone(&exit_reason),
+        exit_detail: Arc::clone(&exit_detail),
+    };
+
+    // Resource limits: the sampler publishes readings, the monitor acts on them.
+    let limit_monitor = LimitMonitor::new(config.resource_limits.iter().cloned());
+    let readings_tx = if limit_monitor.is_empty() {
+        None
+    } else {
+        let (readings_tx, readings_rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio_rt.spawn(enforce_resource_limits(
+            limit_monitor,
+            readings_rx,
+            control.clone(),
+        ));
+        Some(readings_tx)
+    };
+
+    if let Some(check) = config.health_check.clone() {
+        tokio_rt.spawn(monitor_health(
+            check,
+            config.agent_sock_path.clone(),
+            guest_connector,
+            db.clone(),
+            config.sandbox_id,
+            control,
+        ));
+    }
+
     tokio_rt.spawn(run_metrics_sampler(
         db.clone(),
         config.sandbox_id,
         pid,
         network_metrics_handle
             .map(|handle| Box::new(handle) as Box<dyn crate::metrics::NetworkMetrics>),
+        config.metrics_retention,
+        readings_tx,
     ));
 
-    // Spawn background tasks.
-    let (_relay_shutdown_tx, relay_shutdown_rx) = tokio::sync::watch::channel(false);
-    let (relay_drain_tx, mut relay_drain_rx) = tokio::sync::mpsc::channel::<()>(1);
-
     // Relay: spawn a blocking task for wait_ready, then run the accept loop.
     // wait_ready() must run AFTER enter() starts the VM (agentd sends core.ready),
     // so it runs on a background thread, not blocking the main thread.
@@ -349,7 +494,10 @@ fn run(config: Config) -> RuntimeResult<std::convert::Infallible> {
 
         match ready_result {
             Ok(Ok(relay)) => {
-                if let Err(e) = relay.run(relay_shutdown_rx, relay_drain_tx).await {
+                if let Err(e) = relay
+                    .run(relay_shutdown_rx, relay_drain_tx, relay_command_rx)
+                    .await
+                {
                     tracing::error!("agent relay error: {e}");
                 }
             }
@@ -362,9 +510,13 @@ fn run(config: Config) -> RuntimeResult<std::convert::Infallible> {
     // client (e.g. sandbox.stop()), trigger VM exit.
     {
         let shutdown_exit_handle = exit_handle.clone();
+        let shutdown_events = event_sink.clone();
         tokio_rt.spawn(async move {
             if relay_drain_rx.recv().await.is_some() {
                 tracing::info!("core.shutdown received, triggering exit");
+                shutdown_events.emit(EventKind::Draining {
+                    reason: "shutdown requested".into(),
+                });
                 shutdown_exit_handle.trigger();
             }
         });
@@ -411,27 +563,175 @@ fn run(config: Config) -> RuntimeResult<std::convert::Infallible> {
     std::mem::forget(tokio_rt);
 
     // Enter the VM (never returns).
+    drop(setup_span);
     tracing::info!(sandbox = %config.sandbox_name, "entering VM");
     vm.enter()
         .map_err(|e| RuntimeError::Custom(format!("VM enter: {e}")))
 }
 
+/// Check each metrics reading against the policy's resource limits and carry
+/// out the configured action when one is hit.
+async fn enforce_resource_limits(
+    mut monitor: LimitMonitor,
+    mut readings: tokio::sync::mpsc::UnboundedReceiver<ResourceReading>,
+    control: VmControl,
+) {
+    while let Some(reading) = readings.recv().await {
+        for breach in monitor.check(&reading, Instant::now()) {
+            let detail = breach.detail();
+            tracing::warn!(limit = %breach.limit, "resource limit hit: {detail}");
+            control.event_sink.emit(EventKind::ResourceLimit {
+                limit: breach.limit.to_string(),
+                detail: detail.clone(),
+            });
+
+            if carry_out_action(
+                &control,
+                breach.limit.action,
+                EXIT_REASON_RESOURCE_LIMIT,
+                detail,
+            )
+            .await
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Probe the guest on the health check's interval, record status changes and
+/// carry out the unhealthy action.
+async fn monitor_health(
+    check: HealthCheck,
+    agent_sock: PathBuf,
+    connector: Option<GuestConnector>,
+    db: DatabaseConnection,
+    sandbox_id: i32,
+    control: VmControl,
+) {
+    let mut monitor = HealthMonitor::new(&check, Instant::now());
+    set_health(&db, sandbox_id, monitor.status()).await;
+
+    let interval = Duration::from_secs(check.interval_secs.max(1));
+    let timeout = Duration::from_secs(check.timeout_secs.max(1));
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let result =
+            health::run_probe(&check.probe, timeout, &agent_sock, connector.as_ref()).await;
+        if let Err(e) = &result {
+            tracing::debug!(probe = %check.probe, "health probe failed: {e}");
+        }
+        let Some(status) = monitor.record(result.is_ok(), Instant::now()) else {
+            continue;
+        };
+
+        let detail = result.err();
+        tracing::info!(probe = %check.probe, ?status, "sandbox health changed");
+        set_health(&db, sandbox_id, status).await;
+        control.event_sink.emit(EventKind::HealthChanged {
+            status,
+            detail: detail.clone(),
+        });
+        if status != sandbox_entity::HealthStatus::Unhealthy {
+            continue;
+        }
+
+        let detail = format!(
+            "health check failed: {}",
+            detail.as_deref().unwrap_or("unknown error")
+        );
+        if carry_out_action(&control, check.on_unhealthy, EXIT_REASON_UNHEALTHY, detail).await {
+            return;
+        }
+    }
+}
+
+/// Carry out a resource limit or health check action. `exit_reason` and
+/// `detail` are recorded as the cause when the action stops the VM.
+///
+/// Returns `true` once the VM has been told to exit.
+async fn carry_out_action(
+    control: &VmControl,
+    action: LimitAction,
+    exit_reason: u8,
+    detail: String,
+) -> bool {
+    match action {
+        LimitAction::Log => false,
+        LimitAction::Signal(signal) => {
+            let _ = control
+                .relay_commands
+                .send(RelayCommand::SignalSessions(signal))
+                .await;
+            false
+        }
+        LimitAction::Drain | LimitAction::Kill => {
+            control
+                .exit_reason
+                .store(exit_reason, std::sync::atomic::Ordering::SeqCst);
+            if let Ok(mut slot) = control.exit_detail.lock() {
+                *slot = Some(detail.clone());
+            }
+
+            if action == LimitAction::Drain {
+                control.event_sink.emit(EventKind::Draining {
+                    reason: detail.clone(),
+                });
+                let _ = control.relay_commands.send(RelayCommand::Shutdown).await;
+                tokio::time::sleep(LIMIT_DRAIN_GRACE).await;
+                tracing::info!("guest did not shut down after drain ({detail}), stopping VM");
+            }
+            control.exit_handle.trigger();
+            true
+        }
+    }
+}
+
+/// Record the sandbox's health status. Failures are logged, not fatal.
+async fn set_health(
+    db: &DatabaseConnection,
+    sandbox_id: i32,
+    status: sandbox_entity::HealthStatus,
+) {
+    use sea_orm::QueryFilter;
+    use sea_orm::sea_query::Expr;
+
+    if let Err(e) = sandbox_entity::Entity::update_many()
+        .col_expr(sandbox_entity::Column::Health, Expr::value(status))
+        .filter(sandbox_entity::Column::Id.eq(sandbox_id))
+        .exec(db)
+        .await
+    {
+        tracing::warn!(error = %e, "failed to record sandbox health");
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions: VM Builder
 //--------------------------------------------------------------------------------------------------
 
+/// Sensitive guest env vars handed down through [`SENSITIVE_ENV_VAR`].
+fn sensitive_env_from_parent() -> Vec<String> {
+    let Ok(json) = std::env::var(SENSITIVE_ENV_VAR) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "ignoring malformed {SENSITIVE_ENV_VAR}");
+        Vec::new()
+    })
+}
+
 /// Build the `Vm` from config with an exit observer for cleanup.
 fn build_vm(
     config: &Config,
     console_backend: AgentConsoleBackend,
+    event_sink: &EventSink,
     on_exit: impl Fn(i32) + Send + 'static,
     tokio_handle: tokio::runtime::Handle,
-) -> RuntimeResult<(
-    msb_krun::Vm,
-    Option<NetworkTerminationHandle>,
-    Option<NetworkMetricsHandle>,
-)> {
+) -> RuntimeResult<BuiltVm> {
     let mut exec_env = config.vm.env.clone();
+    exec_env.extend(sensitive_env_from_parent());
     let vm = &config.vm;
 
     let mut builder = VmBuilder::new()
@@ -518,6 +818,7 @@ fn build_vm(
 
     let mut network_termination_handle = None;
     let mut network_metrics_handle = None;
+    let mut guest_connector = None;
 
     // Network.
     #[cfg(feature = "net")]
@@ -526,8 +827,17 @@ fn build_vm(
 
         let mut network =
             microsandbox_network::network::SmoltcpNetwork::new(vm.network.clone(), vm.sandbox_slot);
+        network
+            .join_network()
+            .map_err(|e| RuntimeError::Custom(format!("join network: {e}")))?;
         network_termination_handle = Some(network.termination_handle());
         network_metrics_handle = Some(network.metrics_handle());
+        guest_connector = Some(network.guest_connector());
+
+        let network_events = event_sink.clone();
+        network.event_handle().set_hook(Arc::new(move |event| {
+            network_events.emit(network_event_kind(event))
+        }));
 
         network.start(tokio_handle.clone());
 
@@ -586,13 +896,39 @@ fn build_vm(
         .build()
         .map_err(|e| RuntimeError::Custom(format!("build VM: {e}")))?;
 
-    Ok((vm, network_termination_handle, network_metrics_handle))
+    Ok((
+        vm,
+        network_termination_handle,
+        network_metrics_handle,
+        guest_connector,
+    ))
 }
 
 //--------------------------------------------------------------------------------------------------
 // Functions: Helpers
 //--------------------------------------------------------------------------------------------------
 
+/// Map a network stack event to its lifecycle event.
+#[cfg(feature = "net")]
+fn network_event_kind(event: microsandbox_network::shared::NetworkEvent) -> EventKind {
+    use microsandbox_network::shared::NetworkEvent;
+
+    match event {
+        NetworkEvent::SecretViolation { host } => EventKind::SecretViolation { host },
+        NetworkEvent::PolicyDenial {
+            destination,
+            port,
+            protocol,
+        } => EventKind::PolicyDenial {
+            destination: match port {
+                Some(port) => std::net::SocketAddr::new(destination, port).to_string(),
+                None => destination.to_string(),
+            },
+            protocol: format!("{protocol:?}").to_lowercase(),
+        },
+    }
+}
+
 /// Set up host log capture.
 ///
 /// Redirects stderr through a pipe so a background thread can write to a
@@ -649,13 +985,19 @@ async fn connect_db(db_path: &std::path::Path) -> RuntimeResult<DatabaseConnecti
 }
 
 /// Insert a run record into the database.
-async fn insert_run(db: &DatabaseConnection, sandbox_id: i32, pid: u32) -> RuntimeResult<i32> {
+async fn insert_run(
+    db: &DatabaseConnection,
+    sandbox_id: i32,
+    pid: u32,
+    restart_count: u32,
+) -> RuntimeResult<i32> {
     let now = chrono::Utc::now().naive_utc();
     let record = run_entity::ActiveModel {
         sandbox_id: Set(sandbox_id),
         pid: Set(Some(pid as i32)),
         status: Set(run_entity::RunStatus::Running),
         started_at: Set(Some(now)),
+        restart_count: Set(restart_count as i32),
         ..Default::default()
     };
     let result = run_entity::Entity::insert(record)
@@ -816,7 +1158,18 @@ pub fn build_overlay_rootfs(
 
     for layer in layers {
         let index_path = layer.with_extension("index");
-        if index_path.exists() {
+        let toc_path = layer.with_extension("toc");
+        if toc_path.exists() {
+            // Lazily pulled layer: entries are materialized on first lookup,
+            // from the estargz blob if there is one, else the raw tarball.
+            let blob_path = layer.with_extension("blob");
+            let data_path = if blob_path.exists() {
+                blob_path
+            } else {
+                layer.with_extension("tar")
+            };
+            overlay_builder = overlay_builder.layer_lazy(layer, &index_path, &toc_path, data_path);
+        } else if index_path.exists() {
             overlay_builder = overlay_builder.layer_with_index(layer, &index_path);
         } else {
             overlay_builder = overlay_builder.layer(layer);
@@ -1,9 +1,11 @@
 //! Sidecar index for layer acceleration.
 //!
 //! Provides wire format types, a zero-copy mmap reader, and a builder/writer
-//! for the binary per-layer index generated at OCI extraction time.
+//! for the binary per-layer index generated at OCI extraction time, plus the
+//! table of contents used to materialize lazily-pulled layers on demand.
 
 mod reader;
+mod toc;
 mod types;
 mod writer;
 
@@ -12,5 +14,6 @@ mod writer;
 //--------------------------------------------------------------------------------------------------
 
 pub use reader::*;
+pub use toc::*;
 pub use types::*;
 pub use writer::*;
@@ -0,0 +1,197 @@
+//! Table of contents for lazily materialized layers.
+//!
+//! A lazy layer is prepared without extracting its tarball. The pull pipeline
+//! builds the sidecar index and records where each entry's data lives in a
+//! `LayerToc`. OverlayFs then materializes individual entries into the layer
+//! directory the first time they are looked up.
+//!
+//! Entry data comes from one of two sources (see [`TocSource`]):
+//!
+//! - **Tar**: the blob is downloaded in full, decompressed once into a raw tar,
+//!   and the index is built from the tar headers.
+//! - **Estargz**: only the blob's footer and embedded table of contents are
+//!   fetched before the layer is ready. The blob itself is streamed in the
+//!   background and each file is decompressed from its own gzip member, so a
+//!   lookup waits only for the bytes it needs.
+//!
+//! Sibling files share the layer directory's stem:
+//! ```text
+//! <layer>/           # materialized entries (initially empty)
+//! <layer>.index      # sidecar index built from the tar headers or estargz TOC
+//! <layer>.tar        # uncompressed layer tarball (tar source)
+//! <layer>.blob       # compressed estargz blob, possibly still downloading
+//! <layer>.blob.lock  # held by the process downloading the blob
+//! <layer>.toc        # this table of contents (written last)
+//! ```
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// Current table of contents format version.
+pub const TOC_VERSION: u32 = 1;
+
+/// File extension of the table of contents next to a lazy layer directory.
+pub const TOC_EXTENSION: &str = "toc";
+
+/// File extension of the raw tarball next to a lazy layer directory.
+pub const TOC_TAR_EXTENSION: &str = "tar";
+
+/// File extension of the estargz blob next to a lazy layer directory.
+pub const TOC_BLOB_EXTENSION: &str = "blob";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Table of contents for a lazy layer.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LayerToc {
+    /// Format version (`TOC_VERSION`).
+    pub version: u32,
+    /// Where entry data is read from.
+    #[serde(default)]
+    pub source: TocSource,
+    /// Entries in tar order. Whiteouts are not listed; they live in the index.
+    pub entries: Vec<TocEntry>,
+}
+
+/// Backing file for the data of a lazy layer's entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum TocSource {
+    /// Uncompressed tarball; [`TocEntry::offset`] is a tar data offset.
+    #[default]
+    Tar,
+    /// Compressed estargz blob; file data lives in [`TocEntry::chunks`].
+    Estargz {
+        /// Size of the complete blob in bytes.
+        blob_size: u64,
+    },
+}
+
+/// One independently compressed piece of a file in an estargz blob.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TocChunk {
+    /// Byte offset of the chunk's gzip member in the blob.
+    pub offset: u64,
+    /// Blob bytes that must be present before the chunk can be decompressed.
+    pub compressed_size: u64,
+    /// Offset of the chunk's data within the file.
+    pub chunk_offset: u64,
+    /// Uncompressed size of the chunk.
+    pub chunk_size: u64,
+}
+
+/// A single materializable entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TocEntry {
+    /// Path relative to the layer root (e.g. `"usr/bin/python3"`).
+    pub path: String,
+    /// Entry kind.
+    pub kind: TocEntryKind,
+    /// Guest-visible mode including `S_IFMT` type bits.
+    pub mode: u32,
+    /// Guest-visible uid.
+    pub uid: u32,
+    /// Guest-visible gid.
+    pub gid: u32,
+    /// Device number for block/char devices.
+    #[serde(default)]
+    pub rdev: u32,
+    /// Data size in bytes (regular files only).
+    #[serde(default)]
+    pub size: u64,
+    /// Byte offset of the entry's data in the raw tarball (regular files only).
+    #[serde(default)]
+    pub offset: u64,
+    /// Symlink target, or the layer-relative path of a hardlink's target.
+    #[serde(default)]
+    pub link: Option<String>,
+    /// Directory carries an opaque whiteout marker.
+    #[serde(default)]
+    pub opaque: bool,
+    /// Directory created only because a child referenced it. Its metadata is
+    /// inherited from lower layers during the post-pull fixup pass.
+    #[serde(default)]
+    pub implicit: bool,
+    /// Compressed chunks holding a regular file's data (estargz source only).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<TocChunk>,
+    /// `sha256:<hex>` digest of a regular file's contents, when recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+}
+
+/// Kind of a [`TocEntry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TocEntryKind {
+    /// Directory.
+    Dir,
+    /// Regular file with data in the raw tarball.
+    File,
+    /// Symbolic link.
+    Symlink,
+    /// Hard link to another entry in the same layer.
+    Hardlink,
+    /// Device node or FIFO, stored as an empty file with the type in the override xattr.
+    Special,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl LayerToc {
+    /// Create an empty table of contents.
+    pub fn new() -> Self {
+        Self {
+            version: TOC_VERSION,
+            source: TocSource::Tar,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Read and validate a table of contents from disk.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let data = std::fs::read(path)?;
+        let toc: Self = serde_json::from_slice(&data).map_err(io::Error::other)?;
+        if toc.version != TOC_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported layer toc version {}", toc.version),
+            ));
+        }
+        Ok(toc)
+    }
+
+    /// Atomically write the table of contents to disk.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_vec(self).map_err(io::Error::other)?;
+        let part = path.with_extension(format!("{TOC_EXTENSION}.part"));
+        std::fs::write(&part, data)?;
+        std::fs::rename(&part, path)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Path of the lock file held while an estargz blob is being downloaded.
+///
+/// Readers that need bytes past the end of a partial blob keep waiting only
+/// while some process holds this lock.
+pub fn blob_lock_path(blob_path: &Path) -> PathBuf {
+    let mut path = blob_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
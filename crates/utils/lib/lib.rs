@@ -29,6 +29,9 @@ pub const SANDBOXES_SUBDIR: &str = "sandboxes";
 /// Subdirectory for named volumes.
 pub const VOLUMES_SUBDIR: &str = "volumes";
 
+/// Subdirectory for private sandbox networks.
+pub const NETWORKS_SUBDIR: &str = "networks";
+
 /// Subdirectory for logs.
 pub const LOGS_SUBDIR: &str = "logs";
 
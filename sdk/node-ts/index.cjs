@@ -609,5 +609,6 @@ module.exports.PolicyAction = nativeBinding.PolicyAction
 module.exports.PolicyDirection = nativeBinding.PolicyDirection
 module.exports.PolicyProtocol = nativeBinding.PolicyProtocol
 module.exports.PullPolicy = nativeBinding.PullPolicy
+module.exports.renderOpenMetrics = nativeBinding.renderOpenMetrics
 module.exports.SandboxStatus = nativeBinding.SandboxStatus
 module.exports.ViolationAction = nativeBinding.ViolationAction
@@ -221,10 +221,17 @@ export declare class Sandbox {
   static startDetached(name: string): Promise<Sandbox>
   /** Get a lightweight handle to an existing sandbox. */
   static get(name: string): Promise<JsSandboxHandle>
-  /** List all sandboxes. */
-  static list(): Promise<Array<SandboxInfo>>
+  /**
+   * List all sandboxes, optionally filtered by a label selector
+   * (e.g. `"session=abc,tier in (web,api)"`).
+   */
+  static list(selector?: string | undefined | null): Promise<Array<SandboxInfo>>
   /** Remove a stopped sandbox from the database. */
   static remove(name: string): Promise<void>
+  /** Export a stopped sandbox to a portable archive. */
+  static export(name: string, path: string, opts?: ExportOptions | undefined | null): Promise<void>
+  /** Import a sandbox from an archive created by `export()`. */
+  static import(path: string, opts?: ImportOptions | undefined | null): Promise<JsSandboxHandle>
   /** Sandbox name. */
   get name(): Promise<string>
   /** Whether this handle owns the sandbox lifecycle (attached mode). */
@@ -332,6 +339,8 @@ export declare class SandboxHandle {
   stop(): Promise<void>
   /** Kill the sandbox (SIGKILL). */
   kill(): Promise<void>
+  /** Export the sandbox to a portable archive. */
+  export(path: string, opts?: ExportOptions | undefined | null): Promise<void>
   /** Remove the sandbox from the database. */
   remove(): Promise<void>
 }
@@ -366,8 +375,8 @@ export declare class Volume {
   static create(config: VolumeConfig): Promise<Volume>
   /** Get a lightweight handle to an existing volume. */
   static get(name: string): Promise<VolumeHandle>
-  /** List all volumes. */
-  static list(): Promise<Array<VolumeInfo>>
+  /** List all volumes, optionally filtered by a label selector. */
+  static list(selector?: string | undefined | null): Promise<Array<VolumeInfo>>
   /** Remove a volume. */
   static remove(name: string): Promise<void>
   /** Volume name. */
@@ -397,6 +406,14 @@ export type JsVolumeHandle = VolumeHandle
 /** Get metrics for all running sandboxes. */
 export declare function allSandboxMetrics(): Promise<Record<string, SandboxMetrics>>
 
+/**
+ * Render metrics for every sandbox in OpenMetrics text format.
+ *
+ * Serve the result from your own HTTP endpoint to expose sandbox metrics to
+ * Prometheus-compatible scrapers.
+ */
+export declare function renderOpenMetrics(): Promise<string>
+
 /** Configuration for interactive attach sessions. */
 export interface AttachConfig {
   /** Command to execute. */
@@ -459,6 +476,14 @@ export interface ExitStatus {
   success: boolean
 }
 
+/** Options for exporting a sandbox to an archive. */
+export interface ExportOptions {
+  /** Bundle the contents of named volumes mounted by the sandbox. */
+  includeVolumes?: boolean
+  /** Bundle image layers so the archive imports without registry access. */
+  includeLayers?: boolean
+}
+
 /** Filesystem entry metadata returned by `fs.list()`. */
 export interface FsEntry {
   path: string
@@ -488,6 +513,14 @@ export interface FsMetadata {
   created?: number
 }
 
+/** Options for importing a sandbox from an archive. */
+export interface ImportOptions {
+  /** Import under a different sandbox name. */
+  name?: string
+  /** Replace an existing sandbox with the same name. */
+  replace?: boolean
+}
+
 /** Download and install msb + libkrunfw to ~/.microsandbox/. */
 export declare function install(): Promise<void>
 
@@ -668,6 +701,10 @@ export interface SandboxConfig {
   patches?: Array<PatchConfig>
   /** Image pull policy: "always", "if-missing", or "never". */
   pullPolicy?: string
+  /** Image platform to select, e.g. "linux/amd64". Defaults to the host. */
+  platform?: string
+  /** Materialize image layers on first access instead of extracting them up front. */
+  lazyLayers?: boolean
   /** Log level: "trace", "debug", "info", "warn", "error". */
   logLevel?: string
   /** Kill any existing sandbox with the same name before creating. */
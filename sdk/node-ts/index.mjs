@@ -18,6 +18,7 @@ export const {
   allSandboxMetrics,
   install,
   isInstalled,
+  renderOpenMetrics,
 } = binding;
 
 export default binding;
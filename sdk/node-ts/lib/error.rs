@@ -26,14 +26,25 @@ fn error_type_str(err: &MicrosandboxError) -> &'static str {
         MicrosandboxError::Protocol(_) => "Protocol",
         MicrosandboxError::Nix(_) => "Nix",
         MicrosandboxError::ExecTimeout(_) => "ExecTimeout",
+        MicrosandboxError::ExecSessionNotFound(_) => "ExecSessionNotFound",
+        MicrosandboxError::ExecSessionAlreadyExists(_) => "ExecSessionAlreadyExists",
         MicrosandboxError::Terminal(_) => "Terminal",
         MicrosandboxError::SandboxFs(_) => "SandboxFs",
         MicrosandboxError::ImageNotFound(_) => "ImageNotFound",
         MicrosandboxError::ImageInUse(_) => "ImageInUse",
         MicrosandboxError::VolumeNotFound(_) => "VolumeNotFound",
         MicrosandboxError::VolumeAlreadyExists(_) => "VolumeAlreadyExists",
+        MicrosandboxError::NetworkNotFound(_) => "NetworkNotFound",
+        MicrosandboxError::NetworkAlreadyExists(_) => "NetworkAlreadyExists",
+        MicrosandboxError::NetworkInUse(_) => "NetworkInUse",
         MicrosandboxError::Image(_) => "Image",
         MicrosandboxError::PatchFailed(_) => "PatchFailed",
+        MicrosandboxError::HealthCheck(_) => "HealthCheck",
+        MicrosandboxError::InvalidLabelSelector(_) => "InvalidLabelSelector",
+        MicrosandboxError::Gateway(_) => "Gateway",
+        MicrosandboxError::PortForward(_) => "PortForward",
+        MicrosandboxError::IncompatibleAgent(_) => "IncompatibleAgent",
+        MicrosandboxError::Unsupported(_) => "Unsupported",
         MicrosandboxError::Custom(_) => "Custom",
     }
 }
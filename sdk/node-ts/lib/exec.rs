@@ -222,7 +222,7 @@ fn exec_event_to_js(event: RustExecEvent) -> ExecEvent {
             data: Some(data.to_vec().into()),
             code: None,
         },
-        RustExecEvent::Exited { code } => ExecEvent {
+        RustExecEvent::Exited { code, .. } => ExecEvent {
             event_type: "exited".to_string(),
             pid: None,
             data: None,
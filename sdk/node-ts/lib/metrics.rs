@@ -22,3 +22,14 @@ pub async fn all_sandbox_metrics() -> Result<HashMap<String, SandboxMetrics>> {
         .map(|(name, m)| (name.clone(), metrics_to_js(m)))
         .collect())
 }
+
+/// Render metrics for every sandbox in OpenMetrics text format.
+///
+/// Serve the result from your own HTTP endpoint to expose sandbox metrics to
+/// Prometheus-compatible scrapers.
+#[napi]
+pub async fn render_open_metrics() -> Result<String> {
+    microsandbox::sandbox::render_openmetrics()
+        .await
+        .map_err(to_napi_error)
+}
@@ -123,10 +123,12 @@ impl Sandbox {
         Ok(JsSandboxHandle::from_rust(handle))
     }
 
-    /// List all sandboxes.
+    /// List all sandboxes, optionally filtered by a label selector
+    /// (e.g. `"session=abc,tier in (web,api)"`).
     #[napi]
-    pub async fn list() -> Result<Vec<SandboxInfo>> {
-        let handles = microsandbox::sandbox::Sandbox::list()
+    pub async fn list(selector: Option<String>) -> Result<Vec<SandboxInfo>> {
+        let selector = parse_selector(selector)?;
+        let handles = microsandbox::sandbox::Sandbox::list_matching(&selector)
             .await
             .map_err(to_napi_error)?;
         Ok(handles.iter().map(sandbox_handle_to_info).collect())
@@ -140,6 +142,31 @@ impl Sandbox {
             .map_err(to_napi_error)
     }
 
+    /// Export a stopped sandbox to a portable archive.
+    #[napi]
+    pub async fn export(name: String, path: String, opts: Option<ExportOptions>) -> Result<()> {
+        microsandbox::sandbox::Sandbox::export(&name, &path, convert_export_options(opts))
+            .await
+            .map_err(to_napi_error)
+    }
+
+    /// Import a sandbox from an archive created by `export()`.
+    #[napi]
+    pub async fn import(path: String, opts: Option<ImportOptions>) -> Result<JsSandboxHandle> {
+        let opts = opts.unwrap_or(ImportOptions {
+            name: None,
+            replace: None,
+        });
+        let options = microsandbox::sandbox::ImportOptions {
+            name: opts.name,
+            replace: opts.replace.unwrap_or(false),
+        };
+        let handle = microsandbox::sandbox::Sandbox::import(&path, options)
+            .await
+            .map_err(to_napi_error)?;
+        Ok(JsSandboxHandle::from_rust(handle))
+    }
+
     //----------------------------------------------------------------------------------------------
     // Properties
     //----------------------------------------------------------------------------------------------
@@ -475,6 +502,15 @@ fn convert_config(config: SandboxConfig) -> Result<RustSandboxConfig> {
         };
         builder = builder.pull_policy(policy);
     }
+    if let Some(ref platform) = config.platform {
+        builder = builder.platform(platform);
+    }
+    if let Some(lazy) = config.lazy_layers {
+        builder = builder.lazy_layers(lazy);
+    }
+    if let Some(ref labels) = config.labels {
+        builder = builder.labels(labels.clone());
+    }
     if let Some(ref log_level) = config.log_level {
         let level = match log_level.as_str() {
             "trace" => LogLevel::Trace,
@@ -698,6 +734,16 @@ pub fn metrics_to_js(m: &microsandbox::sandbox::SandboxMetrics) -> SandboxMetric
     }
 }
 
+/// Parse an optional label selector, treating `None` as match-all.
+pub(crate) fn parse_selector(selector: Option<String>) -> Result<microsandbox::LabelSelector> {
+    selector
+        .as_deref()
+        .map(microsandbox::LabelSelector::parse)
+        .transpose()
+        .map(Option::unwrap_or_default)
+        .map_err(to_napi_error)
+}
+
 fn sandbox_handle_to_info(handle: &microsandbox::sandbox::SandboxHandle) -> SandboxInfo {
     SandboxInfo {
         name: handle.name().to_string(),
@@ -102,6 +102,15 @@ impl JsSandboxHandle {
         self.inner.stop().await.map_err(to_napi_error)
     }
 
+    /// Export the sandbox to a portable archive.
+    #[napi]
+    pub async fn export(&self, path: String, opts: Option<ExportOptions>) -> Result<()> {
+        self.inner
+            .export(&path, convert_export_options(opts))
+            .await
+            .map_err(to_napi_error)
+    }
+
     /// Remove the sandbox from the database.
     #[napi]
     pub async fn remove(&self) -> Result<()> {
@@ -39,6 +39,10 @@ pub struct SandboxConfig {
     pub patches: Option<Vec<PatchConfig>>,
     /// Image pull policy: "always", "if-missing", or "never".
     pub pull_policy: Option<String>,
+    /// Image platform to select, e.g. "linux/amd64". Defaults to the host.
+    pub platform: Option<String>,
+    /// Materialize image layers on first access instead of extracting them up front.
+    pub lazy_layers: Option<bool>,
     /// Log level: "trace", "debug", "info", "warn", "error".
     pub log_level: Option<String>,
     /// Kill any existing sandbox with the same name before creating.
@@ -307,6 +311,24 @@ pub struct SandboxInfo {
     pub updated_at: Option<f64>,
 }
 
+/// Options for exporting a sandbox to an archive.
+#[napi(object)]
+pub struct ExportOptions {
+    /// Bundle the contents of named volumes mounted by the sandbox.
+    pub include_volumes: Option<bool>,
+    /// Bundle image layers so the archive imports without registry access.
+    pub include_layers: Option<bool>,
+}
+
+/// Options for importing a sandbox from an archive.
+#[napi(object)]
+pub struct ImportOptions {
+    /// Import under a different sandbox name.
+    pub name: Option<String>,
+    /// Replace an existing sandbox with the same name.
+    pub replace: Option<bool>,
+}
+
 /// Volume configuration for creation.
 #[napi(object)]
 pub struct VolumeConfig {
@@ -340,3 +362,15 @@ pub fn datetime_to_ms(dt: &chrono::DateTime<chrono::Utc>) -> f64 {
 pub fn opt_datetime_to_ms(dt: &Option<chrono::DateTime<chrono::Utc>>) -> Option<f64> {
     dt.as_ref().map(datetime_to_ms)
 }
+
+/// Convert JS export options to the Rust equivalent.
+pub fn convert_export_options(opts: Option<ExportOptions>) -> microsandbox::sandbox::ExportOptions {
+    let opts = opts.unwrap_or(ExportOptions {
+        include_volumes: None,
+        include_layers: None,
+    });
+    microsandbox::sandbox::ExportOptions {
+        include_volumes: opts.include_volumes.unwrap_or(false),
+        include_layers: opts.include_layers.unwrap_or(false),
+    }
+}
@@ -52,10 +52,13 @@ impl JsVolume {
         Ok(JsVolumeHandle { inner: handle })
     }
 
-    /// List all volumes.
+    /// List all volumes, optionally filtered by a label selector.
     #[napi]
-    pub async fn list() -> Result<Vec<VolumeInfo>> {
-        let handles = Volume::list().await.map_err(to_napi_error)?;
+    pub async fn list(selector: Option<String>) -> Result<Vec<VolumeInfo>> {
+        let selector = crate::sandbox::parse_selector(selector)?;
+        let handles = Volume::list_matching(&selector)
+            .await
+            .map_err(to_napi_error)?;
         Ok(handles.iter().map(volume_handle_to_info).collect())
     }
 
@@ -31,6 +31,7 @@ from microsandbox._microsandbox import (
     all_sandbox_metrics,
     install,
     is_installed,
+    render_openmetrics,
     version,
 )
 from microsandbox.errors import (
@@ -181,6 +182,7 @@ __all__ = [
     # Metrics
     "SandboxMetrics",
     "all_sandbox_metrics",
+    "render_openmetrics",
     # Size helpers
     "Size",
     "MiB",
@@ -13,10 +13,22 @@ class Sandbox:
     @staticmethod
     async def get(name: str) -> SandboxHandle: ...
     @staticmethod
-    async def list() -> list[SandboxHandle]: ...
+    async def list(selector: str | None = None) -> list[SandboxHandle]: ...
     @staticmethod
     async def remove(name: str) -> None: ...
     @staticmethod
+    async def export(
+        name: str,
+        path: str,
+        *,
+        include_volumes: bool = False,
+        include_layers: bool = False,
+    ) -> None: ...
+    @staticmethod
+    async def import_archive(
+        path: str, *, name: str | None = None, replace: bool = False
+    ) -> SandboxHandle: ...
+    @staticmethod
     def create_with_progress(
         name_or_config: str | dict[str, Any], **kwargs: Any
     ) -> PullSession: ...
@@ -69,6 +81,13 @@ class SandboxHandle:
     async def stop(self) -> None: ...
     async def kill(self) -> None: ...
     async def remove(self) -> None: ...
+    async def export(
+        self,
+        path: str,
+        *,
+        include_volumes: bool = False,
+        include_layers: bool = False,
+    ) -> None: ...
 
 class ExecOutput:
     @property
@@ -175,7 +194,7 @@ class Volume:
     @staticmethod
     async def get(name: str) -> VolumeHandle: ...
     @staticmethod
-    async def list() -> list[VolumeHandle]: ...
+    async def list(selector: str | None = None) -> list[VolumeHandle]: ...
     @staticmethod
     async def remove(name: str) -> None: ...
     @staticmethod
@@ -240,6 +259,7 @@ class PullEvent:
     bytes_read: int | None
 
 async def all_sandbox_metrics() -> dict[str, SandboxMetrics]: ...
+async def render_openmetrics() -> str: ...
 def install() -> None: ...
 def is_installed() -> bool: ...
 def version() -> str: ...
@@ -237,7 +237,7 @@ fn convert_exec_event(event: microsandbox::ExecEvent) -> PyExecEvent {
             data: Some(data.to_vec()),
             code: None,
         },
-        microsandbox::ExecEvent::Exited { code } => PyExecEvent {
+        microsandbox::ExecEvent::Exited { code, .. } => PyExecEvent {
             event_type: "exited",
             pid: None,
             data: None,
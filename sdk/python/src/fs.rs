@@ -266,9 +266,9 @@ impl PyFsWriteSink {
     fn write<'py>(&self, py: Python<'py>, data: Vec<u8>) -> PyResult<Bound<'py, PyAny>> {
         let inner = self.inner.clone();
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let guard = inner.lock().await;
+            let mut guard = inner.lock().await;
             let sink = guard
-                .as_ref()
+                .as_mut()
                 .ok_or_else(|| pyo3::exceptions::PyRuntimeError::new_err("write stream closed"))?;
             sink.write(&data).await.map_err(to_py_err)?;
             Ok(())
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use microsandbox::sandbox::{NetworkPolicy, Patch, PullPolicy, SandboxConfig};
 use microsandbox::{LogLevel, RegistryAuth};
 use pyo3::prelude::*;
@@ -128,6 +130,21 @@ pub fn build_config_from_kwargs(
         builder = builder.pull_policy(policy);
     }
 
+    // Image platform.
+    if let Some(platform) = extract_opt::<String>(kwargs, "platform")? {
+        builder = builder.platform(platform);
+    }
+
+    // Lazy layer materialization.
+    if let Some(lazy) = extract_opt::<bool>(kwargs, "lazy_layers")? {
+        builder = builder.lazy_layers(lazy);
+    }
+
+    // Labels.
+    if let Some(labels) = extract_opt::<HashMap<String, String>>(kwargs, "labels")? {
+        builder = builder.labels(labels);
+    }
+
     // Log level.
     if let Some(ll) = extract_opt::<String>(kwargs, "log_level")? {
         let level = match ll.as_str() {
@@ -225,6 +242,16 @@ pub fn build_config_from_kwargs(
 // Functions: Mount
 //--------------------------------------------------------------------------------------------------
 
+/// Parse an optional label selector, treating `None` as match-all.
+pub fn parse_selector(selector: Option<String>) -> PyResult<microsandbox::LabelSelector> {
+    selector
+        .as_deref()
+        .map(microsandbox::LabelSelector::parse)
+        .transpose()
+        .map(Option::unwrap_or_default)
+        .map_err(to_py_err)
+}
+
 fn apply_mount(
     builder: microsandbox::sandbox::SandboxBuilder,
     guest_path: String,
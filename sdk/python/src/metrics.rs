@@ -117,3 +117,13 @@ pub fn all_sandbox_metrics<'py>(py: Python<'py>) -> PyResult<Bound<'py, PyAny>>
         Ok(result)
     })
 }
+
+/// Render metrics for every sandbox in OpenMetrics text format.
+#[pyfunction]
+pub fn render_openmetrics<'py>(py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+    pyo3_async_runtimes::tokio::future_into_py(py, async move {
+        microsandbox::sandbox::render_openmetrics()
+            .await
+            .map_err(to_py_err)
+    })
+}
@@ -142,11 +142,14 @@ impl PySandbox {
         })
     }
 
-    /// List all sandboxes.
+    /// List all sandboxes, optionally filtered by a label selector
+    /// (e.g. `"session=abc,tier in (web,api)"`).
     #[staticmethod]
-    fn list<'py>(py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+    #[pyo3(signature = (selector=None))]
+    fn list<'py>(py: Python<'py>, selector: Option<String>) -> PyResult<Bound<'py, PyAny>> {
+        let selector = crate::helpers::parse_selector(selector)?;
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let handles = microsandbox::sandbox::Sandbox::list()
+            let handles = microsandbox::sandbox::Sandbox::list_matching(&selector)
                 .await
                 .map_err(to_py_err)?;
             let py_handles: Vec<PySandboxHandle> = handles
@@ -168,6 +171,46 @@ impl PySandbox {
         })
     }
 
+    /// Export a stopped sandbox to a portable archive.
+    #[staticmethod]
+    #[pyo3(signature = (name, path, *, include_volumes = false, include_layers = false))]
+    fn export<'py>(
+        py: Python<'py>,
+        name: String,
+        path: String,
+        include_volumes: bool,
+        include_layers: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let options = microsandbox::sandbox::ExportOptions {
+                include_volumes,
+                include_layers,
+            };
+            microsandbox::sandbox::Sandbox::export(&name, &path, options)
+                .await
+                .map_err(to_py_err)?;
+            Ok(())
+        })
+    }
+
+    /// Import a sandbox from an archive created by `export`.
+    #[staticmethod]
+    #[pyo3(signature = (path, *, name = None, replace = false))]
+    fn import_archive<'py>(
+        py: Python<'py>,
+        path: String,
+        name: Option<String>,
+        replace: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let options = microsandbox::sandbox::ImportOptions { name, replace };
+            let handle = microsandbox::sandbox::Sandbox::import(&path, options)
+                .await
+                .map_err(to_py_err)?;
+            Ok(PySandboxHandle::from_rust(handle))
+        })
+    }
+
     //----------------------------------------------------------------------------------------------
     // Properties
     //----------------------------------------------------------------------------------------------
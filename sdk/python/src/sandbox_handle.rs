@@ -136,6 +136,27 @@ impl PySandboxHandle {
         })
     }
 
+    /// Export the sandbox to a portable archive.
+    #[pyo3(signature = (path, *, include_volumes = false, include_layers = false))]
+    fn export<'py>(
+        &self,
+        py: Python<'py>,
+        path: String,
+        include_volumes: bool,
+        include_layers: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let options = microsandbox::sandbox::ExportOptions {
+                include_volumes,
+                include_layers,
+            };
+            let guard = inner.lock().await;
+            guard.export(&path, options).await.map_err(to_py_err)?;
+            Ok(())
+        })
+    }
+
     /// Remove the sandbox from the database.
     fn remove<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
         let inner = self.inner.clone();
@@ -64,11 +64,15 @@ impl PyVolume {
         })
     }
 
-    /// List all volumes.
+    /// List all volumes, optionally filtered by a label selector.
     #[staticmethod]
-    fn list<'py>(py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+    #[pyo3(signature = (selector=None))]
+    fn list<'py>(py: Python<'py>, selector: Option<String>) -> PyResult<Bound<'py, PyAny>> {
+        let selector = crate::helpers::parse_selector(selector)?;
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let handles = microsandbox::Volume::list().await.map_err(to_py_err)?;
+            let handles = microsandbox::Volume::list_matching(&selector)
+                .await
+                .map_err(to_py_err)?;
             let py_handles: Vec<PyVolumeHandle> = handles
                 .into_iter()
                 .map(|h| PyVolumeHandle { inner: h })